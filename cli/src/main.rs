@@ -1,11 +1,16 @@
+mod s3_store;
+mod spool;
+
 use chrono::NaiveDate;
 use chrono::{DateTime, Utc};
 use clap::{Parser, Subcommand};
 use flate2::write::GzEncoder;
 use flate2::Compression;
+use futures::TryStreamExt;
 use log::Level;
 use puppylog::{DrainParser, LogEntry, LogLevel, Prop, PuppylogBuilder};
 use puppylog_server::{config::log_path, db, segment};
+use s3_store::{S3Args, S3Target};
 use rand::{distributions::Alphanumeric, prelude::*};
 use reqwest::{self, Client, Url};
 use std::collections::HashMap;
@@ -161,19 +166,60 @@ fn random_num() -> u32 {
 }
 
 fn random_log_entry(timestamp: DateTime<Utc>) -> LogEntry {
-	let mut rng = thread_rng();
+	weighted_log_entry(timestamp, &HashMap::new(), &HashMap::new())
+}
 
-	// Select log level using weights
-	let level = LOG_LEVELS
-		.choose_weighted(&mut rng, |&item| {
-			LOG_LEVEL_WEIGHTS[LOG_LEVELS.iter().position(|&x| x == item).unwrap()]
-		})
-		.unwrap()
-		.clone();
+/// Picks a level from `level_weights` (keyed by `LogLevel::from_string` name)
+/// if given, falling back to the hardcoded `LOG_LEVEL_WEIGHTS` distribution
+/// when it's empty.
+fn pick_level(level_weights: &HashMap<String, f64>, rng: &mut impl Rng) -> LogLevel {
+	if level_weights.is_empty() {
+		return LOG_LEVELS
+			.choose_weighted(rng, |&item| {
+				LOG_LEVEL_WEIGHTS[LOG_LEVELS.iter().position(|&x| x == item).unwrap()]
+			})
+			.unwrap()
+			.clone();
+	}
+	let pairs: Vec<(LogLevel, f64)> = level_weights
+		.iter()
+		.map(|(name, weight)| (LogLevel::from_string(name), *weight))
+		.collect();
+	pairs.choose_weighted(rng, |(_, weight)| *weight).unwrap().0.clone()
+}
 
-	let entity = *ENTITY_TYPES.choose(&mut rng).unwrap();
-	let actions = ACTIONS.get(entity).unwrap();
-	let action = *actions.choose(&mut rng).unwrap();
+/// Picks an entity type from `entity_weights` if given, falling back to a
+/// uniform pick over `ENTITY_TYPES`.
+fn pick_entity(entity_weights: &HashMap<String, f64>, rng: &mut impl Rng) -> String {
+	if entity_weights.is_empty() {
+		return (*ENTITY_TYPES.choose(rng).unwrap()).to_string();
+	}
+	let pairs: Vec<(&String, &f64)> = entity_weights.iter().collect();
+	pairs.choose_weighted(rng, |(_, weight)| **weight).unwrap().0.clone()
+}
+
+/// Generates a log entry the same way `random_log_entry` always has, but
+/// drawing its level and entity from `level_weights`/`entity_weights` when
+/// they're non-empty instead of the hardcoded distributions — what the
+/// `bench` subcommand's workload spec uses to shape its synthetic traffic.
+fn weighted_log_entry(
+	timestamp: DateTime<Utc>,
+	level_weights: &HashMap<String, f64>,
+	entity_weights: &HashMap<String, f64>,
+) -> LogEntry {
+	let mut rng = thread_rng();
+
+	let level = pick_level(level_weights, &mut rng);
+	let entity_owned = pick_entity(entity_weights, &mut rng);
+	let entity = entity_owned.as_str();
+	// A caller-supplied `entity_weights` key might not be one of the
+	// built-in `ENTITY_TYPES`, so fall back to a generic action instead of
+	// assuming a match the way the hardcoded-weights path safely can.
+	let action = ACTIONS
+		.get(entity)
+		.and_then(|actions| actions.choose(&mut rng))
+		.copied()
+		.unwrap_or("occurred");
 
 	// Generate the log line based on entity type
 	let log_line = match entity {
@@ -186,7 +232,7 @@ fn random_log_entry(timestamp: DateTime<Utc>) -> LogEntry {
 				msg: format!("{} {} {}", entity, username, action),
 				props: vec![Prop {
 					key: "username".to_string(),
-					value: username,
+					value: username.into(),
 				}],
 				..Default::default()
 			}
@@ -203,11 +249,11 @@ fn random_log_entry(timestamp: DateTime<Utc>) -> LogEntry {
 					props: vec![
 						Prop {
 							key: "api_name".to_string(),
-							value: api_name.to_string(),
+							value: api_name.to_string().into(),
 						},
 						Prop {
 							key: "status".to_string(),
-							value: status.to_string(),
+							value: status.to_string().into(),
 						},
 					],
 					..Default::default()
@@ -220,7 +266,7 @@ fn random_log_entry(timestamp: DateTime<Utc>) -> LogEntry {
 					msg: format!("{} {} {}", entity, api_name, action),
 					props: vec![Prop {
 						key: "api_name".to_string(),
-						value: api_name.to_string(),
+						value: api_name.to_string().into(),
 					}],
 					..Default::default()
 				}
@@ -236,7 +282,7 @@ fn random_log_entry(timestamp: DateTime<Utc>) -> LogEntry {
 				msg: format!("{} {} {}", entity, generic_id, action),
 				props: vec![Prop {
 					key: "id".to_string(),
-					value: generic_id,
+					value: generic_id.into(),
 				}],
 				..Default::default()
 			}
@@ -246,6 +292,332 @@ fn random_log_entry(timestamp: DateTime<Utc>) -> LogEntry {
 	log_line
 }
 
+/// Lowest power-of-ten decade (in milliseconds) tracked by [`LatencyHistogram`].
+/// Sub-millisecond POST latencies are folded into this decade rather than
+/// given their own, since nothing this harness measures resolves below ~0.1ms.
+const HISTOGRAM_MIN_DECADE_EXP: i32 = -1;
+/// Highest power-of-ten decade (in milliseconds) tracked by [`LatencyHistogram`] —
+/// 10^6 ms is over 16 minutes, far past anything a single POST should take.
+const HISTOGRAM_MAX_DECADE_EXP: i32 = 6;
+/// Linear sub-buckets per decade. 100 buckets per decade gives ~1% relative
+/// resolution, which is plenty for p50/p90/p99/p999 reporting without
+/// storing every sample the way a raw latency vector would.
+const HISTOGRAM_BUCKETS_PER_DECADE: usize = 100;
+
+/// A log-linear bucketed latency histogram, HdrHistogram-style: each
+/// power-of-ten decade between `HISTOGRAM_MIN_DECADE_EXP` and
+/// `HISTOGRAM_MAX_DECADE_EXP` is split into `HISTOGRAM_BUCKETS_PER_DECADE`
+/// equal-width linear buckets, so resolution scales with magnitude instead
+/// of being fixed across the whole range. Only bucket counts are kept, so
+/// memory is constant regardless of how many samples are recorded, and
+/// `percentile` derives p50/p90/p99/p999 from the cumulative counts.
+#[derive(Debug, Clone)]
+struct LatencyHistogram {
+	counts: Vec<u64>,
+	total: u64,
+}
+
+impl LatencyHistogram {
+	fn new() -> Self {
+		let decades = (HISTOGRAM_MAX_DECADE_EXP - HISTOGRAM_MIN_DECADE_EXP + 1) as usize;
+		Self {
+			counts: vec![0; decades * HISTOGRAM_BUCKETS_PER_DECADE],
+			total: 0,
+		}
+	}
+
+	/// Maps a latency in milliseconds to a bucket index, clamping values
+	/// outside the tracked range to the nearest edge bucket instead of
+	/// panicking or dropping the sample.
+	fn bucket_index(value_ms: f64) -> usize {
+		let min = 10f64.powi(HISTOGRAM_MIN_DECADE_EXP);
+		let max = 10f64.powi(HISTOGRAM_MAX_DECADE_EXP + 1);
+		let value = value_ms.max(min).min(max - f64::EPSILON);
+		let decade_exp = value.log10().floor() as i32;
+		let decade_exp = decade_exp.max(HISTOGRAM_MIN_DECADE_EXP).min(HISTOGRAM_MAX_DECADE_EXP);
+		let decade_start = 10f64.powi(decade_exp);
+		let decade_width = 10f64.powi(decade_exp + 1) - decade_start;
+		let offset_in_decade =
+			((value - decade_start) / decade_width * HISTOGRAM_BUCKETS_PER_DECADE as f64) as usize;
+		let offset_in_decade = offset_in_decade.min(HISTOGRAM_BUCKETS_PER_DECADE - 1);
+		let decade_index = (decade_exp - HISTOGRAM_MIN_DECADE_EXP) as usize;
+		decade_index * HISTOGRAM_BUCKETS_PER_DECADE + offset_in_decade
+	}
+
+	/// The midpoint latency (in milliseconds) a bucket index represents.
+	fn bucket_value_ms(index: usize) -> f64 {
+		let decade_index = index / HISTOGRAM_BUCKETS_PER_DECADE;
+		let offset_in_decade = index % HISTOGRAM_BUCKETS_PER_DECADE;
+		let decade_exp = decade_index as i32 + HISTOGRAM_MIN_DECADE_EXP;
+		let decade_start = 10f64.powi(decade_exp);
+		let decade_width = 10f64.powi(decade_exp + 1) - decade_start;
+		let bucket_width = decade_width / HISTOGRAM_BUCKETS_PER_DECADE as f64;
+		decade_start + (offset_in_decade as f64 + 0.5) * bucket_width
+	}
+
+	fn record(&mut self, value_ms: f64) {
+		let index = Self::bucket_index(value_ms);
+		self.counts[index] += 1;
+		self.total += 1;
+	}
+
+	fn merge(&mut self, other: &LatencyHistogram) {
+		for (a, b) in self.counts.iter_mut().zip(other.counts.iter()) {
+			*a += b;
+		}
+		self.total += other.total;
+	}
+
+	fn total(&self) -> u64 {
+		self.total
+	}
+
+	/// The midpoint of the first bucket at which the cumulative count reaches
+	/// the given percentile (0-100), so the result is accurate to the bucket
+	/// width rather than an exact recorded value. Returns 0.0 if nothing has
+	/// been recorded.
+	fn percentile(&self, pct: f64) -> f64 {
+		if self.total == 0 {
+			return 0.0;
+		}
+		let target = (pct / 100.0 * self.total as f64).ceil() as u64;
+		let mut cumulative = 0u64;
+		for (index, &count) in self.counts.iter().enumerate() {
+			cumulative += count;
+			if cumulative >= target.max(1) {
+				return Self::bucket_value_ms(index);
+			}
+		}
+		Self::bucket_value_ms(self.counts.len() - 1)
+	}
+}
+
+/// How many entries a single bench worker batches into one POST.
+const BENCH_BATCH_SIZE: u32 = 200;
+
+fn default_bench_workers() -> u32 {
+	1
+}
+
+fn default_true() -> bool {
+	true
+}
+
+/// A single load-test run read from a `bench` workload file: how much
+/// traffic to generate and how to shape it, mirroring what `Upload`
+/// hard-codes today (level weights, entity mix, parallelism) but as
+/// declarative, repeatable JSON instead of CLI flags and constants.
+#[derive(Debug, Clone, serde::Deserialize)]
+struct BenchWorkload {
+	name: String,
+	count: u32,
+	#[serde(default = "default_bench_workers")]
+	workers: u32,
+	#[serde(default)]
+	warmup: u32,
+	/// Target logs/sec, shared across all workers. `None` runs flat-out.
+	#[serde(default)]
+	rate: Option<f64>,
+	/// Overrides `--address`/the default server address for this workload.
+	#[serde(default)]
+	address: Option<String>,
+	#[serde(default)]
+	auth: Option<String>,
+	#[serde(default)]
+	level_weights: HashMap<String, f64>,
+	#[serde(default)]
+	entity_weights: HashMap<String, f64>,
+	#[serde(default = "default_true")]
+	gzip: bool,
+}
+
+/// Machine-readable result of running one [`BenchWorkload`], emitted as JSON
+/// so runs can be diffed or tracked over time instead of eyeballed off stdout.
+#[derive(Debug, Clone, serde::Serialize)]
+struct BenchWorkloadReport {
+	name: String,
+	count: u32,
+	errors: u32,
+	p50_ms: f64,
+	p90_ms: f64,
+	p99_ms: f64,
+	p999_ms: f64,
+	bytes_uncompressed: u64,
+	bytes_compressed: u64,
+	compression_ratio: f64,
+	wall_clock_ms: u64,
+	throughput_logs_per_sec: f64,
+}
+
+/// Builds one POST body of `entries` random log entries shaped by
+/// `level_weights`/`entity_weights`, gzip-compressing it when `gzip` is set.
+/// Returns `(body, uncompressed_len)` so the caller can track both the wire
+/// size and the size that would have gone out uncompressed.
+fn build_bench_batch(
+	entries: u32,
+	timestamp: &mut DateTime<Utc>,
+	level_weights: &HashMap<String, f64>,
+	entity_weights: &HashMap<String, f64>,
+	gzip: bool,
+) -> Result<(Vec<u8>, usize), Box<dyn Error>> {
+	let mut buffer = Vec::new();
+	for _ in 0..entries {
+		let log = weighted_log_entry(*timestamp, level_weights, entity_weights);
+		log.serialize(&mut buffer).unwrap();
+		*timestamp += Duration::from_millis(100);
+	}
+	let uncompressed_len = buffer.len();
+	if gzip {
+		let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+		encoder.write_all(&buffer)?;
+		Ok((encoder.finish()?, uncompressed_len))
+	} else {
+		Ok((buffer, uncompressed_len))
+	}
+}
+
+/// Executes one `BenchWorkload`: an unmeasured warmup pass, then `workers`
+/// parallel tokio tasks POSTing batches of `BENCH_BATCH_SIZE` entries each
+/// (optionally paced to `rate` logs/sec) until `count` entries have been
+/// sent, merging every worker's latency histogram and byte/error counters
+/// into one report.
+async fn run_bench_workload(spec: &BenchWorkload, default_address: &str) -> Result<BenchWorkloadReport, Box<dyn Error>> {
+	let address = spec.address.clone().unwrap_or_else(|| default_address.to_string());
+	let auth = spec.auth.clone().unwrap_or_default();
+	let client = Client::new();
+
+	if spec.warmup > 0 {
+		let mut remaining = spec.warmup;
+		let mut timestamp = Utc::now();
+		while remaining > 0 {
+			let batch = remaining.min(BENCH_BATCH_SIZE);
+			let (body, _) = build_bench_batch(
+				batch,
+				&mut timestamp,
+				&spec.level_weights,
+				&spec.entity_weights,
+				spec.gzip,
+			)?;
+			let _ = client
+				.post(&address)
+				.header("Authorization", auth.clone())
+				.body(body)
+				.send()
+				.await;
+			remaining -= batch;
+		}
+	}
+
+	let workers = spec.workers.max(1);
+	let per_worker_rate = spec.rate.map(|rate| rate / workers as f64);
+	let base_count = spec.count / workers;
+	let extra = spec.count % workers;
+
+	let started = std::time::Instant::now();
+	let mut handles = Vec::new();
+	for worker in 0..workers {
+		let count = base_count + if worker < extra { 1 } else { 0 };
+		let address = address.clone();
+		let auth = auth.clone();
+		let level_weights = spec.level_weights.clone();
+		let entity_weights = spec.entity_weights.clone();
+		let gzip = spec.gzip;
+
+		handles.push(tokio::spawn(async move {
+			let client = Client::new();
+			let mut histogram = LatencyHistogram::new();
+			let mut bytes_uncompressed = 0u64;
+			let mut bytes_compressed = 0u64;
+			let mut errors = 0u32;
+			let mut timestamp = Utc::now();
+			let mut remaining = count;
+			while remaining > 0 {
+				let batch = remaining.min(BENCH_BATCH_SIZE);
+				let batch_started = std::time::Instant::now();
+				let (body, uncompressed_len) = build_bench_batch(
+					batch,
+					&mut timestamp,
+					&level_weights,
+					&entity_weights,
+					gzip,
+				)?;
+				bytes_uncompressed += uncompressed_len as u64;
+				bytes_compressed += body.len() as u64;
+
+				let response = client
+					.post(&address)
+					.header("Authorization", auth.clone())
+					.body(body)
+					.send()
+					.await;
+				let latency_ms = batch_started.elapsed().as_secs_f64() * 1000.0;
+				histogram.record(latency_ms);
+				match response {
+					Ok(response) if response.status().is_success() => {}
+					_ => errors += 1,
+				}
+
+				remaining -= batch;
+
+				if let Some(per_worker_rate) = per_worker_rate {
+					let expected_secs = batch as f64 / per_worker_rate;
+					let elapsed_secs = batch_started.elapsed().as_secs_f64();
+					if expected_secs > elapsed_secs {
+						tokio::time::sleep(Duration::from_secs_f64(expected_secs - elapsed_secs)).await;
+					}
+				}
+			}
+			Ok::<_, Box<dyn Error + Send + Sync>>((histogram, bytes_uncompressed, bytes_compressed, errors))
+		}));
+	}
+
+	let mut histogram = LatencyHistogram::new();
+	let mut bytes_uncompressed = 0u64;
+	let mut bytes_compressed = 0u64;
+	let mut errors = 0u32;
+	for handle in handles {
+		match handle.await? {
+			Ok((worker_histogram, worker_uncompressed, worker_compressed, worker_errors)) => {
+				histogram.merge(&worker_histogram);
+				bytes_uncompressed += worker_uncompressed;
+				bytes_compressed += worker_compressed;
+				errors += worker_errors;
+			}
+			Err(err) => {
+				eprintln!("bench worker failed: {}", err);
+				errors += 1;
+			}
+		}
+	}
+	let wall_clock = started.elapsed();
+	let wall_clock_ms = wall_clock.as_millis() as u64;
+	let throughput_logs_per_sec = if wall_clock.as_secs_f64() > 0.0 {
+		spec.count as f64 / wall_clock.as_secs_f64()
+	} else {
+		0.0
+	};
+	let compression_ratio = if bytes_compressed > 0 {
+		bytes_uncompressed as f64 / bytes_compressed as f64
+	} else {
+		1.0
+	};
+
+	Ok(BenchWorkloadReport {
+		name: spec.name.clone(),
+		count: spec.count,
+		errors,
+		p50_ms: histogram.percentile(50.0),
+		p90_ms: histogram.percentile(90.0),
+		p99_ms: histogram.percentile(99.0),
+		p999_ms: histogram.percentile(99.9),
+		bytes_uncompressed,
+		bytes_compressed,
+		compression_ratio,
+		wall_clock_ms,
+		throughput_logs_per_sec,
+	})
+}
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
@@ -278,6 +650,21 @@ struct UploadLogsArgs {
 	auth: Option<String>,
 }
 
+#[derive(Parser)]
+struct BenchArgs {
+	/// One or more JSON workload files to run, in order.
+	files: Vec<String>,
+	/// Default server address for workloads that don't set their own.
+	#[arg(long)]
+	address: Option<String>,
+	/// Write the JSON report array here instead of stdout.
+	#[arg(long)]
+	output: Option<String>,
+	/// POST the JSON report array to this results endpoint after the run.
+	#[arg(long)]
+	report_to: Option<String>,
+}
+
 #[derive(Subcommand)]
 enum SegmentSubCommand {
 	Get {
@@ -299,7 +686,10 @@ enum SegmentSubCommand {
 		count: Option<u32>,
 		#[arg(long)]
 		sort: Option<String>,
+		/// Local directory, or an `s3://bucket/prefix` to upload segments to instead.
 		output: String,
+		#[command(flatten)]
+		s3: S3Args,
 	},
 	Download {
 		#[arg(long)]
@@ -310,7 +700,10 @@ enum SegmentSubCommand {
 		count: Option<u32>,
 		#[arg(long)]
 		sort: Option<String>,
+		/// Local directory, or an `s3://bucket/prefix` to upload segments to instead.
 		output: String,
+		#[command(flatten)]
+		s3: S3Args,
 	},
 }
 
@@ -318,6 +711,8 @@ enum SegmentSubCommand {
 enum Commands {
 	/// Upload log data
 	Upload(UploadLogsArgs),
+	/// Run one or more JSON workload files as a repeatable load test
+	Bench(BenchArgs),
 	Tokenize {
 		#[command(subcommand)]
 		subcommand: TokenizeSubcommands,
@@ -325,9 +720,17 @@ enum Commands {
 	UpdateMetadata(UpdateMetadataArgs),
 	#[command(subcommand)]
 	Segment(SegmentSubCommand),
-	/// Import compressed log segments from a directory
+	/// Import compressed log segments from a directory, or from an
+	/// `s3://bucket/prefix`
 	Import {
 		folder: String,
+		#[command(flatten)]
+		s3: S3Args,
+		/// Re-encode each segment with Drain template compression (see
+		/// `segment::LogSegment::serialize_templated`) before storing it,
+		/// instead of keeping the original bytes as-is.
+		#[arg(long)]
+		templated: bool,
 	},
 }
 
@@ -358,17 +761,87 @@ async fn upload_logs(address: &str, logs: &[String], compress: bool) -> Result<(
 	let response = client
 		.post(address)
 		.headers(headers)
-		.body(body)
+		.body(body.clone())
 		.send()
-		.await?;
+		.await;
 
-	println!("Upload status: {}", response.status());
+	match response {
+		Ok(response) if response.status().is_success() => {
+			println!("Upload status: {}", response.status());
+		}
+		Ok(response) => {
+			println!("Upload status: {}", response.status());
+			spool::spool_failed_batch(address, "", compress, body);
+		}
+		Err(err) => {
+			eprintln!("Upload request failed: {}", err);
+			spool::spool_failed_batch(address, "", compress, body);
+		}
+	}
 	Ok(())
 }
 
-async fn import_segments(path: &str) -> anyhow::Result<()> {
+/// Registers one already-decoded segment with the DB and writes its
+/// compressed bytes to `log_dir`, exactly as the local-directory and S3
+/// import paths both need to. When `templated` is set, the segment is
+/// re-encoded with Drain template compression (see
+/// `segment::LogSegment::serialize_templated`) before it's written, rather
+/// than storing `compressed` as-is — useful for shrinking segments imported
+/// from a store that was never given the flag on write.
+async fn register_imported_segment(
+	db: &db::DB,
+	log_dir: &Path,
+	compressed: &[u8],
+	templated: bool,
+) -> anyhow::Result<()> {
 	use std::collections::HashSet;
 	use std::io::Cursor;
+
+	let decoded = zstd::decode_all(Cursor::new(compressed))?;
+	let decoded_size = decoded.len();
+	let mut cursor = Cursor::new(decoded);
+	let segment = segment::LogSegment::parse(&mut cursor).unwrap_or_else(|err| err.recovered());
+	if segment.buffer.is_empty() {
+		return Ok(());
+	}
+	let first_timestamp = segment.buffer.first().unwrap().timestamp;
+	let last_timestamp = segment.buffer.last().unwrap().timestamp;
+	let logs_count = segment.buffer.len() as u64;
+
+	let (compressed, original_size) = if templated {
+		let mut raw = Vec::new();
+		segment.serialize_templated(&mut raw);
+		let original_size = raw.len();
+		(segment::compress_segment(&raw)?, original_size)
+	} else {
+		(compressed.to_vec(), decoded_size)
+	};
+	let compressed_size = compressed.len();
+
+	let segment_id = db
+		.new_segment(db::NewSegmentArgs {
+			first_timestamp,
+			last_timestamp,
+			original_size,
+			compressed_size,
+			logs_count,
+		})
+		.await?;
+
+	let mut unique_props = HashSet::new();
+	for log in &segment.buffer {
+		for prop in &log.props {
+			unique_props.insert(prop.clone());
+		}
+	}
+	db.upsert_segment_props(segment_id, unique_props.iter())
+		.await?;
+
+	tokio::fs::write(log_dir.join(format!("{segment_id}.log")), &compressed).await?;
+	Ok(())
+}
+
+async fn import_segments(path: &str, s3_args: &S3Args, templated: bool) -> anyhow::Result<()> {
 	use tokio::fs::{create_dir_all, read, read_dir};
 
 	let log_dir = log_path();
@@ -377,46 +850,39 @@ async fn import_segments(path: &str) -> anyhow::Result<()> {
 	}
 
 	let db = db::DB::new(db::open_db());
+
+	if let Some(s3) = S3Target::parse(path, s3_args)? {
+		let mut listing = Box::pin(s3.list());
+		while let Some(meta) = listing.try_next().await? {
+			// Stream the object to a staging file as it downloads. The
+			// staged file is then read back whole into memory to be
+			// decoded and registered, so a segment is still held in
+			// memory in full once it is on disk.
+			let staging_path = log_dir.join(format!(
+				"{}-{:06}.importing",
+				chrono::Utc::now().timestamp_micros(),
+				rand::thread_rng().gen_range(0..1_000_000)
+			));
+			let mut reader = s3.get_stream(&meta.location).await?;
+			let mut staging = tokio::fs::File::create(&staging_path).await?;
+			tokio::io::copy(&mut reader, &mut staging).await?;
+			drop(staging);
+
+			let compressed = read(&staging_path).await?;
+			register_imported_segment(&db, &log_dir, &compressed, templated).await?;
+			tokio::fs::remove_file(&staging_path).await?;
+		}
+		return Ok(());
+	}
+
 	let mut dir = read_dir(path).await?;
 	while let Some(entry) = dir.next_entry().await? {
 		let file_path = entry.path();
 		if !file_path.is_file() {
 			continue;
 		}
-
 		let compressed = read(&file_path).await?;
-		let compressed_size = compressed.len();
-		let decoded = zstd::decode_all(Cursor::new(&compressed))?;
-		let original_size = decoded.len();
-		let mut cursor = Cursor::new(decoded);
-		let segment = segment::LogSegment::parse(&mut cursor);
-		if segment.buffer.is_empty() {
-			continue;
-		}
-		let first_timestamp = segment.buffer.first().unwrap().timestamp;
-		let last_timestamp = segment.buffer.last().unwrap().timestamp;
-		let logs_count = segment.buffer.len() as u64;
-
-		let segment_id = db
-			.new_segment(db::NewSegmentArgs {
-				first_timestamp,
-				last_timestamp,
-				original_size,
-				compressed_size,
-				logs_count,
-			})
-			.await?;
-
-		let mut unique_props = HashSet::new();
-		for log in &segment.buffer {
-			for prop in &log.props {
-				unique_props.insert(prop.clone());
-			}
-		}
-		db.upsert_segment_props(segment_id, unique_props.iter())
-			.await?;
-
-		tokio::fs::write(log_dir.join(format!("{segment_id}.log")), &compressed).await?;
+		register_imported_segment(&db, &log_dir, &compressed, templated).await?;
 	}
 
 	Ok(())
@@ -426,6 +892,12 @@ async fn import_segments(path: &str) -> anyhow::Result<()> {
 async fn main() -> Result<(), Box<dyn Error>> {
 	let cli = Cli::parse();
 
+	// Retries whatever a previous run spooled (and anything this run spools)
+	// in the background for as long as the process stays alive; a short-lived
+	// invocation just gets through as much of the backlog as it can before
+	// exiting, picking up where it left off next run.
+	tokio::spawn(spool::drain_spool());
+
 	match cli.subcommand {
 		Commands::Upload(args) => {
 			let success_count = Arc::new(AtomicUsize::new(0));
@@ -457,19 +929,28 @@ async fn main() -> Result<(), Box<dyn Error>> {
 					}
 
 					let client = reqwest::Client::new();
+					let auth = auth.unwrap_or_default();
 					let response = client
 						.post(&addr)
-						.header("Authorization", auth.unwrap_or_default())
-						.body(buffer)
+						.header("Authorization", auth.clone())
+						.body(buffer.clone())
 						.send()
-						.await
-						.unwrap();
-
-					if !response.status().is_success() {
-						eprintln!("[{}] Upload failed: {}", i, response.status());
-						fail_count.fetch_add(1, Ordering::SeqCst);
-					} else {
-						success_count.fetch_add(1, Ordering::SeqCst);
+						.await;
+
+					match response {
+						Ok(response) if response.status().is_success() => {
+							success_count.fetch_add(1, Ordering::SeqCst);
+						}
+						Ok(response) => {
+							eprintln!("[{}] Upload failed: {}", i, response.status());
+							fail_count.fetch_add(1, Ordering::SeqCst);
+							spool::spool_failed_batch(&addr, &auth, false, buffer);
+						}
+						Err(err) => {
+							eprintln!("[{}] Upload request failed: {}", i, err);
+							fail_count.fetch_add(1, Ordering::SeqCst);
+							spool::spool_failed_batch(&addr, &auth, false, buffer);
+						}
 					}
 				});
 				handles.push(handle);
@@ -484,6 +965,40 @@ async fn main() -> Result<(), Box<dyn Error>> {
 			println!("Success count: {}", success_count.load(Ordering::SeqCst));
 			println!("Fail count: {}", fail_count.load(Ordering::SeqCst));
 		}
+		Commands::Bench(args) => {
+			let default_address = args
+				.address
+				.clone()
+				.or_else(|| cli.address.clone())
+				.or_else(load_default_address)
+				.unwrap_or_else(|| "http://127.0.0.1:3337".to_string());
+
+			let mut reports = Vec::new();
+			for file in &args.files {
+				let contents = std::fs::read_to_string(file)?;
+				let spec: BenchWorkload = serde_json::from_str(&contents)?;
+				println!("Running workload: {}", spec.name);
+				let report = run_bench_workload(&spec, &default_address).await?;
+				reports.push(report);
+			}
+
+			let report_json = serde_json::to_string_pretty(&reports)?;
+			if let Some(output) = &args.output {
+				std::fs::write(output, &report_json)?;
+			} else {
+				println!("{}", report_json);
+			}
+
+			if let Some(report_to) = &args.report_to {
+				let response = Client::new()
+					.post(report_to)
+					.header("Content-Type", "application/json")
+					.body(report_json)
+					.send()
+					.await?;
+				println!("Report upload status: {}", response.status());
+			}
+		}
 		Commands::Tokenize { subcommand } => {
 			match subcommand {
 				TokenizeSubcommands::Drain { src, output } => {
@@ -596,10 +1111,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
 					count,
 					sort,
 					output,
+					s3,
 				} => {
 					let mut url = Url::parse(&format!("{}/api/v1/segments", base_addr))?;
+					let s3_target = S3Target::parse(&output, &s3)?;
 					let output_path = Path::new(&output);
-					if !output_path.exists() {
+					if s3_target.is_none() && !output_path.exists() {
 						std::fs::create_dir_all(output_path)?;
 					}
 					{
@@ -636,13 +1153,29 @@ async fn main() -> Result<(), Box<dyn Error>> {
 						let url =
 							Url::parse(&format!("{}/api/v1/segment/{}/download", base_addr, id))
 								.unwrap();
-						let file_path = output_path.join(format!("segment_{}.zstd", id));
-						if !file_path.exists() {
-							println!("downloading: {}", url);
-							let response = client.get(url).send().await?.bytes().await?;
-							println!("saving to file: {}", file_path.display());
-							let mut file = std::fs::File::create(file_path)?;
-							file.write_all(&response)?;
+						let key = format!("segment_{}.zstd", id);
+						if let Some(s3) = &s3_target {
+							if !s3.exists(&key).await? {
+								println!("downloading: {}", url);
+								let response = client.get(url).send().await?.error_for_status()?;
+								// Streamed straight into the object store as it
+								// arrives off the network, so a large segment
+								// never sits fully buffered in memory.
+								let byte_stream =
+									response.bytes_stream().map_err(std::io::Error::other);
+								let reader = tokio_util::io::StreamReader::new(byte_stream);
+								s3.put_stream(&key, reader).await?;
+								println!("uploaded to s3: {}", key);
+							}
+						} else {
+							let file_path = output_path.join(&key);
+							if !file_path.exists() {
+								println!("downloading: {}", url);
+								let response = client.get(url).send().await?.bytes().await?;
+								println!("saving to file: {}", file_path.display());
+								let mut file = std::fs::File::create(file_path)?;
+								file.write_all(&response)?;
+							}
 						}
 
 						let url =
@@ -657,10 +1190,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
 					count,
 					sort,
 					output,
+					s3,
 				} => {
 					let mut url = Url::parse(&format!("{}/api/v1/segments", base_addr))?;
+					let s3_target = S3Target::parse(&output, &s3)?;
 					let output_path = Path::new(&output);
-					if !output_path.exists() {
+					if s3_target.is_none() && !output_path.exists() {
 						std::fs::create_dir_all(output_path)?;
 					}
 					{
@@ -697,7 +1232,26 @@ async fn main() -> Result<(), Box<dyn Error>> {
 						let url =
 							Url::parse(&format!("{}/api/v1/segment/{}/download", base_addr, id))
 								.unwrap();
-						let file_path = output_path.join(format!("segment_{}.zst", id));
+						let key = format!("segment_{}.zst", id);
+
+						if let Some(s3) = &s3_target {
+							if s3.exists(&key).await? {
+								println!("already exists in s3, skipping: {}", key);
+								continue;
+							}
+							println!("downloading: {}", url);
+							let response = client.get(url).send().await?.error_for_status()?;
+							// Streamed straight into the object store as it
+							// arrives off the network, so a large segment never
+							// sits fully buffered in memory.
+							let byte_stream = response.bytes_stream().map_err(std::io::Error::other);
+							let reader = tokio_util::io::StreamReader::new(byte_stream);
+							s3.put_stream(&key, reader).await?;
+							println!("uploaded to s3: {}", key);
+							continue;
+						}
+
+						let file_path = output_path.join(&key);
 						if file_path.exists() {
 							println!("file already exists: {}", file_path.display());
 							continue;
@@ -712,8 +1266,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
 				}
 			}
 		}
-		Commands::Import { folder } => {
-			import_segments(&folder).await?;
+		Commands::Import { folder, s3, templated } => {
+			import_segments(&folder, &s3, templated).await?;
 		}
 	}
 
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+
+/// Endpoint/region/credentials overrides for an `s3://` source or sink,
+/// shared by `Import` and `Segment Download`/`DownloadRemove`. Anything left
+/// unset falls back to the usual AWS env vars via `AmazonS3Builder::from_env`,
+/// the same defaulting `puppylog_server`'s own `S3Store` uses.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct S3Args {
+	/// S3-compatible endpoint, e.g. `http://localhost:9000` for MinIO.
+	#[arg(long)]
+	pub s3_endpoint: Option<String>,
+	#[arg(long)]
+	pub s3_region: Option<String>,
+	#[arg(long)]
+	pub s3_access_key: Option<String>,
+	#[arg(long)]
+	pub s3_secret_key: Option<String>,
+}
+
+/// A parsed `s3://bucket/prefix` argument plus the client to talk to it.
+pub struct S3Target {
+	pub store: Arc<dyn ObjectStore>,
+	prefix: String,
+}
+
+impl S3Target {
+	/// Parses `spec` as `s3://bucket[/prefix]`. Returns `None` (not an error)
+	/// when `spec` doesn't use the `s3://` scheme, so callers can fall back to
+	/// treating it as a local path unchanged.
+	pub fn parse(spec: &str, args: &S3Args) -> anyhow::Result<Option<Self>> {
+		let Some(rest) = spec.strip_prefix("s3://") else {
+			return Ok(None);
+		};
+		let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+
+		let mut builder = AmazonS3Builder::from_env().with_bucket_name(bucket);
+		if let Some(endpoint) = &args.s3_endpoint {
+			// A custom endpoint is almost always a non-AWS MinIO/R2/etc.
+			// deployment reachable over plain HTTP in dev; `with_allow_http`
+			// only matters when the endpoint itself is `http://`.
+			builder = builder.with_endpoint(endpoint).with_allow_http(true);
+		}
+		if let Some(region) = &args.s3_region {
+			builder = builder.with_region(region);
+		}
+		if let Some(key) = &args.s3_access_key {
+			builder = builder.with_access_key_id(key);
+		}
+		if let Some(secret) = &args.s3_secret_key {
+			builder = builder.with_secret_access_key(secret);
+		}
+
+		Ok(Some(Self {
+			store: Arc::new(builder.build()?),
+			prefix: prefix.trim_end_matches('/').to_string(),
+		}))
+	}
+
+	fn object_path(&self, name: &str) -> ObjectPath {
+		if self.prefix.is_empty() {
+			ObjectPath::from(name)
+		} else {
+			ObjectPath::from(format!("{}/{}", self.prefix, name))
+		}
+	}
+
+	pub async fn exists(&self, name: &str) -> anyhow::Result<bool> {
+		match self.store.head(&self.object_path(name)).await {
+			Ok(_) => Ok(true),
+			Err(object_store::Error::NotFound { .. }) => Ok(false),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	/// Streams `reader` straight into `name` via a multipart upload, so the
+	/// caller never has to hold the whole (possibly large) segment in memory.
+	pub async fn put_stream<R>(&self, name: &str, mut reader: R) -> anyhow::Result<()>
+	where
+		R: tokio::io::AsyncRead + Unpin,
+	{
+		use object_store::buffered::BufWriter;
+		use tokio::io::AsyncWriteExt;
+
+		let mut writer = BufWriter::new(self.store.clone(), self.object_path(name));
+		tokio::io::copy(&mut reader, &mut writer).await?;
+		writer.shutdown().await?;
+		Ok(())
+	}
+
+	/// Lists every object under the configured prefix.
+	pub fn list(&self) -> impl futures::Stream<Item = object_store::Result<object_store::ObjectMeta>> + '_ {
+		let prefix = if self.prefix.is_empty() { None } else { Some(ObjectPath::from(self.prefix.clone())) };
+		self.store.list(prefix.as_ref())
+	}
+
+	/// Opens `location` (as returned by `list`) as a streamed, bridge-able
+	/// async reader instead of a fully buffered `Bytes`.
+	pub async fn get_stream(&self, location: &ObjectPath) -> anyhow::Result<impl tokio::io::AsyncRead> {
+		use futures::TryStreamExt;
+		use tokio_util::io::StreamReader;
+
+		let result = self.store.get(location).await?;
+		let stream = result.into_stream().map_err(std::io::Error::other);
+		Ok(StreamReader::new(stream))
+	}
+}
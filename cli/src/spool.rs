@@ -0,0 +1,154 @@
+use rand::Rng;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Base delay for the first spool retry; doubles on each subsequent attempt
+/// the same way `puppylog::logger`'s websocket reconnect backs off.
+const RETRY_BASE: Duration = Duration::from_secs(1);
+/// Upper bound on the backoff delay, reached after a handful of attempts.
+const RETRY_MAX: Duration = Duration::from_secs(60);
+/// Attempts allowed before a batch is moved to `failed/` instead of retried
+/// again.
+const MAX_ATTEMPTS: u32 = 10;
+/// Total bytes the spool directory is allowed to hold before the oldest
+/// queued batches are dropped to make room for new ones.
+const DEFAULT_QUOTA_BYTES: u64 = 256 * 1024 * 1024;
+
+/// One POST that couldn't be delivered, persisted to disk so it survives a
+/// process restart instead of being dropped the moment `upload_logs` fails.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SpooledBatch {
+	pub address: String,
+	pub auth: String,
+	pub gzip: bool,
+	/// The already-built request body (gzip-encoded already, if `gzip`).
+	pub body: Vec<u8>,
+	pub attempts: u32,
+}
+
+/// `~/.puppylog/spool`, mirroring `load_default_address`'s use of
+/// `$HOME/.puppylog` as the CLI's per-user state directory.
+pub fn spool_dir() -> PathBuf {
+	let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+	Path::new(&home).join(".puppylog").join("spool")
+}
+
+fn failed_dir() -> PathBuf {
+	spool_dir().join("failed")
+}
+
+fn dir_size(dir: &Path) -> u64 {
+	fs::read_dir(dir)
+		.into_iter()
+		.flatten()
+		.flatten()
+		.filter_map(|entry| entry.metadata().ok())
+		.filter(|metadata| metadata.is_file())
+		.map(|metadata| metadata.len())
+		.sum()
+}
+
+/// Queued batches under `spool_dir()`, oldest first by file name (batches
+/// are named after their enqueue time in microseconds plus a random suffix,
+/// so name order is enqueue order).
+fn queued_files() -> Vec<PathBuf> {
+	let mut files: Vec<PathBuf> = fs::read_dir(spool_dir())
+		.into_iter()
+		.flatten()
+		.flatten()
+		.map(|entry| entry.path())
+		.filter(|path| path.is_file())
+		.collect();
+	files.sort();
+	files
+}
+
+/// Persists `batch` to a new file under `spool_dir()`, dropping the oldest
+/// queued batches first if doing so would push the spool over `quota_bytes`.
+pub fn enqueue(batch: &SpooledBatch, quota_bytes: u64) -> Result<(), Box<dyn Error>> {
+	let dir = spool_dir();
+	fs::create_dir_all(&dir)?;
+	let encoded = serde_json::to_vec(batch)?;
+
+	let mut files = queued_files();
+	while dir_size(&dir) + encoded.len() as u64 > quota_bytes && !files.is_empty() {
+		let oldest = files.remove(0);
+		log::warn!("spool quota exceeded, dropping oldest queued batch {:?}", oldest);
+		let _ = fs::remove_file(oldest);
+	}
+
+	let name = format!("{}-{:06}.json", chrono::Utc::now().timestamp_micros(), rand::thread_rng().gen_range(0..1_000_000));
+	fs::write(dir.join(name), encoded)?;
+	Ok(())
+}
+
+/// Runs forever, periodically retrying every batch under `spool_dir()` with
+/// exponential backoff plus jitter: deletes the file on a 2xx response, bumps
+/// its attempt count and waits before the next pass otherwise, and moves it
+/// to `failed/` once it has failed `MAX_ATTEMPTS` times. Meant to be spawned once as
+/// a background task for the lifetime of a long-running `Upload` invocation.
+pub async fn drain_spool() {
+	loop {
+		for path in queued_files() {
+			let Ok(contents) = fs::read(&path) else { continue };
+			let Ok(mut batch) = serde_json::from_slice::<SpooledBatch>(&contents) else {
+				continue;
+			};
+
+			let client = reqwest::Client::new();
+			let result = client
+				.post(&batch.address)
+				.header("Authorization", batch.auth.clone())
+				.body(batch.body.clone())
+				.send()
+				.await;
+
+			match result {
+				Ok(response) if response.status().is_success() => {
+					let _ = fs::remove_file(&path);
+				}
+				_ => {
+					batch.attempts += 1;
+					if batch.attempts >= MAX_ATTEMPTS {
+						log::error!("spool batch {:?} reached {} attempts, moving to failed/", path, MAX_ATTEMPTS);
+						let failed = failed_dir();
+						if fs::create_dir_all(&failed).is_ok() {
+							let _ = fs::rename(&path, failed.join(path.file_name().unwrap()));
+						}
+					} else if let Ok(encoded) = serde_json::to_vec(&batch) {
+						let _ = fs::write(&path, encoded);
+					}
+				}
+			}
+		}
+
+		let attempt = queued_files()
+			.into_iter()
+			.filter_map(|path| fs::read(path).ok())
+			.filter_map(|contents| serde_json::from_slice::<SpooledBatch>(&contents).ok())
+			.map(|batch| batch.attempts)
+			.max()
+			.unwrap_or(0);
+		let exp = RETRY_BASE.saturating_mul(1u32.checked_shl(attempt.min(16)).unwrap_or(u32::MAX));
+		let capped = exp.min(RETRY_MAX);
+		let jitter_ms = rand::thread_rng().gen_range(0..=(capped.as_millis() as u64 / 4).max(1));
+		tokio::time::sleep(capped + Duration::from_millis(jitter_ms)).await;
+	}
+}
+
+/// Spools `body` for later retry via [`drain_spool`] instead of dropping it,
+/// using [`DEFAULT_QUOTA_BYTES`] as the spool size quota.
+pub fn spool_failed_batch(address: &str, auth: &str, gzip: bool, body: Vec<u8>) {
+	let batch = SpooledBatch {
+		address: address.to_string(),
+		auth: auth.to_string(),
+		gzip,
+		body,
+		attempts: 0,
+	};
+	if let Err(err) = enqueue(&batch, DEFAULT_QUOTA_BYTES) {
+		log::error!("failed to spool batch for retry: {}", err);
+	}
+}
@@ -15,7 +15,7 @@ fn generate_logs(count: usize) -> Vec<LogEntry> {
 			msg: format!("log {}", i),
 			props: vec![Prop {
 				key: "device".into(),
-				value: (i % 10).to_string(),
+				value: (i % 10).to_string().into(),
 			}],
 			..Default::default()
 		})
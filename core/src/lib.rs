@@ -1,20 +1,35 @@
 mod chunk_reader;
 mod drain;
+mod log_buffer;
+mod log_buffer2;
+mod log_rotator;
 mod logentry;
 mod logfile;
 mod logger;
+mod otlp;
 mod query_eval;
 mod query_parsing;
 
 pub use chunk_reader::ChunkReader;
 pub use drain::{DrainParser, LogGroup, LogTemplate};
+pub use log_buffer::chunk_digest;
+pub use log_rotator::{LogRotator, RepairOutcome, RotationPolicy, SegmentInfo};
 pub use logentry::*;
-pub use logger::PuppylogBuilder;
+pub use logger::{OtlpTransport, PuppylogBuilder};
+pub use query_eval::aggregate;
 pub use query_eval::check_expr;
 pub use query_eval::check_props;
 pub use query_eval::extract_date_conditions;
+pub use query_eval::extract_equality_props;
 pub use query_eval::match_date_range;
+pub use query_eval::match_segment;
+pub use query_eval::new_aggregator;
+pub use query_eval::prop_bounds;
+pub use query_eval::register_fn;
+pub use query_eval::simplify;
 pub use query_eval::timestamp_bounds;
+pub use query_eval::{AggrOp, Aggregate, Aggregator, TimeBucket};
+pub use query_eval::SegmentStats;
 pub use query_parsing::*;
 use serde::Deserialize;
 use serde::Serialize;
@@ -107,13 +107,310 @@ pub enum LogentryDeserializerError {
 	NotEnoughData
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
+/// Writes `value` as an unsigned LEB128 varint: 7 payload bits per byte,
+/// high bit set on every byte but the last. Used from version 2 onward so a
+/// prop key/value/count or message length past 255 (or 4 billion) doesn't
+/// silently wrap like the old fixed-width prefixes did.
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+	loop {
+		let byte = (value & 0x7f) as u8;
+		value >>= 7;
+		if value != 0 {
+			writer.write_u8(byte | 0x80)?;
+		} else {
+			writer.write_u8(byte)?;
+			break;
+		}
+	}
+	Ok(())
+}
+
+/// `Read`-based twin of `write_varint`. A short read surfaces as the
+/// underlying `io::Error` (`UnexpectedEof` from a partial chunk), which
+/// `LogEntryChunkParser` already treats as "not enough data yet" and rolls
+/// back from.
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+	let mut result: u64 = 0;
+	let mut shift = 0;
+	loop {
+		let byte = reader.read_u8()?;
+		result |= ((byte & 0x7f) as u64) << shift;
+		if byte & 0x80 == 0 {
+			break;
+		}
+		shift += 7;
+	}
+	Ok(result)
+}
+
+/// Slice-based twin of `write_varint`, for `LogEntry::fast_deserialize`'s
+/// zero-copy parser.
+fn read_varint_from_slice(data: &[u8], ptr: &mut usize) -> Result<u64, LogentryDeserializerError> {
+	let mut result: u64 = 0;
+	let mut shift = 0;
+	loop {
+		if *ptr >= data.len() {
+			return Err(LogentryDeserializerError::NotEnoughData);
+		}
+		let byte = data[*ptr];
+		*ptr += 1;
+		result |= ((byte & 0x7f) as u64) << shift;
+		if byte & 0x80 == 0 {
+			break;
+		}
+		shift += 7;
+	}
+	Ok(result)
+}
+
+/// Checks that `len` more bytes are actually available at `*ptr` before a
+/// caller slices or allocates for them. The LEB128 length prefixes
+/// (`prop_count`, `key_len`, `msg_len`, TLV `len`) are attacker-controlled
+/// and unbounded, unlike the old fixed-width `u8`/`u32` prefixes they
+/// replaced — comparing in `u64` here (rather than `*ptr + len > data.len()`
+/// in `usize`) avoids the overflow a `len` near `u64::MAX` would otherwise
+/// cause, which could panic or wrap around and defeat the check entirely.
+fn check_remaining(data: &[u8], ptr: usize, len: u64) -> Result<(), LogentryDeserializerError> {
+	let remaining = (data.len() - ptr) as u64;
+	if len > remaining {
+		return Err(LogentryDeserializerError::NotEnoughData);
+	}
+	Ok(())
+}
+
+/// A `Prop`'s value, tag-length-value encoded on the wire (the scheme
+/// aya-log uses): one `u8` tag, then for `Str`/`Bytes` a varint length, then
+/// the payload. Lets numeric/boolean props round-trip without the lossy
+/// string formatting `Prop { value: String }` used to force on them, and is
+/// the building block for range-filtering typed props later.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropValue {
+	Str(String),
+	I64(i64),
+	U64(u64),
+	F64(f64),
+	Bool(bool),
+	Bytes(Vec<u8>),
+}
+
+impl PropValue {
+	const TAG_STR: u8 = 0;
+	const TAG_I64: u8 = 1;
+	const TAG_U64: u8 = 2;
+	const TAG_F64: u8 = 3;
+	const TAG_BOOL: u8 = 4;
+	const TAG_BYTES: u8 = 5;
+
+	fn tag(&self) -> u8 {
+		match self {
+			PropValue::Str(_) => Self::TAG_STR,
+			PropValue::I64(_) => Self::TAG_I64,
+			PropValue::U64(_) => Self::TAG_U64,
+			PropValue::F64(_) => Self::TAG_F64,
+			PropValue::Bool(_) => Self::TAG_BOOL,
+			PropValue::Bytes(_) => Self::TAG_BYTES,
+		}
+	}
+
+	/// Writes this value's TLV encoding: tag, then (for `Str`/`Bytes`) a
+	/// varint length, then the payload.
+	fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+		writer.write_u8(self.tag())?;
+		match self {
+			PropValue::Str(s) => {
+				write_varint(writer, s.len() as u64)?;
+				writer.write_all(s.as_bytes())?;
+			}
+			PropValue::Bytes(b) => {
+				write_varint(writer, b.len() as u64)?;
+				writer.write_all(b)?;
+			}
+			PropValue::I64(v) => writer.write_i64::<LittleEndian>(*v)?,
+			PropValue::U64(v) => writer.write_u64::<LittleEndian>(*v)?,
+			PropValue::F64(v) => writer.write_f64::<LittleEndian>(*v)?,
+			PropValue::Bool(v) => writer.write_u8(if *v { 1 } else { 0 })?,
+		}
+		Ok(())
+	}
+
+	/// Reads a TLV-encoded value from a `Read`. Unknown tags report
+	/// `InvalidPropValue` rather than guessing a shape for the bytes that
+	/// follow.
+	fn read<R: Read>(reader: &mut R) -> Result<PropValue, LogentryDeserializerError> {
+		let tag = reader
+			.read_u8()
+			.map_err(|_| LogentryDeserializerError::InvalidPropValue)?;
+		let err = |_| LogentryDeserializerError::InvalidPropValue;
+		match tag {
+			Self::TAG_STR | Self::TAG_BYTES => {
+				let len = read_varint(reader).map_err(err)?;
+				let mut buf = vec![0u8; len as usize];
+				reader.read_exact(&mut buf).map_err(err)?;
+				if tag == Self::TAG_STR {
+					Ok(PropValue::Str(String::from_utf8_lossy(&buf).to_string()))
+				} else {
+					Ok(PropValue::Bytes(buf))
+				}
+			}
+			Self::TAG_I64 => Ok(PropValue::I64(reader.read_i64::<LittleEndian>().map_err(err)?)),
+			Self::TAG_U64 => Ok(PropValue::U64(reader.read_u64::<LittleEndian>().map_err(err)?)),
+			Self::TAG_F64 => Ok(PropValue::F64(reader.read_f64::<LittleEndian>().map_err(err)?)),
+			Self::TAG_BOOL => Ok(PropValue::Bool(reader.read_u8().map_err(err)? != 0)),
+			_ => Err(LogentryDeserializerError::InvalidPropValue),
+		}
+	}
+
+	/// Slice-based twin of `read`, for `LogEntry::fast_deserialize`'s
+	/// zero-copy parser.
+	fn read_from_slice(data: &[u8], ptr: &mut usize) -> Result<PropValue, LogentryDeserializerError> {
+		if *ptr + 1 > data.len() {
+			return Err(LogentryDeserializerError::NotEnoughData);
+		}
+		let tag = data[*ptr];
+		*ptr += 1;
+		match tag {
+			Self::TAG_STR | Self::TAG_BYTES => {
+				let len = read_varint_from_slice(data, ptr)?;
+				check_remaining(data, *ptr, len)?;
+				let len = len as usize;
+				let bytes = data[*ptr..*ptr + len].to_vec();
+				*ptr += len;
+				if tag == Self::TAG_STR {
+					Ok(PropValue::Str(String::from_utf8_lossy(&bytes).to_string()))
+				} else {
+					Ok(PropValue::Bytes(bytes))
+				}
+			}
+			Self::TAG_I64 => {
+				if *ptr + 8 > data.len() {
+					return Err(LogentryDeserializerError::NotEnoughData);
+				}
+				let v = i64::from_le_bytes(data[*ptr..*ptr + 8].try_into().unwrap());
+				*ptr += 8;
+				Ok(PropValue::I64(v))
+			}
+			Self::TAG_U64 => {
+				if *ptr + 8 > data.len() {
+					return Err(LogentryDeserializerError::NotEnoughData);
+				}
+				let v = u64::from_le_bytes(data[*ptr..*ptr + 8].try_into().unwrap());
+				*ptr += 8;
+				Ok(PropValue::U64(v))
+			}
+			Self::TAG_F64 => {
+				if *ptr + 8 > data.len() {
+					return Err(LogentryDeserializerError::NotEnoughData);
+				}
+				let v = f64::from_le_bytes(data[*ptr..*ptr + 8].try_into().unwrap());
+				*ptr += 8;
+				Ok(PropValue::F64(v))
+			}
+			Self::TAG_BOOL => {
+				if *ptr + 1 > data.len() {
+					return Err(LogentryDeserializerError::NotEnoughData);
+				}
+				let v = data[*ptr] != 0;
+				*ptr += 1;
+				Ok(PropValue::Bool(v))
+			}
+			_ => Err(LogentryDeserializerError::InvalidPropValue),
+		}
+	}
+}
+
+impl std::fmt::Display for PropValue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			PropValue::Str(s) => write!(f, "{}", s),
+			PropValue::I64(v) => write!(f, "{}", v),
+			PropValue::U64(v) => write!(f, "{}", v),
+			PropValue::F64(v) => write!(f, "{}", v),
+			PropValue::Bool(v) => write!(f, "{}", v),
+			PropValue::Bytes(b) => {
+				for byte in b {
+					write!(f, "{:02x}", byte)?;
+				}
+				Ok(())
+			}
+		}
+	}
+}
+
+impl PartialEq<str> for PropValue {
+	fn eq(&self, other: &str) -> bool {
+		matches!(self, PropValue::Str(s) if s == other)
+	}
+}
+
+impl PartialEq<&str> for PropValue {
+	fn eq(&self, other: &&str) -> bool {
+		self == *other
+	}
+}
+
+impl From<String> for PropValue {
+	fn from(value: String) -> Self {
+		PropValue::Str(value)
+	}
+}
+
+impl From<&str> for PropValue {
+	fn from(value: &str) -> Self {
+		PropValue::Str(value.to_string())
+	}
+}
+
+/// Serializes as a plain JSON scalar (string/number/bool, or a hex string
+/// for `Bytes`) rather than a tagged `{"Str": ...}` object, so existing API
+/// consumers that expect `Prop.value` to be a bare JSON value keep working.
+impl Serialize for PropValue {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			PropValue::Str(s) => serializer.serialize_str(s),
+			PropValue::I64(v) => serializer.serialize_i64(*v),
+			PropValue::U64(v) => serializer.serialize_u64(*v),
+			PropValue::F64(v) => serializer.serialize_f64(*v),
+			PropValue::Bool(v) => serializer.serialize_bool(*v),
+			PropValue::Bytes(_) => serializer.serialize_str(&self.to_string()),
+		}
+	}
+}
+
+/// Mirrors `Serialize`: any JSON scalar deserializes straight into the
+/// matching variant, with strings going to `Str` (there's no JSON
+/// representation for `Bytes` a client could send back).
+impl<'de> Deserialize<'de> for PropValue {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let value = serde_json::Value::deserialize(deserializer)?;
+		match value {
+			serde_json::Value::String(s) => Ok(PropValue::Str(s)),
+			serde_json::Value::Bool(b) => Ok(PropValue::Bool(b)),
+			serde_json::Value::Number(n) => {
+				if let Some(v) = n.as_i64() {
+					Ok(PropValue::I64(v))
+				} else if let Some(v) = n.as_u64() {
+					Ok(PropValue::U64(v))
+				} else if let Some(v) = n.as_f64() {
+					Ok(PropValue::F64(v))
+				} else {
+					Err(serde::de::Error::custom("invalid prop value number"))
+				}
+			}
+			other => Err(serde::de::Error::custom(format!(
+				"unsupported prop value: {:?}",
+				other
+			))),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Prop {
 	pub key: String,
-	pub value: String
+	pub value: PropValue,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
 	pub version: u16,
 	pub random: u32,
@@ -126,7 +423,7 @@ pub struct LogEntry {
 impl Default for LogEntry {
 	fn default() -> Self {
 		LogEntry {
-			version: 1,
+			version: 2,
 			random: 0,
 			timestamp: Utc::now(),
 			level: LogLevel::Info,
@@ -156,20 +453,37 @@ impl LogEntry {
 		writer.write_i64::<LittleEndian>(self.timestamp.timestamp_micros())?;
 		writer.write_u32::<LittleEndian>(self.random)?;
 		writer.write_u8((&self.level).into())?;
-		writer.write_u8(self.props.len() as u8)?;
+		if self.version >= 2 {
+			write_varint(writer, self.props.len() as u64)?;
+		} else {
+			writer.write_u8(self.props.len() as u8)?;
+		}
 		for prop in &self.props {
-			writer.write_u8(prop.key.len() as u8)?;
+			if self.version >= 2 {
+				write_varint(writer, prop.key.len() as u64)?;
+			} else {
+				writer.write_u8(prop.key.len() as u8)?;
+			}
 			writer.write_all(prop.key.as_bytes())?;
-			writer.write_u8(prop.value.len() as u8)?;
-			writer.write_all(prop.value.as_bytes())?;
+			if self.version >= 2 {
+				prop.value.write(writer)?;
+			} else {
+				let value = prop.value.to_string();
+				writer.write_u8(value.len() as u8)?;
+				writer.write_all(value.as_bytes())?;
+			}
+		}
+		if self.version >= 2 {
+			write_varint(writer, self.msg.len() as u64)?;
+		} else {
+			writer.write_u32::<LittleEndian>(self.msg.len() as u32)?;
 		}
-		writer.write_u32::<LittleEndian>(self.msg.len() as u32)?;
 		writer.write_all(self.msg.as_bytes())?;
 		Ok(())
 	}
 
 	pub fn fast_deserialize(data: &[u8], ptr: &mut usize) -> Result<LogEntry, LogentryDeserializerError> {
-		if *ptr + 16 > data.len() {
+		if *ptr + 15 > data.len() {
 			return Err(LogentryDeserializerError::NotEnoughData);
 		}
 		let version = u16::from_le_bytes(data[*ptr..(*ptr+2)].try_into().unwrap());
@@ -190,43 +504,69 @@ impl LogEntry {
 			Err(_) => return Err(LogentryDeserializerError::InvalidLogLevel)
 		};
 		*ptr += 1;
-		let prop_count = data[*ptr];
-		*ptr += 1;
-		let mut props = Vec::with_capacity(prop_count as usize);
-		for _ in 0..prop_count {
+		let prop_count = if version >= 2 {
+			read_varint_from_slice(data, ptr)?
+		} else {
 			if *ptr + 1 > data.len() {
 				return Err(LogentryDeserializerError::NotEnoughData);
 			}
-			let key_len = data[*ptr] as usize;
+			let v = data[*ptr] as u64;
 			*ptr += 1;
-			if *ptr + key_len + 1 > data.len() {
-				return Err(LogentryDeserializerError::NotEnoughData);
-			}
+			v
+		};
+		// Each prop consumes at least one more byte (its key-length varint),
+		// so a `prop_count` bigger than the remaining buffer is already
+		// provably bogus — reject it before `Vec::with_capacity` ever sees
+		// the raw attacker-controlled count.
+		check_remaining(data, *ptr, prop_count)?;
+		let mut props = Vec::with_capacity(prop_count as usize);
+		for _ in 0..prop_count {
+			let key_len = if version >= 2 {
+				read_varint_from_slice(data, ptr)?
+			} else {
+				if *ptr + 1 > data.len() {
+					return Err(LogentryDeserializerError::NotEnoughData);
+				}
+				let v = data[*ptr] as u64;
+				*ptr += 1;
+				v
+			};
+			check_remaining(data, *ptr, key_len)?;
+			let key_len = key_len as usize;
 			let key = String::from_utf8_lossy(&data[*ptr..*ptr + key_len]).to_string();
 			*ptr += key_len;
-			if *ptr + 1 > data.len() {
-				return Err(LogentryDeserializerError::NotEnoughData);
-			}
-			let value_len = data[*ptr] as usize;
-			*ptr += 1;
-			if *ptr + value_len > data.len() {
-				return Err(LogentryDeserializerError::NotEnoughData);
-			}
-			let value = String::from_utf8_lossy(&data[*ptr..*ptr + value_len]).to_string();
-			*ptr += value_len;
+			let value = if version >= 2 {
+				PropValue::read_from_slice(data, ptr)?
+			} else {
+				if *ptr + 1 > data.len() {
+					return Err(LogentryDeserializerError::NotEnoughData);
+				}
+				let value_len = data[*ptr] as usize;
+				*ptr += 1;
+				if *ptr + value_len > data.len() {
+					return Err(LogentryDeserializerError::NotEnoughData);
+				}
+				let value = String::from_utf8_lossy(&data[*ptr..*ptr + value_len]).to_string();
+				*ptr += value_len;
+				PropValue::Str(value)
+			};
 			props.push(Prop {
 				key,
 				value
 			});
 		}
-		if *ptr + 4 > data.len() {
-			return Err(LogentryDeserializerError::NotEnoughData);
-		}
-		let msg_len = u32::from_le_bytes(data[*ptr..*ptr + 4].try_into().unwrap()) as usize;
-		*ptr += 4;
-		if *ptr + msg_len > data.len() {
-			return Err(LogentryDeserializerError::NotEnoughData);
-		}
+		let msg_len = if version >= 2 {
+			read_varint_from_slice(data, ptr)?
+		} else {
+			if *ptr + 4 > data.len() {
+				return Err(LogentryDeserializerError::NotEnoughData);
+			}
+			let v = u32::from_le_bytes(data[*ptr..*ptr + 4].try_into().unwrap()) as u64;
+			*ptr += 4;
+			v
+		};
+		check_remaining(data, *ptr, msg_len)?;
+		let msg_len = msg_len as usize;
 		let msg = String::from_utf8_lossy(&data[*ptr..*ptr + msg_len]).to_string();
 		*ptr += msg_len;
 		Ok(LogEntry {
@@ -255,23 +595,41 @@ impl LogEntry {
 			Ok(level) => level,
 			Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid log level"))
 		};
-		let prop_count = reader.read_u8()?;
+		let prop_count = if version >= 2 {
+			read_varint(reader)?
+		} else {
+			reader.read_u8()? as u64
+		};
 		let mut props = Vec::with_capacity(prop_count as usize);
 		for _ in 0..prop_count {
-			let key_len = reader.read_u8()?;
+			let key_len = if version >= 2 {
+				read_varint(reader)?
+			} else {
+				reader.read_u8()? as u64
+			};
 			let mut key = vec![0; key_len as usize];
 			reader.read_exact(&mut key)?;
 			let key = String::from_utf8_lossy(&key).to_string();
-			let value_len = reader.read_u8()?;
-			let mut value = vec![0; value_len as usize];
-			reader.read_exact(&mut value)?;
-			let value = String::from_utf8_lossy(&value).to_string();
+			let value = if version >= 2 {
+				PropValue::read(reader).map_err(|_| {
+					io::Error::new(io::ErrorKind::InvalidData, "Invalid prop value")
+				})?
+			} else {
+				let value_len = reader.read_u8()?;
+				let mut value = vec![0; value_len as usize];
+				reader.read_exact(&mut value)?;
+				PropValue::Str(String::from_utf8_lossy(&value).to_string())
+			};
 			props.push(Prop {
 				key,
 				value
 			});
 		}
-		let msg_len = reader.read_u32::<LittleEndian>()?;
+		let msg_len = if version >= 2 {
+			read_varint(reader)?
+		} else {
+			reader.read_u32::<LittleEndian>()? as u64
+		};
 		let mut msg = vec![0; msg_len as usize];
 		reader.read_exact(&mut msg)?;
 		let msg = String::from_utf8_lossy(&msg).to_string();
@@ -332,8 +690,8 @@ mod tests {
 			timestamp: Utc::now(),
 			level: LogLevel::Info,
 			props: vec![
-				Prop { key: "key1".to_string(), value: "value1".to_string() },
-				Prop { key: "key2".to_string(), value: "value2".to_string() }
+				Prop { key: "key1".to_string(), value: "value1".to_string().into() },
+				Prop { key: "key2".to_string(), value: "value2".to_string().into() }
 			],
 			msg: "Hello, world!".to_string(),
 			..Default::default()
@@ -356,8 +714,8 @@ mod tests {
 			timestamp: Utc::now(),
 			level: LogLevel::Info,
 			props: vec![
-				Prop { key: "key1".to_string(), value: "value1".to_string() },
-				Prop { key: "key2".to_string(), value: "value2".to_string() }
+				Prop { key: "key1".to_string(), value: "value1".to_string().into() },
+				Prop { key: "key2".to_string(), value: "value2".to_string().into() }
 			],
 			msg: "Hello, world!".to_string(),
 			..Default::default()
@@ -373,6 +731,41 @@ mod tests {
 		assert_eq!(entry.msg, deserialized.msg);
 	}
 
+	/// A malicious/corrupted `prop_count` (or `key_len`/`msg_len`/TLV `len`)
+	/// near `u64::MAX` must be rejected cleanly instead of overflowing the
+	/// `*ptr + len` bounds check or driving `Vec::with_capacity` into an
+	/// allocator abort — this is reachable straight from a device's uploaded
+	/// bytes via `fast_deserialize`.
+	#[test]
+	fn fast_deserialize_rejects_a_huge_prop_count_instead_of_panicking() {
+		let mut header = vec![2u8, 0]; // version = 2
+		header.extend_from_slice(&0i64.to_le_bytes()); // timestamp
+		header.extend_from_slice(&0u32.to_le_bytes()); // random
+		header.push(3); // level = Info (see LogLevel::try_from)
+		// prop_count as a varint encoding u64::MAX
+		header.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+
+		let result = LogEntry::fast_deserialize(&header, &mut 0);
+		assert!(matches!(result, Err(super::LogentryDeserializerError::NotEnoughData)));
+	}
+
+	#[test]
+	fn fast_deserialize_rejects_a_huge_tlv_value_length_instead_of_panicking() {
+		let mut header = vec![2u8, 0]; // version = 2
+		header.extend_from_slice(&0i64.to_le_bytes()); // timestamp
+		header.extend_from_slice(&0u32.to_le_bytes()); // random
+		header.push(3); // level = Info (see LogLevel::try_from)
+		header.push(1); // prop_count = 1
+		header.push(3); // key_len = 3
+		header.extend_from_slice(b"key");
+		header.push(super::PropValue::TAG_STR);
+		// TLV len as a varint encoding u64::MAX
+		header.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+
+		let result = LogEntry::fast_deserialize(&header, &mut 0);
+		assert!(matches!(result, Err(super::LogentryDeserializerError::NotEnoughData)));
+	}
+
     #[test]
 	fn parse_many_log_entries_in_different_chuncks() {
 		fn gen_loentries() -> Vec<LogEntry> {
@@ -382,8 +775,8 @@ mod tests {
 					timestamp: chrono::Utc::now(),
 					level: LogLevel::Info,
 					props: vec![
-						Prop { key: "key1".to_string(), value: "value1".to_string() },
-						Prop { key: "key2".to_string(), value: "value2".to_string() }
+						Prop { key: "key1".to_string(), value: "value1".to_string().into() },
+						Prop { key: "key2".to_string(), value: "value2".to_string().into() }
 					],
 					msg: format!("Hello, world! {}", i),
 					..Default::default()
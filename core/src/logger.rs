@@ -1,19 +1,24 @@
-use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::net::TcpStream;
 use std::str::FromStr;
 use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
 use std::path::{Path, PathBuf};
 use std::thread;
 use std::time::{Duration, Instant};
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
 use bytes::Bytes;
 use log::{Record, Level, Metadata, SetLoggerError};
 use chrono::Utc;
 use native_tls::TlsConnector;
+use rand::Rng;
 use tungstenite::client::client_with_config;
 use tungstenite::http::Uri;
 use tungstenite::stream::MaybeTlsStream;
 use tungstenite::{ClientRequestBuilder, Message, WebSocket};
 
+use crate::check_expr;
 use crate::log_buffer::LogBuffer;
 use crate::parse_log_query;
 use crate::LogEntry;
@@ -32,6 +37,364 @@ enum WorkerMessage {
 	FlushClose(mpsc::Sender<()>),
 }
 
+/// Which TLS stack `worker` uses for `wss://` connections.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TlsBackend {
+	#[default]
+	NativeTls,
+	Rustls,
+}
+
+/// Custom trust/identity material for the `wss://` connection, on top of
+/// whichever `TlsBackend` is selected. Left at its defaults, `worker` just
+/// uses the backend's own system root store and presents no client
+/// certificate.
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+	pub backend: TlsBackend,
+	/// PEM-encoded CA certificate to trust, for a server behind a private CA.
+	/// With the `NativeTls` backend this is trusted alongside the system
+	/// root store; the `Rustls` backend builds its root store from this
+	/// alone (it doesn't pull in a bundled system-root-store dependency), so
+	/// leaving it unset with `Rustls` means every handshake fails.
+	pub root_cert_pem: Option<Vec<u8>>,
+	/// PEM-encoded (certificate, private key) pair to present for mTLS.
+	pub client_identity_pem: Option<(Vec<u8>, Vec<u8>)>,
+	/// Skip certificate verification entirely. Only honored by the
+	/// `NativeTls` backend; only ever useful against a known self-signed dev
+	/// server, never for a production `log_server`.
+	pub danger_accept_invalid_certs: bool,
+}
+
+/// Delay before reconnect attempt number `attempt` (0-indexed, 0 = the first
+/// retry after a failed/never-established connection): `base * 2^attempt`,
+/// capped at `max`, plus up to 25% random jitter so many clients reconnecting
+/// to a recovering server don't all land on the same instant.
+fn backoff_delay(attempt: u32, base: Duration, max: Duration) -> Duration {
+	let exp = base.saturating_mul(1u32.checked_shl(attempt.min(16)).unwrap_or(u32::MAX));
+	let capped = exp.min(max);
+	let jitter_ms = rand::rng().random_range(0..=(capped.as_millis() as u64 / 4).max(1));
+	capped + Duration::from_millis(jitter_ms)
+}
+
+/// Which tunneling protocol `connect_via_proxy` speaks to `ProxyConfig::url`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ProxyKind {
+	#[default]
+	Http,
+	Socks5,
+}
+
+/// Forward proxy to tunnel the log-server connection through, for networks
+/// where only the proxy has egress. Left at its default (`url: None`),
+/// `resolve_proxy` falls back to the `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY`
+/// environment variables, the same as most HTTP clients.
+#[derive(Clone, Default)]
+pub struct ProxyConfig {
+	pub url: Option<Uri>,
+	pub kind: ProxyKind,
+	pub username: Option<String>,
+	pub password: Option<String>,
+}
+
+/// Wire encoding `send_otlp_batch` sends an `.otlp()` batch with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum OtlpTransport {
+	#[default]
+	HttpProtobuf,
+	/// Wraps the protobuf payload in the gRPC message framing (a one-byte
+	/// compression flag plus a four-byte big-endian length prefix) and POSTs
+	/// it to the `LogsService/Export` path. A conformant gRPC server expects
+	/// HTTP/2, which this client doesn't speak, so this only reaches
+	/// collectors lenient enough to accept gRPC framing over a plain
+	/// request/response exchange; point `HttpProtobuf` at the OTLP/HTTP
+	/// receiver for anything else.
+	Grpc,
+}
+
+/// Where/how `.otlp()` batches get exported, independent of the puppylog
+/// `log_server` sink above — the two run side by side off the same entries,
+/// so a client can keep shipping to its own server while also feeding an
+/// existing observability backend.
+#[derive(Clone)]
+pub struct OtlpConfig {
+	pub endpoint: Uri,
+	pub transport: OtlpTransport,
+	/// Extra headers on the export request, e.g. `Authorization` or a
+	/// collector-specific tenant header.
+	pub headers: Vec<(String, String)>,
+	/// Flush once this many entries have accumulated, even if
+	/// `flush_interval` hasn't elapsed yet.
+	pub max_batch_records: usize,
+	/// Flush whatever has accumulated at least this often, even if
+	/// `max_batch_records` hasn't been reached.
+	pub flush_interval: Duration,
+}
+
+/// `NO_PROXY`/`no_proxy`-style matching: a bare host, or a suffix match on
+/// `.`-prefixed domains.
+fn env_no_proxy_matches(host: &str) -> bool {
+	let no_proxy = match std::env::var("NO_PROXY").or_else(|_| std::env::var("no_proxy")) {
+		Ok(v) => v,
+		Err(_) => return false,
+	};
+	no_proxy.split(',').map(|s| s.trim()).any(|pattern| {
+		!pattern.is_empty() && (host == pattern || host.ends_with(&format!(".{}", pattern.trim_start_matches('.'))))
+	})
+}
+
+/// Picks the proxy (if any) a connection to `target` should tunnel through:
+/// an explicit `builder.proxy`, else `HTTPS_PROXY`/`HTTP_PROXY` depending on
+/// `target`'s scheme, honoring `NO_PROXY` either way.
+fn resolve_proxy(builder: &PuppylogBuilder, target: &Uri) -> Option<ProxyConfig> {
+	let host = target.host()?;
+	if env_no_proxy_matches(host) {
+		return None;
+	}
+	if builder.proxy.url.is_some() {
+		return Some(builder.proxy.clone());
+	}
+	let is_wss = target.scheme().map(|s| s.as_str()) == Some("wss");
+	let var = if is_wss { "HTTPS_PROXY" } else { "HTTP_PROXY" };
+	let env_url = std::env::var(var)
+		.or_else(|_| std::env::var(var.to_lowercase()))
+		.ok()?;
+	let url = Uri::from_str(&env_url).ok()?;
+	let kind = match url.scheme().map(|s| s.as_str()) {
+		Some("socks5") => ProxyKind::Socks5,
+		_ => ProxyKind::Http,
+	};
+	Some(ProxyConfig { url: Some(url), kind, username: None, password: None })
+}
+
+/// Opens a TCP connection to `proxy` and tunnels it to `target_host:target_port`,
+/// so the caller can layer TLS/WS over the returned stream exactly as it would
+/// a direct connection.
+fn connect_via_proxy(proxy: &ProxyConfig, target_host: &str, target_port: u16) -> Result<TcpStream, PuppyLogError> {
+	let proxy_url = proxy.url.as_ref().ok_or_else(|| PuppyLogError::new("proxy config has no url"))?;
+	let proxy_host = proxy_url.host().ok_or_else(|| PuppyLogError::new("proxy url has no host"))?;
+	let proxy_port = proxy_url.port().map(|p| p.as_u16())
+		.unwrap_or(if proxy.kind == ProxyKind::Socks5 { 1080 } else { 8080 });
+	let mut socket = TcpStream::connect((proxy_host, proxy_port))?;
+	match proxy.kind {
+		ProxyKind::Http => http_connect_tunnel(&mut socket, target_host, target_port, proxy)?,
+		ProxyKind::Socks5 => socks5_handshake(&mut socket, target_host, target_port, proxy)?,
+	}
+	Ok(socket)
+}
+
+/// HTTP `CONNECT host:port` tunnel, the same mechanism forward proxies use
+/// to relay any opaque TCP stream (TLS, raw WebSocket) through themselves.
+fn http_connect_tunnel(socket: &mut TcpStream, host: &str, port: u16, proxy: &ProxyConfig) -> Result<(), PuppyLogError> {
+	let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+	if let (Some(user), Some(pass)) = (&proxy.username, &proxy.password) {
+		let credentials = BASE64_STANDARD.encode(format!("{}:{}", user, pass));
+		request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+	}
+	request.push_str("\r\n");
+	socket.write_all(request.as_bytes())?;
+
+	let mut reader = BufReader::new(socket.try_clone()?);
+	let mut status_line = String::new();
+	reader.read_line(&mut status_line)?;
+	if !status_line.contains(" 200 ") {
+		return Err(PuppyLogError::new(&format!("proxy CONNECT failed: {}", status_line.trim())));
+	}
+	// Drain the rest of the response headers up to the blank line.
+	loop {
+		let mut line = String::new();
+		let n = reader.read_line(&mut line)?;
+		if n == 0 || line == "\r\n" || line == "\n" {
+			break;
+		}
+	}
+	Ok(())
+}
+
+/// Minimal SOCKS5 client handshake (RFC 1928): no-auth or username/password
+/// negotiation, followed by a `CONNECT` request for `host:port` resolved by
+/// the proxy itself (domain-name addressing, so DNS happens proxy-side too).
+fn socks5_handshake(socket: &mut TcpStream, host: &str, port: u16, proxy: &ProxyConfig) -> Result<(), PuppyLogError> {
+	let has_auth = proxy.username.is_some() && proxy.password.is_some();
+	let methods: &[u8] = if has_auth { &[0x00, 0x02] } else { &[0x00] };
+	let mut greeting = vec![0x05u8, methods.len() as u8];
+	greeting.extend_from_slice(methods);
+	socket.write_all(&greeting)?;
+
+	let mut method_reply = [0u8; 2];
+	socket.read_exact(&mut method_reply)?;
+	if method_reply[0] != 0x05 {
+		return Err(PuppyLogError::new("socks5: unexpected version in method reply"));
+	}
+	match method_reply[1] {
+		0x00 => {},
+		0x02 if has_auth => {
+			let user = proxy.username.as_ref().unwrap();
+			let pass = proxy.password.as_ref().unwrap();
+			let mut auth = vec![0x01u8, user.len() as u8];
+			auth.extend_from_slice(user.as_bytes());
+			auth.push(pass.len() as u8);
+			auth.extend_from_slice(pass.as_bytes());
+			socket.write_all(&auth)?;
+			let mut auth_reply = [0u8; 2];
+			socket.read_exact(&mut auth_reply)?;
+			if auth_reply[1] != 0x00 {
+				return Err(PuppyLogError::new("socks5: proxy authentication failed"));
+			}
+		},
+		0xff => return Err(PuppyLogError::new("socks5: proxy rejected all auth methods")),
+		other => return Err(PuppyLogError::new(&format!("socks5: unsupported auth method {}", other))),
+	}
+
+	let mut request = vec![0x05u8, 0x01, 0x00, 0x03, host.len() as u8];
+	request.extend_from_slice(host.as_bytes());
+	request.extend_from_slice(&port.to_be_bytes());
+	socket.write_all(&request)?;
+
+	let mut header = [0u8; 4];
+	socket.read_exact(&mut header)?;
+	if header[0] != 0x05 {
+		return Err(PuppyLogError::new("socks5: unexpected version in connect reply"));
+	}
+	if header[1] != 0x00 {
+		return Err(PuppyLogError::new(&format!("socks5: connect request failed with code {}", header[1])));
+	}
+	// Drain the bound address the proxy reports back, length depends on its type.
+	match header[3] {
+		0x01 => { let mut buf = [0u8; 4 + 2]; socket.read_exact(&mut buf)?; },
+		0x03 => {
+			let mut len = [0u8; 1];
+			socket.read_exact(&mut len)?;
+			let mut buf = vec![0u8; len[0] as usize + 2];
+			socket.read_exact(&mut buf)?;
+		},
+		0x04 => { let mut buf = [0u8; 16 + 2]; socket.read_exact(&mut buf)?; },
+		other => return Err(PuppyLogError::new(&format!("socks5: unsupported bound address type {}", other))),
+	}
+	Ok(())
+}
+
+fn connect_tls(tls: &TlsConfig, host: &str, socket: TcpStream) -> Result<MaybeTlsStream<TcpStream>, PuppyLogError> {
+	match tls.backend {
+		TlsBackend::NativeTls => connect_native_tls(tls, host, socket),
+		TlsBackend::Rustls => connect_rustls(tls, host, socket),
+	}
+}
+
+fn connect_native_tls(tls: &TlsConfig, host: &str, socket: TcpStream) -> Result<MaybeTlsStream<TcpStream>, PuppyLogError> {
+	let mut builder = TlsConnector::builder();
+	builder.danger_accept_invalid_certs(tls.danger_accept_invalid_certs);
+	if let Some(pem) = &tls.root_cert_pem {
+		let cert = native_tls::Certificate::from_pem(pem)
+			.map_err(|e| PuppyLogError::new(&format!("invalid root_cert_pem: {}", e)))?;
+		builder.add_root_certificate(cert);
+	}
+	if let Some((cert_pem, key_pem)) = &tls.client_identity_pem {
+		let identity = native_tls::Identity::from_pkcs8(cert_pem, key_pem)
+			.map_err(|e| PuppyLogError::new(&format!("invalid client_identity: {}", e)))?;
+		builder.identity(identity);
+	}
+	let connector = builder.build()
+		.map_err(|e| PuppyLogError::new(&format!("Failed to create TlsConnector: {}", e)))?;
+	let stream = connector.connect(host, socket)
+		.map_err(|e| PuppyLogError::new(&format!("TLS handshake failed: {}", e)))?;
+	Ok(MaybeTlsStream::NativeTls(stream))
+}
+
+fn connect_rustls(tls: &TlsConfig, host: &str, socket: TcpStream) -> Result<MaybeTlsStream<TcpStream>, PuppyLogError> {
+	let mut roots = rustls::RootCertStore::empty();
+	if let Some(pem) = &tls.root_cert_pem {
+		for cert in rustls_pemfile::certs(&mut pem.as_slice()) {
+			let cert = cert.map_err(|e| PuppyLogError::new(&format!("invalid root_cert_pem: {}", e)))?;
+			roots.add(cert).map_err(|e| PuppyLogError::new(&format!("invalid root_cert_pem: {}", e)))?;
+		}
+	}
+	let builder = rustls::ClientConfig::builder().with_root_certificates(roots);
+	let config = if let Some((cert_pem, key_pem)) = &tls.client_identity_pem {
+		let certs = rustls_pemfile::certs(&mut cert_pem.as_slice())
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|e| PuppyLogError::new(&format!("invalid client_identity cert: {}", e)))?;
+		let key = rustls_pemfile::pkcs8_private_keys(&mut key_pem.as_slice())
+			.next()
+			.ok_or_else(|| PuppyLogError::new("no private key found in client_identity key"))?
+			.map_err(|e| PuppyLogError::new(&format!("invalid client_identity key: {}", e)))?;
+		builder.with_client_auth_cert(certs, key.into())
+			.map_err(|e| PuppyLogError::new(&format!("invalid client_identity: {}", e)))?
+	} else {
+		builder.with_no_client_auth()
+	};
+	let server_name = rustls::pki_types::ServerName::try_from(host.to_string())
+		.map_err(|e| PuppyLogError::new(&format!("invalid host for TLS: {}", e)))?;
+	let conn = rustls::ClientConnection::new(Arc::new(config), server_name)
+		.map_err(|e| PuppyLogError::new(&format!("TLS handshake failed: {}", e)))?;
+	Ok(MaybeTlsStream::Rustls(rustls::StreamOwned::new(conn, socket)))
+}
+
+/// Frames `payload` as a single gRPC message: a one-byte "not compressed"
+/// flag followed by the payload's length as a 4-byte big-endian integer, per
+/// the gRPC wire format used by `OtlpTransport::Grpc`.
+fn grpc_frame(payload: &[u8]) -> Vec<u8> {
+	let mut framed = Vec::with_capacity(5 + payload.len());
+	framed.push(0);
+	framed.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+	framed.extend_from_slice(payload);
+	framed
+}
+
+/// POSTs one OTLP protobuf batch to `cfg.endpoint`, opening a fresh
+/// TCP/TLS connection per flush rather than keeping one alive — `.otlp()`
+/// batches are seconds apart at most, so the extra handshake is cheap next
+/// to the complexity of sharing connection state with the websocket path
+/// above. Best-effort: a failed export is logged and dropped, same as a
+/// filtered-out entry, rather than retried or spooled like the `log_server`
+/// path.
+fn send_otlp_batch(cfg: &OtlpConfig, entries: &[LogEntry]) -> Result<(), PuppyLogError> {
+	let body = crate::otlp::to_otlp_protobuf(entries);
+	let https = matches!(cfg.endpoint.scheme_str(), Some("https") | Some("grpcs"));
+	let port = cfg.endpoint.port().map(|p| p.as_u16()).unwrap_or(if https { 443 } else { 80 });
+	let host = cfg.endpoint.host().ok_or_else(|| PuppyLogError::new("otlp endpoint has no host"))?;
+	let socket = TcpStream::connect((host, port))?;
+	let mut stream = if https {
+		connect_tls(&TlsConfig::default(), host, socket)?
+	} else {
+		MaybeTlsStream::Plain(socket)
+	};
+
+	let (path, content_type, payload) = match cfg.transport {
+		OtlpTransport::HttpProtobuf => {
+			let path = cfg.endpoint.path_and_query().map(|p| p.as_str()).filter(|p| *p != "/" && !p.is_empty());
+			(path.unwrap_or("/v1/logs").to_string(), "application/x-protobuf", body)
+		},
+		OtlpTransport::Grpc => (
+			"/opentelemetry.proto.collector.logs.v1.LogsService/Export".to_string(),
+			"application/grpc",
+			grpc_frame(&body),
+		),
+	};
+
+	let mut request = format!(
+		"POST {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: {content_type}\r\nContent-Length: {len}\r\nConnection: close\r\n",
+		path = path,
+		host = host,
+		content_type = content_type,
+		len = payload.len(),
+	);
+	for (key, value) in &cfg.headers {
+		request.push_str(&format!("{}: {}\r\n", key, value));
+	}
+	request.push_str("\r\n");
+	stream.write_all(request.as_bytes())?;
+	stream.write_all(&payload)?;
+
+	let mut reader = BufReader::new(stream);
+	let mut status_line = String::new();
+	reader.read_line(&mut status_line)?;
+	if !status_line.contains(" 200 ") && !status_line.contains(" 204 ") {
+		return Err(PuppyLogError::new(&format!("otlp export failed: {}", status_line.trim())));
+	}
+	Ok(())
+}
+
 fn worker(rx: Receiver<WorkerMessage>, builder: PuppylogBuilder) {
 	let url = match &builder.log_server {
 		Some(url) => url.clone(),
@@ -40,24 +403,68 @@ fn worker(rx: Receiver<WorkerMessage>, builder: PuppylogBuilder) {
 	let mut client: Option<WebSocket<MaybeTlsStream<TcpStream>>> = None;
 	let mut logquery: Option<QueryAst> = None;
 	let mut connect_timer = Instant::now();
+	// Consecutive failed-or-not-yet-attempted reconnects since the last
+	// successful handshake, driving the exponential backoff below. Reset to
+	// 0 as soon as `client = Some(c)`.
+	let mut retry_attempt: u32 = 0;
+	// `LogBuffer::new` already opens the configured folder (if any) and
+	// rehydrates whatever spilled to disk on a previous run. It's also the
+	// send queue itself now: entries get serialized straight into it below,
+	// and it's what makes queued-but-unsent batches crash-safe (they spool
+	// to rotating files under `log_folder`, bounded by `max_file_size`/
+	// `max_file_count`) instead of only ever living in this process's
+	// memory, as `queue: VecDeque<Bytes>` used to.
 	let mut buffer = LogBuffer::new(&builder);
-	if let Some(path) = &builder.log_folder {
-		buffer.set_folder_path(&builder);
-	}
 	let mut send_timer = Instant::now();
 	let mut serialize_buffer = Vec::with_capacity(builder.max_buffer_size);
-	let mut queue = VecDeque::new();
+	// A chunk pulled from `buffer` but not yet acknowledged by the server:
+	// held here (instead of immediately truncated) so a failed send retries
+	// the same bytes next tick rather than losing them or skipping ahead to
+	// a fresher chunk out of order.
+	let mut pending_chunk: Option<Bytes> = None;
+	// How many entries the active `logquery` has dropped since the last
+	// report, surfaced through `internal_logging` so an operator can see a
+	// tightened server-side query actually taking effect without having to
+	// restart the client.
+	let mut filtered_out: u64 = 0;
+	// Heartbeat: when we last sent a `Ping` (`None` until the first one goes
+	// out on a given connection) and whether we're still waiting on its
+	// `Pong`. A `Pong` that doesn't show up within `heartbeat_timeout` means
+	// the socket is dead but not closed (NAT timeout, half-open connection),
+	// so we force a reconnect instead of silently piling up unsent batches.
+	let mut last_ping_sent: Option<Instant> = None;
+	let mut awaiting_pong = false;
+	// Entries awaiting their next `.otlp()` flush, independent of the
+	// `log_server` path above (and of `logquery`, since the OTLP collector
+	// has no server-pushed query of its own to filter against).
+	let mut otlp_batch: Vec<LogEntry> = Vec::new();
+	let mut otlp_flush_timer = Instant::now();
 
 	'main: loop {
 		loop {
 			match rx.recv_timeout(Duration::from_millis(100)) {
 				Ok(WorkerMessage::LogEntry(entry)) => {
-					// if let Some(q) = &logquery {
-					// 	if let Ok(true) = check_expr(&q.root, &entry) {
-					// 		entry.serialize(&mut buffer).unwrap_or_default();
-					// 	}
-					// }
-					entry.serialize(&mut serialize_buffer).unwrap_or_default();
+					// No query set forwards everything, same as before the
+					// worker could receive `QueryChanged`. `logquery` is only
+					// ever swapped wholesale (below, between batches), so a
+					// batch always filters against one consistent query.
+					let matches = match &logquery {
+						Some(q) => {
+							let tz = q
+								.tz_offset
+								.unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
+							check_expr(&q.root, &entry, &tz).unwrap_or(false)
+						}
+						None => true,
+					};
+					if matches {
+						entry.serialize(&mut serialize_buffer).unwrap_or_default();
+					} else {
+						filtered_out += 1;
+					}
+					if builder.otlp.is_some() {
+						otlp_batch.push(entry);
+					}
 					if serialize_buffer.len() > builder.max_buffer_size {
 						println!("max serialize buffer size reached");
 						break;
@@ -85,10 +492,29 @@ fn worker(rx: Receiver<WorkerMessage>, builder: PuppylogBuilder) {
 		}
 
 		if serialize_buffer.len() > 10 {
-			queue.push_back(Bytes::copy_from_slice(&serialize_buffer));
+			if let Err(err) = buffer.write_all(&serialize_buffer) {
+				eprintln!("failed to spool log batch: {}", err);
+			}
 			serialize_buffer.clear();
 		}
 
+		if builder.internal_logging && filtered_out > 0 {
+			println!("query filter dropped {} entries this batch", filtered_out);
+			filtered_out = 0;
+		}
+
+		if let Some(otlp) = &builder.otlp {
+			let due = otlp_batch.len() >= otlp.max_batch_records
+				|| (!otlp_batch.is_empty() && otlp_flush_timer.elapsed() > otlp.flush_interval);
+			if due {
+				if let Err(e) = send_otlp_batch(otlp, &otlp_batch) {
+					eprintln!("otlp export failed: {}", e);
+				}
+				otlp_batch.clear();
+				otlp_flush_timer = Instant::now();
+			}
+		}
+
 		let mut client_broken = false;
 		match &mut client {
 			Some(c) => {
@@ -117,28 +543,91 @@ fn worker(rx: Receiver<WorkerMessage>, builder: PuppylogBuilder) {
 							client_broken = true;
 							break;
 						},
+						Message::Ping(payload) => {
+							// tungstenite queues an auto-pong on `read()`, but
+							// only a subsequent `flush()` actually puts it on
+							// the wire, so reply explicitly rather than
+							// relying on that to happen before we next block.
+							match c.send(Message::Pong(payload)) {
+								Ok(_) => {
+									if let Err(e) = c.flush() {
+										eprintln!("Failed to flush pong: {}", e);
+										client_broken = true;
+									}
+								},
+								Err(e) => {
+									eprintln!("Failed to send pong: {}", e);
+									client_broken = true;
+								}
+							}
+						},
+						Message::Pong(_) => {
+							awaiting_pong = false;
+						},
 						msg => {
 							println!("unhandled msg: {:?}", msg);
 						}
 					}
 				}
 
+				if !client_broken {
+					match last_ping_sent {
+						Some(sent) if awaiting_pong => {
+							if sent.elapsed() > builder.heartbeat_timeout {
+								eprintln!("heartbeat timed out, no pong received");
+								client_broken = true;
+							}
+						},
+						Some(sent) => {
+							if sent.elapsed() > builder.heartbeat_interval {
+								match c.send(Message::Ping(Bytes::new())) {
+									Ok(_) => {
+										if let Err(e) = c.flush() {
+											eprintln!("Failed to flush ping: {}", e);
+											client_broken = true;
+										}
+										last_ping_sent = Some(Instant::now());
+										awaiting_pong = true;
+									},
+									Err(e) => {
+										eprintln!("Failed to send ping: {}", e);
+										client_broken = true;
+									}
+								}
+							}
+						},
+						None => {
+							last_ping_sent = Some(Instant::now());
+						}
+					}
+				}
+
 				send_timer = Instant::now();
-				if let Some(batch) = queue.pop_front() {
-					match c.send(Message::Binary(batch)) {
-						Ok(_) => { serialize_buffer.clear(); },
+				let chunk = pending_chunk.take().or_else(|| buffer.next_chunk());
+				if let Some(batch) = chunk {
+					let batch_len = batch.len();
+					match c.send(Message::Binary(batch.clone())) {
+						Ok(_) => {
+							// Only truncates the on-disk spool if this chunk
+							// actually came from it; an in-memory chunk was
+							// never written there in the first place.
+							buffer.ack_chunk(batch_len);
+						},
 						Err(e) => {
 							eprintln!("Failed to send message: {}", e);
 							client_broken = true;
+							pending_chunk = Some(batch);
 						}
 					};
 				}
 			},
 			None => {
-				if connect_timer.elapsed().as_secs() < 1 {
+				let delay = backoff_delay(retry_attempt, builder.reconnect_base_delay, builder.reconnect_max_delay);
+				if connect_timer.elapsed() < delay {
 					continue;
 				}
 				connect_timer = Instant::now();
+				retry_attempt = retry_attempt.saturating_add(1);
 
 				let https = match &url.scheme() {
 					Some(scheme) => match scheme.as_str() {
@@ -160,38 +649,43 @@ fn worker(rx: Receiver<WorkerMessage>, builder: PuppylogBuilder) {
 					None => if https { 443 } else { 80 }
 				};
 				let host = url.host().ok_or(PuppyLogError::new("no host in url")).unwrap();
-				let host = format!("{}:{}", host, port);
-				let socket = match TcpStream::connect(host) {
+				let addr = format!("{}:{}", host, port);
+				let proxy = resolve_proxy(&builder, &url);
+				let connected = match &proxy {
+					Some(p) => connect_via_proxy(p, host, port),
+					None => TcpStream::connect(&addr).map_err(PuppyLogError::from),
+				};
+				let socket = match connected {
 					Ok(socket) => socket,
 					Err(e) => {
 						eprintln!("Failed to connect: {}", e);
 						continue;
 					}
 				};
+				if proxy.is_some() {
+					println!("tcp tunnel established through proxy");
+				}
 				socket.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
 				println!("tcp socket connected");
 				let stream = if https {
-					let connector = match  TlsConnector::builder().build() {
-						Ok(c) => c,
-						Err(_) => {
-							eprintln!("Failed to create Tlsconnector");
+					match connect_tls(&builder.tls, &url.host().unwrap(), socket) {
+						Ok(s) => {
+							println!("tls connected");
+							s
+						},
+						Err(e) => {
+							eprintln!("Failed to connect: {}", e);
 							continue;
 						}
-					};
-					let stream = match connector.connect(&url.host().unwrap(), socket) {
-						Ok(s) => s,
-						Err(_) => {
-							eprintln!("Failed to connect");
-							continue;
-						},
-					};
-					println!("tls connected");
-					MaybeTlsStream::NativeTls(stream)
+					}
 				}
 				else { MaybeTlsStream::Plain(socket) };
 				println!("creating ws client addr: {}", url);
-				let req = ClientRequestBuilder::new(url.clone())
+				let mut req = ClientRequestBuilder::new(url.clone())
 					.with_header("Authorization", builder.authorization.clone().unwrap_or_default());
+				for (key, value) in &builder.headers {
+					req = req.with_header(key, value);
+				}
 				let c = match client_with_config(req, stream, None) {
 					Ok((c, _)) => c,
 					Err(e) => {
@@ -201,11 +695,14 @@ fn worker(rx: Receiver<WorkerMessage>, builder: PuppylogBuilder) {
 				};
 				println!("connected");
 				client = Some(c);
+				retry_attempt = 0;
 			},
 		};
 
 		if client_broken {
 			client = None;
+			last_ping_sent = None;
+			awaiting_pong = false;
 		}
 	}
 
@@ -285,10 +782,10 @@ impl log::Log for PuppylogClient {
 			let mut props = self.props.clone();
 			props.push(Prop {
 				key: "module".to_string(),
-				value: record.target().to_string(),
+				value: record.target().to_string().into(),
 			});
 			let entry = LogEntry {
-				version: 1,
+				version: 2,
 				level,
 				timestamp: Utc::now(),
 				random: 0,
@@ -333,6 +830,9 @@ pub struct PuppylogBuilder {
 	pub chunk_size: usize,
 	pub max_file_count: usize,
 	pub max_file_size: usize,
+	/// zstd level used to compress sealed chunks before shipping/spilling
+	/// them; `0` disables compression (chunks are stored as-is).
+	pub chunk_compression_level: i32,
 	pub min_buffer_size: u64,
 	pub max_buffer_size: usize,
 	pub max_batch_size: u64,
@@ -340,10 +840,38 @@ pub struct PuppylogBuilder {
 	pub log_folder: Option<PathBuf>,
 	pub log_server: Option<Uri>,
 	pub authorization: Option<String>,
+	/// Extra headers applied to the WebSocket upgrade request alongside
+	/// `Authorization`, in the order they were added via `header`. Lets a
+	/// single client carry a tenant ID, API key, or tracing header a
+	/// multi-tenant log backend's routing layer requires.
+	pub headers: Vec<(String, String)>,
 	pub log_stdout: bool,
 	pub level_filter: Level,
 	pub props: Vec<Prop>,
 	pub internal_logging: bool,
+	/// How often the worker pings the server on an idle connection to detect
+	/// a dead-but-not-closed socket (NAT timeout, half-open connection).
+	pub heartbeat_interval: Duration,
+	/// How long to wait for a `Pong` after sending a `Ping` before treating
+	/// the connection as broken and reconnecting.
+	pub heartbeat_timeout: Duration,
+	/// TLS backend/trust material for `wss://` connections. Defaults to
+	/// `native_tls` with the system root store and no client certificate.
+	pub tls: TlsConfig,
+	/// Delay before the first reconnect attempt; doubles on each further
+	/// consecutive failure up to `reconnect_max_delay`.
+	pub reconnect_base_delay: Duration,
+	/// Upper bound on the reconnect backoff delay, regardless of how many
+	/// consecutive attempts have failed.
+	pub reconnect_max_delay: Duration,
+	/// Forward proxy to tunnel the log-server connection through. Left at
+	/// its default, `resolve_proxy` falls back to
+	/// `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY`.
+	pub proxy: ProxyConfig,
+	/// Secondary OTLP logs export, fed from the same entries as `log_server`.
+	/// `None` (the default) means `.otlp()` was never called and no export
+	/// happens.
+	pub otlp: Option<OtlpConfig>,
 }
 
 impl PuppylogBuilder {
@@ -352,6 +880,7 @@ impl PuppylogBuilder {
 			chunk_size: 4096,
 			max_file_count: 5,
 			max_file_size: 1024 * 1024 * 10,
+			chunk_compression_level: 3,
 			min_buffer_size: 1024,
 			max_buffer_size: 1024 * 1024,
 			max_batch_size: 1024 * 1024,
@@ -360,9 +889,17 @@ impl PuppylogBuilder {
 			log_server: None,
 			log_stdout: true,
 			authorization: None,
+			headers: Vec::new(),
 			level_filter: Level::Info,
 			props: Vec::new(),
 			internal_logging: false,
+			heartbeat_interval: Duration::from_secs(30),
+			heartbeat_timeout: Duration::from_secs(10),
+			tls: TlsConfig::default(),
+			reconnect_base_delay: Duration::from_secs(1),
+			reconnect_max_delay: Duration::from_secs(60),
+			proxy: ProxyConfig::default(),
+			otlp: None,
 		}
 	}
 
@@ -382,6 +919,13 @@ impl PuppylogBuilder {
 		self
 	}
 
+	/// Add an extra header to the WebSocket upgrade request, alongside
+	/// `Authorization`. Can be called multiple times to add several headers.
+	pub fn header(mut self, key: &str, value: &str) -> Self {
+		self.headers.push((key.to_string(), value.to_string()));
+		self
+	}
+
 	pub fn level(mut self, level: Level) -> Self {
 		self.level_filter = level;
 		self
@@ -395,7 +939,7 @@ impl PuppylogBuilder {
 	pub fn prop(mut self, key: &str, value: &str) -> Self {
 		self.props.push(Prop {
 			key: key.to_string(),
-			value: value.to_string(),
+			value: value.to_string().into(),
 		});
 		self
 	}
@@ -406,6 +950,117 @@ impl PuppylogBuilder {
 		self
 	}
 
+	/// Ping the server every `interval` on an otherwise idle connection, and
+	/// reconnect if a `Pong` doesn't arrive within `timeout`.
+	pub fn heartbeat(mut self, interval: Duration, timeout: Duration) -> Self {
+		self.heartbeat_interval = interval;
+		self.heartbeat_timeout = timeout;
+		self
+	}
+
+	/// Select which TLS stack `worker` uses for `wss://` connections.
+	pub fn tls_backend(mut self, backend: TlsBackend) -> Self {
+		self.tls.backend = backend;
+		self
+	}
+
+	/// Trust this PEM-encoded CA certificate for the `wss://` connection, so
+	/// a server behind a private CA doesn't need `danger_accept_invalid_certs`.
+	pub fn root_cert_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+		self.tls.root_cert_pem = Some(pem.into());
+		self
+	}
+
+	/// Present this PEM-encoded client certificate + private key for mTLS.
+	pub fn client_identity(mut self, cert_pem: impl Into<Vec<u8>>, key_pem: impl Into<Vec<u8>>) -> Self {
+		self.tls.client_identity_pem = Some((cert_pem.into(), key_pem.into()));
+		self
+	}
+
+	/// Skip certificate verification on the `NativeTls` backend. Only ever
+	/// useful against a known self-signed dev server.
+	pub fn danger_accept_invalid_certs(mut self) -> Self {
+		self.tls.danger_accept_invalid_certs = true;
+		self
+	}
+
+	/// Set the reconnect backoff range: `base` before the first retry,
+	/// doubling on each further consecutive failure up to `max`.
+	pub fn reconnect_backoff(mut self, base: Duration, max: Duration) -> Self {
+		self.reconnect_base_delay = base;
+		self.reconnect_max_delay = max;
+		self
+	}
+
+	/// Tunnel the log-server connection through this forward proxy instead of
+	/// connecting to it directly. `url`'s scheme selects the tunneling
+	/// protocol: `socks5://` for a SOCKS5 handshake, anything else for an
+	/// HTTP `CONNECT` tunnel.
+	pub fn proxy(mut self, url: &str) -> Result<Self, PuppyLogError> {
+		let url = Uri::from_str(url).map_err(|e| PuppyLogError::new(&e.to_string()))?;
+		let kind = match url.scheme().map(|s| s.as_str()) {
+			Some("socks5") => ProxyKind::Socks5,
+			_ => ProxyKind::Http,
+		};
+		self.proxy.url = Some(url);
+		self.proxy.kind = kind;
+		Ok(self)
+	}
+
+	/// Credentials for the configured `proxy` (Basic auth for HTTP `CONNECT`,
+	/// username/password negotiation for SOCKS5).
+	pub fn proxy_auth(mut self, username: &str, password: &str) -> Self {
+		self.proxy.username = Some(username.to_string());
+		self.proxy.password = Some(password.to_string());
+		self
+	}
+
+	/// Fan out every log entry to an OTLP logs collector at `endpoint` (e.g.
+	/// `http://localhost:4318/v1/logs`), alongside whatever `.server()` is
+	/// also configured. Defaults to OTLP/HTTP protobuf, flushing once 512
+	/// entries have queued or every 5 seconds, whichever comes first; adjust
+	/// with `otlp_transport`/`otlp_batch`, add auth with `otlp_header`.
+	pub fn otlp(mut self, endpoint: &str) -> Result<Self, PuppyLogError> {
+		let endpoint = Uri::from_str(endpoint).map_err(|e| PuppyLogError::new(&e.to_string()))?;
+		self.otlp = Some(OtlpConfig {
+			endpoint,
+			transport: OtlpTransport::default(),
+			headers: Vec::new(),
+			max_batch_records: 512,
+			flush_interval: Duration::from_secs(5),
+		});
+		Ok(self)
+	}
+
+	/// Select the wire transport `.otlp()` exports use. No-op unless `.otlp()`
+	/// was already called.
+	pub fn otlp_transport(mut self, transport: OtlpTransport) -> Self {
+		if let Some(otlp) = &mut self.otlp {
+			otlp.transport = transport;
+		}
+		self
+	}
+
+	/// Add an extra header (e.g. `Authorization`) to `.otlp()` export
+	/// requests. No-op unless `.otlp()` was already called.
+	pub fn otlp_header(mut self, key: &str, value: &str) -> Self {
+		if let Some(otlp) = &mut self.otlp {
+			otlp.headers.push((key.to_string(), value.to_string()));
+		}
+		self
+	}
+
+	/// Override the `.otlp()` flush thresholds: export once `max_records`
+	/// have queued, or every `interval`, whichever comes first. No-op unless
+	/// `.otlp()` was already called.
+	pub fn otlp_batch(mut self, max_records: usize, interval: Duration) -> Self {
+		if let Some(otlp) = &mut self.otlp {
+			otlp.max_batch_records = max_records;
+			otlp.flush_interval = interval;
+		}
+		self
+	}
+
 	pub fn build(self) -> Result<&'static mut PuppylogClient, SetLoggerError> {
 		let logger = PuppylogClient::new(self);
 		unsafe {
@@ -0,0 +1,195 @@
+use crate::{LogEntry, LogLevel};
+
+// Maps `LogEntry` onto the OpenTelemetry logs data model
+// (https://github.com/open-telemetry/opentelemetry-proto, logs/v1) for
+// `PuppylogBuilder::otlp`, the same mapping `logen`'s exporter uses for
+// generated traffic.
+
+/// OTLP severityNumber/severityText for a LogLevel.
+pub fn severity(level: &LogLevel) -> (i32, &'static str) {
+	match level {
+		LogLevel::Trace => (1, "TRACE"),
+		LogLevel::Debug => (5, "DEBUG"),
+		LogLevel::Info => (9, "INFO"),
+		LogLevel::Warn => (13, "WARN"),
+		LogLevel::Error => (17, "ERROR"),
+		LogLevel::Fatal => (21, "FATAL"),
+		LogLevel::Uknown => (0, "UNSPECIFIED"),
+	}
+}
+
+fn time_unix_nanos(entry: &LogEntry) -> u64 {
+	(entry.timestamp.timestamp_micros() as u64).saturating_mul(1000)
+}
+
+// ---- Protobuf encoding ----
+//
+// Hand-rolled rather than generated from the .proto files (this tree has no
+// build.rs/protoc step), but follows the wire-stable opentelemetry-proto
+// field numbers exactly, so the bytes are valid input for any OTLP/HTTP
+// collector expecting `application/x-protobuf`.
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+	loop {
+		let mut byte = (value & 0x7f) as u8;
+		value >>= 7;
+		if value != 0 {
+			byte |= 0x80;
+		}
+		buf.push(byte);
+		if value == 0 {
+			break;
+		}
+	}
+}
+
+fn write_tag(buf: &mut Vec<u8>, field_num: u32, wire_type: u8) {
+	write_varint(buf, ((field_num as u64) << 3) | wire_type as u64);
+}
+
+fn write_len_delimited(buf: &mut Vec<u8>, field_num: u32, data: &[u8]) {
+	write_tag(buf, field_num, 2);
+	write_varint(buf, data.len() as u64);
+	buf.extend_from_slice(data);
+}
+
+fn write_string_field(buf: &mut Vec<u8>, field_num: u32, value: &str) {
+	write_len_delimited(buf, field_num, value.as_bytes());
+}
+
+fn write_varint_field(buf: &mut Vec<u8>, field_num: u32, value: u64) {
+	write_tag(buf, field_num, 0);
+	write_varint(buf, value);
+}
+
+fn write_fixed64_field(buf: &mut Vec<u8>, field_num: u32, value: u64) {
+	write_tag(buf, field_num, 1);
+	buf.extend_from_slice(&value.to_le_bytes());
+}
+
+// common.v1.AnyValue { oneof value { string string_value = 1; ... } }
+fn encode_any_value_string(value: &str) -> Vec<u8> {
+	let mut buf = Vec::new();
+	write_string_field(&mut buf, 1, value);
+	buf
+}
+
+// common.v1.KeyValue { string key = 1; AnyValue value = 2; }
+fn encode_key_value(key: &str, value: &str) -> Vec<u8> {
+	let mut buf = Vec::new();
+	write_string_field(&mut buf, 1, key);
+	write_len_delimited(&mut buf, 2, &encode_any_value_string(value));
+	buf
+}
+
+// logs.v1.LogRecord { fixed64 time_unix_nano = 1; SeverityNumber severity_number = 2;
+//                     string severity_text = 3; AnyValue body = 5; repeated KeyValue attributes = 6; }
+fn encode_log_record(entry: &LogEntry, resource_keys: &[&str]) -> Vec<u8> {
+	let (severity_number, severity_text) = severity(&entry.level);
+	let mut buf = Vec::new();
+	write_fixed64_field(&mut buf, 1, time_unix_nanos(entry));
+	write_varint_field(&mut buf, 2, severity_number as u64);
+	write_string_field(&mut buf, 3, severity_text);
+	write_len_delimited(&mut buf, 5, &encode_any_value_string(&entry.msg));
+	for p in &entry.props {
+		// `device`/`app` describe the resource emitting the batch, not this
+		// one record, so they're lifted into the surrounding ResourceLogs
+		// instead of repeated on every LogRecord.
+		if resource_keys.contains(&p.key.as_str()) {
+			continue;
+		}
+		write_len_delimited(&mut buf, 6, &encode_key_value(&p.key, &p.value.to_string()));
+	}
+	buf
+}
+
+// resource.v1.Resource { repeated KeyValue attributes = 1; }
+fn encode_resource(attrs: &[(String, String)]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	for (k, v) in attrs {
+		write_len_delimited(&mut buf, 1, &encode_key_value(k, v));
+	}
+	buf
+}
+
+// logs.v1.ScopeLogs { InstrumentationScope scope = 1; repeated LogRecord log_records = 2; }
+fn encode_scope_logs(entries: &[LogEntry], resource_keys: &[&str]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	for entry in entries {
+		write_len_delimited(&mut buf, 2, &encode_log_record(entry, resource_keys));
+	}
+	buf
+}
+
+// logs.v1.ResourceLogs { Resource resource = 1; repeated ScopeLogs scope_logs = 2; }
+fn encode_resource_logs(entries: &[LogEntry], resource_attrs: &[(String, String)], resource_keys: &[&str]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	write_len_delimited(&mut buf, 1, &encode_resource(resource_attrs));
+	write_len_delimited(&mut buf, 2, &encode_scope_logs(entries, resource_keys));
+	buf
+}
+
+/// `device`/`app` props are pulled out of every entry in `entries` and
+/// merged (last write wins) into the batch's Resource attributes instead of
+/// being repeated as a KeyValue on each LogRecord.
+const RESOURCE_PROP_KEYS: [&str; 2] = ["device", "app"];
+
+// logs.v1.LogsData { repeated ResourceLogs resource_logs = 1; }
+pub fn to_otlp_protobuf(entries: &[LogEntry]) -> Vec<u8> {
+	let mut resource_attrs = Vec::new();
+	for entry in entries {
+		for p in &entry.props {
+			if RESOURCE_PROP_KEYS.contains(&p.key.as_str()) {
+				resource_attrs.retain(|(k, _)| k != &p.key);
+				resource_attrs.push((p.key.clone(), p.value.to_string()));
+			}
+		}
+	}
+	let mut buf = Vec::new();
+	write_len_delimited(&mut buf, 1, &encode_resource_logs(entries, &resource_attrs, &RESOURCE_PROP_KEYS));
+	buf
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Prop;
+	use chrono::Utc;
+
+	fn entry(msg: &str, level: LogLevel, props: Vec<Prop>) -> LogEntry {
+		LogEntry { version: 2, random: 0, timestamp: Utc::now(), level, props, msg: msg.to_string() }
+	}
+
+	#[test]
+	fn device_and_app_props_move_to_resource_not_record_attributes() {
+		let entries = vec![entry(
+			"hello",
+			LogLevel::Info,
+			vec![
+				Prop { key: "device".into(), value: "sensor-1".into() },
+				Prop { key: "app".into(), value: "ingest".into() },
+				Prop { key: "module".into(), value: "main".into() },
+			],
+		)];
+		let body = to_otlp_protobuf(&entries);
+		// Field 1 (resource_logs) length-delimited at the top level.
+		assert_eq!(body[0] >> 3, 1);
+		// `device`'s and `app`'s values land in the encoded bytes exactly once
+		// each (as resource attributes), not duplicated onto the log record.
+		assert_eq!(count_occurrences(&body, b"sensor-1"), 1);
+		assert_eq!(count_occurrences(&body, b"ingest"), 1);
+		assert_eq!(count_occurrences(&body, b"main"), 1);
+	}
+
+	fn count_occurrences(haystack: &[u8], needle: &[u8]) -> usize {
+		haystack.windows(needle.len()).filter(|w| *w == needle).count()
+	}
+
+	#[test]
+	fn severity_matches_otel_scale() {
+		assert_eq!(severity(&LogLevel::Debug).0, 5);
+		assert_eq!(severity(&LogLevel::Info).0, 9);
+		assert_eq!(severity(&LogLevel::Warn).0, 13);
+		assert_eq!(severity(&LogLevel::Error).0, 17);
+	}
+}
@@ -1,5 +1,7 @@
 use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc};
 
+use crate::query_parsing::Arith;
+use crate::query_parsing::ArithOp;
 use crate::query_parsing::Condition;
 use crate::query_parsing::Expr;
 use crate::query_parsing::Operator;
@@ -10,11 +12,19 @@ use crate::LogLevel;
 use crate::Prop;
 use regex::Regex;
 use std::collections::HashMap;
-use std::sync::{LazyLock, Mutex};
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock, Mutex};
 
 static REGEX_CACHE: LazyLock<Mutex<HashMap<String, Regex>>> =
 	LazyLock::new(|| Mutex::new(HashMap::new()));
 
+/// Compiles `pattern` at most once and reuses it for every subsequent
+/// `matches`/`not matches` condition that shares it, rather than
+/// recompiling per log line. A global pattern-keyed cache (vs. stashing the
+/// compiled `Regex` on the parsed `Condition` itself) also lets two
+/// unrelated conditions that happen to use the same pattern share one
+/// compile, and needs no `&mut` threading through `check_expr`'s otherwise
+/// read-only AST walk.
 fn cached_regex(pattern: &str) -> Result<Regex, regex::Error> {
 	let mut cache = REGEX_CACHE.lock().unwrap();
 	if let Some(re) = cache.get(pattern) {
@@ -25,6 +35,35 @@ fn cached_regex(pattern: &str) -> Result<Regex, regex::Error> {
 	Ok(re)
 }
 
+/// Walks `expr` and warms `REGEX_CACHE` for every `matches`/`not matches`
+/// pattern it contains, so `OptimizationLevel::Full` pays the compile cost
+/// once at parse time rather than on the first `check_expr` call per
+/// pattern. Invalid patterns are left for `check_expr` to report as a
+/// regular evaluation error; this pass only primes the cache.
+pub(crate) fn precompile_regexes(expr: &Expr) {
+	match expr {
+		Expr::Condition(cond) => {
+			precompile_regexes(&cond.left);
+			precompile_regexes(&cond.right);
+		}
+		Expr::And(left, right) | Expr::Or(left, right) => {
+			precompile_regexes(left);
+			precompile_regexes(right);
+		}
+		Expr::Not(inner) => precompile_regexes(inner),
+		Expr::FieldAccess(field_access) => precompile_regexes(&field_access.expr),
+		Expr::Arith(arith) => {
+			precompile_regexes(&arith.left);
+			precompile_regexes(&arith.right);
+		}
+		Expr::Coalesce(args) | Expr::Call { args, .. } => args.iter().for_each(precompile_regexes),
+		Expr::Value(Value::Regex(pattern)) => {
+			let _ = cached_regex(pattern);
+		}
+		Expr::Value(_) | Expr::Empty => {}
+	}
+}
+
 #[derive(Debug)]
 enum FieldType {
 	Timestamp,
@@ -48,7 +87,7 @@ fn find_field(v: &str, logline: &LogEntry) -> Option<FieldType> {
 
 	for prop in &logline.props {
 		if prop.key == v {
-			return Some(FieldType::Prop(prop.key.clone(), prop.value.clone()));
+			return Some(FieldType::Prop(prop.key.clone(), prop.value.to_string()));
 		}
 	}
 
@@ -109,6 +148,68 @@ fn cmp_semver_or_string(left: &str, right: &str, op: &Operator) -> bool {
 	semver_cmp(left, right, op).unwrap_or_else(|| magic_cmp(left, right, op))
 }
 
+/// Parses a stored (string) prop value as a number, the same coercion
+/// `cmp_numeric`/`cmp_numeric_int` use for numeric comparisons. Shared with
+/// the aggregation accumulator so `sum`/`avg`/`min`/`max` treat a prop's
+/// numeric-ness identically to a `duration_ms > 12.5`-style condition.
+fn parse_numeric(prop_val: &str) -> Option<f64> {
+	prop_val.parse::<f64>().ok()
+}
+
+/// Compare a stored (string) prop value against a query-side number, parsing
+/// the prop as a float so `duration_ms > 12.5` compares numerically instead
+/// of lexicographically. Props that don't parse as a number never match.
+fn cmp_numeric(prop_val: &str, query_num: f64, op: &Operator) -> bool {
+	match parse_numeric(prop_val) {
+		Some(num) => magic_cmp(num, query_num, op),
+		None => false,
+	}
+}
+
+/// Like `cmp_numeric`, but for a query-side `Value::Number` (always an
+/// exact `i64`): tries an exact `i64` parse of the prop value first so
+/// `duration_ms > 9007199254740993` (beyond `f64`'s 53-bit mantissa) still
+/// compares exactly, falling back to the float path for fractional prop
+/// values (`"150.0" > 99`).
+fn cmp_numeric_int(prop_val: &str, query_num: i64, op: &Operator) -> bool {
+	match prop_val.parse::<i64>() {
+		Ok(num) => magic_cmp(num, query_num, op),
+		Err(_) => cmp_numeric(prop_val, query_num as f64, op),
+	}
+}
+
+/// Stringifies a resolved field for a field-to-field comparison. `Prop`
+/// already carries its value as a string; the other variants don't, so this
+/// renders them the same way they're compared elsewhere (RFC 3339 for
+/// timestamps, `LogLevel`'s name for level).
+fn field_value_string(field: &FieldType, logline: &LogEntry, tz: &FixedOffset) -> String {
+	match field {
+		FieldType::Timestamp => logline.timestamp.with_timezone(tz).to_rfc3339(),
+		FieldType::Level => logline.level.to_string(),
+		FieldType::Msg => logline.msg.clone(),
+		FieldType::Prop(_, val) => val.clone(),
+	}
+}
+
+/// Compares two fields of the same log line against each other, e.g.
+/// `bytes_sent > bytes_received`: numeric coercion when both sides parse as
+/// numbers (so `"150" > "99"` compares as `150 > 99`, not lexicographically),
+/// falling back to `cmp_semver_or_string` otherwise.
+fn compare_fields(
+	left: &FieldType,
+	right: &FieldType,
+	op: &Operator,
+	logline: &LogEntry,
+	tz: &FixedOffset,
+) -> bool {
+	let left_val = field_value_string(left, logline, tz);
+	let right_val = field_value_string(right, logline, tz);
+	match (parse_numeric(&left_val), parse_numeric(&right_val)) {
+		(Some(l), Some(r)) => magic_cmp(l, r, op),
+		_ => cmp_semver_or_string(&left_val, &right_val, op),
+	}
+}
+
 fn any(
 	field: &FieldType,
 	values: &[Value],
@@ -149,6 +250,31 @@ fn does_field_match(
 		(FieldType::Timestamp, Value::Date(val), op) => {
 			Ok(magic_cmp(logline.timestamp.with_timezone(tz), *val, op))
 		}
+		// `timestamp in ((start1, end1), (start2, end2), ...)`: true if the
+		// log line's timestamp falls within any of the `(start, end)` range
+		// pairs, inclusive on both ends. `NotIn` is the complement: outside
+		// every range.
+		(FieldType::Timestamp, Value::List(ranges), Operator::In | Operator::NotIn) => {
+			let ts = logline.timestamp.with_timezone(tz);
+			let mut in_any_range = false;
+			for range in ranges {
+				let Value::List(bounds) = range else {
+					return Err(format!("Invalid timestamp range {:?}", range));
+				};
+				let [Value::Date(start), Value::Date(end)] = bounds.as_slice() else {
+					return Err(format!("Invalid timestamp range {:?}", range));
+				};
+				if ts >= *start && ts <= *end {
+					in_any_range = true;
+					break;
+				}
+			}
+			Ok(if *operator == Operator::In {
+				in_any_range
+			} else {
+				!in_any_range
+			})
+		}
 		(FieldType::Timestamp, _, _) => Err(format!("Invalid value for timestamp {:?}", value)),
 		(FieldType::Level, Value::String(val), op) => {
 			Ok(magic_cmp(&logline.level, &LogLevel::from_string(&val), op))
@@ -159,6 +285,10 @@ fn does_field_match(
 		}
 		(FieldType::Msg, Value::String(val), op) => Ok(cmp_semver_or_string(&logline.msg, val, op)),
 		(FieldType::Msg, Value::Number(n), op) => Ok(magic_cmp(&logline.msg, &n.to_string(), op)),
+		(FieldType::Msg, Value::Float(n), op) => Ok(magic_cmp(&logline.msg, &n.to_string(), op)),
+		(FieldType::Msg, Value::Bool(b), op) => Ok(magic_cmp(&logline.msg, &b.to_string(), op)),
+		(FieldType::Msg, Value::Duration(n), op) => Ok(magic_cmp(&logline.msg, &n.to_string(), op)),
+		(FieldType::Msg, Value::Bytes(n), op) => Ok(magic_cmp(&logline.msg, &n.to_string(), op)),
 		(FieldType::Msg, Value::Date(d), _) => Err(format!("Invalid value for msg {:?}", d)),
 		(FieldType::Prop(_, val1), Value::String(val2), Operator::Like) => {
 			Ok(val1.contains(&val2.to_string()))
@@ -182,8 +312,18 @@ fn does_field_match(
 			Ok(cmp_semver_or_string(val1, val2, op))
 		}
 		(FieldType::Prop(_, val1), Value::Number(val2), op) => {
+			Ok(cmp_numeric_int(val1, *val2, op))
+		}
+		(FieldType::Prop(_, val1), Value::Float(val2), op) => Ok(cmp_numeric(val1, *val2, op)),
+		(FieldType::Prop(_, val1), Value::Bool(val2), op) => {
 			Ok(magic_cmp(val1, &val2.to_string(), op))
 		}
+		(FieldType::Prop(_, val1), Value::Duration(val2), op) => {
+			Ok(cmp_numeric(val1, *val2 as f64, op))
+		}
+		(FieldType::Prop(_, val1), Value::Bytes(val2), op) => {
+			Ok(cmp_numeric(val1, *val2 as f64, op))
+		}
 		(FieldType::Prop(_, _), Value::Date(_), _) => todo!(),
 		(field_type, Value::List(vec), Operator::In) => {
 			any(field_type, vec, &Operator::Equal, logline, tz)
@@ -198,6 +338,86 @@ fn does_field_match(
 	}
 }
 
+/// Flatten a (possibly nested) `FieldAccess` chain such as `props.a.b` into
+/// its root field name plus the ordered list of trailing segments
+/// (`("props", ["a", "b"])`). `FieldAccess` nests with the outermost access
+/// last applied, so this walks down to the root `Value::String` first and
+/// pushes segments on the way back up.
+fn field_path(field_access: &FieldAccess) -> Option<(String, Vec<String>)> {
+	fn walk(expr: &Expr, segments: &mut Vec<String>) -> Option<String> {
+		match expr {
+			Expr::Value(Value::String(base)) => Some(base.clone()),
+			Expr::FieldAccess(inner) => {
+				let base = walk(inner.expr.as_ref(), segments)?;
+				segments.push(inner.field.clone());
+				Some(base)
+			}
+			_ => None,
+		}
+	}
+
+	let mut segments = Vec::new();
+	let base = walk(field_access.expr.as_ref(), &mut segments)?;
+	segments.push(field_access.field.clone());
+	Some((base, segments))
+}
+
+/// Resolve a nested field-access path against a `LogEntry`'s props, trying
+/// three strategies in order: (1) `props.a.b` looks for a prop literally
+/// keyed `"props.a.b"` (flattened-at-ingest props); (2) a trailing numeric
+/// segment (`tags.0`) instead looks up the parent path and indexes into it
+/// as a JSON array; (3) if neither matches, `base` itself is looked up and,
+/// if its value parses as JSON, the remaining segments walk down into it as
+/// object keys/array indices (structured-at-ingest props, e.g. a `http` prop
+/// holding `{"request":{"method":"GET"}}` resolves `http.request.method`).
+/// Returns `None` (rather than an error) when any segment is missing, so
+/// missing structured fields simply don't match.
+fn resolve_path(entry: &LogEntry, base: &str, segments: &[String]) -> Option<String> {
+	fn joined(base: &str, segments: &[String]) -> String {
+		std::iter::once(base)
+			.chain(segments.iter().map(String::as_str))
+			.collect::<Vec<_>>()
+			.join(".")
+	}
+
+	let full_path = joined(base, segments);
+	if let Some(prop) = entry.props.iter().find(|p| p.key == full_path) {
+		return Some(prop.value.to_string());
+	}
+
+	if let Some((last, parent_segments)) = segments.split_last() {
+		if let Ok(index) = last.parse::<usize>() {
+			let parent_path = joined(base, parent_segments);
+			if let Some(parent) = entry.props.iter().find(|p| p.key == parent_path) {
+				let array: Vec<serde_json::Value> = serde_json::from_str(&parent.value.to_string()).ok()?;
+				if let Some(item) = array.get(index) {
+					return Some(json_scalar_to_string(item));
+				}
+			}
+		}
+	}
+
+	let root_prop = entry.props.iter().find(|p| p.key == base)?;
+	let mut current: serde_json::Value = serde_json::from_str(&root_prop.value.to_string()).ok()?;
+	for segment in segments {
+		current = if let Ok(index) = segment.parse::<usize>() {
+			current.as_array()?.get(index)?.clone()
+		} else {
+			current.as_object()?.get(segment.as_str())?.clone()
+		};
+	}
+	Some(json_scalar_to_string(&current))
+}
+
+fn json_scalar_to_string(value: &serde_json::Value) -> String {
+	match value {
+		serde_json::Value::String(s) => s.clone(),
+		serde_json::Value::Bool(b) => b.to_string(),
+		serde_json::Value::Number(n) => n.to_string(),
+		other => other.to_string(),
+	}
+}
+
 fn check_field_access(
 	field_access: &FieldAccess,
 	right: &Expr,
@@ -205,82 +425,537 @@ fn check_field_access(
 	logline: &LogEntry,
 	tz: &FixedOffset,
 ) -> Result<bool, String> {
-	match field_access.expr.as_ref() {
-		Expr::Value(Value::String(obj)) => match obj.as_str() {
-			"timestamp" => {
-				let num = match right {
-					Expr::Value(Value::Number(num)) => *num as i32,
-					_ => return Err("Invalid value for timestamp field".to_string()),
+	let Some((base, segments)) = field_path(field_access) else {
+		return Err(format!("unsupported field access: {:?}", field_access));
+	};
+
+	if base == "timestamp" {
+		let num = match right {
+			Expr::Value(Value::Number(num)) => *num as i32,
+			_ => return Err("Invalid value for timestamp field".to_string()),
+		};
+
+		let [field] = segments.as_slice() else {
+			return Err(format!("Field not found: {}", segments.join(".")));
+		};
+
+		return match field.as_str() {
+			"year" => Ok(magic_cmp(
+				logline.timestamp.with_timezone(tz).year(),
+				num,
+				op,
+			)),
+			"month" => Ok(magic_cmp(
+				logline.timestamp.with_timezone(tz).month(),
+				num as u32,
+				op,
+			)),
+			"day" => Ok(magic_cmp(
+				logline.timestamp.with_timezone(tz).day(),
+				num as u32,
+				op,
+			)),
+			"hour" => Ok(magic_cmp(
+				logline.timestamp.with_timezone(tz).hour(),
+				num as u32,
+				op,
+			)),
+			"minute" => Ok(magic_cmp(
+				logline.timestamp.with_timezone(tz).minute(),
+				num as u32,
+				op,
+			)),
+			"second" => Ok(magic_cmp(
+				logline.timestamp.with_timezone(tz).second(),
+				num as u32,
+				op,
+			)),
+			_ => Err(format!("Field not found: {}", field)),
+		};
+	}
+
+	// Structured prop path, e.g. `props.a.b` or `tags.0 exists`.
+	if matches!(op, Operator::Exists) {
+		return Ok(resolve_path(logline, &base, &segments).is_some());
+	}
+	if matches!(op, Operator::NotExists) {
+		return Ok(resolve_path(logline, &base, &segments).is_none());
+	}
+
+	let right_val = match right {
+		Expr::Value(val) => val,
+		_ => return Err(format!("unsupported field access comparison: {:?}", right)),
+	};
+
+	let full_path = std::iter::once(base.as_str())
+		.chain(segments.iter().map(String::as_str))
+		.collect::<Vec<_>>()
+		.join(".");
+	match resolve_path(logline, &base, &segments) {
+		Some(resolved) => does_field_match(
+			&FieldType::Prop(full_path, resolved),
+			right_val,
+			op,
+			logline,
+			tz,
+		),
+		None => Ok(false),
+	}
+}
+
+/// Resolve an `Expr::Arith` operand (a literal, bare field name, field
+/// access, or nested arithmetic) to the number it represents, for
+/// comparisons like `bytes / 1024 > 500`.
+fn resolve_arith_operand(expr: &Expr, logline: &LogEntry, tz: &FixedOffset) -> Result<f64, String> {
+	match expr {
+		Expr::Value(Value::Number(n)) => Ok(*n as f64),
+		Expr::Value(Value::Float(n)) => Ok(*n),
+		Expr::Value(Value::Duration(n)) => Ok(*n as f64),
+		Expr::Value(Value::Bytes(n)) => Ok(*n as f64),
+		Expr::Value(Value::Date(date)) => Ok(date.timestamp_millis() as f64),
+		Expr::Value(Value::String(field)) => match find_field(field, logline) {
+			Some(FieldType::Prop(_, val)) => val
+				.parse::<f64>()
+				.map_err(|_| format!("Field '{}' is not numeric", field)),
+			Some(FieldType::Timestamp) => {
+				Ok(logline.timestamp.with_timezone(tz).timestamp_millis() as f64)
+			}
+			_ => Err(format!("Field '{}' is not numeric", field)),
+		},
+		Expr::FieldAccess(field_access) => {
+			let Some((base, segments)) = field_path(field_access) else {
+				return Err(format!("unsupported field access: {:?}", field_access));
+			};
+			if base == "timestamp" {
+				let [field] = segments.as_slice() else {
+					return Err(format!("Field not found: {}", segments.join(".")));
+				};
+				let dt = logline.timestamp.with_timezone(tz);
+				return match field.as_str() {
+					"year" => Ok(dt.year() as f64),
+					"month" => Ok(dt.month() as f64),
+					"day" => Ok(dt.day() as f64),
+					"hour" => Ok(dt.hour() as f64),
+					"minute" => Ok(dt.minute() as f64),
+					"second" => Ok(dt.second() as f64),
+					_ => Err(format!("Field not found: {}", field)),
 				};
+			}
+			let full_path = std::iter::once(base.as_str())
+				.chain(segments.iter().map(String::as_str))
+				.collect::<Vec<_>>()
+				.join(".");
+			resolve_path(logline, &base, &segments)
+				.ok_or_else(|| format!("Field not found: {}", full_path))?
+				.parse::<f64>()
+				.map_err(|_| format!("Field '{}' is not numeric", full_path))
+		}
+		Expr::Arith(arith) => evaluate_arith(arith, logline, tz),
+		other => Err(format!("Not a numeric expression: {:?}", other)),
+	}
+}
+
+fn evaluate_arith(arith: &Arith, logline: &LogEntry, tz: &FixedOffset) -> Result<f64, String> {
+	let left = resolve_arith_operand(&arith.left, logline, tz)?;
+	let right = resolve_arith_operand(&arith.right, logline, tz)?;
+	match arith.op {
+		ArithOp::Add => Ok(left + right),
+		ArithOp::Sub => Ok(left - right),
+		ArithOp::Mul => Ok(left * right),
+		ArithOp::Div if right != 0.0 => Ok(left / right),
+		ArithOp::Div => Err("division by zero".to_string()),
+		ArithOp::Mod if right != 0.0 => Ok(left % right),
+		ArithOp::Mod => Err("modulo by zero".to_string()),
+	}
+}
+
+/// SQL-style three-valued logic: a comparison against a field the log line
+/// doesn't have is neither true nor false but `Unknown`, and `And`/`Or`/`Not`
+/// combine it with Kleene logic rather than treating it as `false` outright.
+/// Both [`check_expr`] and [`check_expr_strict_null`] evaluate this way and
+/// collapse `Unknown` to `false` only at the top level; strict mode also lets
+/// `!= x` flip an `Unknown` to `true`, since a field that isn't there
+/// trivially isn't equal to `x`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tri {
+	True,
+	False,
+	Unknown,
+}
+
+impl Tri {
+	fn from_bool(b: bool) -> Tri {
+		if b {
+			Tri::True
+		} else {
+			Tri::False
+		}
+	}
+
+	fn not(self) -> Tri {
+		match self {
+			Tri::True => Tri::False,
+			Tri::False => Tri::True,
+			Tri::Unknown => Tri::Unknown,
+		}
+	}
+
+	fn and(self, other: Tri) -> Tri {
+		match (self, other) {
+			(Tri::False, _) | (_, Tri::False) => Tri::False,
+			(Tri::True, Tri::True) => Tri::True,
+			_ => Tri::Unknown,
+		}
+	}
+
+	fn or(self, other: Tri) -> Tri {
+		match (self, other) {
+			(Tri::True, _) | (_, Tri::True) => Tri::True,
+			(Tri::False, Tri::False) => Tri::False,
+			_ => Tri::Unknown,
+		}
+	}
+
+	/// Collapses to a plain bool once a top-level caller needs a yes/no
+	/// verdict: `Unknown` reads as "didn't match".
+	fn to_bool(self) -> bool {
+		matches!(self, Tri::True)
+	}
+}
+
+fn value_truthy(value: &Value) -> Result<bool, String> {
+	match value {
+		Value::String(value) => Ok(value != ""),
+		Value::Regex(_) => Ok(true),
+		Value::Number(value) => Ok(*value > 0),
+		Value::Float(value) => Ok(*value > 0.0),
+		Value::Bool(value) => Ok(*value),
+		Value::Duration(value) => Ok(*value > 0),
+		Value::Bytes(value) => Ok(*value > 0),
+		Value::Date(_) => Ok(true),
+		Value::List(_) => Err("This is not javascript".to_string()),
+	}
+}
+
+fn field_type_to_value(field_type: &FieldType, logline: &LogEntry) -> Value {
+	match field_type {
+		FieldType::Timestamp => Value::Date(logline.timestamp),
+		FieldType::Level => Value::String(logline.level.to_string()),
+		FieldType::Msg => Value::String(logline.msg.clone()),
+		FieldType::Prop(_, val) => Value::String(val.clone()),
+	}
+}
+
+/// Like `field_type_to_value`, but flattened to a plain `String` — what
+/// `Aggregator` needs both to bucket `group_by` fields (`Value` isn't
+/// `Hash`/`Eq`) and to feed a field's value through the numeric-coercion
+/// helpers the rest of this module already uses for comparisons.
+fn field_string(field: &str, logline: &LogEntry) -> Option<String> {
+	match find_field(field, logline)? {
+		FieldType::Timestamp => Some(logline.timestamp.to_rfc3339()),
+		FieldType::Level => Some(logline.level.to_string()),
+		FieldType::Msg => Some(logline.msg.clone()),
+		FieldType::Prop(_, val) => Some(val),
+	}
+}
 
-				match field_access.field.as_str() {
-					"year" => Ok(magic_cmp(
-						logline.timestamp.with_timezone(tz).year(),
-						num,
-						op,
-					)),
-					"month" => Ok(magic_cmp(
-						logline.timestamp.with_timezone(tz).month(),
-						num as u32,
-						op,
-					)),
-					"day" => Ok(magic_cmp(
-						logline.timestamp.with_timezone(tz).day(),
-						num as u32,
-						op,
-					)),
-					"hour" => Ok(magic_cmp(
-						logline.timestamp.with_timezone(tz).hour(),
-						num as u32,
-						op,
-					)),
-					"minute" => Ok(magic_cmp(
-						logline.timestamp.with_timezone(tz).minute(),
-						num as u32,
-						op,
-					)),
-					"second" => Ok(magic_cmp(
-						logline.timestamp.with_timezone(tz).second(),
-						num as u32,
-						op,
-					)),
-					_ => Err(format!("Field not found: {}", field_access.field)),
+/// Resolves a `coalesce(a, b, ...)` argument list: a bare field name or
+/// field-access argument is skipped if the log line doesn't have it; a
+/// non-string literal (`coalesce(user_id, -1)`) always "exists" and is
+/// returned as the fallback default. `Ok(None)` means every argument was
+/// an absent field, i.e. the whole `coalesce` is itself missing.
+fn resolve_coalesce(exprs: &[Expr], logline: &LogEntry, tz: &FixedOffset) -> Result<Option<Value>, String> {
+	for expr in exprs {
+		match expr {
+			Expr::Value(Value::String(field)) => {
+				if let Some(field_type) = find_field(field, logline) {
+					return Ok(Some(field_type_to_value(&field_type, logline)));
 				}
 			}
-			_ => Err(format!("does not have fields: {}", obj)),
+			Expr::FieldAccess(field_access) => {
+				let Some((base, segments)) = field_path(field_access) else {
+					return Err(format!("unsupported field access: {:?}", field_access));
+				};
+				if let Some(resolved) = resolve_path(logline, &base, &segments) {
+					return Ok(Some(Value::String(resolved)));
+				}
+			}
+			Expr::Value(literal) => return Ok(Some(literal.clone())),
+			Expr::Arith(arith) => return Ok(Some(Value::Float(evaluate_arith(arith, logline, tz)?))),
+			other => return Err(format!("unsupported coalesce argument: {:?}", other)),
+		}
+	}
+	Ok(None)
+}
+
+/// A native function usable as `name(...)` inside queries, registered via
+/// [`register_fn`]. Takes already-resolved argument `Value`s rather than
+/// `Expr`s, mirroring how `cached_regex` keeps the query language's
+/// evaluation concerns (field lookup, coercion) out of the function body.
+type NativeFn = Arc<dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync>;
+
+static FN_REGISTRY: LazyLock<Mutex<HashMap<String, NativeFn>>> =
+	LazyLock::new(|| Mutex::new(default_fn_registry()));
+
+fn arity_error(name: &str, expected: &str, got: usize) -> String {
+	format!("{}() expects {} argument(s), got {}", name, expected, got)
+}
+
+fn expect_string<'a>(name: &str, args: &'a [Value], index: usize) -> Result<&'a str, String> {
+	match args.get(index) {
+		Some(Value::String(s)) => Ok(s.as_str()),
+		Some(other) => Err(format!("{}() expects a string argument, got {:?}", name, other)),
+		None => Err(arity_error(name, "1", args.len())),
+	}
+}
+
+fn default_fn_registry() -> HashMap<String, NativeFn> {
+	let mut registry: HashMap<String, NativeFn> = HashMap::new();
+	registry.insert(
+		"lower".to_string(),
+		Arc::new(|args: &[Value]| -> Result<Value, String> {
+			if args.len() != 1 {
+				return Err(arity_error("lower", "1", args.len()));
+			}
+			Ok(Value::String(expect_string("lower", args, 0)?.to_lowercase()))
+		}) as NativeFn,
+	);
+	registry.insert(
+		"upper".to_string(),
+		Arc::new(|args: &[Value]| -> Result<Value, String> {
+			if args.len() != 1 {
+				return Err(arity_error("upper", "1", args.len()));
+			}
+			Ok(Value::String(expect_string("upper", args, 0)?.to_uppercase()))
+		}) as NativeFn,
+	);
+	registry.insert(
+		"trim".to_string(),
+		Arc::new(|args: &[Value]| -> Result<Value, String> {
+			if args.len() != 1 {
+				return Err(arity_error("trim", "1", args.len()));
+			}
+			Ok(Value::String(expect_string("trim", args, 0)?.trim().to_string()))
+		}) as NativeFn,
+	);
+	registry.insert(
+		"len".to_string(),
+		Arc::new(|args: &[Value]| -> Result<Value, String> {
+			if args.len() != 1 {
+				return Err(arity_error("len", "1", args.len()));
+			}
+			match &args[0] {
+				Value::String(s) => Ok(Value::Number(s.chars().count() as i64)),
+				Value::List(items) => Ok(Value::Number(items.len() as i64)),
+				other => Err(format!("len() expects a string or list argument, got {:?}", other)),
+			}
+		}) as NativeFn,
+	);
+	registry.insert(
+		"substr".to_string(),
+		Arc::new(|args: &[Value]| -> Result<Value, String> {
+			if args.len() != 2 && args.len() != 3 {
+				return Err(arity_error("substr", "2 or 3", args.len()));
+			}
+			let s = expect_string("substr", args, 0)?;
+			let start = match &args[1] {
+				Value::Number(n) if *n >= 0 => *n as usize,
+				other => return Err(format!("substr() expects a non-negative start, got {:?}", other)),
+			};
+			let chars: Vec<char> = s.chars().collect();
+			let end = match args.get(2) {
+				Some(Value::Number(n)) if *n >= 0 => chars.len().min(start + *n as usize),
+				Some(other) => return Err(format!("substr() expects a non-negative length, got {:?}", other)),
+				None => chars.len(),
+			};
+			let end = end.max(start).min(chars.len());
+			let start = start.min(chars.len());
+			Ok(Value::String(chars[start..end].iter().collect()))
+		}) as NativeFn,
+	);
+	registry
+}
+
+/// Registers a native function usable as `name(...)` inside queries, e.g.
+/// `lower(msg) == "hello"`. Overwrites any existing function (including the
+/// built-ins) of the same name — mirroring how an embedding application
+/// registers native functions with a scripting host before evaluation.
+pub fn register_fn(
+	name: impl Into<String>,
+	f: impl Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+) {
+	FN_REGISTRY.lock().unwrap().insert(name.into(), Arc::new(f));
+}
+
+fn call_fn(name: &str, args: &[Value]) -> Result<Value, String> {
+	let registry = FN_REGISTRY.lock().unwrap();
+	let f = registry.get(name).ok_or_else(|| format!("Unknown function: {}", name))?;
+	f(args)
+}
+
+/// Resolves a `Call` argument `Expr` to a `Value`, the same field-or-literal
+/// vocabulary `resolve_coalesce` uses for its arguments — except a missing
+/// field is an error here rather than "skip to the next argument", since a
+/// function call has no fallback-argument semantics.
+fn resolve_call_arg(expr: &Expr, logline: &LogEntry, tz: &FixedOffset) -> Result<Value, String> {
+	match expr {
+		Expr::Value(Value::String(field)) => match find_field(field, logline) {
+			Some(field_type) => Ok(field_type_to_value(&field_type, logline)),
+			None => Err(format!("Field not found: {}", field)),
 		},
-		_ => Err(format!("unsupported field access: {:?}", field_access)),
+		Expr::Value(literal) => Ok(literal.clone()),
+		Expr::FieldAccess(field_access) => {
+			let Some((base, segments)) = field_path(field_access) else {
+				return Err(format!("unsupported field access: {:?}", field_access));
+			};
+			resolve_path(logline, &base, &segments)
+				.map(Value::String)
+				.ok_or_else(|| format!("Field not found: {}", segments.join(".")))
+		}
+		Expr::Arith(arith) => Ok(Value::Float(evaluate_arith(arith, logline, tz)?)),
+		Expr::Call { name, args } => eval_call(name, args, logline, tz),
+		other => Err(format!("unsupported function argument: {:?}", other)),
 	}
 }
 
-fn check_condition(cond: &Condition, logline: &LogEntry, tz: &FixedOffset) -> Result<bool, String> {
+fn eval_call(name: &str, args: &[Expr], logline: &LogEntry, tz: &FixedOffset) -> Result<Value, String> {
+	let values: Vec<Value> = args
+		.iter()
+		.map(|arg| resolve_call_arg(arg, logline, tz))
+		.collect::<Result<_, _>>()?;
+	call_fn(name, &values)
+}
+
+/// Compares a resolved value — a `coalesce(...)` result or a function
+/// `Call`'s return value — against the other side of a `Condition`. Only a
+/// literal `Value` is supported on that other side (the same restriction
+/// `check_field_access` has for its right-hand side).
+fn compare_resolved_value(resolved: &Value, other: &Expr, op: &Operator) -> Result<bool, String> {
+	let other_val = match other {
+		Expr::Value(v) => v,
+		_ => return Err(format!("unsupported comparison operand: {:?}", other)),
+	};
+	match (resolved, other_val, op) {
+		(Value::String(a), Value::String(b), Operator::Like) => Ok(a.contains(b.as_str())),
+		(Value::String(a), Value::String(b), Operator::NotLike) => Ok(!a.contains(b.as_str())),
+		(Value::String(a), Value::Regex(pattern), Operator::Matches) => {
+			cached_regex(pattern).map(|re| re.is_match(a)).map_err(|e| e.to_string())
+		}
+		(Value::String(a), Value::Regex(pattern), Operator::NotMatches) => {
+			cached_regex(pattern).map(|re| !re.is_match(a)).map_err(|e| e.to_string())
+		}
+		(resolved, Value::List(vec), Operator::In) => Ok(vec.iter().any(|v| resolved == v)),
+		(resolved, Value::List(vec), Operator::NotIn) => Ok(!vec.iter().any(|v| resolved == v)),
+		(a, b, Operator::Equal | Operator::NotEqual | Operator::GreaterThan | Operator::GreaterThanOrEqual | Operator::LessThan | Operator::LessThanOrEqual) => {
+			Ok(magic_cmp(a.clone(), b.clone(), op))
+		}
+		(a, b, op) => Err(format!("unsupported comparison {:?} {:?} {:?}", a, op, b)),
+	}
+}
+
+fn check_condition_tri(
+	cond: &Condition,
+	logline: &LogEntry,
+	tz: &FixedOffset,
+	strict_null: bool,
+) -> Result<Tri, String> {
 	fn match_field(
 		field: &str,
 		val: &Value,
 		op: &Operator,
 		logline: &LogEntry,
 		tz: &FixedOffset,
-	) -> Result<bool, String> {
+		strict_null: bool,
+	) -> Result<Tri, String> {
 		match find_field(field, logline) {
-			Some(field) => does_field_match(&field, val, op, logline, tz),
-			None => Ok(false),
+			Some(field) => does_field_match(&field, val, op, logline, tz).map(Tri::from_bool),
+			None if strict_null && matches!(op, Operator::NotEqual) => Ok(Tri::True),
+			None => Ok(Tri::Unknown),
+		}
+	}
+	fn match_coalesce(
+		exprs: &[Expr],
+		other: &Expr,
+		op: &Operator,
+		logline: &LogEntry,
+		tz: &FixedOffset,
+		strict_null: bool,
+	) -> Result<Tri, String> {
+		let resolved = resolve_coalesce(exprs, logline, tz)?;
+		match (resolved, op) {
+			(Some(_), Operator::Exists) => Ok(Tri::True),
+			(None, Operator::Exists) => Ok(Tri::False),
+			(Some(_), Operator::NotExists) => Ok(Tri::False),
+			(None, Operator::NotExists) => Ok(Tri::True),
+			(Some(val), op) => compare_resolved_value(&val, other, op).map(Tri::from_bool),
+			(None, Operator::NotEqual) if strict_null => Ok(Tri::True),
+			(None, _) => Ok(Tri::Unknown),
 		}
 	}
 	match (cond.left.as_ref(), cond.right.as_ref(), &cond.operator) {
+		// Both sides are bare names: if they both resolve to real fields,
+		// this is a field-to-field comparison (`bytes_sent > bytes_received`)
+		// rather than a field compared against a literal string. Falls back
+		// to the literal-string behavior below when either side isn't
+		// actually a field on this log line.
+		(Expr::Value(Value::String(left)), Expr::Value(Value::String(right)), op) => {
+			match (find_field(left, logline), find_field(right, logline)) {
+				(Some(left_field), Some(right_field)) => Ok(Tri::from_bool(compare_fields(
+					&left_field,
+					&right_field,
+					op,
+					logline,
+					tz,
+				))),
+				_ => match_field(left, &Value::String(right.clone()), op, logline, tz, strict_null),
+			}
+		}
 		(Expr::Value(Value::String(left)), Expr::Value(val), op) => {
-			match_field(left, val, op, logline, tz)
+			match_field(left, val, op, logline, tz, strict_null)
 		}
 		(Expr::Value(val), Expr::Value(Value::String(right)), op) => {
-			match_field(right, val, op, logline, tz)
+			match_field(right, val, op, logline, tz, strict_null)
 		}
 		(Expr::Value(Value::String(left)), Expr::Empty, Operator::Exists) => {
-			Ok(find_field(left, logline).is_some())
+			Ok(Tri::from_bool(find_field(left, logline).is_some()))
 		}
 		(Expr::Value(Value::String(left)), Expr::Empty, Operator::NotExists) => {
-			Ok(find_field(left, logline).is_none())
+			Ok(Tri::from_bool(find_field(left, logline).is_none()))
+		}
+		(Expr::Arith(arith), right, op) => {
+			let left_num = evaluate_arith(arith, logline, tz)?;
+			let right_num = resolve_arith_operand(right, logline, tz)?;
+			Ok(Tri::from_bool(magic_cmp(left_num, right_num, op)))
+		}
+		(left, Expr::Arith(arith), op) => {
+			let left_num = resolve_arith_operand(left, logline, tz)?;
+			let right_num = evaluate_arith(arith, logline, tz)?;
+			Ok(Tri::from_bool(magic_cmp(left_num, right_num, op)))
+		}
+		(Expr::Coalesce(exprs), right, op) => match_coalesce(exprs, right, op, logline, tz, strict_null),
+		// Equal/NotEqual are symmetric, so `"x" = coalesce(a, b)` can reuse
+		// the same resolution as `coalesce(a, b) = "x"`. Other operators
+		// aren't (e.g. `>`), so a `coalesce(...)` on the right side of those
+		// falls through to the panic below, same as any other unsupported
+		// operand shape.
+		(left, Expr::Coalesce(exprs), op @ (Operator::Equal | Operator::NotEqual)) => {
+			match_coalesce(exprs, left, op, logline, tz, strict_null)
+		}
+		(Expr::Call { name, args }, right, op) => {
+			let resolved = eval_call(name, args, logline, tz)?;
+			compare_resolved_value(&resolved, right, op).map(Tri::from_bool)
+		}
+		// Equal/NotEqual are symmetric, same as `coalesce(...)` above.
+		(left, Expr::Call { name, args }, op @ (Operator::Equal | Operator::NotEqual)) => {
+			let resolved = eval_call(name, args, logline, tz)?;
+			compare_resolved_value(&resolved, left, op).map(Tri::from_bool)
+		}
+		(Expr::FieldAccess(field), right, op) => {
+			check_field_access(field, right, op, logline, tz).map(Tri::from_bool)
+		}
+		(left, Expr::FieldAccess(field), op) => {
+			check_field_access(field, left, op, logline, tz).map(Tri::from_bool)
 		}
-		(Expr::FieldAccess(field), right, op) => check_field_access(field, right, op, logline, tz),
-		(left, Expr::FieldAccess(field), op) => check_field_access(field, left, op, logline, tz),
 		_ => panic!(
 			"Nothing makes sense anymore {:?} logline: {:?}",
 			cond, logline
@@ -288,27 +963,49 @@ fn check_condition(cond: &Condition, logline: &LogEntry, tz: &FixedOffset) -> Re
 	}
 }
 
-pub fn check_expr(expr: &Expr, logline: &LogEntry, tz: &FixedOffset) -> Result<bool, String> {
+fn check_expr_tri(
+	expr: &Expr,
+	logline: &LogEntry,
+	tz: &FixedOffset,
+	strict_null: bool,
+) -> Result<Tri, String> {
 	match expr {
-		Expr::Condition(cond) => check_condition(&cond, logline, tz),
-		Expr::And(expr, expr1) => {
-			Ok(check_expr(expr, &logline, tz)? && check_expr(expr1, logline, tz)?)
-		}
-		Expr::Or(expr, expr1) => {
-			Ok(check_expr(expr, &logline, tz)? || check_expr(expr1, logline, tz)?)
-		}
-		Expr::Value(value) => match value {
-			Value::String(value) => Ok(value != ""),
-			Value::Regex(_) => Ok(true),
-			Value::Number(value) => Ok(*value > 0),
-			Value::Date(_) => Ok(true),
-			Value::List(_) => Err("This is not javascript".to_string()),
+		Expr::Condition(cond) => check_condition_tri(cond, logline, tz, strict_null),
+		Expr::And(expr, expr1) => Ok(check_expr_tri(expr, logline, tz, strict_null)?
+			.and(check_expr_tri(expr1, logline, tz, strict_null)?)),
+		Expr::Or(expr, expr1) => Ok(check_expr_tri(expr, logline, tz, strict_null)?
+			.or(check_expr_tri(expr1, logline, tz, strict_null)?)),
+		Expr::Not(inner) => Ok(check_expr_tri(inner, logline, tz, strict_null)?.not()),
+		Expr::Value(value) => value_truthy(value).map(Tri::from_bool),
+		Expr::Coalesce(exprs) => match resolve_coalesce(exprs, logline, tz)? {
+			Some(val) => value_truthy(&val).map(Tri::from_bool),
+			None if strict_null => Ok(Tri::Unknown),
+			None => Ok(Tri::False),
 		},
-		Expr::Empty => Ok(true),
-		_ => todo!("expr {:?} not supported yet", expr),
+		Expr::Empty => Ok(Tri::True),
+		Expr::FieldAccess(_) | Expr::Arith(_) | Expr::Call { .. } => {
+			let value = resolve_call_arg(expr, logline, tz)?;
+			value_truthy(&value).map(Tri::from_bool)
+		}
 	}
 }
 
+pub fn check_expr(expr: &Expr, logline: &LogEntry, tz: &FixedOffset) -> Result<bool, String> {
+	Ok(check_expr_tri(expr, logline, tz, false)?.to_bool())
+}
+
+/// Same as [`check_expr`] (a comparison against a field the log line doesn't
+/// have is `Unknown`, propagated through `And`/`Or`/`Not` via Kleene logic,
+/// so `not (missing_field = "x")` is itself `Unknown`, not `true`), but opts
+/// into strict null semantics: `!=` against a missing field resolves straight
+/// to `true` (a field that isn't there trivially isn't equal to anything),
+/// and a bare `coalesce(...)` whose arguments are all absent is `Unknown`
+/// rather than `false`. Still collapses to a plain bool at the top, since
+/// callers need a yes/no verdict.
+pub fn check_expr_strict_null(expr: &Expr, logline: &LogEntry, tz: &FixedOffset) -> Result<bool, String> {
+	Ok(check_expr_tri(expr, logline, tz, true)?.to_bool())
+}
+
 pub fn check_props(expr: &Expr, props: &[Prop]) -> Result<bool, String> {
 	fn is_negative_operator(op: &Operator) -> bool {
 		matches!(
@@ -333,7 +1030,11 @@ pub fn check_props(expr: &Expr, props: &[Prop]) -> Result<bool, String> {
 					Ok(!re.is_match(prop_val))
 				}
 				(Value::String(query_str), _) => Ok(cmp_semver_or_string(prop_val, query_str, op)),
-				(Value::Number(num), _) => Ok(magic_cmp(prop_val, &num.to_string(), op)),
+				(Value::Number(num), _) => Ok(cmp_numeric_int(prop_val, *num, op)),
+				(Value::Float(num), _) => Ok(cmp_numeric(prop_val, *num, op)),
+				(Value::Duration(num), _) => Ok(cmp_numeric(prop_val, *num as f64, op)),
+				(Value::Bytes(num), _) => Ok(cmp_numeric(prop_val, *num as f64, op)),
+				(Value::Bool(b), _) => Ok(magic_cmp(prop_val, &b.to_string(), op)),
 				(Value::List(list), Operator::In) => any(list, prop_val, &Operator::Equal),
 				_ => Ok(false),
 			}
@@ -353,7 +1054,7 @@ pub fn check_props(expr: &Expr, props: &[Prop]) -> Result<bool, String> {
 					continue;
 				}
 
-				if compare(&prop.value, val, op)? {
+				if compare(&prop.value.to_string(), val, op)? {
 					return Ok(true);
 				}
 			}
@@ -385,6 +1086,13 @@ pub fn check_props(expr: &Expr, props: &[Prop]) -> Result<bool, String> {
 			}
 			(left, Expr::Value(_), _) if is_ts_access(left) => Ok(true),
 			(Expr::Value(_), right, _) if is_ts_access(right) => Ok(true),
+			// Arithmetic over props can't be evaluated from a segment's prop
+			// summary alone, same reasoning as `is_ts_access` above: defer to
+			// the real per-log check instead of risking pruning a match.
+			(Expr::Arith(_), _, _) | (_, Expr::Arith(_), _) => Ok(true),
+			// `coalesce(...)` picks between fields depending on which one is
+			// present, which a prop summary alone can't resolve either.
+			(Expr::Coalesce(_), _, _) | (_, Expr::Coalesce(_), _) => Ok(true),
 			_ => Ok(false),
 		}
 	}
@@ -393,18 +1101,38 @@ pub fn check_props(expr: &Expr, props: &[Prop]) -> Result<bool, String> {
 		Expr::Condition(cond) => check_condition(cond, props),
 		Expr::And(expr, expr1) => Ok(check_props(expr, props)? && check_props(expr1, props)?),
 		Expr::Or(expr, expr1) => Ok(check_props(expr, props)? || check_props(expr1, props)?),
+		Expr::Not(inner) => Ok(!check_props(inner, props)?),
 		Expr::Value(value) => match value {
 			Value::String(value) => Ok(value != ""),
 			Value::Regex(_) => Ok(true),
 			Value::Number(value) => Ok(*value > 0),
+			Value::Float(value) => Ok(*value > 0.0),
+			Value::Bool(value) => Ok(*value),
+			Value::Duration(value) => Ok(*value > 0),
+			Value::Bytes(value) => Ok(*value > 0),
 			Value::Date(_) => Ok(true),
 			Value::List(_) => Err("This is not javascript".to_string()),
 		},
 		Expr::Empty => Ok(true),
+		// Same reasoning as the `Arith`/`Coalesce` condition arms above: a
+		// bare `coalesce(...)` in boolean position can't be resolved from a
+		// prop summary alone, so don't risk pruning a matching segment.
+		Expr::Coalesce(_) => Ok(true),
 		_ => todo!("expr {:?} not supported yet", expr),
 	}
 }
 
+/// Partial-evaluation pass over a parsed `Expr`: constant-folds literal
+/// conditions and collapses `and`/`or` short-circuits (`(1=1) and X` ->
+/// `X`) before pushdown extraction (`timestamp_bounds`, `extract_device_ids`,
+/// `extract_equality_props`) walks the tree, so a query padded with
+/// always-true/always-false noise still yields a usable bound. Delegates to
+/// `query_parsing::optimize`, which already implements this constant-folding
+/// and boolean-algebra recurrence.
+pub fn simplify(expr: &Expr) -> Expr {
+	crate::query_parsing::optimize(expr.clone())
+}
+
 pub fn extract_date_conditions(expr: &Expr) -> Vec<Condition> {
 	fn is_timestamp_field(expr: &Expr) -> bool {
 		match expr {
@@ -481,6 +1209,56 @@ pub fn extract_device_ids(expr: &Expr) -> Vec<String> {
 	ids
 }
 
+/// Collects every `key = value` constraint the query AST *guarantees* must
+/// hold, as `"key=value"` strings matching the format segment bloom filters
+/// are built over. Only traverses `Expr::And` (never `Expr::Or`/`Expr::Not`):
+/// a constraint under an `Or` branch isn't implied by the whole expression,
+/// so treating it as a hard requirement would let a true bloom miss hide a
+/// real match. Callers use this for a bloom "definitely absent" check, which
+/// must never have false negatives.
+pub fn extract_equality_props(expr: &Expr) -> Vec<Prop> {
+	fn value_to_prop_string(val: &Value) -> Option<String> {
+		match val {
+			Value::String(s) => Some(s.clone()),
+			Value::Number(n) => Some(n.to_string()),
+			Value::Float(n) => Some(n.to_string()),
+			Value::Bool(b) => Some(b.to_string()),
+			Value::Duration(n) => Some(n.to_string()),
+			Value::Bytes(n) => Some(n.to_string()),
+			Value::Regex(_) | Value::Date(_) | Value::List(_) => None,
+		}
+	}
+
+	let mut out = Vec::new();
+	match expr {
+		Expr::Condition(cond) if cond.operator == Operator::Equal => {
+			let pair = match (cond.left.as_ref(), cond.right.as_ref()) {
+				(Expr::Value(Value::String(field)), Expr::Value(val)) => {
+					Some((field, value_to_prop_string(val)))
+				}
+				(Expr::Value(val), Expr::Value(Value::String(field))) => {
+					Some((field, value_to_prop_string(val)))
+				}
+				_ => None,
+			};
+			if let Some((field, Some(value))) = pair {
+				if field != "msg" && field != "timestamp" {
+					out.push(Prop {
+						key: field.clone(),
+						value: value.into(),
+					});
+				}
+			}
+		}
+		Expr::And(left, right) => {
+			out.extend(extract_equality_props(left));
+			out.extend(extract_equality_props(right));
+		}
+		_ => {}
+	}
+	out
+}
+
 pub fn timestamp_bounds(expr: &Expr) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
 	let mut start: Option<DateTime<Utc>> = None;
 	let mut end: Option<DateTime<Utc>> = None;
@@ -602,6 +1380,355 @@ pub fn match_date_range(
 	}
 	true
 }
+
+/// Per-segment stats used for index-guided segment pruning, generalizing the
+/// min/max timestamp pruning `match_date_range` already does to ordinary
+/// props: the observed min/max for selected keys, plus which keys are known
+/// to be present at all. `prop_keys` being empty means "no presence index
+/// for this segment" (treated as maybe-present for every key), not "this
+/// segment has no props".
+#[derive(Debug, Clone, Default)]
+pub struct SegmentStats {
+	pub prop_bounds: HashMap<String, (Value, Value)>,
+	pub prop_keys: HashSet<String>,
+}
+
+fn value_order(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+	match (a, b) {
+		(Value::Number(x), Value::Number(y)) => x.partial_cmp(y),
+		(Value::Float(x), Value::Float(y)) => x.partial_cmp(y),
+		(Value::Number(x), Value::Float(y)) => (*x as f64).partial_cmp(y),
+		(Value::Float(x), Value::Number(y)) => x.partial_cmp(&(*y as f64)),
+		(Value::Duration(x), Value::Duration(y)) => x.partial_cmp(y),
+		(Value::Bytes(x), Value::Bytes(y)) => x.partial_cmp(y),
+		(Value::Date(x), Value::Date(y)) => x.partial_cmp(y),
+		(Value::String(x), Value::String(y)) => match (parse_semver(x), parse_semver(y)) {
+			(Some(sx), Some(sy)) => sx.partial_cmp(&sy),
+			_ => x.partial_cmp(y),
+		},
+		_ => None,
+	}
+}
+
+fn extract_prop_conditions(expr: &Expr, key: &str) -> Vec<Condition> {
+	let mut out = Vec::new();
+	match expr {
+		Expr::Condition(cond) => {
+			let is_match = matches!(cond.left.as_ref(), Expr::Value(Value::String(f)) if f == key)
+				|| matches!(cond.right.as_ref(), Expr::Value(Value::String(f)) if f == key);
+			if is_match {
+				out.push(cond.clone());
+			}
+		}
+		Expr::And(left, right) => {
+			out.extend(extract_prop_conditions(left, key));
+			out.extend(extract_prop_conditions(right, key));
+		}
+		_ => {}
+	}
+	out
+}
+
+/// The `(lower, upper)` bound a query's AST implies for `key`, mirroring
+/// `timestamp_bounds` but for an arbitrary prop rather than just `timestamp`.
+/// Only literal `key <op> value` / `value <op> key` conditions contribute;
+/// anything under an `Or`/`Not` (or comparing two non-literal operands) is
+/// ignored, since only conditions joined by `And` bound every match.
+pub fn prop_bounds(expr: &Expr, key: &str) -> (Option<Value>, Option<Value>) {
+	let mut lower: Option<Value> = None;
+	let mut upper: Option<Value> = None;
+
+	for cond in extract_prop_conditions(expr, key) {
+		let (val, op) = match (cond.left.as_ref(), cond.right.as_ref()) {
+			(Expr::Value(Value::String(f)), Expr::Value(val)) if f == key => {
+				(val.clone(), cond.operator)
+			}
+			(Expr::Value(val), Expr::Value(Value::String(f))) if f == key => {
+				let op = match cond.operator {
+					Operator::GreaterThan => Operator::LessThan,
+					Operator::GreaterThanOrEqual => Operator::LessThanOrEqual,
+					Operator::LessThan => Operator::GreaterThan,
+					Operator::LessThanOrEqual => Operator::GreaterThanOrEqual,
+					o => o,
+				};
+				(val.clone(), op)
+			}
+			_ => continue,
+		};
+		match op {
+			Operator::GreaterThan | Operator::GreaterThanOrEqual => {
+				if lower.as_ref().map_or(true, |l| value_order(&val, l) == Some(std::cmp::Ordering::Greater)) {
+					lower = Some(val);
+				}
+			}
+			Operator::LessThan | Operator::LessThanOrEqual => {
+				if upper.as_ref().map_or(true, |u| value_order(&val, u) == Some(std::cmp::Ordering::Less)) {
+					upper = Some(val);
+				}
+			}
+			Operator::Equal => {
+				lower = Some(val.clone());
+				upper = Some(val);
+			}
+			_ => {}
+		}
+	}
+
+	(lower, upper)
+}
+
+/// `true` unless `[min, max]` provably can't satisfy `field <op> query_val`
+/// (falls back to `true` for incomparable types, same "can't prune" default
+/// as an unsupported operator).
+fn range_may_match(min: &Value, max: &Value, query_val: &Value, op: Operator) -> bool {
+	use std::cmp::Ordering;
+	match op {
+		Operator::GreaterThan => value_order(max, query_val).map_or(true, |o| o == Ordering::Greater),
+		Operator::GreaterThanOrEqual => value_order(max, query_val).map_or(true, |o| o != Ordering::Less),
+		Operator::LessThan => value_order(min, query_val).map_or(true, |o| o == Ordering::Less),
+		Operator::LessThanOrEqual => value_order(min, query_val).map_or(true, |o| o != Ordering::Greater),
+		Operator::Equal => {
+			let above_max = value_order(query_val, max) == Some(Ordering::Greater);
+			let below_min = value_order(query_val, min) == Some(Ordering::Less);
+			!(above_max || below_min)
+		}
+		_ => true,
+	}
+}
+
+/// Returns `false` only when `expr` provably excludes every row `stats`
+/// could describe, turning a full segment scan into an index-guided one for
+/// high-cardinality prop filters. `And` intersects child verdicts, `Or`
+/// unions them; anything this can't reason about (regex/Like, `Not`,
+/// function calls, `timestamp` — already handled by `match_date_range`)
+/// conservatively reports `true` ("may match").
+pub fn match_segment(expr: &Expr, stats: &SegmentStats) -> bool {
+	match expr {
+		Expr::Condition(cond) => {
+			let pair = match (cond.left.as_ref(), cond.right.as_ref()) {
+				(Expr::Value(Value::String(f)), Expr::Value(val)) if f != "timestamp" => {
+					Some((f.as_str(), val, cond.operator))
+				}
+				(Expr::Value(val), Expr::Value(Value::String(f))) if f != "timestamp" => {
+					let op = match cond.operator {
+						Operator::GreaterThan => Operator::LessThan,
+						Operator::GreaterThanOrEqual => Operator::LessThanOrEqual,
+						Operator::LessThan => Operator::GreaterThan,
+						Operator::LessThanOrEqual => Operator::GreaterThanOrEqual,
+						o => o,
+					};
+					Some((f.as_str(), val, op))
+				}
+				_ => None,
+			};
+			let Some((field, val, op)) = pair else {
+				return true;
+			};
+			if op == Operator::Equal && !stats.prop_keys.is_empty() && !stats.prop_keys.contains(field) {
+				return false;
+			}
+			match stats.prop_bounds.get(field) {
+				Some((min, max)) => range_may_match(min, max, val, op),
+				None => true,
+			}
+		}
+		Expr::And(left, right) => match_segment(left, stats) && match_segment(right, stats),
+		Expr::Or(left, right) => match_segment(left, stats) || match_segment(right, stats),
+		_ => true,
+	}
+}
+
+/// An aggregation op over the stream of entries that pass `check_expr`,
+/// akin to Actyx AQL's `AGGREGATE` clause. `field` names `msg`/`level`/a prop,
+/// same vocabulary `find_field` already resolves for conditions; `group_by`
+/// names zero or more fields to bucket by (empty means a single overall
+/// group).
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Aggregate {
+	pub field: String,
+	pub op: AggrOp,
+	pub group_by: Vec<String>,
+	pub time_bucket: Option<TimeBucket>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum AggrOp {
+	Count,
+	Sum,
+	Avg,
+	Min,
+	Max,
+	Last,
+	CountDistinct,
+}
+
+/// Buckets entries by a slice of their `timestamp`, akin to grouping by the
+/// `timestamp.hour`/`timestamp.day` field access conditions already support,
+/// so an `Aggregate` can produce a time series instead of one overall group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type")]
+pub enum TimeBucket {
+	Hour,
+	Day,
+}
+
+impl TimeBucket {
+	fn key(self, timestamp: DateTime<Utc>) -> String {
+		match self {
+			TimeBucket::Hour => timestamp.format("%Y-%m-%dT%H").to_string(),
+			TimeBucket::Day => timestamp.format("%Y-%m-%d").to_string(),
+		}
+	}
+}
+
+/// Per-group running state. `sum`/`min`/`max`/`avg` skip entries whose
+/// `field` doesn't parse as a number (same "never match" treatment
+/// `cmp_numeric` gives a non-numeric prop), so a group that never saw a
+/// numeric value reports `Value::Float(0.0)` rather than erroring.
+enum GroupState {
+	Count(i64),
+	Sum(f64),
+	Avg { sum: f64, count: i64 },
+	Min(Option<f64>),
+	Max(Option<f64>),
+	Last(Option<f64>),
+	CountDistinct(std::collections::HashSet<String>),
+}
+
+impl GroupState {
+	fn new(op: AggrOp) -> GroupState {
+		match op {
+			AggrOp::Count => GroupState::Count(0),
+			AggrOp::Sum => GroupState::Sum(0.0),
+			AggrOp::Avg => GroupState::Avg { sum: 0.0, count: 0 },
+			AggrOp::Min => GroupState::Min(None),
+			AggrOp::Max => GroupState::Max(None),
+			AggrOp::Last => GroupState::Last(None),
+			AggrOp::CountDistinct => GroupState::CountDistinct(std::collections::HashSet::new()),
+		}
+	}
+
+	fn feed(&mut self, field_val: Option<&str>) {
+		match self {
+			GroupState::Count(n) => *n += 1,
+			GroupState::Sum(sum) => {
+				if let Some(n) = field_val.and_then(parse_numeric) {
+					*sum += n;
+				}
+			}
+			GroupState::Avg { sum, count } => {
+				if let Some(n) = field_val.and_then(parse_numeric) {
+					*sum += n;
+					*count += 1;
+				}
+			}
+			GroupState::Min(min) => {
+				if let Some(n) = field_val.and_then(parse_numeric) {
+					*min = Some(min.map_or(n, |m| m.min(n)));
+				}
+			}
+			GroupState::Max(max) => {
+				if let Some(n) = field_val.and_then(parse_numeric) {
+					*max = Some(max.map_or(n, |m| m.max(n)));
+				}
+			}
+			GroupState::Last(last) => {
+				if let Some(n) = field_val.and_then(parse_numeric) {
+					*last = Some(n);
+				}
+			}
+			GroupState::CountDistinct(seen) => {
+				if let Some(v) = field_val {
+					seen.insert(v.to_string());
+				}
+			}
+		}
+	}
+
+	fn finish(self) -> Value {
+		match self {
+			GroupState::Count(n) => Value::Number(n),
+			GroupState::Sum(sum) => Value::Float(sum),
+			GroupState::Avg { sum, count } => {
+				Value::Float(if count > 0 { sum / count as f64 } else { 0.0 })
+			}
+			GroupState::Min(min) => Value::Float(min.unwrap_or(0.0)),
+			GroupState::Max(max) => Value::Float(max.unwrap_or(0.0)),
+			GroupState::Last(last) => Value::Float(last.unwrap_or(0.0)),
+			GroupState::CountDistinct(seen) => Value::Number(seen.len() as i64),
+		}
+	}
+}
+
+/// Stateful accumulator for an `Aggregate`, fed one `LogEntry` at a time so
+/// callers can pipe a filtered iterator through it without buffering the
+/// whole stream. Grouped by the string form of each `group_by` field
+/// (mirroring `FieldType::Prop`'s string-valued props), since `Value` isn't
+/// `Hash`/`Eq`; `finish` resolves each group key back to `Value`s for the
+/// caller.
+pub struct Aggregator {
+	aggregate: Aggregate,
+	groups: HashMap<Vec<String>, GroupState>,
+}
+
+pub fn new_aggregator(aggregate: Aggregate) -> Aggregator {
+	Aggregator {
+		aggregate,
+		groups: HashMap::new(),
+	}
+}
+
+impl Aggregator {
+	pub fn feed(&mut self, logline: &LogEntry) {
+		let mut key: Vec<String> = self
+			.aggregate
+			.group_by
+			.iter()
+			.map(|field| field_string(field, logline).unwrap_or_default())
+			.collect();
+		if let Some(bucket) = self.aggregate.time_bucket {
+			key.push(bucket.key(logline.timestamp));
+		}
+		let field_val = field_string(&self.aggregate.field, logline);
+		let op = self.aggregate.op;
+		self.groups
+			.entry(key)
+			.or_insert_with(|| GroupState::new(op))
+			.feed(field_val.as_deref());
+	}
+
+	pub fn finish(self) -> Vec<(Vec<Value>, Value)> {
+		self.groups
+			.into_iter()
+			.map(|(key, state)| {
+				let key_values = key.into_iter().map(Value::String).collect();
+				(key_values, state.finish())
+			})
+			.collect()
+	}
+}
+
+/// Filters `entries` through `check_expr`, then feeds the survivors into an
+/// `Aggregator` for `agg`, returning one `(group key, result)` pair per
+/// distinct `group_by`/`time_bucket` combination. Buffers the whole slice —
+/// callers streaming a segment at a time should drive `Aggregator` directly
+/// instead.
+pub fn aggregate(
+	expr: &Expr,
+	entries: &[LogEntry],
+	agg: &Aggregate,
+	tz: &FixedOffset,
+) -> Result<Vec<(Vec<Value>, Value)>, String> {
+	let mut aggregator = new_aggregator(agg.clone());
+	for entry in entries {
+		if check_expr(expr, entry, tz)? {
+			aggregator.feed(entry);
+		}
+	}
+	Ok(aggregator.finish())
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -615,15 +1742,15 @@ mod tests {
 		let props = vec![
 			Prop {
 				key: "service".to_string(),
-				value: "auth".to_string(),
+				value: "auth".to_string().into(),
 			},
 			Prop {
 				key: "user_id".to_string(),
-				value: "123".to_string(),
+				value: "123".to_string().into(),
 			},
 			Prop {
 				key: "duration_ms".to_string(),
-				value: "150".to_string(),
+				value: "150".to_string().into(),
 			},
 		];
 		let expr = Expr::Condition(Condition {
@@ -639,19 +1766,19 @@ mod tests {
 		let props = vec![
 			Prop {
 				key: "service".to_string(),
-				value: "auth".to_string(),
+				value: "auth".to_string().into(),
 			},
 			Prop {
 				key: "user_id".to_string(),
-				value: "123".to_string(),
+				value: "123".to_string().into(),
 			},
 			Prop {
 				key: "duration_ms".to_string(),
-				value: "150".to_string(),
+				value: "150".to_string().into(),
 			},
 			Prop {
 				key: "service".to_string(),
-				value: "auth2".to_string(),
+				value: "auth2".to_string().into(),
 			},
 		];
 		let expr = Expr::Condition(Condition {
@@ -667,15 +1794,15 @@ mod tests {
 		let props = vec![
 			Prop {
 				key: "service".to_string(),
-				value: "auth".to_string(),
+				value: "auth".to_string().into(),
 			},
 			Prop {
 				key: "user_id".to_string(),
-				value: "123".to_string(),
+				value: "123".to_string().into(),
 			},
 			Prop {
 				key: "duration_ms".to_string(),
-				value: "150".to_string(),
+				value: "150".to_string().into(),
 			},
 		];
 		let expr = Expr::Condition(Condition {
@@ -691,15 +1818,15 @@ mod tests {
 		let props = vec![
 			Prop {
 				key: "service".to_string(),
-				value: "auth".to_string(),
+				value: "auth".to_string().into(),
 			},
 			Prop {
 				key: "user_id".to_string(),
-				value: "123".to_string(),
+				value: "123".to_string().into(),
 			},
 			Prop {
 				key: "duration_ms".to_string(),
-				value: "150".to_string(),
+				value: "150".to_string().into(),
 			},
 		];
 		let expr = Expr::Condition(Condition {
@@ -715,15 +1842,15 @@ mod tests {
 		let props = vec![
 			Prop {
 				key: "service".to_string(),
-				value: "auth".to_string(),
+				value: "auth".to_string().into(),
 			},
 			Prop {
 				key: "user_id".to_string(),
-				value: "123".to_string(),
+				value: "123".to_string().into(),
 			},
 			Prop {
 				key: "duration_ms".to_string(),
-				value: "150".to_string(),
+				value: "150".to_string().into(),
 			},
 		];
 		let expr = Expr::And(
@@ -745,7 +1872,7 @@ mod tests {
 	fn ignore_timestamp_fields_in_props_check() {
 		let props = vec![Prop {
 			key: "deviceId".to_string(),
-			value: "237865".to_string(),
+			value: "237865".to_string().into(),
 		}];
 
 		let expr = Expr::And(
@@ -776,6 +1903,51 @@ mod tests {
 		assert_eq!(conds[0].operator, Operator::Equal);
 	}
 
+	#[test]
+	fn extract_date_conditions_does_not_descend_into_not() {
+		// A `Not` over a timestamp condition is not extracted: the condition
+		// describes what must be true for the *negated* range, not the query
+		// as a whole, so pushdown must treat it as "may match" rather than
+		// pruning segments outside the wrapped range.
+		let ast = crate::parse_log_query("not timestamp > \"2024-06-01\"").unwrap();
+		let conds = extract_date_conditions(&ast.root);
+		assert!(conds.is_empty());
+	}
+
+	#[test]
+	fn match_date_range_does_not_prune_a_negated_condition() {
+		let ast = crate::parse_log_query("not timestamp > \"2030-01-01\"").unwrap();
+		let first = DateTime::<Utc>::from_utc(
+			chrono::NaiveDate::from_ymd_opt(2024, 1, 1)
+				.unwrap()
+				.and_hms_opt(0, 0, 0)
+				.unwrap(),
+			Utc,
+		);
+		let last = DateTime::<Utc>::from_utc(
+			chrono::NaiveDate::from_ymd_opt(2024, 6, 1)
+				.unwrap()
+				.and_hms_opt(0, 0, 0)
+				.unwrap(),
+			Utc,
+		);
+		let tz = FixedOffset::east_opt(0).unwrap();
+		assert!(match_date_range(&ast.root, first, last, &tz));
+	}
+
+	#[test]
+	fn match_segment_does_not_prune_a_negated_condition() {
+		// Same conservatism as `match_date_range`: a `Not` over a prunable
+		// prop range can't be pruned from the wrapped range's bounds either,
+		// since the query wants rows *outside* that range.
+		let ast = crate::parse_log_query("not count > 100").unwrap();
+		let mut stats = SegmentStats::default();
+		stats
+			.prop_bounds
+			.insert("count".to_string(), (Value::Number(0), Value::Number(50)));
+		assert!(match_segment(&ast.root, &stats));
+	}
+
 	#[test]
 	fn extract_device_ids_basic() {
 		let ast =
@@ -790,6 +1962,31 @@ mod tests {
 		let ids = extract_device_ids(&ast.root);
 		assert!(ids.is_empty());
 	}
+
+	#[test]
+	fn extract_equality_props_conjunctive() {
+		let ast = crate::parse_log_query("deviceId = dev1 and service = \"api\"").unwrap();
+		let props = extract_equality_props(&ast.root);
+		assert_eq!(props.len(), 2);
+		assert!(props.iter().any(|p| p.key == "deviceId" && p.value == "dev1"));
+		assert!(props.iter().any(|p| p.key == "service" && p.value == "api"));
+	}
+
+	#[test]
+	fn extract_equality_props_ignores_or_branches() {
+		let ast = crate::parse_log_query("deviceId = dev1 or service = \"api\"").unwrap();
+		let props = extract_equality_props(&ast.root);
+		assert!(props.is_empty());
+	}
+
+	#[test]
+	fn extract_equality_props_skips_msg() {
+		let ast = crate::parse_log_query("msg = \"boom\" and deviceId = dev1").unwrap();
+		let props = extract_equality_props(&ast.root);
+		assert_eq!(props.len(), 1);
+		assert_eq!(props[0].key, "deviceId");
+	}
+
 	#[test]
 	fn match_date_range_month() {
 		let expr = crate::parse_log_query("timestamp.month = 4").unwrap();
@@ -895,6 +2092,86 @@ mod tests {
 		assert_eq!(start, Some(start_expected));
 		assert_eq!(end, Some(end_expected));
 	}
+
+	#[test]
+	fn prop_bounds_collects_a_range_from_and_ed_conditions() {
+		let ast = crate::parse_log_query("count > 10 and count <= 100").unwrap();
+		let (lower, upper) = prop_bounds(&ast.root, "count");
+		assert_eq!(lower, Some(Value::Number(10)));
+		assert_eq!(upper, Some(Value::Number(100)));
+	}
+
+	#[test]
+	fn prop_bounds_ignores_an_unrelated_key() {
+		let ast = crate::parse_log_query("count > 10").unwrap();
+		let (lower, upper) = prop_bounds(&ast.root, "version");
+		assert_eq!(lower, None);
+		assert_eq!(upper, None);
+	}
+
+	#[test]
+	fn match_segment_prunes_a_numeric_range_that_cannot_satisfy_the_query() {
+		let ast = crate::parse_log_query("count > 100").unwrap();
+		let mut stats = SegmentStats::default();
+		stats
+			.prop_bounds
+			.insert("count".to_string(), (Value::Number(0), Value::Number(50)));
+		assert!(!match_segment(&ast.root, &stats));
+	}
+
+	#[test]
+	fn match_segment_keeps_a_segment_whose_range_overlaps_the_query() {
+		let ast = crate::parse_log_query("count > 100").unwrap();
+		let mut stats = SegmentStats::default();
+		stats
+			.prop_bounds
+			.insert("count".to_string(), (Value::Number(50), Value::Number(200)));
+		assert!(match_segment(&ast.root, &stats));
+	}
+
+	#[test]
+	fn match_segment_prunes_a_greater_than_against_a_version_range() {
+		let ast = crate::parse_log_query(r#"version > "2.0.0""#).unwrap();
+		let mut stats = SegmentStats::default();
+		stats.prop_bounds.insert(
+			"version".to_string(),
+			(Value::String("1.0.0".to_string()), Value::String("1.10.0".to_string())),
+		);
+		assert!(!match_segment(&ast.root, &stats));
+	}
+
+	#[test]
+	fn match_segment_prunes_an_equality_when_the_key_is_absent_from_the_segment() {
+		let ast = crate::parse_log_query(r#"service = "auth""#).unwrap();
+		let mut stats = SegmentStats::default();
+		stats.prop_keys.insert("other_service".to_string());
+		assert!(!match_segment(&ast.root, &stats));
+	}
+
+	#[test]
+	fn match_segment_defaults_to_maybe_for_regex_conditions() {
+		let ast = crate::parse_log_query("deviceId matches /^dev-/").unwrap();
+		let mut stats = SegmentStats::default();
+		stats
+			.prop_bounds
+			.insert("deviceId".to_string(), (Value::String("a".to_string()), Value::String("b".to_string())));
+		assert!(match_segment(&ast.root, &stats));
+	}
+
+	#[test]
+	fn match_segment_intersects_and_unions_child_verdicts() {
+		let mut stats = SegmentStats::default();
+		stats
+			.prop_bounds
+			.insert("count".to_string(), (Value::Number(0), Value::Number(50)));
+
+		let and_ast = crate::parse_log_query("count > 100 and level = \"error\"").unwrap();
+		assert!(!match_segment(&and_ast.root, &stats));
+
+		let or_ast = crate::parse_log_query("count > 100 or level = \"error\"").unwrap();
+		assert!(match_segment(&or_ast.root, &stats));
+	}
+
 	#[test]
 	fn msg_does_not_match() {
 		let logline = LogEntry {
@@ -902,7 +2179,7 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "key".to_string(),
-				value: "value".to_string(),
+				value: "value".to_string().into(),
 			}],
 			msg: "Hello, world!".to_string(),
 			..Default::default()
@@ -923,7 +2200,7 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "key".to_string(),
-				value: "value".to_string(),
+				value: "value".to_string().into(),
 			}],
 			msg: "Hello, world!".to_string(),
 			..Default::default()
@@ -944,15 +2221,15 @@ mod tests {
 			props: vec![
 				Prop {
 					key: "service".to_string(),
-					value: "auth".to_string(),
+					value: "auth".to_string().into(),
 				},
 				Prop {
 					key: "user_id".to_string(),
-					value: "123".to_string(),
+					value: "123".to_string().into(),
 				},
 				Prop {
 					key: "duration_ms".to_string(),
-					value: "150".to_string(),
+					value: "150".to_string().into(),
 				},
 			],
 			msg: "User login successful".to_string(),
@@ -1194,6 +2471,40 @@ mod tests {
 		assert!(check_expr(&expr, &log, &chrono::FixedOffset::east_opt(0).unwrap()).is_err());
 	}
 
+	#[test]
+	fn arithmetic_condition_evaluates_a_numeric_prop_before_comparing() {
+		let log = create_test_log_entry();
+		let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+		// duration_ms / 1000 > 0.1, with duration_ms = "150".
+		let ast = crate::parse_log_query("duration_ms / 1000 > 0.1").unwrap();
+		assert!(check_expr(&ast.root, &log, &tz).unwrap());
+
+		let ast = crate::parse_log_query("duration_ms * 2 >= 300").unwrap();
+		assert!(check_expr(&ast.root, &log, &tz).unwrap());
+	}
+
+	#[test]
+	fn arithmetic_condition_reports_division_by_zero() {
+		let log = create_test_log_entry();
+		let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+		let ast = crate::parse_log_query("duration_ms / 0 > 1").unwrap();
+		let err = check_expr(&ast.root, &log, &tz).unwrap_err();
+		assert!(err.contains("division by zero"), "unexpected error: {}", err);
+	}
+
+	#[test]
+	fn arithmetic_condition_reports_a_type_mismatch_for_a_non_numeric_operand() {
+		let log = create_test_log_entry();
+		let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+		// `service` is "auth", not numeric.
+		let ast = crate::parse_log_query("service * 2 > 1").unwrap();
+		let err = check_expr(&ast.root, &log, &tz).unwrap_err();
+		assert!(err.contains("not numeric"), "unexpected error: {}", err);
+	}
+
 	#[test]
 	fn test_empty_and_value_expressions() {
 		let log = create_test_log_entry();
@@ -1219,7 +2530,7 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "key".to_string(),
-				value: "value".to_string(),
+				value: "value".to_string().into(),
 			}],
 			msg: "Hello, world!".to_string(),
 			..Default::default()
@@ -1253,7 +2564,7 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "key".to_string(),
-				value: "value".to_string(),
+				value: "value".to_string().into(),
 			}],
 			msg: "Hello, world!".to_string(),
 			..Default::default()
@@ -1297,7 +2608,7 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "key".to_string(),
-				value: "value".to_string(),
+				value: "value".to_string().into(),
 			}],
 			msg: "Hello, world!".to_string(),
 			..Default::default()
@@ -1365,7 +2676,7 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "version".to_string(),
-				value: "1.10.0".to_string(),
+				value: "1.10.0".to_string().into(),
 			}],
 			msg: "".to_string(),
 			..Default::default()
@@ -1459,6 +2770,525 @@ fn negative_check_and_positive_match() {
 	assert!(check_props(&expr, &props).unwrap());
 }
 
+#[test]
+fn numeric_prop_comparison_beyond_f64_precision() {
+	use crate::query_parsing::{Condition, Operator, Value};
+	use crate::{LogEntry, LogLevel, Prop};
+
+	// 2^53 + 1 can't be represented exactly as an f64, so a prop this large
+	// must compare via the exact-i64 path, not a lossy float cast.
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		props: vec![Prop {
+			key: "big".to_string(),
+			value: "9007199254740993".to_string().into(),
+		}],
+		msg: "".to_string(),
+		..Default::default()
+	};
+
+	let expr = Expr::Condition(Condition {
+		left: Box::new(Expr::Value(Value::String("big".to_string()))),
+		operator: Operator::Equal,
+		right: Box::new(Expr::Value(Value::Number(9007199254740993))),
+	});
+	assert!(check_expr(&expr, &logline, &chrono::FixedOffset::east_opt(0).unwrap()).unwrap());
+
+	let expr = Expr::Condition(Condition {
+		left: Box::new(Expr::Value(Value::String("big".to_string()))),
+		operator: Operator::Equal,
+		right: Box::new(Expr::Value(Value::Number(9007199254740992))),
+	});
+	assert!(!check_expr(&expr, &logline, &chrono::FixedOffset::east_opt(0).unwrap()).unwrap());
+}
+
+#[test]
+fn coalesce_falls_back_to_second_field() {
+	use crate::query_parsing::{Condition, Operator, Value};
+	use crate::{LogEntry, LogLevel, Prop};
+
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		props: vec![Prop {
+			key: "user_id".to_string(),
+			value: "42".to_string().into(),
+		}],
+		msg: "".to_string(),
+		..Default::default()
+	};
+
+	let expr = Expr::Condition(Condition {
+		left: Box::new(Expr::Coalesce(vec![
+			Expr::Value(Value::String("alt_user_id".to_string())),
+			Expr::Value(Value::String("user_id".to_string())),
+		])),
+		operator: Operator::Equal,
+		right: Box::new(Expr::Value(Value::String("42".to_string()))),
+	});
+	assert!(check_expr(&expr, &logline, &chrono::FixedOffset::east_opt(0).unwrap()).unwrap());
+
+	let expr = Expr::Condition(Condition {
+		left: Box::new(Expr::Coalesce(vec![
+			Expr::Value(Value::String("alt_user_id".to_string())),
+			Expr::Value(Value::String("missing".to_string())),
+		])),
+		operator: Operator::Equal,
+		right: Box::new(Expr::Value(Value::String("anything".to_string()))),
+	});
+	// Both arguments are bare field names and neither exists on the log
+	// line, so the whole `coalesce` is itself missing: `Unknown` collapses
+	// to `false` under plain `check_expr`.
+	assert!(!check_expr(&expr, &logline, &chrono::FixedOffset::east_opt(0).unwrap()).unwrap());
+
+	let expr = Expr::Condition(Condition {
+		left: Box::new(Expr::Coalesce(vec![
+			Expr::Value(Value::String("alt_user_id".to_string())),
+			Expr::Value(Value::Number(-1)),
+		])),
+		operator: Operator::Equal,
+		right: Box::new(Expr::Value(Value::Number(-1))),
+	});
+	// A non-string literal always "exists", so it supplies the fallback
+	// default once every preceding field argument is absent.
+	assert!(check_expr(&expr, &logline, &chrono::FixedOffset::east_opt(0).unwrap()).unwrap());
+}
+
+#[test]
+fn comparing_a_non_string_resolved_value_against_a_string_only_operator_errors_instead_of_panicking() {
+	use crate::query_parsing::{Condition, Operator, Value};
+	use crate::{LogEntry, LogLevel};
+
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		msg: "".to_string(),
+		..Default::default()
+	};
+
+	// `coalesce(5) matches "x"`: the resolved value is a Number, but `matches`
+	// only makes sense against a String, so this used to fall into
+	// `magic_cmp`'s `todo!()` and panic instead of reporting an error.
+	let expr = Expr::Condition(Condition {
+		left: Box::new(Expr::Coalesce(vec![Expr::Value(Value::Number(5))])),
+		operator: Operator::Matches,
+		right: Box::new(Expr::Value(Value::Regex("x".to_string()))),
+	});
+	assert!(check_expr(&expr, &logline, &chrono::FixedOffset::east_opt(0).unwrap()).is_err());
+}
+
+#[test]
+fn a_bare_arith_or_call_expression_is_truthy_checked_instead_of_panicking() {
+	use crate::query_parsing::{Arith, ArithOp, Value};
+	use crate::{LogEntry, LogLevel};
+
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		msg: "hello".to_string(),
+		..Default::default()
+	};
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let arith = Expr::Arith(Arith {
+		op: ArithOp::Add,
+		left: Box::new(Expr::Value(Value::Number(1))),
+		right: Box::new(Expr::Value(Value::Number(1))),
+	});
+	assert!(check_expr(&arith, &logline, &tz).unwrap());
+
+	let call = Expr::Call {
+		name: "lower".to_string(),
+		args: vec![Expr::Value(Value::String("msg".to_string()))],
+	};
+	assert!(check_expr(&call, &logline, &tz).unwrap());
+}
+
+#[test]
+fn strict_null_kleene_logic_for_missing_fields() {
+	use crate::query_parsing::{Condition, Operator, Value};
+	use crate::{LogEntry, LogLevel};
+
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		props: vec![],
+		msg: "".to_string(),
+		..Default::default()
+	};
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let missing_field_eq = Expr::Condition(Condition {
+		left: Box::new(Expr::Value(Value::String("nonexistent".to_string()))),
+		operator: Operator::Equal,
+		right: Box::new(Expr::Value(Value::String("x".to_string()))),
+	});
+	// Plain `check_expr` collapses the Unknown straight to `false`, same as
+	// before three-valued logic existed.
+	assert!(!check_expr(&missing_field_eq, &logline, &tz).unwrap());
+	// `not` over an Unknown is still Unknown under Kleene logic, which also
+	// collapses to `false` — not `true` as a naive bool negation would give.
+	let negated = Expr::Not(Box::new(missing_field_eq.clone()));
+	assert!(!check_expr_strict_null(&negated, &logline, &tz).unwrap());
+
+	let missing_field_ne = Expr::Condition(Condition {
+		left: Box::new(Expr::Value(Value::String("nonexistent".to_string()))),
+		operator: Operator::NotEqual,
+		right: Box::new(Expr::Value(Value::String("x".to_string()))),
+	});
+	// Opting into strict-null mode resolves `!=` against a missing field to
+	// `true`; plain `check_expr` still treats it as `false`.
+	assert!(!check_expr(&missing_field_ne, &logline, &tz).unwrap());
+	assert!(check_expr_strict_null(&missing_field_ne, &logline, &tz).unwrap());
+}
+
+#[test]
+fn compares_two_props_numerically() {
+	use crate::{LogEntry, LogLevel, Prop};
+
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		props: vec![
+			Prop {
+				key: "bytes_sent".to_string(),
+				value: "150".to_string().into(),
+			},
+			Prop {
+				key: "bytes_received".to_string(),
+				value: "99".to_string().into(),
+			},
+		],
+		msg: "".to_string(),
+		..Default::default()
+	};
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let ast = crate::parse_log_query("bytes_sent > bytes_received").unwrap();
+	assert!(check_expr(&ast.root, &logline, &tz).unwrap());
+
+	let ast = crate::parse_log_query("bytes_received > bytes_sent").unwrap();
+	assert!(!check_expr(&ast.root, &logline, &tz).unwrap());
+}
+
+#[test]
+fn field_to_field_comparison_falls_back_when_one_side_is_not_a_field() {
+	use crate::{LogEntry, LogLevel, Prop};
+
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		props: vec![Prop {
+			key: "service".to_string(),
+			value: "auth".to_string().into(),
+		}],
+		msg: "".to_string(),
+		..Default::default()
+	};
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	// `auth` isn't a field, so this stays a field-vs-literal-string
+	// comparison rather than erroring out.
+	let ast = crate::parse_log_query("service = auth").unwrap();
+	assert!(check_expr(&ast.root, &logline, &tz).unwrap());
+}
+
+#[test]
+fn simplify_preserves_check_expr_semantics() {
+	use crate::{LogEntry, LogLevel, Prop};
+
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		props: vec![
+			Prop {
+				key: "service".to_string(),
+				value: "auth".to_string().into(),
+			},
+			Prop {
+				key: "duration_ms".to_string(),
+				value: "150".to_string().into(),
+			},
+		],
+		msg: "boom".to_string(),
+		..Default::default()
+	};
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let queries = [
+		"service = auth",
+		"(1 = 1) and service = auth",
+		"(1 = 2) or service = auth",
+		"service = auth and (1 = 1)",
+		"duration_ms > 100 and (1 = 1 or 2 = 3)",
+		r#"msg = "boom" or (1 = 2)"#,
+		"service = missing and duration_ms > 100",
+	];
+	for query in queries {
+		let ast = crate::parse_log_query(query).unwrap();
+		let simplified = simplify(&ast.root);
+		assert_eq!(
+			check_expr(&ast.root, &logline, &tz).unwrap(),
+			check_expr(&simplified, &logline, &tz).unwrap(),
+			"simplify changed check_expr result for query {:?}",
+			query
+		);
+	}
+}
+
+#[test]
+fn check_expr_evaluates_timestamp_against_now_minus_duration_without_simplify() {
+	use crate::{LogEntry, LogLevel};
+
+	let logline = LogEntry { timestamp: Utc::now(), level: LogLevel::Info, ..Default::default() };
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	// `now - 1h` is a spaced-out `Expr::Arith` (unlike the single-token
+	// `now-1h`), so this exercises `resolve_arith_operand`'s `Value::Date`
+	// arm directly on the raw, unsimplified AST.
+	let raw = crate::query_parsing::OptimizationLevel::None;
+	let recent = crate::query_parsing::parse_log_query_with_optimization("timestamp > now - 1h", raw).unwrap();
+	assert!(check_expr(&recent.root, &logline, &tz).unwrap());
+
+	let future = crate::query_parsing::parse_log_query_with_optimization("timestamp > now + 1h", raw).unwrap();
+	assert!(!check_expr(&future.root, &logline, &tz).unwrap());
+}
+
+#[test]
+fn aggregator_sums_grouped_by_prop() {
+	use crate::{LogEntry, LogLevel, Prop};
+
+	fn entry(service: &str, duration_ms: &str) -> LogEntry {
+		LogEntry {
+			timestamp: Utc::now(),
+			level: LogLevel::Info,
+			props: vec![
+				Prop {
+					key: "service".to_string(),
+					value: service.to_string().into(),
+				},
+				Prop {
+					key: "duration_ms".to_string(),
+					value: duration_ms.to_string().into(),
+				},
+			],
+			msg: "".to_string(),
+			..Default::default()
+		}
+	}
+
+	let mut agg = new_aggregator(Aggregate {
+		field: "duration_ms".to_string(),
+		op: AggrOp::Sum,
+		group_by: vec!["service".to_string()],
+		time_bucket: None,
+	});
+	agg.feed(&entry("auth", "10"));
+	agg.feed(&entry("auth", "15"));
+	agg.feed(&entry("search", "not-a-number"));
+	agg.feed(&entry("search", "5"));
+
+	let mut results = agg.finish();
+	results.sort_by(|a, b| format!("{:?}", a.0).cmp(&format!("{:?}", b.0)));
+
+	assert_eq!(results[0].0, vec![Value::String("auth".to_string())]);
+	assert_eq!(results[0].1, Value::Float(25.0));
+	// The non-numeric `duration_ms` is skipped, same as a failed
+	// `cmp_numeric` coercion: only the `5` contributes to the sum.
+	assert_eq!(results[1].0, vec![Value::String("search".to_string())]);
+	assert_eq!(results[1].1, Value::Float(5.0));
+}
+
+#[test]
+fn aggregator_count_distinct_without_group_by_reports_one_group() {
+	use crate::{LogEntry, LogLevel, Prop};
+
+	fn entry(user_id: &str) -> LogEntry {
+		LogEntry {
+			timestamp: Utc::now(),
+			level: LogLevel::Info,
+			props: vec![Prop {
+				key: "user_id".to_string(),
+				value: user_id.to_string().into(),
+			}],
+			msg: "".to_string(),
+			..Default::default()
+		}
+	}
+
+	let mut agg = new_aggregator(Aggregate {
+		field: "user_id".to_string(),
+		op: AggrOp::CountDistinct,
+		group_by: vec![],
+		time_bucket: None,
+	});
+	agg.feed(&entry("1"));
+	agg.feed(&entry("2"));
+	agg.feed(&entry("1"));
+
+	let results = agg.finish();
+	assert_eq!(results.len(), 1);
+	assert!(results[0].0.is_empty());
+	assert_eq!(results[0].1, Value::Number(2));
+}
+
+#[test]
+fn aggregator_last_reports_the_most_recently_fed_value_per_group() {
+	use crate::{LogEntry, LogLevel, Prop};
+
+	fn entry(service: &str, duration_ms: &str) -> LogEntry {
+		LogEntry {
+			timestamp: Utc::now(),
+			level: LogLevel::Info,
+			props: vec![
+				Prop {
+					key: "service".to_string(),
+					value: service.to_string().into(),
+				},
+				Prop {
+					key: "duration_ms".to_string(),
+					value: duration_ms.to_string().into(),
+				},
+			],
+			msg: "".to_string(),
+			..Default::default()
+		}
+	}
+
+	let mut agg = new_aggregator(Aggregate {
+		field: "duration_ms".to_string(),
+		op: AggrOp::Last,
+		group_by: vec!["service".to_string()],
+		time_bucket: None,
+	});
+	agg.feed(&entry("auth", "10"));
+	agg.feed(&entry("auth", "20"));
+
+	let results = agg.finish();
+	assert_eq!(results.len(), 1);
+	assert_eq!(results[0].1, Value::Float(20.0));
+}
+
+#[test]
+fn aggregator_time_bucket_splits_groups_by_hour() {
+	use crate::LogEntry;
+	use chrono::TimeZone;
+
+	fn entry(hour: u32) -> LogEntry {
+		LogEntry {
+			timestamp: Utc.with_ymd_and_hms(2024, 1, 1, hour, 0, 0).unwrap(),
+			..Default::default()
+		}
+	}
+
+	let mut agg = new_aggregator(Aggregate {
+		field: "msg".to_string(),
+		op: AggrOp::Count,
+		group_by: vec![],
+		time_bucket: Some(TimeBucket::Hour),
+	});
+	agg.feed(&entry(10));
+	agg.feed(&entry(10));
+	agg.feed(&entry(11));
+
+	let results = agg.finish();
+	assert_eq!(results.len(), 2);
+	for (key, count) in &results {
+		assert_eq!(key.len(), 1);
+		if *count == Value::Number(2) {
+			assert_eq!(key[0], Value::String("2024-01-01T10".to_string()));
+		} else {
+			assert_eq!(*count, Value::Number(1));
+			assert_eq!(key[0], Value::String("2024-01-01T11".to_string()));
+		}
+	}
+}
+
+#[test]
+fn aggregate_filters_through_check_expr_before_feeding_the_aggregator() {
+	use crate::{LogEntry, LogLevel, Prop};
+
+	fn entry(service: &str, duration_ms: &str) -> LogEntry {
+		LogEntry {
+			timestamp: Utc::now(),
+			level: LogLevel::Info,
+			props: vec![
+				Prop {
+					key: "service".to_string(),
+					value: service.to_string().into(),
+				},
+				Prop {
+					key: "duration_ms".to_string(),
+					value: duration_ms.to_string().into(),
+				},
+			],
+			msg: "".to_string(),
+			..Default::default()
+		}
+	}
+
+	let entries = vec![entry("auth", "10"), entry("auth", "15"), entry("search", "100")];
+	let ast = crate::parse_log_query("service = auth").unwrap();
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+	let agg = Aggregate {
+		field: "duration_ms".to_string(),
+		op: AggrOp::Sum,
+		group_by: vec![],
+		time_bucket: None,
+	};
+
+	let results = aggregate(&ast.root, &entries, &agg, &tz).unwrap();
+	assert_eq!(results, vec![(vec![], Value::Float(25.0))]);
+}
+
+#[test]
+fn timestamp_in_matches_any_range_pair() {
+	use crate::query_parsing::{Condition, Operator, Value};
+	use crate::{LogEntry, LogLevel};
+	use chrono::{DateTime, NaiveDate, Utc};
+
+	fn day(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+		DateTime::<Utc>::from_utc(
+			NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+			Utc,
+		)
+	}
+
+	let ranges = Expr::Condition(Condition {
+		left: Box::new(Expr::Value(Value::String("timestamp".to_string()))),
+		operator: Operator::In,
+		right: Box::new(Expr::Value(Value::List(vec![
+			Value::List(vec![Value::Date(day(2024, 1, 1)), Value::Date(day(2024, 2, 1))]),
+			Value::List(vec![Value::Date(day(2024, 3, 1)), Value::Date(day(2024, 4, 1))]),
+		]))),
+	});
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let in_first_range = LogEntry {
+		timestamp: day(2024, 1, 15),
+		level: LogLevel::Info,
+		..Default::default()
+	};
+	assert!(check_expr(&ranges, &in_first_range, &tz).unwrap());
+
+	let between_ranges = LogEntry {
+		timestamp: day(2024, 2, 15),
+		level: LogLevel::Info,
+		..Default::default()
+	};
+	assert!(!check_expr(&ranges, &between_ranges, &tz).unwrap());
+
+	let not_ranges = Expr::Condition(Condition {
+		operator: Operator::NotIn,
+		..match ranges {
+			Expr::Condition(c) => c,
+			_ => unreachable!(),
+		}
+	});
+	assert!(check_expr(&not_ranges, &between_ranges, &tz).unwrap());
+}
+
 #[test]
 fn match_date_range_timestamp_greater_than() {
 	use crate::query_parsing::{Condition, Operator, Value};
@@ -1580,3 +3410,115 @@ fn match_date_range_year_greater_equal() {
 		"segment in 2025 should satisfy year >= 2024",
 	);
 }
+
+#[test]
+fn resolves_dotted_path_into_a_json_object_prop_value() {
+	use crate::{LogEntry, LogLevel, Prop};
+
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		props: vec![Prop {
+			key: "http".to_string(),
+			value: r#"{"request":{"method":"GET"}}"#.to_string().into(),
+		}],
+		msg: "".to_string(),
+		..Default::default()
+	};
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let ast = crate::parse_log_query(r#"http.request.method = "GET""#).unwrap();
+	assert!(check_expr(&ast.root, &logline, &tz).unwrap());
+
+	let ast = crate::parse_log_query(r#"http.request.method = "POST""#).unwrap();
+	assert!(!check_expr(&ast.root, &logline, &tz).unwrap());
+}
+
+#[test]
+fn resolves_array_index_into_a_json_array_prop_value() {
+	use crate::{LogEntry, LogLevel, Prop};
+
+	let logline = LogEntry {
+		timestamp: Utc::now(),
+		level: LogLevel::Info,
+		props: vec![Prop {
+			key: "tags".to_string(),
+			value: r#"["prod","eu"]"#.to_string().into(),
+		}],
+		msg: "".to_string(),
+		..Default::default()
+	};
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let ast = crate::parse_log_query(r#"tags.0 = "prod""#).unwrap();
+	assert!(check_expr(&ast.root, &logline, &tz).unwrap());
+
+	// A missing index doesn't match rather than erroring out.
+	let ast = crate::parse_log_query(r#"tags.5 = "prod""#).unwrap();
+	assert!(!check_expr(&ast.root, &logline, &tz).unwrap());
+}
+
+#[test]
+fn calls_lower_and_len_builtins_inside_a_condition() {
+	use crate::{LogEntry, LogLevel};
+
+	let logline =
+		LogEntry { timestamp: Utc::now(), level: LogLevel::Info, msg: "BOOM".to_string(), ..Default::default() };
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let ast = crate::parse_log_query(r#"lower(msg) = "boom""#).unwrap();
+	assert!(check_expr(&ast.root, &logline, &tz).unwrap());
+
+	let ast = crate::parse_log_query("len(msg) > 3").unwrap();
+	assert!(check_expr(&ast.root, &logline, &tz).unwrap());
+
+	let ast = crate::parse_log_query(r#"trim(msg) = "BOOM""#).unwrap();
+	assert!(check_expr(&ast.root, &logline, &tz).unwrap());
+
+	let ast = crate::parse_log_query(r#"substr(msg, 0, 2) = "BO""#).unwrap();
+	assert!(check_expr(&ast.root, &logline, &tz).unwrap());
+}
+
+#[test]
+fn unknown_function_call_reports_a_clear_error() {
+	use crate::{LogEntry, LogLevel};
+
+	let logline = LogEntry { timestamp: Utc::now(), level: LogLevel::Info, ..Default::default() };
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let ast = crate::parse_log_query(r#"nope(msg) = "boom""#).unwrap();
+	let err = check_expr(&ast.root, &logline, &tz).unwrap_err();
+	assert!(err.contains("Unknown function"), "unexpected error: {}", err);
+}
+
+#[test]
+fn wrong_arity_function_call_reports_a_clear_error() {
+	use crate::{LogEntry, LogLevel};
+
+	let logline = LogEntry { timestamp: Utc::now(), level: LogLevel::Info, ..Default::default() };
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let ast = crate::parse_log_query(r#"lower(msg, msg) = "boom""#).unwrap();
+	let err = check_expr(&ast.root, &logline, &tz).unwrap_err();
+	assert!(err.contains("expects"), "unexpected error: {}", err);
+}
+
+#[test]
+fn register_fn_extends_the_default_registry() {
+	use crate::{LogEntry, LogLevel};
+
+	register_fn("reverse", |args: &[Value]| -> Result<Value, String> {
+		let s = match args.first() {
+			Some(Value::String(s)) => s,
+			_ => return Err("reverse() expects a string argument".to_string()),
+		};
+		Ok(Value::String(s.chars().rev().collect()))
+	});
+
+	let logline =
+		LogEntry { timestamp: Utc::now(), level: LogLevel::Info, msg: "boom".to_string(), ..Default::default() };
+	let tz = chrono::FixedOffset::east_opt(0).unwrap();
+
+	let ast = crate::parse_log_query(r#"reverse(msg) = "moob""#).unwrap();
+	assert!(check_expr(&ast.root, &logline, &tz).unwrap());
+}
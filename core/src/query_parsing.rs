@@ -1,11 +1,12 @@
 use crate::LogEntry;
 use chrono::{DateTime, NaiveDate, Utc};
-use serde::de::value;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
 use crate::query_eval::check_expr;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum Operator {
 	GreaterThan,
 	GreaterThanOrEqual,
@@ -23,34 +24,124 @@ pub enum Operator {
 	NotMatches,
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
 pub enum Value {
 	Date(DateTime<Utc>),
 	String(String),
 	Regex(String),
 	Number(i64),
+	Float(f64),
+	Bool(bool),
+	/// A bare duration literal (`15m`, `2h`), stored as milliseconds. Relative
+	/// timestamp expressions like `now-15m` resolve straight to a `Date` at
+	/// parse time and don't go through this variant; `Duration` is for
+	/// comparing duration-shaped fields (`duration_ms > 5m`).
+	Duration(i64),
+	/// A bare byte-size literal (`1GB`, `512KB`), stored as a byte count, for
+	/// comparing size-shaped fields (`bytes >= 1GB`).
+	Bytes(u64),
 	List(Vec<Value>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// Numbers and floats need to compare equal/ordered across variants (`duration_ms > 12.5`
+// should work whether the field came in as a `Number` or a `Float`), so `Value` can't just
+// derive `PartialEq`/`PartialOrd`.
+impl PartialEq for Value {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Value::Date(a), Value::Date(b)) => a == b,
+			(Value::String(a), Value::String(b)) => a == b,
+			(Value::Regex(a), Value::Regex(b)) => a == b,
+			(Value::Number(a), Value::Number(b)) => a == b,
+			(Value::Float(a), Value::Float(b)) => a == b,
+			(Value::Number(a), Value::Float(b)) | (Value::Float(b), Value::Number(a)) => {
+				*a as f64 == *b
+			}
+			(Value::Bool(a), Value::Bool(b)) => a == b,
+			(Value::Duration(a), Value::Duration(b)) => a == b,
+			(Value::Bytes(a), Value::Bytes(b)) => a == b,
+			(Value::List(a), Value::List(b)) => a == b,
+			_ => false,
+		}
+	}
+}
+
+impl PartialOrd for Value {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		match (self, other) {
+			(Value::Date(a), Value::Date(b)) => a.partial_cmp(b),
+			(Value::String(a), Value::String(b)) => a.partial_cmp(b),
+			(Value::Regex(a), Value::Regex(b)) => a.partial_cmp(b),
+			(Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+			(Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+			(Value::Number(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+			(Value::Float(a), Value::Number(b)) => a.partial_cmp(&(*b as f64)),
+			(Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+			(Value::Duration(a), Value::Duration(b)) => a.partial_cmp(b),
+			(Value::Bytes(a), Value::Bytes(b)) => a.partial_cmp(b),
+			(Value::List(a), Value::List(b)) => a.partial_cmp(b),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Condition {
 	pub left: Box<Expr>,
 	pub operator: Operator,
 	pub right: Box<Expr>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FieldAccess {
 	pub expr: Box<Expr>,
 	pub field: String,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ArithOp {
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Mod,
+}
+
+/// An arithmetic expression over numeric fields/field-access results, e.g.
+/// `bytes / 1024` in `bytes / 1024 > 500`. The parser only produces it as an
+/// operand of a [`Condition`]; evaluated on its own in boolean position it is
+/// true when its result is greater than zero.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Arith {
+	pub op: ArithOp,
+	pub left: Box<Expr>,
+	pub right: Box<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
 pub enum Expr {
 	Condition(Condition),
 	And(Box<Expr>, Box<Expr>),
 	Or(Box<Expr>, Box<Expr>),
+	Not(Box<Expr>),
 	FieldAccess(FieldAccess),
+	Arith(Arith),
+	/// `coalesce(a, b, ...)`: the first argument that "exists" supplies the
+	/// value. A bare name (`Value::String`) is always a field reference
+	/// (consistent with every other operand position in this language) and
+	/// is skipped if the log line lacks it; a non-string literal
+	/// (`coalesce(user_id, -1)`) always "exists" and acts as the fallback
+	/// default. Only usable as a `Condition` operand or in boolean position,
+	/// same as `Arith`.
+	Coalesce(Vec<Expr>),
+	/// A call to a function registered in `query_eval`'s `FN_REGISTRY` (see
+	/// `query_eval::register_fn`), e.g.
+	/// `lower(msg)` in `lower(msg) == "hello"`. Unlike `Coalesce`, `name`
+	/// isn't a fixed keyword — any identifier followed by `(` parses as a
+	/// `Call`, and an unknown name is only reported once evaluated.
+	Call { name: String, args: Vec<Expr> },
 	Value(Value),
 	Empty,
 }
@@ -61,31 +152,140 @@ impl Default for Expr {
 	}
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum OrderDir {
 	Asc,
 	Desc,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OrderBy {
-	fields: Vec<String>,
-	direction: OrderDir,
+	pub fields: Vec<String>,
+	pub direction: OrderDir,
 }
 
-#[derive(Debug, Clone, PartialEq, Default)]
+/// Serializable end-to-end: a `QueryAst` built from `parse_log_query` can be
+/// sent as-is over the wire (e.g. `LogSearcher`'s cluster fan-out POSTing a
+/// query to a peer node) and parsed back into the exact same AST, no
+/// re-stringifying through the query language required.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct QueryAst {
 	pub root: Expr,
 	pub order_by: Option<OrderBy>,
 	pub limit: Option<usize>,
 	pub offset: Option<usize>,
 	pub end_date: Option<DateTime<Utc>>,
+	/// Resume point for paginated `find_logs` scans, set from a previous
+	/// page's [`LogCursor`] instead of backing `end_date` off by a
+	/// microsecond. `None` starts from `end_date` as usual.
+	pub start_after: Option<LogCursor>,
 }
 
 impl QueryAst {
 	pub fn matches(&self, entry: &LogEntry) -> Result<bool, String> {
 		check_expr(&self.root, entry)
 	}
+
+	/// Rewrites `root` via [`optimize`]: folds constants, flattens nested
+	/// `and`/`or` chains, drops duplicate and unsatisfiable branches. A no-op
+	/// when nothing can be simplified.
+	pub fn optimize(mut self) -> Self {
+		self.root = optimize(self.root);
+		self
+	}
+}
+
+/// Opaque resume point for `find_logs` pagination: identifies the exact log
+/// entry to continue scanning after, by timestamp plus its position within
+/// the segment it came from (`segment_id: None` for the in-memory buffer).
+/// Pinpointing the entry rather than just its timestamp means two entries
+/// sharing a timestamp (common under bursty ingestion) never get skipped or
+/// re-delivered across a page boundary, unlike subtracting a microsecond
+/// from `end_date`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogCursor {
+	pub timestamp: DateTime<Utc>,
+	pub segment_id: Option<u32>,
+	pub intra_segment_index: usize,
+}
+
+impl LogCursor {
+	/// Encodes the cursor as an opaque, URL-safe base64 token so HTTP
+	/// clients can round-trip it as an opaque `cursor` query param without
+	/// needing to understand its contents.
+	pub fn to_token(&self) -> String {
+		let raw = format!(
+			"{}:{}:{}",
+			self.timestamp.timestamp_micros(),
+			self.segment_id.map(|id| id.to_string()).unwrap_or_default(),
+			self.intra_segment_index,
+		);
+		base64url_encode(raw.as_bytes())
+	}
+
+	pub fn from_token(token: &str) -> Option<Self> {
+		let raw = base64url_decode(token)?;
+		let raw = String::from_utf8(raw).ok()?;
+		let mut parts = raw.splitn(3, ':');
+		let timestamp = DateTime::<Utc>::from_timestamp_micros(parts.next()?.parse().ok()?)?;
+		let segment_id = match parts.next()? {
+			"" => None,
+			s => Some(s.parse().ok()?),
+		};
+		let intra_segment_index = parts.next()?.parse().ok()?;
+		Some(Self {
+			timestamp,
+			segment_id,
+			intra_segment_index,
+		})
+	}
+}
+
+const BASE64URL_ALPHABET: &[u8; 64] =
+	b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn base64url_encode(bytes: &[u8]) -> String {
+	let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+	for chunk in bytes.chunks(3) {
+		let b0 = chunk[0];
+		let b1 = chunk.get(1).copied();
+		let b2 = chunk.get(2).copied();
+		out.push(BASE64URL_ALPHABET[(b0 >> 2) as usize] as char);
+		out.push(
+			BASE64URL_ALPHABET[(((b0 & 0x03) << 4) | (b1.unwrap_or(0) >> 4)) as usize] as char,
+		);
+		if let Some(b1) = b1 {
+			out.push(
+				BASE64URL_ALPHABET[(((b1 & 0x0f) << 2) | (b2.unwrap_or(0) >> 6)) as usize] as char,
+			);
+		}
+		if let Some(b2) = b2 {
+			out.push(BASE64URL_ALPHABET[(b2 & 0x3f) as usize] as char);
+		}
+	}
+	out
+}
+
+fn base64url_decode(input: &str) -> Option<Vec<u8>> {
+	fn value(c: u8) -> Option<u8> {
+		BASE64URL_ALPHABET.iter().position(|b| *b == c).map(|p| p as u8)
+	}
+	let chars: Vec<u8> = input.bytes().collect();
+	let mut out = Vec::with_capacity(chars.len() / 4 * 3);
+	for chunk in chars.chunks(4) {
+		let v0 = value(chunk[0])?;
+		let v1 = value(*chunk.get(1)?)?;
+		out.push((v0 << 2) | (v1 >> 4));
+		if let Some(&c2) = chunk.get(2) {
+			let v2 = value(c2)?;
+			out.push(((v1 & 0x0f) << 4) | (v2 >> 2));
+			if let Some(&c3) = chunk.get(3) {
+				let v3 = value(c3)?;
+				out.push(((v2 & 0x03) << 6) | v3);
+			}
+		}
+	}
+	Some(out)
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -99,37 +299,311 @@ enum Token {
 	Operator(Operator),
 	Value(Value),
 	Comma,
+	Order,
+	By,
+	Limit,
+	Offset,
+	Asc,
+	Desc,
+	Not,
+	Arith(ArithOp),
+}
+
+/// A 1-based line/column location in a query string, used to point parse
+/// errors at the offending token instead of just describing the problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+	pub line: usize,
+	pub column: usize,
+	/// 0-based byte offset into the source, alongside the 1-based
+	/// line/column, so editor tooling can map a span back onto the raw text
+	/// without re-scanning it.
+	pub offset: usize,
+}
+
+impl Position {
+	fn start() -> Self {
+		Position { line: 1, column: 1, offset: 0 }
+	}
+}
+
+/// The range a token (or a whole sub-expression) spans in the source query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+	pub start: Position,
+	pub end: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SpannedToken {
+	token: Token,
+	span: Span,
+}
+
+/// A lex/parse failure with the span that caused it, before the source
+/// query text is known to the function that raised it.
+#[derive(Debug, Clone, PartialEq)]
+struct RawParseError {
+	message: String,
+	span: Span,
+}
+
+impl RawParseError {
+	fn new(span: Span, message: impl Into<String>) -> Self {
+		RawParseError { message: message.into(), span }
+	}
+
+	/// Attaches the source query text, turning this into a [`ParseError`]
+	/// that can render a caret under the offending span.
+	fn into_parse_error(self, source: &str) -> ParseError {
+		ParseError {
+			message: self.message,
+			span: self.span,
+			source: source.to_string(),
+		}
+	}
+}
+
+/// A lex/parse failure with the source span that caused it, so callers can
+/// render a `^---` caret under the offending token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+	pub message: String,
+	pub span: Span,
+	source: String,
+}
+
+impl std::fmt::Display for ParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		writeln!(
+			f,
+			"{} at line {}, column {}",
+			self.message, self.span.start.line, self.span.start.column
+		)?;
+		if let Some(line) = self.source.lines().nth(self.span.start.line - 1) {
+			writeln!(f, "{}", line)?;
+			let caret_column = self.span.start.column.saturating_sub(1);
+			let width = if self.span.end.line == self.span.start.line {
+				self.span.end.column.saturating_sub(self.span.start.column).max(1)
+			} else {
+				1
+			};
+			write!(f, "{}{}", " ".repeat(caret_column), "^".repeat(width))?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for ParseError {}
+
+type CharIter<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn advance(chars: &mut CharIter, pos: &mut Position) -> Option<char> {
+	let c = chars.next()?;
+	pos.offset += c.len_utf8();
+	if c == '\n' {
+		pos.line += 1;
+		pos.column = 1;
+	} else {
+		pos.column += 1;
+	}
+	Some(c)
+}
+
+/// Consume a numeric literal (`150`, `1.5`, `.5`, `3e9`) starting at the
+/// current position, returning its raw text. Stops before a trailing `.`
+/// that isn't followed by a digit, so field-access chains like `foo.1` don't
+/// get swallowed into the number.
+fn scan_number(chars: &mut CharIter, pos: &mut Position) -> String {
+	let mut literal = String::new();
+	let mut seen_dot = false;
+	let mut seen_exp = false;
+	while let Some(&c) = chars.peek() {
+		if c.is_ascii_digit() {
+			literal.push(advance(chars, pos).unwrap());
+		} else if c == '.' && !seen_dot && !seen_exp {
+			if matches!(chars.clone().nth(1), Some(d) if d.is_ascii_digit()) {
+				seen_dot = true;
+				literal.push(advance(chars, pos).unwrap());
+			} else {
+				break;
+			}
+		} else if (c == 'e' || c == 'E') && !seen_exp && !literal.is_empty() {
+			let mut lookahead = chars.clone();
+			lookahead.next();
+			let exponent_follows = matches!(lookahead.peek(), Some(d) if d.is_ascii_digit())
+				|| matches!(lookahead.peek(), Some('+') | Some('-'));
+			if exponent_follows {
+				seen_exp = true;
+				literal.push(advance(chars, pos).unwrap());
+				if matches!(chars.peek(), Some('+') | Some('-')) {
+					literal.push(advance(chars, pos).unwrap());
+				}
+			} else {
+				break;
+			}
+		} else {
+			break;
+		}
+	}
+	literal
+}
+
+/// Turn a scanned numeric literal into a `Value`, preferring `Number` when
+/// the text has no fractional part or exponent, and `Float` otherwise.
+fn parse_number_literal(literal: &str) -> Value {
+	if let Ok(num) = literal.parse::<i64>() {
+		Value::Number(num)
+	} else {
+		Value::Float(literal.parse::<f64>().unwrap_or(0.0))
+	}
+}
+
+/// Peek the contiguous run of characters up to whitespace, a paren, `.` or
+/// `,`, without consuming any of it, so a literal directly followed by a
+/// list comma (`2024-01-01,`) is still recognised. Lets the digit-start branch classify a token (date, duration, plain
+/// number) before committing to a scan strategy.
+fn peek_word(chars: &CharIter) -> String {
+	let mut lookahead = chars.clone();
+	let mut word = String::new();
+	while let Some(&c) = lookahead.peek() {
+		if c.is_whitespace() || c == '(' || c == ')' || c == '.' || c == ',' {
+			break;
+		}
+		word.push(c);
+		lookahead.next();
+	}
+	word
+}
+
+fn duration_unit_millis(unit: char) -> Option<i64> {
+	match unit {
+		's' => Some(1_000),
+		'm' => Some(60_000),
+		'h' => Some(3_600_000),
+		'd' => Some(86_400_000),
+		'w' => Some(604_800_000),
+		_ => None,
+	}
+}
+
+fn bytes_unit_multiplier(unit: &str) -> Option<u64> {
+	match unit {
+		"B" => Some(1),
+		"KB" => Some(1_024),
+		"MB" => Some(1_024 * 1_024),
+		"GB" => Some(1_024 * 1_024 * 1_024),
+		"TB" => Some(1_024 * 1_024 * 1_024 * 1_024),
+		_ => None,
+	}
+}
+
+/// Parse a bare byte-size literal (`512KB`, `1GB`) into a byte count. Units
+/// are binary (`1KB` = 1024 bytes), checked longest-suffix-first so `KB`
+/// wins over a bare trailing `B`.
+fn parse_bytes_literal(word: &str) -> Option<u64> {
+	for unit in ["TB", "GB", "MB", "KB", "B"] {
+		let Some(digits) = word.strip_suffix(unit) else {
+			continue;
+		};
+		if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+			continue;
+		}
+		return Some(digits.parse::<u64>().ok()? * bytes_unit_multiplier(unit)?);
+	}
+	None
+}
+
+/// Parse a bare duration literal (`15m`, `2h`, `30s`) into milliseconds.
+fn parse_duration_literal(word: &str) -> Option<i64> {
+	let mut chars = word.chars();
+	let unit = chars.next_back()?;
+	let millis_per_unit = duration_unit_millis(unit)?;
+	let digits = chars.as_str();
+	if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+		return None;
+	}
+	Some(digits.parse::<i64>().ok()? * millis_per_unit)
+}
+
+/// Parse an absolute ISO-8601/RFC3339 literal (`2024-01-01`,
+/// `2024-01-01T12:30:00Z`) into a UTC point in time.
+fn parse_timestamp_literal(word: &str) -> Option<DateTime<Utc>> {
+	if let Ok(dt) = DateTime::parse_from_rfc3339(word) {
+		return Some(dt.with_timezone(&Utc));
+	}
+	let date = NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()?;
+	Some(DateTime::<Utc>::from_utc(date.and_hms_opt(0, 0, 0)?, Utc))
 }
 
-/// Tokenize the input string into a sequence of `Token`s.
-fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+/// Resolve `now`, `now+15m`, `now-2h` etc. to an absolute point in time,
+/// relative to when the query is parsed.
+fn resolve_relative_now(word: &str) -> Option<DateTime<Utc>> {
+	let now = Utc::now();
+	let rest = word.strip_prefix("now")?;
+	if rest.is_empty() {
+		return Some(now);
+	}
+	let mut chars = rest.chars();
+	let sign = match chars.next()? {
+		'+' => 1,
+		'-' => -1,
+		_ => return None,
+	};
+	let offset_millis = parse_duration_literal(chars.as_str())?;
+	Some(now + chrono::Duration::milliseconds(sign * offset_millis))
+}
+
+/// Resolve `today`, `today+1d`, `today-7d` etc. to UTC midnight of the
+/// current day, offset by the given duration.
+fn resolve_relative_today(word: &str) -> Option<DateTime<Utc>> {
+	let midnight = Utc::now().date_naive().and_hms_opt(0, 0, 0)?;
+	let today = DateTime::<Utc>::from_utc(midnight, Utc);
+	let rest = word.strip_prefix("today")?;
+	if rest.is_empty() {
+		return Some(today);
+	}
+	let mut chars = rest.chars();
+	let sign = match chars.next()? {
+		'+' => 1,
+		'-' => -1,
+		_ => return None,
+	};
+	let offset_millis = parse_duration_literal(chars.as_str())?;
+	Some(today + chrono::Duration::milliseconds(sign * offset_millis))
+}
+
+/// Tokenize the input string into a sequence of spanned `Token`s.
+fn tokenize(input: &str) -> Result<Vec<SpannedToken>, RawParseError> {
 	let mut tokens = Vec::new();
 	let mut chars = input.chars().peekable();
+	let mut pos = Position::start();
 
 	while let Some(&c) = chars.peek() {
+		let start = pos;
 		match c {
 			'(' => {
-				tokens.push(Token::OpenParen);
-				chars.next();
+				advance(&mut chars, &mut pos);
+				tokens.push(SpannedToken { token: Token::OpenParen, span: Span { start, end: pos } });
 			}
 			')' => {
-				tokens.push(Token::CloseParen);
-				chars.next();
+				advance(&mut chars, &mut pos);
+				tokens.push(SpannedToken { token: Token::CloseParen, span: Span { start, end: pos } });
 			}
 			'.' => {
-				tokens.push(Token::Dot);
-				chars.next();
+				advance(&mut chars, &mut pos);
+				tokens.push(SpannedToken { token: Token::Dot, span: Span { start, end: pos } });
 			}
 			' ' | '\t' | '\n' => {
-				chars.next();
+				advance(&mut chars, &mut pos);
 			}
 			'\"' => {
-				chars.next(); // consume opening quote
+				advance(&mut chars, &mut pos); // consume opening quote
 				let mut value = String::new();
-				while let Some(c) = chars.next() {
+				while let Some(c) = advance(&mut chars, &mut pos) {
 					match c {
 						'\\' => {
-							if let Some(next_c) = chars.next() {
+							if let Some(next_c) = advance(&mut chars, &mut pos) {
 								match next_c {
 									'\\' => value.push('\\'),
 									'"' => value.push('"'),
@@ -150,19 +624,128 @@ fn tokenize(input: &str) -> Result<Vec<Token>, String> {
 						other => value.push(other),
 					}
 				}
-				tokens.push(Token::Value(Value::String(value)));
+				tokens.push(SpannedToken {
+					token: Token::Value(Value::String(value)),
+					span: Span { start, end: pos },
+				});
 			}
 			'/' => {
-				chars.next(); // consume opening slash
-				let mut pattern = String::new();
-				while let Some(c) = chars.next() {
-					if c == '/' {
-						break;
-					} else {
-						pattern.push(c);
+				// `/` opens a regex literal after `matches`/`not matches`;
+				// anywhere else (`bytes / 1024`) it's arithmetic division.
+				let after_matches_operator = matches!(
+					tokens.last().map(|t| &t.token),
+					Some(Token::Operator(Operator::Matches)) | Some(Token::Operator(Operator::NotMatches))
+				);
+				if after_matches_operator {
+					advance(&mut chars, &mut pos); // consume opening slash
+					let mut pattern = String::new();
+					while let Some(c) = advance(&mut chars, &mut pos) {
+						if c == '/' {
+							break;
+						} else {
+							pattern.push(c);
+						}
+					}
+					tokens.push(SpannedToken {
+						token: Token::Value(Value::Regex(pattern)),
+						span: Span { start, end: pos },
+					});
+				} else {
+					advance(&mut chars, &mut pos);
+					tokens.push(SpannedToken {
+						token: Token::Arith(ArithOp::Div),
+						span: Span { start, end: pos },
+					});
+				}
+			}
+			'+' => {
+				advance(&mut chars, &mut pos);
+				tokens.push(SpannedToken {
+					token: Token::Arith(ArithOp::Add),
+					span: Span { start, end: pos },
+				});
+			}
+			'-' => {
+				// `-` before a digit is a negative-number literal when a value
+				// is expected here (after an operator, `(`, `and`/`or`/`not`, a
+				// comma, or at the very start); otherwise it's subtraction, as
+				// in `bytes - 1024`.
+				let next_is_digit = matches!(chars.clone().nth(1), Some(d) if d.is_ascii_digit());
+				let expects_operand = matches!(
+					tokens.last().map(|t| &t.token),
+					None | Some(Token::Operator(_))
+						| Some(Token::OpenParen) | Some(Token::And) | Some(Token::Or)
+						| Some(Token::Not) | Some(Token::Comma) | Some(Token::Arith(_))
+				);
+				if next_is_digit && expects_operand {
+					advance(&mut chars, &mut pos); // consume '-'
+					let literal = scan_number(&mut chars, &mut pos);
+					tokens.push(SpannedToken {
+						token: Token::Value(parse_number_literal(&format!("-{}", literal))),
+						span: Span { start, end: pos },
+					});
+				} else {
+					advance(&mut chars, &mut pos);
+					tokens.push(SpannedToken {
+						token: Token::Arith(ArithOp::Sub),
+						span: Span { start, end: pos },
+					});
+				}
+			}
+			'*' => {
+				advance(&mut chars, &mut pos);
+				tokens.push(SpannedToken {
+					token: Token::Arith(ArithOp::Mul),
+					span: Span { start, end: pos },
+				});
+			}
+			'%' => {
+				advance(&mut chars, &mut pos);
+				tokens.push(SpannedToken {
+					token: Token::Arith(ArithOp::Mod),
+					span: Span { start, end: pos },
+				});
+			}
+			'0'..='9' => {
+				let word = peek_word(&chars);
+				if let Some(dt) = parse_timestamp_literal(&word) {
+					for _ in 0..word.chars().count() {
+						advance(&mut chars, &mut pos);
 					}
+					tokens.push(SpannedToken {
+						token: Token::Value(Value::Date(dt)),
+						span: Span { start, end: pos },
+					});
+				} else if let Some(millis) = parse_duration_literal(&word) {
+					for _ in 0..word.chars().count() {
+						advance(&mut chars, &mut pos);
+					}
+					tokens.push(SpannedToken {
+						token: Token::Value(Value::Duration(millis)),
+						span: Span { start, end: pos },
+					});
+				} else if let Some(bytes) = parse_bytes_literal(&word) {
+					for _ in 0..word.chars().count() {
+						advance(&mut chars, &mut pos);
+					}
+					tokens.push(SpannedToken {
+						token: Token::Value(Value::Bytes(bytes)),
+						span: Span { start, end: pos },
+					});
+				} else {
+					let literal = scan_number(&mut chars, &mut pos);
+					tokens.push(SpannedToken {
+						token: Token::Value(parse_number_literal(&literal)),
+						span: Span { start, end: pos },
+					});
 				}
-				tokens.push(Token::Value(Value::Regex(pattern)));
+			}
+			'.' if matches!(chars.clone().nth(1), Some(d) if d.is_ascii_digit()) => {
+				let literal = scan_number(&mut chars, &mut pos);
+				tokens.push(SpannedToken {
+					token: Token::Value(parse_number_literal(&literal)),
+					span: Span { start, end: pos },
+				});
 			}
 			_ => {
 				let mut word = String::new();
@@ -171,57 +754,109 @@ fn tokenize(input: &str) -> Result<Vec<Token>, String> {
 					if c.is_whitespace() || c == '(' || c == ')' || c == '.' {
 						break;
 					}
-					word.push(chars.next().unwrap());
+					word.push(advance(&mut chars, &mut pos).unwrap());
 				}
+				let span = Span { start, end: pos };
 
 				match word.as_str() {
-					"," => tokens.push(Token::Comma),
-					"and" => tokens.push(Token::And),
-					"or" => tokens.push(Token::Or),
-					"&&" => tokens.push(Token::And),
-					"||" => tokens.push(Token::Or),
-					">" => tokens.push(Token::Operator(Operator::GreaterThan)),
-					"<" => tokens.push(Token::Operator(Operator::LessThan)),
-					">=" => tokens.push(Token::Operator(Operator::GreaterThanOrEqual)),
-					"<=" => tokens.push(Token::Operator(Operator::LessThanOrEqual)),
-					"=" => tokens.push(Token::Operator(Operator::Equal)),
-					"!=" => tokens.push(Token::Operator(Operator::NotEqual)),
-					"like" => tokens.push(Token::Operator(Operator::Like)),
-					"in" => tokens.push(Token::Operator(Operator::In)),
-					"exists" => tokens.push(Token::Operator(Operator::Exists)),
-					"matches" => tokens.push(Token::Operator(Operator::Matches)),
+					"," => tokens.push(SpannedToken { token: Token::Comma, span }),
+					"and" => tokens.push(SpannedToken { token: Token::And, span }),
+					"or" => tokens.push(SpannedToken { token: Token::Or, span }),
+					"&&" => tokens.push(SpannedToken { token: Token::And, span }),
+					"||" => tokens.push(SpannedToken { token: Token::Or, span }),
+					">" => tokens.push(SpannedToken { token: Token::Operator(Operator::GreaterThan), span }),
+					"<" => tokens.push(SpannedToken { token: Token::Operator(Operator::LessThan), span }),
+					">=" => tokens.push(SpannedToken { token: Token::Operator(Operator::GreaterThanOrEqual), span }),
+					"<=" => tokens.push(SpannedToken { token: Token::Operator(Operator::LessThanOrEqual), span }),
+					"=" => tokens.push(SpannedToken { token: Token::Operator(Operator::Equal), span }),
+					"!=" => tokens.push(SpannedToken { token: Token::Operator(Operator::NotEqual), span }),
+					"like" => tokens.push(SpannedToken { token: Token::Operator(Operator::Like), span }),
+					"in" => tokens.push(SpannedToken { token: Token::Operator(Operator::In), span }),
+					"exists" => tokens.push(SpannedToken { token: Token::Operator(Operator::Exists), span }),
+					"matches" => tokens.push(SpannedToken { token: Token::Operator(Operator::Matches), span }),
+					"=~" => tokens.push(SpannedToken { token: Token::Operator(Operator::Matches), span }),
+					"!~" => tokens.push(SpannedToken { token: Token::Operator(Operator::NotMatches), span }),
+					"order" => tokens.push(SpannedToken { token: Token::Order, span }),
+					"by" => tokens.push(SpannedToken { token: Token::By, span }),
+					"limit" => tokens.push(SpannedToken { token: Token::Limit, span }),
+					"offset" => tokens.push(SpannedToken { token: Token::Offset, span }),
+					"asc" => tokens.push(SpannedToken { token: Token::Asc, span }),
+					"desc" => tokens.push(SpannedToken { token: Token::Desc, span }),
 					"not" => {
-						// could be not like / not in / not exists / not matches
-						chars.next(); // consume the whitespace after "not"
+						// Could be `not like` / `not in` / `not exists` / `not matches`
+						// (a combined negated operator), or a standalone prefix `not`
+						// (e.g. `not (level = error)`). Peek the next word without
+						// committing to it so the standalone case leaves it for the
+						// next tokenizer iteration.
+						let mut lookahead = chars.clone();
+						let mut lookahead_pos = pos;
+						while matches!(lookahead.peek(), Some(c) if c.is_whitespace()) {
+							advance(&mut lookahead, &mut lookahead_pos);
+						}
 						let mut next_word = String::new();
-						while let Some(&c) = chars.peek() {
+						while let Some(&c) = lookahead.peek() {
 							if c.is_whitespace() || c == '(' || c == ')' || c == '.' {
 								break;
 							}
-							next_word.push(chars.next().unwrap());
+							next_word.push(advance(&mut lookahead, &mut lookahead_pos).unwrap());
+						}
+						let combined_op = match next_word.as_str() {
+							"like" => Some(Operator::NotLike),
+							"in" => Some(Operator::NotIn),
+							"exists" => Some(Operator::NotExists),
+							"matches" => Some(Operator::NotMatches),
+							_ => None,
+						};
+						match combined_op {
+							Some(op) => {
+								chars = lookahead;
+								pos = lookahead_pos;
+								tokens.push(SpannedToken { token: Token::Operator(op), span: Span { start, end: pos } });
+							}
+							None => tokens.push(SpannedToken { token: Token::Not, span }),
+						}
+					}
+					"!" => tokens.push(SpannedToken { token: Token::Not, span }),
+					_ if word == "now" || word.starts_with("now+") || word.starts_with("now-") => {
+						match resolve_relative_now(&word) {
+							Some(dt) => {
+								tokens.push(SpannedToken { token: Token::Value(Value::Date(dt)), span })
+							}
+							None => {
+								return Err(RawParseError::new(
+									span,
+									format!("Invalid relative time expression: {}", word),
+								))
+							}
 						}
-						match next_word.as_str() {
-							"like" => tokens.push(Token::Operator(Operator::NotLike)),
-							"in" => tokens.push(Token::Operator(Operator::NotIn)),
-							"exists" => tokens.push(Token::Operator(Operator::NotExists)),
-							"matches" => tokens.push(Token::Operator(Operator::NotMatches)),
-							other => {
-								return Err(format!("Unexpected token after 'not': {}", other))
+					}
+					_ if word == "today" || word.starts_with("today+") || word.starts_with("today-") => {
+						match resolve_relative_today(&word) {
+							Some(dt) => {
+								tokens.push(SpannedToken { token: Token::Value(Value::Date(dt)), span })
+							}
+							None => {
+								return Err(RawParseError::new(
+									span,
+									format!("Invalid relative time expression: {}", word),
+								))
 							}
 						}
 					}
 					_ => {
-						// Attempt to parse as date (dd.mm.yyyy), then number, else string
-						if let Ok(date) = NaiveDate::parse_from_str(&word, "%d.%m.%Y") {
-							tokens.push(Token::Value(Value::Date(DateTime::<Utc>::from_utc(
-								date.and_hms_opt(0, 0, 0).unwrap(),
-								Utc,
-							))));
+						// Attempt to parse as date (dd.mm.yyyy), then bool, then number, else string
+						let value = if let Ok(date) = NaiveDate::parse_from_str(&word, "%d.%m.%Y") {
+							Value::Date(DateTime::<Utc>::from_utc(date.and_hms_opt(0, 0, 0).unwrap(), Utc))
+						} else if word == "true" {
+							Value::Bool(true)
+						} else if word == "false" {
+							Value::Bool(false)
 						} else if let Ok(num) = word.parse::<i64>() {
-							tokens.push(Token::Value(Value::Number(num)));
+							Value::Number(num)
 						} else {
-							tokens.push(Token::Value(Value::String(word)));
-						}
+							Value::String(word)
+						};
+						tokens.push(SpannedToken { token: Token::Value(value), span });
 					}
 				}
 			}
@@ -230,43 +865,150 @@ fn tokenize(input: &str) -> Result<Vec<Token>, String> {
 	Ok(tokens)
 }
 
+/// The span to blame when an error occurs at `pos`: the token there if one
+/// exists, otherwise a zero-width span just past the last token (or the
+/// start of the query, if it's empty).
+fn error_span(tokens: &[SpannedToken], pos: usize) -> Span {
+	if let Some(t) = tokens.get(pos) {
+		return t.span;
+	}
+	match tokens.last() {
+		Some(t) => Span { start: t.span.end, end: t.span.end },
+		None => Span { start: Position::start(), end: Position::start() },
+	}
+}
+
+/// Parse the comma-separated values inside an `IN (...)`/`NOT IN (...)`
+/// list, starting just after the opening `(` and consuming its closing `)`.
+/// An item can itself be a parenthesized `(start, end)` pair — parsed as a
+/// nested `Value::List` — so `timestamp in ((start1, end1), (start2, end2))`
+/// means "within any of these ranges" rather than "equal to any of these
+/// instants".
+fn parse_in_list_items(tokens: &[SpannedToken], start: usize) -> Result<(Vec<Value>, usize), RawParseError> {
+	let len = tokens.len();
+	let mut pos = start;
+	let mut values = Vec::new();
+	while pos < len {
+		match &tokens[pos].token {
+			Token::Value(v) => {
+				values.push(v.clone());
+				pos += 1;
+			}
+			Token::OpenParen => {
+				let (pair, next_pos) = parse_in_list_items(tokens, pos + 1)?;
+				pos = next_pos;
+				values.push(Value::List(pair));
+			}
+			Token::Comma => {
+				pos += 1;
+			}
+			Token::CloseParen => {
+				pos += 1; // consume ')'
+				break;
+			}
+			other => {
+				return Err(RawParseError::new(
+					tokens[pos].span,
+					format!("Unexpected token in IN list: {:?}", other),
+				));
+			}
+		}
+	}
+	Ok((values, pos))
+}
+
+/// Parse a comma-separated, parenthesized argument list (`(a, b, c)`),
+/// starting just after the opening `(`. Each argument is a field-chain/
+/// arithmetic operand, same grammar as either side of a `Condition`. Shared
+/// by `coalesce(...)` and generic function calls — they differ only in how
+/// the resulting `Vec<Expr>` is wrapped.
+fn parse_call_args(
+	tokens: &[SpannedToken],
+	start: usize,
+	closing_of: &str,
+) -> Result<(Vec<Expr>, usize), RawParseError> {
+	let mut args = Vec::new();
+	let mut pos = start;
+	if tokens.get(pos).map(|t| &t.token) != Some(&Token::CloseParen) {
+		loop {
+			let (arg, next_pos) = parse_arith_expr(tokens, pos, 0)?;
+			args.push(arg);
+			pos = next_pos;
+			if tokens.get(pos).map(|t| &t.token) == Some(&Token::Comma) {
+				pos += 1;
+				continue;
+			}
+			break;
+		}
+	}
+	if tokens.get(pos).map(|t| &t.token) != Some(&Token::CloseParen) {
+		return Err(RawParseError::new(
+			error_span(tokens, pos),
+			format!("Expected ')' to close {}(...)", closing_of),
+		));
+	}
+	Ok((args, pos + 1))
+}
+
 /// Parse a possible chain of field accesses (e.g. `timestamp.hour`).
 /// If there's just a single token, it remains a `Value` expression.
 /// If there's a chain of dots, build up `FieldAccess` nodes.
-fn parse_field_chain(tokens: &[Token], start: usize) -> Result<(Expr, usize), String> {
+fn parse_field_chain(tokens: &[SpannedToken], start: usize) -> Result<(Expr, usize), RawParseError> {
 	if start >= tokens.len() {
-		return Err("No tokens to parse for field/value".into());
+		return Err(RawParseError::new(
+			error_span(tokens, start),
+			"No tokens to parse for field/value",
+		));
 	}
 
-	let (mut expr, mut pos) = match &tokens[start] {
+	let (mut expr, mut pos) = match &tokens[start].token {
 		Token::OpenParen => {
 			// parse sub-expression in parentheses
 			let (subexpr, next_pos) = parse_expression(tokens, start + 1)?;
 			if next_pos >= tokens.len() {
-				return Err("Missing closing parenthesis".into());
+				return Err(RawParseError::new(
+					error_span(tokens, next_pos),
+					"Missing closing parenthesis",
+				));
 			}
-			if tokens[next_pos] != Token::CloseParen {
-				return Err("Expected ')'".into());
+			if tokens[next_pos].token != Token::CloseParen {
+				return Err(RawParseError::new(tokens[next_pos].span, "Expected ')'"));
 			}
 			(subexpr, next_pos + 1)
 		}
+		Token::Value(Value::String(name))
+			if name == "coalesce"
+				&& tokens.get(start + 1).map(|t| &t.token) == Some(&Token::OpenParen) =>
+		{
+			let (args, next_pos) = parse_call_args(tokens, start + 2, "coalesce")?;
+			(Expr::Coalesce(args), next_pos)
+		}
+		Token::Value(Value::String(name))
+			if tokens.get(start + 1).map(|t| &t.token) == Some(&Token::OpenParen) =>
+		{
+			let (args, next_pos) = parse_call_args(tokens, start + 2, name)?;
+			(Expr::Call { name: name.clone(), args }, next_pos)
+		}
 		Token::Value(val) => (Expr::Value(val.clone()), start + 1),
 		other => {
-			return Err(format!(
-				"Unexpected token {:?} while expecting value or '('",
-				other
+			return Err(RawParseError::new(
+				tokens[start].span,
+				format!("Unexpected token {:?} while expecting value or '('", other),
 			));
 		}
 	};
 
 	// Possibly parse .field .another etc
 	while pos < tokens.len() {
-		if let Token::Dot = tokens[pos] {
+		if let Token::Dot = tokens[pos].token {
 			let next_pos = pos + 1;
 			if next_pos >= tokens.len() {
-				return Err("Expected field name after '.'".into());
+				return Err(RawParseError::new(
+					error_span(tokens, next_pos),
+					"Expected field name after '.'",
+				));
 			}
-			match &tokens[next_pos] {
+			match &tokens[next_pos].token {
 				Token::Value(Value::String(field_name)) => {
 					expr = Expr::FieldAccess(FieldAccess {
 						expr: Box::new(expr),
@@ -275,9 +1017,9 @@ fn parse_field_chain(tokens: &[Token], start: usize) -> Result<(Expr, usize), St
 					pos = next_pos + 1;
 				}
 				other => {
-					return Err(format!(
-						"Expected identifier after '.', but found: {:?}",
-						other
+					return Err(RawParseError::new(
+						tokens[next_pos].span,
+						format!("Expected identifier after '.', but found: {:?}", other),
 					));
 				}
 			}
@@ -289,12 +1031,62 @@ fn parse_field_chain(tokens: &[Token], start: usize) -> Result<(Expr, usize), St
 	Ok((expr, pos))
 }
 
+/// `+`/`-` and `*`/`/`/`%`'s binding power for [`parse_arith_expr`].
+/// `*`/`/`/`%` bind tighter than `+`/`-`, and arithmetic as a whole binds
+/// tighter than any comparison operator, so `bytes / 1024 > 500` parses as
+/// `(bytes / 1024) > 500` rather than `bytes / (1024 > 500)`.
+fn arith_binding_power(token: &Token) -> Option<(u8, u8)> {
+	match token {
+		Token::Arith(ArithOp::Add) | Token::Arith(ArithOp::Sub) => Some((1, 2)),
+		Token::Arith(ArithOp::Mul) | Token::Arith(ArithOp::Div) | Token::Arith(ArithOp::Mod) => {
+			Some((3, 4))
+		}
+		_ => None,
+	}
+}
+
+/// Precedence-climbing parser for a field-chain/value optionally followed by
+/// `+ - * /` arithmetic over other field-chains/values, e.g.
+/// `timestamp.hour - timestamp.minute`. A field-chain with no arithmetic
+/// operator after it is returned unchanged, so ordinary conditions parse to
+/// the exact same `Expr` shape as before this existed. `+ - * / %` are all
+/// supported, with `* / %` binding tighter per [`arith_binding_power`].
+fn parse_arith_expr(
+	tokens: &[SpannedToken],
+	start: usize,
+	min_bp: u8,
+) -> Result<(Expr, usize), RawParseError> {
+	let (mut left, mut pos) = parse_field_chain(tokens, start)?;
+
+	while pos < tokens.len() {
+		let Some((lbp, rbp)) = arith_binding_power(&tokens[pos].token) else {
+			break;
+		};
+		if lbp < min_bp {
+			break;
+		}
+		let op = match &tokens[pos].token {
+			Token::Arith(op) => op.clone(),
+			_ => unreachable!(),
+		};
+		pos += 1;
+		let (right, new_pos) = parse_arith_expr(tokens, pos, rbp)?;
+		left = Expr::Arith(Arith { op, left: Box::new(left), right: Box::new(right) });
+		pos = new_pos;
+	}
+
+	Ok((left, pos))
+}
+
 /// Parse a condition of the form `<expr> <operator> <expr>`.
 /// Handles special cases like `field EXISTS` or `field IN ( ... )`.
-fn parse_condition(tokens: &[Token], start: usize) -> Result<(Expr, usize), String> {
+fn parse_condition(tokens: &[SpannedToken], start: usize) -> Result<(Expr, usize), RawParseError> {
 	let len = tokens.len();
 	if start >= len {
-		return Err("No tokens left for condition".into());
+		return Err(RawParseError::new(
+			error_span(tokens, start),
+			"No tokens left for condition",
+		));
 	}
 
 	// Check for `<expr> EXISTS` / `<expr> NOT EXISTS`
@@ -302,7 +1094,7 @@ fn parse_condition(tokens: &[Token], start: usize) -> Result<(Expr, usize), Stri
 		if let (
 			Token::Value(left_val),
 			Token::Operator(ref op @ (Operator::Exists | Operator::NotExists)),
-		) = (&tokens[start], &tokens[start + 1])
+		) = (&tokens[start].token, &tokens[start + 1].token)
 		{
 			return Ok((
 				Expr::Condition(Condition {
@@ -315,11 +1107,12 @@ fn parse_condition(tokens: &[Token], start: usize) -> Result<(Expr, usize), Stri
 		}
 	}
 
-	// For a normal condition, parse the left side (potentially a field chain).
-	let (left_expr, mut pos) = parse_field_chain(tokens, start)?;
+	// For a normal condition, parse the left side (potentially a field chain,
+	// or an arithmetic expression over field chains/values).
+	let (left_expr, mut pos) = parse_arith_expr(tokens, start, 0)?;
 	// If the next token is a boolean operator or we have reached the end, and the left_expr is a bare string,
 	// wrap it as a default text search on the "msg" field.
-	if pos >= tokens.len() || matches!(tokens[pos], Token::And | Token::Or | Token::CloseParen) {
+	if pos >= tokens.len() || matches!(tokens[pos].token, Token::And | Token::Or | Token::CloseParen) {
 		if let Expr::Value(Value::String(text)) = left_expr {
 			return Ok((
 				Expr::Condition(Condition {
@@ -332,10 +1125,11 @@ fn parse_condition(tokens: &[Token], start: usize) -> Result<(Expr, usize), Stri
 		}
 	}
 	if pos >= len {
-		return Err("Missing operator".into());
+		return Err(RawParseError::new(error_span(tokens, pos), "Missing operator"));
 	}
 
-	let op_token = &tokens[pos];
+	let op_token = &tokens[pos].token;
+	let op_span = tokens[pos].span;
 	pos += 1;
 
 	match op_token {
@@ -344,32 +1138,17 @@ fn parse_condition(tokens: &[Token], start: usize) -> Result<(Expr, usize), Stri
 			// handle <expr> IN (...) or <expr> NOT IN (...)
 			if operator == Operator::In || operator == Operator::NotIn {
 				if pos >= len {
-					return Err("Expected '(' after IN".into());
+					return Err(RawParseError::new(
+						error_span(tokens, pos),
+						"Expected '(' after IN",
+					));
 				}
-				if tokens[pos] != Token::OpenParen {
-					return Err("Expected '(' after IN".into());
+				if tokens[pos].token != Token::OpenParen {
+					return Err(RawParseError::new(tokens[pos].span, "Expected '(' after IN"));
 				}
 				pos += 1; // consume '('
-				let mut values = Vec::new();
-				while pos < len {
-					match &tokens[pos] {
-						Token::Value(v) => {
-							values.push(v.clone());
-							pos += 1;
-						}
-						Token::Comma => {
-							pos += 1;
-						}
-						Token::CloseParen => {
-							// end of list
-							pos += 1; // consume ')'
-							break;
-						}
-						other => {
-							return Err(format!("Unexpected token in IN list: {:?}", other));
-						}
-					}
-				}
+				let (values, next_pos) = parse_in_list_items(tokens, pos)?;
+				pos = next_pos;
 				Ok((
 					Expr::Condition(Condition {
 						left: Box::new(left_expr),
@@ -379,8 +1158,8 @@ fn parse_condition(tokens: &[Token], start: usize) -> Result<(Expr, usize), Stri
 					pos,
 				))
 			} else {
-				// parse the right side (potential field chain)
-				let (right_expr, new_pos) = parse_field_chain(tokens, pos)?;
+				// parse the right side (potential field chain, or arithmetic)
+				let (right_expr, new_pos) = parse_arith_expr(tokens, pos, 0)?;
 				Ok((
 					Expr::Condition(Condition {
 						left: Box::new(left_expr),
@@ -391,67 +1170,471 @@ fn parse_condition(tokens: &[Token], start: usize) -> Result<(Expr, usize), Stri
 				))
 			}
 		}
-		other => Err(format!("Expected operator, found {:?}", other)),
+		other => Err(RawParseError::new(op_span, format!("Expected operator, found {:?}", other))),
+	}
+}
+
+/// Parse a "primary": either a parenthesized sub-expression or a single
+/// condition. This is what binds tighter than any `and`/`or`.
+fn parse_primary(tokens: &[SpannedToken], start: usize) -> Result<(Expr, usize), RawParseError> {
+	let len = tokens.len();
+	if start >= len {
+		return Err(RawParseError::new(
+			error_span(tokens, start),
+			"Unexpected end of tokens",
+		));
+	}
+
+	match &tokens[start].token {
+		Token::Not => {
+			let (inner, new_pos) = parse_primary(tokens, start + 1)?;
+			Ok((Expr::Not(Box::new(inner)), new_pos))
+		}
+		Token::OpenParen => {
+			let (expr, new_pos) = parse_expr_bp(tokens, start + 1, 0)?;
+			if new_pos >= len || tokens[new_pos].token != Token::CloseParen {
+				return Err(RawParseError::new(
+					error_span(tokens, new_pos),
+					"Missing closing parenthesis",
+				));
+			}
+			Ok((expr, new_pos + 1))
+		}
+		_ => parse_condition(tokens, start),
+	}
+}
+
+/// `or`'s and `and`'s left binding power, used by [`parse_expr_bp`]. Higher
+/// binds tighter, so `and` groups before `or`.
+fn infix_binding_power(token: &Token) -> Option<(u8, bool)> {
+	match token {
+		Token::Or => Some((1, false)),
+		Token::And => Some((2, true)),
+		_ => None,
+	}
+}
+
+/// Precedence-climbing (Pratt) parser for a chain of conditions combined
+/// with `and`/`or`: parses a primary, then repeatedly pulls in the next
+/// `and`/`or` whose binding power is at least `min_bp`, recursing into the
+/// right-hand side with `lbp + 1` so same-precedence operators stay
+/// left-associative.
+fn parse_expr_bp(tokens: &[SpannedToken], start: usize, min_bp: u8) -> Result<(Expr, usize), RawParseError> {
+	let (mut left_expr, mut pos) = parse_primary(tokens, start)?;
+
+	while pos < tokens.len() {
+		let Some((lbp, is_and)) = infix_binding_power(&tokens[pos].token) else {
+			break;
+		};
+		if lbp < min_bp {
+			break;
+		}
+		pos += 1;
+		let (right_expr, new_pos) = parse_expr_bp(tokens, pos, lbp + 1)?;
+		left_expr = if is_and {
+			Expr::And(Box::new(left_expr), Box::new(right_expr))
+		} else {
+			Expr::Or(Box::new(left_expr), Box::new(right_expr))
+		};
+		pos = new_pos;
+	}
+
+	Ok((left_expr, pos))
+}
+
+/// Parse an expression, which can consist of conditions combined with AND/OR,
+/// or a parenthesized sub-expression.
+fn parse_expression(tokens: &[SpannedToken], start: usize) -> Result<(Expr, usize), RawParseError> {
+	parse_expr_bp(tokens, start, 0)
+}
+
+/// The result of parsing a full query: the filter expression plus whatever
+/// tail clauses (`order by`/`limit`/`offset`) followed it.
+struct ParsedQuery {
+	root: Expr,
+	order_by: Option<OrderBy>,
+	limit: Option<usize>,
+	offset: Option<usize>,
+}
+
+/// Parse `order by <field>[, <field>]* [asc|desc]`, starting just after the
+/// `order` token.
+fn parse_order_by(tokens: &[SpannedToken], start: usize) -> Result<(OrderBy, usize), RawParseError> {
+	let mut pos = start;
+	if tokens.get(pos).map(|t| &t.token) != Some(&Token::By) {
+		return Err(RawParseError::new(error_span(tokens, pos), "Expected 'by' after 'order'"));
+	}
+	pos += 1;
+
+	let mut fields = Vec::new();
+	loop {
+		match tokens.get(pos).map(|t| &t.token) {
+			Some(Token::Value(Value::String(field))) => {
+				fields.push(field.clone());
+				pos += 1;
+			}
+			_ => {
+				return Err(RawParseError::new(
+					error_span(tokens, pos),
+					"Expected a field name in 'order by'",
+				));
+			}
+		}
+		if tokens.get(pos).map(|t| &t.token) == Some(&Token::Comma) {
+			pos += 1;
+			continue;
+		}
+		break;
+	}
+
+	let direction = match tokens.get(pos).map(|t| &t.token) {
+		Some(Token::Asc) => {
+			pos += 1;
+			OrderDir::Asc
+		}
+		Some(Token::Desc) => {
+			pos += 1;
+			OrderDir::Desc
+		}
+		_ => OrderDir::Asc,
+	};
+
+	Ok((OrderBy { fields, direction }, pos))
+}
+
+/// Parse a non-negative integer literal after `limit`/`offset`, starting
+/// just after the keyword token.
+fn parse_usize_arg(
+	tokens: &[SpannedToken],
+	start: usize,
+	keyword: &str,
+) -> Result<(usize, usize), RawParseError> {
+	match tokens.get(start).map(|t| &t.token) {
+		Some(Token::Value(Value::Number(n))) if *n >= 0 => Ok((*n as usize, start + 1)),
+		_ => Err(RawParseError::new(
+			error_span(tokens, start),
+			format!("Expected a non-negative number after '{}'", keyword),
+		)),
+	}
+}
+
+fn parse_tokens(tokens: &[SpannedToken]) -> Result<ParsedQuery, RawParseError> {
+	let (root, mut pos) = parse_expression(tokens, 0)?;
+
+	let mut order_by = None;
+	let mut limit = None;
+	let mut offset = None;
+
+	if tokens.get(pos).map(|t| &t.token) == Some(&Token::Order) {
+		let (parsed, next_pos) = parse_order_by(tokens, pos + 1)?;
+		order_by = Some(parsed);
+		pos = next_pos;
+	}
+
+	if tokens.get(pos).map(|t| &t.token) == Some(&Token::Limit) {
+		let (parsed, next_pos) = parse_usize_arg(tokens, pos + 1, "limit")?;
+		limit = Some(parsed);
+		pos = next_pos;
+	}
+
+	if tokens.get(pos).map(|t| &t.token) == Some(&Token::Offset) {
+		let (parsed, next_pos) = parse_usize_arg(tokens, pos + 1, "offset")?;
+		offset = Some(parsed);
+		pos = next_pos;
+	}
+
+	if pos < tokens.len() {
+		return Err(RawParseError::new(
+			error_span(tokens, pos),
+			"Unexpected tokens after expression",
+		));
+	}
+
+	Ok(ParsedQuery {
+		root,
+		order_by,
+		limit,
+		offset,
+	})
+}
+
+/// How much `parse_log_query` should rewrite the parsed AST before handing
+/// it back. `matches` is run against potentially millions of `LogEntry`
+/// rows, so pruning dead branches and folding constants up front is a real
+/// throughput win; `None` skips that pass when callers want the literal
+/// parse tree (e.g. to inspect/serialize it). `Full` additionally precompiles
+/// every `matches`/`not matches` regex pattern into the shared cache up
+/// front, so the first `check_expr` call per pattern doesn't pay a one-time
+/// `Regex::new` cost mid-scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptimizationLevel {
+	None,
+	#[default]
+	Simple,
+	Full,
+}
+
+pub fn parse_log_query(src: &str) -> Result<QueryAst, ParseError> {
+	parse_log_query_with_optimization(src, OptimizationLevel::default())
+}
+
+pub fn parse_log_query_with_optimization(
+	src: &str,
+	level: OptimizationLevel,
+) -> Result<QueryAst, ParseError> {
+	let tokens = tokenize(src).map_err(|err| err.into_parse_error(src))?;
+	let parsed = parse_tokens(&tokens).map_err(|err| err.into_parse_error(src))?;
+	let root = match level {
+		OptimizationLevel::None => parsed.root,
+		OptimizationLevel::Simple => optimize(parsed.root),
+		OptimizationLevel::Full => {
+			let root = optimize(parsed.root);
+			crate::query_eval::precompile_regexes(&root);
+			root
+		}
+	};
+	Ok(QueryAst {
+		root,
+		order_by: parsed.order_by,
+		limit: parsed.limit,
+		offset: parsed.offset,
+		..Default::default()
+	})
+}
+
+/// Bottom-up rewrite of a parsed `Expr`: fold conditions whose both sides
+/// are unambiguous literal values (never a bare string, since those are
+/// indistinguishable from a field name) into a constant; drop always-true
+/// operands from `and` (short-circuiting to `false` on an always-false
+/// one) and always-false operands from `or` (short-circuiting to `true` on
+/// an always-true one); flatten nested `and`/`and` and `or`/`or` chains;
+/// and de-duplicate structurally identical conditions within a chain.
+pub fn optimize(expr: Expr) -> Expr {
+	match expr {
+		Expr::Condition(cond) => optimize_condition(cond),
+		Expr::And(left, right) => optimize_and(optimize(*left), optimize(*right)),
+		Expr::Or(left, right) => optimize_or(optimize(*left), optimize(*right)),
+		Expr::Not(inner) => optimize_not(optimize(*inner)),
+		Expr::Arith(arith) => optimize_arith(arith),
+		Expr::Coalesce(args) => Expr::Coalesce(args.into_iter().map(optimize).collect()),
+		Expr::Call { name, args } => {
+			Expr::Call { name, args: args.into_iter().map(optimize).collect() }
+		}
+		other => other,
+	}
+}
+
+/// `Some(n)` if `expr` is a numeric literal, for folding arithmetic over two
+/// constants (`2 * 1024`) at optimize time.
+fn arith_literal(expr: &Expr) -> Option<f64> {
+	match expr {
+		Expr::Value(Value::Number(n)) => Some(*n as f64),
+		Expr::Value(Value::Float(n)) => Some(*n),
+		Expr::Value(Value::Duration(n)) => Some(*n as f64),
+		Expr::Value(Value::Bytes(n)) => Some(*n as f64),
+		_ => None,
+	}
+}
+
+fn optimize_arith(arith: Arith) -> Expr {
+	let left = optimize(*arith.left);
+	let right = optimize(*arith.right);
+	if let (Expr::Value(Value::Date(date)), Some(offset_millis), ArithOp::Add | ArithOp::Sub) =
+		(&left, arith_literal(&right), arith.op)
+	{
+		let offset = chrono::Duration::milliseconds(offset_millis as i64);
+		let result = match arith.op {
+			ArithOp::Add => *date + offset,
+			ArithOp::Sub => *date - offset,
+			_ => unreachable!(),
+		};
+		return Expr::Value(Value::Date(result));
+	}
+	if let (Some(offset_millis), Expr::Value(Value::Date(date)), ArithOp::Add) =
+		(arith_literal(&left), &right, arith.op)
+	{
+		let offset = chrono::Duration::milliseconds(offset_millis as i64);
+		return Expr::Value(Value::Date(*date + offset));
+	}
+	if let (Some(l), Some(r)) = (arith_literal(&left), arith_literal(&right)) {
+		let result = match arith.op {
+			ArithOp::Add => Some(l + r),
+			ArithOp::Sub => Some(l - r),
+			ArithOp::Mul => Some(l * r),
+			ArithOp::Div if r != 0.0 => Some(l / r),
+			ArithOp::Div => None,
+			ArithOp::Mod if r != 0.0 => Some(l % r),
+			ArithOp::Mod => None,
+		};
+		if let Some(result) = result {
+			return Expr::Value(Value::Float(result));
+		}
+	}
+	Expr::Arith(Arith { op: arith.op, left: Box::new(left), right: Box::new(right) })
+}
+
+/// Folds `not <constant>` to its inverted constant (`not true` -> `false`),
+/// same short-circuit `literal_truth` already gives `optimize_and`/`optimize_or`;
+/// otherwise just wraps the (already-optimized) inner expression back up.
+fn optimize_not(inner: Expr) -> Expr {
+	match literal_truth(&inner) {
+		Some(b) => Expr::Value(Value::Bool(!b)),
+		None => Expr::Not(Box::new(inner)),
+	}
+}
+
+fn optimize_condition(cond: Condition) -> Expr {
+	let left = optimize(*cond.left);
+	let right = optimize(*cond.right);
+	if let (Expr::Value(left_val), Expr::Value(right_val)) = (&left, &right) {
+		// A bare string could be a field name (`level = "info"` is a
+		// `Condition` over two `Value::String`s), so only fold when
+		// neither side could possibly be one.
+		if !matches!(left_val, Value::String(_)) && !matches!(right_val, Value::String(_)) {
+			if let Some(result) = evaluate_literal_condition(left_val, right_val, &cond.operator) {
+				return Expr::Value(Value::Bool(result));
+			}
+		}
 	}
+	Expr::Condition(Condition {
+		left: Box::new(left),
+		operator: cond.operator,
+		right: Box::new(right),
+	})
 }
 
-/// Parse an expression, which can consist of conditions combined with AND/OR,
-/// or a parenthesized sub-expression.
-fn parse_expression(tokens: &[Token], start: usize) -> Result<(Expr, usize), String> {
-	let len = tokens.len();
-	if start >= len {
-		return Err("Unexpected end of tokens".into());
+fn evaluate_literal_condition(left: &Value, right: &Value, op: &Operator) -> Option<bool> {
+	match op {
+		Operator::Equal => Some(left == right),
+		Operator::NotEqual => Some(left != right),
+		Operator::GreaterThan => left.partial_cmp(right).map(|o| o.is_gt()),
+		Operator::GreaterThanOrEqual => left.partial_cmp(right).map(|o| !o.is_lt()),
+		Operator::LessThan => left.partial_cmp(right).map(|o| o.is_lt()),
+		Operator::LessThanOrEqual => left.partial_cmp(right).map(|o| !o.is_gt()),
+		_ => None,
+	}
+}
+
+/// `Some(b)` if `expr` is a constant that always evaluates to `b`.
+fn literal_truth(expr: &Expr) -> Option<bool> {
+	match expr {
+		Expr::Empty => Some(true),
+		Expr::Value(Value::Bool(b)) => Some(*b),
+		_ => None,
 	}
+}
 
-	let (mut left_expr, mut pos) = match &tokens[start] {
-		Token::OpenParen => {
-			let (expr, new_pos) = parse_expression(tokens, start + 1)?;
-			if new_pos >= len || tokens[new_pos] != Token::CloseParen {
-				return Err("Missing closing parenthesis".into());
+fn flatten_and(expr: Expr, out: &mut Vec<Expr>) {
+	match expr {
+		Expr::And(left, right) => {
+			flatten_and(*left, out);
+			flatten_and(*right, out);
+		}
+		other => out.push(other),
+	}
+}
+
+fn flatten_or(expr: Expr, out: &mut Vec<Expr>) {
+	match expr {
+		Expr::Or(left, right) => {
+			flatten_or(*left, out);
+			flatten_or(*right, out);
+		}
+		other => out.push(other),
+	}
+}
+
+/// Re-nest a flattened, deduplicated list of operands left-associatively,
+/// matching how the parser would have grouped them.
+fn rebuild_chain(parts: Vec<Expr>, is_and: bool, empty_value: Expr) -> Expr {
+	let mut iter = parts.into_iter();
+	match iter.next() {
+		None => empty_value,
+		Some(first) => iter.fold(first, |acc, next| {
+			if is_and {
+				Expr::And(Box::new(acc), Box::new(next))
+			} else {
+				Expr::Or(Box::new(acc), Box::new(next))
 			}
-			(expr, new_pos + 1)
+		}),
+	}
+}
+
+/// `Some((field, value))` if `expr` is `<field> = <literal>` in the shape
+/// the parser produces for a plain field condition (bare field name on the
+/// left). Used to spot `field = a and field = b` contradictions.
+fn field_equality(expr: &Expr) -> Option<(&str, &Value)> {
+	if let Expr::Condition(Condition { left, operator: Operator::Equal, right }) = expr {
+		if let (Expr::Value(Value::String(field)), Expr::Value(value)) = (left.as_ref(), right.as_ref()) {
+			return Some((field.as_str(), value));
 		}
-		_ => parse_condition(tokens, start)?,
-	};
+	}
+	None
+}
 
-	while pos < len {
-		match &tokens[pos] {
-			Token::And => {
-				pos += 1;
-				let (right_expr, new_pos) = parse_expression(tokens, pos)?;
-				left_expr = Expr::And(Box::new(left_expr), Box::new(right_expr));
-				pos = new_pos;
+/// `true` if `kept` contains two equality conditions on the same field with
+/// different literal values, e.g. `level = "info" and level = "error"` —
+/// no log line can satisfy both, so the whole chain is always false.
+fn has_unsatisfiable_equality_pair(kept: &[Expr]) -> bool {
+	for i in 0..kept.len() {
+		let Some((field_a, value_a)) = field_equality(&kept[i]) else {
+			continue;
+		};
+		for other in &kept[i + 1..] {
+			if let Some((field_b, value_b)) = field_equality(other) {
+				if field_a == field_b && value_a != value_b {
+					return true;
+				}
 			}
-			Token::Or => {
-				pos += 1;
-				let (right_expr, new_pos) = parse_expression(tokens, pos)?;
-				left_expr = Expr::Or(Box::new(left_expr), Box::new(right_expr));
-				pos = new_pos;
+		}
+	}
+	false
+}
+
+fn optimize_and(left: Expr, right: Expr) -> Expr {
+	let mut parts = Vec::new();
+	flatten_and(left, &mut parts);
+	flatten_and(right, &mut parts);
+
+	let mut kept = Vec::new();
+	for part in parts {
+		match literal_truth(&part) {
+			Some(false) => return Expr::Value(Value::Bool(false)),
+			Some(true) => {}
+			None => {
+				if !kept.contains(&part) {
+					kept.push(part);
+				}
 			}
-			Token::CloseParen => break,
-			Token::Dot => break,
-			_ => break,
 		}
 	}
 
-	Ok((left_expr, pos))
+	if has_unsatisfiable_equality_pair(&kept) {
+		return Expr::Value(Value::Bool(false));
+	}
+
+	rebuild_chain(kept, true, Expr::Empty)
 }
 
-fn parse_tokens(tokens: &[Token]) -> Result<Expr, String> {
-	let (expr, pos) = parse_expression(tokens, 0)?;
-	if pos < tokens.len() {
-		return Err("Unexpected tokens after expression".into());
+fn optimize_or(left: Expr, right: Expr) -> Expr {
+	let mut parts = Vec::new();
+	flatten_or(left, &mut parts);
+	flatten_or(right, &mut parts);
+
+	let mut kept = Vec::new();
+	for part in parts {
+		match literal_truth(&part) {
+			Some(true) => return Expr::Value(Value::Bool(true)),
+			Some(false) => {}
+			None => {
+				if !kept.contains(&part) {
+					kept.push(part);
+				}
+			}
+		}
 	}
-	Ok(expr)
-}
 
-pub fn parse_log_query(src: &str) -> Result<QueryAst, String> {
-	let tokens = tokenize(src)?;
-	let root = parse_tokens(&tokens)?;
-	Ok(QueryAst {
-		root,
-		..Default::default()
-	})
+	rebuild_chain(kept, false, Expr::Value(Value::Bool(false)))
 }
 
 #[cfg(test)]
@@ -973,6 +2156,36 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn parse_matches_symbolic_operator() {
+		// `=~`/`!~` are shorthand for `matches`/`not matches`, same regex
+		// literal syntax.
+		let query = r#"msg =~ /conn.*timeout/"#;
+		let ast = parse_log_query(query).unwrap();
+		match ast.root {
+			Expr::Condition(c) => {
+				assert_eq!(
+					c.left,
+					Box::new(Expr::Value(Value::String("msg".to_string())))
+				);
+				assert_eq!(c.operator, Operator::Matches);
+				assert_eq!(
+					c.right,
+					Box::new(Expr::Value(Value::Regex("conn.*timeout".to_string())))
+				);
+			}
+			_ => panic!("Expected Condition"),
+		}
+		let query = r#"msg !~ /conn.*timeout/"#;
+		let ast = parse_log_query(query).unwrap();
+		match ast.root {
+			Expr::Condition(c) => {
+				assert_eq!(c.operator, Operator::NotMatches);
+			}
+			_ => panic!("Expected Condition"),
+		}
+	}
+
 	#[test]
 	fn parse_in() {
 		let query = r#"level in ("info", "error")"#;
@@ -1015,6 +2228,31 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn parse_timestamp_in_ranges() {
+		let query = r#"timestamp in ((2024-01-01, 2024-02-01), (2024-03-01, 2024-04-01))"#;
+		let ast = parse_log_query(query).unwrap();
+		match ast.root {
+			Expr::Condition(c) => {
+				assert_eq!(c.operator, Operator::In);
+				assert_eq!(
+					c.right,
+					Box::new(Expr::Value(Value::List(vec![
+						Value::List(vec![
+							Value::Date(datetime(2024, 1, 1)),
+							Value::Date(datetime(2024, 2, 1)),
+						]),
+						Value::List(vec![
+							Value::Date(datetime(2024, 3, 1)),
+							Value::Date(datetime(2024, 4, 1)),
+						]),
+					])))
+				);
+			}
+			_ => panic!("Expected Condition"),
+		}
+	}
+
 	#[test]
 	fn parse_field_access() {
 		let query = r#"timestamp.hour < 5"#;
@@ -1124,4 +2362,358 @@ mod tests {
 			)
 		);
 	}
+
+	#[test]
+	fn not_negates_a_parenthesized_group() {
+		let query = r#"not (level = "error" and deviceId matches /^dev-/)"#;
+		let ast = parse_log_query(query).unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::Not(Box::new(Expr::And(
+				Box::new(Expr::Condition(Condition {
+					left: Box::new(Expr::Value(Value::String("level".to_string()))),
+					operator: Operator::Equal,
+					right: Box::new(Expr::Value(Value::String("error".to_string()))),
+				})),
+				Box::new(Expr::Condition(Condition {
+					left: Box::new(Expr::Value(Value::String("deviceId".to_string()))),
+					operator: Operator::Matches,
+					right: Box::new(Expr::Value(Value::Regex("^dev-".to_string()))),
+				})),
+			)))
+		);
+
+		let query = r#"!(level = "error") or level = "info""#;
+		let ast = parse_log_query(query).unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::Or(
+				Box::new(Expr::Not(Box::new(Expr::Condition(Condition {
+					left: Box::new(Expr::Value(Value::String("level".to_string()))),
+					operator: Operator::Equal,
+					right: Box::new(Expr::Value(Value::String("error".to_string()))),
+				})))),
+				Box::new(Expr::Condition(Condition {
+					left: Box::new(Expr::Value(Value::String("level".to_string()))),
+					operator: Operator::Equal,
+					right: Box::new(Expr::Value(Value::String("info".to_string()))),
+				})),
+			)
+		);
+	}
+
+	#[test]
+	fn not_binds_tighter_than_and_without_parens() {
+		let query = r#"not level = "error" and msg = "boom""#;
+		let ast = parse_log_query(query).unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::And(
+				Box::new(Expr::Not(Box::new(Expr::Condition(Condition {
+					left: Box::new(Expr::Value(Value::String("level".to_string()))),
+					operator: Operator::Equal,
+					right: Box::new(Expr::Value(Value::String("error".to_string()))),
+				})))),
+				Box::new(Expr::Condition(Condition {
+					left: Box::new(Expr::Value(Value::String("msg".to_string()))),
+					operator: Operator::Equal,
+					right: Box::new(Expr::Value(Value::String("boom".to_string()))),
+				})),
+			)
+		);
+	}
+
+	#[test]
+	fn query_ast_round_trips_through_json() {
+		let query = r#"(level = "error" or level = "warn") and not deviceId matches /^dev-/"#;
+		let ast = parse_log_query(query).unwrap();
+
+		let json = serde_json::to_string(&ast.root).unwrap();
+		let restored: Expr = serde_json::from_str(&json).unwrap();
+		assert_eq!(ast.root, restored);
+	}
+
+	#[test]
+	fn parses_a_byte_size_literal_as_a_condition_operand() {
+		let ast = parse_log_query("bytes >= 1GB").unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Value(Value::String("bytes".to_string()))),
+				operator: Operator::GreaterThanOrEqual,
+				right: Box::new(Expr::Value(Value::Bytes(1_073_741_824))),
+			})
+		);
+	}
+
+	#[test]
+	fn parses_byte_size_literals_with_each_binary_unit() {
+		assert_eq!(parse_bytes_literal("512B"), Some(512));
+		assert_eq!(parse_bytes_literal("1KB"), Some(1_024));
+		assert_eq!(parse_bytes_literal("1MB"), Some(1_024 * 1_024));
+		assert_eq!(parse_bytes_literal("1GB"), Some(1_024 * 1_024 * 1_024));
+		assert_eq!(parse_bytes_literal("1TB"), Some(1_024 * 1_024 * 1_024 * 1_024));
+		assert_eq!(parse_bytes_literal("nope"), None);
+	}
+
+	#[test]
+	fn value_variants_round_trip_through_json() {
+		let values = vec![
+			Value::Date(datetime(2024, 1, 1)),
+			Value::String("info".to_string()),
+			Value::Regex("^dev-".to_string()),
+			Value::Number(42),
+			Value::Float(1.5),
+			Value::Bool(true),
+			Value::Duration(900_000),
+			Value::Bytes(10_485_760),
+			Value::List(vec![Value::Number(1), Value::String("x".to_string())]),
+		];
+		for value in values {
+			let json = serde_json::to_string(&value).unwrap();
+			let restored: Value = serde_json::from_str(&json).unwrap();
+			assert_eq!(value, restored);
+		}
+	}
+
+	#[test]
+	fn optimize_collapses_unsatisfiable_equality_and() {
+		let ast = parse_log_query(r#"level = "info" and level = "error""#).unwrap();
+		assert_eq!(ast.root, Expr::Value(Value::Bool(false)));
+	}
+
+	#[test]
+	fn optimize_folds_not_over_a_constant() {
+		let ast = parse_log_query("not (level = \"info\" and level = \"error\")").unwrap();
+		assert_eq!(ast.root, Expr::Value(Value::Bool(true)));
+	}
+
+	#[test]
+	fn parses_a_function_call_as_a_condition_operand() {
+		let ast = parse_log_query(r#"lower(msg) = "boom""#).unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Call {
+					name: "lower".to_string(),
+					args: vec![Expr::Value(Value::String("msg".to_string()))],
+				}),
+				operator: Operator::Equal,
+				right: Box::new(Expr::Value(Value::String("boom".to_string()))),
+			})
+		);
+	}
+
+	#[test]
+	fn parses_a_function_call_with_multiple_arguments() {
+		let ast = parse_log_query(r#"substr(msg, 0, 4) = "boom""#).unwrap();
+		let Expr::Condition(Condition { left, .. }) = ast.root else {
+			panic!("expected a condition");
+		};
+		assert_eq!(
+			*left,
+			Expr::Call {
+				name: "substr".to_string(),
+				args: vec![
+					Expr::Value(Value::String("msg".to_string())),
+					Expr::Value(Value::Number(0)),
+					Expr::Value(Value::Number(4)),
+				],
+			}
+		);
+	}
+
+	#[test]
+	fn full_optimization_level_precompiles_regex_patterns_and_preserves_the_tree() {
+		let simple = parse_log_query_with_optimization(
+			r#"msg matches /^boom.*/"#,
+			OptimizationLevel::Simple,
+		)
+		.unwrap();
+		let full =
+			parse_log_query_with_optimization(r#"msg matches /^boom.*/"#, OptimizationLevel::Full)
+				.unwrap();
+		// `Full` only warms the regex cache as a side effect; the tree it
+		// hands back is identical to `Simple`'s.
+		assert_eq!(simple.root, full.root);
+	}
+
+	#[test]
+	fn query_ast_optimize_method_is_idempotent_and_preserves_satisfiable_queries() {
+		let ast = parse_log_query_with_optimization(
+			r#"level = "info" and level = "info""#,
+			OptimizationLevel::None,
+		)
+		.unwrap();
+		let optimized = ast.optimize();
+		assert_eq!(
+			optimized.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Value(Value::String("level".to_string()))),
+				operator: Operator::Equal,
+				right: Box::new(Expr::Value(Value::String("info".to_string()))),
+			})
+		);
+		assert_eq!(optimized.clone().optimize(), optimized);
+	}
+
+	#[test]
+	fn parse_error_reports_line_and_column_on_multiline_query() {
+		let query = "level = info\nor )";
+		let err = parse_log_query(query).unwrap_err();
+		assert_eq!(err.span.start.line, 2);
+		assert_eq!(err.span.start.column, 4);
+		let rendered = err.to_string();
+		assert!(
+			rendered.starts_with("Unexpected token") && rendered.contains("at line 2, column 4"),
+			"unexpected error rendering: {}",
+			rendered
+		);
+	}
+
+	#[test]
+	fn parses_arithmetic_on_the_left_side_of_a_comparison() {
+		let ast = parse_log_query("bytes / 1024 > 500").unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Arith(Arith {
+					op: ArithOp::Div,
+					left: Box::new(Expr::Value(Value::String("bytes".to_string()))),
+					right: Box::new(Expr::Value(Value::Number(1024))),
+				})),
+				operator: Operator::GreaterThan,
+				right: Box::new(Expr::Value(Value::Number(500))),
+			})
+		);
+	}
+
+	#[test]
+	fn parses_arithmetic_between_two_field_accesses() {
+		let ast = parse_log_query("timestamp.hour - timestamp.minute >= 0").unwrap();
+		let hour = Expr::FieldAccess(FieldAccess {
+			expr: Box::new(Expr::Value(Value::String("timestamp".to_string()))),
+			field: "hour".to_string(),
+		});
+		let minute = Expr::FieldAccess(FieldAccess {
+			expr: Box::new(Expr::Value(Value::String("timestamp".to_string()))),
+			field: "minute".to_string(),
+		});
+		assert_eq!(
+			ast.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Arith(Arith {
+					op: ArithOp::Sub,
+					left: Box::new(hour),
+					right: Box::new(minute),
+				})),
+				operator: Operator::GreaterThanOrEqual,
+				right: Box::new(Expr::Value(Value::Number(0))),
+			})
+		);
+	}
+
+	#[test]
+	fn arithmetic_multiplication_binds_tighter_than_addition() {
+		let ast = parse_log_query("a + b * c > 0").unwrap();
+		let field = |name: &str| Expr::Value(Value::String(name.to_string()));
+		assert_eq!(
+			ast.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Arith(Arith {
+					op: ArithOp::Add,
+					left: Box::new(field("a")),
+					right: Box::new(Expr::Arith(Arith {
+						op: ArithOp::Mul,
+						left: Box::new(field("b")),
+						right: Box::new(field("c")),
+					})),
+				})),
+				operator: Operator::GreaterThan,
+				right: Box::new(Expr::Value(Value::Number(0))),
+			})
+		);
+	}
+
+	#[test]
+	fn optimize_folds_arithmetic_over_two_literals() {
+		let ast = parse_log_query("bytes > 2 * 1024").unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Value(Value::String("bytes".to_string()))),
+				operator: Operator::GreaterThan,
+				right: Box::new(Expr::Value(Value::Float(2048.0))),
+			})
+		);
+	}
+
+	#[test]
+	fn parses_modulo_at_the_same_precedence_as_multiplication_and_division() {
+		let ast = parse_log_query("retries % limit > 0").unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Arith(Arith {
+					op: ArithOp::Mod,
+					left: Box::new(Expr::Value(Value::String("retries".to_string()))),
+					right: Box::new(Expr::Value(Value::String("limit".to_string()))),
+				})),
+				operator: Operator::GreaterThan,
+				right: Box::new(Expr::Value(Value::Number(0))),
+			})
+		);
+	}
+
+	#[test]
+	fn optimize_folds_modulo_over_two_literals() {
+		let ast = parse_log_query("bytes > 10 % 3").unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Value(Value::String("bytes".to_string()))),
+				operator: Operator::GreaterThan,
+				right: Box::new(Expr::Value(Value::Float(1.0))),
+			})
+		);
+	}
+
+	#[test]
+	fn today_resolves_to_utc_midnight_of_the_current_day() {
+		let ast = parse_log_query("timestamp > today").unwrap();
+		let Expr::Condition(Condition { right, .. }) = ast.root else {
+			panic!("expected a condition");
+		};
+		let Expr::Value(Value::Date(resolved)) = *right else {
+			panic!("expected today to resolve to a Date literal");
+		};
+		let expected_midnight =
+			DateTime::<Utc>::from_utc(resolved.date_naive().and_hms_opt(0, 0, 0).unwrap(), Utc);
+		assert_eq!(resolved, expected_midnight);
+	}
+
+	#[test]
+	fn optimize_folds_now_minus_duration_into_a_concrete_date() {
+		let ast = parse_log_query("timestamp > now - 1h").unwrap();
+		let Expr::Condition(Condition { right, .. }) = ast.root else {
+			panic!("expected a condition");
+		};
+		match *right {
+			Expr::Value(Value::Date(_)) => {}
+			other => panic!("expected `now - 1h` to fold into a Date literal, got {:?}", other),
+		}
+	}
+
+	#[test]
+	fn negative_number_literal_still_parses_after_adding_subtraction() {
+		let ast = parse_log_query(r#"level = -5"#).unwrap();
+		assert_eq!(
+			ast.root,
+			Expr::Condition(Condition {
+				left: Box::new(Expr::Value(Value::String("level".to_string()))),
+				operator: Operator::Equal,
+				right: Box::new(Expr::Value(Value::Number(-5))),
+			})
+		);
+	}
 }
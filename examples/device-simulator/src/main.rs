@@ -1,13 +1,16 @@
-use std::time::Duration;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use chrono::{DateTime, Utc};
 use clap::Parser;
-use puppylog::{LogEntry, LogLevel, Prop};
+use puppylog::{chunk_digest, LogEntry, LogLevel, LogentryDeserializerError, Prop};
 use rand::distr::Alphanumeric;
 use rand::{rng, Rng};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tokio::time::sleep;
 
 #[derive(Parser, Debug)]
@@ -32,6 +35,118 @@ struct Args {
 	/// Optional upper bound on batches before exiting.
 	#[arg(long)]
 	max_batches: Option<u64>,
+	/// Append every generated batch to this capture file, for later --replay.
+	#[arg(long)]
+	record: Option<PathBuf>,
+	/// Replay a previously captured file instead of generating random logs.
+	#[arg(long)]
+	replay: Option<PathBuf>,
+	/// Speed multiplier applied to the recorded inter-batch delays during --replay.
+	#[arg(long, default_value_t = 1.0)]
+	speed: f64,
+}
+
+/// Magic + version header for capture files, so a malformed or mismatched
+/// capture is rejected cleanly instead of replaying garbage. Mirrors the
+/// framing used for on-disk log segments.
+const CAPTURE_MAGIC: &str = "PUPPYSIMCAP";
+const CAPTURE_VERSION: u16 = 1;
+const CAPTURE_HEADER_SIZE: usize = CAPTURE_MAGIC.len() + 2;
+
+/// One recorded batch: the monotonic offset (ms since the capture started)
+/// at which it was generated, plus the logs themselves.
+struct CapturedBatch {
+	offset_ms: u64,
+	logs: Vec<LogEntry>,
+}
+
+/// Appends generated batches to a capture file as they're produced, each
+/// framed as `[offset_ms: u64 BE][payload_len: u32 BE][serialized log entries]`.
+struct CaptureWriter {
+	file: File,
+	start: Instant,
+}
+
+impl CaptureWriter {
+	fn create(path: &Path) -> Result<Self> {
+		let mut file = File::create(path)
+			.with_context(|| format!("failed to create capture file {}", path.display()))?;
+		file.write_all(CAPTURE_MAGIC.as_bytes())?;
+		file.write_all(&CAPTURE_VERSION.to_be_bytes())?;
+		Ok(CaptureWriter {
+			file,
+			start: Instant::now(),
+		})
+	}
+
+	fn record_batch(&mut self, logs: &[LogEntry]) -> Result<()> {
+		let mut payload = Vec::with_capacity(logs.len() * 128);
+		for entry in logs {
+			entry
+				.serialize(&mut payload)
+				.map_err(|e| anyhow!("serialize log failed: {}", e))?;
+		}
+		let offset_ms = self.start.elapsed().as_millis() as u64;
+		self.file.write_all(&offset_ms.to_be_bytes())?;
+		self.file.write_all(&(payload.len() as u32).to_be_bytes())?;
+		self.file.write_all(&payload)?;
+		Ok(())
+	}
+}
+
+/// Reads a capture file written by `CaptureWriter` back into memory, validating
+/// the header and every record frame so a truncated or foreign file fails fast.
+fn load_capture(path: &Path) -> Result<Vec<CapturedBatch>> {
+	let data = std::fs::read(path)
+		.with_context(|| format!("failed to read capture file {}", path.display()))?;
+	if data.len() < CAPTURE_HEADER_SIZE {
+		bail!("capture file too short to contain a header");
+	}
+	if &data[0..CAPTURE_MAGIC.len()] != CAPTURE_MAGIC.as_bytes() {
+		bail!("not a puppylog capture file (bad magic)");
+	}
+	let version = u16::from_be_bytes(data[CAPTURE_MAGIC.len()..CAPTURE_HEADER_SIZE].try_into().unwrap());
+	if version != CAPTURE_VERSION {
+		bail!("unsupported capture version {}", version);
+	}
+
+	let mut batches = Vec::new();
+	let mut ptr = CAPTURE_HEADER_SIZE;
+	while ptr < data.len() {
+		if ptr + 12 > data.len() {
+			bail!("truncated capture record header");
+		}
+		let offset_ms = u64::from_be_bytes(data[ptr..ptr + 8].try_into().unwrap());
+		let payload_len = u32::from_be_bytes(data[ptr + 8..ptr + 12].try_into().unwrap()) as usize;
+		ptr += 12;
+		if ptr + payload_len > data.len() {
+			bail!("truncated capture record payload");
+		}
+		let payload = &data[ptr..ptr + payload_len];
+		ptr += payload_len;
+
+		let mut logs = Vec::new();
+		let mut lptr = 0;
+		loop {
+			match LogEntry::fast_deserialize(payload, &mut lptr) {
+				Ok(entry) => logs.push(entry),
+				Err(LogentryDeserializerError::NotEnoughData) => break,
+				Err(err) => bail!("failed to decode captured log entry: {:?}", err),
+			}
+		}
+		batches.push(CapturedBatch { offset_ms, logs });
+	}
+	Ok(batches)
+}
+
+#[derive(Debug, Serialize)]
+struct ChunkManifestRequest {
+	digests: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkManifestResponse {
+	missing: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -55,6 +170,15 @@ async fn main() -> Result<()> {
 	let device_id = args.device_id.clone().unwrap_or_else(random_device_id);
 	log::info!("starting simulator for device {}", device_id);
 
+	if let Some(replay_path) = &args.replay {
+		return replay_capture(&client, &args, &device_id, replay_path).await;
+	}
+
+	let mut recorder = match &args.record {
+		Some(path) => Some(CaptureWriter::create(path)?),
+		None => None,
+	};
+
 	let mut batches_sent = 0u64;
 	let mut last_status: Option<DeviceStatus> = None;
 
@@ -95,6 +219,12 @@ async fn main() -> Result<()> {
 			continue;
 		}
 
+		if let Some(recorder) = &mut recorder {
+			if let Err(err) = recorder.record_batch(&logs) {
+				log::warn!("failed to record batch to capture file: {}", err);
+			}
+		}
+
 		match send_logs(&client, &args.server_url, &device_id, &logs).await {
 			Ok(sent) => {
 				batches_sent += 1;
@@ -131,6 +261,49 @@ fn wait_duration(args: &Args, status: &DeviceStatus) -> Duration {
 	}
 }
 
+/// Replays a capture file written with `--record`, sending each batch at the
+/// same offset (scaled by `--speed`) it was originally generated at, so a
+/// captured session reproduces its timing and ordering exactly.
+async fn replay_capture(
+	client: &Client,
+	args: &Args,
+	device_id: &str,
+	path: &Path,
+) -> Result<()> {
+	let batches = load_capture(path)?;
+	log::info!("replaying {} captured batches from {}", batches.len(), path.display());
+	let speed = if args.speed > 0.0 { args.speed } else { 1.0 };
+
+	let replay_start = Instant::now();
+	let mut batches_sent = 0u64;
+	for batch in &batches {
+		if let Some(limit) = args.max_batches {
+			if batches_sent >= limit {
+				log::info!("max batches reached ({}), exiting", limit);
+				break;
+			}
+		}
+
+		let target = Duration::from_millis(batch.offset_ms).div_f64(speed);
+		let elapsed = replay_start.elapsed();
+		if target > elapsed {
+			sleep(target - elapsed).await;
+		}
+
+		match send_logs(client, &args.server_url, device_id, &batch.logs).await {
+			Ok(sent) => {
+				batches_sent += 1;
+				log::info!("batch {}: replayed {} entries", batches_sent, sent);
+			}
+			Err(err) => {
+				log::error!("failed to upload replayed logs: {}", err);
+			}
+		}
+	}
+
+	Ok(())
+}
+
 async fn fetch_status(client: &Client, server_url: &str, device_id: &str) -> Result<DeviceStatus> {
 	let url = format!(
 		"{}/api/v1/device/{}/status",
@@ -149,6 +322,33 @@ async fn fetch_status(client: &Client, server_url: &str, device_id: &str) -> Res
 		.context("status json parse failed")
 }
 
+/// Asks the server which of `digests` it doesn't already have, so a retry
+/// after a flaky connection can skip re-uploading chunks it already ingested.
+async fn missing_chunk_digests(
+	client: &Client,
+	server_url: &str,
+	device_id: &str,
+	digests: Vec<String>,
+) -> Result<Vec<String>> {
+	let url = format!(
+		"{}/api/v1/device/{}/logs/manifest",
+		trimmed_base(server_url),
+		urlencoding::encode(device_id)
+	);
+	let resp = client
+		.post(url)
+		.json(&ChunkManifestRequest { digests })
+		.send()
+		.await
+		.context("manifest request failed")?
+		.error_for_status()
+		.context("manifest response status not ok")?
+		.json::<ChunkManifestResponse>()
+		.await
+		.context("manifest json parse failed")?;
+	Ok(resp.missing)
+}
+
 async fn send_logs(
 	client: &Client,
 	server_url: &str,
@@ -161,6 +361,21 @@ async fn send_logs(
 			.serialize(&mut payload)
 			.map_err(|e| anyhow!("serialize log failed: {}", e))?;
 	}
+	let digest = chunk_digest(&payload);
+
+	// Two-phase exchange: check whether the server already has this exact
+	// chunk (e.g. a retransmit of an earlier attempt) before paying to
+	// upload the body again.
+	match missing_chunk_digests(client, server_url, device_id, vec![digest.clone()]).await {
+		Ok(missing) if !missing.contains(&digest) => {
+			log::debug!("server already has chunk {}, skipping upload", digest);
+			return Ok(logs.len());
+		}
+		Ok(_) => {}
+		Err(err) => {
+			log::warn!("manifest check failed, uploading anyway: {}", err);
+		}
+	}
 
 	let url = format!(
 		"{}/api/v1/device/{}/logs",
@@ -171,6 +386,7 @@ async fn send_logs(
 		.post(url)
 		.body(payload)
 		.header("content-type", "application/octet-stream")
+		.header("x-chunk-digest", digest)
 		.send()
 		.await
 		.context("log upload request failed")?;
@@ -204,15 +420,15 @@ fn generate_logs(
 		let mut props = vec![
 			Prop {
 				key: "deviceId".to_string(),
-				value: device_id.to_string(),
+				value: device_id.to_string().into(),
 			},
 			Prop {
 				key: "firmware".to_string(),
-				value: firmware,
+				value: firmware.into(),
 			},
 			Prop {
 				key: "region".to_string(),
-				value: region,
+				value: region.into(),
 			},
 		];
 		props.append(&mut extra_props);
@@ -301,15 +517,15 @@ fn random_plain_payload<R: Rng>(
 	let props = vec![
 		Prop {
 			key: "payloadFormat".to_string(),
-			value: "text".to_string(),
+			value: "text".to_string().into(),
 		},
 		Prop {
 			key: "component".to_string(),
-			value: component.to_string(),
+			value: component.to_string().into(),
 		},
 		Prop {
 			key: "anomaly".to_string(),
-			value: anomaly.to_string(),
+			value: anomaly.to_string().into(),
 		},
 	];
 	(message, props)
@@ -382,15 +598,15 @@ fn random_json_payload<R: Rng>(
 	let props = vec![
 		Prop {
 			key: "payloadFormat".to_string(),
-			value: "json".to_string(),
+			value: "json".to_string().into(),
 		},
 		Prop {
 			key: "payloadLength".to_string(),
-			value: json.len().to_string(),
+			value: json.len().to_string().into(),
 		},
 		Prop {
 			key: "batchId".to_string(),
-			value: batch_id,
+			value: batch_id.into(),
 		},
 	];
 	(json, props)
@@ -450,19 +666,19 @@ fn random_xml_payload<R: Rng>(
 	let props = vec![
 		Prop {
 			key: "payloadFormat".to_string(),
-			value: "xml".to_string(),
+			value: "xml".to_string().into(),
 		},
 		Prop {
 			key: "payloadLength".to_string(),
-			value: xml.len().to_string(),
+			value: xml.len().to_string().into(),
 		},
 		Prop {
 			key: "sequence".to_string(),
-			value: sequence.to_string(),
+			value: sequence.to_string().into(),
 		},
 		Prop {
 			key: "category".to_string(),
-			value: category.to_string(),
+			value: category.to_string().into(),
 		},
 	];
 	(xml, props)
@@ -1,14 +1,21 @@
 use chrono::{DateTime, Duration, Utc};
 use clap::{Parser, Subcommand};
-use puppylog::{LogEntry, LogLevel};
+use puppylog::{LogEntry, LogLevel, Prop};
 use rand::{distributions::Alphanumeric, prelude::*};
 use reqwest;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::error::Error;
+use std::path::PathBuf;
+use std::time::{Duration as StdDuration, Instant};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use std::io::Write;
 
+mod otlp;
+mod spool;
+mod stream;
+
 // Constants from the Python version
 const LOG_LEVELS: &[LogLevel] = &[LogLevel::Debug, LogLevel::Info, LogLevel::Warn, LogLevel::Error];
 const LOG_LEVEL_WEIGHTS: &[f64] = &[5.0, 50.0, 30.0, 10.0, 5.0];
@@ -70,6 +77,45 @@ struct Cli {
 	#[arg(short, long, default_value_t = 0)]
 	interval: u64,
 	address: String,
+	/// Path to a JSON workload spec; when set, this drives generation instead of `count`
+	#[arg(long)]
+	workload: Option<PathBuf>,
+	/// URL to POST the run summary to once a workload finishes
+	#[arg(long)]
+	report_to: Option<String>,
+	/// Directory for the disk-backed retry spool (defaults to $SPOOL_PATH or ./spool)
+	#[arg(long)]
+	spool_dir: Option<PathBuf>,
+	/// Resend previously-failed batches from the spool until it drains, then exit
+	#[arg(long)]
+	drain_spool: bool,
+	/// Max concurrent in-flight uploads while draining the spool
+	#[arg(long, default_value_t = 4)]
+	spool_concurrency: usize,
+	/// Emit logs as OTLP log records to an OTLP/HTTP collector instead of puppylog's native format
+	#[arg(long)]
+	otlp: bool,
+	/// OTLP/HTTP endpoint to POST to (e.g. http://localhost:4318/v1/logs); defaults to `address`
+	#[arg(long)]
+	otlp_endpoint: Option<String>,
+	/// Wire encoding for --otlp export
+	#[arg(long, value_enum, default_value = "json")]
+	otlp_encoding: OtlpEncoding,
+	/// Resource attribute to attach to the OTLP batch, e.g. service.name=logen (repeatable)
+	#[arg(long = "resource-attr")]
+	resource_attrs: Vec<String>,
+	/// Continuously stream `count` entries at a steady target rate instead of one burst upload
+	#[arg(long)]
+	stream: bool,
+	/// Target entries/sec for --stream mode
+	#[arg(long, default_value_t = 100.0)]
+	rate: f64,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum OtlpEncoding {
+	Json,
+	Protobuf,
 }
 
 #[derive(Subcommand)]
@@ -126,87 +172,70 @@ fn random_timestamp(base_time: DateTime<Utc>) -> DateTime<Utc> {
     base_time + offset
 }
 
-fn generate_log_line(order: u32, base_time: DateTime<Utc>) -> LogEntry {
+fn prop(key: &str, value: impl Into<String>) -> Prop {
+    Prop { key: key.to_string(), value: value.into() }
+}
+
+pub(crate) fn generate_log_line(order: u32, base_time: DateTime<Utc>) -> LogEntry {
     let mut rng = thread_rng();
-    
+
     // Select log level using weights
     let level = LOG_LEVELS.choose_weighted(&mut rng, |&item| {
         LOG_LEVEL_WEIGHTS[LOG_LEVELS.iter().position(|&x| x == item).unwrap()]
     }).unwrap().clone();
-    
+
     let entity = *ENTITY_TYPES.choose(&mut rng).unwrap();
     let actions = ACTIONS.get(entity).unwrap();
     let action = *actions.choose(&mut rng).unwrap();
-    
+
     let timestamp = random_timestamp(base_time);
-	println!("timestamp: {:?}", timestamp);
-    
+
     // Generate the log line based on entity type
     let log_line = match entity {
         "user" => {
             let username = random_string_name();
-            // format!("{} {} {} {} {}", 
-            //        timestamp.to_rfc3339(),
-            //        log_level,
-            //        entity,
-            //        username,
-            //        action);
 			LogEntry {
 				timestamp,
 				level,
 				msg: format!("{} {} {}", entity, username, action),
-				props: vec![("username".to_string(), username)]
+				props: vec![prop("username", username)],
+				..Default::default()
 			}
         },
         "api request" => {
             let api_name = API_NAMES.choose(&mut rng).unwrap();
             if action == "returned status" {
                 let status = STATUS_CODES.choose(&mut rng).unwrap();
-                // format!("{} {} {} {} returned status {}", 
-                //        timestamp.to_rfc3339(),
-                //        log_level,
-                //        entity,
-                //        api_name,
-                //        status)
 				LogEntry {
 					timestamp,
 					level,
 					msg: format!("{} {} returned status {}", entity, api_name, status),
-					props: vec![("api_name".to_string(), api_name.to_string()), ("status".to_string(), status.to_string())]
+					props: vec![prop("api_name", api_name.to_string()), prop("status", status.to_string())],
+					..Default::default()
 				}
 			} else {
-                // format!("{} {} {} {} {}", 
-                //        timestamp.to_rfc3339(),
-                //        log_level,
-                //        entity,
-                //        api_name,
-                //        action)
 				LogEntry {
 					timestamp,
 					level,
 					msg: format!("{} {} {}", entity, api_name, action),
-					props: vec![("api_name".to_string(), api_name.to_string())]
+					props: vec![prop("api_name", api_name.to_string())],
+					..Default::default()
 				}
             }
         },
         // Add similar matches for other entity types...
         _ => {
             let generic_id = generate_random_id("id", 8);
-            // format!("{} {} {} {} {}", 
-            //        timestamp.to_rfc3339(),
-            //        log_level,
-            //        entity,
-            //        generic_id,
-            //        action)
 			LogEntry {
 				timestamp,
 				level,
 				msg: format!("{} {} {}", entity, generic_id, action),
-				props: vec![("id".to_string(), generic_id)]
+				props: vec![prop("id", generic_id)],
+				..Default::default()
 			}
         }
     };
-    
+
     log_line
 }
 
@@ -220,6 +249,294 @@ fn generate_logs(count: usize) -> Vec<LogEntry> {
         .collect()
 }
 
+// --- Workload-driven generation ---
+//
+// A workload spec turns this from a fixed, hardcoded generator into a
+// repeatable benchmarking tool: the JSON file declares how many entries to
+// produce (or for how long), how entity types/levels should be weighted,
+// what time range timestamps should be drawn from, and what templated
+// props each entry should carry.
+
+const WORKLOAD_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Deserialize)]
+struct WorkloadSpec {
+    /// Total number of entries to generate; mutually interchangeable with `duration_secs`.
+    count: Option<usize>,
+    /// Generate entries until this many seconds have elapsed instead of a fixed count.
+    duration_secs: Option<u64>,
+    #[serde(default)]
+    entity_weights: HashMap<String, f64>,
+    #[serde(default)]
+    level_weights: HashMap<String, f64>,
+    #[serde(default)]
+    time_range: Option<TimeRangeSpec>,
+    #[serde(default)]
+    props: Vec<PropTemplate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeRangeSpec {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PropTemplate {
+    key: String,
+    #[serde(flatten)]
+    generator: PropGenerator,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "gen", rename_all = "snake_case")]
+enum PropGenerator {
+    Id {
+        #[serde(default)]
+        prefix: Option<String>,
+    },
+    Email,
+    Name,
+    Enum { values: Vec<String> },
+}
+
+fn pick_level(level_weights: &HashMap<String, f64>, rng: &mut impl Rng) -> LogLevel {
+    if level_weights.is_empty() {
+        return LOG_LEVELS.choose_weighted(rng, |&item| {
+            LOG_LEVEL_WEIGHTS[LOG_LEVELS.iter().position(|&x| x == item).unwrap()]
+        }).unwrap().clone();
+    }
+    let pairs: Vec<(LogLevel, f64)> = level_weights
+        .iter()
+        .map(|(name, weight)| (LogLevel::from_string(name), *weight))
+        .collect();
+    pairs.choose_weighted(rng, |(_, weight)| *weight).unwrap().0.clone()
+}
+
+fn pick_entity(entity_weights: &HashMap<String, f64>, rng: &mut impl Rng) -> String {
+    if entity_weights.is_empty() {
+        return (*ENTITY_TYPES.choose(rng).unwrap()).to_string();
+    }
+    let pairs: Vec<(&String, &f64)> = entity_weights.iter().collect();
+    pairs.choose_weighted(rng, |(_, weight)| **weight).unwrap().0.clone()
+}
+
+fn gen_timestamp(time_range: &Option<TimeRangeSpec>, base_time: DateTime<Utc>, rng: &mut impl Rng) -> DateTime<Utc> {
+    match time_range {
+        Some(range) => {
+            let span_ms = (range.end - range.start).num_milliseconds().max(1);
+            range.start + Duration::milliseconds(rng.gen_range(0..span_ms))
+        }
+        None => random_timestamp(base_time),
+    }
+}
+
+fn gen_prop_value(generator: &PropGenerator, rng: &mut impl Rng) -> String {
+    match generator {
+        PropGenerator::Id { prefix } => generate_random_id(prefix.as_deref().unwrap_or("id"), 8),
+        PropGenerator::Email => random_email(),
+        PropGenerator::Name => random_string_name(),
+        PropGenerator::Enum { values } => values.choose(rng).cloned().unwrap_or_default(),
+    }
+}
+
+fn generate_workload_entry(spec: &WorkloadSpec, base_time: DateTime<Utc>) -> LogEntry {
+    let mut rng = thread_rng();
+    let level = pick_level(&spec.level_weights, &mut rng);
+    let entity = pick_entity(&spec.entity_weights, &mut rng);
+    let action = ACTIONS
+        .get(entity.as_str())
+        .and_then(|actions| actions.choose(&mut rng))
+        .copied()
+        .unwrap_or("occurred");
+    let timestamp = gen_timestamp(&spec.time_range, base_time, &mut rng);
+    let mut props: Vec<Prop> = spec
+        .props
+        .iter()
+        .map(|template| prop(&template.key, gen_prop_value(&template.generator, &mut rng)))
+        .collect();
+    if props.is_empty() {
+        props.push(prop("entity", entity.clone()));
+    }
+
+    LogEntry {
+        timestamp,
+        level,
+        msg: format!("{} {}", entity, action),
+        props,
+        ..Default::default()
+    }
+}
+
+fn percentile(sorted_ms: &[f64], pct: f64) -> f64 {
+    if sorted_ms.is_empty() {
+        return 0.0;
+    }
+    let rank = ((pct / 100.0) * (sorted_ms.len() as f64 - 1.0)).round() as usize;
+    sorted_ms[rank.min(sorted_ms.len() - 1)]
+}
+
+#[derive(Debug, Serialize)]
+struct RunSummary {
+    entries_generated: usize,
+    bytes_sent: usize,
+    upload_wall_clock_ms: u128,
+    p50_latency_ms: f64,
+    p99_latency_ms: f64,
+    entries_per_sec: f64,
+}
+
+async fn run_workload(cli: &Cli, spec: WorkloadSpec) -> Result<(), Box<dyn Error>> {
+    if spec.count.is_none() && spec.duration_secs.is_none() {
+        return Err("workload spec must set either \"count\" or \"duration_secs\"".into());
+    }
+
+    let base_time = Utc::now();
+    let client = reqwest::Client::new();
+    let deadline = spec.duration_secs.map(|secs| Instant::now() + std::time::Duration::from_secs(secs));
+    let spool_dir = cli.spool_dir.clone().unwrap_or_else(spool::default_spool_path);
+
+    let mut entries_generated = 0usize;
+    let mut bytes_sent = 0usize;
+    let mut latencies_ms: Vec<f64> = Vec::new();
+    let run_start = Instant::now();
+
+    loop {
+        if let Some(target) = spec.count {
+            if entries_generated >= target {
+                break;
+            }
+        }
+        if let Some(deadline) = deadline {
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+
+        let batch_len = match spec.count {
+            Some(target) => WORKLOAD_BATCH_SIZE.min(target - entries_generated),
+            None => WORKLOAD_BATCH_SIZE,
+        };
+        let batch: Vec<LogEntry> = (0..batch_len)
+            .map(|_| generate_workload_entry(&spec, base_time))
+            .collect();
+
+        let mut buffer = Vec::new();
+        {
+            let mut cursor = std::io::Cursor::new(&mut buffer);
+            for entry in &batch {
+                entry.serialize(&mut cursor)?;
+            }
+        }
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::CONTENT_ENCODING,
+            reqwest::header::HeaderValue::from_static("gzip"),
+        );
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&buffer)?;
+        let body = encoder.finish()?;
+        let body_len = body.len();
+
+        let request_start = Instant::now();
+        let result = client
+            .post(&cli.address)
+            .headers(headers)
+            .body(body.clone())
+            .send()
+            .await;
+        let latency = request_start.elapsed();
+        match result {
+            Ok(resp) if resp.status().is_success() => {}
+            Ok(resp) => {
+                eprintln!("upload batch failed with {}, spooling for retry", resp.status());
+                if let Err(e) = spool::enqueue(&spool_dir, &body) {
+                    eprintln!("failed to spool batch: {}", e);
+                }
+            }
+            Err(e) => {
+                eprintln!("upload batch failed: {}, spooling for retry", e);
+                if let Err(e) = spool::enqueue(&spool_dir, &body) {
+                    eprintln!("failed to spool batch: {}", e);
+                }
+            }
+        }
+
+        entries_generated += batch.len();
+        bytes_sent += body_len;
+        latencies_ms.push(latency.as_secs_f64() * 1000.0);
+    }
+
+    let upload_wall_clock = run_start.elapsed();
+    latencies_ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let summary = RunSummary {
+        entries_generated,
+        bytes_sent,
+        upload_wall_clock_ms: upload_wall_clock.as_millis(),
+        p50_latency_ms: percentile(&latencies_ms, 50.0),
+        p99_latency_ms: percentile(&latencies_ms, 99.0),
+        entries_per_sec: entries_generated as f64 / upload_wall_clock.as_secs_f64().max(1e-9),
+    };
+
+    println!(
+        "generated {} entries, sent {} bytes in {:?} ({:.2} entries/s, p50={:.2}ms, p99={:.2}ms)",
+        summary.entries_generated,
+        summary.bytes_sent,
+        upload_wall_clock,
+        summary.entries_per_sec,
+        summary.p50_latency_ms,
+        summary.p99_latency_ms,
+    );
+
+    if let Some(report_url) = &cli.report_to {
+        let report_client = reqwest::Client::new();
+        match report_client.post(report_url).json(&summary).send().await {
+            Ok(resp) => println!("reported run summary to {}: {}", report_url, resp.status()),
+            Err(e) => eprintln!("failed to report run summary to {}: {}", report_url, e),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_resource_attrs(raw: &[String]) -> Vec<(String, String)> {
+    raw.iter()
+        .filter_map(|kv| kv.split_once('=').map(|(k, v)| (k.to_string(), v.to_string())))
+        .collect()
+}
+
+async fn run_otlp_export(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let entries = match &cli.workload {
+        Some(path) => {
+            let raw = std::fs::read_to_string(path)?;
+            let spec: WorkloadSpec = serde_json::from_str(&raw)?;
+            let base_time = Utc::now();
+            let count = spec.count.unwrap_or(cli.count);
+            (0..count).map(|_| generate_workload_entry(&spec, base_time)).collect()
+        }
+        None => generate_logs(cli.count),
+    };
+
+    let resource_attrs = parse_resource_attrs(&cli.resource_attrs);
+    let (body, content_type) = match cli.otlp_encoding {
+        OtlpEncoding::Json => (otlp::to_otlp_json(&entries, &resource_attrs), "application/json"),
+        OtlpEncoding::Protobuf => (otlp::to_otlp_protobuf(&entries, &resource_attrs), "application/x-protobuf"),
+    };
+
+    let endpoint = cli.otlp_endpoint.clone().unwrap_or_else(|| cli.address.clone());
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&endpoint)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .send()
+        .await?;
+
+    println!("OTLP export status: {}", response.status());
+    Ok(())
+}
+
 async fn upload_logs(address: &str, logs: &[String], compress: bool) -> Result<(), Box<dyn Error>> {
     let client = reqwest::Client::new();
     let logs_str = logs.join("\n");
@@ -253,7 +570,32 @@ async fn upload_logs(address: &str, logs: &[String], compress: bool) -> Result<(
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
-    
+
+    if cli.drain_spool {
+        let spool_dir = cli.spool_dir.clone().unwrap_or_else(spool::default_spool_path);
+        spool::drain(&spool_dir, &cli.address, cli.spool_concurrency).await?;
+        return Ok(());
+    }
+
+    if cli.otlp {
+        return run_otlp_export(&cli).await;
+    }
+
+    if cli.stream {
+        let flush_interval = if cli.interval > 0 {
+            StdDuration::from_millis(cli.interval)
+        } else {
+            StdDuration::from_millis(1000)
+        };
+        return stream::run(&cli.address, cli.rate, cli.count, flush_interval).await;
+    }
+
+    if let Some(workload_path) = &cli.workload {
+        let raw = std::fs::read_to_string(workload_path)?;
+        let spec: WorkloadSpec = serde_json::from_str(&raw)?;
+        return run_workload(&cli, spec).await;
+    }
+
     let logs = generate_logs(cli.count);
 
 	let client = reqwest::Client::new();
@@ -275,14 +617,28 @@ async fn main() -> Result<(), Box<dyn Error>> {
 	encoder.write_all(&buffer)?;
 	let body = encoder.finish()?;
 
-    let response = client
-        .post(cli.address)
+    let result = client
+        .post(&cli.address)
         .headers(headers)
-        .body(body)
+        .body(body.clone())
         .send()
-        .await?;
+        .await;
+
+    match result {
+        Ok(response) if response.status().is_success() => {
+            println!("Upload status: {}", response.status());
+        }
+        Ok(response) => {
+            eprintln!("Upload status: {}, spooling for retry", response.status());
+            let spool_dir = cli.spool_dir.clone().unwrap_or_else(spool::default_spool_path);
+            spool::enqueue(&spool_dir, &body)?;
+        }
+        Err(e) => {
+            eprintln!("upload failed: {}, spooling for retry", e);
+            let spool_dir = cli.spool_dir.clone().unwrap_or_else(spool::default_spool_path);
+            spool::enqueue(&spool_dir, &body)?;
+        }
+    }
 
-    println!("Upload status: {}", response.status());
-    
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,220 @@
+use puppylog::{LogEntry, LogLevel};
+use serde::Serialize;
+
+// Maps generated LogEntry records onto the OpenTelemetry logs data model
+// (https://github.com/open-telemetry/opentelemetry-proto, logs/v1) so the
+// generator can drive any OTLP-compatible collector, not just puppylog.
+
+/// OTLP severityNumber/severityText for a LogLevel, per the mapping this
+/// generator was asked to support.
+fn severity(level: &LogLevel) -> (i32, &'static str) {
+	match level {
+		LogLevel::Trace => (1, "TRACE"),
+		LogLevel::Debug => (5, "DEBUG"),
+		LogLevel::Info => (9, "INFO"),
+		LogLevel::Warn => (13, "WARN"),
+		LogLevel::Error => (17, "ERROR"),
+		LogLevel::Fatal => (21, "FATAL"),
+		LogLevel::Uknown => (0, "UNSPECIFIED"),
+	}
+}
+
+fn time_unix_nanos(entry: &LogEntry) -> u64 {
+	(entry.timestamp.timestamp_micros() as u64).saturating_mul(1000)
+}
+
+// ---- JSON encoding (OTLP/HTTP JSON mapping of the same proto messages) ----
+
+#[derive(Serialize)]
+struct JsonAnyValue {
+	#[serde(rename = "stringValue")]
+	string_value: String,
+}
+
+#[derive(Serialize)]
+struct JsonKeyValue {
+	key: String,
+	value: JsonAnyValue,
+}
+
+#[derive(Serialize)]
+struct JsonLogRecord {
+	#[serde(rename = "timeUnixNano")]
+	time_unix_nano: String,
+	#[serde(rename = "severityNumber")]
+	severity_number: i32,
+	#[serde(rename = "severityText")]
+	severity_text: String,
+	body: JsonAnyValue,
+	attributes: Vec<JsonKeyValue>,
+}
+
+#[derive(Serialize)]
+struct JsonScopeLogs {
+	scope: serde_json::Value,
+	#[serde(rename = "logRecords")]
+	log_records: Vec<JsonLogRecord>,
+}
+
+#[derive(Serialize)]
+struct JsonResource {
+	attributes: Vec<JsonKeyValue>,
+}
+
+#[derive(Serialize)]
+struct JsonResourceLogs {
+	resource: JsonResource,
+	#[serde(rename = "scopeLogs")]
+	scope_logs: Vec<JsonScopeLogs>,
+}
+
+#[derive(Serialize)]
+struct JsonLogsData {
+	#[serde(rename = "resourceLogs")]
+	resource_logs: Vec<JsonResourceLogs>,
+}
+
+fn json_key_values(attrs: &[(String, String)]) -> Vec<JsonKeyValue> {
+	attrs
+		.iter()
+		.map(|(k, v)| JsonKeyValue { key: k.clone(), value: JsonAnyValue { string_value: v.clone() } })
+		.collect()
+}
+
+pub fn to_otlp_json(entries: &[LogEntry], resource_attrs: &[(String, String)]) -> Vec<u8> {
+	let log_records = entries
+		.iter()
+		.map(|entry| {
+			let (severity_number, severity_text) = severity(&entry.level);
+			let attributes = entry
+				.props
+				.iter()
+				.map(|p| JsonKeyValue { key: p.key.clone(), value: JsonAnyValue { string_value: p.value.clone() } })
+				.collect();
+			JsonLogRecord {
+				time_unix_nano: time_unix_nanos(entry).to_string(),
+				severity_number,
+				severity_text: severity_text.to_string(),
+				body: JsonAnyValue { string_value: entry.msg.clone() },
+				attributes,
+			}
+		})
+		.collect();
+
+	let data = JsonLogsData {
+		resource_logs: vec![JsonResourceLogs {
+			resource: JsonResource { attributes: json_key_values(resource_attrs) },
+			scope_logs: vec![JsonScopeLogs { scope: serde_json::json!({}), log_records }],
+		}],
+	};
+
+	serde_json::to_vec(&data).unwrap_or_default()
+}
+
+// ---- Protobuf encoding ----
+//
+// Hand-rolled rather than generated from the .proto files (this tree has no
+// build.rs/protoc step), but follows the wire-stable opentelemetry-proto
+// field numbers exactly, so the bytes are valid input for any OTLP/HTTP
+// collector expecting `application/x-protobuf`.
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+	loop {
+		let mut byte = (value & 0x7f) as u8;
+		value >>= 7;
+		if value != 0 {
+			byte |= 0x80;
+		}
+		buf.push(byte);
+		if value == 0 {
+			break;
+		}
+	}
+}
+
+fn write_tag(buf: &mut Vec<u8>, field_num: u32, wire_type: u8) {
+	write_varint(buf, ((field_num as u64) << 3) | wire_type as u64);
+}
+
+fn write_len_delimited(buf: &mut Vec<u8>, field_num: u32, data: &[u8]) {
+	write_tag(buf, field_num, 2);
+	write_varint(buf, data.len() as u64);
+	buf.extend_from_slice(data);
+}
+
+fn write_string_field(buf: &mut Vec<u8>, field_num: u32, value: &str) {
+	write_len_delimited(buf, field_num, value.as_bytes());
+}
+
+fn write_varint_field(buf: &mut Vec<u8>, field_num: u32, value: u64) {
+	write_tag(buf, field_num, 0);
+	write_varint(buf, value);
+}
+
+fn write_fixed64_field(buf: &mut Vec<u8>, field_num: u32, value: u64) {
+	write_tag(buf, field_num, 1);
+	buf.extend_from_slice(&value.to_le_bytes());
+}
+
+// common.v1.AnyValue { oneof value { string string_value = 1; ... } }
+fn encode_any_value_string(value: &str) -> Vec<u8> {
+	let mut buf = Vec::new();
+	write_string_field(&mut buf, 1, value);
+	buf
+}
+
+// common.v1.KeyValue { string key = 1; AnyValue value = 2; }
+fn encode_key_value(key: &str, value: &str) -> Vec<u8> {
+	let mut buf = Vec::new();
+	write_string_field(&mut buf, 1, key);
+	write_len_delimited(&mut buf, 2, &encode_any_value_string(value));
+	buf
+}
+
+// logs.v1.LogRecord { fixed64 time_unix_nano = 1; SeverityNumber severity_number = 2;
+//                     string severity_text = 3; AnyValue body = 5; repeated KeyValue attributes = 6; }
+fn encode_log_record(entry: &LogEntry) -> Vec<u8> {
+	let (severity_number, severity_text) = severity(&entry.level);
+	let mut buf = Vec::new();
+	write_fixed64_field(&mut buf, 1, time_unix_nanos(entry));
+	write_varint_field(&mut buf, 2, severity_number as u64);
+	write_string_field(&mut buf, 3, severity_text);
+	write_len_delimited(&mut buf, 5, &encode_any_value_string(&entry.msg));
+	for p in &entry.props {
+		write_len_delimited(&mut buf, 6, &encode_key_value(&p.key, &p.value));
+	}
+	buf
+}
+
+// resource.v1.Resource { repeated KeyValue attributes = 1; }
+fn encode_resource(attrs: &[(String, String)]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	for (k, v) in attrs {
+		write_len_delimited(&mut buf, 1, &encode_key_value(k, v));
+	}
+	buf
+}
+
+// logs.v1.ScopeLogs { InstrumentationScope scope = 1; repeated LogRecord log_records = 2; }
+fn encode_scope_logs(entries: &[LogEntry]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	for entry in entries {
+		write_len_delimited(&mut buf, 2, &encode_log_record(entry));
+	}
+	buf
+}
+
+// logs.v1.ResourceLogs { Resource resource = 1; repeated ScopeLogs scope_logs = 2; }
+fn encode_resource_logs(entries: &[LogEntry], resource_attrs: &[(String, String)]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	write_len_delimited(&mut buf, 1, &encode_resource(resource_attrs));
+	write_len_delimited(&mut buf, 2, &encode_scope_logs(entries));
+	buf
+}
+
+// logs.v1.LogsData { repeated ResourceLogs resource_logs = 1; }
+pub fn to_otlp_protobuf(entries: &[LogEntry], resource_attrs: &[(String, String)]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	write_len_delimited(&mut buf, 1, &encode_resource_logs(entries, resource_attrs));
+	buf
+}
@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+// Disk-backed upload spool: when a gzip batch fails to upload, it's written
+// here instead of being dropped, alongside a small metadata record tracking
+// attempts and the next retry time. `drain` rescans this directory on each
+// pass so it also picks up batches spooled by a previous, crashed run.
+
+const BASE_BACKOFF_SECS: u64 = 1;
+const MAX_BACKOFF_SECS: u64 = 300;
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SpoolMeta {
+	created_at_unix: u64,
+	attempts: u32,
+	next_retry_at_unix: u64,
+}
+
+pub fn default_spool_path() -> PathBuf {
+	match std::env::var("SPOOL_PATH") {
+		Ok(val) => PathBuf::from(val),
+		Err(_) => PathBuf::from("./spool"),
+	}
+}
+
+/// Persists a gzip-compressed batch body so it survives a crash or a
+/// transient server outage; `drain` picks it up on a later retry pass.
+pub fn enqueue(spool_dir: &Path, body: &[u8]) -> std::io::Result<()> {
+	fs::create_dir_all(spool_dir)?;
+	let id = format!("{}-{}", now_unix_ms(), rand::thread_rng().gen::<u32>());
+	let now = now_unix();
+	let meta = SpoolMeta { created_at_unix: now, attempts: 0, next_retry_at_unix: now };
+	fs::write(spool_dir.join(format!("{}.gz", id)), body)?;
+	fs::write(spool_dir.join(format!("{}.json", id)), serde_json::to_vec(&meta)?)?;
+	Ok(())
+}
+
+struct SpoolEntry {
+	id: String,
+	body_path: PathBuf,
+	meta_path: PathBuf,
+	meta: SpoolMeta,
+}
+
+fn scan_due_entries(spool_dir: &Path) -> std::io::Result<Vec<SpoolEntry>> {
+	if !spool_dir.exists() {
+		return Ok(Vec::new());
+	}
+	let now = now_unix();
+	let mut due = Vec::new();
+	for dir_entry in fs::read_dir(spool_dir)? {
+		let path = dir_entry?.path();
+		if path.extension().and_then(|e| e.to_str()) != Some("json") {
+			continue;
+		}
+		let Some(id) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+		let body_path = spool_dir.join(format!("{}.gz", id));
+		if !body_path.is_file() {
+			// Orphaned metadata with no body; nothing to retry.
+			let _ = fs::remove_file(&path);
+			continue;
+		}
+		let Ok(raw) = fs::read_to_string(&path) else { continue };
+		let Ok(meta) = serde_json::from_str::<SpoolMeta>(&raw) else { continue };
+		if meta.next_retry_at_unix > now {
+			continue;
+		}
+		due.push(SpoolEntry { id: id.to_string(), body_path, meta_path: path, meta });
+	}
+	Ok(due)
+}
+
+fn spool_is_empty(spool_dir: &Path) -> std::io::Result<bool> {
+	if !spool_dir.exists() {
+		return Ok(true);
+	}
+	Ok(fs::read_dir(spool_dir)?.next().is_none())
+}
+
+fn backoff_duration(attempts: u32) -> Duration {
+	let exp = BASE_BACKOFF_SECS.saturating_mul(1u64 << attempts.min(16));
+	let capped = exp.min(MAX_BACKOFF_SECS);
+	let jitter = rand::thread_rng().gen_range(0..=(capped / 4).max(1));
+	Duration::from_secs(capped.saturating_add(jitter))
+}
+
+fn reschedule(entry: &SpoolEntry) -> std::io::Result<()> {
+	let mut meta = entry.meta.clone();
+	meta.attempts += 1;
+	meta.next_retry_at_unix = now_unix() + backoff_duration(meta.attempts).as_secs();
+	fs::write(&entry.meta_path, serde_json::to_vec(&meta)?)?;
+	Ok(())
+}
+
+async fn retry_one(entry: SpoolEntry, address: String) {
+	let body = match fs::read(&entry.body_path) {
+		Ok(b) => b,
+		Err(e) => {
+			eprintln!("spool: failed to read {}: {}", entry.body_path.display(), e);
+			return;
+		}
+	};
+
+	let client = Client::new();
+	let mut headers = reqwest::header::HeaderMap::new();
+	headers.insert(
+		reqwest::header::CONTENT_ENCODING,
+		reqwest::header::HeaderValue::from_static("gzip"),
+	);
+	let result = client.post(&address).headers(headers).body(body).send().await;
+
+	match result {
+		Ok(resp) if resp.status().is_success() => {
+			let _ = fs::remove_file(&entry.body_path);
+			let _ = fs::remove_file(&entry.meta_path);
+			println!("spool: delivered {} after {} attempt(s)", entry.id, entry.meta.attempts + 1);
+		}
+		Ok(resp) => {
+			eprintln!("spool: retry of {} failed with status {}", entry.id, resp.status());
+			if let Err(e) = reschedule(&entry) {
+				eprintln!("spool: failed to reschedule {}: {}", entry.id, e);
+			}
+		}
+		Err(e) => {
+			eprintln!("spool: retry of {} failed: {}", entry.id, e);
+			if let Err(e) = reschedule(&entry) {
+				eprintln!("spool: failed to reschedule {}: {}", entry.id, e);
+			}
+		}
+	}
+}
+
+/// Scans `spool_dir` for batches whose backoff has elapsed and resends them,
+/// with at most `max_concurrent` uploads in flight at once, until the spool
+/// is fully drained (every batch delivered). Batches still waiting out their
+/// backoff just cause the loop to idle-poll rather than being retried early.
+pub async fn drain(spool_dir: &Path, address: &str, max_concurrent: usize) -> std::io::Result<()> {
+	let semaphore = Arc::new(Semaphore::new(max_concurrent.max(1)));
+	loop {
+		if spool_is_empty(spool_dir)? {
+			return Ok(());
+		}
+
+		let due = scan_due_entries(spool_dir)?;
+		if due.is_empty() {
+			tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+			continue;
+		}
+
+		let mut handles = Vec::with_capacity(due.len());
+		for entry in due {
+			let permit = semaphore.clone().acquire_owned().await.unwrap();
+			let address = address.to_string();
+			handles.push(tokio::spawn(async move {
+				let _permit = permit;
+				retry_one(entry, address).await;
+			}));
+		}
+		for handle in handles {
+			let _ = handle.await;
+		}
+	}
+}
+
+fn now_unix() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn now_unix_ms() -> u128 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()
+}
@@ -0,0 +1,136 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::generate_log_line;
+
+const FLUSH_BATCH_SIZE: usize = 500;
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Token-bucket rate limiter: refills at `rate_per_sec` and makes `acquire`
+/// block (via an async sleep, not a busy loop) once the bucket is empty,
+/// instead of letting a caller burst ahead of the configured rate.
+struct TokenBucket {
+	capacity: f64,
+	tokens: f64,
+	rate_per_sec: f64,
+	last_refill: Instant,
+}
+
+impl TokenBucket {
+	fn new(rate_per_sec: f64) -> Self {
+		let rate_per_sec = rate_per_sec.max(0.001);
+		Self { capacity: rate_per_sec.max(1.0), tokens: 0.0, rate_per_sec, last_refill: Instant::now() }
+	}
+
+	fn refill(&mut self) {
+		let now = Instant::now();
+		let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+		self.tokens = (self.tokens + elapsed * self.rate_per_sec).min(self.capacity);
+		self.last_refill = now;
+	}
+
+	async fn acquire(&mut self) {
+		loop {
+			self.refill();
+			if self.tokens >= 1.0 {
+				self.tokens -= 1.0;
+				return;
+			}
+			let deficit = 1.0 - self.tokens;
+			tokio::time::sleep(Duration::from_secs_f64((deficit / self.rate_per_sec).max(0.001))).await;
+		}
+	}
+}
+
+/// A `Write` sink that just accumulates bytes; the stream loop below drains
+/// it on every flush and hands the drained bytes to the channel feeding the
+/// persistent upload request, so the gzip member stays open across flushes
+/// instead of a new request being made per batch.
+struct ChannelWriter {
+	buf: Vec<u8>,
+}
+
+impl Write for ChannelWriter {
+	fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+		self.buf.extend_from_slice(data);
+		Ok(data.len())
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Generates entries at `rate` entries/sec until `total` have been produced,
+/// uploading them over a single long-lived, gzip-encoded request body.
+/// Accumulated entries are flushed onto the wire whenever `FLUSH_BATCH_SIZE`
+/// is reached or `flush_interval` elapses, whichever comes first - this
+/// keeps the tool usable as a steady-state soak-test load source rather
+/// than a one-shot burst uploader.
+pub async fn run(
+	address: &str,
+	rate: f64,
+	total: usize,
+	flush_interval: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+	let (tx, rx) = mpsc::channel::<Result<Vec<u8>, std::io::Error>>(CHANNEL_CAPACITY);
+
+	let address = address.to_string();
+	let sender = tokio::spawn(async move {
+		let client = reqwest::Client::new();
+		let body = reqwest::Body::wrap_stream(ReceiverStream::new(rx));
+		client
+			.post(&address)
+			.header(reqwest::header::CONTENT_ENCODING, "gzip")
+			.body(body)
+			.send()
+			.await
+	});
+
+	let mut bucket = TokenBucket::new(rate);
+	let base_time = Utc::now();
+	let mut encoder = GzEncoder::new(ChannelWriter { buf: Vec::new() }, Compression::default());
+	let mut pending = 0usize;
+	let mut generated = 0usize;
+	let mut last_flush = Instant::now();
+
+	while generated < total {
+		bucket.acquire().await;
+		let entry = generate_log_line(0, base_time);
+		entry.serialize(&mut encoder)?;
+		generated += 1;
+		pending += 1;
+
+		if pending >= FLUSH_BATCH_SIZE || last_flush.elapsed() >= flush_interval {
+			encoder.flush()?;
+			let chunk = std::mem::take(&mut encoder.get_mut().buf);
+			if !chunk.is_empty() && tx.send(Ok(chunk)).await.is_err() {
+				// The upload request ended (e.g. the connection dropped); stop generating.
+				break;
+			}
+			pending = 0;
+			last_flush = Instant::now();
+		}
+	}
+
+	let writer = encoder.finish()?;
+	if !writer.buf.is_empty() {
+		let _ = tx.send(Ok(writer.buf)).await;
+	}
+	drop(tx);
+
+	let response = sender.await??;
+	println!(
+		"stream complete: {} entries at ~{:.1}/s target, upload status {}",
+		generated,
+		rate,
+		response.status()
+	);
+	Ok(())
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::context::Context;
+use crate::supervisor::{Worker, WorkerState};
+
+/// Records the most recent time each segment was read by a query, in memory,
+/// so `EvictionOrder::LeastRecentlyUsed` retention can tell cold-but-old
+/// segments apart from hot ones without a DB write on every query. Touches
+/// are coalesced by `AccessTrackerWorker` into one batched update instead of
+/// one transaction per segment read, so a busy query workload doesn't cost a
+/// write transaction per hit.
+#[derive(Default)]
+pub struct AccessTracker {
+	touches: Mutex<HashMap<u32, DateTime<Utc>>>,
+}
+
+impl AccessTracker {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Records that `segment_id` was read right now.
+	pub fn touch(&self, segment_id: u32) {
+		if let Ok(mut touches) = self.touches.lock() {
+			touches.insert(segment_id, Utc::now());
+		}
+	}
+
+	/// Takes every touch recorded since the last drain, leaving the tracker
+	/// empty.
+	fn drain(&self) -> HashMap<u32, DateTime<Utc>> {
+		match self.touches.lock() {
+			Ok(mut touches) => std::mem::take(&mut *touches),
+			Err(_) => HashMap::new(),
+		}
+	}
+}
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Periodically flushes `Context::access_tracker`'s coalesced touches to the
+/// `log_segments.last_accessed` column.
+pub struct AccessTrackerWorker {
+	ctx: std::sync::Arc<Context>,
+	status: String,
+}
+
+impl AccessTrackerWorker {
+	pub fn new(ctx: std::sync::Arc<Context>) -> Self {
+		Self {
+			ctx,
+			status: String::new(),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl Worker for AccessTrackerWorker {
+	fn name(&self) -> &str {
+		"access_tracker"
+	}
+
+	async fn work(&mut self) -> WorkerState {
+		let touches = self.ctx.access_tracker.drain();
+		if !touches.is_empty() {
+			let count = touches.len();
+			if let Err(err) = self.ctx.db.update_last_accessed_batch(&touches).await {
+				log::error!("access_tracker: failed to flush {} touch(es): {}", count, err);
+			} else {
+				self.status = format!("last flush: {} segment(s)", count);
+			}
+		}
+		WorkerState::Idle(FLUSH_INTERVAL)
+	}
+
+	fn status(&self) -> String {
+		self.status.clone()
+	}
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+/// How urgent an alert is: operators typically want a page for `Error`, a
+/// quieter channel for `Warning`, and to just log `Info` events. The registry
+/// itself does not route by severity: every sink receives every alert, with
+/// the severity passed along (Slack prefixes it to the text, the JSON
+/// webhook sends it as a `severity` field).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	Info,
+	Warning,
+	Error,
+}
+
+/// A destination an alert can be sent to.
+#[async_trait::async_trait]
+pub trait Notifier: Send + Sync {
+	fn name(&self) -> &str;
+	async fn send(&self, severity: Severity, text: &str) -> anyhow::Result<()>;
+}
+
+/// Posts to a Slack incoming webhook.
+pub struct SlackNotifier {
+	webhook: String,
+}
+
+impl SlackNotifier {
+	pub fn new(webhook: String) -> Self {
+		Self { webhook }
+	}
+}
+
+#[async_trait::async_trait]
+impl Notifier for SlackNotifier {
+	fn name(&self) -> &str {
+		"slack"
+	}
+
+	async fn send(&self, severity: Severity, text: &str) -> anyhow::Result<()> {
+		let client = reqwest::Client::new();
+		client
+			.post(&self.webhook)
+			.json(&json!({ "text": format!("[{:?}] {}", severity, text) }))
+			.send()
+			.await?
+			.error_for_status()?;
+		Ok(())
+	}
+}
+
+/// Posts a plain `{severity, message}` JSON body to an arbitrary webhook URL.
+pub struct WebhookNotifier {
+	url: String,
+}
+
+impl WebhookNotifier {
+	pub fn new(url: String) -> Self {
+		Self { url }
+	}
+}
+
+#[async_trait::async_trait]
+impl Notifier for WebhookNotifier {
+	fn name(&self) -> &str {
+		"webhook"
+	}
+
+	async fn send(&self, severity: Severity, text: &str) -> anyhow::Result<()> {
+		let client = reqwest::Client::new();
+		client
+			.post(&self.url)
+			.json(&json!({ "severity": format!("{:?}", severity), "message": text }))
+			.send()
+			.await?
+			.error_for_status()?;
+		Ok(())
+	}
+}
+
+/// Drops every alert. Used when no sink is configured so callers don't need
+/// to special-case "alerting is off".
+pub struct NoopNotifier;
+
+#[async_trait::async_trait]
+impl Notifier for NoopNotifier {
+	fn name(&self) -> &str {
+		"noop"
+	}
+
+	async fn send(&self, _severity: Severity, _text: &str) -> anyhow::Result<()> {
+		Ok(())
+	}
+}
+
+struct RecentAlert {
+	suppressed: u32,
+	last_sent: Instant,
+}
+
+/// Same message seen again within this window is collapsed into an
+/// occurrence count instead of re-firing every sink, so a flapping condition
+/// doesn't spam.
+const DEDUP_WINDOW: Duration = Duration::from_secs(300);
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Fans an alert out to every registered sink, retrying transient failures
+/// with exponential backoff and deduplicating repeats.
+pub struct AlertRegistry {
+	sinks: Vec<Box<dyn Notifier>>,
+	recent: Mutex<HashMap<String, RecentAlert>>,
+}
+
+impl AlertRegistry {
+	pub fn new(sinks: Vec<Box<dyn Notifier>>) -> Self {
+		Self {
+			sinks,
+			recent: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Builds the registry from environment config: a Slack sink if
+	/// `SLACK_WEBHOOK` is set, a generic JSON webhook sink if
+	/// `ALERT_WEBHOOK_URL` is set, both if both are set, or a no-op sink if
+	/// neither is configured.
+	pub fn from_env() -> Self {
+		let mut sinks: Vec<Box<dyn Notifier>> = Vec::new();
+		if let Ok(url) = std::env::var("SLACK_WEBHOOK") {
+			sinks.push(Box::new(SlackNotifier::new(url)));
+		}
+		if let Ok(url) = std::env::var("ALERT_WEBHOOK_URL") {
+			sinks.push(Box::new(WebhookNotifier::new(url)));
+		}
+		if sinks.is_empty() {
+			sinks.push(Box::new(NoopNotifier));
+		}
+		Self::new(sinks)
+	}
+
+	pub async fn notify(&self, severity: Severity, text: &str) {
+		let suppressed = {
+			let mut recent = self.recent.lock().unwrap();
+			let now = Instant::now();
+			match recent.get_mut(text) {
+				Some(entry) if now.duration_since(entry.last_sent) < DEDUP_WINDOW => {
+					entry.suppressed += 1;
+					return;
+				}
+				Some(entry) => {
+					let suppressed = entry.suppressed;
+					entry.suppressed = 0;
+					entry.last_sent = now;
+					suppressed
+				}
+				None => {
+					recent.insert(
+						text.to_string(),
+						RecentAlert { suppressed: 0, last_sent: now },
+					);
+					0
+				}
+			}
+		};
+		let text = if suppressed > 0 {
+			format!("{} (suppressed {} repeat(s))", text, suppressed)
+		} else {
+			text.to_string()
+		};
+		for sink in &self.sinks {
+			let mut attempt = 0;
+			loop {
+				match sink.send(severity, &text).await {
+					Ok(()) => break,
+					Err(err) => {
+						attempt += 1;
+						if attempt >= MAX_ATTEMPTS {
+							log::error!(
+								"alert sink '{}' failed after {} attempts: {}",
+								sink.name(),
+								attempt,
+								err
+							);
+							break;
+						}
+						let backoff = Duration::from_millis(200 * 2u64.pow(attempt - 1));
+						tokio::time::sleep(backoff).await;
+					}
+				}
+			}
+		}
+	}
+}
+
+static REGISTRY: OnceLock<AlertRegistry> = OnceLock::new();
+
+fn global() -> &'static AlertRegistry {
+	REGISTRY.get_or_init(AlertRegistry::from_env)
+}
+
+/// Send an alert at an explicit severity through every configured sink.
+pub async fn notify_with(severity: Severity, text: &str) {
+	global().notify(severity, text).await;
+}
+
+/// Convenience wrapper for the common case, kept so call sites that only
+/// care "something's wrong" don't need to pick a severity.
+pub async fn notify(text: &str) {
+	notify_with(Severity::Warning, text).await;
+}
@@ -1,18 +1,190 @@
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use axum::extract::{FromRef, FromRequestParts};
+use axum::extract::{FromRef, FromRequestParts, Query, State};
 use axum::http::request::Parts;
 use axum::http::{header, HeaderMap, StatusCode};
-use axum::response::{IntoResponse, Response};
+use axum::response::{IntoResponse, Redirect, Response};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use lru::LruCache;
+use rsa::pkcs1v15::{Signature, VerifyingKey};
+use rsa::signature::Verifier;
+use rsa::{BigUint, RsaPublicKey};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
 
 use crate::context::Context;
 
+const GOOGLE_JWKS_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
+const GOOGLE_ISSUERS: [&str; 2] = ["accounts.google.com", "https://accounts.google.com"];
+// Clock skew allowance when checking `exp`, and the JWKS cache lifetime to
+// fall back to when Google's response doesn't carry a `Cache-Control` header.
+const JWT_EXP_SKEW_SECS: i64 = 60;
+const DEFAULT_JWKS_TTL_SECS: u64 = 3600;
+// Verification cache defaults: sized for a moderate fleet of distinct
+// callers, and a short negative TTL so a burst of unauthorized probes with
+// the same bad token doesn't hit the upstream/offline path on every request.
+const DEFAULT_VERIFY_CACHE_SIZE: usize = 4096;
+const DEFAULT_NEGATIVE_CACHE_TTL_SECS: u64 = 5;
+
+/// A cached, auto-refreshing JWK set for one issuer's signing keys. Shared by
+/// [`GoogleAuth`] and [`OidcBackend`] so both OIDC verifiers follow the same
+/// `Cache-Control`-aware caching instead of duplicating it per backend.
+#[derive(Debug, Clone)]
+struct JwksClient {
+	url: String,
+	http: reqwest::Client,
+	cache: Arc<Mutex<Option<CachedJwks>>>,
+}
+
+impl JwksClient {
+	fn new(url: String, http: reqwest::Client) -> Self {
+		Self {
+			url,
+			http,
+			cache: Arc::new(Mutex::new(None)),
+		}
+	}
+
+	/// Returns the cached JWKS, refreshing it first if it's missing or past
+	/// the `Cache-Control: max-age` lifetime recorded when it was fetched.
+	async fn keys(&self) -> Result<Vec<GoogleJwk>, AuthError> {
+		{
+			let cache = self.cache.lock().await;
+			if let Some(cached) = cache.as_ref() {
+				if cached.expires_at > Instant::now() {
+					return Ok(cached.keys.clone());
+				}
+			}
+		}
+		self.refresh().await
+	}
+
+	async fn refresh(&self) -> Result<Vec<GoogleJwk>, AuthError> {
+		let response = self
+			.http
+			.get(&self.url)
+			.send()
+			.await
+			.map_err(|err| AuthError::Upstream(format!("jwks request failed: {err}")))?;
+		if !response.status().is_success() {
+			return Err(AuthError::Upstream(format!(
+				"jwks request returned {}",
+				response.status()
+			)));
+		}
+		let ttl = max_age_from_header(response.headers()).unwrap_or(DEFAULT_JWKS_TTL_SECS);
+		let jwk_set: GoogleJwkSet = response
+			.json()
+			.await
+			.map_err(|err| AuthError::Upstream(format!("invalid jwks response: {err}")))?;
+
+		let mut cache = self.cache.lock().await;
+		*cache = Some(CachedJwks {
+			keys: jwk_set.keys.clone(),
+			expires_at: Instant::now() + Duration::from_secs(ttl),
+		});
+		Ok(jwk_set.keys)
+	}
+}
+
 #[derive(Debug, Clone)]
 pub struct GoogleAuth {
 	client_id: String,
 	allowed_domains: Option<Vec<String>>,
 	http: reqwest::Client,
+	offline_verify: bool,
+	tokeninfo_fallback: bool,
+	jwks: JwksClient,
+	client_secret: Option<String>,
+	redirect_uri: Option<String>,
+	pending_logins: Arc<Mutex<HashMap<String, PendingLogin>>>,
+	sessions: Arc<dyn SessionStore>,
+	session_secret: Arc<[u8]>,
+	authorization: AuthorizationRules,
+	verification_cache: Arc<Mutex<LruCache<String, CachedVerification>>>,
+	negative_cache_ttl: Duration,
+}
+
+/// The result of a past `verify_token` call, cached under a hash of the
+/// token so repeated presentations within its validity window skip the
+/// upstream/offline verification work entirely.
+#[derive(Debug, Clone)]
+enum CachedVerification {
+	/// Valid until the token's own `exp`, same as the token itself.
+	Valid(GoogleUser, Instant),
+	/// A short, independent TTL, not tied to the token's `exp` since a
+	/// rejected token may not even parse far enough to have one.
+	Rejected(AuthError, Instant),
+}
+
+/// A login attempt that has been sent to Google but hasn't come back
+/// through the callback yet, keyed by its CSRF `state` value.
+#[derive(Debug, Clone)]
+struct PendingLogin {
+	nonce: String,
+	redirect: Option<String>,
+	created_at: Instant,
+}
+
+// How long an authorization request is allowed to sit unanswered before its
+// `state`/`nonce` pair is no longer accepted.
+const LOGIN_STATE_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone)]
+struct CachedJwks {
+	keys: Vec<GoogleJwk>,
+	expires_at: Instant,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GoogleJwk {
+	kid: String,
+	n: String,
+	e: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GoogleJwkSet {
+	keys: Vec<GoogleJwk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+	kid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GoogleIdClaims {
+	iss: String,
+	aud: String,
+	exp: i64,
+	email: Option<String>,
+	email_verified: Option<serde_json::Value>,
+	hd: Option<String>,
+	name: Option<String>,
+	picture: Option<String>,
+	nonce: Option<String>,
+}
+
+impl GoogleIdClaims {
+	fn email_verified(&self) -> bool {
+		match &self.email_verified {
+			Some(serde_json::Value::Bool(b)) => *b,
+			Some(serde_json::Value::String(s)) => matches!(s.as_str(), "true" | "1"),
+			_ => false,
+		}
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct GoogleTokenResponse {
+	id_token: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -20,6 +192,7 @@ pub struct GoogleAuthConfig {
 	pub client_id: String,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub allowed_domains: Option<Vec<String>>,
+	pub authorization: AuthorizationRules,
 }
 
 impl GoogleAuth {
@@ -38,21 +211,316 @@ impl GoogleAuth {
 					.collect::<Vec<_>>()
 			})
 			.filter(|domains| !domains.is_empty());
+		let offline_verify = std::env::var("GOOGLE_OAUTH_OFFLINE_VERIFY")
+			.map(|v| matches!(v.trim(), "true" | "1"))
+			.unwrap_or(false);
+		let tokeninfo_fallback = std::env::var("GOOGLE_OAUTH_TOKENINFO_FALLBACK")
+			.map(|v| matches!(v.trim(), "true" | "1"))
+			.unwrap_or(true);
+		let client_secret = std::env::var("GOOGLE_OAUTH_CLIENT_SECRET").ok();
+		let redirect_uri = std::env::var("GOOGLE_OAUTH_REDIRECT_URI").ok();
+		let session_secret = std::env::var("SESSION_COOKIE_SECRET")
+			.map(String::into_bytes)
+			.unwrap_or_else(|_| {
+				let mut bytes = vec![0u8; 32];
+				rand::Rng::fill(&mut rand::rng(), bytes.as_mut_slice());
+				bytes
+			});
+		let verify_cache_size = std::env::var("GOOGLE_OAUTH_VERIFY_CACHE_SIZE")
+			.ok()
+			.and_then(|v| v.parse().ok())
+			.unwrap_or(DEFAULT_VERIFY_CACHE_SIZE)
+			.max(1);
+		let negative_cache_ttl = std::env::var("GOOGLE_OAUTH_VERIFY_NEGATIVE_CACHE_TTL_SECS")
+			.ok()
+			.and_then(|v| v.parse().ok())
+			.map(Duration::from_secs)
+			.unwrap_or(Duration::from_secs(DEFAULT_NEGATIVE_CACHE_TTL_SECS));
 		Some(Self {
 			client_id,
 			allowed_domains,
 			http: reqwest::Client::new(),
+			offline_verify,
+			tokeninfo_fallback,
+			jwks: JwksClient::new(GOOGLE_JWKS_URL.to_string(), reqwest::Client::new()),
+			client_secret,
+			redirect_uri,
+			pending_logins: Arc::new(Mutex::new(HashMap::new())),
+			sessions: Arc::new(InMemorySessionStore::new()),
+			session_secret: session_secret.into(),
+			authorization: AuthorizationRules::from_env(),
+			verification_cache: Arc::new(Mutex::new(LruCache::new(
+				NonZeroUsize::new(verify_cache_size).unwrap(),
+			))),
+			negative_cache_ttl,
 		})
 	}
 
+	/// Resolves `user`'s group and permissions from the configured
+	/// [`AuthorizationRules`]; used by [`RequireRole`]/[`RequirePermission`].
+	pub fn authorize(&self, user: &GoogleUser) -> AuthorizedUser {
+		self.authorization.resolve(user)
+	}
+
+	/// Swaps in a different [`SessionStore`] (e.g. [`FileSessionStore`]),
+	/// replacing the in-memory default `from_env` starts with.
+	pub fn with_session_store(mut self, sessions: Arc<dyn SessionStore>) -> Self {
+		self.sessions = sessions;
+		self
+	}
+
+	/// Whether this instance is configured for the full login flow (as
+	/// opposed to only verifying bearer tokens minted elsewhere).
+	pub fn supports_login(&self) -> bool {
+		self.client_secret.is_some() && self.redirect_uri.is_some()
+	}
+
+	/// Builds Google's authorization URL for a fresh login attempt and
+	/// remembers its CSRF `state`/`nonce` pair so the callback can validate
+	/// them. `redirect` is an optional app-internal path to return to once
+	/// the session cookie has been issued.
+	pub async fn begin_login(&self, redirect: Option<String>) -> Result<String, AuthError> {
+		let redirect_uri = self
+			.redirect_uri
+			.as_deref()
+			.ok_or_else(|| AuthError::Upstream("login flow is not configured".to_string()))?;
+		let state = random_token();
+		let nonce = random_token();
+
+		self.sweep_expired_logins().await;
+		self.pending_logins.lock().await.insert(
+			state.clone(),
+			PendingLogin {
+				nonce: nonce.clone(),
+				redirect,
+				created_at: Instant::now(),
+			},
+		);
+
+		let url = format!(
+			"https://accounts.google.com/o/oauth2/v2/auth?client_id={client_id}&redirect_uri={redirect_uri}&response_type=code&scope={scope}&state={state}&nonce={nonce}&access_type=online",
+			client_id = urlencode(&self.client_id),
+			redirect_uri = urlencode(redirect_uri),
+			scope = urlencode("openid email profile"),
+			state = urlencode(&state),
+			nonce = urlencode(&nonce),
+		);
+		Ok(url)
+	}
+
+	/// Completes a login: exchanges `code` for tokens, checks `state` against
+	/// a pending login, verifies the returned ID token's signature/claims and
+	/// `nonce`, and returns the authenticated user plus the redirect path the
+	/// login started with.
+	pub async fn complete_login(&self, code: &str, state: &str) -> Result<(GoogleUser, Option<String>), AuthError> {
+		let pending = self
+			.pending_logins
+			.lock()
+			.await
+			.remove(state)
+			.ok_or_else(|| AuthError::Unauthorized("unknown or expired login state".to_string()))?;
+		if pending.created_at.elapsed() > LOGIN_STATE_TTL {
+			return Err(AuthError::Unauthorized("login state has expired".to_string()));
+		}
+
+		let client_secret = self
+			.client_secret
+			.as_deref()
+			.ok_or_else(|| AuthError::Upstream("login flow is not configured".to_string()))?;
+		let redirect_uri = self
+			.redirect_uri
+			.as_deref()
+			.ok_or_else(|| AuthError::Upstream("login flow is not configured".to_string()))?;
+
+		let response = self
+			.http
+			.post("https://oauth2.googleapis.com/token")
+			.form(&[
+				("code", code),
+				("client_id", self.client_id.as_str()),
+				("client_secret", client_secret),
+				("redirect_uri", redirect_uri),
+				("grant_type", "authorization_code"),
+			])
+			.send()
+			.await
+			.map_err(|err| AuthError::Upstream(format!("google token exchange failed: {err}")))?;
+		if !response.status().is_success() {
+			return Err(AuthError::Unauthorized(
+				"authorization code was rejected by google".to_string(),
+			));
+		}
+		let token_response: GoogleTokenResponse = response
+			.json()
+			.await
+			.map_err(|err| AuthError::Upstream(format!("invalid google token response: {err}")))?;
+
+		let claims = self
+			.verify_id_token(&token_response.id_token, Some(&pending.nonce))
+			.await?;
+		let user = self.user_from_claims(claims)?;
+		Ok((user, pending.redirect))
+	}
+
+	async fn sweep_expired_logins(&self) {
+		let mut pending = self.pending_logins.lock().await;
+		pending.retain(|_, login| login.created_at.elapsed() <= LOGIN_STATE_TTL);
+	}
+
 	pub fn config(&self) -> GoogleAuthConfig {
 		GoogleAuthConfig {
 			client_id: self.client_id.clone(),
 			allowed_domains: self.allowed_domains.clone(),
+			authorization: self.authorization.clone(),
 		}
 	}
 
+	/// Verifies `token`, preferring offline JWKS verification when enabled
+	/// and falling back to the `tokeninfo` endpoint when that's disabled or
+	/// (if `tokeninfo_fallback` is set) when offline verification fails.
+	/// Under high request volume many calls carry the same token, so results
+	/// are cached (keyed on a hash of the token, never the token itself) for
+	/// as long as the token remains valid; rejections get a short negative
+	/// TTL of their own so a burst of probing with one bad token doesn't
+	/// repeat the full verification for every request.
 	pub async fn verify_token(&self, token: &str) -> Result<GoogleUser, AuthError> {
+		let cache_key = Self::verification_cache_key(token);
+		{
+			let mut cache = self.verification_cache.lock().await;
+			if let Some(entry) = cache.get(&cache_key).cloned() {
+				match entry {
+					CachedVerification::Valid(user, expires_at) if expires_at > Instant::now() => {
+						return Ok(user);
+					}
+					CachedVerification::Rejected(err, expires_at) if expires_at > Instant::now() => {
+						return Err(err);
+					}
+					_ => {
+						cache.pop(&cache_key);
+					}
+				}
+			}
+		}
+
+		let result = self.verify_token_uncached(token).await;
+		self.cache_verification_result(cache_key, token, &result).await;
+		result
+	}
+
+	fn verification_cache_key(token: &str) -> String {
+		format!("{:x}", Sha256::digest(token.as_bytes()))
+	}
+
+	/// Records `result` in the verification cache. A successful result is
+	/// kept until the token's own `exp` claim (best-effort decoded from the
+	/// unverified payload, since the signature was already checked by the
+	/// caller); a rejection gets `negative_cache_ttl` regardless, since a
+	/// malformed token may not have a decodable `exp` at all.
+	async fn cache_verification_result(
+		&self,
+		cache_key: String,
+		token: &str,
+		result: &Result<GoogleUser, AuthError>,
+	) {
+		let entry = match result {
+			Ok(user) => {
+				let expires_at = token_exp_instant(token).unwrap_or_else(|| Instant::now() + self.negative_cache_ttl);
+				CachedVerification::Valid(user.clone(), expires_at)
+			}
+			Err(err) => CachedVerification::Rejected(err.clone(), Instant::now() + self.negative_cache_ttl),
+		};
+		self.verification_cache.lock().await.put(cache_key, entry);
+	}
+
+	async fn verify_token_uncached(&self, token: &str) -> Result<GoogleUser, AuthError> {
+		if self.offline_verify {
+			match self.verify_token_offline(token).await {
+				Ok(user) => return Ok(user),
+				Err(err) if self.tokeninfo_fallback => {
+					log::warn!(
+						"offline google token verification failed, falling back to tokeninfo: {}",
+						err.message()
+					);
+				}
+				Err(err) => return Err(err),
+			}
+		}
+		self.verify_token_tokeninfo(token).await
+	}
+
+	/// Verifies the JWT locally against Google's cached JWKS: checks the
+	/// RS256 signature, then `iss`/`aud`/`exp`/`email_verified`/domain
+	/// claims. Refetches the key set once if the token's `kid` isn't in the
+	/// cache, to ride out Google's routine key rotation.
+	pub async fn verify_token_offline(&self, token: &str) -> Result<GoogleUser, AuthError> {
+		let claims = self.verify_id_token(token, None).await?;
+		self.user_from_claims(claims)
+	}
+
+	/// Verifies an ID token the same way as [`Self::verify_token_offline`],
+	/// additionally requiring its `nonce` claim to match `expected_nonce`
+	/// (used by the authorization-code login flow to bind the token to the
+	/// login attempt that requested it).
+	async fn verify_id_token(&self, token: &str, expected_nonce: Option<&str>) -> Result<GoogleIdClaims, AuthError> {
+		let (header_b64, payload_b64, signature_b64) = split_jwt(token)?;
+		let header: JwtHeader = decode_json_segment(header_b64)?;
+
+		let mut keys = self.jwks.keys().await?;
+		let mut key = keys.iter().find(|k| k.kid == header.kid);
+		if key.is_none() {
+			keys = self.jwks.refresh().await?;
+			key = keys.iter().find(|k| k.kid == header.kid);
+		}
+		let key = key.ok_or_else(|| AuthError::Unauthorized("unknown signing key".to_string()))?;
+		verify_rs256_signature(key, header_b64, payload_b64, signature_b64)?;
+
+		let claims: GoogleIdClaims = decode_json_segment(payload_b64)?;
+		if !GOOGLE_ISSUERS.contains(&claims.iss.as_str()) {
+			return Err(AuthError::Unauthorized("unexpected token issuer".to_string()));
+		}
+		if claims.aud != self.client_id {
+			return Err(AuthError::Unauthorized("token audience mismatch".to_string()));
+		}
+		if claims.exp + JWT_EXP_SKEW_SECS < chrono::Utc::now().timestamp() {
+			return Err(AuthError::Unauthorized("token has expired".to_string()));
+		}
+		if claims.email.is_none() {
+			return Err(AuthError::Unauthorized("token missing email".to_string()));
+		}
+		if !claims.email_verified() {
+			return Err(AuthError::Unauthorized("email is not verified".to_string()));
+		}
+		if let Some(expected_nonce) = expected_nonce {
+			if claims.nonce.as_deref() != Some(expected_nonce) {
+				return Err(AuthError::Unauthorized("token nonce mismatch".to_string()));
+			}
+		}
+		if let Some(allowed) = &self.allowed_domains {
+			let domain = claims
+				.hd
+				.clone()
+				.or_else(|| claims.email.as_deref().and_then(|e| e.split('@').nth(1)).map(str::to_string))
+				.map(|d| d.to_lowercase())
+				.unwrap_or_default();
+			if !allowed.iter().any(|d| d == &domain) {
+				return Err(AuthError::Forbidden(format!(
+					"email domain `{domain}` is not allowed"
+				)));
+			}
+		}
+
+		Ok(claims)
+	}
+
+	fn user_from_claims(&self, claims: GoogleIdClaims) -> Result<GoogleUser, AuthError> {
+		Ok(GoogleUser {
+			email: claims.email.unwrap(),
+			name: claims.name,
+			picture: claims.picture,
+		})
+	}
+
+	async fn verify_token_tokeninfo(&self, token: &str) -> Result<GoogleUser, AuthError> {
 		let response = self
 			.http
 			.get("https://oauth2.googleapis.com/tokeninfo")
@@ -127,7 +595,362 @@ impl GoogleTokenInfo {
 	}
 }
 
+// How long an issued session cookie stays valid before the user has to log
+// in again.
+const SESSION_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 14);
+pub const SESSION_COOKIE_NAME: &str = "puppylog_session";
+
+/// A logged-in user as recorded by a [`SessionStore`], independent of the
+/// bearer/ID token that created it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+	pub email: String,
+	pub name: Option<String>,
+	pub picture: Option<String>,
+	pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Session {
+	fn is_expired(&self) -> bool {
+		self.expires_at < chrono::Utc::now()
+	}
+}
+
+/// Where logged-in sessions live. Swappable so deployments that already
+/// persist other state in the database aren't stuck with the in-memory
+/// default.
+#[async_trait::async_trait]
+pub trait SessionStore: Send + Sync + std::fmt::Debug {
+	async fn create(&self, user: GoogleUser, ttl: Duration) -> String;
+	async fn get(&self, session_id: &str) -> Option<Session>;
+	async fn remove(&self, session_id: &str);
+}
+
+/// Default [`SessionStore`]: sessions live only as long as the process does.
+#[derive(Debug, Default)]
+pub struct InMemorySessionStore {
+	sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl InMemorySessionStore {
+	pub fn new() -> Self {
+		Self::default()
+	}
+}
+
+#[async_trait::async_trait]
+impl SessionStore for InMemorySessionStore {
+	async fn create(&self, user: GoogleUser, ttl: Duration) -> String {
+		let id = random_token();
+		let session = Session {
+			email: user.email,
+			name: user.name,
+			picture: user.picture,
+			expires_at: chrono::Utc::now() + chrono::Duration::from_std(ttl).unwrap_or_default(),
+		};
+		self.sessions.lock().await.insert(id.clone(), session);
+		id
+	}
+
+	async fn get(&self, session_id: &str) -> Option<Session> {
+		let mut sessions = self.sessions.lock().await;
+		match sessions.get(session_id) {
+			Some(session) if session.is_expired() => {
+				sessions.remove(session_id);
+				None
+			}
+			Some(session) => Some(session.clone()),
+			None => None,
+		}
+	}
+
+	async fn remove(&self, session_id: &str) {
+		self.sessions.lock().await.remove(session_id);
+	}
+}
+
+fn sessions_path(logs_path: &std::path::Path) -> std::path::PathBuf {
+	logs_path.join("sessions.json")
+}
+
+/// [`SessionStore`] that persists to a flat JSON file under the logs
+/// directory, the same way `Settings` and `ScrubWorker`'s cursor survive a
+/// restart without a dedicated database table.
 #[derive(Debug)]
+pub struct FileSessionStore {
+	path: std::path::PathBuf,
+	sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl FileSessionStore {
+	pub fn load(logs_path: &std::path::Path) -> Self {
+		let path = sessions_path(logs_path);
+		let sessions = std::fs::read_to_string(&path)
+			.ok()
+			.and_then(|text| serde_json::from_str(&text).ok())
+			.unwrap_or_default();
+		Self {
+			path,
+			sessions: Mutex::new(sessions),
+		}
+	}
+
+	async fn save(&self, sessions: &HashMap<String, Session>) {
+		if let Ok(json) = serde_json::to_string(sessions) {
+			let _ = tokio::fs::write(&self.path, json).await;
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl SessionStore for FileSessionStore {
+	async fn create(&self, user: GoogleUser, ttl: Duration) -> String {
+		let id = random_token();
+		let session = Session {
+			email: user.email,
+			name: user.name,
+			picture: user.picture,
+			expires_at: chrono::Utc::now() + chrono::Duration::from_std(ttl).unwrap_or_default(),
+		};
+		let mut sessions = self.sessions.lock().await;
+		sessions.insert(id.clone(), session);
+		self.save(&sessions).await;
+		id
+	}
+
+	async fn get(&self, session_id: &str) -> Option<Session> {
+		let mut sessions = self.sessions.lock().await;
+		match sessions.get(session_id) {
+			Some(session) if session.is_expired() => {
+				sessions.remove(session_id);
+				self.save(&sessions).await;
+				None
+			}
+			Some(session) => Some(session.clone()),
+			None => None,
+		}
+	}
+
+	async fn remove(&self, session_id: &str) {
+		let mut sessions = self.sessions.lock().await;
+		sessions.remove(session_id);
+		self.save(&sessions).await;
+	}
+}
+
+/// The coarse-grained group a [`GoogleUser`] belongs to, used by
+/// [`RequireRole`] to gate whole classes of routes (e.g. log deletion vs.
+/// read-only query access).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Group {
+	Admin,
+	Viewer,
+	Custom(String),
+}
+
+impl From<&str> for Group {
+	fn from(value: &str) -> Self {
+		match value.to_lowercase().as_str() {
+			"admin" => Group::Admin,
+			"viewer" => Group::Viewer,
+			_ => Group::Custom(value.to_string()),
+		}
+	}
+}
+
+impl Serialize for Group {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		match self {
+			Group::Admin => serializer.serialize_str("admin"),
+			Group::Viewer => serializer.serialize_str("viewer"),
+			Group::Custom(name) => serializer.serialize_str(name),
+		}
+	}
+}
+
+impl<'de> Deserialize<'de> for Group {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		Ok(Group::from(String::deserialize(deserializer)?.as_str()))
+	}
+}
+
+/// One entry in an [`AuthorizationRules`] list: any user whose email or
+/// email domain matches gets `group` and `permissions`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizationRule {
+	#[serde(default)]
+	pub emails: Vec<String>,
+	#[serde(default)]
+	pub domains: Vec<String>,
+	pub group: Group,
+	#[serde(default)]
+	pub permissions: Vec<String>,
+}
+
+/// The group/permission mapping resolved from config (env or JSON), matched
+/// top-to-bottom against a user's email and domain. Serializable so the
+/// frontend can render what a user is allowed to do.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizationRules {
+	#[serde(default)]
+	pub rules: Vec<AuthorizationRule>,
+	#[serde(default = "default_group")]
+	pub default_group: Group,
+}
+
+fn default_group() -> Group {
+	Group::Viewer
+}
+
+impl Default for AuthorizationRules {
+	fn default() -> Self {
+		Self {
+			rules: Vec::new(),
+			default_group: default_group(),
+		}
+	}
+}
+
+impl AuthorizationRules {
+	/// Reads a JSON-encoded rule list from `GOOGLE_AUTH_RULES_JSON`; with no
+	/// rules configured every authenticated user resolves to `default_group`
+	/// (`Viewer`) and no extra permissions.
+	pub fn from_env() -> Self {
+		std::env::var("GOOGLE_AUTH_RULES_JSON")
+			.ok()
+			.and_then(|json| serde_json::from_str(&json).ok())
+			.unwrap_or_default()
+	}
+
+	pub fn resolve(&self, user: &GoogleUser) -> AuthorizedUser {
+		let domain = user
+			.email
+			.split('@')
+			.nth(1)
+			.map(|d| d.to_lowercase())
+			.unwrap_or_default();
+		for rule in &self.rules {
+			let matches_email = rule.emails.iter().any(|e| e.eq_ignore_ascii_case(&user.email));
+			let matches_domain = rule.domains.iter().any(|d| d.eq_ignore_ascii_case(&domain));
+			if matches_email || matches_domain {
+				return AuthorizedUser {
+					group: rule.group.clone(),
+					permissions: rule.permissions.iter().cloned().collect(),
+				};
+			}
+		}
+		AuthorizedUser {
+			group: self.default_group.clone(),
+			permissions: HashSet::new(),
+		}
+	}
+}
+
+/// The resolved group/permissions for one authenticated request.
+#[derive(Debug, Clone)]
+pub struct AuthorizedUser {
+	pub group: Group,
+	pub permissions: HashSet<String>,
+}
+
+/// Implemented by marker types passed to [`RequireRole`], e.g.
+/// `struct AdminOnly; impl RoleRequirement for AdminOnly { fn required_group() -> Group { Group::Admin } }`.
+pub trait RoleRequirement: Send + Sync + 'static {
+	fn required_group() -> Group;
+}
+
+/// Implemented by marker types passed to [`RequirePermission`].
+pub trait PermissionRequirement: Send + Sync + 'static {
+	fn required_permission() -> &'static str;
+}
+
+/// Extractor that only admits requests from a user in (or above) the
+/// required [`Group`]; `Admin` always satisfies any role requirement.
+#[derive(Debug, Clone)]
+pub struct RequireRole<R> {
+	pub user: GoogleUser,
+	pub group: Group,
+	_role: std::marker::PhantomData<R>,
+}
+
+/// Extractor that only admits requests from a user holding the required
+/// permission string.
+#[derive(Debug, Clone)]
+pub struct RequirePermission<P> {
+	pub user: GoogleUser,
+	_permission: std::marker::PhantomData<P>,
+}
+
+impl<S, R> FromRequestParts<S> for RequireRole<R>
+where
+	Arc<Context>: FromRef<S>,
+	S: Sync,
+	R: RoleRequirement,
+{
+	type Rejection = AuthRejection;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		let MaybeAuthUser(user) = MaybeAuthUser::from_request_parts(parts, state).await?;
+		let user = user.ok_or_else(|| {
+			AuthRejection(AuthError::Unauthorized("authentication required".to_string()))
+		})?;
+		let ctx: Arc<Context> = Arc::from_ref(state);
+		let auth = ctx
+			.google_auth()
+			.ok_or_else(|| AuthRejection(AuthError::Upstream("authorization is not configured".to_string())))?;
+		let authorized = auth.authorize(&user);
+		let required = R::required_group();
+		if authorized.group != required && authorized.group != Group::Admin {
+			return Err(AuthRejection(AuthError::Forbidden(format!(
+				"requires the {required:?} role"
+			))));
+		}
+		Ok(RequireRole {
+			user,
+			group: authorized.group,
+			_role: std::marker::PhantomData,
+		})
+	}
+}
+
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+	Arc<Context>: FromRef<S>,
+	S: Sync,
+	P: PermissionRequirement,
+{
+	type Rejection = AuthRejection;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		let MaybeAuthUser(user) = MaybeAuthUser::from_request_parts(parts, state).await?;
+		let user = user.ok_or_else(|| {
+			AuthRejection(AuthError::Unauthorized("authentication required".to_string()))
+		})?;
+		let ctx: Arc<Context> = Arc::from_ref(state);
+		let auth = ctx
+			.google_auth()
+			.ok_or_else(|| AuthRejection(AuthError::Upstream("authorization is not configured".to_string())))?;
+		let authorized = auth.authorize(&user);
+		let required = P::required_permission();
+		if authorized.group != Group::Admin && !authorized.permissions.iter().any(|p| p == required) {
+			return Err(AuthRejection(AuthError::Forbidden(format!(
+				"missing required permission `{required}`"
+			))));
+		}
+		Ok(RequirePermission {
+			user,
+			_permission: std::marker::PhantomData,
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
 pub enum AuthError {
 	Unauthorized(String),
 	Forbidden(String),
@@ -159,6 +982,414 @@ impl IntoResponse for AuthError {
 	}
 }
 
+/// What an [`AuthBackend`] is asked to turn into an [`AuthUser`]. Which
+/// variant a given backend accepts depends on the backend: OIDC-style
+/// backends only handle `BearerToken`, the local backend only
+/// `UsernamePassword`.
+#[derive(Debug, Clone)]
+pub enum Credentials {
+	BearerToken(String),
+	UsernamePassword { username: String, password: String },
+}
+
+/// The provider-agnostic successor to treating "authenticated user" as
+/// synonymous with "Google user". Kept as an alias to `GoogleUser` rather
+/// than a parallel type, so `MaybeAuthUser`/`RequireRole`/session storage
+/// don't need a second user shape to learn.
+pub type AuthUser = GoogleUser;
+
+/// A pluggable way to turn [`Credentials`] into an [`AuthUser`]. Lets
+/// operators who can't use Google (air-gapped deployments) still protect the
+/// log UI and ingestion endpoints with local accounts or another OIDC
+/// issuer; `MaybeAuthUser` tries each configured backend in turn. It only
+/// ever passes the request's bearer token (`Credentials::BearerToken`), so
+/// a `PasswordBackend` does not authenticate requests through it.
+#[async_trait::async_trait]
+pub trait AuthBackend: Send + Sync + std::fmt::Debug {
+	/// Short identifier used in logs and config, e.g. `"google"`, `"local"`.
+	fn name(&self) -> &str;
+	async fn authenticate(&self, credentials: &Credentials) -> Result<AuthUser, AuthError>;
+}
+
+#[async_trait::async_trait]
+impl AuthBackend for GoogleAuth {
+	fn name(&self) -> &str {
+		"google"
+	}
+
+	async fn authenticate(&self, credentials: &Credentials) -> Result<AuthUser, AuthError> {
+		match credentials {
+			Credentials::BearerToken(token) => self.verify_token(token).await,
+			Credentials::UsernamePassword { .. } => Err(AuthError::Unauthorized(
+				"google backend does not accept a username and password".to_string(),
+			)),
+		}
+	}
+}
+
+/// Config for a generic OIDC issuer: any provider that publishes a JWKS and
+/// issues RS256 ID tokens, for deployments not on Google.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+	pub issuer: String,
+	pub jwks_url: String,
+	pub client_id: String,
+}
+
+#[derive(Debug)]
+pub struct OidcBackend {
+	config: OidcConfig,
+	jwks: JwksClient,
+}
+
+impl OidcBackend {
+	pub fn new(config: OidcConfig) -> Self {
+		let jwks = JwksClient::new(config.jwks_url.clone(), reqwest::Client::new());
+		Self { config, jwks }
+	}
+}
+
+#[async_trait::async_trait]
+impl AuthBackend for OidcBackend {
+	fn name(&self) -> &str {
+		"oidc"
+	}
+
+	async fn authenticate(&self, credentials: &Credentials) -> Result<AuthUser, AuthError> {
+		let Credentials::BearerToken(token) = credentials else {
+			return Err(AuthError::Unauthorized(
+				"oidc backend does not accept a username and password".to_string(),
+			));
+		};
+
+		let (header_b64, payload_b64, signature_b64) = split_jwt(token)?;
+		let header: JwtHeader = decode_json_segment(header_b64)?;
+
+		let mut keys = self.jwks.keys().await?;
+		let mut key = keys.iter().find(|k| k.kid == header.kid);
+		if key.is_none() {
+			keys = self.jwks.refresh().await?;
+			key = keys.iter().find(|k| k.kid == header.kid);
+		}
+		let key = key.ok_or_else(|| AuthError::Unauthorized("unknown signing key".to_string()))?;
+		verify_rs256_signature(key, header_b64, payload_b64, signature_b64)?;
+
+		let claims: GoogleIdClaims = decode_json_segment(payload_b64)?;
+		if claims.iss != self.config.issuer {
+			return Err(AuthError::Unauthorized("unexpected token issuer".to_string()));
+		}
+		if claims.aud != self.config.client_id {
+			return Err(AuthError::Unauthorized("token audience mismatch".to_string()));
+		}
+		if claims.exp + JWT_EXP_SKEW_SECS < chrono::Utc::now().timestamp() {
+			return Err(AuthError::Unauthorized("token has expired".to_string()));
+		}
+		let email = claims
+			.email
+			.ok_or_else(|| AuthError::Unauthorized("token missing email".to_string()))?;
+		Ok(AuthUser {
+			email,
+			name: claims.name,
+			picture: claims.picture,
+		})
+	}
+}
+
+struct PasswordAccount {
+	password_hash: String,
+}
+
+/// Local username+password authentication for air-gapped deployments that
+/// can't reach Google or another OIDC issuer. Accounts are loaded from
+/// `username:phc_hash` lines (one per account) produced by
+/// `password_auth::generate_hash`, which hashes with Argon2id.
+#[derive(Default)]
+pub struct PasswordBackend {
+	accounts: HashMap<String, PasswordAccount>,
+}
+
+impl std::fmt::Debug for PasswordBackend {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("PasswordBackend")
+			.field("accounts", &self.accounts.len())
+			.finish()
+	}
+}
+
+impl PasswordBackend {
+	pub fn from_env() -> Option<Self> {
+		let path = std::env::var("LOCAL_AUTH_ACCOUNTS_FILE").ok()?;
+		let text = std::fs::read_to_string(path).ok()?;
+		let mut accounts = HashMap::new();
+		for line in text.lines() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			if let Some((username, hash)) = line.split_once(':') {
+				accounts.insert(
+					username.to_string(),
+					PasswordAccount {
+						password_hash: hash.to_string(),
+					},
+				);
+			}
+		}
+		Some(Self { accounts })
+	}
+}
+
+#[async_trait::async_trait]
+impl AuthBackend for PasswordBackend {
+	fn name(&self) -> &str {
+		"local"
+	}
+
+	async fn authenticate(&self, credentials: &Credentials) -> Result<AuthUser, AuthError> {
+		let Credentials::UsernamePassword { username, password } = credentials else {
+			return Err(AuthError::Unauthorized(
+				"local backend only accepts a username and password".to_string(),
+			));
+		};
+		// `verify_password` runs the hash comparison in constant time
+		// regardless of whether `username` is registered, so a missing
+		// account and a wrong password aren't distinguishable by timing.
+		let account = self.accounts.get(username);
+		let hash = account.map(|a| a.password_hash.as_str()).unwrap_or(UNKNOWN_ACCOUNT_HASH);
+		let verified = password_auth::verify_password(password, hash).is_ok();
+		if account.is_none() || !verified {
+			return Err(AuthError::Unauthorized("invalid username or password".to_string()));
+		}
+		Ok(AuthUser {
+			email: username.clone(),
+			name: None,
+			picture: None,
+		})
+	}
+}
+
+// A valid-but-unmatchable Argon2id hash, compared against when the username
+// isn't registered so the constant-time check still has something to do.
+const UNKNOWN_ACCOUNT_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$AAAAAAAAAAAAAAAAAAAAAA$AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+/// A log-shipping agent can't interactively obtain a Google ID token, so this
+/// is the machine-to-machine alternative: the agent signs a JWT assertion
+/// with its own RSA private key, and the server verifies it against the
+/// agent's registered public key, keyed by `iss`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceAccount {
+	pub issuer: String,
+	pub public_key_n: String,
+	pub public_key_e: String,
+	pub allowed_scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ServiceAccountClaims {
+	iss: String,
+	aud: String,
+	iat: i64,
+	exp: i64,
+	scope: Option<String>,
+}
+
+// Assertions that outlive this are rejected outright, independent of the
+// `exp` claim itself, so a compromised signing key can't be used to mint a
+// token that's valid "forever".
+const SERVICE_ACCOUNT_MAX_TTL_SECS: i64 = 3600;
+
+/// Service accounts registered for machine-to-machine ingestion, keyed by the
+/// `iss` they sign their assertions with.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceAccountRegistry {
+	accounts: HashMap<String, ServiceAccount>,
+}
+
+impl ServiceAccountRegistry {
+	pub fn register(&mut self, account: ServiceAccount) {
+		self.accounts.insert(account.issuer.clone(), account);
+	}
+
+	/// Reads a JSON array of [`ServiceAccount`] from `SERVICE_ACCOUNTS_JSON`.
+	pub fn from_env() -> Self {
+		let mut registry = Self::default();
+		if let Ok(raw) = std::env::var("SERVICE_ACCOUNTS_JSON") {
+			if let Ok(accounts) = serde_json::from_str::<Vec<ServiceAccount>>(&raw) {
+				for account in accounts {
+					registry.register(account);
+				}
+			}
+		}
+		registry
+	}
+}
+
+/// Exchanges a signed service-account assertion for a short-lived opaque
+/// access token, then accepts that token as a [`Credentials::BearerToken`].
+/// The access token (not the assertion itself) is what agents present on
+/// subsequent requests, so a leaked request log doesn't also leak something
+/// that's directly re-signable.
+#[derive(Debug)]
+pub struct ServiceAccountBackend {
+	registry: ServiceAccountRegistry,
+	access_tokens: Mutex<HashMap<String, (AuthUser, Instant)>>,
+	token_audience: String,
+}
+
+impl ServiceAccountBackend {
+	pub fn new(registry: ServiceAccountRegistry, token_audience: String) -> Self {
+		Self {
+			registry,
+			access_tokens: Mutex::new(HashMap::new()),
+			token_audience,
+		}
+	}
+
+	/// Verifies a signed JWT assertion and, if valid, mints a short-lived
+	/// opaque access token good for the assertion's remaining lifetime.
+	pub async fn exchange_assertion(&self, assertion: &str) -> Result<(String, Duration), AuthError> {
+		let (header_b64, payload_b64, signature_b64) = split_jwt(assertion)?;
+		let claims: ServiceAccountClaims = decode_json_segment(payload_b64)?;
+		let account = self
+			.registry
+			.accounts
+			.get(&claims.iss)
+			.ok_or_else(|| AuthError::Unauthorized("unknown service account issuer".to_string()))?;
+
+		verify_rs256_with_modulus_exponent(
+			&account.public_key_n,
+			&account.public_key_e,
+			header_b64,
+			payload_b64,
+			signature_b64,
+		)?;
+
+		if claims.aud != self.token_audience {
+			return Err(AuthError::Unauthorized("assertion audience mismatch".to_string()));
+		}
+		let now = chrono::Utc::now().timestamp();
+		if claims.exp + JWT_EXP_SKEW_SECS < now {
+			return Err(AuthError::Unauthorized("assertion has expired".to_string()));
+		}
+		if claims.exp - claims.iat > SERVICE_ACCOUNT_MAX_TTL_SECS {
+			return Err(AuthError::Unauthorized(
+				"assertion lifetime exceeds the maximum allowed".to_string(),
+			));
+		}
+		if let Some(scope) = &claims.scope {
+			for requested in scope.split_whitespace() {
+				if !account.allowed_scopes.iter().any(|s| s == requested) {
+					return Err(AuthError::Forbidden(format!("scope {requested} not allowed")));
+				}
+			}
+		}
+
+		let ttl = Duration::from_secs((claims.exp - now).max(0) as u64);
+		let access_token = random_token();
+		let user = AuthUser {
+			email: account.issuer.clone(),
+			name: None,
+			picture: None,
+		};
+		self.access_tokens
+			.lock()
+			.await
+			.insert(access_token.clone(), (user, Instant::now() + ttl));
+		Ok((access_token, ttl))
+	}
+}
+
+#[async_trait::async_trait]
+impl AuthBackend for ServiceAccountBackend {
+	fn name(&self) -> &str {
+		"service-account"
+	}
+
+	async fn authenticate(&self, credentials: &Credentials) -> Result<AuthUser, AuthError> {
+		let Credentials::BearerToken(token) = credentials else {
+			return Err(AuthError::Unauthorized(
+				"service-account backend does not accept a username and password".to_string(),
+			));
+		};
+		let mut access_tokens = self.access_tokens.lock().await;
+		match access_tokens.get(token) {
+			Some((user, expires_at)) if *expires_at > Instant::now() => Ok(user.clone()),
+			Some(_) => {
+				access_tokens.remove(token);
+				Err(AuthError::Unauthorized("access token has expired".to_string()))
+			}
+			None => Err(AuthError::Unauthorized("unknown access token".to_string())),
+		}
+	}
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ServiceAccountTokenRequest {
+	pub assertion: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceAccountTokenResponse {
+	pub access_token: String,
+	pub token_type: &'static str,
+	pub expires_in: u64,
+}
+
+/// `POST /auth/service-account/token` — exchanges a signed assertion for a
+/// short-lived bearer token, mirroring the shape of an OAuth2 token endpoint
+/// so existing client libraries for that flow can be reused by agents.
+pub async fn service_account_token(
+	State(ctx): State<Arc<Context>>,
+	axum::Json(body): axum::Json<ServiceAccountTokenRequest>,
+) -> Result<axum::Json<ServiceAccountTokenResponse>, AuthError> {
+	let backend = ctx.service_account_backend().ok_or_else(|| {
+		AuthError::Unauthorized("service-account authentication is not configured".to_string())
+	})?;
+	let (access_token, ttl) = backend.exchange_assertion(&body.assertion).await?;
+	Ok(axum::Json(ServiceAccountTokenResponse {
+		access_token,
+		token_type: "Bearer",
+		expires_in: ttl.as_secs(),
+	}))
+}
+
+/// Client-side helper for an agent to build and sign its own assertion:
+/// base64url-encodes a `{"alg":"RS256","typ":"JWT"}` header and the claims,
+/// then signs `header.payload` with the agent's RSA private key. Pairs with
+/// [`ServiceAccountBackend::exchange_assertion`] on the server side.
+pub fn sign_service_account_assertion(
+	issuer: &str,
+	audience: &str,
+	private_key: &rsa::RsaPrivateKey,
+	ttl: Duration,
+	scope: Option<&str>,
+) -> Result<String, String> {
+	use rsa::pkcs1v15::SigningKey;
+	use rsa::signature::{RandomizedSigner, SignatureEncoding};
+
+	if ttl.as_secs() as i64 > SERVICE_ACCOUNT_MAX_TTL_SECS {
+		return Err("assertion ttl exceeds the server's maximum allowed lifetime".to_string());
+	}
+	let now = chrono::Utc::now().timestamp();
+	let header_b64 = URL_SAFE_NO_PAD.encode(br#"{"alg":"RS256","typ":"JWT"}"#);
+	let claims = ServiceAccountClaims {
+		iss: issuer.to_string(),
+		aud: audience.to_string(),
+		iat: now,
+		exp: now + ttl.as_secs() as i64,
+		scope: scope.map(str::to_string),
+	};
+	let payload = serde_json::to_vec(&claims).map_err(|err| format!("failed to encode claims: {err}"))?;
+	let payload_b64 = URL_SAFE_NO_PAD.encode(payload);
+
+	let signing_key = SigningKey::<Sha256>::new(private_key.clone());
+	let signed_input = format!("{header_b64}.{payload_b64}");
+	let signature = signing_key.sign_with_rng(&mut rsa::rand_core::OsRng, signed_input.as_bytes());
+	let signature_b64 = URL_SAFE_NO_PAD.encode(signature.to_bytes());
+
+	Ok(format!("{header_b64}.{payload_b64}.{signature_b64}"))
+}
+
 #[derive(Debug, Clone)]
 pub struct MaybeAuthUser(pub Option<GoogleUser>);
 
@@ -171,6 +1402,110 @@ impl IntoResponse for AuthRejection {
 	}
 }
 
+fn split_jwt(token: &str) -> Result<(&str, &str, &str), AuthError> {
+	let mut parts = token.split('.');
+	match (parts.next(), parts.next(), parts.next(), parts.next()) {
+		(Some(header), Some(payload), Some(signature), None) => Ok((header, payload, signature)),
+		_ => Err(AuthError::Unauthorized("malformed id token".to_string())),
+	}
+}
+
+/// Best-effort `exp` claim extraction from a JWT's payload, used only to
+/// size the verification cache entry — the signature has already been (or
+/// is about to be) checked by the caller, so this doesn't need to validate
+/// anything beyond "the payload decodes".
+fn token_exp_instant(token: &str) -> Option<Instant> {
+	let (_, payload_b64, _) = split_jwt(token).ok()?;
+	let claims: GoogleIdClaims = decode_json_segment(payload_b64).ok()?;
+	let remaining = claims.exp - chrono::Utc::now().timestamp();
+	if remaining <= 0 {
+		return None;
+	}
+	Some(Instant::now() + Duration::from_secs(remaining as u64))
+}
+
+fn decode_json_segment<T: serde::de::DeserializeOwned>(segment: &str) -> Result<T, AuthError> {
+	let bytes = URL_SAFE_NO_PAD
+		.decode(segment)
+		.map_err(|_| AuthError::Unauthorized("invalid token encoding".to_string()))?;
+	serde_json::from_slice(&bytes)
+		.map_err(|_| AuthError::Unauthorized("invalid token claims".to_string()))
+}
+
+fn verify_rs256_signature(
+	key: &GoogleJwk,
+	header_b64: &str,
+	payload_b64: &str,
+	signature_b64: &str,
+) -> Result<(), AuthError> {
+	verify_rs256_with_modulus_exponent(&key.n, &key.e, header_b64, payload_b64, signature_b64)
+}
+
+/// Verifies an RS256 JWT signature against a raw base64url modulus/exponent
+/// pair rather than a [`GoogleJwk`], so callers whose key material isn't
+/// JWKS-shaped (e.g. [`ServiceAccount`]) can reuse the same verification
+/// logic without fabricating a fake JWK wrapper.
+fn verify_rs256_with_modulus_exponent(
+	n_b64: &str,
+	e_b64: &str,
+	header_b64: &str,
+	payload_b64: &str,
+	signature_b64: &str,
+) -> Result<(), AuthError> {
+	let n = URL_SAFE_NO_PAD
+		.decode(n_b64)
+		.map_err(|_| AuthError::Unauthorized("invalid jwk modulus".to_string()))?;
+	let e = URL_SAFE_NO_PAD
+		.decode(e_b64)
+		.map_err(|_| AuthError::Unauthorized("invalid jwk exponent".to_string()))?;
+	let public_key = RsaPublicKey::new(BigUint::from_bytes_be(&n), BigUint::from_bytes_be(&e))
+		.map_err(|err| AuthError::Unauthorized(format!("invalid jwk key: {err}")))?;
+	let verifying_key = VerifyingKey::<Sha256>::new(public_key);
+
+	let signature_bytes = URL_SAFE_NO_PAD
+		.decode(signature_b64)
+		.map_err(|_| AuthError::Unauthorized("invalid token signature encoding".to_string()))?;
+	let signature = Signature::try_from(signature_bytes.as_slice())
+		.map_err(|_| AuthError::Unauthorized("malformed token signature".to_string()))?;
+
+	let signed_input = format!("{header_b64}.{payload_b64}");
+	verifying_key
+		.verify(signed_input.as_bytes(), &signature)
+		.map_err(|_| AuthError::Unauthorized("token signature verification failed".to_string()))
+}
+
+/// Parses `max-age=<seconds>` out of a `Cache-Control` header, if present.
+fn max_age_from_header(headers: &HeaderMap) -> Option<u64> {
+	let value = headers.get(header::CACHE_CONTROL)?.to_str().ok()?;
+	value.split(',').find_map(|directive| {
+		directive
+			.trim()
+			.strip_prefix("max-age=")
+			.and_then(|v| v.parse::<u64>().ok())
+	})
+}
+
+/// A URL-safe, unguessable token for CSRF `state`/`nonce` values and session
+/// ids: 32 random bytes, base64url-encoded.
+fn random_token() -> String {
+	let mut bytes = [0u8; 32];
+	rand::Rng::fill(&mut rand::rng(), &mut bytes);
+	URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn urlencode(value: &str) -> String {
+	let mut out = String::with_capacity(value.len());
+	for byte in value.bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+				out.push(byte as char)
+			}
+			_ => out.push_str(&format!("%{byte:02X}")),
+		}
+	}
+	out
+}
+
 fn hex_value(byte: u8) -> Option<u8> {
 	match byte {
 		b'0'..=b'9' => Some(byte - b'0'),
@@ -208,6 +1543,14 @@ fn decode_query_value(value: &str) -> Option<String> {
 	String::from_utf8(bytes).ok()
 }
 
+fn extract_session_cookie(headers: &HeaderMap) -> Option<String> {
+	let cookie_header = headers.get(header::COOKIE)?.to_str().ok()?;
+	cookie_header.split(';').find_map(|pair| {
+		let (name, value) = pair.trim().split_once('=')?;
+		(name == SESSION_COOKIE_NAME).then(|| value.to_string())
+	})
+}
+
 fn extract_token(headers: &HeaderMap, parts: &Parts) -> Option<String> {
 	if let Some(value) = headers.get(header::AUTHORIZATION) {
 		if let Ok(value) = value.to_str() {
@@ -241,18 +1584,335 @@ where
 
 	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
 		let ctx: Arc<Context> = Arc::from_ref(state);
-		let Some(auth) = ctx.google_auth() else {
+
+		// Google's login flow is the only backend that currently mints
+		// session cookies, so the cookie check stays Google-specific even
+		// though bearer-token verification below now fans out to every
+		// configured backend.
+		if let Some(auth) = ctx.google_auth() {
+			if let Some(cookie_value) = extract_session_cookie(&parts.headers) {
+				if let Some(session_id) = verify_session_cookie(&auth.session_secret, &cookie_value) {
+					if let Some(session) = auth.sessions.get(&session_id).await {
+						return Ok(MaybeAuthUser(Some(GoogleUser {
+							email: session.email,
+							name: session.name,
+							picture: session.picture,
+						})));
+					}
+				}
+			}
+		}
+
+		let backends = ctx.auth_backends();
+		if backends.is_empty() {
 			return Ok(MaybeAuthUser(None));
-		};
-		let token = extract_token(&parts.headers, parts);
-		let Some(token) = token else {
+		}
+
+		let Some(token) = extract_token(&parts.headers, parts) else {
 			return Err(AuthRejection(AuthError::Unauthorized(
-				"missing bearer token".to_string(),
+				"missing bearer token or session".to_string(),
 			)));
 		};
-		match auth.verify_token(&token).await {
-			Ok(user) => Ok(MaybeAuthUser(Some(user))),
-			Err(err) => Err(AuthRejection(err)),
+		let credentials = Credentials::BearerToken(token);
+
+		let mut last_err = None;
+		for backend in backends {
+			match backend.authenticate(&credentials).await {
+				Ok(user) => return Ok(MaybeAuthUser(Some(user))),
+				Err(err) => last_err = Some(err),
+			}
+		}
+		Err(AuthRejection(last_err.unwrap_or_else(|| {
+			AuthError::Unauthorized("no configured auth backend accepted this token".to_string())
+		})))
+	}
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+	pub redirect: Option<String>,
+}
+
+/// `GET /login/google` — redirects the browser to Google's consent screen,
+/// remembering this attempt's CSRF `state`/`nonce` server-side.
+pub async fn login_google(
+	State(ctx): State<Arc<Context>>,
+	Query(query): Query<LoginQuery>,
+) -> Result<Redirect, AuthError> {
+	let auth = ctx
+		.google_auth()
+		.ok_or_else(|| AuthError::Upstream("google login is not configured".to_string()))?;
+	let url = auth.begin_login(query.redirect).await?;
+	Ok(Redirect::temporary(&url))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginCallbackQuery {
+	pub code: String,
+	pub state: String,
+}
+
+/// `GET /login/google/code` — Google's redirect target. Exchanges the code,
+/// verifies the ID token, and issues a signed session cookie before sending
+/// the browser back to wherever the login started from.
+pub async fn login_google_callback(
+	State(ctx): State<Arc<Context>>,
+	Query(query): Query<LoginCallbackQuery>,
+) -> Result<Response, AuthError> {
+	let auth = ctx
+		.google_auth()
+		.ok_or_else(|| AuthError::Upstream("google login is not configured".to_string()))?;
+	let (user, redirect) = auth.complete_login(&query.code, &query.state).await?;
+	let session_id = auth.sessions.create(user, SESSION_TTL).await;
+	let cookie_value = sign_session_cookie(&auth.session_secret, &session_id);
+	let cookie = format!(
+		"{SESSION_COOKIE_NAME}={cookie_value}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+		SESSION_TTL.as_secs()
+	);
+
+	let mut response = Redirect::temporary(redirect.as_deref().unwrap_or("/")).into_response();
+	let cookie_value = header::HeaderValue::from_str(&cookie)
+		.map_err(|_| AuthError::Upstream("invalid session cookie".to_string()))?;
+	response.headers_mut().insert(header::SET_COOKIE, cookie_value);
+	Ok(response)
+}
+
+fn sign_session_cookie(secret: &[u8], session_id: &str) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac accepts any key length");
+	mac.update(session_id.as_bytes());
+	let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+	format!("{session_id}.{signature}")
+}
+
+fn verify_session_cookie(secret: &[u8], cookie_value: &str) -> Option<String> {
+	let (session_id, signature) = cookie_value.split_once('.')?;
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret).ok()?;
+	mac.update(session_id.as_bytes());
+	let expected_signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+	mac.verify_slice(&expected_signature).ok()?;
+	Some(session_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rsa::pkcs1v15::SigningKey;
+	use rsa::signature::{RandomizedSigner, SignatureEncoding};
+	use rsa::RsaPrivateKey;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::sync::LazyLock;
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	const TEST_CLIENT_ID: &str = "test-client.apps.googleusercontent.com";
+	const TEST_KID: &str = "test-kid-1";
+
+	// RSA keygen is slow enough that generating it per-test would make this
+	// module noticeably slower to run; every test signs with the same key.
+	static TEST_KEY: LazyLock<RsaPrivateKey> =
+		LazyLock::new(|| RsaPrivateKey::new(&mut rsa::rand_core::OsRng, 2048).unwrap());
+
+	fn test_jwk_json(kid: &str) -> serde_json::Value {
+		let public_key = TEST_KEY.to_public_key();
+		serde_json::json!({
+			"kid": kid,
+			"n": URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+			"e": URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+		})
+	}
+
+	/// Signs `claims` with the shared test RSA key under `kid`, the same way
+	/// Google mints an ID token, so tests exercise the real signature and
+	/// claim-decoding path instead of constructing a `GoogleIdClaims` by hand.
+	fn sign_test_token(kid: &str, claims: serde_json::Value) -> String {
+		let header = serde_json::json!({"alg": "RS256", "typ": "JWT", "kid": kid});
+		let header_b64 = URL_SAFE_NO_PAD.encode(header.to_string());
+		let payload_b64 = URL_SAFE_NO_PAD.encode(claims.to_string());
+		let signing_key = SigningKey::<Sha256>::new(TEST_KEY.clone());
+		let signed_input = format!("{header_b64}.{payload_b64}");
+		let signature = signing_key.sign_with_rng(&mut rsa::rand_core::OsRng, signed_input.as_bytes());
+		let signature_b64 = URL_SAFE_NO_PAD.encode(signature.to_bytes());
+		format!("{header_b64}.{payload_b64}.{signature_b64}")
+	}
+
+	fn valid_claims() -> serde_json::Value {
+		serde_json::json!({
+			"iss": "accounts.google.com",
+			"aud": TEST_CLIENT_ID,
+			"exp": chrono::Utc::now().timestamp() + 3600,
+			"email": "person@example.com",
+			"email_verified": true,
+		})
+	}
+
+	/// A one-shot in-process JWKS server: answers every connection with
+	/// `keys_json` and counts how many requests it has seen, so tests can
+	/// assert on the kid-miss refetch instead of guessing at timing.
+	struct MockJwks {
+		url: String,
+		requests: Arc<AtomicUsize>,
+	}
+
+	fn spawn_mock_jwks(keys_json: serde_json::Value) -> MockJwks {
+		let requests = Arc::new(AtomicUsize::new(0));
+		let requests_for_server = requests.clone();
+		let std_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		std_listener.set_nonblocking(true).unwrap();
+		let addr = std_listener.local_addr().unwrap();
+		let listener = tokio::net::TcpListener::from_std(std_listener).unwrap();
+		let body = keys_json.to_string();
+		tokio::spawn(async move {
+			loop {
+				let Ok((mut socket, _)) = listener.accept().await else {
+					break;
+				};
+				requests_for_server.fetch_add(1, Ordering::SeqCst);
+				let body = body.clone();
+				tokio::spawn(async move {
+					let mut buf = [0u8; 1024];
+					let _ = socket.read(&mut buf).await;
+					let response = format!(
+						"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+						body.len(),
+						body
+					);
+					let _ = socket.write_all(response.as_bytes()).await;
+				});
+			}
+		});
+		MockJwks {
+			url: format!("http://{addr}/certs"),
+			requests,
+		}
+	}
+
+	fn test_auth(jwks_url: &str, allowed_domains: Option<Vec<String>>) -> GoogleAuth {
+		GoogleAuth {
+			client_id: TEST_CLIENT_ID.to_string(),
+			allowed_domains,
+			http: reqwest::Client::new(),
+			offline_verify: true,
+			tokeninfo_fallback: false,
+			jwks: JwksClient::new(jwks_url.to_string(), reqwest::Client::new()),
+			client_secret: None,
+			redirect_uri: None,
+			pending_logins: Arc::new(Mutex::new(HashMap::new())),
+			sessions: Arc::new(InMemorySessionStore::new()),
+			session_secret: vec![0u8; 32].into(),
+			authorization: AuthorizationRules::default(),
+			verification_cache: Arc::new(Mutex::new(LruCache::new(NonZeroUsize::new(16).unwrap()))),
+			negative_cache_ttl: Duration::from_secs(5),
 		}
 	}
+
+	#[test]
+	fn split_jwt_rejects_anything_that_isnt_exactly_three_dot_separated_parts() {
+		assert!(split_jwt("onlyonepart").is_err());
+		assert!(split_jwt("two.parts").is_err());
+		assert!(split_jwt("way.too.many.parts").is_err());
+		assert!(split_jwt("a.b.c").is_ok());
+	}
+
+	#[tokio::test]
+	async fn verify_token_offline_rejects_a_malformed_token_without_touching_the_network() {
+		// The jwks url is unreachable; if this test hangs or errors for the
+		// wrong reason it means malformed-shape checking stopped happening
+		// before the network call.
+		let auth = test_auth("http://127.0.0.1:1/certs", None);
+		let err = auth.verify_token_offline("not-a-jwt").await.unwrap_err();
+		assert!(matches!(err, AuthError::Unauthorized(_)));
+	}
+
+	#[tokio::test]
+	async fn unknown_kid_forces_exactly_one_refetch_then_fails() {
+		let mock = spawn_mock_jwks(serde_json::json!({"keys": [test_jwk_json("some-other-kid")]}));
+		let auth = test_auth(&mock.url, None);
+		let token = sign_test_token(TEST_KID, valid_claims());
+
+		let err = auth.verify_token_offline(&token).await.unwrap_err();
+		assert!(matches!(err, AuthError::Unauthorized(_)));
+		assert_eq!(mock.requests.load(Ordering::SeqCst), 2);
+	}
+
+	#[tokio::test]
+	async fn accepts_a_token_that_expired_within_the_skew_window() {
+		let mock = spawn_mock_jwks(serde_json::json!({"keys": [test_jwk_json(TEST_KID)]}));
+		let auth = test_auth(&mock.url, None);
+		let mut claims = valid_claims();
+		claims["exp"] = serde_json::json!(chrono::Utc::now().timestamp() - (JWT_EXP_SKEW_SECS - 1));
+		let token = sign_test_token(TEST_KID, claims);
+
+		let user = auth.verify_token_offline(&token).await.unwrap();
+		assert_eq!(user.email, "person@example.com");
+	}
+
+	#[tokio::test]
+	async fn rejects_a_token_that_expired_past_the_skew_window() {
+		let mock = spawn_mock_jwks(serde_json::json!({"keys": [test_jwk_json(TEST_KID)]}));
+		let auth = test_auth(&mock.url, None);
+		let mut claims = valid_claims();
+		claims["exp"] = serde_json::json!(chrono::Utc::now().timestamp() - (JWT_EXP_SKEW_SECS + 1));
+		let token = sign_test_token(TEST_KID, claims);
+
+		let err = auth.verify_token_offline(&token).await.unwrap_err();
+		assert!(matches!(err, AuthError::Unauthorized(_)));
+	}
+
+	#[tokio::test]
+	async fn rejects_a_token_whose_audience_does_not_match_the_configured_client_id() {
+		let mock = spawn_mock_jwks(serde_json::json!({"keys": [test_jwk_json(TEST_KID)]}));
+		let auth = test_auth(&mock.url, None);
+		let mut claims = valid_claims();
+		claims["aud"] = serde_json::json!("someone-elses-client-id");
+		let token = sign_test_token(TEST_KID, claims);
+
+		let err = auth.verify_token_offline(&token).await.unwrap_err();
+		assert!(matches!(err, AuthError::Unauthorized(_)));
+	}
+
+	#[tokio::test]
+	async fn accepts_email_verified_as_either_a_bool_or_the_legacy_string_form() {
+		for value in [serde_json::json!(true), serde_json::json!("true")] {
+			let mock = spawn_mock_jwks(serde_json::json!({"keys": [test_jwk_json(TEST_KID)]}));
+			let auth = test_auth(&mock.url, None);
+			let mut claims = valid_claims();
+			claims["email_verified"] = value;
+			let token = sign_test_token(TEST_KID, claims);
+
+			auth.verify_token_offline(&token).await.unwrap();
+		}
+	}
+
+	#[tokio::test]
+	async fn rejects_email_verified_false_in_either_form() {
+		for value in [serde_json::json!(false), serde_json::json!("false")] {
+			let mock = spawn_mock_jwks(serde_json::json!({"keys": [test_jwk_json(TEST_KID)]}));
+			let auth = test_auth(&mock.url, None);
+			let mut claims = valid_claims();
+			claims["email_verified"] = value;
+			let token = sign_test_token(TEST_KID, claims);
+
+			let err = auth.verify_token_offline(&token).await.unwrap_err();
+			assert!(matches!(err, AuthError::Unauthorized(_)));
+		}
+	}
+
+	#[tokio::test]
+	async fn rejects_a_domain_outside_the_allowlist() {
+		let mock = spawn_mock_jwks(serde_json::json!({"keys": [test_jwk_json(TEST_KID)]}));
+		let auth = test_auth(&mock.url, Some(vec!["allowed.com".to_string()]));
+		let token = sign_test_token(TEST_KID, valid_claims());
+
+		let err = auth.verify_token_offline(&token).await.unwrap_err();
+		assert!(matches!(err, AuthError::Forbidden(_)));
+	}
+
+	#[tokio::test]
+	async fn accepts_a_domain_on_the_allowlist() {
+		let mock = spawn_mock_jwks(serde_json::json!({"keys": [test_jwk_json(TEST_KID)]}));
+		let auth = test_auth(&mock.url, Some(vec!["example.com".to_string()]));
+		let token = sign_test_token(TEST_KID, valid_claims());
+
+		let user = auth.verify_token_offline(&token).await.unwrap();
+		assert_eq!(user.email, "person@example.com");
+	}
 }
@@ -31,6 +31,7 @@ async fn cleanup_old_segments(ctx: &Context, min_free_ratio: f64) {
 					device_ids: None,
 					count: Some(1),
 					sort: Some(SortDir::Asc),
+					level: None,
 				})
 				.await
 				.unwrap_or_default();
@@ -42,6 +43,7 @@ async fn cleanup_old_segments(ctx: &Context, min_free_ratio: f64) {
 			log::warn!("deleting old segment {}", path.display());
 			let _ = remove_file(&path).await;
 			ctx.db.delete_segment(seg.id).await.ok();
+			ctx.segment_cache.invalidate(seg.id);
 			removed += 1;
 			free = disk_usage(ctx.logs_path()).map(|(f, _)| f).unwrap_or(free);
 		}
@@ -0,0 +1,147 @@
+//! Bloom filter over a segment's `"key=value"` prop strings, used by
+//! `Context::find_logs` and `LogSearcher::search` to skip the
+//! `fetch_segment_props` round-trip and segment decode for queries whose
+//! equality constraints can't possibly match. Must never produce a false
+//! negative: `might_contain` returning `false` has to mean the key is
+//! definitely absent from the segment.
+//!
+//! Sized per segment for `TARGET_FALSE_POSITIVE_RATE` given the segment's
+//! own distinct-prop count, rather than one fixed size for every segment —
+//! a chatty segment with hundreds of distinct `key=value` pairs would
+//! otherwise blow past a small filter's false-positive budget, while a
+//! quiet one would waste bytes on a filter sized for the chatty case.
+
+/// Target false-positive rate `with_expected_items` sizes the filter for.
+const TARGET_FALSE_POSITIVE_RATE: f64 = 0.01;
+/// Floor on filter size so a segment with only a handful of distinct props
+/// doesn't end up with a filter too small to hash meaningfully into.
+const MIN_BITS: usize = 64;
+/// Ceiling so a segment with an unusually large number of distinct props
+/// can't bloat its stored metadata without bound; `might_contain` just
+/// degrades towards "maybe" (more false positives) past this point.
+const MAX_BITS: usize = 1 << 20;
+/// Hash-count bounds so a pathological `expected_items` (zero, or huge)
+/// can't compute a degenerate number of hash passes.
+const MIN_HASHES: u32 = 1;
+const MAX_HASHES: u32 = 16;
+
+#[derive(Debug, Clone)]
+pub struct SegmentBloom {
+	bits: Vec<u8>,
+	num_hashes: u32,
+}
+
+impl SegmentBloom {
+	/// Sizes a new filter so that, once `expected_items` distinct keys have
+	/// been inserted, its false-positive rate is approximately
+	/// `TARGET_FALSE_POSITIVE_RATE`. Standard bloom filter sizing:
+	/// `m = -n*ln(p) / (ln2)^2` bits, with the hash count `k = (m/n)*ln2`
+	/// that minimizes the false-positive rate at that size.
+	pub fn with_expected_items(expected_items: usize) -> Self {
+		let n = expected_items.max(1) as f64;
+		let ideal_bits =
+			(-(n * TARGET_FALSE_POSITIVE_RATE.ln()) / std::f64::consts::LN_2.powi(2)).ceil();
+		let num_bytes = ((ideal_bits as usize).clamp(MIN_BITS, MAX_BITS)).div_ceil(8);
+		let num_bits = num_bytes * 8;
+		let ideal_hashes = ((num_bits as f64 / n) * std::f64::consts::LN_2).round() as u32;
+		let num_hashes = ideal_hashes.clamp(MIN_HASHES, MAX_HASHES);
+		Self {
+			bits: vec![0; num_bytes],
+			num_hashes,
+		}
+	}
+
+	pub fn insert(&mut self, key: &str) {
+		let num_bits = self.bits.len() * 8;
+		for seed in 0..self.num_hashes {
+			let bit = Self::hash(key, seed) as usize % num_bits;
+			self.bits[bit / 8] |= 1 << (bit % 8);
+		}
+	}
+
+	pub fn might_contain(&self, key: &str) -> bool {
+		let num_bits = self.bits.len() * 8;
+		(0..self.num_hashes).all(|seed| {
+			let bit = Self::hash(key, seed) as usize % num_bits;
+			self.bits[bit / 8] & (1 << (bit % 8)) != 0
+		})
+	}
+
+	/// Hash count prefixed as a single byte, followed by the bit array —
+	/// `num_hashes` varies per filter now that size does, so it has to
+	/// travel with the bits instead of being assumed at the call site.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(1 + self.bits.len());
+		out.push(self.num_hashes as u8);
+		out.extend_from_slice(&self.bits);
+		out
+	}
+
+	/// `None` for anything that doesn't look like a hash-count byte
+	/// followed by at least one byte of bits, so a corrupt or foreign-shaped
+	/// blob is treated as "no bloom" (maybe match) by the caller rather than
+	/// panicking.
+	pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+		let (&num_hashes, bits) = bytes.split_first()?;
+		if bits.is_empty() || num_hashes == 0 {
+			return None;
+		}
+		Some(Self {
+			bits: bits.to_vec(),
+			num_hashes: num_hashes as u32,
+		})
+	}
+
+	/// FNV-1a folded with `seed`, giving `num_hashes` independent-enough
+	/// hashes without pulling in an external hashing crate.
+	fn hash(key: &str, seed: u32) -> u64 {
+		let mut hash: u64 = 0xcbf29ce484222325 ^ (seed as u64);
+		for byte in key.as_bytes() {
+			hash ^= *byte as u64;
+			hash = hash.wrapping_mul(0x100000001b3);
+		}
+		hash
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn inserted_keys_are_found() {
+		let mut bloom = SegmentBloom::with_expected_items(2);
+		bloom.insert("deviceId=dev1");
+		bloom.insert("service=api");
+		assert!(bloom.might_contain("deviceId=dev1"));
+		assert!(bloom.might_contain("service=api"));
+	}
+
+	#[test]
+	fn absent_keys_are_usually_rejected() {
+		let mut bloom = SegmentBloom::with_expected_items(1);
+		bloom.insert("deviceId=dev1");
+		assert!(!bloom.might_contain("deviceId=dev2"));
+	}
+
+	#[test]
+	fn round_trips_through_bytes() {
+		let mut bloom = SegmentBloom::with_expected_items(1);
+		bloom.insert("level=ERROR");
+		let bytes = bloom.to_bytes();
+		let restored = SegmentBloom::from_bytes(&bytes).unwrap();
+		assert!(restored.might_contain("level=ERROR"));
+	}
+
+	#[test]
+	fn from_bytes_rejects_empty_bits() {
+		assert!(SegmentBloom::from_bytes(&[3u8]).is_none());
+	}
+
+	#[test]
+	fn larger_expected_item_counts_size_up_the_filter() {
+		let small = SegmentBloom::with_expected_items(1);
+		let large = SegmentBloom::with_expected_items(10_000);
+		assert!(large.to_bytes().len() > small.to_bytes().len());
+	}
+}
@@ -0,0 +1,143 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::segment::LogSegment;
+
+#[derive(Debug)]
+struct Entry {
+	segment: Arc<LogSegment>,
+	size: usize,
+}
+
+#[derive(Debug)]
+struct Inner {
+	entries: HashMap<u32, Entry>,
+	order: VecDeque<u32>,
+	bytes: usize,
+}
+
+/// Bounded, byte-size-aware LRU cache of decoded archive segments, shared
+/// across all `find_logs` queries. Dashboards and live tails issue many
+/// overlapping windows over the same recent segments, so caching the parsed
+/// `LogSegment` (not just the compressed bytes) turns a repeat scan into an
+/// in-memory filter pass instead of another `zstd::Decoder` + `LogSegment::parse`.
+#[derive(Debug)]
+pub struct SegmentCache {
+	max_bytes: usize,
+	inner: Mutex<Inner>,
+}
+
+impl SegmentCache {
+	pub fn new(max_bytes: usize) -> Self {
+		Self {
+			max_bytes,
+			inner: Mutex::new(Inner {
+				entries: HashMap::new(),
+				order: VecDeque::new(),
+				bytes: 0,
+			}),
+		}
+	}
+
+	/// Returns the cached segment, marking it most-recently-used, or `None`
+	/// on a cache miss.
+	pub fn get(&self, segment_id: u32) -> Option<Arc<LogSegment>> {
+		let mut inner = self.inner.lock().unwrap();
+		if !inner.entries.contains_key(&segment_id) {
+			return None;
+		}
+		inner.order.retain(|id| *id != segment_id);
+		inner.order.push_back(segment_id);
+		inner.entries.get(&segment_id).map(|e| e.segment.clone())
+	}
+
+	/// Inserts a freshly decoded segment, evicting the least-recently-used
+	/// entries until the cache fits back under `max_bytes`. A segment larger
+	/// than the whole budget is simply not cached.
+	pub fn insert(&self, segment_id: u32, segment: Arc<LogSegment>, size: usize) {
+		if size > self.max_bytes {
+			return;
+		}
+		let mut inner = self.inner.lock().unwrap();
+		if let Some(old) = inner.entries.remove(&segment_id) {
+			inner.bytes -= old.size;
+			inner.order.retain(|id| *id != segment_id);
+		}
+		inner.bytes += size;
+		inner.entries.insert(segment_id, Entry { segment, size });
+		inner.order.push_back(segment_id);
+		while inner.bytes > self.max_bytes {
+			let Some(oldest) = inner.order.pop_front() else {
+				break;
+			};
+			if let Some(entry) = inner.entries.remove(&oldest) {
+				inner.bytes -= entry.size;
+			}
+		}
+	}
+
+	/// Drops a segment from the cache, e.g. because compaction, retention, or
+	/// manual deletion rewrote or removed the segment id. Without this a
+	/// stale decoded copy could keep being served after the underlying
+	/// segment is gone.
+	pub fn invalidate(&self, segment_id: u32) {
+		let mut inner = self.inner.lock().unwrap();
+		if let Some(entry) = inner.entries.remove(&segment_id) {
+			inner.bytes -= entry.size;
+			inner.order.retain(|id| *id != segment_id);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use puppylog::LogEntry;
+
+	fn segment() -> Arc<LogSegment> {
+		Arc::new(LogSegment::with_logs(vec![LogEntry::default()]))
+	}
+
+	#[test]
+	fn misses_until_inserted() {
+		let cache = SegmentCache::new(1024);
+		assert!(cache.get(1).is_none());
+		cache.insert(1, segment(), 100);
+		assert!(cache.get(1).is_some());
+	}
+
+	#[test]
+	fn evicts_least_recently_used_past_the_byte_budget() {
+		let cache = SegmentCache::new(150);
+		cache.insert(1, segment(), 100);
+		cache.insert(2, segment(), 100);
+		assert!(cache.get(1).is_none());
+		assert!(cache.get(2).is_some());
+	}
+
+	#[test]
+	fn recently_read_entries_survive_eviction() {
+		let cache = SegmentCache::new(250);
+		cache.insert(1, segment(), 100);
+		cache.insert(2, segment(), 100);
+		cache.get(1);
+		cache.insert(3, segment(), 100);
+		assert!(cache.get(1).is_some());
+		assert!(cache.get(2).is_none());
+	}
+
+	#[test]
+	fn invalidate_drops_an_entry() {
+		let cache = SegmentCache::new(1024);
+		cache.insert(1, segment(), 100);
+		cache.invalidate(1);
+		assert!(cache.get(1).is_none());
+	}
+
+	#[test]
+	fn oversized_segment_is_not_cached() {
+		let cache = SegmentCache::new(50);
+		cache.insert(1, segment(), 100);
+		assert!(cache.get(1).is_none());
+	}
+}
@@ -0,0 +1,220 @@
+//! Content-defined chunking for segment bodies, as explored in Garage's CDC
+//! work: repeated payloads (retried uploads, near-duplicate bursts across
+//! devices) get split into chunks that are stored once and referenced by
+//! hash, instead of paying full storage for every segment that happens to
+//! contain them. See `DB::store_segment_chunks`/`DB::read_segment_chunks`
+//! for how the chunk list turns into `chunks`/`segment_chunks` rows.
+//!
+//! Boundaries are cut with a Buzhash rolling hash over a fixed trailing
+//! window: slide byte by byte, and cut whenever `hash & mask == 0`, clamped
+//! to `[min_size, max_size]`. Because the hash only ever depends on the
+//! last `WINDOW` bytes, two copies of the same content fall into step at
+//! the first cut point they share and cut at the same boundaries from there
+//! on, regardless of what precedes or follows them — the property that
+//! makes cross-segment dedup possible. The Buzhash table is seeded
+//! from a fixed constant (via `splitmix64`, not `rand`) so it's identical
+//! across processes and restarts.
+
+use std::sync::OnceLock;
+
+/// Trailing window Buzhash mixes into the rolling hash. Large enough that a
+/// handful of changed bytes doesn't wash out, small enough to stay cheap
+/// per byte.
+const WINDOW: usize = 48;
+
+/// Bounds and target size for `cut_chunks`. `avg_size` is rounded up to the
+/// next power of two: the cut mask is `avg_size - 1`, so a hash is "low
+/// enough" to cut on roughly 1-in-`avg_size` window positions.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkingConfig {
+	pub min_size: usize,
+	pub avg_size: usize,
+	pub max_size: usize,
+	/// Whether `DB::store_segment_chunks` should cut and dedup chunks at
+	/// all. `false` lets an operator skip the extra transaction on every
+	/// segment write for a workload where devices rarely repeat content,
+	/// without losing `read_segment_chunks`' ability to reassemble segments
+	/// written while it was on.
+	pub enabled: bool,
+}
+
+impl Default for ChunkingConfig {
+	fn default() -> Self {
+		ChunkingConfig {
+			min_size: 4 * 1024,
+			avg_size: 16 * 1024,
+			max_size: 64 * 1024,
+			enabled: true,
+		}
+	}
+}
+
+impl ChunkingConfig {
+	/// Reads `SEGMENT_CHUNKING_ENABLED`, `SEGMENT_CHUNKING_MIN_SIZE_BYTES`,
+	/// `SEGMENT_CHUNKING_AVG_SIZE_BYTES` and `SEGMENT_CHUNKING_MAX_SIZE_BYTES`,
+	/// falling back to `Default` for anything unset or unparseable.
+	pub fn from_env() -> Self {
+		let default = Self::default();
+		Self {
+			min_size: std::env::var("SEGMENT_CHUNKING_MIN_SIZE_BYTES")
+				.ok()
+				.and_then(|v| v.parse().ok())
+				.unwrap_or(default.min_size),
+			avg_size: std::env::var("SEGMENT_CHUNKING_AVG_SIZE_BYTES")
+				.ok()
+				.and_then(|v| v.parse().ok())
+				.unwrap_or(default.avg_size),
+			max_size: std::env::var("SEGMENT_CHUNKING_MAX_SIZE_BYTES")
+				.ok()
+				.and_then(|v| v.parse().ok())
+				.unwrap_or(default.max_size),
+			enabled: std::env::var("SEGMENT_CHUNKING_ENABLED")
+				.ok()
+				.and_then(|v| v.parse().ok())
+				.unwrap_or(default.enabled),
+		}
+	}
+}
+
+fn splitmix64(x: u64) -> u64 {
+	let x = x.wrapping_add(0x9E3779B97F4A7C15);
+	let mut z = x;
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+	z ^ (z >> 31)
+}
+
+/// One pseudo-random `u64` per byte value, generated once from a fixed seed
+/// via `splitmix64` so boundaries are stable across runs without pulling in
+/// `rand`.
+fn buzhash_table() -> &'static [u64; 256] {
+	static TABLE: OnceLock<[u64; 256]> = OnceLock::new();
+	TABLE.get_or_init(|| {
+		let mut state = 0x9E3779B97F4A7C15u64;
+		let mut table = [0u64; 256];
+		for slot in table.iter_mut() {
+			state = splitmix64(state);
+			*slot = state;
+		}
+		table
+	})
+}
+
+/// Splits `data` into content-defined chunks. Empty input yields no chunks;
+/// otherwise every byte belongs to exactly one chunk and the chunks
+/// concatenate back to `data`.
+pub fn cut_chunks(data: &[u8], config: &ChunkingConfig) -> Vec<&[u8]> {
+	if data.is_empty() {
+		return Vec::new();
+	}
+	let table = buzhash_table();
+	let mask = (config.avg_size.next_power_of_two() as u64).saturating_sub(1).max(1);
+
+	let mut chunks = Vec::new();
+	let mut start = 0usize;
+	let mut hash: u64 = 0;
+	for i in 0..data.len() {
+		hash = hash.rotate_left(1) ^ table[data[i] as usize];
+		if i + 1 - start > WINDOW {
+			let outgoing = data[i - WINDOW];
+			hash ^= table[outgoing as usize].rotate_left((WINDOW % 64) as u32);
+		}
+		let len = i + 1 - start;
+		if len >= config.max_size || (len >= config.min_size && hash & mask == 0) {
+			chunks.push(&data[start..=i]);
+			start = i + 1;
+			hash = 0;
+		}
+	}
+	if start < data.len() {
+		chunks.push(&data[start..]);
+	}
+	chunks
+}
+
+/// Content key for a chunk. Two independent FNV-1a passes (different seeds)
+/// widened to a 128-bit hex string, the same dependency-free hash family
+/// `checksum`/`SegmentBloom` already use, rather than pulling in a
+/// dedicated crate like blake2 for one call site.
+pub fn content_hash(bytes: &[u8]) -> String {
+	format!(
+		"{:016x}{:016x}",
+		crate::checksum::checksum(bytes),
+		fnv1a_seeded(bytes, 0x84222325_cbf29ce4)
+	)
+}
+
+fn fnv1a_seeded(bytes: &[u8], seed: u64) -> u64 {
+	let mut hash = seed;
+	for byte in bytes {
+		hash ^= *byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+	hash
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reassembles_to_the_original_bytes() {
+		let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+		let chunks = cut_chunks(&data, &ChunkingConfig::default());
+		let reassembled: Vec<u8> = chunks.into_iter().flatten().copied().collect();
+		assert_eq!(reassembled, data);
+	}
+
+	#[test]
+	fn identical_shared_content_cuts_identical_boundaries() {
+		let shared: Vec<u8> = (0..50_000u64).map(|i| splitmix64(i) as u8).collect();
+		let mut a = vec![1u8; 1000];
+		a.extend_from_slice(&shared);
+		let mut b = vec![2u8; 3000];
+		b.extend_from_slice(&shared);
+
+		let chunks_a = cut_chunks(&a, &ChunkingConfig::default());
+		let chunks_b = cut_chunks(&b, &ChunkingConfig::default());
+		let hashes_a: Vec<String> = chunks_a.iter().map(|c| content_hash(c)).collect();
+		let hashes_b: Vec<String> = chunks_b.iter().map(|c| content_hash(c)).collect();
+
+		// The shared suffix should reappear as an identical run of chunk
+		// hashes at the tail of both, even though the prefixes differ in
+		// both content and length.
+		let shared_tail = hashes_a.len().min(hashes_b.len());
+		assert!(shared_tail > 0);
+		assert_eq!(
+			hashes_a[hashes_a.len() - 1],
+			hashes_b[hashes_b.len() - 1],
+			"last chunk of the shared suffix should match"
+		);
+	}
+
+	#[test]
+	fn respects_min_and_max_size() {
+		let data = vec![7u8; 500_000];
+		let config = ChunkingConfig {
+			min_size: 1024,
+			avg_size: 8192,
+			max_size: 16384,
+			enabled: true,
+		};
+		let chunks = cut_chunks(&data, &config);
+		for (i, chunk) in chunks.iter().enumerate() {
+			assert!(chunk.len() <= config.max_size);
+			if i + 1 < chunks.len() {
+				assert!(chunk.len() >= config.min_size);
+			}
+		}
+	}
+
+	#[test]
+	fn same_bytes_hash_the_same() {
+		assert_eq!(content_hash(b"hello world"), content_hash(b"hello world"));
+	}
+
+	#[test]
+	fn chunking_is_enabled_by_default() {
+		assert!(ChunkingConfig::default().enabled);
+	}
+}
@@ -0,0 +1,83 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+
+/// How many recent chunk digests to remember per device before evicting the
+/// oldest. Bounds memory for devices that never stop sending; dedup only
+/// needs to cover the retransmit window of a flaky connection, not history.
+const MAX_DIGESTS_PER_DEVICE: usize = 256;
+
+#[derive(Debug, Default)]
+struct DeviceDigests {
+	seen: HashSet<String>,
+	order: VecDeque<String>,
+}
+
+impl DeviceDigests {
+	fn remember(&mut self, digest: String) {
+		if !self.seen.insert(digest.clone()) {
+			return;
+		}
+		self.order.push_back(digest);
+		if self.order.len() > MAX_DIGESTS_PER_DEVICE {
+			if let Some(oldest) = self.order.pop_front() {
+				self.seen.remove(&oldest);
+			}
+		}
+	}
+}
+
+/// Tracks which content-addressed log chunk digests a device has already
+/// successfully uploaded, so the client can skip re-sending chunk bodies the
+/// server already has after a retransmit.
+#[derive(Debug, Default)]
+pub struct ChunkManifestCache {
+	devices: Mutex<HashMap<String, DeviceDigests>>,
+}
+
+impl ChunkManifestCache {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn contains(&self, device_id: &str, digest: &str) -> bool {
+		let devices = self.devices.lock().unwrap();
+		devices
+			.get(device_id)
+			.map(|d| d.seen.contains(digest))
+			.unwrap_or(false)
+	}
+
+	pub fn remember(&self, device_id: &str, digest: &str) {
+		let mut devices = self.devices.lock().unwrap();
+		devices
+			.entry(device_id.to_string())
+			.or_default()
+			.remember(digest.to_string());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn remembers_and_reports_known_digests() {
+		let cache = ChunkManifestCache::new();
+		assert!(!cache.contains("dev1", "abc"));
+		cache.remember("dev1", "abc");
+		assert!(cache.contains("dev1", "abc"));
+		assert!(!cache.contains("dev2", "abc"));
+	}
+
+	#[test]
+	fn evicts_oldest_digest_past_the_cap() {
+		let cache = ChunkManifestCache::new();
+		for i in 0..MAX_DIGESTS_PER_DEVICE {
+			cache.remember("dev1", &format!("digest-{i}"));
+		}
+		assert!(cache.contains("dev1", "digest-0"));
+		cache.remember("dev1", "digest-overflow");
+		assert!(!cache.contains("dev1", "digest-0"));
+		assert!(cache.contains("dev1", "digest-overflow"));
+	}
+}
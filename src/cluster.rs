@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use puppylog::{LogEntry, QueryAst};
+use tokio::sync::mpsc;
+
+/// Read-only list of peer nodes this node fans a search out to, modeled on
+/// lavina's cluster metadata: just addresses, no membership protocol or
+/// gossip. Loaded once at startup and handed to every `LogSearcher`; an empty
+/// list (the default) means this node searches alone, exactly as it did
+/// before cluster support existed.
+#[derive(Debug, Clone, Default)]
+pub struct ClusterMetadata {
+	pub peers: Vec<String>,
+}
+
+impl ClusterMetadata {
+	/// Reads `CLUSTER_PEERS` (comma-separated base URLs, e.g.
+	/// `http://node-a:3337,http://node-b:3337`), trimming blanks. Unset or
+	/// empty means no peers.
+	pub fn from_env() -> Self {
+		let peers = std::env::var("CLUSTER_PEERS")
+			.unwrap_or_default()
+			.split(',')
+			.map(|s| s.trim().to_string())
+			.filter(|s| !s.is_empty())
+			.collect();
+		Self { peers }
+	}
+
+	pub fn peer_clients(&self) -> Vec<PeerClient> {
+		self.peers.iter().cloned().map(PeerClient::new).collect()
+	}
+}
+
+/// Talks to one other node's `/api/v1/cluster/search` endpoint: POSTs the
+/// serialized `QueryAst` and decodes the newline-delimited `LogEntry` JSON it
+/// streams back.
+#[derive(Debug, Clone)]
+pub struct PeerClient {
+	addr: String,
+	http: reqwest::Client,
+}
+
+impl PeerClient {
+	pub fn new(addr: String) -> Self {
+		Self {
+			addr,
+			http: reqwest::Client::new(),
+		}
+	}
+
+	pub fn addr(&self) -> &str {
+		&self.addr
+	}
+
+	/// Runs `query` against this peer and forwards every entry it streams
+	/// back into `tx`, in the order the peer sent them. Logs and returns
+	/// rather than propagating an error on timeout/connection/decode
+	/// failure, since one unreachable peer shouldn't abort the whole cluster
+	/// search — the coordinator just merges in whatever sources did respond.
+	pub async fn search(&self, query: &QueryAst, tx: &mpsc::Sender<LogEntry>) {
+		if let Err(err) = self.search_inner(query, tx).await {
+			log::error!("cluster peer {} search failed: {}", self.addr, err);
+		}
+	}
+
+	async fn search_inner(
+		&self,
+		query: &QueryAst,
+		tx: &mpsc::Sender<LogEntry>,
+	) -> anyhow::Result<()> {
+		let resp = self
+			.http
+			.post(format!("{}/api/v1/cluster/search", self.addr))
+			.json(query)
+			.timeout(Duration::from_secs(30))
+			.send()
+			.await?
+			.error_for_status()?;
+
+		let mut buf = String::new();
+		let mut body = resp.bytes_stream();
+		while let Some(chunk) = body.next().await {
+			buf.push_str(&String::from_utf8_lossy(&chunk?));
+			while let Some(pos) = buf.find('\n') {
+				let line = buf[..pos].to_string();
+				buf.drain(..=pos);
+				if line.is_empty() {
+					continue;
+				}
+				let entry: LogEntry = serde_json::from_str(&line)?;
+				if tx.send(entry).await.is_err() {
+					return Ok(());
+				}
+			}
+		}
+		Ok(())
+	}
+}
@@ -25,3 +25,14 @@ pub fn upload_path() -> std::path::PathBuf {
 		Err(_) => std::path::Path::new("./uploads").to_owned(),
 	}
 }
+
+/// How many archived segments `LogSearcher::search` decodes concurrently
+/// within one window. Defaults to the host's available parallelism, since
+/// decode is CPU-bound (zstd + `check_expr`) and bottlenecks on cores, not
+/// I/O concurrency.
+pub fn search_decode_concurrency() -> usize {
+	match std::env::var("SEARCH_DECODE_CONCURRENCY") {
+		Ok(val) => val.parse().unwrap_or_else(|_| num_cpus::get()),
+		Err(_) => num_cpus::get(),
+	}
+}
@@ -0,0 +1,34 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+use crate::context::Context;
+
+const REPAIR_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Background task periodically reconciling `devices.logs_size`/`logs_count`
+/// against the `log_segments` rows that actually exist, and clearing out
+/// `segment_props`/`bucket_logs` rows left behind by a deleted segment or
+/// bucket. Always runs for real (never `dry_run`) — operators wanting a
+/// preview can call `DB::repair_device_stats(true)` directly instead.
+pub async fn run_consistency_repair(ctx: Arc<Context>) {
+	loop {
+		match ctx.db.repair_device_stats(false).await {
+			Ok(report) if report.devices_fixed > 0
+				|| report.orphaned_segment_props_removed > 0
+				|| report.orphaned_bucket_logs_removed > 0 =>
+			{
+				log::info!(
+					"consistency_repair: fixed {} device(s), removed {} orphaned segment_props row(s) and {} orphaned bucket_logs row(s)",
+					report.devices_fixed,
+					report.orphaned_segment_props_removed,
+					report.orphaned_bucket_logs_removed
+				);
+			}
+			Ok(_) => {}
+			Err(e) => log::error!("consistency_repair: failed to repair device stats: {}", e),
+		}
+		sleep(REPAIR_INTERVAL).await;
+	}
+}
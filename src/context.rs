@@ -1,73 +1,293 @@
+use crate::bloom::SegmentBloom;
+use crate::cache::SegmentCache;
+use crate::chunk_manifest::ChunkManifestCache;
+use crate::cluster::{ClusterMetadata, PeerClient};
 use crate::db::open_db;
 use crate::db::NewSegmentArgs;
 use crate::db::DB;
+use crate::level_compactor::LevelCompactionConfig;
+use crate::metrics::Metrics;
+use crate::search::{ExportedSegment, LogSearcher, LogStreamItem};
 use crate::segment::compress_segment;
 use crate::segment::LogSegment;
+use crate::segment::SegmentCorrupt;
+use crate::segment::SegmentMeta;
+use crate::segment_store::{build_segment_store, SegmentStore};
 use crate::settings::Settings;
-use crate::subscribe_worker::Subscriber;
-use crate::subscribe_worker::Worker;
+use crate::subscribe_worker::WorkerManager as LiveTailWorkers;
+use crate::supervisor::WorkerManager;
 use crate::types::GetSegmentsQuery;
-use crate::upload_guard::UploadGuard;
+use crate::upload_guard::{AsyncUploadGuard, AsyncUploadLimiter};
 use crate::wal::load_logs_from_wal;
 use crate::wal::Wal;
 use chrono::DateTime;
 use chrono::Utc;
+use futures::future::select_all;
 use puppylog::match_date_range;
+use puppylog::Expr;
+use puppylog::LogCursor;
 use puppylog::LogEntry;
 use puppylog::Prop;
 use puppylog::PuppylogEvent;
 use puppylog::QueryAst;
-use puppylog::{check_expr, check_props, extract_device_ids, timestamp_bounds};
+use puppylog::{
+	check_expr, check_props, extract_device_ids, extract_equality_props, simplify, timestamp_bounds,
+};
 use std::collections::{HashMap, HashSet};
-use std::fs::File;
-use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::sync::Mutex as StdMutex;
 use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
-use tokio::sync::mpsc::Sender;
 use tokio::sync::Mutex;
 
 const CONCURRENCY_LIMIT: usize = 10;
 /// Default number of buffered log entries before logs are flushed to disk.
 pub const UPLOAD_FLUSH_THRESHOLD: usize = 3_000_000;
+/// Default byte budget for the decoded-segment cache, overridable via
+/// `SEGMENT_CACHE_MAX_BYTES`.
+const SEGMENT_CACHE_DEFAULT_BYTES: usize = 256 * 1024 * 1024;
 
 #[derive(Debug)]
 pub struct Context {
-	pub subscriber: Subscriber,
-	pub publisher: Sender<LogEntry>,
+	/// Live-tail subscriber pool: `find_logs`/`LogSearcher` callers aside,
+	/// `stream_logs` registers here so newly ingested entries (fanned out
+	/// via `save_logs`) reach it without rescanning history.
+	pub subscriber: LiveTailWorkers,
 	pub settings: Settings,
 	pub event_tx: broadcast::Sender<PuppylogEvent>,
+	/// Fed one clone of every entry `save_logs` buffers, so a
+	/// `LogSearcher` in `StreamMode::Subscribe`/`SubscribeFuture` can
+	/// `subscribe()` a receiver and tail newly ingested logs instead of
+	/// only ever seeing the historical snapshot. Lagging subscribers drop
+	/// the oldest queued entries rather than blocking ingestion.
+	pub log_tail: broadcast::Sender<LogEntry>,
 	pub db: DB,
 	pub current: Mutex<LogSegment>,
 	pub wal: Wal,
-	pub upload_queue: AtomicUsize,
+	pub workers: WorkerManager,
+	pub upload_limiter: AsyncUploadLimiter,
+	pub metrics: Metrics,
+	pub chunk_manifest: ChunkManifestCache,
+	pub segment_cache: Arc<SegmentCache>,
+	pub store: Arc<dyn SegmentStore>,
+	/// Count/size/max-level thresholds `LevelCompactor` uses, loaded once
+	/// from `LEVEL_COMPACTION_*` env vars (see
+	/// `LevelCompactionConfig::from_env`).
+	pub level_compaction: LevelCompactionConfig,
+	/// Min/avg/max chunk sizes and on/off switch `store_segment_chunks`
+	/// cuts content-defined chunks with, loaded once from
+	/// `SEGMENT_CHUNKING_*` env vars (see `ChunkingConfig::from_env`).
+	pub chunking: crate::cdc::ChunkingConfig,
+	/// Free list of reclaimable segment ids `DeviceMerger` recycles for
+	/// freshly flushed segments instead of always minting a new one, so a
+	/// batch pass's delete-old/create-new churn doesn't transiently double
+	/// its on-disk footprint. See `SegmentSlotAllocator`.
+	pub segment_slots: crate::segment_slot::SegmentSlotAllocator,
+	/// Other cluster nodes, loaded from `CLUSTER_PEERS`, that
+	/// `find_logs_with_progress` fans searches out to alongside the local
+	/// store. Empty on a node run without that env var, which keeps search
+	/// behaving exactly as it did before cluster support existed.
+	pub peers: Vec<PeerClient>,
+	/// Coalesced per-segment last-read timestamps, flushed to
+	/// `log_segments.last_accessed` by `AccessTrackerWorker` and consulted by
+	/// `EvictionOrder::LeastRecentlyUsed` retention passes.
+	pub access_tracker: Arc<crate::access_tracker::AccessTracker>,
+	/// Single-flight guard so only one live `cleanup_old_segments` pass runs
+	/// at a time; a dry-run preview doesn't take this.
+	pub cleanup_running: std::sync::atomic::AtomicBool,
+	pub cleanup_status: StdMutex<crate::cleanup::CleanupStatus>,
+	/// Free-space ratios and alert byte-thresholds `run_disk_space_monitor`
+	/// reacts to, loaded once from `DISK_CLEANUP_*` env vars (see
+	/// `DiskCleanupConfig::from_env`).
+	pub disk_cleanup: crate::cleanup::DiskCleanupConfig,
+	/// AES-256-GCM master key from `SEGMENT_ENCRYPTION_KEY`. `None` disables
+	/// encryption-at-rest entirely, leaving existing plaintext segments and
+	/// the write path untouched.
+	encryption_key: Option<[u8; crate::encryption::KEY_LEN]>,
+	/// Bounds log entries accepted by `save_logs` per second, so a single
+	/// noisy device can't monopolize flush/compaction I/O.
+	ingest_limiter: crate::rate_limit::TokenBucket,
+	/// Bounds bytes read from segment storage per second while walking
+	/// archived segments, so a broad historical query yields instead of
+	/// saturating disk I/O for everyone else.
+	pub scan_limiter: Arc<crate::rate_limit::TokenBucket>,
 	upload_flush_threshold: AtomicUsize,
 	logs_path: PathBuf,
 	wal_max_bytes: u64,
 	flush_interval: Duration,
 	last_flush: StdMutex<Instant>,
+	/// Gates the `device="..."`/`bucket="..."` labels on the DB-derived
+	/// `/metrics` gauges. Off by default: a fleet-wide deployment can have
+	/// enough devices or buckets that per-entity labels blow up scrape
+	/// cardinality, so an operator opts in via `METRICS_PER_DEVICE_LABELS`
+	/// once they know their fleet size is small enough to afford it.
+	pub metrics_per_device_labels: bool,
+	/// `None` when `GOOGLE_OAUTH_CLIENT_ID` isn't set, which disables the
+	/// Google login flow and `RequireRole`/`RequirePermission` entirely
+	/// rather than failing requests at startup.
+	google_auth: Option<Arc<crate::auth::GoogleAuth>>,
+	/// Bearer-token backends `MaybeAuthUser` tries in turn, beyond the
+	/// session-cookie check `google_auth` handles on its own.
+	auth_backends: Vec<Arc<dyn crate::auth::AuthBackend>>,
+	/// `None` unless `SERVICE_ACCOUNTS_JSON` registers at least one account.
+	service_account_backend: Option<Arc<crate::auth::ServiceAccountBackend>>,
+}
+
+/// Filters one decoded segment, returning `(intra_segment_index, entry)`
+/// pairs newest-first so a match can be turned straight into a [`LogCursor`].
+/// `index_ceiling` is `Some(i)` when resuming a page inside this exact
+/// segment: indices at or past `i` were already delivered (or are the resume
+/// point itself) and are skipped, regardless of `end_cutoff`.
+fn filter_entries(
+	segment: &LogSegment,
+	root: &Expr,
+	tz: &chrono::FixedOffset,
+	end_cutoff: DateTime<Utc>,
+	index_ceiling: Option<usize>,
+) -> Vec<(usize, LogEntry)> {
+	let mut matched = Vec::new();
+	for (idx, entry) in segment.buffer.iter().enumerate().rev() {
+		if let Some(ceiling) = index_ceiling {
+			if idx >= ceiling {
+				continue;
+			}
+		}
+		if entry.timestamp > end_cutoff {
+			continue;
+		}
+		if matches!(check_expr(root, entry, tz), Ok(true)) {
+			matched.push((idx, entry.clone()));
+		}
+	}
+	matched
+}
+
+/// Loads, decompresses, and filters one archived segment, returning the
+/// entries that satisfy `root` and fall at or before `end_cutoff`, plus
+/// whether the decoded segment came from `cache` (for hit/miss metrics).
+/// Split out of `find_logs` so it can run as its own `tokio::spawn`ed task:
+/// the store read stays async, while the zstd decode and `check_expr` scan —
+/// both CPU-bound — run on a blocking-pool thread instead of the async
+/// runtime.
+async fn decode_segment_entries(
+	store: Arc<dyn SegmentStore>,
+	cache: Arc<SegmentCache>,
+	scan_limiter: Arc<crate::rate_limit::TokenBucket>,
+	segment_id: u32,
+	original_size: usize,
+	compressed_size: usize,
+	expected_checksum: Option<u64>,
+	encrypted: bool,
+	encryption_key: Option<[u8; crate::encryption::KEY_LEN]>,
+	data_dir: Option<String>,
+	compressed: bool,
+	root: Arc<Expr>,
+	tz: chrono::FixedOffset,
+	end_cutoff: DateTime<Utc>,
+	index_ceiling: Option<usize>,
+) -> anyhow::Result<(Vec<(usize, LogEntry)>, bool)> {
+	if let Some(cached) = cache.get(segment_id) {
+		let matched = filter_entries(&cached, &root, &tz, end_cutoff, index_ceiling);
+		return Ok((matched, true));
+	}
+	// Only a cache miss actually touches disk, so only a cache miss pays the
+	// scan-rate cost: a wide query re-reading already-cached segments
+	// shouldn't be throttled for I/O it isn't doing.
+	scan_limiter.acquire(compressed_size as f64).await;
+	let bytes = store.get(segment_id, data_dir.as_deref()).await?;
+	// Verify the bytes we just read match what was recorded at write time
+	// before spending a blocking-pool thread on decompressing them. `None`
+	// means the segment predates checksums, so it's trusted unconditionally.
+	if let Some(expected) = expected_checksum {
+		if crate::checksum::checksum(&bytes) != expected {
+			return Err(SegmentCorrupt { segment_id }.into());
+		}
+	}
+	let bytes = if encrypted {
+		let key = encryption_key.ok_or_else(|| {
+			anyhow::anyhow!(
+				"segment {} is encrypted but no SEGMENT_ENCRYPTION_KEY is configured",
+				segment_id
+			)
+		})?;
+		crate::encryption::decrypt(&key, segment_id, &bytes)?
+	} else {
+		bytes
+	};
+	let (parsed, matched) = tokio::task::spawn_blocking(move || {
+		let parsed = if compressed {
+			let mut decoder = zstd::Decoder::new(std::io::Cursor::new(bytes)).unwrap();
+			LogSegment::parse(&mut decoder)
+		} else {
+			LogSegment::parse(&mut std::io::Cursor::new(bytes))
+		}
+		.unwrap_or_else(|err| {
+			log::warn!("segment {} failed to parse: {}", segment_id, err);
+			err.recovered()
+		});
+		let matched = filter_entries(&parsed, &root, &tz, end_cutoff, index_ceiling);
+		(parsed, matched)
+	})
+	.await?;
+	cache.insert(segment_id, Arc::new(parsed), original_size);
+	Ok((matched, false))
+}
+
+/// Drops replayed WAL entries that are already covered by a durably flushed
+/// segment for their device, keyed on `(device_id, timestamp)`. A crash can
+/// leave the WAL un-truncated even after some devices' entries made it to
+/// disk (`flush_locked` only clears it once every device flushed cleanly), so
+/// a naive replay would re-buffer — and eventually re-flush — duplicates.
+async fn dedup_replayed_logs(db: &DB, logs: Vec<LogEntry>) -> Vec<LogEntry> {
+	let mut cutoffs: HashMap<String, Option<DateTime<Utc>>> = HashMap::new();
+	let mut kept = Vec::with_capacity(logs.len());
+	for log in logs {
+		let device_id = log
+			.props
+			.iter()
+			.find(|p| p.key == "deviceId")
+			.map(|p| p.value.to_string())
+			.unwrap_or_else(|| crate::dev_segment_merger::UNKNOWN_DEVICE_ID.to_string());
+		let cutoff = match cutoffs.get(&device_id) {
+			Some(cutoff) => *cutoff,
+			None => {
+				let cutoff = db
+					.prev_segment_end(None, Some(&[device_id.clone()]))
+					.await
+					.unwrap_or(None);
+				cutoffs.insert(device_id.clone(), cutoff);
+				cutoff
+			}
+		};
+		match cutoff {
+			Some(cutoff) if log.timestamp <= cutoff => {
+				// Already durably flushed in a segment written before the
+				// crash; the WAL copy is a stale duplicate.
+			}
+			_ => kept.push(log),
+		}
+	}
+	kept
 }
 
 impl Context {
 	pub async fn new<P: AsRef<Path>>(logs_path: P) -> Self {
-		let (subtx, subrx) = mpsc::channel(100);
-		let (pubtx, pubrx) = mpsc::channel(100);
-		tokio::spawn(async move {
-			Worker::new(subrx, pubrx).run().await;
-		});
+		let subscriber = LiveTailWorkers::new();
 		let (event_tx, _) = broadcast::channel(100);
+		let (log_tail, _) = broadcast::channel(1000);
 		let wal = Wal::new();
+		let db = DB::new(open_db());
 		let logs = if cfg!(test) {
 			Vec::new()
 		} else {
-			load_logs_from_wal()
+			let replayed = load_logs_from_wal();
+			dedup_replayed_logs(&db, replayed).await
 		};
-		let db = DB::new(open_db());
 		let settings = if cfg!(test) {
 			Settings::new()
 		} else {
@@ -84,20 +304,143 @@ impl Context {
 			.map(Duration::from_secs)
 			.unwrap_or(Duration::from_secs(300)); // 5 minutes default
 
+		// RETENTION_MAX_BYTES/RETENTION_MAX_AGE_SECS seed (and enable) the
+		// retention policy on first boot; an operator-saved policy from
+		// Settings always wins once one has been saved.
+		let retention_max_bytes = std::env::var("RETENTION_MAX_BYTES")
+			.ok()
+			.and_then(|v| v.parse::<u64>().ok());
+		let retention_max_age_secs = std::env::var("RETENTION_MAX_AGE_SECS")
+			.ok()
+			.and_then(|v| v.parse::<u64>().ok());
+		if retention_max_bytes.is_some() || retention_max_age_secs.is_some() {
+			let mut inner = settings.inner().await;
+			if !inner.retention_policy.enabled {
+				inner.retention_policy.enabled = true;
+				inner.retention_policy.max_total_bytes = retention_max_bytes;
+				inner.retention_policy.max_age_secs = retention_max_age_secs;
+				if let Err(err) = inner.save() {
+					log::warn!("failed to persist retention policy from env: {}", err);
+				}
+			}
+		}
+
+		let segment_cache_max_bytes = std::env::var("SEGMENT_CACHE_MAX_BYTES")
+			.ok()
+			.and_then(|v| v.parse::<usize>().ok())
+			.unwrap_or(SEGMENT_CACHE_DEFAULT_BYTES);
+
+		let encryption_key = std::env::var("SEGMENT_ENCRYPTION_KEY")
+			.ok()
+			.and_then(|v| crate::encryption::parse_key_hex(&v));
+		if std::env::var("SEGMENT_ENCRYPTION_KEY").is_ok() && encryption_key.is_none() {
+			log::warn!(
+				"SEGMENT_ENCRYPTION_KEY is set but isn't {} hex bytes; encryption-at-rest stays disabled",
+				crate::encryption::KEY_LEN
+			);
+		}
+
+		// Token-bucket knobs for INGEST_RATE_ENTRIES_PER_SEC/INGEST_BURST_ENTRIES
+		// (save_logs) and SCAN_RATE_BYTES_PER_SEC/SCAN_BURST_BYTES (find_logs'
+		// archive scan). Defaults are generous enough not to bottleneck normal
+		// traffic; an operator under noisy-neighbor pressure tightens them.
+		let ingest_rate = std::env::var("INGEST_RATE_ENTRIES_PER_SEC")
+			.ok()
+			.and_then(|v| v.parse::<f64>().ok())
+			.unwrap_or(200_000.0);
+		let ingest_burst = std::env::var("INGEST_BURST_ENTRIES")
+			.ok()
+			.and_then(|v| v.parse::<f64>().ok())
+			.unwrap_or(200_000.0);
+		let scan_rate_bytes = std::env::var("SCAN_RATE_BYTES_PER_SEC")
+			.ok()
+			.and_then(|v| v.parse::<f64>().ok())
+			.unwrap_or(256.0 * 1024.0 * 1024.0);
+		let scan_burst_bytes = std::env::var("SCAN_BURST_BYTES")
+			.ok()
+			.and_then(|v| v.parse::<f64>().ok())
+			.unwrap_or(256.0 * 1024.0 * 1024.0);
+
+		let metrics_per_device_labels = std::env::var("METRICS_PER_DEVICE_LABELS")
+			.ok()
+			.map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+			.unwrap_or(false);
+
+		let workers = WorkerManager::new();
+		workers.register(crate::wal::WalStatusWorker::new());
+
+		// Auth is entirely opt-in: with none of GOOGLE_OAUTH_CLIENT_ID,
+		// LOCAL_AUTH_ACCOUNTS_FILE, OIDC_*, or SERVICE_ACCOUNTS_JSON set, the
+		// server runs exactly as it did before auth existed.
+		let google_auth = crate::auth::GoogleAuth::from_env().map(Arc::new);
+		let mut auth_backends: Vec<Arc<dyn crate::auth::AuthBackend>> = Vec::new();
+		if let Some(google_auth) = &google_auth {
+			auth_backends.push(google_auth.clone() as Arc<dyn crate::auth::AuthBackend>);
+		}
+		if let Some(password_backend) = crate::auth::PasswordBackend::from_env() {
+			auth_backends.push(Arc::new(password_backend));
+		}
+		if let (Ok(issuer), Ok(jwks_url), Ok(client_id)) = (
+			std::env::var("OIDC_ISSUER"),
+			std::env::var("OIDC_JWKS_URL"),
+			std::env::var("OIDC_CLIENT_ID"),
+		) {
+			auth_backends.push(Arc::new(crate::auth::OidcBackend::new(
+				crate::auth::OidcConfig {
+					issuer,
+					jwks_url,
+					client_id,
+				},
+			)));
+		}
+		let service_account_backend = std::env::var("SERVICE_ACCOUNTS_JSON").ok().map(|_| {
+			Arc::new(crate::auth::ServiceAccountBackend::new(
+				crate::auth::ServiceAccountRegistry::from_env(),
+				std::env::var("SERVICE_ACCOUNT_TOKEN_AUDIENCE")
+					.unwrap_or_else(|_| "puppylog".to_string()),
+			))
+		});
+		if let Some(service_account_backend) = &service_account_backend {
+			auth_backends.push(service_account_backend.clone() as Arc<dyn crate::auth::AuthBackend>);
+		}
+
 		Context {
-			subscriber: Subscriber::new(subtx),
-			publisher: pubtx,
+			subscriber,
 			settings,
 			event_tx,
+			log_tail,
 			db,
 			current: Mutex::new(LogSegment::with_logs(logs)),
 			wal,
-			upload_queue: AtomicUsize::new(0),
+			workers,
+			upload_limiter: AsyncUploadLimiter::new(CONCURRENCY_LIMIT, Some(CONCURRENCY_LIMIT * 10)),
+			metrics: Metrics::new(),
+			chunk_manifest: ChunkManifestCache::new(),
+			segment_cache: Arc::new(SegmentCache::new(segment_cache_max_bytes)),
+			store: build_segment_store(logs_path.as_ref()),
+			level_compaction: LevelCompactionConfig::from_env(),
+			chunking: crate::cdc::ChunkingConfig::from_env(),
+			segment_slots: crate::segment_slot::SegmentSlotAllocator::from_env(),
+			peers: ClusterMetadata::from_env().peer_clients(),
+			access_tracker: Arc::new(crate::access_tracker::AccessTracker::new()),
+			cleanup_running: std::sync::atomic::AtomicBool::new(false),
+			cleanup_status: StdMutex::new(crate::cleanup::CleanupStatus::default()),
+			disk_cleanup: crate::cleanup::DiskCleanupConfig::from_env(),
+			encryption_key,
+			ingest_limiter: crate::rate_limit::TokenBucket::new(ingest_rate, ingest_burst),
+			scan_limiter: Arc::new(crate::rate_limit::TokenBucket::new(
+				scan_rate_bytes,
+				scan_burst_bytes,
+			)),
 			upload_flush_threshold: AtomicUsize::new(UPLOAD_FLUSH_THRESHOLD),
 			logs_path: logs_path.as_ref().to_owned(),
 			wal_max_bytes,
 			flush_interval,
 			last_flush: StdMutex::new(Instant::now()),
+			metrics_per_device_labels,
+			google_auth,
+			auth_backends,
+			service_account_backend,
 		}
 	}
 
@@ -107,19 +450,49 @@ impl Context {
 			.store(threshold, Ordering::Relaxed);
 	}
 
-	pub async fn save_logs(&self, logs: &[LogEntry]) {
+	/// `None` when `GOOGLE_OAUTH_CLIENT_ID` isn't set, which leaves the
+	/// Google login flow and `RequireRole`/`RequirePermission` disabled.
+	pub fn google_auth(&self) -> Option<&crate::auth::GoogleAuth> {
+		self.google_auth.as_deref()
+	}
+
+	/// Bearer-token backends `MaybeAuthUser` tries in turn, most specific
+	/// first as configured by [`Self::new`].
+	pub fn auth_backends(&self) -> &[Arc<dyn crate::auth::AuthBackend>] {
+		&self.auth_backends
+	}
+
+	/// `None` unless `SERVICE_ACCOUNTS_JSON` registers at least one account.
+	pub fn service_account_backend(&self) -> Option<&crate::auth::ServiceAccountBackend> {
+		self.service_account_backend.as_deref()
+	}
+
+	/// Buffers `logs` for the next flush. Rejects the whole batch with a
+	/// retryable "throttled" error, without buffering anything, if doing so
+	/// would exceed the ingest token bucket — the caller (an upload handler
+	/// or the upload-file importer) is expected to back off and retry rather
+	/// than have this block.
+	pub async fn save_logs(&self, logs: &[LogEntry]) -> Result<(), &'static str> {
+		if !self.ingest_limiter.try_acquire(logs.len() as f64) {
+			self.metrics
+				.ingest_throttled
+				.fetch_add(1, Ordering::Relaxed);
+			return Err("ingest rate limit exceeded, try again later");
+		}
 		let mut current = self.current.lock().await;
 		current.buffer.extend_from_slice(logs);
 		for entry in logs {
 			self.wal.write(entry.clone());
+			// Errors only when nobody is subscribed right now; a tailing
+			// search just hasn't registered a receiver yet.
+			let _ = self.log_tail.send(entry.clone());
+			self.subscriber.publish(entry.clone()).await;
 		}
 		current.sort();
 		let flush_threshold = self.upload_flush_threshold.load(Ordering::Relaxed);
 
 		// Policy-based flush triggers: threshold, WAL size cap, or time interval
-		let wal_size = std::fs::metadata(crate::wal::wal_path())
-			.map(|m| m.len())
-			.unwrap_or(0);
+		let wal_size = crate::wal::wal_size_bytes();
 		let last_flush_elapsed = self
 			.last_flush
 			.lock()
@@ -131,6 +504,7 @@ impl Context {
 		if current.buffer.len() > flush_threshold || policy_trigger {
 			self.flush_locked(&mut current).await;
 		}
+		Ok(())
 	}
 
 	// Internal helper used by both save_logs (policy) and force_flush.
@@ -138,7 +512,6 @@ impl Context {
 		if current.buffer.is_empty() {
 			return;
 		}
-		self.wal.clear();
 
 		// Group logs by device ID (or UNKNOWN_DEVICE_ID)
 		let mut by_device: HashMap<String, Vec<LogEntry>> = HashMap::new();
@@ -147,24 +520,31 @@ impl Context {
 				.props
 				.iter()
 				.find(|p| p.key == "deviceId")
-				.map(|p| p.value.clone())
+				.map(|p| p.value.to_string())
 				.unwrap_or_else(|| crate::dev_segment_merger::UNKNOWN_DEVICE_ID.to_string());
 			by_device.entry(device_id).or_default().push(log);
 		}
 
+		// Only safe to truncate the WAL once every device's entries below have
+		// made it into a durably written segment; a partial failure leaves it
+		// in place so a crash/restart replays (and re-dedups) the remainder
+		// instead of silently losing it.
+		let mut all_flushed = true;
+
 		for (device_id, mut logs) in by_device {
 			logs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
 			let first_timestamp = logs.first().unwrap().timestamp;
 			let last_timestamp = logs.last().unwrap().timestamp;
-			let seg = LogSegment { buffer: logs };
+			let seg = LogSegment::from_buffer(logs);
 
 			let mut buff = Vec::new();
 			seg.serialize(&mut buff);
 			let original_size = buff.len();
-			let buff: Vec<u8> = match compress_segment(&buff) {
-				Ok(compressed) => compressed,
+			let (buff, is_compressed): (Vec<u8>, bool) = match compress_segment(&buff) {
+				Ok(result) => result,
 				Err(e) => {
 					log::error!("failed to compress segment: {}", e);
+					all_flushed = false;
 					continue;
 				}
 			};
@@ -186,16 +566,63 @@ impl Context {
 				unique_props.extend(log.props.iter().cloned());
 				unique_props.insert(Prop {
 					key: "level".into(),
-					value: log.level.to_string(),
+					value: log.level.to_string().into(),
 				});
 			}
 			self.db
 				.upsert_segment_props(segment_id, unique_props.iter())
 				.await
 				.unwrap();
-			let path = self.logs_path.join(format!("{}.log", segment_id));
-			let mut file = File::create(&path).unwrap();
-			file.write_all(&buff).unwrap();
+			let mut bloom = SegmentBloom::with_expected_items(unique_props.len());
+			for prop in &unique_props {
+				bloom.insert(&format!("{}={}", prop.key, prop.value));
+			}
+			if let Err(err) = self.db.set_segment_bloom(segment_id, bloom.to_bytes()).await {
+				log::error!("failed to store bloom for segment {}: {}", segment_id, err);
+			}
+			let buff = match self.encryption_key {
+				Some(key) => crate::encryption::encrypt(&key, &buff),
+				None => buff,
+			};
+			if let Err(err) = self
+				.db
+				.set_segment_encrypted(segment_id, self.encryption_key.is_some())
+				.await
+			{
+				log::error!(
+					"failed to store encrypted flag for segment {}: {}",
+					segment_id,
+					err
+				);
+			}
+			let segment_checksum = crate::checksum::checksum(&buff);
+			if let Err(err) = self.db.set_segment_checksum(segment_id, segment_checksum).await {
+				log::error!("failed to store checksum for segment {}: {}", segment_id, err);
+			}
+			if !is_compressed {
+				if let Err(err) = self.db.set_segment_compressed(segment_id, false).await {
+					log::error!("failed to store compressed flag for segment {}: {}", segment_id, err);
+				}
+			}
+			if let Err(err) = self.db.store_segment_chunks(segment_id, &buff, &self.chunking).await {
+				log::error!("failed to store chunks for segment {}: {}", segment_id, err);
+			}
+			let placed = match self.store.put(segment_id, buff).await {
+				Ok(placed) => placed,
+				Err(err) => {
+					log::error!("failed to write segment {}: {}", segment_id, err);
+					all_flushed = false;
+					continue;
+				}
+			};
+			self.record_segment_data_dir(segment_id, placed).await;
+			self.metrics
+				.segments_written
+				.fetch_add(1, Ordering::Relaxed);
+		}
+
+		if all_flushed {
+			self.wal.clear();
 		}
 
 		if let Ok(mut t) = self.last_flush.lock() {
@@ -210,17 +637,49 @@ impl Context {
 		self.flush_locked(&mut current).await;
 	}
 
+	/// Persists the directory `SegmentStore::put` chose for `segment_id`, if
+	/// it reported one (multi-directory layouts only; `None` from a
+	/// single-directory store or S3 is a no-op). Failure is logged rather
+	/// than propagated: the segment is already durably written, so losing
+	/// this just means the next `get`/`delete` falls back to scanning every
+	/// configured directory instead of going straight to it.
+	pub async fn record_segment_data_dir(&self, segment_id: u32, placed: Option<String>) {
+		if let Some(dir) = placed {
+			if let Err(err) = self.db.set_segment_data_dir(segment_id, &dir).await {
+				log::error!("failed to record data dir for segment {}: {}", segment_id, err);
+			}
+		}
+	}
+
 	pub async fn find_logs(
 		&self,
 		query: QueryAst,
 		tx: &mpsc::Sender<LogEntry>,
-	) -> anyhow::Result<()> {
-		let mut end = query.end_date.unwrap_or(Utc::now());
-		let device_ids = extract_device_ids(&query.root);
+	) -> anyhow::Result<Option<LogCursor>> {
+		let mut last_cursor: Option<LogCursor> = None;
+		let mut end = query
+			.start_after
+			.map(|c| c.timestamp)
+			.or(query.end_date)
+			.unwrap_or(Utc::now());
+		// `None` segment_id means the resume point is in the in-memory buffer,
+		// so the archive loop below never needs an index ceiling of its own.
+		let memory_index_ceiling = query
+			.start_after
+			.and_then(|c| if c.segment_id.is_none() { Some(c.intra_segment_index) } else { None });
+		// Simplify before pushdown extraction so a query padded with
+		// always-true/always-false noise (`(1=1) and timestamp > X`) still
+		// yields a usable device-id/prop/timestamp bound.
+		let simplified_root = simplify(&query.root);
+		let device_ids = extract_device_ids(&simplified_root);
+		let equality_props: Vec<String> = extract_equality_props(&simplified_root)
+			.iter()
+			.map(|p| format!("{}={}", p.key, p.value))
+			.collect();
 		let tz = query
 			.tz_offset
 			.unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
-		let (start_bound, end_bound) = timestamp_bounds(&query.root);
+		let (start_bound, end_bound) = timestamp_bounds(&simplified_root);
 		log::info!(
 			"start_bound = {:?}, end_bound = {:?}",
 			start_bound,
@@ -234,10 +693,14 @@ impl Context {
 		{
 			let mut end = end;
 			let current = self.current.lock().await;
-			let iter = current.iter();
-			for entry in iter {
+			for (idx, entry) in current.buffer.iter().enumerate().rev() {
 				if tx.is_closed() {
-					return Ok(());
+					return Ok(last_cursor);
+				}
+				if let Some(ceiling) = memory_index_ceiling {
+					if idx >= ceiling {
+						continue;
+					}
 				}
 				if entry.timestamp > end {
 					continue;
@@ -253,8 +716,13 @@ impl Context {
 					_ => continue,
 				}
 				if tx.send(entry.clone()).await.is_err() {
-					return Ok(());
+					return Ok(last_cursor);
 				}
+				last_cursor = Some(LogCursor {
+					timestamp: entry.timestamp,
+					segment_id: None,
+					intra_segment_index: idx,
+				});
 			}
 		}
 		log::info!("looking from archive");
@@ -339,6 +807,12 @@ impl Context {
 				end,
 				timer.elapsed()
 			);
+			// Phase 1: cheap sequential filtering. Dedup, time/bloom/prop checks
+			// stay sequential since each one can move `end` backwards, which the
+			// next segment's cutoff depends on. What survives is queued up for
+			// concurrent decode instead of being decoded right away.
+			let mut pending: Vec<(SegmentMeta, DateTime<Utc>, std::time::Instant, Option<usize>)> =
+				Vec::new();
 			for segment in &segments {
 				if tx.is_closed() {
 					break 'outer;
@@ -346,17 +820,17 @@ impl Context {
 				if !processed_segments.insert(segment.id) {
 					continue;
 				}
-				let timer = std::time::Instant::now();
-				let props = match self.db.fetch_segment_props(segment.id).await {
-					Ok(props) => props,
-					Err(err) => {
-						log::error!("failed to fetch segment props: {}", err);
-						continue;
+				let index_ceiling = query.start_after.and_then(|c| {
+					if c.segment_id == Some(segment.id) {
+						Some(c.intra_segment_index)
+					} else {
+						None
 					}
-				};
+				});
+				let timer = std::time::Instant::now();
 				// First check whether the segment’s time window could satisfy the query.
 				let time_match = match_date_range(
-					&query.root,
+					&simplified_root,
 					segment.first_timestamp,
 					segment.last_timestamp,
 					&tz,
@@ -366,61 +840,257 @@ impl Context {
 					continue;
 				}
 
-				// Only if the date range fits do we bother checking the segment’s properties.
+				// A segment with a bloom is skipped outright on a definite miss,
+				// saving the props round-trip and decode below. `None` means the
+				// segment predates blooms, so it's always a "maybe".
+				if let Some(bloom_bytes) = &segment.bloom {
+					if let Some(bloom) = SegmentBloom::from_bytes(bloom_bytes) {
+						let definite_miss = equality_props
+							.iter()
+							.any(|key| !bloom.might_contain(key));
+						if definite_miss {
+							end = segment.first_timestamp;
+							continue;
+						}
+					}
+				}
+
+				let props = match self.db.fetch_segment_props(segment.id).await {
+					Ok(props) => props,
+					Err(err) => {
+						log::error!("failed to fetch segment props: {}", err);
+						continue;
+					}
+				};
+				// Only if the date range and bloom fit do we bother checking the segment’s properties.
 				let prop_match = check_props(&query.root, &props).unwrap_or_default();
 				if !prop_match {
 					end = segment.first_timestamp;
 					continue;
 				}
-				let path = self.logs_path.join(format!("{}.log", segment.id));
+				self.access_tracker.touch(segment.id);
+				pending.push((segment.clone(), end, timer, index_ceiling));
+			}
+
+			// Phase 2: decode up to `CONCURRENCY_LIMIT` pending segments at a
+			// time on real tokio tasks, so a slow store read or decode for one
+			// segment doesn't stall the rest. Real tasks (rather than futures
+			// polled in a `FuturesUnordered`) are what makes `tx.is_closed()`
+			// able to actually cancel in-flight decodes via `abort`.
+			let root = Arc::new(query.root.clone());
+			type DecodeOutput = (usize, anyhow::Result<(Vec<(usize, LogEntry)>, bool)>);
+			let mut handles: Vec<tokio::task::JoinHandle<DecodeOutput>> = Vec::new();
+			let mut decoded: Vec<Option<Vec<(usize, LogEntry)>>> =
+				(0..pending.len()).map(|_| None).collect();
+			let mut next = 0;
+			let mut cancelled = false;
+			let spawn_decode = |index: usize,
+			                    segment: &SegmentMeta,
+			                    end_cutoff: DateTime<Utc>,
+			                    index_ceiling: Option<usize>| {
 				log::info!(
 					"loading {} segment {} - {}",
 					segment.id,
 					segment.first_timestamp,
 					segment.last_timestamp
 				);
-				let file: File = match File::open(path) {
-					Ok(file) => file,
-					Err(err) => {
-						log::error!("failed to open log file: {}", err);
-						continue;
+				let store = self.store.clone();
+				let cache = self.segment_cache.clone();
+				let scan_limiter = self.scan_limiter.clone();
+				let segment_id = segment.id;
+				let original_size = segment.original_size;
+				let compressed_size = segment.compressed_size;
+				let expected_checksum = segment.checksum;
+				let encrypted = segment.encrypted;
+				let encryption_key = self.encryption_key;
+				let data_dir = segment.data_dir.clone();
+				let compressed = segment.compressed;
+				let root = root.clone();
+				tokio::spawn(async move {
+					(
+						index,
+						decode_segment_entries(
+							store,
+							cache,
+							scan_limiter,
+							segment_id,
+							original_size,
+							compressed_size,
+							expected_checksum,
+							encrypted,
+							encryption_key,
+							data_dir,
+							compressed,
+							root,
+							tz,
+							end_cutoff,
+							index_ceiling,
+						)
+						.await,
+					)
+				})
+			};
+			while next < pending.len() && handles.len() < CONCURRENCY_LIMIT {
+				let (segment, end_cutoff, _, index_ceiling) = &pending[next];
+				handles.push(spawn_decode(next, segment, *end_cutoff, *index_ceiling));
+				next += 1;
+			}
+			while !handles.is_empty() {
+				let (result, _, remaining) = select_all(handles).await;
+				handles = remaining;
+				if tx.is_closed() {
+					cancelled = true;
+					for handle in &handles {
+						handle.abort();
+					}
+					handles.clear();
+					continue;
+				}
+				match result {
+					Ok((index, Ok((entries, hit)))) => {
+						if hit {
+							self.metrics.segment_cache_hits.fetch_add(1, Ordering::Relaxed);
+						} else {
+							self.metrics.segment_cache_misses.fetch_add(1, Ordering::Relaxed);
+						}
+						decoded[index] = Some(entries);
 					}
+					Ok((index, Err(err))) => {
+						log::error!(
+							"failed to decode segment {}: {}",
+							pending[index].0.id,
+							err
+						);
+					}
+					Err(err) => log::error!("segment decode task panicked: {}", err),
+				}
+				if !cancelled && next < pending.len() {
+					let (segment, end_cutoff, _, index_ceiling) = &pending[next];
+					handles.push(spawn_decode(next, segment, *end_cutoff, *index_ceiling));
+					next += 1;
+				}
+			}
+			if cancelled {
+				break 'outer;
+			}
+
+			// Phase 3: flush decoded entries back to `tx` in the original
+			// segment order, preserving delivery order even though decoding
+			// itself ran out of order.
+			for (index, (segment, _, timer, _)) in pending.into_iter().enumerate() {
+				let Some(entries) = decoded[index].take() else {
+					continue;
 				};
-				let mut decoder = zstd::Decoder::new(file).unwrap();
-				let segment = LogSegment::parse(&mut decoder);
-				let iter = segment.iter();
-				for entry in iter {
+				for (idx, entry) in entries {
 					if tx.is_closed() {
 						break 'outer;
 					}
-					if entry.timestamp > end {
-						continue;
-					}
-					match check_expr(&query.root, entry, &tz) {
-						Ok(true) => {}
-						_ => continue,
-					}
-					if tx.send(entry.clone()).await.is_err() {
-						log::info!("stopped searching logs at {:?}", entry);
+					let timestamp = entry.timestamp;
+					if tx.send(entry).await.is_err() {
+						log::info!("stopped searching logs at {:?}", timestamp);
 						break 'outer;
 					}
+					last_cursor = Some(LogCursor {
+						timestamp,
+						segment_id: Some(segment.id),
+						intra_segment_index: idx,
+					});
 				}
+				self.metrics.segment_scan.observe(timer.elapsed());
 			}
 		}
-		Ok(())
+		Ok(last_cursor)
+	}
+
+	/// Like [`Context::find_logs`], but streams [`crate::search::SegmentProgress`]
+	/// and [`crate::search::SearchProgress`] events alongside matching entries
+	/// so a caller driving an SSE response can show search progress instead of
+	/// going quiet while a wide query walks archived segments.
+	pub async fn find_logs_with_progress(
+		&self,
+		query: QueryAst,
+		tx: &mpsc::Sender<LogStreamItem>,
+	) -> anyhow::Result<()> {
+		let mut searcher = LogSearcher::new(
+			&self.db,
+			&self.current,
+			self.store.as_ref(),
+			self.encryption_key,
+			Some(&self.access_tracker),
+		);
+		searcher.live_tail = Some(&self.log_tail);
+		searcher.peers = &self.peers;
+		searcher.search(query, tx).await
+	}
+
+	/// Like [`Context::find_logs_with_progress`], but never fans out to
+	/// `peers`. This is what a node's own `/api/v1/cluster/search` endpoint
+	/// calls to answer another node's fan-out request, so a query doesn't
+	/// turn into an ever-growing broadcast across the cluster.
+	pub async fn find_logs_local_with_progress(
+		&self,
+		query: QueryAst,
+		tx: &mpsc::Sender<LogStreamItem>,
+	) -> anyhow::Result<()> {
+		let mut searcher = LogSearcher::new(
+			&self.db,
+			&self.current,
+			self.store.as_ref(),
+			self.encryption_key,
+			Some(&self.access_tracker),
+		);
+		searcher.live_tail = Some(&self.log_tail);
+		searcher.search(query, tx).await
+	}
+
+	/// Runs `query` to completion and writes the matches out as new,
+	/// downloadable segments via [`crate::search::LogSearcher::search_to_segment`],
+	/// rather than streaming them to a live client. `max_bytes` bounds each
+	/// resulting segment's size; see `search_to_segment` for how it rotates
+	/// into multiple files.
+	pub async fn export_logs_to_segment(
+		&self,
+		query: QueryAst,
+		max_bytes: Option<usize>,
+	) -> anyhow::Result<Vec<ExportedSegment>> {
+		let searcher = LogSearcher::new(
+			&self.db,
+			&self.current,
+			self.store.as_ref(),
+			self.encryption_key,
+			Some(&self.access_tracker),
+		);
+		searcher.search_to_segment(query, max_bytes).await
 	}
 
 	pub fn allowed_to_upload(&self) -> bool {
-		self.upload_queue.load(Ordering::SeqCst) < CONCURRENCY_LIMIT
+		self.upload_limiter.in_flight() < CONCURRENCY_LIMIT
 	}
 
-	pub fn upload_guard(&self) -> Result<UploadGuard<'_>, &str> {
-		UploadGuard::new(&self.upload_queue, CONCURRENCY_LIMIT)
+	/// Async admission into the upload concurrency cap: waits up to `wait`
+	/// on a fair queue instead of rejecting outright once `CONCURRENCY_LIMIT`
+	/// in-flight uploads are running.
+	pub async fn acquire_upload_slot(&self, wait: Duration) -> Result<AsyncUploadGuard, &'static str> {
+		self.upload_limiter.acquire(wait).await
 	}
 
 	pub fn logs_path(&self) -> &Path {
 		&self.logs_path
 	}
+
+	/// The AES-256-GCM master key configured via `SEGMENT_ENCRYPTION_KEY`, if
+	/// any. `None` means encryption-at-rest is disabled on this node.
+	pub fn encryption_key(&self) -> Option<[u8; crate::encryption::KEY_LEN]> {
+		self.encryption_key
+	}
+
+	/// Runs one retention-enforcement pass immediately, evicting whatever
+	/// `Settings`'s current `retention_policy` selects. Returns the number
+	/// of segments evicted. Lets tests trigger eviction deterministically
+	/// instead of waiting on `run_retention_enforcer`'s interval.
+	pub async fn enforce_retention(&self) -> anyhow::Result<usize> {
+		crate::retention::enforce_retention(self).await
+	}
 }
 
 #[cfg(test)]
@@ -453,13 +1123,13 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "service".to_string(),
-				value: "search".to_string(),
+				value: "search".to_string().into(),
 			}],
 			msg: "match me".to_string(),
 			..Default::default()
 		};
 
-		ctx.save_logs(&[entry.clone()]).await;
+		ctx.save_logs(&[entry.clone()]).await.unwrap();
 
 		let query = parse_log_query("msg = \"match me\"").unwrap();
 		let (tx, mut rx) = mpsc::channel(10);
@@ -484,7 +1154,7 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "service".to_string(),
-				value: "segment".to_string(),
+				value: "segment".to_string().into(),
 			}],
 			msg: "segment log".to_string(),
 			..Default::default()
@@ -496,7 +1166,7 @@ mod tests {
 		let mut buff = Vec::new();
 		segment.serialize(&mut buff);
 		let original_size = buff.len();
-		let compressed = compress_segment(&buff).unwrap();
+		let (compressed, is_compressed) = compress_segment(&buff).unwrap();
 		let compressed_size = compressed.len();
 
 		let segment_id = ctx
@@ -511,11 +1181,14 @@ mod tests {
 			})
 			.await
 			.unwrap();
+		if !is_compressed {
+			ctx.db.set_segment_compressed(segment_id, false).await.unwrap();
+		}
 
 		let mut props_vec: Vec<Prop> = entry.props.clone();
 		props_vec.push(Prop {
 			key: "level".into(),
-			value: entry.level.to_string(),
+			value: entry.level.to_string().into(),
 		});
 		ctx.db
 			.upsert_segment_props(segment_id, props_vec.iter())
@@ -570,7 +1243,7 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "service".to_string(),
-				value: "old".to_string(),
+				value: "old".to_string().into(),
 			}],
 			msg: "duplicate".to_string(),
 			..Default::default()
@@ -581,7 +1254,7 @@ mod tests {
 		let mut buff = Vec::new();
 		old_seg.serialize(&mut buff);
 		let orig_size = buff.len();
-		let compressed = compress_segment(&buff).unwrap();
+		let (compressed, is_compressed) = compress_segment(&buff).unwrap();
 		let comp_size = compressed.len();
 		let old_seg_id = ctx
 			.db
@@ -595,10 +1268,13 @@ mod tests {
 			})
 			.await
 			.unwrap();
+		if !is_compressed {
+			ctx.db.set_segment_compressed(old_seg_id, false).await.unwrap();
+		}
 		let mut props_old_vec: Vec<Prop> = entry_old.props.clone();
 		props_old_vec.push(Prop {
 			key: "level".into(),
-			value: entry_old.level.to_string(),
+			value: entry_old.level.to_string().into(),
 		});
 		ctx.db
 			.upsert_segment_props(old_seg_id, props_old_vec.iter())
@@ -613,7 +1289,7 @@ mod tests {
 			level: LogLevel::Info,
 			props: vec![Prop {
 				key: "service".to_string(),
-				value: "new".to_string(),
+				value: "new".to_string().into(),
 			}],
 			msg: "new".to_string(),
 			..Default::default()
@@ -624,7 +1300,7 @@ mod tests {
 		let mut buff = Vec::new();
 		new_seg.serialize(&mut buff);
 		let orig_size = buff.len();
-		let compressed = compress_segment(&buff).unwrap();
+		let (compressed, is_compressed) = compress_segment(&buff).unwrap();
 		let comp_size = compressed.len();
 		let new_seg_id = ctx
 			.db
@@ -638,10 +1314,13 @@ mod tests {
 			})
 			.await
 			.unwrap();
+		if !is_compressed {
+			ctx.db.set_segment_compressed(new_seg_id, false).await.unwrap();
+		}
 		let mut props_new_vec: Vec<Prop> = entry_new.props.clone();
 		props_new_vec.push(Prop {
 			key: "level".into(),
-			value: entry_new.level.to_string(),
+			value: entry_new.level.to_string().into(),
 		});
 		ctx.db
 			.upsert_segment_props(new_seg_id, props_new_vec.iter())
@@ -686,7 +1365,7 @@ mod tests {
 		let mut buff = Vec::new();
 		seg1.serialize(&mut buff);
 		let orig_size = buff.len();
-		let compressed = compress_segment(&buff).unwrap();
+		let (compressed, is_compressed) = compress_segment(&buff).unwrap();
 		let comp_size = compressed.len();
 		let seg_id1 = ctx
 			.db
@@ -700,10 +1379,13 @@ mod tests {
 			})
 			.await
 			.unwrap();
+		if !is_compressed {
+			ctx.db.set_segment_compressed(seg_id1, false).await.unwrap();
+		}
 		let mut props_vec: Vec<Prop> = entry1.props.clone();
 		props_vec.push(Prop {
 			key: "level".into(),
-			value: entry1.level.to_string(),
+			value: entry1.level.to_string().into(),
 		});
 		ctx.db
 			.upsert_segment_props(seg_id1, props_vec.iter())
@@ -727,7 +1409,7 @@ mod tests {
 		let mut buff = Vec::new();
 		seg2.serialize(&mut buff);
 		let orig_size = buff.len();
-		let compressed = compress_segment(&buff).unwrap();
+		let (compressed, is_compressed) = compress_segment(&buff).unwrap();
 		let comp_size = compressed.len();
 		let seg_id2 = ctx
 			.db
@@ -741,10 +1423,13 @@ mod tests {
 			})
 			.await
 			.unwrap();
+		if !is_compressed {
+			ctx.db.set_segment_compressed(seg_id2, false).await.unwrap();
+		}
 		let mut props_vec: Vec<Prop> = entry2.props.clone();
 		props_vec.push(Prop {
 			key: "level".into(),
-			value: entry2.level.to_string(),
+			value: entry2.level.to_string().into(),
 		});
 		ctx.db
 			.upsert_segment_props(seg_id2, props_vec.iter())
@@ -788,7 +1473,7 @@ mod tests {
 		let mut buff = Vec::new();
 		seg_old.serialize(&mut buff);
 		let orig_size = buff.len();
-		let compressed = compress_segment(&buff).unwrap();
+		let (compressed, is_compressed) = compress_segment(&buff).unwrap();
 		let comp_size = compressed.len();
 		let seg_old_id = ctx
 			.db
@@ -802,10 +1487,13 @@ mod tests {
 			})
 			.await
 			.unwrap();
+		if !is_compressed {
+			ctx.db.set_segment_compressed(seg_old_id, false).await.unwrap();
+		}
 		let mut props_vec: Vec<Prop> = entry_old.props.clone();
 		props_vec.push(Prop {
 			key: "level".into(),
-			value: entry_old.level.to_string(),
+			value: entry_old.level.to_string().into(),
 		});
 		ctx.db
 			.upsert_segment_props(seg_old_id, props_vec.iter())
@@ -833,7 +1521,7 @@ mod tests {
 		let mut buff = Vec::new();
 		seg_other.serialize(&mut buff);
 		let orig_size = buff.len();
-		let compressed = compress_segment(&buff).unwrap();
+		let (compressed, is_compressed) = compress_segment(&buff).unwrap();
 		let comp_size = compressed.len();
 		let seg_other_id = ctx
 			.db
@@ -847,10 +1535,13 @@ mod tests {
 			})
 			.await
 			.unwrap();
+		if !is_compressed {
+			ctx.db.set_segment_compressed(seg_other_id, false).await.unwrap();
+		}
 		let mut props_vec2: Vec<Prop> = entry_other.props.clone();
 		props_vec2.push(Prop {
 			key: "level".into(),
-			value: entry_other.level.to_string(),
+			value: entry_other.level.to_string().into(),
 		});
 		ctx.db
 			.upsert_segment_props(seg_other_id, props_vec2.iter())
@@ -910,7 +1601,7 @@ mod tests {
 			});
 		}
 
-		ctx.save_logs(&logs).await;
+		ctx.save_logs(&logs).await.unwrap();
 
 		let segs = ctx
 			.db
@@ -945,7 +1636,7 @@ mod tests {
 			});
 		}
 
-		ctx.save_logs(&logs).await;
+		ctx.save_logs(&logs).await.unwrap();
 
 		let segs = ctx
 			.db
@@ -962,8 +1653,6 @@ mod tests {
 
 	#[tokio::test]
 	async fn find_logs_pagination_resume_segment() {
-		use std::sync::Arc;
-
 		let (ctx, dir) = prepare_test_ctx().await;
 		let ctx = Arc::new(ctx);
 		let now = Utc::now();
@@ -1013,7 +1702,7 @@ mod tests {
 		let mut buff = Vec::new();
 		seg.serialize(&mut buff);
 		let orig_size = buff.len();
-		let compressed = compress_segment(&buff).unwrap();
+		let (compressed, is_compressed) = compress_segment(&buff).unwrap();
 		let comp_size = compressed.len();
 		let seg_id = ctx
 			.db
@@ -1027,6 +1716,9 @@ mod tests {
 			})
 			.await
 			.unwrap();
+		if !is_compressed {
+			ctx.db.set_segment_compressed(seg_id, false).await.unwrap();
+		}
 		ctx.db
 			.upsert_segment_props(
 				seg_id,
@@ -1037,7 +1729,7 @@ mod tests {
 					},
 					Prop {
 						key: "level".into(),
-						value: LogLevel::Info.to_string(),
+						value: LogLevel::Info.to_string().into(),
 					},
 				]
 				.iter(),
@@ -1050,17 +1742,15 @@ mod tests {
 		query.end_date = Some(ts0);
 		let (tx, mut rx) = mpsc::channel(10);
 		let ctx_clone = Arc::clone(&ctx);
-		let handle = tokio::spawn(async move {
-			ctx_clone.find_logs(query, &tx).await.unwrap();
-		});
+		let handle = tokio::spawn(async move { ctx_clone.find_logs(query, &tx).await.unwrap() });
 		let first = rx.recv().await.unwrap();
 		drop(rx);
-		handle.await.unwrap();
+		let cursor = handle.await.unwrap();
 
 		assert_eq!(first.timestamp.timestamp_millis(), ts0.timestamp_millis());
 
 		let mut query2 = parse_log_query("deviceId = dev1").unwrap();
-		query2.end_date = Some(first.timestamp - Duration::microseconds(1));
+		query2.start_after = cursor;
 		let (tx2, mut rx2) = mpsc::channel(10);
 		ctx.find_logs(query2, &tx2).await.unwrap();
 		drop(tx2);
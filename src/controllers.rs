@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use axum::body::{Body, BodyDataStream};
@@ -6,6 +8,9 @@ use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
 use axum::response::sse::Event;
 use axum::response::{Html, IntoResponse, Response, Sse};
 use axum::Json;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use futures::executor::block_on;
 use futures::{future, Stream, StreamExt};
@@ -13,18 +18,20 @@ use puppylog::*;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, to_string, Value};
-use tokio::fs::{self, read_dir, File, OpenOptions};
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::fs::{metadata, read_dir, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
 use tokio::sync::mpsc;
 use tokio::task::spawn_blocking;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::io::ReaderStream;
 
+use crate::auth::{Group, RequireRole, RoleRequirement};
 use crate::config::{log_path, upload_path};
-use crate::context::{Context, LogStreamItem, SearchProgress, SegmentProgress};
+use crate::context::Context;
+use crate::search::{ExportedSegment, LogStreamItem, SearchProgress, SegmentProgress};
 use crate::db::{
-	BucketProp, LogBucket, MetaProp, NewBucketLogEntry, UpdateDeviceSettings, UpsertBucketArgs,
-	BUCKET_LOG_LIMIT,
+	BucketPoll, BucketProp, LogBucket, MetaProp, NewBucketLogEntry, UpdateDeviceSettings,
+	UpsertBucketArgs, BUCKET_LOG_LIMIT,
 };
 use crate::types::GetSegmentsQuery;
 
@@ -35,6 +42,7 @@ pub(crate) struct GetLogsQuery {
 	pub query: Option<String>,
 	pub end_date: Option<DateTime<Utc>>,
 	pub tz_offset: Option<i32>,
+	pub cursor: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -44,6 +52,8 @@ pub(crate) struct GetHistogramQuery {
 	pub bucket_secs: Option<u64>,
 	pub end_date: Option<DateTime<Utc>>,
 	pub tz_offset: Option<i32>,
+	pub group_by: Option<String>,
+	pub max_series: Option<usize>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -175,6 +185,26 @@ pub async fn get_server_info() -> Json<Value> {
 	)
 }
 
+/// Prometheus text-exposition scrape target for ingestion/query counters,
+/// plus gauges derived from the devices/segments/buckets tables
+/// (`DB::metrics_snapshot`). Per-device/per-bucket labels only render when
+/// `Context::metrics_per_device_labels` is set, to keep scrape cardinality
+/// bounded by default.
+pub async fn get_metrics(State(ctx): State<Arc<Context>>) -> impl IntoResponse {
+	let mut body = ctx.metrics.render();
+	match ctx.db.metrics_snapshot().await {
+		Ok(snapshot) => body.push_str(&snapshot.render(ctx.metrics_per_device_labels)),
+		Err(err) => log::error!("failed to collect db metrics snapshot: {}", err),
+	}
+	body.push_str(&ctx.subscriber.render_metrics());
+	crate::metrics::render_gauge(
+		"puppylog_segment_slot_freelist_depth",
+		ctx.segment_slots.depth() as i64,
+		&mut body,
+	);
+	([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
 pub async fn get_segment_metadata(State(ctx): State<Arc<Context>>) -> Json<Value> {
 	let meta = ctx.db.fetch_segments_metadata().await.unwrap();
 	let avg_logs_per_segment = meta.logs_count as f64 / meta.segment_count as f64;
@@ -185,7 +215,8 @@ pub async fn get_segment_metadata(State(ctx): State<Arc<Context>>) -> Json<Value
 			"compressedSize": meta.compressed_size,
 			"logsCount": meta.logs_count,
 			"averageLogsPerSegment": avg_logs_per_segment,
-			"averageSegmentSize": avg_segment_size
+			"averageSegmentSize": avg_segment_size,
+			"deduplicatedSize": meta.deduplicated_size
 	}))
 }
 
@@ -234,13 +265,44 @@ pub async fn append_bucket_logs(
 		.filter_map(normalize_bucket_log)
 		.take(BUCKET_LOG_LIMIT)
 		.collect();
+	let log_count = logs.len() as u64;
 	match ctx.db.append_bucket_logs(bucket_id, &logs).await {
-		Ok(Some(bucket)) => Ok(Json(bucket)),
+		Ok(Some(bucket)) => {
+			ctx.metrics.logs_ingested.fetch_add(log_count, Ordering::Relaxed);
+			Ok(Json(bucket))
+		}
 		Ok(None) => Err(StatusCode::NOT_FOUND),
 		Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
 	}
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PollBucketQuery {
+	pub since: Option<i32>,
+	pub timeout_ms: Option<u64>,
+}
+
+const MAX_POLL_BUCKET_TIMEOUT_MS: u64 = 60_000;
+const DEFAULT_POLL_BUCKET_TIMEOUT_MS: u64 = 25_000;
+
+pub async fn poll_bucket(
+	State(ctx): State<Arc<Context>>,
+	Path(bucket_id): Path<i32>,
+	Query(params): Query<PollBucketQuery>,
+) -> Result<Json<BucketPoll>, StatusCode> {
+	let since = params.since.unwrap_or(0);
+	let timeout_ms = params
+		.timeout_ms
+		.unwrap_or(DEFAULT_POLL_BUCKET_TIMEOUT_MS)
+		.min(MAX_POLL_BUCKET_TIMEOUT_MS);
+	ctx.db
+		.poll_bucket(bucket_id, since, std::time::Duration::from_millis(timeout_ms))
+		.await
+		.map(Json)
+		.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
 pub async fn clear_bucket_logs(
 	State(ctx): State<Arc<Context>>,
 	Path(bucket_id): Path<i32>,
@@ -252,8 +314,21 @@ pub async fn clear_bucket_logs(
 	}
 }
 
+/// Marker for [`RequireRole`]: gates the mutation endpoints below (bucket and
+/// segment deletion, bulk device edits, retention/CORS policy changes) behind
+/// the `Admin` group so a read-only `Viewer` can't reshape retention or
+/// deletion policy even if they can query logs.
+pub struct AdminOnly;
+
+impl RoleRequirement for AdminOnly {
+	fn required_group() -> Group {
+		Group::Admin
+	}
+}
+
 pub async fn delete_bucket(
 	State(ctx): State<Arc<Context>>,
+	_admin: RequireRole<AdminOnly>,
 	Path(bucket_id): Path<i32>,
 ) -> StatusCode {
 	match ctx.db.delete_bucket(bucket_id).await {
@@ -276,9 +351,42 @@ pub async fn get_segment(
 	Json(serde_json::to_value(&segment).unwrap())
 }
 
-pub async fn download_segment(Path(segment_id): Path<u32>) -> Response {
+/// Parses a `Range: bytes=start-end` header (open-ended and suffix ranges
+/// included) against a known total length. Returns the inclusive
+/// `(start, end)` byte offsets, or `None` if the range is unsatisfiable.
+fn parse_byte_range(value: &str, total: u64) -> Option<(u64, u64)> {
+	let spec = value.strip_prefix("bytes=")?;
+	// Only a single range is supported; multi-range responses aren't worth
+	// the multipart/byteranges complexity for a segment download.
+	let spec = spec.split(',').next()?.trim();
+	let (start, end) = spec.split_once('-')?;
+	if start.is_empty() {
+		// Suffix range: last N bytes.
+		let suffix_len: u64 = end.trim().parse().ok()?;
+		if suffix_len == 0 || total == 0 {
+			return None;
+		}
+		let start = total.saturating_sub(suffix_len);
+		return Some((start, total - 1));
+	}
+	let start: u64 = start.trim().parse().ok()?;
+	if start >= total {
+		return None;
+	}
+	let end = if end.trim().is_empty() {
+		total - 1
+	} else {
+		end.trim().parse::<u64>().ok()?.min(total - 1)
+	};
+	if end < start {
+		return None;
+	}
+	Some((start, end))
+}
+
+pub async fn download_segment(Path(segment_id): Path<u32>, headers: HeaderMap) -> Response {
 	let path = log_path().join(format!("{segment_id}.log"));
-	let file = match File::open(&path).await {
+	let mut file = match File::open(&path).await {
 		Ok(f) => f,
 		Err(e) => {
 			return (
@@ -289,43 +397,103 @@ pub async fn download_segment(Path(segment_id): Path<u32>) -> Response {
 		}
 	};
 
-	let len = file.metadata().await.ok().map(|m| m.len());
-	let stream = ReaderStream::new(BufReader::new(file));
-	let body = Body::from_stream(stream);
+	let meta = match file.metadata().await {
+		Ok(m) => m,
+		Err(e) => {
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				format!("cannot stat segment {segment_id}: {e}"),
+			)
+				.into_response();
+		}
+	};
+	let total = meta.len();
+	let modified_nanos = meta
+		.modified()
+		.ok()
+		.and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+		.map(|d| d.as_nanos())
+		.unwrap_or(0);
+	let etag = etag_for(format!("{segment_id}:{total}:{modified_nanos}").as_bytes());
+
+	if let Some(candidate) = headers.get(header::IF_NONE_MATCH) {
+		if if_none_match_matches(&etag, candidate) {
+			let mut resp_headers = HeaderMap::new();
+			resp_headers.insert(header::ETAG, etag.parse().unwrap());
+			return (StatusCode::NOT_MODIFIED, resp_headers).into_response();
+		}
+	}
 
-	let mut headers = HeaderMap::new();
-	headers.insert(header::CONTENT_TYPE, "application/zstd".parse().unwrap());
-	headers.insert(
+	let range = headers
+		.get(header::RANGE)
+		.and_then(|v| v.to_str().ok())
+		.filter(|_| {
+			// If-Range: serve the full body once the validator is stale.
+			match headers.get(header::IF_RANGE) {
+				Some(validator) => validator.to_str().ok() == Some(etag.as_str()),
+				None => true,
+			}
+		})
+		.map(|v| v.to_string());
+
+	let mut resp_headers = HeaderMap::new();
+	resp_headers.insert(header::CONTENT_TYPE, "application/zstd".parse().unwrap());
+	resp_headers.insert(header::ACCEPT_RANGES, "bytes".parse().unwrap());
+	resp_headers.insert(header::ETAG, etag.parse().unwrap());
+	resp_headers.insert(
 		header::CONTENT_DISPOSITION,
-		format!(
-			"attachment; filename=\"{}\"",
-			format!("segment-{segment_id}.zst")
-		)
-		.parse()
-		.unwrap(),
+		format!("attachment; filename=\"segment-{segment_id}.zst\"")
+			.parse()
+			.unwrap(),
 	);
-	if let Some(len) = len {
-		headers.insert(header::CONTENT_LENGTH, len.into());
+
+	if let Some(range) = range {
+		let (start, end) = match parse_byte_range(&range, total) {
+			Some(v) => v,
+			None => {
+				resp_headers.insert(
+					header::CONTENT_RANGE,
+					format!("bytes */{total}").parse().unwrap(),
+				);
+				return (StatusCode::RANGE_NOT_SATISFIABLE, resp_headers).into_response();
+			}
+		};
+		let len = end - start + 1;
+		if let Err(e) = file.seek(std::io::SeekFrom::Start(start)).await {
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				format!("cannot seek segment {segment_id}: {e}"),
+			)
+				.into_response();
+		}
+		resp_headers.insert(
+			header::CONTENT_RANGE,
+			format!("bytes {start}-{end}/{total}").parse().unwrap(),
+		);
+		resp_headers.insert(header::CONTENT_LENGTH, len.into());
+		let stream = ReaderStream::new(BufReader::new(file).take(len));
+		let body = Body::from_stream(stream);
+		return (StatusCode::PARTIAL_CONTENT, resp_headers, body).into_response();
 	}
 
-	(headers, body).into_response()
+	resp_headers.insert(header::CONTENT_LENGTH, total.into());
+	let stream = ReaderStream::new(BufReader::new(file));
+	let body = Body::from_stream(stream);
+	(StatusCode::OK, resp_headers, body).into_response()
 }
 
 pub async fn delete_segment(
 	State(ctx): State<Arc<Context>>,
+	_admin: RequireRole<AdminOnly>,
 	Path(segment_id): Path<u32>,
 ) -> &'static str {
 	log::info!("delete_segment: {:?}", segment_id);
+	let data_dir = ctx.db.fetch_segment(segment_id).await.ok().and_then(|s| s.data_dir);
 	ctx.db.delete_segment(segment_id).await.unwrap();
-	let path = log_path().join(format!("{segment_id}.log"));
-	if !path.exists() {
-		log::warn!(
-			"segment file {} does not exist, skipping deletion",
-			path.display()
-		);
-		return "ok";
+	if let Err(e) = ctx.store.delete(segment_id, data_dir.as_deref()).await {
+		log::warn!("failed to delete segment {} from store: {}", segment_id, e);
 	}
-	fs::remove_file(path).await.unwrap();
+	ctx.segment_cache.invalidate(segment_id);
 	"ok"
 }
 
@@ -337,6 +505,24 @@ pub async fn get_segment_props(
 	Json(serde_json::to_value(&props).unwrap())
 }
 
+pub async fn pin_segment(
+	State(ctx): State<Arc<Context>>,
+	Path(segment_id): Path<u32>,
+) -> &'static str {
+	log::info!("pin_segment: {:?}", segment_id);
+	ctx.db.set_segment_pinned(segment_id, true).await.unwrap();
+	"ok"
+}
+
+pub async fn unpin_segment(
+	State(ctx): State<Arc<Context>>,
+	Path(segment_id): Path<u32>,
+) -> &'static str {
+	log::info!("unpin_segment: {:?}", segment_id);
+	ctx.db.set_segment_pinned(segment_id, false).await.unwrap();
+	"ok"
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct BulkEdit {
@@ -346,7 +532,11 @@ pub(crate) struct BulkEdit {
 	pub device_ids: Vec<String>,
 }
 
-pub async fn bulk_edit(State(ctx): State<Arc<Context>>, body: Json<BulkEdit>) -> &'static str {
+pub async fn bulk_edit(
+	State(ctx): State<Arc<Context>>,
+	_admin: RequireRole<AdminOnly>,
+	body: Json<BulkEdit>,
+) -> &'static str {
 	log::info!("bulk_edit: {:?}", body);
 	for device_id in body.device_ids.iter() {
 		ctx.db
@@ -389,11 +579,39 @@ pub async fn validate_query(Query(params): Query<GetLogsQuery>) -> Result<(), Ba
 pub async fn update_device_settings(
 	State(ctx): State<Arc<Context>>,
 	Path(device_id): Path<String>,
+	Query(token_q): Query<DeviceTokenQuery>,
+	headers: HeaderMap,
 	body: Json<UpdateDeviceSettings>,
-) -> &'static str {
+) -> Result<&'static str, StatusCode> {
+	require_device_token(&ctx, &device_id, &headers, token_q.token.as_deref()).await?;
 	log::info!("update_device_settings device_id: {:?}", device_id);
 	ctx.db.update_device_settings(&device_id, &body).await;
-	"ok"
+	Ok("ok")
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MintDeviceTokenRequest {
+	pub ttl_secs: u64,
+}
+
+/// Admin endpoint: mints an HMAC-signed upload token scoped to `device_id`
+/// with the given TTL. Minting always succeeds regardless of
+/// `device_auth.enabled`; the policy's `enabled` flag only controls
+/// whether the device-facing endpoints require the token.
+pub async fn mint_device_token(
+	State(ctx): State<Arc<Context>>,
+	_admin: RequireRole<AdminOnly>,
+	Path(device_id): Path<String>,
+	body: Json<MintDeviceTokenRequest>,
+) -> Json<Value> {
+	let policy = ctx.settings.inner().await.device_auth.clone();
+	let expires_at = Utc::now() + chrono::Duration::seconds(body.ttl_secs as i64);
+	let token = crate::device_token::mint(policy.secret.as_bytes(), &device_id, expires_at);
+	Json(json!({
+		"token": token,
+		"expiresAt": expires_at,
+	}))
 }
 
 pub async fn get_devices(State(ctx): State<Arc<Context>>) -> Json<Value> {
@@ -422,20 +640,121 @@ pub async fn get_segments(
 	if params.count.is_none() {
 		params.count = Some(100);
 	}
-	let segments = ctx.db.find_segments(&params).await.unwrap();
-	Json(serde_json::to_value(&segments).unwrap())
+	let page = ctx.db.find_segments_page(&params).await.unwrap();
+	Json(serde_json::to_value(&page).unwrap())
+}
+
+/// Parses a `Content-Range: bytes START-END/TOTAL` header into its parts.
+fn parse_content_range(value: &str) -> Option<(u64, u64, u64)> {
+	let rest = value.strip_prefix("bytes ")?;
+	let (range, total) = rest.split_once('/')?;
+	let (start, end) = range.split_once('-')?;
+	let start: u64 = start.trim().parse().ok()?;
+	let end: u64 = end.trim().parse().ok()?;
+	let total: u64 = total.trim().parse().ok()?;
+	Some((start, end, total))
+}
+
+/// Resolves the `.part` path for an `upload-id` header, rejecting anything
+/// that doesn't belong to `device_id` so one device can't poke another's
+/// in-flight upload.
+fn resumable_part_path(device_id: &str, upload_id: &str) -> Option<std::path::PathBuf> {
+	if upload_id.contains(['/', '\\']) || upload_id.contains("..") {
+		return None;
+	}
+	if !upload_id.starts_with(&format!("{device_id}-")) {
+		return None;
+	}
+	Some(upload_path().join(format!("{upload_id}.part")))
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+	headers.get(name)?.to_str().ok()
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct DeviceTokenQuery {
+	pub token: Option<String>,
+}
+
+/// Verifies the HMAC-signed upload token presented for `device_id`, either
+/// as an `x-device-token` header or a `token` query param. A no-op while
+/// `device_auth.enabled` is false, so existing token-less devices keep
+/// working until an operator opts in.
+async fn require_device_token(
+	ctx: &Context,
+	device_id: &str,
+	headers: &HeaderMap,
+	query_token: Option<&str>,
+) -> Result<(), StatusCode> {
+	let policy = ctx.settings.inner().await.device_auth.clone();
+	if !policy.enabled {
+		return Ok(());
+	}
+	let token = header_str(headers, "x-device-token").or(query_token);
+	match token {
+		Some(token) => crate::device_token::verify(policy.secret.as_bytes(), device_id, token)
+			.map_err(|_| StatusCode::UNAUTHORIZED),
+		None => Err(StatusCode::UNAUTHORIZED),
+	}
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ChunkManifestRequest {
+	pub digests: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct ChunkManifestResponse {
+	pub missing: Vec<String>,
+}
+
+/// Content-addressed dedup handshake: the client POSTs the digests of the
+/// chunks it's about to upload, and gets back the subset it actually needs
+/// to send. Lets a retransmit after a flaky connection skip chunk bodies the
+/// server already ingested, instead of re-sending the whole batch.
+pub async fn device_chunk_manifest(
+	State(ctx): State<Arc<Context>>,
+	Path(device_id): Path<String>,
+	Query(token_q): Query<DeviceTokenQuery>,
+	headers: HeaderMap,
+	Json(request): Json<ChunkManifestRequest>,
+) -> impl IntoResponse {
+	if let Err(status) =
+		require_device_token(&ctx, &device_id, &headers, token_q.token.as_deref()).await
+	{
+		return status.into_response();
+	}
+	let missing: Vec<String> = request
+		.digests
+		.into_iter()
+		.filter(|digest| !ctx.chunk_manifest.contains(&device_id, digest))
+		.collect();
+	Json(ChunkManifestResponse { missing }).into_response()
 }
 
 pub async fn upload_device_logs(
 	State(ctx): State<Arc<Context>>,
 	Path(device_id): Path<String>,
+	Query(token_q): Query<DeviceTokenQuery>,
+	headers: HeaderMap,
 	body: Body,
 ) -> impl IntoResponse {
-	let _guard = match ctx.upload_guard() {
+	if let Err(status) =
+		require_device_token(&ctx, &device_id, &headers, token_q.token.as_deref()).await
+	{
+		return status.into_response();
+	}
+
+	// Smooth bursty upload traffic: wait briefly on a fair queue for a free
+	// slot instead of rejecting the instant the concurrency cap is hit.
+	// Acquired once per request, so a resumable upload pays this cost per
+	// chunk rather than once for the whole transfer.
+	let _guard = match ctx.acquire_upload_slot(std::time::Duration::from_secs(10)).await {
 		Ok(g) => g,
 		Err(err) => {
 			let retry_after = rand::rng().random_range(10..=5_000);
-			log::warn!("Upload guard busy: {}", err);
+			log::warn!("Upload admission failed: {}", err);
 			let mut resp =
 				(StatusCode::SERVICE_UNAVAILABLE, "Upload limit reached").into_response();
 			resp.headers_mut().insert(
@@ -446,6 +765,127 @@ pub async fn upload_device_logs(
 		}
 	};
 
+	let upload_id_header = header_str(&headers, "upload-id").map(|s| s.to_string());
+
+	// Step 1: create a resumable upload session and hand back its upload-id.
+	if header_str(&headers, "upload-init").is_some() && upload_id_header.is_none() {
+		let upload_dir = upload_path();
+		let ts = chrono::Utc::now().timestamp_millis();
+		let nonce: u32 = rand::rng().random_range(0..=u32::MAX);
+		let upload_id = format!("{device_id}-{ts:013}-{nonce:08x}");
+		let part_path = upload_dir.join(format!("{upload_id}.part"));
+		if let Err(e) = OpenOptions::new()
+			.create(true)
+			.write(true)
+			.truncate(true)
+			.open(&part_path)
+			.await
+		{
+			log::error!("cannot create {}: {}", part_path.display(), e);
+			return (StatusCode::INTERNAL_SERVER_ERROR, "cannot create file").into_response();
+		}
+		let mut resp = (StatusCode::OK, "ok").into_response();
+		resp.headers_mut()
+			.insert("upload-id", upload_id.parse().unwrap());
+		resp.headers_mut().insert("upload-offset", "0".parse().unwrap());
+		return resp;
+	}
+
+	// Step 3: commit a completed resumable upload.
+	if let Some(upload_id) = upload_id_header.clone() {
+		if header_str(&headers, "upload-complete").is_some() {
+			let part_path = match resumable_part_path(&device_id, &upload_id) {
+				Some(p) => p,
+				None => return (StatusCode::BAD_REQUEST, "invalid upload-id").into_response(),
+			};
+			let file = match File::open(&part_path).await {
+				Ok(f) => f,
+				Err(e) => {
+					log::warn!("commit failed, no such upload {}: {}", part_path.display(), e);
+					return (StatusCode::NOT_FOUND, "unknown upload").into_response();
+				}
+			};
+			if let Err(e) = file.sync_all().await {
+				log::warn!("sync_all failed on {}: {}", part_path.display(), e);
+			}
+			drop(file);
+			let ready_path = part_path.with_extension("ready");
+			if let Err(e) = tokio::fs::rename(&part_path, &ready_path).await {
+				log::error!(
+					"rename {} -> {} failed: {}",
+					part_path.display(),
+					ready_path.display(),
+					e
+				);
+				return (StatusCode::INTERNAL_SERVER_ERROR, "rename failed").into_response();
+			}
+			return (StatusCode::OK, "ok").into_response();
+		}
+	}
+
+	// Step 2: append a single chunk of a resumable upload.
+	if let (Some(upload_id), Some(range)) = (
+		upload_id_header,
+		header_str(&headers, "content-range").map(|s| s.to_string()),
+	) {
+		let part_path = match resumable_part_path(&device_id, &upload_id) {
+			Some(p) => p,
+			None => return (StatusCode::BAD_REQUEST, "invalid upload-id").into_response(),
+		};
+		let (start, _end, _total) = match parse_content_range(&range) {
+			Some(v) => v,
+			None => return (StatusCode::BAD_REQUEST, "malformed Content-Range").into_response(),
+		};
+		let current_len = match metadata(&part_path).await {
+			Ok(m) => m.len(),
+			Err(e) => {
+				log::warn!("chunk for unknown upload {}: {}", part_path.display(), e);
+				return (StatusCode::NOT_FOUND, "unknown upload").into_response();
+			}
+		};
+		if start != current_len {
+			let mut resp = (StatusCode::CONFLICT, "offset mismatch").into_response();
+			resp.headers_mut()
+				.insert("upload-offset", current_len.to_string().parse().unwrap());
+			return resp;
+		}
+
+		let mut file = match OpenOptions::new().append(true).open(&part_path).await {
+			Ok(f) => f,
+			Err(e) => {
+				log::error!("cannot append to {}: {}", part_path.display(), e);
+				return (StatusCode::INTERNAL_SERVER_ERROR, "cannot open upload").into_response();
+			}
+		};
+
+		let mut stream: BodyDataStream = body.into_data_stream();
+		let mut written: u64 = 0;
+		while let Some(chunk) = stream.next().await {
+			match chunk {
+				Ok(bytes) => {
+					if let Err(e) = file.write_all(&bytes).await {
+						log::error!("write failed for {}: {}", part_path.display(), e);
+						return (StatusCode::INTERNAL_SERVER_ERROR, "write error").into_response();
+					}
+					written += bytes.len() as u64;
+				}
+				Err(e) => {
+					log::error!("Error receiving chunk: {}", e);
+					return (StatusCode::BAD_REQUEST, "malformed upload").into_response();
+				}
+			}
+		}
+
+		let mut resp = (StatusCode::OK, "ok").into_response();
+		resp.headers_mut().insert(
+			"upload-offset",
+			(current_len + written).to_string().parse().unwrap(),
+		);
+		return resp;
+	}
+
+	// Legacy single-shot path: the whole body arrives in one request.
+	let chunk_digest_header = header_str(&headers, "x-chunk-digest").map(|s| s.to_string());
 	let upload_dir = upload_path();
 	let ts = chrono::Utc::now().timestamp_millis();
 	let nonce: u32 = rand::rng().random_range(0..=u32::MAX);
@@ -492,7 +932,7 @@ pub async fn upload_device_logs(
 	let ready_path = part_path.with_extension("ready");
 	if let Err(e) = tokio::fs::rename(&part_path, &ready_path).await {
 		log::error!(
-			"rename {} â†’ {} failed: {}",
+			"rename {} -> {} failed: {}",
 			part_path.display(),
 			ready_path.display(),
 			e
@@ -500,6 +940,10 @@ pub async fn upload_device_logs(
 		return (StatusCode::INTERNAL_SERVER_ERROR, "rename failed").into_response();
 	}
 
+	if let Some(digest) = chunk_digest_header {
+		ctx.chunk_manifest.remember(&device_id, &digest);
+	}
+
 	(StatusCode::OK, "ok").into_response()
 }
 
@@ -516,12 +960,15 @@ struct DeviceStatus {
 pub async fn get_device_status(
 	State(ctx): State<Arc<Context>>,
 	Path(device_id): Path<String>,
-) -> Json<Value> {
+	Query(token_q): Query<DeviceTokenQuery>,
+	headers: HeaderMap,
+) -> Result<Json<Value>, StatusCode> {
+	require_device_token(&ctx, &device_id, &headers, token_q.token.as_deref()).await?;
 	let device = match ctx.db.get_or_create_device(&device_id).await {
 		Ok(device) => device,
 		Err(err) => {
 			log::error!("failed to get or create device {}: {}", device_id, err);
-			return Json(Value::Null);
+			return Ok(Json(Value::Null));
 		}
 	};
 
@@ -543,7 +990,7 @@ pub async fn get_device_status(
 		);
 	}
 
-	Json(serde_json::to_value(resp).unwrap())
+	Ok(Json(serde_json::to_value(resp).unwrap()))
 }
 
 pub async fn update_device_metadata(
@@ -714,9 +1161,8 @@ struct UpdateQuery {
 
 pub async fn post_settings_query(State(ctx): State<Arc<Context>>, body: String) -> &'static str {
 	log::info!("post_settings_query: {:?}", body);
-	let mut settings = ctx.settings.inner().await;
-	settings.collection_query = body.clone();
-	settings.save().unwrap();
+	ctx.settings.add_query("default", &body).await.unwrap();
+	ctx.settings.activate_query("default").await.unwrap();
 	ctx.event_tx
 		.send(PuppylogEvent::QueryChanged { query: body })
 		.unwrap();
@@ -724,8 +1170,79 @@ pub async fn post_settings_query(State(ctx): State<Arc<Context>>, body: String)
 }
 
 pub async fn get_settings_query(State(ctx): State<Arc<Context>>) -> String {
+	ctx.settings.active_query_text().await
+}
+
+pub async fn get_retention_policy(State(ctx): State<Arc<Context>>) -> Json<Value> {
 	let settings = ctx.settings.inner().await;
-	settings.collection_query.clone()
+	Json(serde_json::to_value(&settings.retention_policy).unwrap())
+}
+
+pub async fn put_retention_policy(
+	State(ctx): State<Arc<Context>>,
+	_admin: RequireRole<AdminOnly>,
+	Json(policy): Json<crate::settings::RetentionPolicy>,
+) -> &'static str {
+	log::info!("put_retention_policy: {:?}", policy);
+	let mut settings = ctx.settings.inner().await;
+	settings.retention_policy = policy;
+	settings.save().unwrap();
+	"ok"
+}
+
+pub async fn get_cors_policy(State(ctx): State<Arc<Context>>) -> Json<Value> {
+	let settings = ctx.settings.inner().await;
+	Json(serde_json::to_value(&settings.cors_policy).unwrap())
+}
+
+pub async fn put_cors_policy(
+	State(ctx): State<Arc<Context>>,
+	_admin: RequireRole<AdminOnly>,
+	Json(policy): Json<crate::settings::CorsPolicy>,
+) -> &'static str {
+	log::info!("put_cors_policy: {:?}", policy);
+	let mut settings = ctx.settings.inner().await;
+	settings.cors_policy = policy;
+	settings.save().unwrap();
+	"ok"
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CleanupPreviewQuery {
+	/// Fraction of total disk space the pass would stop evicting at. Defaults
+	/// to `DiskCleanupConfig::target_free_ratio` (15% unless
+	/// `DISK_CLEANUP_TARGET_FREE_RATIO` overrides it), the target
+	/// `run_disk_space_monitor` uses once it's already
+	/// decided a directory is low, so the preview matches what an emergency
+	/// pass would actually do.
+	min_free_ratio: Option<f64>,
+}
+
+pub async fn get_cleanup_status(State(ctx): State<Arc<Context>>) -> Json<Value> {
+	let status = ctx.cleanup_status.lock().unwrap().clone();
+	Json(serde_json::to_value(&status).unwrap())
+}
+
+pub async fn preview_cleanup(
+	State(ctx): State<Arc<Context>>,
+	Query(params): Query<CleanupPreviewQuery>,
+) -> Json<Value> {
+	let min_free_ratio = params
+		.min_free_ratio
+		.unwrap_or(ctx.disk_cleanup.target_free_ratio);
+	let report = crate::cleanup::run_cleanup_pass(&ctx, min_free_ratio, None, true).await;
+	Json(serde_json::to_value(&report).unwrap())
+}
+
+pub async fn preview_retention(State(ctx): State<Arc<Context>>) -> Result<Json<Value>, StatusCode> {
+	let policy = ctx.settings.inner().await.retention_policy.clone();
+	match crate::retention::plan_evictions(&ctx, &policy).await {
+		Ok(segments) => Ok(Json(serde_json::to_value(&segments).unwrap())),
+		Err(err) => {
+			log::error!("preview_retention failed: {}", err);
+			Err(StatusCode::INTERNAL_SERVER_ERROR)
+		}
+	}
 }
 
 pub async fn favicon(headers: HeaderMap) -> Result<Response, StatusCode> {
@@ -802,12 +1319,48 @@ fn search_progress_to_json(progress: &SearchProgress) -> Value {
 	})
 }
 
+/// Opaque SSE resumption id for a log entry: `{timestamp_micros}:{id}`.
+/// `id` is `LogEntry::id_string()`, which already packs `(timestamp_ms,
+/// random)` into a single monotonic integer, so comparing it alone is
+/// enough to tell "already sent" from "new" on reconnect; the timestamp
+/// is kept alongside so it can directly reseed `query.end_date`.
+fn log_event_id(entry: &LogEntry) -> String {
+	format!("{}:{}", entry.timestamp.timestamp_micros(), entry.id_string())
+}
+
+struct LogCursor {
+	timestamp: DateTime<Utc>,
+	id: u128,
+}
+
+fn parse_log_cursor(value: &str) -> Option<LogCursor> {
+	let (ts_micros, id) = value.split_once(':')?;
+	let timestamp = DateTime::<Utc>::from_timestamp_micros(ts_micros.parse().ok()?)?;
+	let id: u128 = id.parse().ok()?;
+	Some(LogCursor { timestamp, id })
+}
+
+/// Opaque pagination cursor for the JSON `get_logs` page: base64 of the same
+/// `{timestamp_micros}:{id}` pair `log_event_id` uses for SSE resumption, so
+/// a page boundary and a reconnect boundary mean exactly the same thing.
+fn encode_logs_cursor(entry: &LogEntry) -> String {
+	URL_SAFE_NO_PAD.encode(log_event_id(entry))
+}
+
+fn decode_logs_cursor(value: &str) -> Option<LogCursor> {
+	let decoded = URL_SAFE_NO_PAD.decode(value).ok()?;
+	let value = String::from_utf8(decoded).ok()?;
+	parse_log_cursor(&value)
+}
+
 pub async fn get_logs(
 	State(ctx): State<Arc<Context>>,
 	Query(params): Query<GetLogsQuery>,
 	headers: HeaderMap,
 ) -> Result<Response, BadRequestError> {
 	log::info!("get_logs {:?}", params);
+	let query_started = std::time::Instant::now();
+	ctx.metrics.queries_served.fetch_add(1, Ordering::Relaxed);
 	let mut query = match params.query {
 		Some(ref q) => {
 			let q = q.replace('\n', " ");
@@ -833,11 +1386,29 @@ pub async fn get_logs(
 		None => Some(Utc::now() + chrono::Duration::days(200)),
 	};
 
+	// A reconnecting EventSource sends back the id of the last event it
+	// saw; resume the query right there instead of restarting from scratch.
+	let last_event_cursor = headers
+		.get("last-event-id")
+		.and_then(|v| v.to_str().ok())
+		.and_then(parse_log_cursor);
+	if let Some(cursor) = &last_event_cursor {
+		query.end_date = Some(cursor.timestamp);
+	}
+
+	// S3-list-style pagination for the JSON branch: `cursor` is the opaque
+	// `nextCursor` an earlier page returned, so the query picks up strictly
+	// after whatever that page already delivered.
+	let page_cursor = params.cursor.as_deref().and_then(decode_logs_cursor);
+	if let Some(cursor) = &page_cursor {
+		query.end_date = Some(cursor.timestamp);
+	}
+
 	let (tx, rx) = mpsc::channel(100);
 	let ctx_clone = Arc::clone(&ctx);
 	let q = query.clone();
 	spawn_blocking(move || {
-		let _ = block_on(ctx_clone.find_logs(q, &tx));
+		let _ = block_on(ctx_clone.find_logs_with_progress(q, &tx));
 	});
 
 	let wants_stream = headers
@@ -849,68 +1420,140 @@ pub async fn get_logs(
 	let limit = query.limit.unwrap_or(200) as usize;
 
 	let res = if wants_stream {
-		#[derive(Debug)]
 		struct StreamState {
 			entries_sent: usize,
 			done: bool,
+			// Entries at-or-before the resumed cursor that find_logs will
+			// re-walk past before reaching genuinely new ground.
+			skip_until_id: Option<u128>,
+			// Holds the SSE-subscriber gauge up for as long as this stream
+			// (and thus the client's connection) is alive.
+			_subscriber_guard: SseSubscriberGuard,
 		}
 
-		let stream = ReceiverStream::new(rx).scan(
-			StreamState {
-				entries_sent: 0,
-				done: limit == 0,
-			},
-			move |state, item| {
-				let limit = limit;
-				if state.done {
-					return future::ready(None);
-				}
-				match item {
-					LogStreamItem::Entry(log) => {
-						let data = to_string(&logentry_to_json(&log)).unwrap();
-						state.entries_sent += 1;
-						if state.entries_sent >= limit {
-							state.done = true;
-						}
-						future::ready(Some(Ok::<Event, std::convert::Infallible>(
-							Event::default().data(data),
-						)))
-					}
-					LogStreamItem::SegmentProgress(progress) => {
-						let data = to_string(&segment_progress_to_json(&progress)).unwrap();
-						future::ready(Some(Ok::<Event, std::convert::Infallible>(
-							Event::default().event("progress").data(data),
-						)))
+		let skip_until_id = last_event_cursor.map(|c| c.id);
+		let stream = ReceiverStream::new(rx)
+			.scan(
+				StreamState {
+					entries_sent: 0,
+					done: limit == 0,
+					skip_until_id,
+					_subscriber_guard: SseSubscriberGuard::new(ctx.clone()),
+				},
+				move |state, item| {
+					let limit = limit;
+					if state.done {
+						return future::ready(None);
 					}
-					LogStreamItem::SearchProgress(progress) => {
-						let data = to_string(&search_progress_to_json(&progress)).unwrap();
-						future::ready(Some(Ok::<Event, std::convert::Infallible>(
-							Event::default().event("progress").data(data),
-						)))
+					match item {
+						LogStreamItem::Entry(log) => {
+							if let Some(skip_id) = state.skip_until_id {
+								if log.id() >= skip_id {
+									return future::ready(Some(None));
+								}
+								state.skip_until_id = None;
+							}
+							let id = log_event_id(&log);
+							let data = to_string(&logentry_to_json(&log)).unwrap();
+							state.entries_sent += 1;
+							if state.entries_sent >= limit {
+								state.done = true;
+							}
+							future::ready(Some(Some(Ok::<Event, std::convert::Infallible>(
+								Event::default().id(id).data(data),
+							))))
+						}
+						LogStreamItem::SegmentProgress(progress) => {
+							let data = to_string(&segment_progress_to_json(&progress)).unwrap();
+							future::ready(Some(Some(Ok::<Event, std::convert::Infallible>(
+								Event::default().event("progress").data(data),
+							))))
+						}
+						LogStreamItem::SearchProgress(progress) => {
+							let data = to_string(&search_progress_to_json(&progress)).unwrap();
+							future::ready(Some(Some(Ok::<Event, std::convert::Infallible>(
+								Event::default().event("progress").data(data),
+							))))
+						}
+						// Not wired into this endpoint's own cursor/count pagination.
+						LogStreamItem::Cursor(_)
+						| LogStreamItem::Tail
+						| LogStreamItem::BatchEntry { .. } => future::ready(Some(None)),
 					}
-				}
-			},
-		);
+				},
+			)
+			.filter_map(future::ready);
 		Sse::new(stream).into_response()
 	} else {
-		let logs: Vec<_> = ReceiverStream::new(rx)
-			.filter_map(|item| async move {
-				match item {
-					LogStreamItem::Entry(log) => Some(logentry_to_json(&log)),
-					LogStreamItem::SegmentProgress(_) | LogStreamItem::SearchProgress(_) => None,
+		let mut skip_until_id = page_cursor.map(|c| c.id);
+		let mut entries = Vec::new();
+		let mut more = false;
+		let mut stream = ReceiverStream::new(rx).filter_map(|item| async move {
+			match item {
+				LogStreamItem::Entry(log) => Some(log),
+				LogStreamItem::SegmentProgress(_)
+				| LogStreamItem::SearchProgress(_)
+				| LogStreamItem::Cursor(_)
+				| LogStreamItem::Tail
+				| LogStreamItem::BatchEntry { .. } => None,
+			}
+		});
+		while let Some(log) = stream.next().await {
+			if let Some(skip_id) = skip_until_id {
+				if log.id() >= skip_id {
+					continue;
 				}
-			})
-			.take(limit)
-			.collect()
-			.await;
-		Json(serde_json::to_value(&logs).unwrap()).into_response()
+				skip_until_id = None;
+			}
+			if entries.len() == limit {
+				// Another match showed up past the page we're returning;
+				// the caller needs to come back with a nextCursor.
+				more = true;
+				break;
+			}
+			entries.push(log);
+		}
+		let next_cursor = if more {
+			entries.last().map(encode_logs_cursor)
+		} else {
+			None
+		};
+		let logs: Vec<_> = entries.iter().map(logentry_to_json).collect();
+		ctx.metrics.query_latency.observe(query_started.elapsed());
+		Json(json!({
+			"logs": logs,
+			"nextCursor": next_cursor,
+			"more": more,
+		}))
+		.into_response()
 	};
 	Ok(res)
 }
 
+/// RAII handle for the `puppylog_sse_subscribers` gauge: increments on
+/// construction, decrements when the SSE stream (and thus the client
+/// connection) is dropped.
+struct SseSubscriberGuard {
+	ctx: Arc<Context>,
+}
+
+impl SseSubscriberGuard {
+	fn new(ctx: Arc<Context>) -> Self {
+		ctx.metrics.sse_subscribers.fetch_add(1, Ordering::Relaxed);
+		Self { ctx }
+	}
+}
+
+impl Drop for SseSubscriberGuard {
+	fn drop(&mut self) {
+		self.ctx.metrics.sse_subscribers.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
 pub async fn stream_logs(
 	State(ctx): State<Arc<Context>>,
 	Query(params): Query<GetLogsQuery>,
+	headers: HeaderMap,
 ) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, BadRequestError> {
 	log::info!("stream logs {:?}", params);
 	let mut query = match params.query {
@@ -923,20 +1566,232 @@ pub async fn stream_logs(
 	if let Some(offset) = params.tz_offset {
 		query.tz_offset = chrono::FixedOffset::east_opt(-offset * 60);
 	}
-	let rx = ctx.subscriber.subscribe(query).await;
-	let stream = tokio_stream::wrappers::ReceiverStream::new(rx).map(|p| {
-		let data = to_string(&logentry_to_json(&p)).unwrap();
-		Ok(Event::default().data(data))
+
+	// A reconnecting EventSource sends back the id of the last event it saw;
+	// replay whatever matched while it was disconnected before handing off
+	// to the live feed, so the merged stream has no holes or duplicates.
+	let last_event_cursor = headers
+		.get("last-event-id")
+		.and_then(|v| v.to_str().ok())
+		.and_then(parse_log_cursor);
+
+	let live_rx = ctx.subscriber.subscribe(query.clone()).await;
+	let (out_tx, out_rx) = mpsc::channel::<Result<Event, axum::Error>>(100);
+
+	tokio::spawn(async move {
+		let _subscriber_guard = SseSubscriberGuard::new(ctx.clone());
+		if let Some(cursor) = last_event_cursor {
+			let mut replay_query = query;
+			replay_query.end_date = Some(Utc::now());
+			let (entry_tx, mut entry_rx) = mpsc::channel(100);
+			let search_ctx = Arc::clone(&ctx);
+			let search = tokio::spawn(async move {
+				let _ = search_ctx
+					.find_logs_with_progress(replay_query, &entry_tx)
+					.await;
+			});
+			let mut replay = Vec::new();
+			while let Some(item) = entry_rx.recv().await {
+				if let LogStreamItem::Entry(entry) = item {
+					// find_logs walks newest-first; once we reach an entry
+					// the client already saw, everything after is old news.
+					if entry.timestamp < cursor.timestamp
+						|| (entry.timestamp == cursor.timestamp && entry.id() <= cursor.id)
+					{
+						break;
+					}
+					replay.push(entry);
+				}
+			}
+			drop(entry_rx);
+			let _ = search.await;
+			for entry in replay.into_iter().rev() {
+				let id = log_event_id(&entry);
+				let data = to_string(&logentry_to_json(&entry)).unwrap();
+				if out_tx
+					.send(Ok(Event::default().id(id).data(data)))
+					.await
+					.is_err()
+				{
+					return;
+				}
+			}
+		}
+
+		let mut live_rx = live_rx;
+		while let Some(entry) = live_rx.recv().await {
+			let id = log_event_id(&entry);
+			let data = to_string(&logentry_to_json(&entry)).unwrap();
+			if out_tx
+				.send(Ok(Event::default().id(id).data(data)))
+				.await
+				.is_err()
+			{
+				break;
+			}
+		}
 	});
-	Ok(Sse::new(stream))
+
+	Ok(Sse::new(ReceiverStream::new(out_rx)))
+}
+
+/// One named sub-query of a `/api/v1/logs/batch` request. Mirrors
+/// [`GetLogsQuery`] field-for-field except `cursor`, which a batch entry
+/// doesn't take, so callers can lift an existing single-query payload
+/// straight into a batch entry.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NamedQuery {
+	pub name: String,
+	pub query: Option<String>,
+	pub count: Option<usize>,
+	pub end_date: Option<DateTime<Utc>>,
+	pub tz_offset: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchQueryRequest {
+	pub queries: Vec<NamedQuery>,
+}
+
+/// A sub-query either resolves to its matching entries, or fails on its own
+/// without taking the rest of the batch down with it.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchQueryResult {
+	Ok { logs: Vec<Value> },
+	Err { error: String },
+}
+
+/// How many sub-queries of a batch request run at once. Keeps a single
+/// dashboard refresh from fanning out an unbounded number of concurrent
+/// segment walks against the store.
+const BATCH_MAX_CONCURRENCY: usize = 8;
+
+async fn run_named_query(ctx: Arc<Context>, item: NamedQuery) -> (String, BatchQueryResult) {
+	let name = item.name;
+	let mut query = match item.query {
+		Some(ref q) => {
+			let q = q.replace('\n', " ");
+			let q = q.trim();
+			if q.is_empty() {
+				QueryAst::default()
+			} else {
+				match parse_log_query(q) {
+					Ok(query) => query,
+					Err(err) => {
+						return (
+							name,
+							BatchQueryResult::Err {
+								error: err.to_string(),
+							},
+						)
+					}
+				}
+			}
+		}
+		None => QueryAst::default(),
+	};
+	if let Some(offset) = item.tz_offset {
+		query.tz_offset = chrono::FixedOffset::east_opt(-offset * 60);
+	}
+	query.limit = item.count;
+	query.end_date = match item.end_date {
+		Some(end_date) => Some(end_date),
+		None => Some(Utc::now() + chrono::Duration::days(200)),
+	};
+
+	let limit = query.limit.unwrap_or(200) as usize;
+	let query_started = std::time::Instant::now();
+	ctx.metrics.queries_served.fetch_add(1, Ordering::Relaxed);
+
+	let (tx, rx) = mpsc::channel(100);
+	let ctx_clone = Arc::clone(&ctx);
+	let q = query.clone();
+	spawn_blocking(move || {
+		let _ = block_on(ctx_clone.find_logs_with_progress(q, &tx));
+	});
+
+	let logs: Vec<_> = ReceiverStream::new(rx)
+		.filter_map(|item| async move {
+			match item {
+				LogStreamItem::Entry(log) => Some(logentry_to_json(&log)),
+				LogStreamItem::SegmentProgress(_)
+				| LogStreamItem::SearchProgress(_)
+				| LogStreamItem::Cursor(_)
+				| LogStreamItem::Tail
+				| LogStreamItem::BatchEntry { .. } => None,
+			}
+		})
+		.take(limit)
+		.collect()
+		.await;
+	ctx.metrics.query_latency.observe(query_started.elapsed());
+	(name, BatchQueryResult::Ok { logs })
+}
+
+/// Runs several independent `get_logs`-style queries in one round trip, the
+/// way K2V's ReadBatch lets a client fetch many keys at once instead of
+/// issuing one request per dashboard panel. Each sub-query is keyed by its
+/// caller-supplied `name` in the response and fails independently: a bad
+/// query string only errors out its own entry.
+pub async fn batch_get_logs(
+	State(ctx): State<Arc<Context>>,
+	Json(request): Json<BatchQueryRequest>,
+) -> Json<Value> {
+	log::info!("batch_get_logs {} queries", request.queries.len());
+	let results: HashMap<String, BatchQueryResult> = futures::stream::iter(request.queries)
+		.map(|item| {
+			let ctx = ctx.clone();
+			async move { run_named_query(ctx, item).await }
+		})
+		.buffer_unordered(BATCH_MAX_CONCURRENCY)
+		.collect()
+		.await;
+	Json(json!(results))
+}
+
+/// Pulls the value `group_by` names out of a log entry: `"level"` reads the
+/// entry's level, anything else is looked up in `props`. `None` means the
+/// entry has no value for that key at all.
+fn group_value(entry: &LogEntry, group_by: &str) -> Option<String> {
+	if group_by == "level" {
+		return Some(entry.level.to_string());
+	}
+	entry
+		.props
+		.iter()
+		.find(|p| p.key == group_by)
+		.map(|p| p.value.to_string())
+}
+
+const DEFAULT_MAX_SERIES: usize = 50;
+const OTHER_SERIES: &str = "_other";
+
+/// SSE resumption id for a histogram bucket: `{bucket_start_millis}:{seq}`.
+/// `seq` is a per-connection counter that only exists to break ties if two
+/// buckets ever shared a timestamp; the bucket start alone is what resuming
+/// clients care about.
+fn histogram_event_id(bucket_start_millis: i64, seq: u64) -> String {
+	format!("{bucket_start_millis}:{seq}")
+}
+
+fn parse_histogram_cursor(value: &str) -> Option<DateTime<Utc>> {
+	let (ts_millis, _seq) = value.split_once(':')?;
+	DateTime::<Utc>::from_timestamp_millis(ts_millis.parse().ok()?)
 }
 
 pub async fn get_histogram(
 	State(ctx): State<Arc<Context>>,
 	Query(params): Query<GetHistogramQuery>,
+	headers: HeaderMap,
 ) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, BadRequestError> {
 	log::info!("get histogram {:?}", params);
+	ctx.metrics.queries_served.fetch_add(1, Ordering::Relaxed);
 	let bucket_secs = params.bucket_secs.unwrap_or(60);
+	let group_by = params.group_by.clone();
+	let max_series = params.max_series.unwrap_or(DEFAULT_MAX_SERIES).max(1);
 	let mut query = match params.query {
 		Some(ref q) => match parse_log_query(q) {
 			Ok(q) => q,
@@ -947,21 +1802,54 @@ pub async fn get_histogram(
 	if let Some(offset) = params.tz_offset {
 		query.tz_offset = chrono::FixedOffset::east_opt(-offset * 60);
 	}
+
+	// A reconnecting EventSource resumes an interrupted historical scan via
+	// Last-Event-ID; continue the descending walk just before the bucket it
+	// already received so that bucket isn't counted twice.
+	if let Some(bucket_start) = headers
+		.get("last-event-id")
+		.and_then(|v| v.to_str().ok())
+		.and_then(parse_histogram_cursor)
+	{
+		query.end_date = Some(bucket_start - chrono::Duration::nanoseconds(1));
+	}
+
 	let (tx, rx) = mpsc::channel(100);
 	let (entry_tx, mut entry_rx) = mpsc::channel(100);
 	let ctx_clone = Arc::clone(&ctx);
 	let q = query.clone();
 	spawn_blocking(move || {
-		let _ = block_on(ctx_clone.find_logs(q, &entry_tx));
+		let _ = block_on(ctx_clone.find_logs_with_progress(q, &entry_tx));
 	});
 
 	tokio::spawn(async move {
 		let mut current_bucket: Option<i64> = None;
 		let mut count: u64 = 0;
+		let mut seq: u64 = 0;
+		let mut series: HashMap<String, u64> = HashMap::new();
+		let emit = |cb: i64, count: u64, series: &HashMap<String, u64>, seq: u64| {
+			let id = histogram_event_id(cb * 1000, seq);
+			let data = if group_by.is_some() {
+				json!({
+					"timestamp": DateTime::<Utc>::from_timestamp(cb, 0).unwrap(),
+					"series": series,
+				})
+			} else {
+				json!({
+					"timestamp": DateTime::<Utc>::from_timestamp(cb, 0).unwrap(),
+					"count": count,
+				})
+			};
+			(id, data)
+		};
 		while let Some(item) = entry_rx.recv().await {
 			let entry = match item {
 				LogStreamItem::Entry(entry) => entry,
-				LogStreamItem::SegmentProgress(_) | LogStreamItem::SearchProgress(_) => continue,
+				LogStreamItem::SegmentProgress(_)
+				| LogStreamItem::SearchProgress(_)
+				| LogStreamItem::Cursor(_)
+				| LogStreamItem::Tail
+				| LogStreamItem::BatchEntry { .. } => continue,
 			};
 			let ts = entry.timestamp.timestamp();
 			let bucket = ts - ts % bucket_secs as i64;
@@ -970,15 +1858,14 @@ pub async fn get_histogram(
 					if tx.is_closed() {
 						break;
 					}
-					let item = json!({
-					"timestamp": DateTime::<Utc>::from_timestamp(cb, 0).unwrap(),
-					"count": count,
-					});
+					let item = emit(cb, count, &series, seq);
+					seq += 1;
 					if tx.send(item).await.is_err() {
 						break;
 					}
 					current_bucket = Some(bucket);
 					count = 1;
+					series.clear();
 				} else {
 					count += 1;
 				}
@@ -986,22 +1873,98 @@ pub async fn get_histogram(
 				current_bucket = Some(bucket);
 				count = 1;
 			}
+			if let Some(group_by) = &group_by {
+				let key = group_value(&entry, group_by).unwrap_or_default();
+				if series.contains_key(&key) || series.len() < max_series {
+					*series.entry(key).or_insert(0) += 1;
+				} else {
+					*series.entry(OTHER_SERIES.to_string()).or_insert(0) += 1;
+				}
+			}
 		}
 		if let Some(cb) = current_bucket {
-			let item = json!({
-			"timestamp": DateTime::<Utc>::from_timestamp(cb, 0).unwrap(),
-			"count": count,
-			});
+			let item = emit(cb, count, &series, seq);
 			let _ = tx.send(item).await;
 		}
 	});
 
-	let stream = tokio_stream::wrappers::ReceiverStream::new(rx).map(|item| {
+	let stream = tokio_stream::wrappers::ReceiverStream::new(rx).map(|(id, item)| {
 		let data = to_string(&item).unwrap();
-		Ok(Event::default().data(data))
+		Ok(Event::default().id(id).data(data))
 	});
 	Ok(Sse::new(stream))
 }
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportLogsQuery {
+	pub query: Option<String>,
+	pub end_date: Option<DateTime<Utc>>,
+	pub tz_offset: Option<i32>,
+	/// Caps each resulting segment's pre-compression size in bytes; omit for
+	/// a single unbounded segment.
+	pub max_bytes: Option<usize>,
+}
+
+/// Runs a query to completion and writes the matches out as one or more new,
+/// downloadable segments, instead of streaming them to a live client — the
+/// "download these results" button, backed by [`Context::export_logs_to_segment`].
+/// Each returned segment is a normal segment, fetchable from
+/// `/api/v1/segment/{segmentId}/download` like any other.
+pub async fn export_logs(
+	State(ctx): State<Arc<Context>>,
+	Query(params): Query<ExportLogsQuery>,
+) -> Result<Json<Vec<ExportedSegment>>, BadRequestError> {
+	log::info!("export logs {:?}", params);
+	let mut query = match params.query {
+		Some(ref q) => match parse_log_query(q) {
+			Ok(q) => q,
+			Err(err) => return Err(BadRequestError(err.to_string())),
+		},
+		None => QueryAst::default(),
+	};
+	query.end_date = params.end_date;
+	if let Some(offset) = params.tz_offset {
+		query.tz_offset = chrono::FixedOffset::east_opt(-offset * 60);
+	}
+	match ctx.export_logs_to_segment(query, params.max_bytes).await {
+		Ok(exported) => Ok(Json(exported)),
+		Err(err) => Err(BadRequestError(err.to_string())),
+	}
+}
+
+/// What another node's `crate::cluster::PeerClient::search` POSTs here: the
+/// already-parsed `QueryAst` a coordinating node's own `search` is fanning
+/// out, verbatim — no query-string parsing on this side, since the AST
+/// round-trips as JSON.
+pub async fn cluster_search(
+	State(ctx): State<Arc<Context>>,
+	Json(query): Json<QueryAst>,
+) -> Response {
+	let (tx, rx) = mpsc::channel(100);
+	let ctx_clone = Arc::clone(&ctx);
+	tokio::spawn(async move {
+		let _ = ctx_clone.find_logs_local_with_progress(query, &tx).await;
+	});
+	let stream = ReceiverStream::new(rx).filter_map(|item| async move {
+		match item {
+			LogStreamItem::Entry(entry) => {
+				let mut line = to_string(&entry).unwrap();
+				line.push('\n');
+				Some(Ok::<_, std::io::Error>(Bytes::from(line)))
+			}
+			LogStreamItem::SegmentProgress(_)
+			| LogStreamItem::SearchProgress(_)
+			| LogStreamItem::Cursor(_)
+			| LogStreamItem::Tail
+			| LogStreamItem::BatchEntry { .. } => None,
+		}
+	});
+	let mut headers = HeaderMap::new();
+	headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/x-ndjson"));
+	(headers, Body::from_stream(stream)).into_response()
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -1048,7 +2011,7 @@ mod tests {
 				..Default::default()
 			},
 		])
-		.await;
+		.await.unwrap();
 
 		let app = Router::new()
 			.route("/api/v1/logs/histogram", get(get_histogram))
@@ -1132,7 +2095,7 @@ mod tests {
 				..Default::default()
 			},
 		])
-		.await;
+		.await.unwrap();
 
 		let app = Router::new()
 			.route("/api/logs", get(get_logs))
@@ -1152,8 +2115,89 @@ mod tests {
 
 		assert_eq!(res.status(), StatusCode::OK);
 		let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
-		let logs: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+		let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+		let logs = body["logs"].as_array().unwrap();
 		assert_eq!(logs.len(), 2);
+		assert_eq!(body["more"], false);
+		assert!(body["nextCursor"].is_null());
+	}
+
+	#[tokio::test]
+	async fn get_logs_paginates_with_cursor() {
+		let dir = TempDir::new().unwrap();
+		let log_dir = dir.path().join("logs");
+		std::fs::create_dir_all(&log_dir).unwrap();
+		std::env::set_var("LOG_PATH", &log_dir);
+		std::env::set_var("DB_PATH", dir.path().join("db.sqlite"));
+		std::env::set_var("SETTINGS_PATH", dir.path().join("settings.json"));
+		std::fs::write(
+			dir.path().join("settings.json"),
+			"{\"collection_query\":\"\"}",
+		)
+		.unwrap();
+
+		let ctx = Arc::new(Context::new(log_dir).await);
+
+		let base = Utc::now();
+		ctx.save_logs(&[
+			LogEntry {
+				timestamp: base,
+				msg: "a".into(),
+				..Default::default()
+			},
+			LogEntry {
+				timestamp: base + chrono::Duration::seconds(1),
+				msg: "b".into(),
+				..Default::default()
+			},
+			LogEntry {
+				timestamp: base + chrono::Duration::seconds(2),
+				msg: "c".into(),
+				..Default::default()
+			},
+		])
+		.await.unwrap();
+
+		let app = Router::new()
+			.route("/api/logs", get(get_logs))
+			.with_state(ctx);
+
+		let res = app
+			.clone()
+			.oneshot(
+				Request::builder()
+					.uri("/api/logs?count=2")
+					.header("accept", "application/json")
+					.body(Body::empty())
+					.unwrap(),
+			)
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::OK);
+		let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+		let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+		let logs = body["logs"].as_array().unwrap();
+		assert_eq!(logs.len(), 2);
+		assert_eq!(body["more"], true);
+		let next_cursor = body["nextCursor"].as_str().unwrap().to_string();
+
+		let res = app
+			.oneshot(
+				Request::builder()
+					.uri(format!("/api/logs?count=2&cursor={next_cursor}"))
+					.header("accept", "application/json")
+					.body(Body::empty())
+					.unwrap(),
+			)
+			.await
+			.unwrap();
+		assert_eq!(res.status(), StatusCode::OK);
+		let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+		let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+		let logs = body["logs"].as_array().unwrap();
+		assert_eq!(logs.len(), 1);
+		assert_eq!(body["more"], false);
+		assert!(body["nextCursor"].is_null());
 	}
 
 	#[tokio::test]
@@ -1420,4 +2464,82 @@ mod tests {
 		assert_eq!(v.get("uploadFilesCount").unwrap().as_u64().unwrap(), 2);
 		assert_eq!(v.get("uploadBytes").unwrap().as_u64().unwrap(), 5 + 6);
 	}
+
+	async fn serve_cluster_search(ctx: Arc<Context>) -> std::net::SocketAddr {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		let app = Router::new()
+			.route("/api/v1/cluster/search", post(cluster_search))
+			.with_state(ctx);
+		tokio::spawn(async move {
+			axum::serve(listener, app).await.unwrap();
+		});
+		addr
+	}
+
+	/// End-to-end: a real peer node runs its own `cluster_search` endpoint
+	/// over an actual TCP listener, and `Context::find_logs_with_progress`
+	/// (with that peer wired into `ctx.peers`) merges its entries in with
+	/// the local store's, via `crate::cluster::PeerClient` doing a genuine
+	/// HTTP round-trip rather than an in-process call.
+	#[tokio::test]
+	#[serial_test::serial]
+	async fn cluster_search_merges_local_and_peer_entries() {
+		let peer_dir = TempDir::new().unwrap();
+		let peer_log_dir = peer_dir.path().join("logs");
+		std::fs::create_dir_all(&peer_log_dir).unwrap();
+		std::env::set_var("LOG_PATH", &peer_log_dir);
+		std::env::set_var("DB_PATH", peer_dir.path().join("db.sqlite"));
+		std::env::set_var("SETTINGS_PATH", peer_dir.path().join("settings.json"));
+		std::fs::write(peer_dir.path().join("settings.json"), "{}").unwrap();
+		let peer_ctx = Arc::new(Context::new(peer_log_dir).await);
+		let base = Utc::now();
+		peer_ctx
+			.save_logs(&[LogEntry {
+				timestamp: base + chrono::Duration::seconds(5),
+				msg: "from-peer".into(),
+				..Default::default()
+			}])
+			.await
+			.unwrap();
+		let peer_addr = serve_cluster_search(peer_ctx).await;
+
+		let local_dir = TempDir::new().unwrap();
+		let local_log_dir = local_dir.path().join("logs");
+		std::fs::create_dir_all(&local_log_dir).unwrap();
+		std::env::set_var("LOG_PATH", &local_log_dir);
+		std::env::set_var("DB_PATH", local_dir.path().join("db.sqlite"));
+		std::env::set_var("SETTINGS_PATH", local_dir.path().join("settings.json"));
+		std::fs::write(local_dir.path().join("settings.json"), "{}").unwrap();
+		let mut local_ctx = Context::new(local_log_dir).await;
+		local_ctx.peers = vec![crate::cluster::PeerClient::new(format!(
+			"http://{peer_addr}"
+		))];
+		local_ctx
+			.save_logs(&[LogEntry {
+				timestamp: base,
+				msg: "from-local".into(),
+				..Default::default()
+			}])
+			.await
+			.unwrap();
+
+		let (tx, mut rx) = mpsc::channel(100);
+		local_ctx
+			.find_logs_with_progress(QueryAst::default(), &tx)
+			.await
+			.unwrap();
+		drop(tx);
+
+		let mut messages = Vec::new();
+		while let Some(item) = rx.recv().await {
+			if let LogStreamItem::Entry(entry) = item {
+				messages.push(entry.msg);
+			}
+		}
+		// Newest first: the peer's entry (5s after base) sorts ahead of the
+		// local one (at base), exactly like two overlapping local segments
+		// would merge.
+		assert_eq!(messages, vec!["from-peer", "from-local"]);
+	}
 }
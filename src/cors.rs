@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::context::Context;
+use crate::settings::CorsPolicy;
+
+impl CorsPolicy {
+	fn matches_origin(&self, origin: &str) -> bool {
+		self.allowed_origins
+			.iter()
+			.any(|allowed| allowed == "*" || allowed == origin)
+	}
+}
+
+fn apply_headers(policy: &CorsPolicy, origin: &str, headers: &mut HeaderMap) {
+	// Credentialed responses can't use the "*" wildcard, so echo the
+	// actual origin back instead; that's the only case where reflecting
+	// an arbitrary request header into the response is safe here, since
+	// we've already checked it against the configured allow-list.
+	let allow_origin = if policy.allow_credentials || !policy.allowed_origins.iter().any(|o| o == "*") {
+		origin.to_string()
+	} else {
+		"*".to_string()
+	};
+	headers.insert(
+		header::ACCESS_CONTROL_ALLOW_ORIGIN,
+		allow_origin.parse().unwrap(),
+	);
+	if policy.allow_credentials {
+		headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".parse().unwrap());
+	}
+	headers.insert(header::VARY, "Origin".parse().unwrap());
+}
+
+/// Applies the runtime-configurable CORS policy stored in `Settings`:
+/// answers preflight `OPTIONS` requests directly and attaches
+/// `Access-Control-Allow-*` headers to every other response, in place of
+/// the fixed `tower_http::cors::CorsLayer` this used to be.
+pub async fn cors_middleware(
+	State(ctx): State<Arc<Context>>,
+	req: Request,
+	next: Next,
+) -> Response {
+	let policy = ctx.settings.inner().await.cors_policy.clone();
+	let origin = req
+		.headers()
+		.get(header::ORIGIN)
+		.and_then(|v| v.to_str().ok())
+		.map(|s| s.to_string());
+
+	let is_preflight = req.method() == Method::OPTIONS
+		&& req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+	let Some(origin) = origin.filter(|o| policy.matches_origin(o)) else {
+		return if is_preflight {
+			StatusCode::NO_CONTENT.into_response()
+		} else {
+			next.run(req).await
+		};
+	};
+
+	if is_preflight {
+		let mut resp = StatusCode::NO_CONTENT.into_response();
+		apply_headers(&policy, &origin, resp.headers_mut());
+		resp.headers_mut().insert(
+			header::ACCESS_CONTROL_ALLOW_METHODS,
+			policy.allowed_methods.join(", ").parse().unwrap(),
+		);
+		resp.headers_mut().insert(
+			header::ACCESS_CONTROL_ALLOW_HEADERS,
+			policy.allowed_headers.join(", ").parse().unwrap(),
+		);
+		resp.headers_mut().insert(
+			header::ACCESS_CONTROL_MAX_AGE,
+			policy.max_age_secs.to_string().parse().unwrap(),
+		);
+		return resp;
+	}
+
+	let mut resp = next.run(req).await;
+	apply_headers(&policy, &origin, resp.headers_mut());
+	resp
+}
@@ -0,0 +1,231 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Whether a [`DataDir`] accepts newly written segments. A directory that's
+/// filled up (or that an operator wants to drain ahead of removing a mount)
+/// is flipped to `ReadOnly` without losing the segments already on it: reads
+/// and cleanup still consider it, only placement of new segments skips it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDirState {
+	Active,
+	ReadOnly,
+}
+
+/// One data directory in a [`DataLayout`]: a path, the capacity an operator
+/// has declared for it, and whether it still accepts new segments.
+#[derive(Debug)]
+pub struct DataDir {
+	pub path: PathBuf,
+	pub capacity_bytes: u64,
+	pub state: DataDirState,
+	used_bytes: AtomicU64,
+}
+
+impl DataDir {
+	pub fn new(path: PathBuf, capacity_bytes: u64, state: DataDirState) -> Self {
+		let used_bytes = Self::scan_used_bytes(&path);
+		Self {
+			path,
+			capacity_bytes,
+			state,
+			used_bytes: AtomicU64::new(used_bytes),
+		}
+	}
+
+	/// Sums the size of every `*.log` segment file already on disk, so a
+	/// restart picks capacity-aware placement back up where it left off
+	/// instead of believing every directory is empty.
+	fn scan_used_bytes(path: &Path) -> u64 {
+		let mut total = 0u64;
+		if let Ok(entries) = std::fs::read_dir(path) {
+			for entry in entries.flatten() {
+				if entry.path().extension().and_then(|e| e.to_str()) == Some("log") {
+					if let Ok(meta) = entry.metadata() {
+						total += meta.len();
+					}
+				}
+			}
+		}
+		total
+	}
+
+	fn used(&self) -> u64 {
+		self.used_bytes.load(Ordering::Relaxed)
+	}
+
+	/// Remaining bytes below `capacity_bytes`, for ranking directories by
+	/// free space when placing a new segment.
+	fn free_bytes(&self) -> u64 {
+		self.capacity_bytes.saturating_sub(self.used())
+	}
+
+	/// Whether this directory is allowed to receive new segments and still
+	/// has headroom below `capacity_bytes * FULL_THRESHOLD`.
+	fn has_headroom(&self) -> bool {
+		self.state == DataDirState::Active
+			&& self.used() < (self.capacity_bytes as f64 * FULL_THRESHOLD) as u64
+	}
+
+	pub fn record_write(&self, bytes: u64) {
+		self.used_bytes.fetch_add(bytes, Ordering::Relaxed);
+	}
+
+	pub fn record_delete(&self, bytes: u64) {
+		self.used_bytes.fetch_sub(bytes.min(self.used()), Ordering::Relaxed);
+	}
+}
+
+/// Fraction of `capacity_bytes` a directory may fill before it's treated as
+/// full for placement purposes, even though nothing stops it from actually
+/// filling up further. Leaves headroom for in-flight writes.
+const FULL_THRESHOLD: f64 = 0.9;
+
+/// Spreads segments across a fixed set of data directories, each with a
+/// declared capacity and `Active`/`ReadOnly` state, so storage can grow
+/// across multiple mounts without reconfiguring everything at once.
+///
+/// A new segment goes to whichever `Active` directory with headroom
+/// currently has the most free capacity (in bytes, not as a share of its
+/// size), so writes go where the most room is left rather than a uniform
+/// hash spreading writes evenly regardless of how full each mount already
+/// is. Because that choice depends
+/// on live usage rather than just the segment id, `SegmentStore::put` hands
+/// the chosen directory back so the caller can record it on the segment row
+/// (`DB::set_segment_data_dir`) for future reads to go straight to it. A read
+/// or delete without a recorded directory (older segments, or a directory
+/// added after they were written) falls back to `search_order`, which still
+/// scans every configured directory in a fixed preference order.
+#[derive(Debug)]
+pub struct DataLayout {
+	dirs: Vec<DataDir>,
+}
+
+impl DataLayout {
+	pub fn new(dirs: Vec<DataDir>) -> Self {
+		assert!(!dirs.is_empty(), "DataLayout needs at least one directory");
+		Self { dirs }
+	}
+
+	/// Single-directory layout with effectively unbounded capacity, matching
+	/// the pre-`DataLayout` behavior of `LocalFsStore` for deployments that
+	/// haven't configured `SEGMENT_DATA_DIRS`.
+	pub fn single(path: PathBuf) -> Self {
+		Self::new(vec![DataDir::new(path, u64::MAX, DataDirState::Active)])
+	}
+
+	fn partition(&self, segment_id: u32) -> usize {
+		segment_id as usize % self.dirs.len()
+	}
+
+	/// The directory a new segment should be written to: the `Active`
+	/// directory with headroom that currently has the most free capacity, or
+	/// (if every directory is `ReadOnly` or full) whichever has the most free
+	/// capacity regardless, so a write still lands somewhere rather than
+	/// failing outright.
+	pub fn dir_for_write(&self) -> &DataDir {
+		self.dirs
+			.iter()
+			.filter(|d| d.has_headroom())
+			.max_by_key(|d| d.free_bytes())
+			.unwrap_or_else(|| self.dirs.iter().max_by_key(|d| d.free_bytes()).expect("dirs is non-empty"))
+	}
+
+	/// Every directory a segment's bytes might live in, a hashed guess first,
+	/// for a read/delete that has no `DB`-recorded directory to go straight
+	/// to (an old segment written before `data_dir` was tracked, or one
+	/// whose recorded directory was since removed from the layout). The
+	/// hash doesn't correspond to where `dir_for_write` actually placed the
+	/// segment, so this is just a deterministic starting point, not a
+	/// prediction — every directory still gets scanned if the first guess
+	/// misses.
+	pub fn search_order(&self, segment_id: u32) -> Vec<&DataDir> {
+		let preferred_idx = self.partition(segment_id);
+		let mut order = Vec::with_capacity(self.dirs.len());
+		order.push(&self.dirs[preferred_idx]);
+		order.extend(
+			self.dirs
+				.iter()
+				.enumerate()
+				.filter(|(i, _)| *i != preferred_idx)
+				.map(|(_, d)| d),
+		);
+		order
+	}
+
+	pub fn dirs(&self) -> &[DataDir] {
+		&self.dirs
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::tempdir;
+
+	#[test]
+	fn prefers_the_directory_with_the_most_free_capacity() {
+		let smaller_free = tempdir().unwrap();
+		let larger_free = tempdir().unwrap();
+		let layout = DataLayout::new(vec![
+			DataDir::new(smaller_free.path().to_owned(), 1_000, DataDirState::Active),
+			DataDir::new(larger_free.path().to_owned(), 1_000, DataDirState::Active),
+		]);
+		layout.dirs()[0].record_write(400);
+		let picked = layout.dir_for_write().path.clone();
+		assert_eq!(picked, larger_free.path());
+	}
+
+	#[test]
+	fn falls_back_when_preferred_directory_is_full() {
+		let full = tempdir().unwrap();
+		let spare = tempdir().unwrap();
+		let layout = DataLayout::new(vec![
+			DataDir::new(full.path().to_owned(), 100, DataDirState::Active),
+			DataDir::new(spare.path().to_owned(), 100, DataDirState::Active),
+		]);
+		layout.dirs()[0].record_write(100);
+		let picked = layout.dir_for_write().path.clone();
+		assert_eq!(picked, spare.path());
+	}
+
+	#[test]
+	fn falls_back_when_preferred_directory_is_read_only() {
+		let readonly = tempdir().unwrap();
+		let active = tempdir().unwrap();
+		let layout = DataLayout::new(vec![
+			DataDir::new(readonly.path().to_owned(), u64::MAX, DataDirState::ReadOnly),
+			DataDir::new(active.path().to_owned(), u64::MAX, DataDirState::Active),
+		]);
+		let picked = layout.dir_for_write().path.clone();
+		assert_eq!(picked, active.path());
+	}
+
+	#[test]
+	fn writes_somewhere_even_when_every_directory_is_full() {
+		let fullest = tempdir().unwrap();
+		let least_full = tempdir().unwrap();
+		let layout = DataLayout::new(vec![
+			DataDir::new(fullest.path().to_owned(), 100, DataDirState::Active),
+			DataDir::new(least_full.path().to_owned(), 100, DataDirState::Active),
+		]);
+		// Both exceed the 90% headroom threshold, but `least_full` still has
+		// more bytes free than `fullest`.
+		layout.dirs()[0].record_write(100);
+		layout.dirs()[1].record_write(95);
+		let picked = layout.dir_for_write().path.clone();
+		assert_eq!(picked, least_full.path());
+	}
+
+	#[test]
+	fn search_order_puts_the_preferred_directory_first() {
+		let a = tempdir().unwrap();
+		let b = tempdir().unwrap();
+		let layout = DataLayout::new(vec![
+			DataDir::new(a.path().to_owned(), u64::MAX, DataDirState::Active),
+			DataDir::new(b.path().to_owned(), u64::MAX, DataDirState::Active),
+		]);
+		let order = layout.search_order(1);
+		assert_eq!(order[0].path, layout.dirs()[1].path);
+		assert_eq!(order[1].path, layout.dirs()[0].path);
+	}
+}
@@ -1,5 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::create_dir_all;
+use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, NaiveDateTime, Utc};
@@ -8,19 +10,25 @@ use diesel::dsl::{exists, now};
 use diesel::expression::BoxableExpression;
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool, PooledConnection};
-use diesel::sql_types::{BigInt, Bool, Integer, Text};
+use diesel::sql_types::{BigInt, Bool, Integer, Nullable, Text, Timestamp};
 use diesel::sqlite::{Sqlite, SqliteConnection};
 use diesel::{insert_into, insert_or_ignore_into};
 use puppylog::{LogLevel, Prop};
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
 
 use crate::config::db_path;
 use crate::schema::{
-	bucket_logs, device_props, devices, log_buckets, log_segments, migrations, segment_props,
+	bucket_logs, chunks, device_props, devices, log_buckets, log_segments, migrations,
+	retention_policies, segment_chunks, segment_props,
 };
 use crate::segment::SegmentMeta;
-use crate::types::{GetSegmentsQuery, SortDir};
+use crate::types::{GetSegmentsQuery, PropFilter, SortDir};
 
+/// `sql` is SQLite DDL (`AUTOINCREMENT`, SQLite's relaxed typing, etc.) run
+/// verbatim through `batch_execute`. Supporting `Backend::Postgres` will mean
+/// giving each entry a per-backend `sql` (or a portable builder) instead of
+/// one shared string.
 struct Migration {
 	id: u32,
 	name: &'static str,
@@ -115,20 +123,221 @@ const MIGRATIONS: &[Migration] = &[
                                 ON bucket_logs(bucket_id, created_at DESC);
                 "#,
 	},
+	Migration {
+		id: 20250710,
+		name: "segment_level",
+		sql: r#"
+                        ALTER TABLE log_segments ADD COLUMN level INTEGER NOT NULL DEFAULT 0;
+                        CREATE INDEX IF NOT EXISTS log_segments_device_id_level_idx
+                                ON log_segments(device_id, level, first_timestamp);
+                "#,
+	},
+	Migration {
+		id: 20250718,
+		name: "segment_bloom",
+		sql: r#"
+                        ALTER TABLE log_segments ADD COLUMN bloom BLOB;
+                "#,
+	},
+	Migration {
+		id: 20250725,
+		name: "segment_checksum",
+		sql: r#"
+                        ALTER TABLE log_segments ADD COLUMN checksum BIGINT;
+                        ALTER TABLE log_segments ADD COLUMN quarantined BOOLEAN NOT NULL DEFAULT 0;
+                "#,
+	},
+	Migration {
+		id: 20250801,
+		name: "segment_encryption",
+		sql: r#"
+                        ALTER TABLE log_segments ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT 0;
+                "#,
+	},
+	Migration {
+		id: 20250808,
+		name: "segment_last_accessed",
+		sql: r#"
+                        ALTER TABLE log_segments ADD COLUMN last_accessed TIMESTAMP;
+                "#,
+	},
+	Migration {
+		id: 20250815,
+		name: "segment_pinned",
+		sql: r#"
+                        ALTER TABLE log_segments ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT 0;
+                "#,
+	},
+	Migration {
+		id: 20250822,
+		name: "device_compressed_upload_stats",
+		sql: r#"
+                        ALTER TABLE devices ADD COLUMN logs_compressed_size BIGINT;
+                "#,
+	},
+	Migration {
+		id: 20250829,
+		name: "retention_policies",
+		sql: r#"
+                        CREATE TABLE retention_policies (
+                                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                                        device_id TEXT,
+                                        prop_key TEXT,
+                                        prop_value TEXT,
+                                        max_age_seconds BIGINT,
+                                        max_total_bytes BIGINT,
+                                        enabled BOOLEAN NOT NULL DEFAULT 1,
+                                        created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+                        );
+                "#,
+	},
+	Migration {
+		id: 20250905,
+		name: "segment_props_key_value_idx",
+		sql: r#"
+                        CREATE INDEX IF NOT EXISTS segment_props_key_value_segment_id_idx
+                                ON segment_props(key, value, segment_id);
+                "#,
+	},
+	Migration {
+		id: 20250912,
+		name: "segment_chunks",
+		sql: r#"
+                        CREATE TABLE IF NOT EXISTS chunks (
+                                hash TEXT PRIMARY KEY,
+                                blob BLOB NOT NULL,
+                                refcount INTEGER NOT NULL DEFAULT 0
+                        );
+                        CREATE TABLE IF NOT EXISTS segment_chunks (
+                                segment_id INTEGER NOT NULL,
+                                seq INTEGER NOT NULL,
+                                chunk_hash TEXT NOT NULL,
+                                PRIMARY KEY (segment_id, seq)
+                        );
+                        CREATE INDEX IF NOT EXISTS segment_chunks_chunk_hash_idx
+                                ON segment_chunks(chunk_hash);
+                "#,
+	},
+	Migration {
+		id: 20250919,
+		name: "segment_data_dir",
+		sql: r#"
+                        ALTER TABLE log_segments ADD COLUMN data_dir TEXT;
+                "#,
+	},
+	Migration {
+		id: 20250920,
+		name: "segment_compressed",
+		sql: r#"
+                        ALTER TABLE log_segments ADD COLUMN compressed BOOLEAN NOT NULL DEFAULT 1;
+                "#,
+	},
+	Migration {
+		id: 20250927,
+		name: "segment_last_scrubbed",
+		sql: r#"
+                        ALTER TABLE log_segments ADD COLUMN last_scrubbed TIMESTAMP;
+                "#,
+	},
 ];
 
-#[derive(Debug, Default)]
-struct SqlitePragmaSetup;
+/// Which database engine `database_url` points at. Only `Sqlite` is wired up
+/// today — `MIGRATIONS`, `SqlitePragmaSetup`, and every query in this module
+/// still assume a `SqliteConnection`. This is the first step of splitting
+/// the DB layer out behind a backend abstraction (so a server can run
+/// against Postgres for larger deployments): detecting the scheme up front
+/// so `establish_pool` can fail loudly on an unsupported one instead of
+/// silently misinterpreting the URL as a SQLite file path. Making `DB`,
+/// `DbPool`, `MIGRATIONS`, and the `on_conflict` upserts generic over the
+/// backend is follow-up work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+	Sqlite,
+	Postgres,
+}
+
+impl Backend {
+	fn from_url(database_url: &str) -> Backend {
+		if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+			Backend::Postgres
+		} else {
+			Backend::Sqlite
+		}
+	}
+}
+
+/// WAL lets readers (`find_segments`, `fetch_segment_props`, ...) proceed
+/// against the read pool while a writer on the write pool is mid-`INSERT`
+/// on `new_segment`, instead of bouncing off `SQLITE_BUSY`. `busy_timeout_ms`
+/// is the fallback for the remaining window where a writer briefly holds
+/// the single WAL write lock. `:memory:` pools should use
+/// [`DbConfig::in_memory`]: WAL and the mmap/cache sizing are meaningless
+/// (and `journal_mode=WAL` silently downgrades to `memory` there anyway), so
+/// skipping them avoids paying for pragmas that do nothing.
+#[derive(Debug, Clone, Copy)]
+pub struct DbConfig {
+	pub enable_wal: bool,
+	pub busy_timeout_ms: u32,
+	pub synchronous_normal: bool,
+	pub mmap_size_bytes: i64,
+	pub cache_size_pages: i64,
+}
+
+impl Default for DbConfig {
+	fn default() -> Self {
+		DbConfig {
+			enable_wal: true,
+			busy_timeout_ms: 5_000,
+			synchronous_normal: true,
+			mmap_size_bytes: 256 * 1024 * 1024,
+			cache_size_pages: -64_000,
+		}
+	}
+}
+
+impl DbConfig {
+	/// Config for `:memory:` pools used in tests: keeps `foreign_keys` on but
+	/// skips the pragmas that only matter for an on-disk, multi-connection
+	/// database.
+	pub fn in_memory() -> DbConfig {
+		DbConfig {
+			enable_wal: false,
+			synchronous_normal: false,
+			mmap_size_bytes: 0,
+			cache_size_pages: 0,
+			..DbConfig::default()
+		}
+	}
+}
+
+#[derive(Debug)]
+struct SqlitePragmaSetup(DbConfig);
 
 impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for SqlitePragmaSetup {
 	fn on_acquire(
 		&self,
 		conn: &mut SqliteConnection,
 	) -> std::result::Result<(), diesel::r2d2::Error> {
-		conn.batch_execute(
-			"PRAGMA journal_mode=WAL; PRAGMA busy_timeout = 5000; PRAGMA foreign_keys = ON;",
-		)
-		.map_err(diesel::r2d2::Error::QueryError)
+		let config = &self.0;
+		let mut pragmas = String::new();
+		if config.enable_wal {
+			pragmas.push_str("PRAGMA journal_mode=WAL; ");
+		}
+		pragmas.push_str(&format!(
+			"PRAGMA busy_timeout = {}; PRAGMA foreign_keys = ON;",
+			config.busy_timeout_ms
+		));
+		if config.synchronous_normal {
+			pragmas.push_str(" PRAGMA synchronous = NORMAL;");
+		}
+		if config.mmap_size_bytes > 0 {
+			pragmas.push_str(&format!(" PRAGMA mmap_size = {};", config.mmap_size_bytes));
+		}
+		if config.cache_size_pages != 0 {
+			pragmas.push_str(&format!(" PRAGMA cache_size = {};", config.cache_size_pages));
+		}
+		conn.batch_execute(&pragmas)
+			.map_err(diesel::r2d2::Error::QueryError)
 	}
 }
 
@@ -141,7 +350,13 @@ pub struct DbPools {
 	pub read_pool: DbPool,
 }
 
-pub fn establish_pool(database_url: &str) -> Result<DbPool> {
+pub fn establish_pool(database_url: &str, config: DbConfig) -> Result<DbPool> {
+	if Backend::from_url(database_url) != Backend::Sqlite {
+		anyhow::bail!(
+			"postgres backend is not supported yet; database_url must be a sqlite path, got {}",
+			database_url
+		);
+	}
 	let manager = ConnectionManager::<SqliteConnection>::new(database_url);
 	let mut builder = Pool::builder();
 	if database_url == ":memory:" {
@@ -150,14 +365,14 @@ pub fn establish_pool(database_url: &str) -> Result<DbPool> {
 		builder = builder.max_size(10);
 	}
 	builder
-		.connection_customizer(Box::<SqlitePragmaSetup>::default())
+		.connection_customizer(Box::new(SqlitePragmaSetup(config)))
 		.build(manager)
 		.context("failed to build sqlite pool")
 }
 
 pub fn open_db() -> DbPools {
 	if cfg!(test) {
-		let pool = establish_pool(":memory:").expect("in-memory pool");
+		let pool = establish_pool(":memory:", DbConfig::in_memory()).expect("in-memory pool");
 		DbPools {
 			write_pool: pool.clone(),
 			read_pool: pool,
@@ -170,8 +385,8 @@ pub fn open_db() -> DbPools {
 			}
 		}
 		let database_url = path.to_str().expect("database path is not utf-8");
-		let write_pool = establish_pool(database_url).expect("pool");
-		let read_pool = establish_pool(database_url).expect("pool");
+		let write_pool = establish_pool(database_url, DbConfig::default()).expect("pool");
+		let read_pool = establish_pool(database_url, DbConfig::default()).expect("pool");
 		DbPools {
 			write_pool,
 			read_pool,
@@ -187,6 +402,83 @@ fn opt_naive_to_utc(ts: Option<NaiveDateTime>) -> Option<DateTime<Utc>> {
 	ts.map(naive_to_utc)
 }
 
+/// Opaque keyset-pagination cursor for `find_segments_page`, encoding the
+/// `(first_timestamp, id)` pair a page left off at as `<unix_millis>_<id>`.
+fn encode_segment_cursor(first_timestamp: DateTime<Utc>, id: i32) -> String {
+	format!("{}_{}", first_timestamp.timestamp_millis(), id)
+}
+
+fn decode_segment_cursor(cursor: &str) -> Result<(NaiveDateTime, i32)> {
+	let (ts, id) = cursor
+		.split_once('_')
+		.context("malformed pagination cursor")?;
+	let ts: i64 = ts.parse().context("malformed pagination cursor timestamp")?;
+	let id: i32 = id.parse().context("malformed pagination cursor id")?;
+	let ts = DateTime::<Utc>::from_timestamp_millis(ts)
+		.context("malformed pagination cursor timestamp")?
+		.naive_utc();
+	Ok((ts, id))
+}
+
+/// Checks a single in-memory `SegmentMeta` against `query`'s device/time/
+/// level filters, for `DB::await_segments` deciding whether a freshly
+/// broadcast insert matches a live-tail subscription. Mirrors the `WHERE`
+/// clauses `find_segments` builds, minus `sort`/`count`/cursor, which only
+/// make sense against a result set rather than one candidate row.
+fn segment_matches_query(meta: &SegmentMeta, query: &GetSegmentsQuery) -> bool {
+	if let Some(start) = &query.start {
+		if meta.last_timestamp <= *start {
+			return false;
+		}
+	}
+	if let Some(end) = &query.end {
+		if meta.first_timestamp > *end {
+			return false;
+		}
+	}
+	if let Some(ids) = &query.device_ids {
+		let matches = match &meta.device_id {
+			Some(id) => ids.iter().any(|x| x == id),
+			None => false,
+		};
+		if !matches {
+			return false;
+		}
+	}
+	if let Some(level) = query.level {
+		if meta.level != level {
+			return false;
+		}
+	}
+	true
+}
+
+/// Drops `segment_id`'s `segment_chunks` rows and decrements the `refcount`
+/// of every chunk it referenced, deleting any chunk whose `refcount` hits
+/// zero. Shared by `delete_segment`/`delete_segments`, which both call this
+/// inside their own transaction so a chunk's refcount and its referencing
+/// `segment_chunks` rows are never out of sync, and a segment with no
+/// chunk rows (never passed to `store_segment_chunks`) is a no-op.
+fn release_segment_chunks(
+	conn: &mut SqliteConnection,
+	segment_id: u32,
+) -> std::result::Result<(), diesel::result::Error> {
+	let hashes: Vec<String> = segment_chunks::table
+		.filter(segment_chunks::segment_id.eq(segment_id as i32))
+		.select(segment_chunks::chunk_hash)
+		.load(conn)?;
+	diesel::delete(segment_chunks::table.filter(segment_chunks::segment_id.eq(segment_id as i32)))
+		.execute(conn)?;
+	for hash in hashes {
+		diesel::update(chunks::table.filter(chunks::hash.eq(&hash)))
+			.set(chunks::refcount.eq(chunks::refcount - 1))
+			.execute(conn)?;
+		diesel::delete(chunks::table.filter(chunks::hash.eq(&hash)).filter(chunks::refcount.le(0)))
+			.execute(conn)?;
+	}
+	Ok(())
+}
+
 #[derive(Queryable, Debug)]
 #[diesel(table_name = devices)]
 struct DeviceRow {
@@ -198,6 +490,7 @@ struct DeviceRow {
 	created_at: NaiveDateTime,
 	last_upload_at: Option<NaiveDateTime>,
 	send_interval: i32,
+	logs_compressed_size: Option<i64>,
 }
 
 #[derive(Queryable, Debug)]
@@ -212,6 +505,16 @@ struct SegmentRow {
 	compressed_size: Option<i64>,
 	logs_count: i64,
 	created_at: NaiveDateTime,
+	level: i32,
+	bloom: Option<Vec<u8>>,
+	checksum: Option<i64>,
+	quarantined: bool,
+	encrypted: bool,
+	last_accessed: Option<NaiveDateTime>,
+	pinned: bool,
+	data_dir: Option<String>,
+	compressed: bool,
+	last_scrubbed: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable)]
@@ -226,6 +529,32 @@ struct NewSegmentRecord {
 	logs_count: i64,
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = log_segments)]
+struct NewSegmentWithIdRecord {
+	id: i32,
+	bucket_id: Option<i32>,
+	device_id: Option<String>,
+	first_timestamp: NaiveDateTime,
+	last_timestamp: NaiveDateTime,
+	original_size: i64,
+	compressed_size: Option<i64>,
+	logs_count: i64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = log_segments)]
+struct NewLeveledSegmentRecord {
+	bucket_id: Option<i32>,
+	device_id: Option<String>,
+	first_timestamp: NaiveDateTime,
+	last_timestamp: NaiveDateTime,
+	original_size: i64,
+	compressed_size: Option<i64>,
+	logs_count: i64,
+	level: i32,
+}
+
 #[derive(Queryable, Debug)]
 #[diesel(table_name = log_buckets)]
 struct LogBucketRow {
@@ -267,6 +596,70 @@ struct NewBucketLogRecord {
 	props: String,
 }
 
+#[derive(Queryable, Debug)]
+#[diesel(table_name = retention_policies)]
+struct RetentionPolicyRow {
+	id: i32,
+	device_id: Option<String>,
+	prop_key: Option<String>,
+	prop_value: Option<String>,
+	max_age_seconds: Option<i64>,
+	max_total_bytes: Option<i64>,
+	enabled: bool,
+	created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = retention_policies)]
+struct NewRetentionPolicyRecord {
+	device_id: Option<String>,
+	prop_key: Option<String>,
+	prop_value: Option<String>,
+	max_age_seconds: Option<i64>,
+	max_total_bytes: Option<i64>,
+	enabled: bool,
+}
+
+/// A segment lifecycle rule: segments matching the scope (device and/or
+/// prop) are expired once they exceed `max_age_seconds` or once the total
+/// bytes they occupy exceeds `max_total_bytes`, whichever triggers first.
+/// Unlike `Settings::retention_policy`, which is a single global/per-device
+/// policy object, these are independently created and queried rows so a
+/// deployment can layer any number of scoped rules.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentRetentionPolicy {
+	pub id: i32,
+	pub device_id: Option<String>,
+	pub prop_key: Option<String>,
+	pub prop_value: Option<String>,
+	pub max_age_seconds: Option<u64>,
+	pub max_total_bytes: Option<u64>,
+	pub enabled: bool,
+	pub created_at: DateTime<Utc>,
+}
+
+pub struct NewRetentionPolicyArgs {
+	pub device_id: Option<String>,
+	pub prop_key: Option<String>,
+	pub prop_value: Option<String>,
+	pub max_age_seconds: Option<u64>,
+	pub max_total_bytes: Option<u64>,
+	pub enabled: bool,
+}
+
+/// A segment that `expire_segments` removed from the database. The caller
+/// is responsible for unlinking the on-disk bytes, mirroring how
+/// `retention::evict_segment` hands a deleted segment's id back to its
+/// caller rather than reaching into storage itself.
+#[derive(Debug, Clone)]
+pub struct ExpiredSegment {
+	pub id: u32,
+	pub device_id: Option<String>,
+	pub original_size: usize,
+	pub compressed_size: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BucketProp {
@@ -314,6 +707,17 @@ pub struct NewBucketLogEntry {
 const MAX_BUCKET_ENTRIES: usize = 200;
 pub const BUCKET_LOG_LIMIT: usize = MAX_BUCKET_ENTRIES;
 
+/// Result of a `poll_bucket` call: any entries appended after `cursor`
+/// (the caller's last-seen `bucket_logs.id`), plus the new high-water
+/// cursor to pass on the next call. `entries` is empty only when the poll
+/// timed out with nothing new, in which case `cursor` is unchanged.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketPoll {
+	pub entries: Vec<BucketLogEntry>,
+	pub cursor: i32,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Device {
@@ -323,6 +727,7 @@ pub struct Device {
 	pub send_interval: u32,
 	pub logs_size: usize,
 	pub logs_count: usize,
+	pub logs_compressed_size: Option<usize>,
 	pub created_at: DateTime<Utc>,
 	pub last_upload_at: Option<DateTime<Utc>>,
 	pub props: Vec<MetaProp>,
@@ -350,6 +755,28 @@ pub struct UpdateDeviceSettings {
 	pub send_interval: u32,
 }
 
+/// One page of `DB::find_segments_page`. `next_cursor` is opaque to callers
+/// — pass it back as `after` (or `before`, walking the other direction) to
+/// fetch the next page.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentPage {
+	pub segments: Vec<SegmentMeta>,
+	pub next_cursor: Option<String>,
+}
+
+/// Result of `DB::await_segments`: `entries` is empty iff the call timed
+/// out without a match. `cursor` is `(first_timestamp, id)` of the newest
+/// matching segment seen so far, or the caller's original `since_cursor`
+/// if nothing new landed — pass it back in as `since_cursor` on the next
+/// call to keep a live-tail subscription exactly-once across reconnects.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentPoll {
+	pub entries: Vec<SegmentMeta>,
+	pub cursor: Option<(DateTime<Utc>, u32)>,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SegmentsMetadata {
@@ -357,6 +784,127 @@ pub struct SegmentsMetadata {
 	pub original_size: u64,
 	pub compressed_size: u64,
 	pub logs_count: u64,
+	/// Total bytes actually stored in `chunks` (each distinct chunk counted
+	/// once), for segments written through `store_segment_chunks`. Always
+	/// `<= compressed_size`; the gap is cross-segment dedup savings.
+	/// Segments with no chunk rows (written before this feature, or via a
+	/// path that never called `store_segment_chunks`) don't contribute
+	/// here even though they count towards `compressed_size`.
+	pub deduplicated_size: u64,
+}
+
+pub struct DeviceMetrics {
+	pub device_id: String,
+	pub logs_size: u64,
+	pub logs_count: u64,
+	pub send_logs: bool,
+}
+
+/// Result of a `DB::repair_device_stats` pass. Each count reflects rows that
+/// were corrected/removed, or would be if `dry_run` is set.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceStatsRepairReport {
+	pub dry_run: bool,
+	pub devices_checked: u64,
+	pub devices_fixed: u64,
+	pub orphaned_segment_props_removed: u64,
+	pub orphaned_bucket_logs_removed: u64,
+}
+
+pub struct BucketMetrics {
+	pub bucket_id: i32,
+	pub name: String,
+	pub entry_count: u64,
+}
+
+/// Aggregate operational state returned by `DB::metrics_snapshot`, rendered
+/// to Prometheus text exposition format by `render`. `devices`/`buckets`
+/// carry per-entity labels and are only rendered when `per_entity_labels`
+/// is set, so a fleet with many devices or buckets doesn't blow up scrape
+/// cardinality by default.
+pub struct DbMetricsSnapshot {
+	pub devices: Vec<DeviceMetrics>,
+	pub devices_total: u64,
+	pub devices_sending: u64,
+	pub segment_count: u64,
+	pub segment_original_bytes: u64,
+	pub segment_compressed_bytes: u64,
+	pub oldest_segment_timestamp: Option<DateTime<Utc>>,
+	pub newest_segment_timestamp: Option<DateTime<Utc>>,
+	pub buckets: Vec<BucketMetrics>,
+}
+
+impl DbMetricsSnapshot {
+	pub fn render(&self, per_entity_labels: bool) -> String {
+		use std::fmt::Write as _;
+		let mut out = String::new();
+
+		let _ = writeln!(out, "# TYPE puppylog_devices_total gauge");
+		let _ = writeln!(out, "puppylog_devices_total {}", self.devices_total);
+		let _ = writeln!(out, "# TYPE puppylog_devices_sending gauge");
+		let _ = writeln!(out, "puppylog_devices_sending {}", self.devices_sending);
+
+		let _ = writeln!(out, "# TYPE puppylog_segment_count gauge");
+		let _ = writeln!(out, "puppylog_segment_count {}", self.segment_count);
+		let _ = writeln!(out, "# TYPE puppylog_segment_bytes_total gauge");
+		let _ = writeln!(
+			out,
+			"puppylog_segment_bytes_total {}",
+			self.segment_original_bytes
+		);
+		let _ = writeln!(out, "# TYPE puppylog_segment_compressed_bytes_total gauge");
+		let _ = writeln!(
+			out,
+			"puppylog_segment_compressed_bytes_total {}",
+			self.segment_compressed_bytes
+		);
+		if let Some(ts) = self.oldest_segment_timestamp {
+			let _ = writeln!(out, "# TYPE puppylog_oldest_segment_timestamp_seconds gauge");
+			let _ = writeln!(
+				out,
+				"puppylog_oldest_segment_timestamp_seconds {}",
+				ts.timestamp()
+			);
+		}
+		if let Some(ts) = self.newest_segment_timestamp {
+			let _ = writeln!(out, "# TYPE puppylog_newest_segment_timestamp_seconds gauge");
+			let _ = writeln!(
+				out,
+				"puppylog_newest_segment_timestamp_seconds {}",
+				ts.timestamp()
+			);
+		}
+
+		if per_entity_labels {
+			let _ = writeln!(out, "# TYPE puppylog_device_logs_size gauge");
+			for device in &self.devices {
+				let _ = writeln!(
+					out,
+					"puppylog_device_logs_size{{device=\"{}\"}} {}",
+					device.device_id, device.logs_size
+				);
+			}
+			let _ = writeln!(out, "# TYPE puppylog_device_logs_count gauge");
+			for device in &self.devices {
+				let _ = writeln!(
+					out,
+					"puppylog_device_logs_count{{device=\"{}\"}} {}",
+					device.device_id, device.logs_count
+				);
+			}
+			let _ = writeln!(out, "# TYPE puppylog_bucket_entries gauge");
+			for bucket in &self.buckets {
+				let _ = writeln!(
+					out,
+					"puppylog_bucket_entries{{bucket=\"{}\"}} {}",
+					bucket.name, bucket.entry_count
+				);
+			}
+		}
+
+		out
+	}
 }
 
 pub struct NewSegmentArgs {
@@ -368,10 +916,36 @@ pub struct NewSegmentArgs {
 	pub logs_count: u64,
 }
 
+/// Like `NewSegmentArgs`, but for segments produced by the leveled compactor,
+/// which need to stamp the merged output at a specific level rather than the
+/// default of 0.
+pub struct NewLeveledSegmentArgs {
+	pub device_id: Option<String>,
+	pub level: u32,
+	pub first_timestamp: chrono::DateTime<chrono::Utc>,
+	pub last_timestamp: chrono::DateTime<chrono::Utc>,
+	pub original_size: usize,
+	pub compressed_size: usize,
+	pub logs_count: u64,
+}
+
 #[derive(Debug)]
 pub struct DB {
 	write_pool: DbPool,
 	read_pool: DbPool,
+	/// One broadcast channel per bucket that has ever been polled, used to
+	/// wake a blocked `poll_bucket` call as soon as `append_bucket_logs` or
+	/// `clear_bucket_logs` commits a change, instead of making clients poll
+	/// on a timer. Lazily populated; channels for buckets nobody is
+	/// long-polling are never created.
+	bucket_notifiers: Mutex<HashMap<i32, broadcast::Sender<()>>>,
+	/// Broadcasts every `SegmentMeta` inserted by `new_segment`/
+	/// `new_segment_at_level`, so `await_segments` can long-poll for new
+	/// segments instead of busy-polling `find_segments`. Unlike
+	/// `bucket_notifiers` there's only one channel: segments aren't
+	/// partitioned by subscriber, so `await_segments` filters the stream
+	/// itself instead of the sender picking a channel per query.
+	segment_notifier: broadcast::Sender<SegmentMeta>,
 }
 
 impl DB {
@@ -389,9 +963,27 @@ impl DB {
 		DB {
 			write_pool,
 			read_pool,
+			bucket_notifiers: Mutex::new(HashMap::new()),
+			segment_notifier: broadcast::channel(256).0,
 		}
 	}
 
+	fn notify_bucket(&self, bucket_id: i32) {
+		let mut notifiers = self.bucket_notifiers.lock().unwrap();
+		let tx = notifiers
+			.entry(bucket_id)
+			.or_insert_with(|| broadcast::channel(16).0);
+		let _ = tx.send(());
+	}
+
+	fn bucket_notifier(&self, bucket_id: i32) -> broadcast::Sender<()> {
+		let mut notifiers = self.bucket_notifiers.lock().unwrap();
+		notifiers
+			.entry(bucket_id)
+			.or_insert_with(|| broadcast::channel(16).0)
+			.clone()
+	}
+
 	fn conn(&self) -> Result<DbConn> {
 		self.write_pool
 			.get()
@@ -478,6 +1070,54 @@ impl DB {
 		Ok(Some(Self::assemble_bucket(row, logs)))
 	}
 
+	/// Rows appended to `bucket_id` after `since_cursor` (`bucket_logs.id`,
+	/// which only ever increases), oldest first, plus the new high-water
+	/// cursor — `since_cursor` itself if nothing matched.
+	fn fetch_bucket_logs_since(
+		&self,
+		bucket_id: i32,
+		since_cursor: i32,
+	) -> Result<(Vec<BucketLogEntry>, i32)> {
+		let mut conn = self.read_conn()?;
+		let rows: Vec<BucketLogRow> = bucket_logs::table
+			.filter(bucket_logs::bucket_id.eq(bucket_id))
+			.filter(bucket_logs::id.gt(since_cursor))
+			.order(bucket_logs::id.asc())
+			.load(&mut conn)?;
+		let cursor = rows.last().map(|row| row.id).unwrap_or(since_cursor);
+		let entries = rows.iter().filter_map(Self::decode_bucket_log).collect();
+		Ok((entries, cursor))
+	}
+
+	/// Returns immediately with anything appended to `bucket_id` after
+	/// `since_cursor`; if there's nothing yet, awaits the bucket's
+	/// append/clear notification up to `timeout` and re-checks once, so a
+	/// UI can long-poll for a live tail instead of re-fetching on a timer.
+	/// Times out to an empty result with `cursor` unchanged, never an error.
+	pub async fn poll_bucket(
+		&self,
+		bucket_id: i32,
+		since_cursor: i32,
+		timeout: Duration,
+	) -> Result<BucketPoll> {
+		let (entries, cursor) = self.fetch_bucket_logs_since(bucket_id, since_cursor)?;
+		if !entries.is_empty() {
+			return Ok(BucketPoll { entries, cursor });
+		}
+
+		let mut rx = self.bucket_notifier(bucket_id).subscribe();
+		match tokio::time::timeout(timeout, rx.recv()).await {
+			Ok(_) => {
+				let (entries, cursor) = self.fetch_bucket_logs_since(bucket_id, since_cursor)?;
+				Ok(BucketPoll { entries, cursor })
+			}
+			Err(_) => Ok(BucketPoll {
+				entries: Vec::new(),
+				cursor: since_cursor,
+			}),
+		}
+	}
+
 	pub async fn upsert_bucket(&self, args: UpsertBucketArgs) -> Result<LogBucket> {
 		let UpsertBucketArgs { id, name, query } = args;
 		let bucket_id = {
@@ -587,6 +1227,9 @@ impl DB {
 				Ok(Some(id))
 			})?
 		};
+		if let Some(id) = maybe_bucket {
+			self.notify_bucket(id);
+		}
 		match maybe_bucket {
 			Some(id) => self.get_bucket(id).await,
 			None => Ok(None),
@@ -613,6 +1256,9 @@ impl DB {
 				Ok(Some(id))
 			})?
 		};
+		if let Some(id) = maybe_bucket {
+			self.notify_bucket(id);
+		}
 		match maybe_bucket {
 			Some(id) => self.get_bucket(id).await,
 			None => Ok(None),
@@ -637,24 +1283,187 @@ impl DB {
 		device_id: &str,
 		logs_size: usize,
 		logs_count: usize,
+		compressed_size: Option<usize>,
 	) -> Result<()> {
 		let mut conn = self.conn()?;
 		diesel::sql_query(
-			"INSERT INTO devices (id, logs_size, logs_count, last_upload_at) \
-                         VALUES (?1, ?2, ?3, current_timestamp) \
+			"INSERT INTO devices (id, logs_size, logs_count, logs_compressed_size, last_upload_at) \
+                         VALUES (?1, ?2, ?3, ?4, current_timestamp) \
                          ON CONFLICT(id) DO UPDATE SET \
                                 logs_size = devices.logs_size + ?2, \
                                 logs_count = devices.logs_count + ?3, \
+                                logs_compressed_size = COALESCE(devices.logs_compressed_size, 0) + COALESCE(?4, ?2), \
                                 last_upload_at = current_timestamp",
 		)
 		.bind::<Text, _>(device_id)
 		.bind::<BigInt, _>(logs_size as i64)
 		.bind::<BigInt, _>(logs_count as i64)
+		.bind::<Nullable<BigInt>, _>(compressed_size.map(|v| v as i64))
 		.execute(&mut conn)
 		.context("failed to update device stats")?;
 		Ok(())
 	}
 
+	pub async fn create_retention_policy(
+		&self,
+		args: NewRetentionPolicyArgs,
+	) -> Result<SegmentRetentionPolicy> {
+		let mut conn = self.conn()?;
+		let NewRetentionPolicyArgs {
+			device_id,
+			prop_key,
+			prop_value,
+			max_age_seconds,
+			max_total_bytes,
+			enabled,
+		} = args;
+		let record = NewRetentionPolicyRecord {
+			device_id,
+			prop_key,
+			prop_value,
+			max_age_seconds: max_age_seconds.map(|v| v as i64),
+			max_total_bytes: max_total_bytes.map(|v| v as i64),
+			enabled,
+		};
+		let row: RetentionPolicyRow = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+			insert_into(retention_policies::table)
+				.values(&record)
+				.execute(conn)?;
+			let id: LastInsertRow =
+				diesel::sql_query("SELECT last_insert_rowid() as id").get_result(conn)?;
+			retention_policies::table
+				.filter(retention_policies::id.eq(id.id as i32))
+				.first(conn)
+		})?;
+		Ok(Self::decode_retention_policy(row))
+	}
+
+	pub async fn list_retention_policies(&self) -> Result<Vec<SegmentRetentionPolicy>> {
+		let mut conn = self.read_conn()?;
+		let rows: Vec<RetentionPolicyRow> = retention_policies::table.load(&mut conn)?;
+		Ok(rows.into_iter().map(Self::decode_retention_policy).collect())
+	}
+
+	fn decode_retention_policy(row: RetentionPolicyRow) -> SegmentRetentionPolicy {
+		SegmentRetentionPolicy {
+			id: row.id,
+			device_id: row.device_id,
+			prop_key: row.prop_key,
+			prop_value: row.prop_value,
+			max_age_seconds: row.max_age_seconds.map(|v| v as u64),
+			max_total_bytes: row.max_total_bytes.map(|v| v as u64),
+			enabled: row.enabled,
+			created_at: naive_to_utc(row.created_at),
+		}
+	}
+
+	/// Applies every enabled `SegmentRetentionPolicy` against the segments it
+	/// scopes to, deleting violators (age first, then oldest-first over the
+	/// byte quota) and decrementing the owning device's stats to match.
+	/// Returns the deleted segments so the caller can unlink their on-disk
+	/// bytes, the same split of responsibility `retention::evict_segment`
+	/// uses for the global policy.
+	pub async fn expire_segments(&self) -> Result<Vec<ExpiredSegment>> {
+		let policies = self.list_retention_policies().await?;
+		let mut expired_ids: HashSet<u32> = HashSet::new();
+		let mut expired = Vec::new();
+		let now = Utc::now();
+
+		for policy in policies.iter().filter(|p| p.enabled) {
+			let mut conn = self.read_conn()?;
+			let mut q = log_segments::table
+				.filter(log_segments::quarantined.eq(false))
+				.filter(log_segments::pinned.eq(false))
+				.into_boxed();
+			if let Some(device_id) = &policy.device_id {
+				q = q.filter(log_segments::device_id.eq(device_id));
+			}
+			if let (Some(key), Some(value)) = (&policy.prop_key, &policy.prop_value) {
+				q = q.filter(exists(
+					segment_props::table
+						.filter(segment_props::segment_id.eq(log_segments::id))
+						.filter(segment_props::key.eq(key))
+						.filter(segment_props::value.eq(value)),
+				));
+			}
+			let rows: Vec<SegmentRow> = q.order(log_segments::first_timestamp.asc()).load(&mut conn)?;
+			drop(conn);
+
+			let mut remaining_bytes: u64 = 0;
+			let mut candidates = Vec::with_capacity(rows.len());
+			for row in rows {
+				if expired_ids.contains(&(row.id as u32)) {
+					continue;
+				}
+				let age_secs = (now - naive_to_utc(row.first_timestamp)).num_seconds().max(0) as u64;
+				let size = row.compressed_size.unwrap_or(row.original_size) as u64;
+				let violates_age = policy
+					.max_age_seconds
+					.map(|max| age_secs > max)
+					.unwrap_or(false);
+				if violates_age {
+					expired_ids.insert(row.id as u32);
+					expired.push(ExpiredSegment {
+						id: row.id as u32,
+						device_id: row.device_id.clone(),
+						original_size: row.original_size as usize,
+						compressed_size: row.compressed_size.unwrap_or(0) as usize,
+					});
+					continue;
+				}
+				remaining_bytes += size;
+				candidates.push((row, size));
+			}
+
+			if let Some(max_total_bytes) = policy.max_total_bytes {
+				for (row, size) in candidates {
+					if remaining_bytes <= max_total_bytes {
+						break;
+					}
+					remaining_bytes = remaining_bytes.saturating_sub(size);
+					expired_ids.insert(row.id as u32);
+					expired.push(ExpiredSegment {
+						id: row.id as u32,
+						device_id: row.device_id.clone(),
+						original_size: row.original_size as usize,
+						compressed_size: row.compressed_size.unwrap_or(0) as usize,
+					});
+				}
+			}
+		}
+
+		for segment in &expired {
+			self.delete_expired_segment(segment).await?;
+		}
+
+		Ok(expired)
+	}
+
+	async fn delete_expired_segment(&self, segment: &ExpiredSegment) -> Result<()> {
+		let mut conn = self.conn()?;
+		conn.transaction::<_, diesel::result::Error, _>(|conn| {
+			diesel::delete(
+				segment_props::table.filter(segment_props::segment_id.eq(segment.id as i32)),
+			)
+			.execute(conn)?;
+			diesel::delete(log_segments::table.filter(log_segments::id.eq(segment.id as i32)))
+				.execute(conn)?;
+			if let Some(device_id) = &segment.device_id {
+				diesel::sql_query(
+					"UPDATE devices SET \
+                                                logs_size = MAX(logs_size - ?2, 0), \
+                                                logs_count = MAX(logs_count - 1, 0) \
+                                             WHERE id = ?1",
+				)
+				.bind::<Text, _>(device_id)
+				.bind::<BigInt, _>(segment.original_size as i64)
+				.execute(conn)?;
+			}
+			Ok(())
+		})?;
+		Ok(())
+	}
+
 	pub async fn get_devices(&self) -> Result<Vec<Device>> {
 		let mut conn = self.read_conn()?;
 		let rows: Vec<DeviceRow> = devices::table.load(&mut conn)?;
@@ -668,6 +1477,7 @@ impl DB {
 				send_interval: row.send_interval as u32,
 				logs_size: row.logs_size as usize,
 				logs_count: row.logs_count as usize,
+				logs_compressed_size: row.logs_compressed_size.map(|v| v as usize),
 				created_at: naive_to_utc(row.created_at),
 				last_upload_at: opt_naive_to_utc(row.last_upload_at),
 				props,
@@ -692,6 +1502,7 @@ impl DB {
 					send_interval: row.send_interval as u32,
 					logs_size: row.logs_size as usize,
 					logs_count: row.logs_count as usize,
+					logs_compressed_size: row.logs_compressed_size.map(|v| v as usize),
 					created_at: naive_to_utc(row.created_at),
 					last_upload_at: opt_naive_to_utc(row.last_upload_at),
 					props,
@@ -727,6 +1538,7 @@ impl DB {
 			send_interval: row.send_interval as u32,
 			logs_size: row.logs_size as usize,
 			logs_count: row.logs_count as usize,
+			logs_compressed_size: row.logs_compressed_size.map(|v| v as usize),
 			created_at: naive_to_utc(row.created_at),
 			last_upload_at: opt_naive_to_utc(row.last_upload_at),
 			props,
@@ -829,29 +1641,493 @@ impl DB {
 		} = args;
 		let record = NewSegmentRecord {
 			bucket_id: None,
+			device_id: device_id.clone(),
+			first_timestamp: first_timestamp.naive_utc(),
+			last_timestamp: last_timestamp.naive_utc(),
+			original_size: original_size as i64,
+			compressed_size: Some(compressed_size as i64),
+			logs_count: logs_count as i64,
+		};
+
+		let id = conn
+			.transaction::<_, diesel::result::Error, _>(|conn| {
+				insert_into(log_segments::table)
+					.values(&record)
+					.execute(conn)?;
+				let row: LastInsertRow =
+					diesel::sql_query("SELECT last_insert_rowid() as id").get_result(conn)?;
+				Ok(row.id)
+			})
+			.map(|id| id as u32)?;
+
+		let _ = self.segment_notifier.send(SegmentMeta {
+			id,
+			device_id,
+			first_timestamp,
+			last_timestamp,
+			original_size,
+			compressed_size,
+			logs_count,
+			created_at: Utc::now(),
+			level: 0,
+			bloom: None,
+			checksum: None,
+			quarantined: false,
+			encrypted: false,
+			last_accessed: None,
+			pinned: false,
+			data_dir: None,
+			compressed: true,
+			last_scrubbed: None,
+		});
+		Ok(id)
+	}
+
+	/// Like `new_segment`, but inserts at a caller-chosen `id` instead of
+	/// letting SQLite assign the next rowid — for `DeviceMerger` reusing an
+	/// id `SegmentSlotAllocator::take` just handed back, so the replacement
+	/// segment reoccupies the same `{id}.log` path instead of mandating a
+	/// fresh file. `log_segments.id` has no `AUTOINCREMENT`, so an explicit,
+	/// previously-deleted id is just an ordinary row insert; it's an error
+	/// (not silently overwritten) if `id` happens to still be occupied.
+	pub async fn new_segment_with_id(&self, id: u32, args: NewSegmentArgs) -> Result<u32> {
+		let mut conn = self.conn()?;
+		let NewSegmentArgs {
+			device_id,
+			first_timestamp,
+			last_timestamp,
+			original_size,
+			compressed_size,
+			logs_count,
+		} = args;
+		let record = NewSegmentWithIdRecord {
+			id: id as i32,
+			bucket_id: None,
+			device_id: device_id.clone(),
+			first_timestamp: first_timestamp.naive_utc(),
+			last_timestamp: last_timestamp.naive_utc(),
+			original_size: original_size as i64,
+			compressed_size: Some(compressed_size as i64),
+			logs_count: logs_count as i64,
+		};
+
+		conn.transaction::<_, diesel::result::Error, _>(|conn| {
+			insert_into(log_segments::table).values(&record).execute(conn)?;
+			Ok(())
+		})?;
+
+		let _ = self.segment_notifier.send(SegmentMeta {
+			id,
 			device_id,
+			first_timestamp,
+			last_timestamp,
+			original_size,
+			compressed_size,
+			logs_count,
+			created_at: Utc::now(),
+			level: 0,
+			bloom: None,
+			checksum: None,
+			quarantined: false,
+			encrypted: false,
+			last_accessed: None,
+			pinned: false,
+			data_dir: None,
+			compressed: true,
+			last_scrubbed: None,
+		});
+		Ok(id)
+	}
+
+	pub async fn new_segment_at_level(&self, args: NewLeveledSegmentArgs) -> Result<u32> {
+		let mut conn = self.conn()?;
+		let NewLeveledSegmentArgs {
+			device_id,
+			level,
+			first_timestamp,
+			last_timestamp,
+			original_size,
+			compressed_size,
+			logs_count,
+		} = args;
+		let record = NewLeveledSegmentRecord {
+			bucket_id: None,
+			device_id: device_id.clone(),
 			first_timestamp: first_timestamp.naive_utc(),
 			last_timestamp: last_timestamp.naive_utc(),
 			original_size: original_size as i64,
 			compressed_size: Some(compressed_size as i64),
 			logs_count: logs_count as i64,
+			level: level as i32,
 		};
 
+		let id = conn
+			.transaction::<_, diesel::result::Error, _>(|conn| {
+				insert_into(log_segments::table)
+					.values(&record)
+					.execute(conn)?;
+				let row: LastInsertRow =
+					diesel::sql_query("SELECT last_insert_rowid() as id").get_result(conn)?;
+				Ok(row.id)
+			})
+			.map(|id| id as u32)?;
+
+		let _ = self.segment_notifier.send(SegmentMeta {
+			id,
+			device_id,
+			first_timestamp,
+			last_timestamp,
+			original_size,
+			compressed_size,
+			logs_count,
+			created_at: Utc::now(),
+			level,
+			bloom: None,
+			checksum: None,
+			quarantined: false,
+			encrypted: false,
+			last_accessed: None,
+			pinned: false,
+			data_dir: None,
+			compressed: true,
+			last_scrubbed: None,
+		});
+		Ok(id)
+	}
+
+	/// Splits `bytes` (the segment's compressed, possibly encrypted, on-disk
+	/// body) into content-defined chunks via `cdc::cut_chunks` and records
+	/// them for `segment_id`: each distinct chunk is `insert_or_ignore`d into
+	/// `chunks` keyed by `cdc::content_hash`, its `refcount` bumped, and
+	/// `segment_chunks` gets the ordered `(segment_id, seq) -> chunk_hash`
+	/// mapping `read_segment_chunks` reassembles from. Like
+	/// `set_segment_bloom`/`set_segment_checksum`, this is a focused
+	/// follow-up write after `new_segment` already inserted the segment row
+	/// — the compressed bytes aren't known until the caller has picked
+	/// compression/encryption for them — but the chunk inserts and refcount
+	/// bumps themselves are one transaction, so a crash mid-call can never
+	/// leave a chunk's refcount out of sync with `segment_chunks`.
+	pub async fn store_segment_chunks(
+		&self,
+		segment_id: u32,
+		bytes: &[u8],
+		config: &crate::cdc::ChunkingConfig,
+	) -> Result<()> {
+		if !config.enabled {
+			return Ok(());
+		}
+		let pieces = crate::cdc::cut_chunks(bytes, config);
+		let mut conn = self.conn()?;
 		conn.transaction::<_, diesel::result::Error, _>(|conn| {
-			insert_into(log_segments::table)
-				.values(&record)
-				.execute(conn)?;
-			let row: LastInsertRow =
-				diesel::sql_query("SELECT last_insert_rowid() as id").get_result(conn)?;
-			Ok(row.id)
+			for (seq, piece) in pieces.into_iter().enumerate() {
+				let hash = crate::cdc::content_hash(piece);
+				insert_or_ignore_into(chunks::table)
+					.values((
+						chunks::hash.eq(&hash),
+						chunks::blob.eq(piece),
+						chunks::refcount.eq(0),
+					))
+					.execute(conn)?;
+				diesel::update(chunks::table.filter(chunks::hash.eq(&hash)))
+					.set(chunks::refcount.eq(chunks::refcount + 1))
+					.execute(conn)?;
+				insert_into(segment_chunks::table)
+					.values((
+						segment_chunks::segment_id.eq(segment_id as i32),
+						segment_chunks::seq.eq(seq as i32),
+						segment_chunks::chunk_hash.eq(&hash),
+					))
+					.execute(conn)?;
+			}
+			Ok(())
+		})?;
+		Ok(())
+	}
+
+	/// Reassembles a segment's bytes from its `segment_chunks`/`chunks` rows
+	/// in `seq` order, or `None` if it has none — a segment written before
+	/// this feature, or by a caller that never called
+	/// `store_segment_chunks` and relies solely on the `SegmentStore` blob.
+	pub async fn read_segment_chunks(&self, segment_id: u32) -> Result<Option<Vec<u8>>> {
+		let mut conn = self.read_conn()?;
+		let rows: Vec<Vec<u8>> = segment_chunks::table
+			.inner_join(chunks::table.on(segment_chunks::chunk_hash.eq(chunks::hash)))
+			.filter(segment_chunks::segment_id.eq(segment_id as i32))
+			.order(segment_chunks::seq.asc())
+			.select(chunks::blob)
+			.load(&mut conn)?;
+		if rows.is_empty() {
+			return Ok(None);
+		}
+		let mut bytes = Vec::with_capacity(rows.iter().map(Vec::len).sum());
+		for chunk in rows {
+			bytes.extend_from_slice(&chunk);
+		}
+		Ok(Some(bytes))
+	}
+
+	/// Keyset-paginated variant of `find_segments`, for callers walking a
+	/// large result set page by page (the `/api/segments`-style HTTP
+	/// handlers). Instead of SQL `OFFSET` — which scans and discards every
+	/// row before it — the next page filters on `(first_timestamp, id) >
+	/// (cursor_ts, cursor_id)` (flipped to `<` for `reverse`), so paging
+	/// stays roughly O(page) at any depth and stays stable even as new
+	/// segments are inserted mid-scan. `query.count` caps the page size
+	/// (default 100). `next_cursor` is `None` once a page comes back
+	/// shorter than the requested size, meaning there's nothing left.
+	pub async fn find_segments_page(&self, query: &GetSegmentsQuery) -> Result<SegmentPage> {
+		let mut conn = self.read_conn()?;
+		let reverse = query.reverse.unwrap_or(false);
+		let limit = query.count.unwrap_or(100) as i64;
+
+		let mut q = log_segments::table
+			.filter(log_segments::quarantined.eq(false))
+			.into_boxed();
+
+		if let Some(start) = &query.start {
+			q = q.filter(log_segments::last_timestamp.gt(start.naive_utc()));
+		}
+		if let Some(end) = &query.end {
+			q = q.filter(log_segments::first_timestamp.le(end.naive_utc()));
+		}
+		if let Some(ids) = &query.device_ids {
+			if ids.is_empty() {
+				return Ok(SegmentPage {
+					segments: Vec::new(),
+					next_cursor: None,
+				});
+			}
+			let filter_ids: Vec<Option<String>> = ids.iter().cloned().map(Some).collect();
+			q = q.filter(log_segments::device_id.eq_any(filter_ids));
+		}
+		if let Some(level) = query.level {
+			q = q.filter(log_segments::level.eq(level as i32));
+		}
+
+		// (first_timestamp, id) > (cursor_ts, cursor_id), expanded by hand
+		// since diesel has no tuple comparison: either the timestamp is
+		// already past the cursor, or it's tied and the id breaks the tie.
+		// `reverse` flips both `>` to `<`.
+		if let Some(cursor) = &query.after {
+			let (ts, id) = decode_segment_cursor(cursor)?;
+			q = q.filter(if reverse {
+				log_segments::first_timestamp
+					.lt(ts)
+					.or(log_segments::first_timestamp
+						.eq(ts)
+						.and(log_segments::id.lt(id)))
+			} else {
+				log_segments::first_timestamp
+					.gt(ts)
+					.or(log_segments::first_timestamp
+						.eq(ts)
+						.and(log_segments::id.gt(id)))
+			});
+		}
+		if let Some(cursor) = &query.before {
+			let (ts, id) = decode_segment_cursor(cursor)?;
+			q = q.filter(if reverse {
+				log_segments::first_timestamp
+					.gt(ts)
+					.or(log_segments::first_timestamp
+						.eq(ts)
+						.and(log_segments::id.gt(id)))
+			} else {
+				log_segments::first_timestamp
+					.lt(ts)
+					.or(log_segments::first_timestamp
+						.eq(ts)
+						.and(log_segments::id.lt(id)))
+			});
+		}
+
+		q = if reverse {
+			q.order((log_segments::first_timestamp.desc(), log_segments::id.desc()))
+		} else {
+			q.order((log_segments::first_timestamp.asc(), log_segments::id.asc()))
+		};
+		q = q.limit(limit);
+
+		let rows: Vec<SegmentRow> = q.load(&mut conn)?;
+		let next_cursor = if rows.len() as i64 == limit {
+			rows.last()
+				.map(|row| encode_segment_cursor(naive_to_utc(row.first_timestamp), row.id))
+		} else {
+			None
+		};
+		let segments = rows
+			.into_iter()
+			.map(|row| SegmentMeta {
+				id: row.id as u32,
+				device_id: row.device_id,
+				first_timestamp: naive_to_utc(row.first_timestamp),
+				last_timestamp: naive_to_utc(row.last_timestamp),
+				original_size: row.original_size as usize,
+				compressed_size: row.compressed_size.unwrap_or(0) as usize,
+				logs_count: row.logs_count as u64,
+				created_at: naive_to_utc(row.created_at),
+				level: row.level as u32,
+				bloom: row.bloom,
+				checksum: row.checksum.map(|c| c as u64),
+				quarantined: row.quarantined,
+				encrypted: row.encrypted,
+				last_accessed: opt_naive_to_utc(row.last_accessed),
+				pinned: row.pinned,
+				data_dir: row.data_dir.clone(),
+				compressed: row.compressed,
+				last_scrubbed: opt_naive_to_utc(row.last_scrubbed),
+			})
+			.collect();
+		Ok(SegmentPage {
+			segments,
+			next_cursor,
 		})
-		.map(|id| id as u32)
-		.map_err(Into::into)
 	}
 
-	pub async fn find_segments(&self, query: &GetSegmentsQuery) -> Result<Vec<SegmentMeta>> {
+	pub async fn find_segments(&self, query: &GetSegmentsQuery) -> Result<Vec<SegmentMeta>> {
+		let mut conn = self.read_conn()?;
+		// Quarantined segments failed a scrub checksum verification; never
+		// hand them back to a query, so a single corrupt file can't poison
+		// or abort a scan.
+		let mut q = log_segments::table
+			.filter(log_segments::quarantined.eq(false))
+			.into_boxed();
+
+		if let Some(start) = &query.start {
+			q = q.filter(log_segments::last_timestamp.gt(start.naive_utc()));
+		}
+		if let Some(end) = &query.end {
+			q = q.filter(log_segments::first_timestamp.le(end.naive_utc()));
+		}
+		if let Some(ids) = &query.device_ids {
+			if ids.is_empty() {
+				return Ok(Vec::new());
+			}
+			let filter_ids: Vec<Option<String>> = ids.iter().cloned().map(Some).collect();
+			q = q.filter(log_segments::device_id.eq_any(filter_ids));
+		}
+		if let Some(level) = query.level {
+			q = q.filter(log_segments::level.eq(level as i32));
+		}
+
+		q = match query.sort {
+			Some(SortDir::Asc) => q.order(log_segments::first_timestamp.asc()),
+			Some(SortDir::Desc) => q.order(log_segments::first_timestamp.desc()),
+			Some(SortDir::LastAccessedAsc) => q.order(log_segments::last_accessed.asc()),
+			None => q.order(log_segments::id.asc()),
+		};
+
+		if let Some(count) = query.count {
+			q = q.limit(count as i64);
+		}
+
+		let rows: Vec<SegmentRow> = q.load(&mut conn)?;
+		Ok(rows
+			.into_iter()
+			.map(|row| SegmentMeta {
+				id: row.id as u32,
+				device_id: row.device_id,
+				first_timestamp: naive_to_utc(row.first_timestamp),
+				last_timestamp: naive_to_utc(row.last_timestamp),
+				original_size: row.original_size as usize,
+				compressed_size: row.compressed_size.unwrap_or(0) as usize,
+				logs_count: row.logs_count as u64,
+				created_at: naive_to_utc(row.created_at),
+				level: row.level as u32,
+				bloom: row.bloom,
+				checksum: row.checksum.map(|c| c as u64),
+				quarantined: row.quarantined,
+				encrypted: row.encrypted,
+				last_accessed: opt_naive_to_utc(row.last_accessed),
+				pinned: row.pinned,
+				data_dir: row.data_dir.clone(),
+				compressed: row.compressed,
+				last_scrubbed: opt_naive_to_utc(row.last_scrubbed),
+			})
+			.collect())
+	}
+
+	/// Long-polls for a segment matching `query` inserted after
+	/// `since_cursor`, modeled on K2V's poll/watch endpoint and on this
+	/// module's own `poll_bucket`. First runs `find_segments` to pick up
+	/// anything that landed just before the caller subscribed (closing the
+	/// race between "checked, nothing new" and "started watching"); if that
+	/// comes back empty, watches `segment_notifier` - filtered by `query`'s
+	/// device/time/level - until a match arrives or `timeout` elapses.
+	/// `SegmentPoll::cursor` is `(first_timestamp, id)` of the newest match,
+	/// so a reconnecting caller passes it back as `since_cursor` to resume
+	/// exactly where it left off without missing or repeating a segment.
+	pub async fn await_segments(
+		&self,
+		query: &GetSegmentsQuery,
+		since_cursor: Option<(DateTime<Utc>, u32)>,
+		timeout: Duration,
+	) -> Result<SegmentPoll> {
+		let past_cursor = |meta: &SegmentMeta| match since_cursor {
+			Some((ts, id)) => (meta.first_timestamp, meta.id) > (ts, id),
+			None => true,
+		};
+		let newest_cursor = |entries: &[SegmentMeta]| {
+			entries
+				.iter()
+				.map(|m| (m.first_timestamp, m.id))
+				.max()
+				.or(since_cursor)
+		};
+
+		let initial: Vec<SegmentMeta> = self
+			.find_segments(query)
+			.await?
+			.into_iter()
+			.filter(past_cursor)
+			.collect();
+		if !initial.is_empty() {
+			let cursor = newest_cursor(&initial);
+			return Ok(SegmentPoll {
+				entries: initial,
+				cursor,
+			});
+		}
+
+		let mut rx = self.segment_notifier.subscribe();
+		let deadline = tokio::time::Instant::now() + timeout;
+		let entries = loop {
+			let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+			if remaining.is_zero() {
+				break Vec::new();
+			}
+			match tokio::time::timeout(remaining, rx.recv()).await {
+				Ok(Ok(meta)) if past_cursor(&meta) && segment_matches_query(&meta, query) => {
+					break vec![meta]
+				}
+				Ok(Ok(_)) => continue,
+				Ok(Err(broadcast::error::RecvError::Lagged(_))) => continue,
+				Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => break Vec::new(),
+			}
+		};
+		let cursor = newest_cursor(&entries);
+		Ok(SegmentPoll { entries, cursor })
+	}
+
+	/// Like `find_segments`, but additionally restricted to segments
+	/// matching every entry of `prop_filters` (AND across filters, OR across
+	/// a single filter's `values`), via one correlated `EXISTS` against
+	/// `segment_props` per filter. `segment_props_key_value_segment_id_idx`
+	/// keeps each `EXISTS` an index-only lookup, so segments that don't
+	/// match are pruned by the database instead of being loaded and
+	/// decompressed — the main cost for wide prop queries. Not yet wired
+	/// into the AST-driven bloom-filter search engine in `search.rs`; this
+	/// is the building block for exact key/value pushdown, used directly by
+	/// callers that just want "segments with these props".
+	pub async fn find_segments_by_props(
+		&self,
+		query: &GetSegmentsQuery,
+		prop_filters: &[PropFilter],
+	) -> Result<Vec<SegmentMeta>> {
 		let mut conn = self.read_conn()?;
-		let mut q = log_segments::table.into_boxed();
+		let mut q = log_segments::table
+			.filter(log_segments::quarantined.eq(false))
+			.into_boxed();
 
 		if let Some(start) = &query.start {
 			q = q.filter(log_segments::last_timestamp.gt(start.naive_utc()));
@@ -866,13 +2142,27 @@ impl DB {
 			let filter_ids: Vec<Option<String>> = ids.iter().cloned().map(Some).collect();
 			q = q.filter(log_segments::device_id.eq_any(filter_ids));
 		}
+		if let Some(level) = query.level {
+			q = q.filter(log_segments::level.eq(level as i32));
+		}
+		for filter in prop_filters {
+			if filter.values.is_empty() {
+				return Ok(Vec::new());
+			}
+			q = q.filter(exists(
+				segment_props::table
+					.filter(segment_props::segment_id.eq(log_segments::id))
+					.filter(segment_props::key.eq(filter.key.clone()))
+					.filter(segment_props::value.eq_any(filter.values.clone())),
+			));
+		}
 
 		q = match query.sort {
 			Some(SortDir::Asc) => q.order(log_segments::first_timestamp.asc()),
 			Some(SortDir::Desc) => q.order(log_segments::first_timestamp.desc()),
+			Some(SortDir::LastAccessedAsc) => q.order(log_segments::last_accessed.asc()),
 			None => q.order(log_segments::id.asc()),
 		};
-
 		if let Some(count) = query.count {
 			q = q.limit(count as i64);
 		}
@@ -889,6 +2179,16 @@ impl DB {
 				compressed_size: row.compressed_size.unwrap_or(0) as usize,
 				logs_count: row.logs_count as u64,
 				created_at: naive_to_utc(row.created_at),
+				level: row.level as u32,
+				bloom: row.bloom,
+				checksum: row.checksum.map(|c| c as u64),
+				quarantined: row.quarantined,
+				encrypted: row.encrypted,
+				last_accessed: opt_naive_to_utc(row.last_accessed),
+				pinned: row.pinned,
+				data_dir: row.data_dir.clone(),
+				compressed: row.compressed,
+				last_scrubbed: opt_naive_to_utc(row.last_scrubbed),
 			})
 			.collect())
 	}
@@ -955,9 +2255,105 @@ impl DB {
 			compressed_size: row.compressed_size.unwrap_or(0) as usize,
 			logs_count: row.logs_count as u64,
 			created_at: naive_to_utc(row.created_at),
+			level: row.level as u32,
+			bloom: row.bloom,
+			checksum: row.checksum.map(|c| c as u64),
+			quarantined: row.quarantined,
+			encrypted: row.encrypted,
+			last_accessed: opt_naive_to_utc(row.last_accessed),
+			pinned: row.pinned,
+			data_dir: row.data_dir.clone(),
+			compressed: row.compressed,
+			last_scrubbed: opt_naive_to_utc(row.last_scrubbed),
 		})
 	}
 
+	/// Segments whose `last_timestamp` is older than `older_than`, oldest
+	/// first. Skips quarantined, pinned, and bucket-assigned segments — the
+	/// same protections `find_segments`/`select_evictions` already apply —
+	/// so a sweep built on this never touches a segment a named bucket still
+	/// overlaps.
+	pub async fn find_segments_older_than(
+		&self,
+		older_than: DateTime<Utc>,
+		limit: usize,
+	) -> Result<Vec<SegmentMeta>> {
+		let mut conn = self.read_conn()?;
+		let rows: Vec<SegmentRow> = log_segments::table
+			.filter(log_segments::quarantined.eq(false))
+			.filter(log_segments::pinned.eq(false))
+			.filter(log_segments::bucket_id.is_null())
+			.filter(log_segments::last_timestamp.lt(older_than.naive_utc()))
+			.order(log_segments::first_timestamp.asc())
+			.limit(limit as i64)
+			.load(&mut conn)?;
+		Ok(rows
+			.into_iter()
+			.map(|row| SegmentMeta {
+				id: row.id as u32,
+				device_id: row.device_id,
+				first_timestamp: naive_to_utc(row.first_timestamp),
+				last_timestamp: naive_to_utc(row.last_timestamp),
+				original_size: row.original_size as usize,
+				compressed_size: row.compressed_size.unwrap_or(0) as usize,
+				logs_count: row.logs_count as u64,
+				created_at: naive_to_utc(row.created_at),
+				level: row.level as u32,
+				bloom: row.bloom,
+				checksum: row.checksum.map(|c| c as u64),
+				quarantined: row.quarantined,
+				encrypted: row.encrypted,
+				last_accessed: opt_naive_to_utc(row.last_accessed),
+				pinned: row.pinned,
+				data_dir: row.data_dir.clone(),
+				compressed: row.compressed,
+				last_scrubbed: opt_naive_to_utc(row.last_scrubbed),
+			})
+			.collect())
+	}
+
+	/// Batched variant of `delete_segment`: releases every id's chunk
+	/// references and removes its `segment_props` and `log_segments` rows,
+	/// all in one transaction, returning how many segment rows were
+	/// actually deleted.
+	pub async fn delete_segments(&self, ids: &[u32]) -> Result<usize> {
+		if ids.is_empty() {
+			return Ok(0);
+		}
+		let int_ids: Vec<i32> = ids.iter().map(|&id| id as i32).collect();
+		let mut conn = self.conn()?;
+		let deleted = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+			for &id in ids {
+				release_segment_chunks(conn, id)?;
+			}
+			diesel::delete(
+				segment_props::table.filter(segment_props::segment_id.eq_any(&int_ids)),
+			)
+			.execute(conn)?;
+			diesel::delete(log_segments::table.filter(log_segments::id.eq_any(&int_ids)))
+				.execute(conn)
+		})?;
+		Ok(deleted)
+	}
+
+	/// Per-device `retention_days` overrides sourced from `device_props`, for
+	/// callers like `retention::plan_evictions` that want a lighter, ad-hoc
+	/// way to make a noisy device expire faster without adding it to
+	/// `Settings.retention_policy.per_device_max_age_secs`.
+	pub async fn device_retention_day_overrides(&self) -> Result<HashMap<String, u64>> {
+		let mut conn = self.read_conn()?;
+		let rows: Vec<(String, String)> = device_props::table
+			.filter(device_props::key.eq("retention_days"))
+			.select((device_props::device_id, device_props::value))
+			.load(&mut conn)?;
+		Ok(rows
+			.into_iter()
+			.filter_map(|(device_id, value)| {
+				value.parse::<u64>().ok().map(|days| (device_id, days))
+			})
+			.collect())
+	}
+
 	pub async fn find_segments_without_device(
 		&self,
 		limit: Option<u32>,
@@ -981,6 +2377,16 @@ impl DB {
 				compressed_size: row.compressed_size.unwrap_or(0) as usize,
 				logs_count: row.logs_count as u64,
 				created_at: naive_to_utc(row.created_at),
+				level: row.level as u32,
+				bloom: row.bloom,
+				checksum: row.checksum.map(|c| c as u64),
+				quarantined: row.quarantined,
+				encrypted: row.encrypted,
+				last_accessed: opt_naive_to_utc(row.last_accessed),
+				pinned: row.pinned,
+				data_dir: row.data_dir.clone(),
+				compressed: row.compressed,
+				last_scrubbed: opt_naive_to_utc(row.last_scrubbed),
 			})
 			.collect())
 	}
@@ -988,6 +2394,7 @@ impl DB {
 	pub async fn delete_segment(&self, segment: u32) -> Result<()> {
 		let mut conn = self.conn()?;
 		conn.transaction::<_, diesel::result::Error, _>(|conn| {
+			release_segment_chunks(conn, segment)?;
 			diesel::delete(
 				segment_props::table.filter(segment_props::segment_id.eq(segment as i32)),
 			)
@@ -1018,11 +2425,180 @@ impl DB {
                          COALESCE(SUM(logs_count), 0) as logs_count FROM log_segments",
 		)
 		.get_result::<MetadataRow>(&mut conn)?;
+
+		#[derive(QueryableByName)]
+		struct DedupRow {
+			#[diesel(sql_type = BigInt)]
+			deduplicated_size: i64,
+		}
+		let dedup_row = diesel::sql_query(
+			"SELECT COALESCE(SUM(LENGTH(blob)), 0) as deduplicated_size FROM chunks",
+		)
+		.get_result::<DedupRow>(&mut conn)?;
+
 		Ok(SegmentsMetadata {
 			segment_count: row.count as u32,
 			original_size: row.original_size as u64,
 			compressed_size: row.compressed_size as u64,
 			logs_count: row.logs_count as u64,
+			deduplicated_size: dedup_row.deduplicated_size as u64,
+		})
+	}
+
+	/// Cheap aggregate queries over `devices`/`log_segments`/`log_buckets`,
+	/// collected in one place so `/metrics` can render them as Prometheus
+	/// gauges without re-deriving the same counts the rest of this module
+	/// already exposes piecemeal (`get_devices`, `fetch_segments_metadata`).
+	pub async fn metrics_snapshot(&self) -> Result<DbMetricsSnapshot> {
+		let mut conn = self.read_conn()?;
+
+		let device_rows: Vec<DeviceRow> = devices::table.load(&mut conn)?;
+		let devices_total = device_rows.len() as u64;
+		let devices_sending = device_rows.iter().filter(|d| d.send_logs).count() as u64;
+		let devices = device_rows
+			.into_iter()
+			.map(|row| DeviceMetrics {
+				device_id: row.id,
+				logs_size: row.logs_size as u64,
+				logs_count: row.logs_count as u64,
+				send_logs: row.send_logs,
+			})
+			.collect();
+
+		#[derive(QueryableByName)]
+		struct SegmentAggRow {
+			#[diesel(sql_type = BigInt)]
+			count: i64,
+			#[diesel(sql_type = BigInt)]
+			original_size: i64,
+			#[diesel(sql_type = BigInt)]
+			compressed_size: i64,
+			#[diesel(sql_type = Nullable<Timestamp>)]
+			oldest: Option<NaiveDateTime>,
+			#[diesel(sql_type = Nullable<Timestamp>)]
+			newest: Option<NaiveDateTime>,
+		}
+		let agg = diesel::sql_query(
+			"SELECT COUNT(*) as count, COALESCE(SUM(original_size), 0) as original_size, \
+                         COALESCE(SUM(compressed_size), 0) as compressed_size, \
+                         MIN(first_timestamp) as oldest, MAX(last_timestamp) as newest \
+                         FROM log_segments",
+		)
+		.get_result::<SegmentAggRow>(&mut conn)?;
+
+		let bucket_rows: Vec<LogBucketRow> = log_buckets::table.load(&mut conn)?;
+		let mut buckets = Vec::with_capacity(bucket_rows.len());
+		for row in bucket_rows {
+			let entry_count: i64 = bucket_logs::table
+				.filter(bucket_logs::bucket_id.eq(row.id))
+				.count()
+				.get_result(&mut conn)?;
+			buckets.push(BucketMetrics {
+				bucket_id: row.id,
+				name: row.name,
+				entry_count: entry_count as u64,
+			});
+		}
+
+		Ok(DbMetricsSnapshot {
+			devices,
+			devices_total,
+			devices_sending,
+			segment_count: agg.count as u64,
+			segment_original_bytes: agg.original_size as u64,
+			segment_compressed_bytes: agg.compressed_size as u64,
+			oldest_segment_timestamp: agg.oldest.map(naive_to_utc),
+			newest_segment_timestamp: agg.newest.map(naive_to_utc),
+			buckets,
+		})
+	}
+
+	/// Recomputes each device's `logs_size`/`logs_count` from the
+	/// `log_segments` rows that actually reference it, correcting whatever
+	/// drift crashes, retries, or segment deletions left in the running
+	/// totals `update_device_stats` maintains incrementally. Also finds (and,
+	/// unless `dry_run`, deletes) `segment_props` rows pointing at a deleted
+	/// segment and `bucket_logs` rows pointing at a deleted bucket.
+	pub async fn repair_device_stats(&self, dry_run: bool) -> Result<DeviceStatsRepairReport> {
+		let mut conn = self.conn()?;
+
+		#[derive(QueryableByName)]
+		struct DeviceAggRow {
+			#[diesel(sql_type = Text)]
+			device_id: String,
+			#[diesel(sql_type = BigInt)]
+			total_size: i64,
+			#[diesel(sql_type = BigInt)]
+			total_count: i64,
+		}
+		let aggs: Vec<DeviceAggRow> = diesel::sql_query(
+			"SELECT device_id, COALESCE(SUM(original_size), 0) as total_size, \
+                         COALESCE(SUM(logs_count), 0) as total_count \
+                         FROM log_segments WHERE device_id IS NOT NULL GROUP BY device_id",
+		)
+		.load(&mut conn)?;
+		let mut totals: HashMap<String, (i64, i64)> = HashMap::new();
+		for agg in aggs {
+			totals.insert(agg.device_id, (agg.total_size, agg.total_count));
+		}
+
+		let device_rows: Vec<DeviceRow> = devices::table.load(&mut conn)?;
+		let devices_checked = device_rows.len() as u64;
+		let mut devices_fixed = 0u64;
+		for row in device_rows {
+			let (total_size, total_count) = totals.get(&row.id).copied().unwrap_or((0, 0));
+			if row.logs_size != total_size || row.logs_count != total_count {
+				devices_fixed += 1;
+				if !dry_run {
+					diesel::update(devices::table.filter(devices::id.eq(&row.id)))
+						.set((
+							devices::logs_size.eq(total_size),
+							devices::logs_count.eq(total_count),
+						))
+						.execute(&mut conn)?;
+				}
+			}
+		}
+
+		#[derive(QueryableByName)]
+		struct CountRow {
+			#[diesel(sql_type = BigInt)]
+			count: i64,
+		}
+		let orphaned_segment_props = diesel::sql_query(
+			"SELECT COUNT(*) as count FROM segment_props \
+                         WHERE segment_id NOT IN (SELECT id FROM log_segments)",
+		)
+		.get_result::<CountRow>(&mut conn)?
+		.count as u64;
+		let orphaned_bucket_logs = diesel::sql_query(
+			"SELECT COUNT(*) as count FROM bucket_logs \
+                         WHERE bucket_id NOT IN (SELECT id FROM log_buckets)",
+		)
+		.get_result::<CountRow>(&mut conn)?
+		.count as u64;
+
+		if !dry_run {
+			if orphaned_segment_props > 0 {
+				diesel::sql_query(
+					"DELETE FROM segment_props WHERE segment_id NOT IN (SELECT id FROM log_segments)",
+				)
+				.execute(&mut conn)?;
+			}
+			if orphaned_bucket_logs > 0 {
+				diesel::sql_query(
+					"DELETE FROM bucket_logs WHERE bucket_id NOT IN (SELECT id FROM log_buckets)",
+				)
+				.execute(&mut conn)?;
+			}
+		}
+
+		Ok(DeviceStatsRepairReport {
+			dry_run,
+			devices_checked,
+			devices_fixed,
+			orphaned_segment_props_removed: orphaned_segment_props,
+			orphaned_bucket_logs_removed: orphaned_bucket_logs,
 		})
 	}
 
@@ -1038,7 +2614,7 @@ impl DB {
 					.values((
 						segment_props::segment_id.eq(segment as i32),
 						segment_props::key.eq(&prop.key),
-						segment_props::value.eq(&prop.value),
+						segment_props::value.eq(prop.value.to_string()),
 					))
 					.execute(conn)?;
 			}
@@ -1047,6 +2623,117 @@ impl DB {
 		Ok(())
 	}
 
+	/// Stores a precomputed bloom filter for a segment. Called as a second
+	/// step right after `upsert_segment_props`, once a segment's full prop
+	/// set is known, rather than threading `bloom` through `NewSegmentArgs`.
+	pub async fn set_segment_bloom(&self, segment: u32, bloom: Vec<u8>) -> Result<()> {
+		let mut conn = self.conn()?;
+		diesel::update(log_segments::table.filter(log_segments::id.eq(segment as i32)))
+			.set(log_segments::bloom.eq(bloom))
+			.execute(&mut conn)?;
+		Ok(())
+	}
+
+	pub async fn set_segment_checksum(&self, segment: u32, checksum: u64) -> Result<()> {
+		let mut conn = self.conn()?;
+		diesel::update(log_segments::table.filter(log_segments::id.eq(segment as i32)))
+			.set(log_segments::checksum.eq(checksum as i64))
+			.execute(&mut conn)?;
+		Ok(())
+	}
+
+	/// Marks a segment quarantined after `ScrubWorker` finds its checksum
+	/// doesn't match, so `find_segments` stops handing it to queries.
+	pub async fn quarantine_segment(&self, segment: u32) -> Result<()> {
+		let mut conn = self.conn()?;
+		diesel::update(log_segments::table.filter(log_segments::id.eq(segment as i32)))
+			.set(log_segments::quarantined.eq(true))
+			.execute(&mut conn)?;
+		Ok(())
+	}
+
+	/// Stamps a segment as just having passed `ScrubWorker`'s integrity
+	/// check, so its next pick by `ScrubWorker::next_segment_id` waits out
+	/// `scrub_interval` instead of re-verifying it every pass.
+	pub async fn set_segment_last_scrubbed(&self, segment: u32, at: DateTime<Utc>) -> Result<()> {
+		let mut conn = self.conn()?;
+		diesel::update(log_segments::table.filter(log_segments::id.eq(segment as i32)))
+			.set(log_segments::last_scrubbed.eq(at.naive_utc()))
+			.execute(&mut conn)?;
+		Ok(())
+	}
+
+	/// Marks or clears a segment as pinned. A pinned segment is skipped by
+	/// `run_cleanup_pass` and `plan_evictions` but otherwise behaves like any
+	/// other segment — it still shows up in `find_segments` and queries, it
+	/// just can't be deleted by cleanup or retention until unpinned.
+	pub async fn set_segment_pinned(&self, segment: u32, pinned: bool) -> Result<()> {
+		let mut conn = self.conn()?;
+		diesel::update(log_segments::table.filter(log_segments::id.eq(segment as i32)))
+			.set(log_segments::pinned.eq(pinned))
+			.execute(&mut conn)?;
+		Ok(())
+	}
+
+	/// Records whether a segment's bytes on disk are AES-256-GCM encrypted,
+	/// so a store can hold a mix of plaintext and encrypted segments while a
+	/// `SEGMENT_ENCRYPTION_KEY` is rolled out.
+	pub async fn set_segment_encrypted(&self, segment: u32, encrypted: bool) -> Result<()> {
+		let mut conn = self.conn()?;
+		diesel::update(log_segments::table.filter(log_segments::id.eq(segment as i32)))
+			.set(log_segments::encrypted.eq(encrypted))
+			.execute(&mut conn)?;
+		Ok(())
+	}
+
+	/// Records which `DataLayout` directory `{segment}.log` actually landed
+	/// in, so a later `get`/`delete` can go straight there instead of
+	/// scanning every configured data dir. `SegmentStore::put` picks the
+	/// directory by live free-space ranking rather than a hash of the id, so
+	/// unlike every other segment column this one can't be recomputed from
+	/// `segment` alone — it has to be recorded at write time.
+	pub async fn set_segment_data_dir(&self, segment: u32, dir: &str) -> Result<()> {
+		let mut conn = self.conn()?;
+		diesel::update(log_segments::table.filter(log_segments::id.eq(segment as i32)))
+			.set(log_segments::data_dir.eq(dir))
+			.execute(&mut conn)?;
+		Ok(())
+	}
+
+	/// Records whether `{id}.log` holds zstd-compressed bytes, so a reader
+	/// knows whether to decode it. Every segment is compressed by default
+	/// (see the `segment_compressed` migration); this is only called to flip
+	/// it to `false` when `compress_segment` skipped compression because it
+	/// wouldn't have shrunk the data.
+	pub async fn set_segment_compressed(&self, segment: u32, compressed: bool) -> Result<()> {
+		let mut conn = self.conn()?;
+		diesel::update(log_segments::table.filter(log_segments::id.eq(segment as i32)))
+			.set(log_segments::compressed.eq(compressed))
+			.execute(&mut conn)?;
+		Ok(())
+	}
+
+	/// Applies many segments' `last_accessed` touches in one transaction.
+	/// `AccessTrackerWorker` coalesces every touch recorded since its last
+	/// drain into a single map before calling this, so a busy query workload
+	/// costs one write transaction per flush interval instead of one per
+	/// segment read.
+	pub async fn update_last_accessed_batch(
+		&self,
+		touches: &HashMap<u32, DateTime<Utc>>,
+	) -> Result<()> {
+		let mut conn = self.conn()?;
+		conn.transaction::<_, diesel::result::Error, _>(|conn| {
+			for (segment, ts) in touches {
+				diesel::update(log_segments::table.filter(log_segments::id.eq(*segment as i32)))
+					.set(log_segments::last_accessed.eq(ts.naive_utc()))
+					.execute(conn)?;
+			}
+			Ok(())
+		})?;
+		Ok(())
+	}
+
 	pub async fn fetch_segment_props(&self, segment: u32) -> Result<Vec<Prop>> {
 		let mut conn = self.read_conn()?;
 		let rows: Vec<(String, String)> = segment_props::table
@@ -1055,7 +2742,7 @@ impl DB {
 			.load(&mut conn)?;
 		Ok(rows
 			.into_iter()
-			.map(|(key, value)| Prop { key, value })
+			.map(|(key, value)| Prop { key, value: value.into() })
 			.collect())
 	}
 
@@ -1082,7 +2769,7 @@ impl DB {
 			for (segment_id, key, value) in rows {
 				map.entry(segment_id as u32)
 					.or_default()
-					.push(Prop { key, value });
+					.push(Prop { key, value: value.into() });
 			}
 		}
 		Ok(map)
@@ -1154,10 +2841,9 @@ pub fn run_migrations(conn: &mut SqliteConnection) -> Result<()> {
 mod tests {
 	use super::*;
 	use puppylog::Prop;
-	use std::collections::HashSet;
 
 	fn test_db() -> DB {
-		let pool = establish_pool(":memory:").unwrap();
+		let pool = establish_pool(":memory:", DbConfig::in_memory()).unwrap();
 		DB::new(DbPools {
 			write_pool: pool.clone(),
 			read_pool: pool,
@@ -1183,7 +2869,7 @@ mod tests {
 
 		let prop = Prop {
 			key: "kind".to_string(),
-			value: "value".to_string(),
+			value: "value".to_string().into(),
 		};
 		db.upsert_segment_props(segment, [prop.clone()].iter())
 			.await
@@ -1222,6 +2908,7 @@ mod tests {
 				device_ids: None,
 				count: None,
 				sort: None,
+				level: None,
 			})
 			.await
 			.unwrap();
@@ -1392,4 +3079,208 @@ mod tests {
 		let unique: HashSet<&str> = refreshed.logs.iter().map(|log| log.id.as_str()).collect();
 		assert_eq!(unique.len(), refreshed.logs.len());
 	}
+
+	#[tokio::test]
+	async fn expire_segments_keeps_device_stats_consistent() {
+		let db = test_db();
+		let device_id = "dev1";
+		let current_time = Utc::now();
+
+		for i in 0..3 {
+			let ts = current_time - chrono::Duration::days(10 - i);
+			db.new_segment(NewSegmentArgs {
+				device_id: Some(device_id.into()),
+				first_timestamp: ts,
+				last_timestamp: ts,
+				original_size: 100,
+				compressed_size: 50,
+				logs_count: 1,
+			})
+			.await
+			.unwrap();
+			db.update_device_stats(device_id, 100, 1, Some(50))
+				.await
+				.unwrap();
+		}
+
+		db.create_retention_policy(NewRetentionPolicyArgs {
+			device_id: Some(device_id.into()),
+			prop_key: None,
+			prop_value: None,
+			max_age_seconds: Some(60 * 60 * 24 * 5),
+			max_total_bytes: None,
+			enabled: true,
+		})
+		.await
+		.unwrap();
+
+		let expired = db.expire_segments().await.unwrap();
+		assert_eq!(expired.len(), 2);
+
+		let remaining = db
+			.find_segments(&GetSegmentsQuery {
+				start: None,
+				end: None,
+				device_ids: Some(vec![device_id.into()]),
+				count: None,
+				sort: None,
+				level: None,
+			})
+			.await
+			.unwrap();
+		assert_eq!(remaining.len(), 1);
+
+		let device = db.get_device(device_id).await.unwrap().unwrap();
+		let expected_size: usize = remaining.iter().map(|s| s.original_size).sum();
+		let expected_count: usize = remaining.len();
+		assert_eq!(device.logs_size, expected_size);
+		assert_eq!(device.logs_count, expected_count);
+	}
+
+	#[tokio::test]
+	async fn poll_bucket_returns_immediately_when_already_behind() {
+		let db = test_db();
+		let bucket = db
+			.upsert_bucket(UpsertBucketArgs {
+				id: None,
+				name: "Live".into(),
+				query: "host:web".into(),
+			})
+			.await
+			.unwrap();
+
+		db.append_bucket_logs(
+			bucket.id,
+			&[NewBucketLogEntry {
+				id: "log-1".into(),
+				timestamp: Utc::now().to_rfc3339(),
+				level: "info".into(),
+				msg: "hello".into(),
+				props: vec![],
+			}],
+		)
+		.await
+		.unwrap();
+
+		let poll = db
+			.poll_bucket(bucket.id, 0, Duration::from_millis(500))
+			.await
+			.unwrap();
+		assert_eq!(poll.entries.len(), 1);
+		assert_eq!(poll.entries[0].id, "log-1");
+		assert!(poll.cursor > 0);
+	}
+
+	#[tokio::test]
+	async fn poll_bucket_wakes_on_new_entry_and_times_out_otherwise() {
+		let db = std::sync::Arc::new(test_db());
+		let bucket = db
+			.upsert_bucket(UpsertBucketArgs {
+				id: None,
+				name: "Live".into(),
+				query: "host:web".into(),
+			})
+			.await
+			.unwrap();
+
+		let poller_db = db.clone();
+		let bucket_id = bucket.id;
+		let poller = tokio::spawn(async move {
+			poller_db
+				.poll_bucket(bucket_id, 0, Duration::from_secs(5))
+				.await
+				.unwrap()
+		});
+
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		db.append_bucket_logs(
+			bucket.id,
+			&[NewBucketLogEntry {
+				id: "log-1".into(),
+				timestamp: Utc::now().to_rfc3339(),
+				level: "info".into(),
+				msg: "hello".into(),
+				props: vec![],
+			}],
+		)
+		.await
+		.unwrap();
+
+		let poll = poller.await.unwrap();
+		assert_eq!(poll.entries.len(), 1);
+		assert_eq!(poll.entries[0].id, "log-1");
+
+		let timed_out = db
+			.poll_bucket(bucket.id, poll.cursor, Duration::from_millis(50))
+			.await
+			.unwrap();
+		assert!(timed_out.entries.is_empty());
+		assert_eq!(timed_out.cursor, poll.cursor);
+	}
+
+	#[tokio::test]
+	async fn metrics_snapshot_aggregates_devices_segments_and_buckets() {
+		let db = test_db();
+		let current_time = Utc::now();
+
+		db.new_segment(NewSegmentArgs {
+			device_id: Some("dev1".into()),
+			first_timestamp: current_time,
+			last_timestamp: current_time,
+			original_size: 100,
+			compressed_size: 40,
+			logs_count: 5,
+		})
+		.await
+		.unwrap();
+		db.update_device_stats("dev1", 100, 5, Some(40))
+			.await
+			.unwrap();
+		db.update_device_settings(
+			"dev1",
+			&UpdateDeviceSettings {
+				send_logs: true,
+				filter_level: LogLevel::Info,
+				send_interval: 60,
+			},
+		)
+		.await;
+
+		let bucket = db
+			.upsert_bucket(UpsertBucketArgs {
+				id: None,
+				name: "Recent".into(),
+				query: "host:web".into(),
+			})
+			.await
+			.unwrap();
+		db.append_bucket_logs(
+			bucket.id,
+			&[NewBucketLogEntry {
+				id: "log-1".into(),
+				timestamp: current_time.to_rfc3339(),
+				level: "info".into(),
+				msg: "hello".into(),
+				props: vec![],
+			}],
+		)
+		.await
+		.unwrap();
+
+		let snapshot = db.metrics_snapshot().await.unwrap();
+		assert_eq!(snapshot.devices_total, 1);
+		assert_eq!(snapshot.devices_sending, 1);
+		assert_eq!(snapshot.segment_count, 1);
+		assert_eq!(snapshot.segment_original_bytes, 100);
+		assert_eq!(snapshot.segment_compressed_bytes, 40);
+		assert_eq!(snapshot.buckets.len(), 1);
+		assert_eq!(snapshot.buckets[0].entry_count, 1);
+
+		let rendered = snapshot.render(true);
+		assert!(rendered.contains("puppylog_device_logs_size{device=\"dev1\"} 100"));
+		assert!(rendered.contains("puppylog_bucket_entries{bucket=\"Recent\"} 1"));
+
+		let rendered_without_labels = snapshot.render(false);
+		assert!(!rendered_without_labels.contains("device=\"dev1\""));
+	}
 }
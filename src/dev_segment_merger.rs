@@ -2,17 +2,22 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 
+use crate::bloom::SegmentBloom;
 use crate::context::Context;
 use crate::db::NewSegmentArgs;
 use crate::segment::LogSegment;
 use lru::LruCache;
 use puppylog::{LogEntry, Prop};
-use tokio::fs::remove_file;
 
 pub const TARGET_SEGMENT_SIZE: usize = 300_000;
 pub const MERGER_BATCH_SIZE: u32 = 2000;
 pub const PER_DEVICE_MAX: usize = 1_000;
 pub const MAX_IN_CORE: usize = 10_000_000;
+/// How many devices' segments `run_once`'s end-of-batch flush compresses and
+/// writes concurrently. Each flush is independent (its own segment row,
+/// its own file), so the only reason to bound this instead of spawning one
+/// task per device is to cap peak concurrent zstd encoders and file handles.
+pub const FLUSH_CONCURRENCY: usize = 8;
 /// Fallback device identifier used when a log entry has no explicit `deviceId`.
 pub const UNKNOWN_DEVICE_ID: &str = "unknown";
 
@@ -39,50 +44,87 @@ impl DeviceMerger {
 		}
 	}
 
+	/// Compresses, persists and stores chunks for one device's buffered
+	/// `logs`, exactly as `flush_device` used to do inline. Takes `ctx` by
+	/// reference instead of `&mut self` so it can run as an independent
+	/// `tokio` task alongside other devices' flushes (see `run_once`'s
+	/// end-of-batch drain) without borrowing the merger itself.
+	async fn flush_logs(ctx: &Arc<Context>, device_id: &str, mut logs: Vec<LogEntry>) -> anyhow::Result<()> {
+		if logs.is_empty() {
+			return Ok(());
+		}
+		logs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+		let first = logs.first().unwrap().timestamp;
+		let last = logs.last().unwrap().timestamp;
+		let seg = LogSegment::from_buffer(logs);
+		let mut buf = Vec::new();
+		seg.serialize(&mut buf);
+		let orig_size = buf.len();
+		let compressed = zstd::encode_all(std::io::Cursor::new(buf), 0)?;
+		let comp_size = compressed.len();
+		let args = NewSegmentArgs {
+			device_id: Some(device_id.to_string()),
+			first_timestamp: first,
+			last_timestamp: last,
+			original_size: orig_size,
+			compressed_size: comp_size,
+			logs_count: seg.buffer.len() as u64,
+		};
+		// Reuse an aged-out freed id when one is available instead of always
+		// minting a new one, so this flush's delete-old/create-new churn
+		// doesn't transiently double the on-disk footprint. See
+		// `SegmentSlotAllocator`.
+		let segment_id = match ctx.segment_slots.take() {
+			Some(id) => ctx.db.new_segment_with_id(id, args).await?,
+			None => ctx.db.new_segment(args).await?,
+		};
+		let mut unique = HashSet::new();
+		for log in &seg.buffer {
+			for p in &log.props {
+				unique.insert(p.clone());
+			}
+			unique.insert(Prop {
+				key: "level".into(),
+				value: log.level.to_string().into(),
+			});
+		}
+		ctx.db.upsert_segment_props(segment_id, unique.iter()).await?;
+		let mut bloom = SegmentBloom::with_expected_items(unique.len());
+		for prop in &unique {
+			bloom.insert(&format!("{}={}", prop.key, prop.value));
+		}
+		ctx.db.set_segment_bloom(segment_id, bloom.to_bytes()).await?;
+		let compressed = match ctx.encryption_key() {
+			Some(key) => crate::encryption::encrypt(&key, &compressed),
+			None => compressed,
+		};
+		ctx.db
+			.set_segment_encrypted(segment_id, ctx.encryption_key().is_some())
+			.await?;
+		ctx.db
+			.set_segment_checksum(segment_id, crate::checksum::checksum(&compressed))
+			.await?;
+		// Same content-defined chunking/refcounting `Context::persist_segment`
+		// does for freshly ingested segments — merged segments re-share the
+		// same runs of bytes across repeated device uploads just as often,
+		// so this is the other write path that needs to feed `chunks`.
+		if let Err(err) = ctx
+			.db
+			.store_segment_chunks(segment_id, &compressed, &ctx.chunking)
+			.await
+		{
+			log::error!("failed to store chunks for segment {}: {}", segment_id, err);
+		}
+		let placed = ctx.store.put(segment_id, compressed).await?;
+		ctx.record_segment_data_dir(segment_id, placed).await;
+		Ok(())
+	}
+
 	async fn flush_device(&mut self, device_id: &str) -> anyhow::Result<()> {
-		if let Some(mut logs) = self.buffers.remove(device_id) {
+		if let Some(logs) = self.buffers.remove(device_id) {
 			self.lru.pop(device_id);
 			self.total_buffered = self.total_buffered.saturating_sub(logs.len());
-			if logs.is_empty() {
-				return Ok(());
-			}
-			logs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-			let first = logs.first().unwrap().timestamp;
-			let last = logs.last().unwrap().timestamp;
-			let seg = LogSegment { buffer: logs };
-			let mut buf = Vec::new();
-			seg.serialize(&mut buf);
-			let orig_size = buf.len();
-			let compressed = zstd::encode_all(std::io::Cursor::new(buf), 0)?;
-			let comp_size = compressed.len();
-			let segment_id = self
-				.ctx
-				.db
-				.new_segment(NewSegmentArgs {
-					device_id: Some(device_id.to_string()),
-					first_timestamp: first,
-					last_timestamp: last,
-					original_size: orig_size,
-					compressed_size: comp_size,
-					logs_count: seg.buffer.len() as u64,
-				})
-				.await?;
-			let mut unique = HashSet::new();
-			for log in &seg.buffer {
-				for p in &log.props {
-					unique.insert(p.clone());
-				}
-				unique.insert(Prop {
-					key: "level".into(),
-					value: log.level.to_string(),
-				});
-			}
-			self.ctx
-				.db
-				.upsert_segment_props(segment_id, unique.iter())
-				.await?;
-			let path = self.ctx.logs_path().join(format!("{}.log", segment_id));
-			tokio::fs::write(path, compressed).await?;
+			Self::flush_logs(&self.ctx, device_id, logs).await?;
 		}
 		Ok(())
 	}
@@ -90,7 +132,7 @@ impl DeviceMerger {
     async fn handle_log(&mut self, mut log: LogEntry) -> anyhow::Result<()> {
         // Determine the device ID, falling back to the special constant.
         let device_id = if let Some(prop) = log.props.iter().find(|p| p.key == "deviceId") {
-            prop.value.clone()
+            prop.value.to_string()
         } else {
             // Attach a synthetic `deviceId` so that downstream logic and tests
             // can treat it like any normal device‑specific log.
@@ -138,38 +180,110 @@ impl DeviceMerger {
 
 			log::info!("processing {} segments", segments.len());
 			for seg in segments {
-				let path = self.ctx.logs_path().join(format!("{}.log", seg.id));
-				let file = match std::fs::File::open(&path) {
-					Ok(f) => f,
+				let bytes = match self.ctx.store.get(seg.id, seg.data_dir.as_deref()).await {
+					Ok(b) => b,
 					Err(_) => continue,
 				};
-				log::info!("process segment {} from {}", seg.id, path.display());
-				let mut decoder = zstd::Decoder::new(file)?;
-				let log_seg = LogSegment::parse(&mut decoder);
+				let bytes = if seg.encrypted {
+					match self
+						.ctx
+						.encryption_key()
+						.ok_or_else(|| {
+							anyhow::anyhow!(
+								"segment {} is encrypted but no SEGMENT_ENCRYPTION_KEY is configured",
+								seg.id
+							)
+						})
+						.and_then(|key| crate::encryption::decrypt(&key, seg.id, &bytes).map_err(Into::into))
+					{
+						Ok(b) => b,
+						Err(err) => {
+							log::error!("cannot decrypt segment {}: {}", seg.id, err);
+							continue;
+						}
+					}
+				} else {
+					bytes
+				};
+				log::info!("process segment {} from store", seg.id);
+				let log_seg = if seg.compressed {
+					let mut decoder = zstd::Decoder::new(std::io::Cursor::new(bytes))?;
+					LogSegment::parse(&mut decoder)
+				} else {
+					LogSegment::parse(&mut std::io::Cursor::new(bytes))
+				}
+				.unwrap_or_else(|err| {
+					log::warn!("segment {} failed to parse: {}", seg.id, err);
+					err.recovered()
+				});
 				for log in log_seg.buffer {
 					let device_id = log
 						.props
 						.iter()
 						.find(|p| p.key == "deviceId")
-						.map_or(UNKNOWN_DEVICE_ID.to_string(), |p| p.value.clone());
+						.map_or(UNKNOWN_DEVICE_ID.to_string(), |p| p.value.to_string());
 					if device_ids.insert(device_id.clone()) {
 						log::info!("[{}] devices", device_ids.len());
 					}
 					self.handle_log(log).await?;
 				}
-				to_delete.push((seg.id, path));
+				to_delete.push((seg.id, seg.data_dir));
 			}
 
-			// Flush remaining buffers unconditionally
-			let keys: Vec<String> = self.buffers.keys().cloned().collect();
-			for k in keys {
-				self.flush_device(&k).await?;
+			// Flush remaining buffers unconditionally. Each device's flush is
+			// independent, so rather than `await`ing them one at a time,
+			// drain the buffers up front (so `self` is free again) and run
+			// up to `FLUSH_CONCURRENCY` of them at once in a `JoinSet`
+			// gated by a semaphore. `to_delete` below is only touched once
+			// every task here has finished, preserving the existing
+			// guarantee that orphans are deleted only after every segment
+			// they contributed to has durably landed.
+			let pending: Vec<(String, Vec<LogEntry>)> = self.buffers.drain().collect();
+			for (device_id, logs) in &pending {
+				self.lru.pop(device_id);
+				self.total_buffered = self.total_buffered.saturating_sub(logs.len());
+			}
+			let semaphore = Arc::new(tokio::sync::Semaphore::new(FLUSH_CONCURRENCY));
+			let mut flushes = tokio::task::JoinSet::new();
+			for (device_id, logs) in pending {
+				let ctx = self.ctx.clone();
+				let semaphore = semaphore.clone();
+				flushes.spawn(async move {
+					let _permit = semaphore
+						.acquire_owned()
+						.await
+						.expect("semaphore is never closed");
+					(device_id.clone(), Self::flush_logs(&ctx, &device_id, logs).await)
+				});
+			}
+			let mut first_err = None;
+			while let Some(joined) = flushes.join_next().await {
+				let (device_id, result) = joined?;
+				if let Err(err) = result {
+					log::error!("failed to flush device {}: {}", device_id, err);
+					if first_err.is_none() {
+						first_err = Some(err);
+					}
+				}
+			}
+			if let Some(err) = first_err {
+				return Err(err);
 			}
 
+			// Consumed orphans are only ever deleted after every resulting
+			// device segment above has been durably persisted (flushed just
+			// above, in the same `run_once` pass), and the DB rows for all
+			// of them disappear in one transaction via `delete_segments`
+			// rather than one `delete_segment` per id — so a crash between
+			// two of these deletes can never leave some orphans consumed
+			// and others still pending reprocessing.
 			log::info!("removing {} old segments", to_delete.len());
-			for (seg_id, path) in &to_delete {
-				self.ctx.db.delete_segment(*seg_id).await?;
-				let _ = remove_file(path).await;
+			let delete_ids: Vec<u32> = to_delete.iter().map(|(id, _)| *id).collect();
+			self.ctx.db.delete_segments(&delete_ids).await?;
+			for (seg_id, data_dir) in &to_delete {
+				let _ = self.ctx.store.delete(*seg_id, data_dir.as_deref()).await;
+				self.ctx.segment_cache.invalidate(*seg_id);
+				self.ctx.segment_slots.free(*seg_id);
 			}
 			to_delete.clear();
 		}
@@ -534,7 +648,7 @@ mod tests {
 						level: LogLevel::Info,
 						props: vec![Prop {
 							key: "deviceId".into(),
-							value: device.to_string(),
+							value: device.to_string().into(),
 						}],
 						msg: format!("{}‑{}", device, i),
 						..Default::default()
@@ -623,7 +737,7 @@ mod tests {
 				level: LogLevel::Info,
 				props: vec![Prop {
 					key: "deviceId".into(),
-					value: format!("dev{i}"),
+					value: format!("dev{i}").into(),
 				}],
 				msg: "x".into(),
 				..Default::default()
@@ -692,4 +806,112 @@ mod tests {
 		assert_eq!(segs[0].device_id.as_deref(), Some(UNKNOWN_DEVICE_ID));
 		assert_eq!(segs[0].logs_count, 1);
 	}
+
+	/// `flush_device` writes merged segments through `store_segment_chunks`
+	/// just like `Context::persist_segment` does for freshly ingested ones,
+	/// so a device's repeated (heavily overlapping) uploads should show up
+	/// as shared `chunks` rows instead of only counting against the
+	/// uncompressed/compressed segment totals.
+	#[tokio::test]
+	async fn merged_segment_feeds_chunk_dedup_accounting() {
+		let (ctx, _dir) = prepare_ctx().await;
+		let ts = Utc::now();
+		let log = LogEntry {
+			timestamp: ts,
+			level: LogLevel::Info,
+			props: vec![Prop {
+				key: "deviceId".into(),
+				value: "dev1".into(),
+			}],
+			msg: "a fairly long repeated log message to give the chunker something to cut".into(),
+			..Default::default()
+		};
+		let mut seg = LogSegment::new();
+		for _ in 0..50 {
+			seg.add_log_entry(log.clone());
+		}
+		seg.sort();
+		let mut buff = Vec::new();
+		seg.serialize(&mut buff);
+		let orig = buff.len();
+		let comp = zstd::encode_all(std::io::Cursor::new(buff), 0).unwrap();
+		let comp_size = comp.len();
+		let seg_id = ctx
+			.db
+			.new_segment(NewSegmentArgs {
+				device_id: None,
+				first_timestamp: ts,
+				last_timestamp: ts,
+				original_size: orig,
+				compressed_size: comp_size,
+				logs_count: 50,
+			})
+			.await
+			.unwrap();
+		std::fs::write(ctx.logs_path().join(format!("{}.log", seg_id)), comp).unwrap();
+
+		let mut merger = DeviceMerger::new(ctx.clone());
+		assert!(merger.run_once().await.unwrap());
+
+		let metadata = ctx.db.fetch_segments_metadata().await.unwrap();
+		assert!(metadata.deduplicated_size > 0);
+	}
+
+	/// When `Context::segment_slots` already has an aged-out freed id
+	/// sitting in it, `flush_logs` should reoccupy that id via
+	/// `new_segment_with_id` instead of minting a fresh one.
+	#[tokio::test]
+	async fn flush_reuses_aged_freed_slot() {
+		let (ctx, _dir) = prepare_ctx().await;
+		let ts = Utc::now();
+		let log = LogEntry {
+			timestamp: ts,
+			level: LogLevel::Info,
+			props: vec![Prop {
+				key: "deviceId".into(),
+				value: "dev1".into(),
+			}],
+			msg: "msg".into(),
+			..Default::default()
+		};
+		let mut seg = LogSegment::new();
+		seg.add_log_entry(log.clone());
+		seg.sort();
+		let mut buff = Vec::new();
+		seg.serialize(&mut buff);
+		let orig = buff.len();
+		let comp = zstd::encode_all(std::io::Cursor::new(buff), 0).unwrap();
+		let comp_size = comp.len();
+		let seg_id = ctx
+			.db
+			.new_segment(NewSegmentArgs {
+				device_id: None,
+				first_timestamp: ts,
+				last_timestamp: ts,
+				original_size: orig,
+				compressed_size: comp_size,
+				logs_count: 1,
+			})
+			.await
+			.unwrap();
+		std::fs::write(ctx.logs_path().join(format!("{}.log", seg_id)), comp).unwrap();
+
+		// Prime the allocator with a reusable id, aging it past the default
+		// reuse delay with a run of unrelated frees.
+		ctx.segment_slots.free(999_999);
+		for filler in 0..20 {
+			ctx.segment_slots.free(1_000_000 + filler);
+		}
+
+		let mut merger = DeviceMerger::new(ctx.clone());
+		assert!(merger.run_once().await.unwrap());
+
+		let segs = ctx
+			.db
+			.find_segments(&crate::types::GetSegmentsQuery::default())
+			.await
+			.unwrap();
+		assert_eq!(segs.len(), 1);
+		assert_eq!(segs[0].id, 999_999, "flush should reoccupy the aged freed id");
+	}
 }
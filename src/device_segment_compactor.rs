@@ -1,21 +1,50 @@
-use std::collections::{HashMap, HashSet};
-use std::io::Write;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use crate::bloom::SegmentBloom;
 use crate::context::Context;
 use crate::db::NewSegmentArgs;
 use crate::dev_segment_merger::TARGET_SEGMENT_SIZE;
-use crate::segment::LogSegment;
+use crate::segment::{compress_segment, LogSegment, SegmentEntryStream};
 use crate::types::{GetSegmentsQuery, SortDir};
+use chrono::{DateTime, Utc};
 use puppylog::{LogEntry, Prop};
-use tokio::fs::remove_file;
-use zstd::Encoder;
 
 pub struct DeviceSegmentCompactor {
 	ctx: Arc<Context>,
 }
 
+/// One candidate head entry in the k-way merge `run_once` does across a
+/// device's small segments: ordered by `(timestamp, segment_idx)` so the
+/// `BinaryHeap` always surfaces the globally-earliest pending entry, with
+/// `segment_idx` as a tiebreaker only to give `Ord` a total order (which
+/// segment wins a timestamp tie doesn't matter for correctness).
+struct HeapItem {
+	timestamp: DateTime<Utc>,
+	segment_idx: usize,
+	entry: LogEntry,
+}
+
+impl PartialEq for HeapItem {
+	fn eq(&self, other: &Self) -> bool {
+		(self.timestamp, self.segment_idx) == (other.timestamp, other.segment_idx)
+	}
+}
+impl Eq for HeapItem {}
+impl PartialOrd for HeapItem {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+impl Ord for HeapItem {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		(self.timestamp, self.segment_idx).cmp(&(other.timestamp, other.segment_idx))
+	}
+}
+
 impl DeviceSegmentCompactor {
 	pub fn new(ctx: Arc<Context>) -> Self {
 		Self { ctx }
@@ -29,16 +58,13 @@ impl DeviceSegmentCompactor {
 		logs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
 		let first = logs.first().unwrap().timestamp;
 		let last = logs.last().unwrap().timestamp;
-		let seg = LogSegment { buffer: logs };
+		let seg = LogSegment::from_buffer(logs);
 
 		let mut buf = Vec::new();
 		seg.serialize(&mut buf);
 		let orig_size = buf.len();
 
-		let mut encoder = Encoder::new(Vec::new(), 14)?;
-		encoder.multithread(num_cpus::get() as u32)?;
-		encoder.write_all(&buf)?;
-		let compressed = encoder.finish()?;
+		let (compressed, is_compressed) = compress_segment(&buf)?;
 		let comp_size = compressed.len();
 
 		let segment_id = self
@@ -59,7 +85,7 @@ impl DeviceSegmentCompactor {
 			unique.extend(log.props.iter().cloned());
 			unique.insert(Prop {
 				key: "level".into(),
-				value: log.level.to_string(),
+				value: log.level.to_string().into(),
 			});
 		}
 		self.ctx
@@ -67,8 +93,43 @@ impl DeviceSegmentCompactor {
 			.upsert_segment_props(segment_id, unique.iter())
 			.await?;
 
-		let path = self.ctx.logs_path().join(format!("{}.log", segment_id));
-		tokio::fs::write(path, compressed).await?;
+		let mut bloom = SegmentBloom::with_expected_items(unique.len());
+		for prop in &unique {
+			bloom.insert(&format!("{}={}", prop.key, prop.value));
+		}
+		self.ctx
+			.db
+			.set_segment_bloom(segment_id, bloom.to_bytes())
+			.await?;
+
+		let compressed = match self.ctx.encryption_key() {
+			Some(key) => crate::encryption::encrypt(&key, &compressed),
+			None => compressed,
+		};
+		self.ctx
+			.db
+			.set_segment_encrypted(segment_id, self.ctx.encryption_key().is_some())
+			.await?;
+
+		self.ctx
+			.db
+			.set_segment_checksum(segment_id, crate::checksum::checksum(&compressed))
+			.await?;
+		if !is_compressed {
+			self.ctx.db.set_segment_compressed(segment_id, false).await?;
+		}
+
+		let placed = self.ctx.store.put(segment_id, compressed).await?;
+		self.ctx.record_segment_data_dir(segment_id, placed).await;
+
+		self.ctx
+			.metrics
+			.compaction_output_segments_total
+			.fetch_add(1, Ordering::Relaxed);
+		self.ctx
+			.metrics
+			.compaction_output_bytes_total
+			.fetch_add(comp_size as u64, Ordering::Relaxed);
 
 		log::info!(
 			"created compacted segment {} for device {} ({} logs)",
@@ -90,6 +151,7 @@ impl DeviceSegmentCompactor {
 				device_ids: None,
 				count: None,
 				sort: Some(SortDir::Asc),
+				level: Some(0),
 			})
 			.await?;
 
@@ -109,53 +171,115 @@ impl DeviceSegmentCompactor {
 
 		let mut processed = false;
 
-		for (device, mut segs) in by_device {
+		for (device, segs) in by_device {
 			if segs.len() < 2 {
 				continue;
 			}
 			log::info!("compacting {} segments for device {}", segs.len(), device);
-			segs.sort_by_key(|s| s.first_timestamp);
-			let mut buffer: Vec<LogEntry> = Vec::new();
-			let mut to_delete = Vec::new();
+			let started = Instant::now();
+			let input_segments = segs.len() as u64;
+			let input_bytes: u64 = segs.iter().map(|s| s.compressed_size as u64).sum();
+			let mut to_delete: Vec<(u32, Option<String>)> = Vec::new();
+
+			// Open a streaming decoder per input segment rather than
+			// decoding all of them into one combined `Vec<LogEntry>`: each
+			// `SegmentEntryStream` only ever holds its own decompressed
+			// entry bytes plus whichever single entry is currently its
+			// head, so peak memory is bounded by the number of input
+			// segments (one live cursor each) plus one output buffer,
+			// instead of the sum of every small segment's entries.
+			let mut streams: Vec<SegmentEntryStream> = Vec::new();
 			for seg in segs {
-				let path = self.ctx.logs_path().join(format!("{}.log", seg.id));
-				let file = match std::fs::File::open(&path) {
-					Ok(f) => f,
+				let bytes = match self.ctx.store.get(seg.id, seg.data_dir.as_deref()).await {
+					Ok(b) => b,
 					Err(err) => {
-						log::warn!(
-							"cannot open {} for segment {}: {}",
-							path.display(),
-							seg.id,
-							err
-						);
+						log::warn!("cannot load segment {}: {}", seg.id, err);
 						self.ctx.db.delete_segment(seg.id).await?;
-						let _ = remove_file(&path).await;
+						let _ = self.ctx.store.delete(seg.id, seg.data_dir.as_deref()).await;
+						self.ctx.segment_cache.invalidate(seg.id);
 						continue;
 					}
 				};
-				let mut decoder = zstd::Decoder::new(file)?;
-				let log_seg = LogSegment::parse(&mut decoder);
-				buffer.extend(log_seg.buffer);
-				to_delete.push((seg.id, path));
+				let bytes = if seg.encrypted {
+					let key = self.ctx.encryption_key().ok_or_else(|| {
+						anyhow::anyhow!(
+							"segment {} is encrypted but no SEGMENT_ENCRYPTION_KEY is configured",
+							seg.id
+						)
+					})?;
+					crate::encryption::decrypt(&key, seg.id, &bytes)?
+				} else {
+					bytes
+				};
+				to_delete.push((seg.id, seg.data_dir.clone()));
+				let entries = if seg.compressed {
+					let mut decoder = zstd::Decoder::new(std::io::Cursor::new(bytes))?;
+					LogSegment::open_entries(&mut decoder)
+				} else {
+					LogSegment::open_entries(&mut std::io::Cursor::new(bytes))
+				};
+				match entries {
+					Ok(stream) => streams.push(stream),
+					Err(err) => log::warn!("segment {} failed to parse: {}", seg.id, err),
+				}
+			}
+
+			// Segments are individually timestamp-sorted already (see
+			// `LogSegment::serialize`); a min-heap over each stream's
+			// current head, keyed by `(timestamp, segment_idx)`, merges
+			// them into one globally-sorted order without ever requiring
+			// all of them to be buffered at once.
+			let mut heap: BinaryHeap<Reverse<HeapItem>> = BinaryHeap::new();
+			for (segment_idx, stream) in streams.iter_mut().enumerate() {
+				if let Some(entry) = stream.next() {
+					heap.push(Reverse(HeapItem {
+						timestamp: entry.timestamp,
+						segment_idx,
+						entry,
+					}));
+				}
+			}
 
-				while buffer.len() >= TARGET_SEGMENT_SIZE {
-					let logs: Vec<LogEntry> = buffer.drain(..TARGET_SEGMENT_SIZE).collect();
+			let mut buffer: Vec<LogEntry> = Vec::with_capacity(TARGET_SEGMENT_SIZE);
+			while let Some(Reverse(HeapItem { segment_idx, entry, .. })) = heap.pop() {
+				buffer.push(entry);
+				if let Some(next_entry) = streams[segment_idx].next() {
+					heap.push(Reverse(HeapItem {
+						timestamp: next_entry.timestamp,
+						segment_idx,
+						entry: next_entry,
+					}));
+				}
+
+				if buffer.len() >= TARGET_SEGMENT_SIZE {
+					let logs = std::mem::replace(&mut buffer, Vec::with_capacity(TARGET_SEGMENT_SIZE));
 					self.persist_segment(&device, logs).await?;
 					processed = true;
 				}
 			}
 
 			if !buffer.is_empty() {
-				self.persist_segment(&device, buffer.clone()).await?;
-				buffer.clear();
+				self.persist_segment(&device, buffer).await?;
 				processed = true;
 			}
 
-			for (id, path) in to_delete {
+			for (id, data_dir) in to_delete {
 				self.ctx.db.delete_segment(id).await?;
-				let _ = remove_file(path).await;
+				let _ = self.ctx.store.delete(id, data_dir.as_deref()).await;
+				self.ctx.segment_cache.invalidate(id);
 			}
 			log::info!("device {} compacted", device);
+
+			self.ctx.metrics.compactions_total.fetch_add(1, Ordering::Relaxed);
+			self.ctx
+				.metrics
+				.compaction_input_segments_total
+				.fetch_add(input_segments, Ordering::Relaxed);
+			self.ctx
+				.metrics
+				.compaction_input_bytes_total
+				.fetch_add(input_bytes, Ordering::Relaxed);
+			self.ctx.metrics.compaction_duration.observe(started.elapsed());
 		}
 
 		Ok(processed)
@@ -213,7 +337,7 @@ mod tests {
 			let mut buf = Vec::new();
 			seg.serialize(&mut buf);
 			let orig = buf.len();
-			let comp = compress_segment(&buf).unwrap();
+			let (comp, is_compressed) = compress_segment(&buf).unwrap();
 			let comp_size = comp.len();
 			let seg_id = ctx
 				.db
@@ -227,6 +351,9 @@ mod tests {
 				})
 				.await
 				.unwrap();
+			if !is_compressed {
+				ctx.db.set_segment_compressed(seg_id, false).await.unwrap();
+			}
 			std::fs::write(ctx.logs_path().join(format!("{}.log", seg_id)), comp).unwrap();
 		}
 
@@ -241,9 +368,13 @@ mod tests {
 		assert_eq!(segs.len(), 1);
 		assert_eq!(segs[0].logs_count, 3);
 		let path = ctx.logs_path().join(format!("{}.log", segs[0].id));
-		let file = std::fs::File::open(&path).unwrap();
-		let mut decoder = zstd::Decoder::new(file).unwrap();
-		let seg = LogSegment::parse(&mut decoder);
+		let mut file = std::fs::File::open(&path).unwrap();
+		let seg = if segs[0].compressed {
+			let mut decoder = zstd::Decoder::new(file).unwrap();
+			LogSegment::parse(&mut decoder).unwrap()
+		} else {
+			LogSegment::parse(&mut file).unwrap()
+		};
 		let mut ts: Vec<_> = seg.buffer.iter().map(|l| l.timestamp).collect();
 		let mut sorted = ts.clone();
 		sorted.sort();
@@ -0,0 +1,53 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Why a presented upload token didn't authenticate.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VerifyError {
+	Malformed,
+	BadSignature,
+	Expired,
+	WrongDevice,
+}
+
+/// Mints a presigned-URL-style credential binding `device_id` to an
+/// expiry: `device_id|expiry_unix.signature`, HMAC-SHA256 signed over the
+/// canonical `device_id|expiry_unix` string.
+pub fn mint(secret: &[u8], device_id: &str, expires_at: DateTime<Utc>) -> String {
+	let payload = format!("{device_id}|{}", expires_at.timestamp());
+	let signature = sign(secret, &payload);
+	format!("{payload}.{signature}")
+}
+
+/// Verifies a token was signed by `secret`, still unexpired, and bound to
+/// `device_id`.
+pub fn verify(secret: &[u8], device_id: &str, token: &str) -> Result<(), VerifyError> {
+	let (payload, signature) = token.rsplit_once('.').ok_or(VerifyError::Malformed)?;
+	let (token_device_id, expiry) = payload.split_once('|').ok_or(VerifyError::Malformed)?;
+
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret).map_err(|_| VerifyError::Malformed)?;
+	mac.update(payload.as_bytes());
+	let signature_bytes = URL_SAFE_NO_PAD
+		.decode(signature)
+		.map_err(|_| VerifyError::BadSignature)?;
+	mac.verify_slice(&signature_bytes)
+		.map_err(|_| VerifyError::BadSignature)?;
+
+	if token_device_id != device_id {
+		return Err(VerifyError::WrongDevice);
+	}
+	let expiry: i64 = expiry.parse().map_err(|_| VerifyError::Malformed)?;
+	if Utc::now().timestamp() > expiry {
+		return Err(VerifyError::Expired);
+	}
+	Ok(())
+}
+
+fn sign(secret: &[u8], payload: &str) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac accepts any key length");
+	mac.update(payload.as_bytes());
+	URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
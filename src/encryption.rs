@@ -0,0 +1,109 @@
+//! Optional authenticated encryption-at-rest for stored segment bytes.
+//! Disabled unless a master key is configured, so a deployment that hasn't
+//! opted in keeps writing/reading plain compressed segments exactly as
+//! before. Wraps the *already-compressed* buffer with AES-256-GCM and a
+//! random per-segment nonce, so encryption never affects how segments are
+//! chunked, bloomed, or checksummed — it's one more envelope around the
+//! same bytes.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rand::RngCore;
+
+pub const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// A segment's ciphertext failed to authenticate (wrong key, truncated
+/// file, or bit-rot) — distinct from a decompression panic so the caller
+/// can treat it the same way as a checksum mismatch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentDecryptFailed {
+	pub segment_id: u32,
+}
+
+impl std::fmt::Display for SegmentDecryptFailed {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "segment {} failed authenticated decryption", self.segment_id)
+	}
+}
+
+impl std::error::Error for SegmentDecryptFailed {}
+
+/// Encrypts `plaintext` (the zstd-compressed segment buffer) under `key`
+/// with a fresh random nonce, returning `nonce || ciphertext || tag` ready
+/// to write straight to the `.log` file.
+pub fn encrypt(key: &[u8; KEY_LEN], plaintext: &[u8]) -> Vec<u8> {
+	let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+	let mut nonce_bytes = [0u8; NONCE_LEN];
+	rand::thread_rng().fill_bytes(&mut nonce_bytes);
+	let ciphertext = cipher
+		.encrypt(Nonce::from_slice(&nonce_bytes), plaintext)
+		.expect("AES-256-GCM encryption with a valid key/nonce cannot fail");
+	let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+	out.extend_from_slice(&nonce_bytes);
+	out.extend_from_slice(&ciphertext);
+	out
+}
+
+/// Decrypts bytes produced by [`encrypt`], verifying the AEAD tag.
+pub fn decrypt(key: &[u8; KEY_LEN], segment_id: u32, data: &[u8]) -> Result<Vec<u8>, SegmentDecryptFailed> {
+	if data.len() < NONCE_LEN {
+		return Err(SegmentDecryptFailed { segment_id });
+	}
+	let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
+	let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+	cipher
+		.decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+		.map_err(|_| SegmentDecryptFailed { segment_id })
+}
+
+/// Parses a hex-encoded master key (e.g. from `SEGMENT_ENCRYPTION_KEY`),
+/// hand-rolled since this is the only place in the crate that needs hex
+/// decoding and doesn't warrant a dependency on its own.
+pub fn parse_key_hex(hex: &str) -> Option<[u8; KEY_LEN]> {
+	if hex.len() != KEY_LEN * 2 {
+		return None;
+	}
+	let mut key = [0u8; KEY_LEN];
+	for (i, slot) in key.iter_mut().enumerate() {
+		*slot = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+	}
+	Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_trips_plaintext() {
+		let key = [7u8; KEY_LEN];
+		let plaintext = b"compressed segment bytes go here";
+		let encrypted = encrypt(&key, plaintext);
+		let decrypted = decrypt(&key, 1, &encrypted).unwrap();
+		assert_eq!(decrypted, plaintext);
+	}
+
+	#[test]
+	fn rejects_wrong_key() {
+		let encrypted = encrypt(&[1u8; KEY_LEN], b"secret bytes");
+		assert!(decrypt(&[2u8; KEY_LEN], 1, &encrypted).is_err());
+	}
+
+	#[test]
+	fn rejects_truncated_ciphertext() {
+		let key = [3u8; KEY_LEN];
+		assert!(decrypt(&key, 1, b"short").is_err());
+	}
+
+	#[test]
+	fn parses_valid_hex_key() {
+		let hex = "00".repeat(KEY_LEN);
+		assert_eq!(parse_key_hex(&hex), Some([0u8; KEY_LEN]));
+	}
+
+	#[test]
+	fn rejects_wrong_length_hex_key() {
+		assert_eq!(parse_key_hex("abcd"), None);
+	}
+}
@@ -0,0 +1,465 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::bloom::SegmentBloom;
+use crate::context::Context;
+use crate::db::NewLeveledSegmentArgs;
+use crate::segment::{compress_segment, LogSegment, SegmentMeta};
+use crate::types::{GetSegmentsQuery, SortDir};
+
+/// Level-compaction thresholds, read once from the environment into
+/// `Context` so an operator can tune write amplification vs. file count
+/// (e.g. for a device fleet with unusually large or small per-device
+/// volume) without a rebuild. Defaults match the values this compactor has
+/// always used.
+#[derive(Debug, Clone, Copy)]
+pub struct LevelCompactionConfig {
+	/// Segment count that triggers compacting a device's run at a level,
+	/// regardless of size.
+	pub count_threshold: usize,
+	/// Combined `compressed_size` target for level 0; each subsequent level
+	/// doubles the target, so a key is only ever rewritten O(number of
+	/// levels) times (classic leveled-compaction amplification tradeoff).
+	pub level_0_size_target: u64,
+	/// Highest level `run_once` will look at per pass. Promotions that
+	/// cascade past this are picked up by the next pass of the background
+	/// loop.
+	pub max_level: u32,
+	/// Cap, in compressed bytes, on how much of a level's backlog one
+	/// `merge_run` call is allowed to take as input. A device whose level
+	/// has grown far past its size target is compacted in several
+	/// size-bounded batches across (possibly several) passes rather than
+	/// one pass that reads and rewrites everything at once.
+	pub max_input_bytes: u64,
+	/// Bound on how many bytes of level-(N+2) segments a single level-(N+1)
+	/// output segment is allowed to overlap (by `[first_timestamp,
+	/// last_timestamp]` range). Without this, one compaction's output could
+	/// span a timestamp range wide enough to overlap most of the next
+	/// level down, making *that* level's next compaction re-read almost
+	/// everything it owns — unbounded write amplification two levels out.
+	/// Crossing the limit stops extending the current batch and starts a
+	/// new one instead.
+	pub grandparent_overlap_limit: u64,
+}
+
+impl Default for LevelCompactionConfig {
+	fn default() -> Self {
+		Self {
+			count_threshold: 8,
+			level_0_size_target: 64 * 1024 * 1024,
+			max_level: 6,
+			max_input_bytes: 512 * 1024 * 1024,
+			grandparent_overlap_limit: 256 * 1024 * 1024,
+		}
+	}
+}
+
+impl LevelCompactionConfig {
+	/// Reads `LEVEL_COMPACTION_COUNT_THRESHOLD`, `LEVEL_COMPACTION_L0_SIZE_TARGET_BYTES`
+	/// and `LEVEL_COMPACTION_MAX_LEVEL`, falling back to `Default` for anything unset
+	/// or unparseable.
+	pub fn from_env() -> Self {
+		let default = Self::default();
+		Self {
+			count_threshold: std::env::var("LEVEL_COMPACTION_COUNT_THRESHOLD")
+				.ok()
+				.and_then(|v| v.parse().ok())
+				.unwrap_or(default.count_threshold),
+			level_0_size_target: std::env::var("LEVEL_COMPACTION_L0_SIZE_TARGET_BYTES")
+				.ok()
+				.and_then(|v| v.parse().ok())
+				.unwrap_or(default.level_0_size_target),
+			max_level: std::env::var("LEVEL_COMPACTION_MAX_LEVEL")
+				.ok()
+				.and_then(|v| v.parse().ok())
+				.unwrap_or(default.max_level),
+			max_input_bytes: std::env::var("LEVEL_COMPACTION_MAX_INPUT_BYTES")
+				.ok()
+				.and_then(|v| v.parse().ok())
+				.unwrap_or(default.max_input_bytes),
+			grandparent_overlap_limit: std::env::var("LEVEL_COMPACTION_GRANDPARENT_OVERLAP_LIMIT_BYTES")
+				.ok()
+				.and_then(|v| v.parse().ok())
+				.unwrap_or(default.grandparent_overlap_limit),
+		}
+	}
+
+	fn size_target(&self, level: u32) -> u64 {
+		self.level_0_size_target
+			.saturating_mul(1u64 << level.min(self.max_level))
+	}
+
+	fn should_compact(&self, segs: &[SegmentMeta], level: u32) -> bool {
+		if segs.len() >= self.count_threshold {
+			return true;
+		}
+		let combined: u64 = segs.iter().map(|s| s.compressed_size as u64).sum();
+		combined > self.size_target(level)
+	}
+
+	/// Splits `segs` (sorted by `first_timestamp`) into one or more batches
+	/// for `merge_run`, so a single output segment never (a) exceeds
+	/// `max_input_bytes`, or (b) ends up overlapping more than
+	/// `grandparent_overlap_limit` bytes of `grandparent` (the level two
+	/// below `segs`, i.e. what the *next* compaction of the output level
+	/// would have to re-read). A batch is closed and a new one started as
+	/// soon as extending it would cross either bound, mirroring how
+	/// `size_target` bounds write amplification within a level but applied
+	/// across the level-pair that will eventually merge again.
+	fn bound_by_grandparent_overlap<'a>(
+		&self,
+		segs: &'a [SegmentMeta],
+		grandparent: &[SegmentMeta],
+	) -> Vec<&'a [SegmentMeta]> {
+		let mut batches = Vec::new();
+		let mut start = 0;
+		while start < segs.len() {
+			let mut end = start + 1;
+			let mut batch_bytes = segs[start].compressed_size as u64;
+			while end < segs.len() {
+				let candidate_bytes = batch_bytes + segs[end].compressed_size as u64;
+				if candidate_bytes > self.max_input_bytes {
+					break;
+				}
+				let first = segs[start].first_timestamp;
+				let last = segs[end].last_timestamp.max(segs[start].last_timestamp);
+				let overlap: u64 = grandparent
+					.iter()
+					.filter(|g| g.first_timestamp <= last && g.last_timestamp >= first)
+					.map(|g| g.compressed_size as u64)
+					.sum();
+				if overlap > self.grandparent_overlap_limit {
+					break;
+				}
+				batch_bytes = candidate_bytes;
+				end += 1;
+			}
+			batches.push(&segs[start..end]);
+			start = end;
+		}
+		batches
+	}
+}
+
+/// Merges level-N segments for a device into a single level-(N+1) segment
+/// whenever their count or combined size crosses a threshold, bounding the
+/// number of files `find_logs` must open per 24h window (an LSM-tree-style
+/// tiered/leveled compaction).
+pub struct LevelCompactor {
+	ctx: Arc<Context>,
+	config: LevelCompactionConfig,
+}
+
+impl LevelCompactor {
+	pub fn new(ctx: Arc<Context>) -> Self {
+		let config = ctx.level_compaction;
+		Self { ctx, config }
+	}
+
+	/// Decodes and merges `segs` (already sorted by `first_timestamp`) into
+	/// one new segment at `level`, durably written and committed before the
+	/// caller deletes the inputs, so an in-flight `find_logs` never observes
+	/// a missing file.
+	async fn merge_run(
+		&self,
+		device_id: &str,
+		level: u32,
+		segs: &[SegmentMeta],
+	) -> anyhow::Result<()> {
+		let started = Instant::now();
+		let input_bytes: u64 = segs.iter().map(|s| s.compressed_size as u64).sum();
+		let mut buffer = Vec::new();
+		let mut props = HashSet::new();
+		for seg in segs {
+			let bytes = match self.ctx.store.get(seg.id, seg.data_dir.as_deref()).await {
+				Ok(b) => b,
+				Err(err) => {
+					log::warn!(
+						"level compaction: cannot load segment {}: {}",
+						seg.id,
+						err
+					);
+					self.ctx.db.delete_segment(seg.id).await?;
+					self.ctx.segment_cache.invalidate(seg.id);
+					continue;
+				}
+			};
+			let bytes = if seg.encrypted {
+				let key = self.ctx.encryption_key().ok_or_else(|| {
+					anyhow::anyhow!(
+						"segment {} is encrypted but no SEGMENT_ENCRYPTION_KEY is configured",
+						seg.id
+					)
+				})?;
+				crate::encryption::decrypt(&key, seg.id, &bytes)?
+			} else {
+				bytes
+			};
+			let decoded = if seg.compressed {
+				let mut decoder = zstd::Decoder::new(std::io::Cursor::new(bytes))?;
+				LogSegment::parse(&mut decoder)
+			} else {
+				LogSegment::parse(&mut std::io::Cursor::new(bytes))
+			}
+			.unwrap_or_else(|err| {
+				log::warn!("level compaction: segment {} failed to parse: {}", seg.id, err);
+				err.recovered()
+			});
+			buffer.extend(decoded.buffer);
+			props.extend(self.ctx.db.fetch_segment_props(seg.id).await?);
+		}
+		if buffer.is_empty() {
+			return Ok(());
+		}
+
+		let merged = LogSegment::with_logs(buffer);
+		let first = merged.buffer.first().map(|l| l.timestamp).unwrap();
+		let last = merged.buffer.last().map(|l| l.timestamp).unwrap();
+		let logs_count = merged.buffer.len() as u64;
+
+		let mut plain = Vec::new();
+		merged.serialize(&mut plain);
+		let original_size = plain.len();
+
+		let (compressed, is_compressed) = compress_segment(&plain)?;
+		let compressed_size = compressed.len();
+
+		let new_id = self
+			.ctx
+			.db
+			.new_segment_at_level(NewLeveledSegmentArgs {
+				device_id: Some(device_id.to_string()),
+				level,
+				first_timestamp: first,
+				last_timestamp: last,
+				original_size,
+				compressed_size,
+				logs_count,
+			})
+			.await?;
+		self.ctx.db.upsert_segment_props(new_id, props.iter()).await?;
+		let mut bloom = SegmentBloom::with_expected_items(props.len());
+		for prop in &props {
+			bloom.insert(&format!("{}={}", prop.key, prop.value));
+		}
+		self.ctx.db.set_segment_bloom(new_id, bloom.to_bytes()).await?;
+		let compressed = match self.ctx.encryption_key() {
+			Some(key) => crate::encryption::encrypt(&key, &compressed),
+			None => compressed,
+		};
+		self.ctx
+			.db
+			.set_segment_encrypted(new_id, self.ctx.encryption_key().is_some())
+			.await?;
+		self.ctx
+			.db
+			.set_segment_checksum(new_id, crate::checksum::checksum(&compressed))
+			.await?;
+		if !is_compressed {
+			self.ctx.db.set_segment_compressed(new_id, false).await?;
+		}
+		let output_bytes = compressed.len() as u64;
+		let placed = self.ctx.store.put(new_id, compressed).await?;
+		self.ctx.record_segment_data_dir(new_id, placed).await;
+
+		log::info!(
+			"compacted {} level-{} segments for device {} into segment {} at level {}",
+			segs.len(),
+			level - 1,
+			device_id,
+			new_id,
+			level
+		);
+
+		for seg in segs {
+			self.ctx.db.delete_segment(seg.id).await?;
+			let _ = self.ctx.store.delete(seg.id, seg.data_dir.as_deref()).await;
+			self.ctx.segment_cache.invalidate(seg.id);
+		}
+
+		self.ctx.metrics.compactions_total.fetch_add(1, Ordering::Relaxed);
+		self.ctx
+			.metrics
+			.compaction_input_segments_total
+			.fetch_add(segs.len() as u64, Ordering::Relaxed);
+		self.ctx
+			.metrics
+			.compaction_input_bytes_total
+			.fetch_add(input_bytes, Ordering::Relaxed);
+		self.ctx
+			.metrics
+			.compaction_output_segments_total
+			.fetch_add(1, Ordering::Relaxed);
+		self.ctx
+			.metrics
+			.compaction_output_bytes_total
+			.fetch_add(output_bytes, Ordering::Relaxed);
+		self.ctx.metrics.compaction_duration.observe(started.elapsed());
+
+		Ok(())
+	}
+
+	pub async fn run_once(&self) -> anyhow::Result<bool> {
+		let mut processed = false;
+
+		for level in 0..=self.config.max_level {
+			let segments = self
+				.ctx
+				.db
+				.find_segments(&GetSegmentsQuery {
+					start: None,
+					end: None,
+					device_ids: None,
+					count: None,
+					sort: Some(SortDir::Asc),
+					level: Some(level),
+				})
+				.await?;
+
+			self.ctx
+				.metrics
+				.set_level_segment_count(level, segments.len() as u64);
+
+			let mut by_device: HashMap<String, Vec<SegmentMeta>> = HashMap::new();
+			for seg in segments {
+				if let Some(device_id) = seg.device_id.clone() {
+					by_device.entry(device_id).or_default().push(seg);
+				}
+			}
+
+			if by_device.is_empty() {
+				continue;
+			}
+
+			let grandparent_segments = self
+				.ctx
+				.db
+				.find_segments(&GetSegmentsQuery {
+					start: None,
+					end: None,
+					device_ids: None,
+					count: None,
+					sort: Some(SortDir::Asc),
+					level: Some(level + 2),
+				})
+				.await?;
+			let mut grandparent_by_device: HashMap<String, Vec<SegmentMeta>> = HashMap::new();
+			for seg in grandparent_segments {
+				if let Some(device_id) = seg.device_id.clone() {
+					grandparent_by_device.entry(device_id).or_default().push(seg);
+				}
+			}
+
+			for (device_id, mut segs) in by_device {
+				if !self.config.should_compact(&segs, level) {
+					continue;
+				}
+				segs.sort_by_key(|s| s.first_timestamp);
+				let grandparent = grandparent_by_device.get(&device_id).map(Vec::as_slice).unwrap_or(&[]);
+				for batch in self.config.bound_by_grandparent_overlap(&segs, grandparent) {
+					if batch.len() < 2 {
+						continue;
+					}
+					log::info!(
+						"compacting {} level-{} segments for device {}",
+						batch.len(),
+						level,
+						device_id
+					);
+					self.merge_run(&device_id, level + 1, batch).await?;
+					processed = true;
+				}
+			}
+		}
+
+		Ok(processed)
+	}
+}
+
+pub async fn run_level_compactor(ctx: Arc<Context>) {
+	let compactor = LevelCompactor::new(ctx);
+	loop {
+		if let Err(err) = compactor.run_once().await {
+			log::error!("level compaction failed: {}", err);
+		}
+		tokio::time::sleep(Duration::from_secs(60)).await;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::{Duration as ChronoDuration, Utc};
+
+	fn seg(id: u32, compressed_size: usize, offset_secs: i64) -> SegmentMeta {
+		let ts = Utc::now() + ChronoDuration::seconds(offset_secs);
+		SegmentMeta {
+			id,
+			device_id: Some("dev".into()),
+			first_timestamp: ts,
+			last_timestamp: ts,
+			original_size: compressed_size,
+			compressed_size,
+			logs_count: 1,
+			created_at: Utc::now(),
+			level: 0,
+			bloom: None,
+			checksum: None,
+			quarantined: false,
+			encrypted: false,
+			last_accessed: None,
+			pinned: false,
+			data_dir: None,
+			compressed: true,
+			last_scrubbed: None,
+		}
+	}
+
+	#[test]
+	fn splits_batch_when_grandparent_overlap_exceeded() {
+		let config = LevelCompactionConfig {
+			max_input_bytes: u64::MAX,
+			grandparent_overlap_limit: 150,
+			..LevelCompactionConfig::default()
+		};
+		let segs = vec![seg(1, 10, 0), seg(2, 10, 1), seg(3, 10, 2)];
+		// One grandparent segment overlapping the first two inputs' range,
+		// and a separate one overlapping only the third.
+		let grandparent = vec![seg(10, 100, 0), seg(11, 100, 2)];
+
+		let batches = config.bound_by_grandparent_overlap(&segs, &grandparent);
+
+		assert_eq!(batches.len(), 2);
+		assert_eq!(batches[0].iter().map(|s| s.id).collect::<Vec<_>>(), vec![1, 2]);
+		assert_eq!(batches[1].iter().map(|s| s.id).collect::<Vec<_>>(), vec![3]);
+	}
+
+	#[test]
+	fn splits_batch_when_max_input_bytes_exceeded() {
+		let config = LevelCompactionConfig {
+			max_input_bytes: 25,
+			grandparent_overlap_limit: u64::MAX,
+			..LevelCompactionConfig::default()
+		};
+		let segs = vec![seg(1, 10, 0), seg(2, 10, 1), seg(3, 10, 2)];
+
+		let batches = config.bound_by_grandparent_overlap(&segs, &[]);
+
+		assert_eq!(batches.len(), 2);
+		assert_eq!(batches[0].len(), 2);
+		assert_eq!(batches[1].len(), 1);
+	}
+
+	#[test]
+	fn keeps_one_batch_when_within_limits() {
+		let config = LevelCompactionConfig::default();
+		let segs = vec![seg(1, 10, 0), seg(2, 10, 1), seg(3, 10, 2)];
+
+		let batches = config.bound_by_grandparent_overlap(&segs, &[]);
+
+		assert_eq!(batches.len(), 1);
+		assert_eq!(batches[0].len(), 3);
+	}
+}
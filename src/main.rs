@@ -1,4 +1,5 @@
 use axum::extract::DefaultBodyLimit;
+use axum::middleware;
 use axum::routing::{delete, get, post};
 use axum::Router;
 use config::log_path;
@@ -7,28 +8,51 @@ use log::LevelFilter;
 use simple_logger::SimpleLogger;
 use std::sync::Arc;
 use tower_http::compression::CompressionLayer;
-use tower_http::cors::{AllowMethods, Any, CorsLayer};
 use tower_http::decompression::RequestDecompressionLayer;
 
+mod access_tracker;
+mod alert;
+mod auth;
+mod bloom;
 mod cache;
+mod cdc;
+mod checksum;
+mod chunk_manifest;
 mod cleanup;
+mod cluster;
 mod config;
+mod consistency_repair;
 mod context;
 mod controllers;
+mod cors;
+mod data_layout;
 mod db;
 mod dev_segment_merger;
 mod device_segment_compactor;
+mod device_token;
+mod encryption;
+mod level_compactor;
 mod logline;
+mod metrics;
+mod rate_limit;
+mod retention;
+mod retention_policy;
 mod schema;
+mod scrub;
+mod search;
 mod segment;
+mod segment_slot;
+mod segment_store;
+mod segment_writer;
 mod settings;
-mod slack;
 mod subscribe_worker;
+mod supervisor;
 mod types;
 mod upload;
 mod upload_guard;
 mod utility;
 mod wal;
+mod watch;
 
 #[tokio::main]
 async fn main() {
@@ -49,18 +73,24 @@ async fn main() {
 		}
 	}
 	let ctx = Arc::new(ctx);
+	ctx.workers.register(scrub::ScrubWorker::new(ctx.clone()));
+	ctx.workers
+		.register(access_tracker::AccessTrackerWorker::new(ctx.clone()));
 
 	tokio::spawn(upload::process_log_uploads(ctx.clone()));
+	for spec in watch::watch_specs_from_env() {
+		let ctx = ctx.clone();
+		tokio::spawn(async move { watch::run_watch(ctx, spec).await });
+	}
 	tokio::spawn(cleanup::run_disk_space_monitor(ctx.clone()));
 	tokio::spawn(dev_segment_merger::run_dev_segment_merger(ctx.clone()));
 	tokio::spawn(device_segment_compactor::run_device_segment_compactor(
 		ctx.clone(),
 	));
-
-	let cors = CorsLayer::new()
-		.allow_origin(Any) // Allow requests from any origin
-		.allow_methods(AllowMethods::any()) // Allowed HTTP methods
-		.allow_headers(Any);
+	tokio::spawn(level_compactor::run_level_compactor(ctx.clone()));
+	tokio::spawn(retention::run_retention_enforcer(ctx.clone()));
+	tokio::spawn(retention_policy::run_retention_policy_enforcer(ctx.clone()));
+	tokio::spawn(consistency_repair::run_consistency_repair(ctx.clone()));
 
 	// build our application with a route
 	let app = Router::new()
@@ -71,11 +101,17 @@ async fn main() {
 		.route("/favicon-192x192.png", get(controllers::favicon_192x192))
 		.route("/favicon-512x512.png", get(controllers::favicon_512x512))
 		.route("/manifest.json", get(controllers::manifest))
+		.route("/login/google", get(auth::login_google))
+		.route("/login/google/code", get(auth::login_google_callback))
+		.with_state(ctx.clone())
+		.route(
+			"/auth/service-account/token",
+			post(auth::service_account_token),
+		)
+		.with_state(ctx.clone())
 		.route("/api/logs", get(controllers::get_logs))
 		.layer(CompressionLayer::new())
-		.layer(cors.clone())
 		.route("/api/logs/stream", get(controllers::stream_logs))
-		.layer(cors.clone())
 		.route(
 			"/api/settings/query",
 			post(controllers::post_settings_query),
@@ -92,14 +128,17 @@ async fn main() {
 		.with_state(ctx.clone())
 		.route("/api/v1/validate_query", get(controllers::validate_query))
 		.route("/api/v1/logs/stream", get(controllers::stream_logs))
-		.layer(cors.clone())
 		.route("/api/v1/logs/histogram", get(controllers::get_histogram))
-		.layer(cors.clone())
+		.route("/api/v1/logs/batch", post(controllers::batch_get_logs))
+		.route("/api/v1/logs/export", post(controllers::export_logs))
+		.route(
+			"/api/v1/cluster/search",
+			post(controllers::cluster_search),
+		)
 		.route(
 			"/api/v1/device/{deviceId}/status",
 			get(controllers::get_device_status),
 		)
-		.layer(cors.clone())
 		.with_state(ctx.clone())
 		.route("/api/v1/device/{deviceId}", get(controllers::get_device))
 		.with_state(ctx.clone())
@@ -107,10 +146,14 @@ async fn main() {
 			"/api/v1/device/{deviceId}/logs",
 			post(controllers::upload_device_logs),
 		)
-		.layer(cors.clone())
 		.layer(DefaultBodyLimit::max(1024 * 1024 * 1000))
 		.layer(RequestDecompressionLayer::new().gzip(true).zstd(true))
 		.with_state(ctx.clone())
+		.route(
+			"/api/v1/device/{deviceId}/logs/manifest",
+			post(controllers::device_chunk_manifest),
+		)
+		.with_state(ctx.clone())
 		.route(
 			"/api/v1/device/{deviceId}/metadata",
 			post(controllers::update_device_metadata),
@@ -121,6 +164,11 @@ async fn main() {
 			post(controllers::update_device_settings),
 		)
 		.with_state(ctx.clone())
+		.route(
+			"/api/v1/device/{deviceId}/token",
+			post(controllers::mint_device_token),
+		)
+		.with_state(ctx.clone())
 		.route("/api/v1/device_bulkedit", post(controllers::bulk_edit))
 		.with_state(ctx.clone())
 		.route("/api/v1/settings", post(controllers::post_settings_query))
@@ -145,6 +193,11 @@ async fn main() {
 			post(controllers::clear_bucket_logs),
 		)
 		.with_state(ctx.clone())
+		.route(
+			"/api/v1/buckets/{bucketId}/poll",
+			get(controllers::poll_bucket),
+		)
+		.with_state(ctx.clone())
 		.route(
 			"/api/v1/buckets/{bucketId}",
 			delete(controllers::delete_bucket),
@@ -166,9 +219,48 @@ async fn main() {
 			delete(controllers::delete_segment),
 		)
 		.with_state(ctx.clone())
+		.route(
+			"/api/v1/segment/{segmentId}/pin",
+			post(controllers::pin_segment),
+		)
+		.with_state(ctx.clone())
+		.route(
+			"/api/v1/segment/{segmentId}/pin",
+			delete(controllers::unpin_segment),
+		)
+		.with_state(ctx.clone())
 		.route("/api/v1/server_info", get(controllers::get_server_info))
 		.with_state(ctx.clone())
-		.fallback(get(controllers::root));
+		.route("/metrics", get(controllers::get_metrics))
+		.with_state(ctx.clone())
+		.route(
+			"/api/v1/retention_policy",
+			get(controllers::get_retention_policy),
+		)
+		.with_state(ctx.clone())
+		.route(
+			"/api/v1/retention_policy",
+			post(controllers::put_retention_policy),
+		)
+		.with_state(ctx.clone())
+		.route(
+			"/api/v1/retention_policy/preview",
+			get(controllers::preview_retention),
+		)
+		.with_state(ctx.clone())
+		.route("/api/v1/cleanup/preview", get(controllers::preview_cleanup))
+		.with_state(ctx.clone())
+		.route("/api/v1/cleanup/status", get(controllers::get_cleanup_status))
+		.with_state(ctx.clone())
+		.route("/api/v1/cors_policy", get(controllers::get_cors_policy))
+		.with_state(ctx.clone())
+		.route("/api/v1/cors_policy", post(controllers::put_cors_policy))
+		.with_state(ctx.clone())
+		.fallback(get(controllers::root))
+		.layer(middleware::from_fn_with_state(
+			ctx.clone(),
+			cors::cors_middleware,
+		));
 
 	// run our app with hyper, listening globally on port 3000
 	let listener = tokio::net::TcpListener::bind("0.0.0.0:3337").await.unwrap();
@@ -31,7 +31,7 @@ impl DeviceMerger {
 			logs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
 			let first = logs.first().unwrap().timestamp;
 			let last = logs.last().unwrap().timestamp;
-			let mut seg = LogSegment { buffer: logs };
+			let mut seg = LogSegment::from_buffer(logs);
 			let mut buf = Vec::new();
 			seg.serialize(&mut buf);
 			let orig_size = buf.len();
@@ -56,7 +56,7 @@ impl DeviceMerger {
 				}
 				unique.insert(Prop {
 					key: "level".into(),
-					value: log.level.to_string(),
+					value: log.level.to_string().into(),
 				});
 			}
 			self.ctx
@@ -80,18 +80,22 @@ impl DeviceMerger {
 			};
 			log::info!("process segment {} from {}", seg.id, path.display());
 			let mut decoder = zstd::Decoder::new(file)?;
-			let log_seg = LogSegment::parse(&mut decoder);
+			let log_seg = LogSegment::parse(&mut decoder).unwrap_or_else(|err| {
+				log::warn!("segment {} failed to parse: {}", seg.id, err);
+				err.recovered()
+			});
 			for log in log_seg.buffer {
 				if let Some(prop) = log.props.iter().find(|p| p.key == "deviceId").cloned() {
-					let buf = self.buffers.entry(prop.value.clone()).or_default();
+					let buf = self.buffers.entry(prop.value.to_string()).or_default();
 					buf.push(log);
 					if buf.len() >= TARGET_SEGMENT_SIZE {
-						self.flush_device(&prop.value).await?;
+						self.flush_device(&prop.value.to_string()).await?;
 					}
 				}
 			}
 			self.ctx.db.delete_segment(seg.id).await?;
 			let _ = remove_file(path).await;
+			self.ctx.segment_cache.invalidate(seg.id);
 			processed = true;
 		}
 		let keys: Vec<String> = self.buffers.keys().cloned().collect();
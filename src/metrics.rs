@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Bucket boundaries (seconds) shared by the latency-style histograms below.
+const LATENCY_BOUNDS_SECS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// A fixed-bucket histogram in the Prometheus sense: every observation bumps
+/// all buckets whose bound it falls under, so each bucket already holds the
+/// cumulative count `render` needs to emit.
+pub struct Histogram {
+	bounds: &'static [f64],
+	buckets: Vec<AtomicU64>,
+	sum_micros: AtomicU64,
+	count: AtomicU64,
+}
+
+impl Histogram {
+	fn new(bounds: &'static [f64]) -> Self {
+		Self {
+			bounds,
+			buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+			sum_micros: AtomicU64::new(0),
+			count: AtomicU64::new(0),
+		}
+	}
+
+	pub fn observe(&self, elapsed: std::time::Duration) {
+		let secs = elapsed.as_secs_f64();
+		for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter()) {
+			if secs <= *bound {
+				bucket.fetch_add(1, Ordering::Relaxed);
+			}
+		}
+		self.sum_micros
+			.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+		self.count.fetch_add(1, Ordering::Relaxed);
+	}
+
+	fn render(&self, name: &str, out: &mut String) {
+		let _ = writeln!(out, "# TYPE {name} histogram");
+		for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter()) {
+			let _ = writeln!(
+				out,
+				"{name}_bucket{{le=\"{bound}\"}} {}",
+				bucket.load(Ordering::Relaxed)
+			);
+		}
+		let count = self.count.load(Ordering::Relaxed);
+		let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+		let sum_secs = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+		let _ = writeln!(out, "{name}_sum {sum_secs}");
+		let _ = writeln!(out, "{name}_count {count}");
+	}
+}
+
+/// Ingestion/query counters and histograms exposed at `/metrics` in the
+/// Prometheus text exposition format, so operators can scrape puppylog with
+/// standard monitoring stacks instead of polling `/api/v1/server_info`.
+pub struct Metrics {
+	pub logs_ingested: AtomicU64,
+	pub bytes_uploaded: AtomicU64,
+	pub segments_written: AtomicU64,
+	pub queries_served: AtomicU64,
+	pub sse_subscribers: AtomicI64,
+	pub query_latency: Histogram,
+	pub segment_scan: Histogram,
+	pub segment_cache_hits: AtomicU64,
+	pub segment_cache_misses: AtomicU64,
+	pub retention_bytes_reclaimed: AtomicU64,
+	pub retention_segments_evicted: AtomicU64,
+	/// Batches `save_logs` rejected because the ingest token bucket was
+	/// empty. A steadily climbing counter means the configured rate/burst
+	/// is too tight for real traffic, not just an occasional spike.
+	pub ingest_throttled: AtomicU64,
+	/// Gauges `run_disk_space_monitor` refreshes every cycle, aggregated
+	/// across every monitored data directory.
+	pub cleanup_disk_free_bytes: AtomicU64,
+	pub cleanup_disk_total_bytes: AtomicU64,
+	/// Free ratio * 1000 (i.e. permille), so the gauge can stay an integer;
+	/// `render` divides back down when emitting it.
+	pub cleanup_disk_free_ratio_permille: AtomicU64,
+	pub cleanup_last_run_freed_bytes: AtomicU64,
+	/// Bumped once per output segment the device or level compactor
+	/// persists, so a stalled compactor (backlog of small segments
+	/// growing) shows up as this counter going flat.
+	pub compactions_total: AtomicU64,
+	pub compaction_input_segments_total: AtomicU64,
+	pub compaction_output_segments_total: AtomicU64,
+	/// Compressed bytes read from inputs / written to outputs; their ratio
+	/// is the compaction's effective compression gain.
+	pub compaction_input_bytes_total: AtomicU64,
+	pub compaction_output_bytes_total: AtomicU64,
+	pub compaction_duration: Histogram,
+	/// Segment count per compaction level, last observed by
+	/// `LevelCompactor::run_once`. Level count is bounded by
+	/// `LevelCompactionConfig::max_level` (an operator setting, not
+	/// fleet-sized), so unlike per-device labels this always renders.
+	segments_per_level: Mutex<HashMap<u32, u64>>,
+}
+
+impl Metrics {
+	pub fn new() -> Self {
+		Self {
+			logs_ingested: AtomicU64::new(0),
+			bytes_uploaded: AtomicU64::new(0),
+			segments_written: AtomicU64::new(0),
+			queries_served: AtomicU64::new(0),
+			sse_subscribers: AtomicI64::new(0),
+			query_latency: Histogram::new(LATENCY_BOUNDS_SECS),
+			segment_scan: Histogram::new(LATENCY_BOUNDS_SECS),
+			segment_cache_hits: AtomicU64::new(0),
+			segment_cache_misses: AtomicU64::new(0),
+			retention_bytes_reclaimed: AtomicU64::new(0),
+			retention_segments_evicted: AtomicU64::new(0),
+			ingest_throttled: AtomicU64::new(0),
+			cleanup_disk_free_bytes: AtomicU64::new(0),
+			cleanup_disk_total_bytes: AtomicU64::new(0),
+			cleanup_disk_free_ratio_permille: AtomicU64::new(0),
+			cleanup_last_run_freed_bytes: AtomicU64::new(0),
+			compactions_total: AtomicU64::new(0),
+			compaction_input_segments_total: AtomicU64::new(0),
+			compaction_output_segments_total: AtomicU64::new(0),
+			compaction_input_bytes_total: AtomicU64::new(0),
+			compaction_output_bytes_total: AtomicU64::new(0),
+			compaction_duration: Histogram::new(LATENCY_BOUNDS_SECS),
+			segments_per_level: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Records the segment count `LevelCompactor::run_once` observed for
+	/// `level` on its latest pass, overwriting any prior value.
+	pub fn set_level_segment_count(&self, level: u32, count: u64) {
+		self.segments_per_level.lock().unwrap().insert(level, count);
+	}
+
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+		render_counter(
+			"puppylog_logs_ingested_total",
+			self.logs_ingested.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_bytes_uploaded_total",
+			self.bytes_uploaded.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_segments_written_total",
+			self.segments_written.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_queries_served_total",
+			self.queries_served.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_gauge(
+			"puppylog_sse_subscribers",
+			self.sse_subscribers.load(Ordering::Relaxed),
+			&mut out,
+		);
+		self.query_latency
+			.render("puppylog_query_latency_seconds", &mut out);
+		self.segment_scan
+			.render("puppylog_segment_scan_seconds", &mut out);
+		render_counter(
+			"puppylog_segment_cache_hits_total",
+			self.segment_cache_hits.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_segment_cache_misses_total",
+			self.segment_cache_misses.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_retention_bytes_reclaimed_total",
+			self.retention_bytes_reclaimed.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_retention_segments_evicted_total",
+			self.retention_segments_evicted.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_ingest_throttled_total",
+			self.ingest_throttled.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_gauge(
+			"puppylog_cleanup_disk_free_bytes",
+			self.cleanup_disk_free_bytes.load(Ordering::Relaxed) as i64,
+			&mut out,
+		);
+		render_gauge(
+			"puppylog_cleanup_disk_total_bytes",
+			self.cleanup_disk_total_bytes.load(Ordering::Relaxed) as i64,
+			&mut out,
+		);
+		let _ = writeln!(out, "# TYPE puppylog_cleanup_disk_free_ratio gauge");
+		let _ = writeln!(
+			out,
+			"puppylog_cleanup_disk_free_ratio {}",
+			self.cleanup_disk_free_ratio_permille.load(Ordering::Relaxed) as f64 / 1000.0
+		);
+		render_gauge(
+			"puppylog_cleanup_last_run_freed_bytes",
+			self.cleanup_last_run_freed_bytes.load(Ordering::Relaxed) as i64,
+			&mut out,
+		);
+		render_counter(
+			"puppylog_compactions_total",
+			self.compactions_total.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_compaction_input_segments_total",
+			self.compaction_input_segments_total.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_compaction_output_segments_total",
+			self.compaction_output_segments_total.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_compaction_input_bytes_total",
+			self.compaction_input_bytes_total.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_compaction_output_bytes_total",
+			self.compaction_output_bytes_total.load(Ordering::Relaxed),
+			&mut out,
+		);
+		self.compaction_duration
+			.render("puppylog_compaction_duration_seconds", &mut out);
+		let segments_per_level = self.segments_per_level.lock().unwrap();
+		if !segments_per_level.is_empty() {
+			let _ = writeln!(out, "# TYPE puppylog_segments_per_level gauge");
+			let mut levels: Vec<_> = segments_per_level.keys().copied().collect();
+			levels.sort_unstable();
+			for level in levels {
+				let _ = writeln!(
+					out,
+					"puppylog_segments_per_level{{level=\"{level}\"}} {}",
+					segments_per_level[&level]
+				);
+			}
+		}
+		drop(segments_per_level);
+		out
+	}
+}
+
+impl Default for Metrics {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+pub(crate) fn render_counter(name: &str, value: u64, out: &mut String) {
+	let _ = writeln!(out, "# TYPE {name} counter");
+	let _ = writeln!(out, "{name} {value}");
+}
+
+pub(crate) fn render_gauge(name: &str, value: i64, out: &mut String) {
+	let _ = writeln!(out, "# TYPE {name} gauge");
+	let _ = writeln!(out, "{name} {value}");
+}
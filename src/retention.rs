@@ -0,0 +1,230 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::time::sleep;
+
+use crate::context::Context;
+use crate::segment::SegmentMeta;
+use crate::settings::{EvictionOrder, RetentionPolicy};
+use crate::types::{GetSegmentsQuery, SortDir};
+
+const ENFORCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Oldest-first eviction over one list of segments against a single set of
+/// age/byte thresholds. Shared by `plan_evictions`'s global pass and each of
+/// its per-device passes, so both apply the same low-watermark hysteresis
+/// and `min_age_secs` protection logic rather than drifting apart.
+fn select_evictions<'a>(
+	segments: &[&'a SegmentMeta],
+	max_age_secs: Option<u64>,
+	max_total_bytes: Option<u64>,
+	low_watermark_bytes: Option<u64>,
+	min_age_secs: impl Fn(&SegmentMeta) -> u64,
+	now: DateTime<Utc>,
+) -> Vec<&'a SegmentMeta> {
+	if max_age_secs.is_none() && max_total_bytes.is_none() {
+		return Vec::new();
+	}
+
+	let mut remaining_bytes: u64 = segments.iter().map(|s| s.compressed_size as u64).sum();
+	// Once the high watermark (`max_total_bytes`) trips eviction, keep
+	// evicting down to the low watermark instead of stopping the instant
+	// we're no longer over the cap, so steady ingestion near the boundary
+	// doesn't thrash (evict one segment, dip under, immediately trip again).
+	let low_watermark = low_watermark_bytes.or(max_total_bytes);
+	let mut quota_tripped = false;
+	let mut evicted = Vec::new();
+
+	for seg in segments {
+		let age_secs = (now - seg.last_timestamp).num_seconds().max(0) as u64;
+		let age_exceeded = max_age_secs.is_some_and(|max| age_secs > max);
+		if max_total_bytes.is_some_and(|max| remaining_bytes > max) {
+			quota_tripped = true;
+		}
+		let quota_exceeded =
+			quota_tripped && low_watermark.is_some_and(|low| remaining_bytes > low);
+		if !age_exceeded && !quota_exceeded {
+			break;
+		}
+		if seg.pinned {
+			// Held via the pin endpoint regardless of age or quota pressure;
+			// still count its bytes, same as a per-device min-age override,
+			// so a pinned segment can't mask genuine quota pressure.
+			continue;
+		}
+		if age_secs < min_age_secs(seg) {
+			// Protected by a per-device override: leave it, but still count
+			// its bytes so a protected device can't mask genuine quota
+			// pressure.
+			continue;
+		}
+		remaining_bytes = remaining_bytes.saturating_sub(seg.compressed_size as u64);
+		evicted.push(*seg);
+	}
+
+	evicted
+}
+
+/// Computes which segments `policy` would evict right now, in the order
+/// `policy.eviction_order` selects, without touching the DB or filesystem.
+/// Shared by the enforcement loop and the dry-run endpoint so "preview" and
+/// "apply" can never disagree.
+///
+/// Applies the global `max_age_secs`/`max_total_bytes` cap first, then each
+/// device listed in `per_device_max_age_secs`/`per_device_max_total_bytes`
+/// against its own segments and budget — a per-device cap is enforced in
+/// addition to the global one, not instead of it, so a device can be given a
+/// tighter bound than the fleet-wide default without loosening that default.
+///
+/// A device's `retention_days` entry in `device_props` is folded into its
+/// per-device max age the same way: whichever of the `Settings` override and
+/// the prop is shorter wins, so a device can be told (e.g. by itself, via
+/// its own metadata) to expire faster without an operator touching
+/// `Settings`.
+pub async fn plan_evictions(
+	ctx: &Context,
+	policy: &RetentionPolicy,
+) -> anyhow::Result<Vec<SegmentMeta>> {
+	let device_prop_max_age: HashMap<String, u64> = ctx
+		.db
+		.device_retention_day_overrides()
+		.await?
+		.into_iter()
+		.map(|(device_id, days)| (device_id, days * 86400))
+		.collect();
+	let has_global = policy.max_age_secs.is_some() || policy.max_total_bytes.is_some();
+	let has_per_device = !policy.per_device_max_age_secs.is_empty()
+		|| !policy.per_device_max_total_bytes.is_empty()
+		|| !device_prop_max_age.is_empty();
+	if !policy.enabled || (!has_global && !has_per_device) {
+		return Ok(Vec::new());
+	}
+
+	let sort = match policy.eviction_order {
+		EvictionOrder::OldestFirst => SortDir::Asc,
+		EvictionOrder::LeastRecentlyUsed => SortDir::LastAccessedAsc,
+	};
+	let segments = ctx
+		.db
+		.find_segments(&GetSegmentsQuery {
+			start: None,
+			end: None,
+			device_ids: None,
+			count: None,
+			sort: Some(sort),
+			level: None,
+		})
+		.await?;
+
+	let now = Utc::now();
+	let min_age = |seg: &SegmentMeta| {
+		seg.device_id
+			.as_deref()
+			.and_then(|device_id| policy.per_device_min_age_secs.get(device_id))
+			.copied()
+			.unwrap_or(0)
+	};
+
+	let mut evicted_ids = HashSet::new();
+	let mut to_evict = Vec::new();
+
+	if has_global {
+		let refs: Vec<&SegmentMeta> = segments.iter().collect();
+		for seg in select_evictions(
+			&refs,
+			policy.max_age_secs,
+			policy.max_total_bytes,
+			policy.low_watermark_bytes,
+			min_age,
+			now,
+		) {
+			if evicted_ids.insert(seg.id) {
+				to_evict.push(seg.clone());
+			}
+		}
+	}
+
+	if has_per_device {
+		let mut by_device: HashMap<&str, Vec<&SegmentMeta>> = HashMap::new();
+		for seg in &segments {
+			if let Some(device_id) = seg.device_id.as_deref() {
+				by_device.entry(device_id).or_default().push(seg);
+			}
+		}
+		for (device_id, segs) in by_device {
+			let settings_max_age = policy.per_device_max_age_secs.get(device_id).copied();
+			let prop_max_age = device_prop_max_age.get(device_id).copied();
+			let max_age = match (settings_max_age, prop_max_age) {
+				(Some(a), Some(b)) => Some(a.min(b)),
+				(Some(a), None) => Some(a),
+				(None, Some(b)) => Some(b),
+				(None, None) => None,
+			};
+			let max_bytes = policy.per_device_max_total_bytes.get(device_id).copied();
+			if max_age.is_none() && max_bytes.is_none() {
+				continue;
+			}
+			for seg in select_evictions(&segs, max_age, max_bytes, max_bytes, min_age, now) {
+				if evicted_ids.insert(seg.id) {
+					to_evict.push(seg.clone());
+				}
+			}
+		}
+	}
+
+	Ok(to_evict)
+}
+
+/// Deletes a segment's DB row and its stored bytes, the same pair of steps
+/// `delete_segment` performs for a manual, operator-triggered delete.
+async fn evict_segment(ctx: &Context, segment_id: u32, data_dir: Option<&str>, compressed_size: u64) {
+	if let Err(e) = ctx.store.delete(segment_id, data_dir).await {
+		log::warn!("retention: failed to delete segment {}: {}", segment_id, e);
+	}
+	if let Err(e) = ctx.db.delete_segment(segment_id).await {
+		log::error!("retention: failed to delete segment {} from DB: {}", segment_id, e);
+	}
+	ctx.segment_cache.invalidate(segment_id);
+	ctx.metrics
+		.retention_bytes_reclaimed
+		.fetch_add(compressed_size, Ordering::Relaxed);
+	ctx.metrics
+		.retention_segments_evicted
+		.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Plans and applies one retention pass: evicts everything `plan_evictions`
+/// selects for the policy currently stored in `Settings`. Exposed on
+/// `Context` so tests can trigger enforcement deterministically instead of
+/// waiting on `run_retention_enforcer`'s interval.
+pub async fn enforce_retention(ctx: &Context) -> anyhow::Result<usize> {
+	let policy = ctx.settings.inner().await.retention_policy.clone();
+	let segments = plan_evictions(ctx, &policy).await?;
+	if !segments.is_empty() {
+		let total_bytes: u64 = segments.iter().map(|s| s.compressed_size as u64).sum();
+		log::info!(
+			"retention: evicting {} segment(s), reclaiming {} byte(s)",
+			segments.len(),
+			total_bytes
+		);
+		for seg in &segments {
+			evict_segment(ctx, seg.id, seg.data_dir.as_deref(), seg.compressed_size as u64).await;
+		}
+	}
+	Ok(segments.len())
+}
+
+/// Background task enforcing the retention policy stored in `Settings`,
+/// evicting in `policy.eviction_order` once the age or disk-quota threshold
+/// is crossed.
+pub async fn run_retention_enforcer(ctx: Arc<Context>) {
+	loop {
+		if let Err(e) = enforce_retention(&ctx).await {
+			log::error!("retention: failed to plan evictions: {}", e);
+		}
+		sleep(ENFORCE_INTERVAL).await;
+	}
+}
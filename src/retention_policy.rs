@@ -0,0 +1,52 @@
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::sleep;
+
+use crate::context::Context;
+
+const ENFORCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Runs one expiry pass over every enabled `SegmentRetentionPolicy` row and
+/// unlinks the on-disk bytes of whatever `DB::expire_segments` removed.
+///
+/// `DB::expire_segments` already deletes the DB rows and decrements the
+/// owning device's stats in its own transaction, so this only has to clean
+/// up storage and report the reclaim, mirroring the split `retention`'s
+/// `evict_segment` uses between the DB and `ctx.store`.
+pub async fn enforce_retention_policies(ctx: &Context) -> anyhow::Result<usize> {
+	let expired = ctx.db.expire_segments().await?;
+	if !expired.is_empty() {
+		log::info!("retention_policy: expiring {} segment(s)", expired.len());
+		for segment in &expired {
+			if let Err(e) = ctx.store.delete(segment.id, segment.data_dir.as_deref()).await {
+				log::warn!(
+					"retention_policy: failed to delete segment {}: {}",
+					segment.id,
+					e
+				);
+			}
+			ctx.segment_cache.invalidate(segment.id);
+			ctx.metrics
+				.retention_bytes_reclaimed
+				.fetch_add(segment.compressed_size as u64, Ordering::Relaxed);
+			ctx.metrics
+				.retention_segments_evicted
+				.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+	Ok(expired.len())
+}
+
+/// Background task enforcing the table-driven `SegmentRetentionPolicy` rows,
+/// complementing `retention::run_retention_enforcer`'s single global/
+/// per-device policy with any number of independently scoped rules.
+pub async fn run_retention_policy_enforcer(ctx: Arc<Context>) {
+	loop {
+		if let Err(e) = enforce_retention_policies(&ctx).await {
+			log::error!("retention_policy: failed to expire segments: {}", e);
+		}
+		sleep(ENFORCE_INTERVAL).await;
+	}
+}
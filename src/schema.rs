@@ -8,6 +8,7 @@ diesel::table! {
 		created_at -> Timestamp,
 		last_upload_at -> Nullable<Timestamp>,
 		send_interval -> Integer,
+		logs_compressed_size -> Nullable<BigInt>,
 	}
 }
 
@@ -30,6 +31,16 @@ diesel::table! {
 		compressed_size -> Nullable<BigInt>,
 		logs_count -> BigInt,
 		created_at -> Timestamp,
+		level -> Integer,
+		bloom -> Nullable<Binary>,
+		checksum -> Nullable<BigInt>,
+		quarantined -> Bool,
+		encrypted -> Bool,
+		last_accessed -> Nullable<Timestamp>,
+		pinned -> Bool,
+		data_dir -> Nullable<Text>,
+		compressed -> Bool,
+		last_scrubbed -> Nullable<Timestamp>,
 	}
 }
 
@@ -49,10 +60,42 @@ diesel::table! {
 	}
 }
 
+diesel::table! {
+	chunks (hash) {
+		hash -> Text,
+		blob -> Binary,
+		refcount -> Integer,
+	}
+}
+
+diesel::table! {
+	segment_chunks (segment_id, seq) {
+		segment_id -> Integer,
+		seq -> Integer,
+		chunk_hash -> Text,
+	}
+}
+
+diesel::table! {
+	retention_policies (id) {
+		id -> Integer,
+		device_id -> Nullable<Text>,
+		prop_key -> Nullable<Text>,
+		prop_value -> Nullable<Text>,
+		max_age_seconds -> Nullable<BigInt>,
+		max_total_bytes -> Nullable<BigInt>,
+		enabled -> Bool,
+		created_at -> Timestamp,
+	}
+}
+
 diesel::allow_tables_to_appear_in_same_query!(
 	devices,
 	device_props,
 	log_segments,
 	segment_props,
 	migrations,
+	retention_policies,
+	chunks,
+	segment_chunks,
 );
@@ -0,0 +1,271 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::alert::{self, Severity};
+use crate::context::Context;
+use crate::segment::{LogSegment, SegmentMeta};
+use crate::supervisor::{Worker, WorkerState};
+use crate::types::{GetSegmentsQuery, SortDir};
+
+/// Cross-checks a freshly decoded segment against the DB row that describes
+/// it, catching the case a bare checksum match can't: the file decodes
+/// cleanly but holds stale or swapped-in contents (e.g. a restore from the
+/// wrong backup) that no longer agree with `logs_count`/`first_timestamp`/
+/// `last_timestamp`.
+fn segment_matches_meta(seg: &LogSegment, meta: &SegmentMeta) -> bool {
+	if seg.buffer.len() as u64 != meta.logs_count {
+		return false;
+	}
+	match (seg.buffer.first(), seg.buffer.last()) {
+		(Some(first), Some(last)) => {
+			first.timestamp == meta.first_timestamp && last.timestamp == meta.last_timestamp
+		}
+		(None, None) => true,
+		_ => false,
+	}
+}
+
+/// Persisted progress so a restart resumes scrubbing where it left off
+/// instead of rescanning everything (or silently skipping a whole pass).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ScrubCursor {
+	last_segment_id: u32,
+}
+
+fn cursor_path(logs_path: &Path) -> PathBuf {
+	logs_path.join("scrub_cursor.json")
+}
+
+fn load_cursor(logs_path: &Path) -> ScrubCursor {
+	std::fs::read_to_string(cursor_path(logs_path))
+		.ok()
+		.and_then(|s| serde_json::from_str(&s).ok())
+		.unwrap_or_default()
+}
+
+fn save_cursor(logs_path: &Path, cursor: &ScrubCursor) {
+	if let Ok(json) = serde_json::to_string(cursor) {
+		let _ = std::fs::write(cursor_path(logs_path), json);
+	}
+}
+
+/// Summary emitted once a full pass over every segment completes.
+#[derive(Debug, Clone, Default)]
+pub struct ScrubReport {
+	pub scanned: u64,
+	pub corrupt: u64,
+	pub bytes_verified: u64,
+	pub elapsed: Duration,
+}
+
+/// How long to wait between segments so scrubbing stays in the background
+/// and doesn't compete with query/ingest I/O. Overridable with
+/// `SCRUB_TRANQUILITY_MS` for tests/tuning.
+fn tranquility() -> Duration {
+	std::env::var("SCRUB_TRANQUILITY_MS")
+		.ok()
+		.and_then(|v| v.parse::<u64>().ok())
+		.map(Duration::from_millis)
+		.unwrap_or(Duration::from_millis(500))
+}
+
+/// How long a successfully scrubbed segment is left alone before it's due
+/// for re-verification again, rather than every segment re-running each
+/// hourly pass regardless of how recently it was confirmed good. Overridable
+/// with `SCRUB_INTERVAL_SECS`.
+fn scrub_interval() -> Duration {
+	std::env::var("SCRUB_INTERVAL_SECS")
+		.ok()
+		.and_then(|v| v.parse::<u64>().ok())
+		.map(Duration::from_secs)
+		.unwrap_or(Duration::from_secs(86_400))
+}
+
+/// Walks WAL segments and main-store segments at a throttled rate,
+/// recomputing checksums and flagging corrupt or unreadable entries instead
+/// of only discovering rot at query time.
+pub struct ScrubWorker {
+	ctx: Arc<Context>,
+	cursor: ScrubCursor,
+	pass_started: Instant,
+	pass_scanned: u64,
+	pass_corrupt: u64,
+	pass_bytes: u64,
+	force: Arc<AtomicBool>,
+	status: String,
+}
+
+impl ScrubWorker {
+	pub fn new(ctx: Arc<Context>) -> Self {
+		let cursor = load_cursor(ctx.logs_path());
+		Self {
+			ctx,
+			cursor,
+			pass_started: Instant::now(),
+			pass_scanned: 0,
+			pass_corrupt: 0,
+			pass_bytes: 0,
+			force: Arc::new(AtomicBool::new(false)),
+			status: String::new(),
+		}
+	}
+
+	/// Handle an admin-triggered scrub: the next few ticks run back-to-back
+	/// without waiting for the idle interval between automatic passes.
+	pub fn trigger_handle(&self) -> Arc<AtomicBool> {
+		self.force.clone()
+	}
+
+	/// Next segment past the cursor that's either never been scrubbed or was
+	/// last confirmed good longer than `scrub_interval()` ago, so a full
+	/// pass stops re-verifying everything on every pass and instead only
+	/// re-checks what's actually due.
+	async fn next_segment_id(&self) -> Option<u32> {
+		let segments = self
+			.ctx
+			.db
+			.find_segments(&GetSegmentsQuery {
+				sort: Some(SortDir::Asc),
+				..Default::default()
+			})
+			.await
+			.unwrap_or_default();
+		let due_before = chrono::Utc::now() - chrono::Duration::from_std(scrub_interval()).unwrap_or_default();
+		segments
+			.into_iter()
+			.find(|s| s.id > self.cursor.last_segment_id && s.last_scrubbed.is_none_or(|t| t < due_before))
+			.map(|s| s.id)
+	}
+
+	async fn scrub_wal(&mut self) {
+		let corrupt = crate::wal::scrub_wal_segments();
+		self.pass_scanned += 1;
+		if corrupt > 0 {
+			self.pass_corrupt += corrupt;
+			let msg = format!("scrub: found {} corrupt WAL record(s)", corrupt);
+			log::warn!("{}", msg);
+			alert::notify_with(Severity::Error, &msg).await;
+		}
+	}
+
+	async fn scrub_segment(&mut self, id: u32) {
+		let meta = self.ctx.db.fetch_segment(id).await.ok();
+		let expected_checksum = meta.as_ref().and_then(|m| m.checksum);
+		let encrypted = meta.as_ref().is_some_and(|m| m.encrypted);
+		let compressed = meta.as_ref().is_none_or(|m| m.compressed);
+		let data_dir = meta.as_ref().and_then(|m| m.data_dir.as_deref());
+		let mut bytes_read = 0u64;
+		let ok = match self.ctx.store.get(id, data_dir).await {
+			Ok(bytes) => {
+				bytes_read = bytes.len() as u64;
+				let checksum_ok = expected_checksum
+					.map_or(true, |expected| crate::checksum::checksum(&bytes) == expected);
+				let decrypted = if encrypted {
+					self.ctx
+						.encryption_key()
+						.and_then(|key| crate::encryption::decrypt(&key, id, &bytes).ok())
+				} else {
+					Some(bytes)
+				};
+				checksum_ok
+					&& match decrypted {
+						Some(bytes) => {
+							let parsed = if compressed {
+								zstd::Decoder::new(std::io::Cursor::new(bytes))
+									.ok()
+									.and_then(|mut decoder| LogSegment::parse(&mut decoder).ok())
+							} else {
+								LogSegment::parse(&mut std::io::Cursor::new(bytes)).ok()
+							};
+							match parsed {
+								Some(seg) => match &meta {
+									Some(m) => segment_matches_meta(&seg, m),
+									None => true,
+								},
+								None => false,
+							}
+						}
+						None => false,
+					}
+			}
+			Err(_) => false,
+		};
+		self.pass_scanned += 1;
+		self.pass_bytes += bytes_read;
+		if !ok {
+			self.pass_corrupt += 1;
+			let msg = format!("scrub: segment {} is corrupt or unreadable, quarantining", id);
+			log::warn!("{}", msg);
+			alert::notify_with(Severity::Error, &msg).await;
+			if let Err(err) = self.ctx.db.quarantine_segment(id).await {
+				log::error!("scrub: failed to quarantine segment {}: {}", id, err);
+			}
+			if let Err(err) = self.ctx.store.quarantine(id, data_dir).await {
+				log::error!("scrub: failed to move segment {} to quarantine storage: {}", id, err);
+			}
+			self.ctx.segment_cache.invalidate(id);
+		} else if let Err(err) = self.ctx.db.set_segment_last_scrubbed(id, chrono::Utc::now()).await {
+			log::error!("scrub: failed to record last-scrubbed time for segment {}: {}", id, err);
+		}
+		self.cursor.last_segment_id = id;
+		save_cursor(self.ctx.logs_path(), &self.cursor);
+	}
+
+	fn finish_pass(&mut self) {
+		let report = ScrubReport {
+			scanned: self.pass_scanned,
+			corrupt: self.pass_corrupt,
+			bytes_verified: self.pass_bytes,
+			elapsed: self.pass_started.elapsed(),
+		};
+		log::info!(
+			"scrub pass complete: {} scanned, {} corrupt, {} bytes verified, took {:?}",
+			report.scanned,
+			report.corrupt,
+			report.bytes_verified,
+			report.elapsed
+		);
+		self.status = format!(
+			"last pass: {} scanned, {} corrupt, {} bytes verified ({:?})",
+			report.scanned, report.corrupt, report.bytes_verified, report.elapsed
+		);
+		self.cursor.last_segment_id = 0;
+		self.pass_started = Instant::now();
+		self.pass_scanned = 0;
+		self.pass_corrupt = 0;
+		self.pass_bytes = 0;
+	}
+}
+
+#[async_trait::async_trait]
+impl Worker for ScrubWorker {
+	fn name(&self) -> &str {
+		"scrub"
+	}
+
+	async fn work(&mut self) -> WorkerState {
+		self.scrub_wal().await;
+		match self.next_segment_id().await {
+			Some(id) => {
+				self.scrub_segment(id).await;
+				if self.force.swap(false, Ordering::SeqCst) {
+					WorkerState::Busy
+				} else {
+					WorkerState::Idle(tranquility())
+				}
+			}
+			None => {
+				self.finish_pass();
+				WorkerState::Idle(Duration::from_secs(3600))
+			}
+		}
+	}
+
+	fn status(&self) -> String {
+		self.status.clone()
+	}
+}
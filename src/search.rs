@@ -1,17 +1,23 @@
 use chrono::{DateTime, Utc};
-use puppylog::{check_expr, check_props, extract_device_ids, timestamp_bounds, LogEntry, QueryAst};
-use std::collections::HashSet;
-use std::fs::File;
-use std::path::Path;
+use futures::StreamExt;
+use puppylog::{
+	check_expr, check_props, extract_device_ids, extract_equality_props, simplify,
+	timestamp_bounds, Expr, LogEntry, Prop, QueryAst,
+};
+use std::collections::{BinaryHeap, HashSet};
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 use tokio::time::timeout;
 
 use serde::Serialize;
 use tokio::sync::Mutex;
 
-use crate::db::DB;
-use crate::segment::LogSegment;
+use crate::access_tracker::AccessTracker;
+use crate::bloom::SegmentBloom;
+use crate::cluster::PeerClient;
+use crate::db::{NewSegmentArgs, DB};
+use crate::segment::{compress_segment, estimate_entry_size, LogSegment, SegmentMeta};
+use crate::segment_store::SegmentStore;
 use crate::types::GetSegmentsQuery;
 
 #[derive(Debug, Clone, Serialize)]
@@ -34,11 +40,109 @@ pub struct SearchProgress {
 	pub status: Option<String>,
 }
 
+/// Resumable position in a reverse-chronological search, emitted as
+/// `LogStreamItem::Cursor` once `LogSearcher::limit` matches have been sent.
+/// Feeding this back in as `LogSearcher::cursor` on the next call picks up
+/// exactly where this one stopped: concatenating pages built from successive
+/// cursors reproduces the full unpaginated result with no gaps or
+/// duplicates, even across the memory→archive boundary.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchCursor {
+	pub timestamp: DateTime<Utc>,
+	/// `0` is the in-memory buffer; any other value is an archived segment's
+	/// id. Real segment ids start at 1 (`AUTOINCREMENT`), so `0` never
+	/// collides with one.
+	pub segment_id: u32,
+	pub logs_seen_in_segment: u64,
+}
+
 #[derive(Debug, Clone)]
 pub enum LogStreamItem {
 	Entry(LogEntry),
 	SegmentProgress(SegmentProgress),
 	SearchProgress(SearchProgress),
+	Cursor(SearchCursor),
+	/// Sent once, right before a `Subscribe`/`SubscribeFuture` search starts
+	/// tailing `live_tail`, so a client can tell "this is every historical
+	/// match" from "anything from here on is arriving live" without having
+	/// to guess from a gap in traffic.
+	Tail,
+	/// One match from `LogSearcher::search_batch`, tagged with the position
+	/// (in the `queries` vec that call was given) of the query it matched,
+	/// so a caller driving several dashboard panels off one batch call can
+	/// demultiplex the merged stream back into per-panel results.
+	BatchEntry { batch_index: usize, entry: LogEntry },
+}
+
+/// Sentinel `segment_id` a `SearchCursor` uses for the in-memory buffer,
+/// since it isn't a real, numbered segment.
+const MEMORY_SEGMENT_ID: u32 = 0;
+
+/// Metadata for one `.log` file `LogSearcher::search_to_segment` wrote, so a
+/// caller can list/link what was exported without decompressing and parsing
+/// each one just to show a human what it covers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedSegment {
+	pub segment_id: u32,
+	pub first_timestamp: DateTime<Utc>,
+	pub last_timestamp: DateTime<Utc>,
+	pub logs_count: u64,
+	pub original_size: usize,
+	pub compressed_size: usize,
+}
+
+/// One candidate entry in a k-way merge: across a window's concurrently
+/// decoded segments in `search_local`, or across the local store and peer
+/// nodes in `search_cluster`. Ordered purely by timestamp so a plain
+/// `BinaryHeap` (a max-heap) already pops the overall-newest remaining entry
+/// first, which is the order `search` streams results in — no `Reverse`
+/// wrapper needed. `seg_pos` is only meaningful for the archive-segment
+/// merge, where it feeds a resumable `SearchCursor`; `search_cluster` just
+/// sets it to `0`.
+struct MergeItem {
+	timestamp: DateTime<Utc>,
+	source: usize,
+	seg_pos: u64,
+	entry: LogEntry,
+}
+
+impl PartialEq for MergeItem {
+	fn eq(&self, other: &Self) -> bool {
+		self.timestamp == other.timestamp
+	}
+}
+
+impl Eq for MergeItem {}
+
+impl PartialOrd for MergeItem {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for MergeItem {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		self.timestamp.cmp(&other.timestamp)
+	}
+}
+
+/// How far `LogSearcher::search` goes before returning.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StreamMode {
+	/// Drain the in-memory buffer and archived segments, then return. The
+	/// original, one-shot behavior.
+	#[default]
+	Snapshot,
+	/// Run the historical scan as usual, then emit `LogStreamItem::Tail` and
+	/// keep the stream open, pushing newly ingested entries that match the
+	/// query as they arrive — a log tail seeded with history.
+	Subscribe,
+	/// Skip the historical scan entirely, emit `LogStreamItem::Tail`
+	/// immediately, and only stream entries ingested after the search
+	/// starts — a log tail with no backlog.
+	SubscribeFuture,
 }
 
 fn calculate_logs_per_second(processed_logs: u64, search_start: Instant) -> f64 {
@@ -78,36 +182,247 @@ fn should_emit_progress(processed_logs: u64, last_emit: &Instant) -> bool {
 pub struct LogSearcher<'a> {
 	pub db: &'a DB,
 	pub current: &'a Mutex<LogSegment>,
-	pub logs_path: &'a Path,
+	pub store: &'a dyn SegmentStore,
 	/// How wide the archive search window is when walking backwards.
 	pub window: chrono::Duration,
+	/// AES-256-GCM master key for reading encrypted segments, or `None` if
+	/// encryption-at-rest isn't configured on this node.
+	pub encryption_key: Option<[u8; crate::encryption::KEY_LEN]>,
+	/// Records which archived segments this search actually reads, so LRU
+	/// retention can tell them apart from segments nobody queries. `None` in
+	/// tests that don't exercise retention.
+	pub access_tracker: Option<&'a AccessTracker>,
+	/// `Snapshot` by default; set to `Subscribe`/`SubscribeFuture` to keep
+	/// the search open past the historical scan. Mutate directly on the
+	/// returned searcher, the same way tests override `window`.
+	pub mode: StreamMode,
+	/// Broadcasts every newly ingested `LogEntry`, so `Subscribe` and
+	/// `SubscribeFuture` have something to `subscribe()` once the
+	/// historical scan (if any) is done. `None` disables tailing even if
+	/// `mode` asks for it, which is what tests that don't wire up ingestion
+	/// want.
+	pub live_tail: Option<&'a broadcast::Sender<LogEntry>>,
+	/// Stop after streaming this many matches and emit a resumable
+	/// `LogStreamItem::Cursor` instead of draining the rest. `None` streams
+	/// every match, the original behavior.
+	pub limit: Option<usize>,
+	/// Resumes a paginated search from a cursor an earlier, `limit`-bounded
+	/// call returned. Clamps the reverse walk's `end` to `cursor.timestamp`,
+	/// skips segments already fully consumed by that earlier call, and
+	/// within the segment the cursor paused in, skips its first
+	/// `logs_seen_in_segment` entries.
+	pub cursor: Option<SearchCursor>,
+	/// Other cluster nodes `search` fans the same query out to, merging their
+	/// results in with the local store's. Empty by default, which is what
+	/// every test and any node run without `CLUSTER_PEERS` configured wants:
+	/// `search` behaves exactly as it did before cluster support existed.
+	pub peers: &'a [PeerClient],
 }
 
 impl<'a> LogSearcher<'a> {
-	pub fn new(db: &'a DB, current: &'a Mutex<LogSegment>, logs_path: &'a Path) -> Self {
+	pub fn new(
+		db: &'a DB,
+		current: &'a Mutex<LogSegment>,
+		store: &'a dyn SegmentStore,
+		encryption_key: Option<[u8; crate::encryption::KEY_LEN]>,
+		access_tracker: Option<&'a AccessTracker>,
+	) -> Self {
 		Self {
 			db,
 			current,
-			logs_path,
+			store,
 			window: chrono::Duration::hours(24),
+			encryption_key,
+			access_tracker,
+			mode: StreamMode::default(),
+			live_tail: None,
+			limit: None,
+			cursor: None,
+			peers: &[],
 		}
 	}
 
+	/// Runs `query` against the local store and, when `self.peers` isn't
+	/// empty, against every peer in it too — fanning the same `QueryAst` out
+	/// over HTTP and merging the results back into one globally
+	/// reverse-chronological stream. With an empty peer list (the default)
+	/// this is exactly `search_local`, so every existing caller sees no
+	/// behavior change.
 	pub async fn search(
 		&self,
 		query: QueryAst,
 		tx: &mpsc::Sender<LogStreamItem>,
+	) -> anyhow::Result<()> {
+		if self.peers.is_empty() {
+			return self.search_local(query, tx).await;
+		}
+		self.search_cluster(query, tx).await
+	}
+
+	/// Fans `query` out to the local store and every configured peer
+	/// concurrently, then k-way merges the resulting `LogEntry` streams by
+	/// timestamp (newest first, matching `search_local`'s own order) so a
+	/// caller can't tell which node a given match came from. A peer that
+	/// errors or times out is logged and simply contributes no further
+	/// entries — the merge carries on with whatever sources are still open.
+	///
+	/// Unlike `search_local`, this doesn't support `self.cursor`/pagination
+	/// across the cluster: `self.limit` still stops the stream early, but no
+	/// `LogStreamItem::Cursor` is emitted, since a resumable position that
+	/// spans multiple independently-paginated nodes isn't well-defined yet.
+	async fn search_cluster(
+		&self,
+		query: QueryAst,
+		tx: &mpsc::Sender<LogStreamItem>,
+	) -> anyhow::Result<()> {
+		// The local store is just one more merge source, but `search_local`
+		// speaks `LogStreamItem` (progress/cursor/tail included) rather than
+		// plain entries, so it gets its own channel plus a small pump that
+		// forwards everything that isn't an `Entry` straight through to `tx`
+		// and feeds matches into the same `LogEntry` shape every peer uses.
+		let (local_stream_tx, mut local_stream_rx) = mpsc::channel::<LogStreamItem>(100);
+		let local_query = query.clone();
+		let local_search_fut = self.search_local(local_query, &local_stream_tx);
+
+		let (local_entry_tx, local_entry_rx) = mpsc::channel::<LogEntry>(100);
+		let pump_local_fut = async {
+			while let Some(item) = local_stream_rx.recv().await {
+				match item {
+					LogStreamItem::Entry(entry) => {
+						if local_entry_tx.send(entry).await.is_err() {
+							break;
+						}
+					}
+					other => {
+						if tx.send(other).await.is_err() {
+							break;
+						}
+					}
+				}
+			}
+		};
+
+		let mut sources: Vec<mpsc::Receiver<LogEntry>> = Vec::with_capacity(1 + self.peers.len());
+		sources.push(local_entry_rx);
+		let mut peer_futs = Vec::with_capacity(self.peers.len());
+		for peer in self.peers {
+			let (peer_tx, peer_rx) = mpsc::channel::<LogEntry>(100);
+			sources.push(peer_rx);
+			let query = query.clone();
+			peer_futs.push(async move { peer.search(&query, &peer_tx).await });
+		}
+
+		let merge_fut = async {
+			let mut matches_sent: u64 = 0;
+			let mut heap: BinaryHeap<MergeItem> = BinaryHeap::new();
+			for (source, rx) in sources.iter_mut().enumerate() {
+				if let Some(entry) = rx.recv().await {
+					heap.push(MergeItem {
+						timestamp: entry.timestamp,
+						source,
+						seg_pos: 0,
+						entry,
+					});
+				}
+			}
+			while let Some(item) = heap.pop() {
+				if tx.is_closed() {
+					break;
+				}
+				if tx.send(LogStreamItem::Entry(item.entry)).await.is_err() {
+					break;
+				}
+				matches_sent += 1;
+				if self.limit.is_some_and(|l| matches_sent as usize >= l) {
+					break;
+				}
+				if let Some(entry) = sources[item.source].recv().await {
+					heap.push(MergeItem {
+						timestamp: entry.timestamp,
+						source: item.source,
+						seg_pos: 0,
+						entry,
+					});
+				}
+			}
+		};
+
+		let _ = tokio::join!(
+			local_search_fut,
+			pump_local_fut,
+			futures::future::join_all(peer_futs),
+			merge_fut,
+		);
+		Ok(())
+	}
+
+	/// Runs every query in `queries` concurrently (each one is a full
+	/// `search`, so peers/cursor/limit all behave exactly as a standalone
+	/// call would) and forwards every match into `tx` tagged with its
+	/// position in `queries` via `LogStreamItem::BatchEntry`, so a dashboard
+	/// rendering several panels off the same store can submit them as one
+	/// call instead of resubmitting the same historical scan N times.
+	///
+	/// This doesn't (yet) share a single decode pass across queries the way
+	/// `search_local`'s own archive scan shares one pass across segments in
+	/// a window: each query keeps its own time bounds, device filter, and
+	/// bloom/prop pruning, since those depend on that query's own
+	/// predicates. Progress/cursor/tail chatter from the underlying
+	/// per-query `search` calls isn't forwarded — same as `search_cluster`,
+	/// those aren't well-defined once several queries' results interleave.
+	pub async fn search_batch(
+		&self,
+		queries: Vec<QueryAst>,
+		tx: &mpsc::Sender<LogStreamItem>,
+	) -> anyhow::Result<()> {
+		let runs = queries.into_iter().enumerate().map(|(batch_index, query)| async move {
+			let (inner_tx, mut inner_rx) = mpsc::channel(100);
+			let search_fut = self.search(query, &inner_tx);
+			let forward_fut = async {
+				while let Some(item) = inner_rx.recv().await {
+					if let LogStreamItem::Entry(entry) = item {
+						if tx
+							.send(LogStreamItem::BatchEntry { batch_index, entry })
+							.await
+							.is_err()
+						{
+							break;
+						}
+					}
+				}
+			};
+			let (result, _) = tokio::join!(search_fut, forward_fut);
+			result
+		});
+		for result in futures::future::join_all(runs).await {
+			result?;
+		}
+		Ok(())
+	}
+
+	async fn search_local(
+		&self,
+		query: QueryAst,
+		tx: &mpsc::Sender<LogStreamItem>,
 	) -> anyhow::Result<()> {
 		let search_start = Instant::now();
 		let mut processed_logs: u64 = 0;
 		let mut last_progress_emit = search_start;
 
 		let mut end = query.end_date.unwrap_or(Utc::now());
-		let device_ids = extract_device_ids(&query.root);
+		// Simplify before pushdown extraction so a query padded with
+		// always-true/always-false noise (`(1=1) and timestamp > X`) still
+		// yields a usable device-id/prop/timestamp bound.
+		let simplified_root = simplify(&query.root);
+		let device_ids = extract_device_ids(&simplified_root);
+		let equality_props: Vec<String> = extract_equality_props(&simplified_root)
+			.iter()
+			.map(|p| format!("{}={}", p.key, p.value))
+			.collect();
 		let tz = query
 			.tz_offset
 			.unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
-		let (start_bound, end_bound) = timestamp_bounds(&query.root);
+		let (start_bound, end_bound) = timestamp_bounds(&simplified_root);
 		log::info!(
 			"start_bound = {:?}, end_bound = {:?}",
 			start_bound,
@@ -118,271 +433,400 @@ impl<'a> LogSearcher<'a> {
 				end = e;
 			}
 		}
+		if let Some(cursor) = &self.cursor {
+			if cursor.timestamp < end {
+				end = cursor.timestamp;
+			}
+		}
 
-		// 1) Search in-memory buffer (with timeout on the lock)
-		match timeout(Duration::from_millis(100), self.current.lock()).await {
-			Ok(current) => {
-				let mut end = end;
-				let iter = current.iter();
-				for entry in iter {
-					if tx.is_closed() {
-						return Ok(());
+		// How many matches have been streamed so far, and whether we're still
+		// looking for the segment `self.cursor` paused in (once found, every
+		// later segment is new ground and gets processed in full).
+		let mut matches_sent: u64 = 0;
+		let mut resuming = self.cursor.is_some();
+
+		if !matches!(self.mode, StreamMode::SubscribeFuture) {
+			// 1) Search in-memory buffer (with timeout on the lock)
+			let memory_skip = if resuming {
+				match &self.cursor {
+					Some(c) if c.segment_id == MEMORY_SEGMENT_ID => {
+						resuming = false;
+						Some(c.logs_seen_in_segment)
 					}
-					processed_logs += 1;
-					if should_emit_progress(processed_logs, &last_progress_emit) {
-						let speed = calculate_logs_per_second(processed_logs, search_start);
-						if send_search_progress(tx, processed_logs, speed, None).await {
-							return Ok(());
+					// Paused in an archived segment, so the (always newer)
+					// in-memory buffer was already fully sent.
+					_ => None,
+				}
+			} else {
+				Some(0)
+			};
+			if let Some(skip_n) = memory_skip {
+				match timeout(Duration::from_millis(100), self.current.lock()).await {
+					Ok(current) => {
+						let mut end = end;
+						let mut seg_pos: u64 = 0;
+						let iter = current.iter();
+						for entry in iter {
+							if tx.is_closed() {
+								return Ok(());
+							}
+							seg_pos += 1;
+							if seg_pos <= skip_n {
+								continue;
+							}
+							processed_logs += 1;
+							if should_emit_progress(processed_logs, &last_progress_emit) {
+								let speed = calculate_logs_per_second(processed_logs, search_start);
+								if send_search_progress(tx, processed_logs, speed, None).await {
+									return Ok(());
+								}
+								last_progress_emit = Instant::now();
+							}
+							if entry.timestamp > end {
+								continue;
+							}
+							if let Some(start) = start_bound {
+								if entry.timestamp < start {
+									continue;
+								}
+							}
+							end = entry.timestamp;
+							match check_expr(&query.root, entry, &tz) {
+								Ok(true) => {}
+								_ => continue,
+							}
+							if tx.send(LogStreamItem::Entry(entry.clone())).await.is_err() {
+								return Ok(());
+							}
+							matches_sent += 1;
+							if self.limit.is_some_and(|l| matches_sent as usize >= l) {
+								let _ = tx
+									.send(LogStreamItem::Cursor(SearchCursor {
+										timestamp: entry.timestamp,
+										segment_id: MEMORY_SEGMENT_ID,
+										logs_seen_in_segment: seg_pos,
+									}))
+									.await;
+								return Ok(());
+							}
 						}
-						last_progress_emit = Instant::now();
-					}
-					if entry.timestamp > end {
-						continue;
 					}
-					if let Some(start) = start_bound {
-						if entry.timestamp < start {
-							continue;
+					Err(_) => {
+						if last_progress_emit.elapsed() >= Duration::from_millis(500) {
+							let speed = calculate_logs_per_second(processed_logs, search_start);
+							if send_search_progress(
+								tx,
+								processed_logs,
+								speed,
+								Some("waiting for in-memory log buffer"),
+							)
+							.await
+							{
+								return Ok(());
+							}
+							last_progress_emit = Instant::now();
 						}
 					}
-					end = entry.timestamp;
-					match check_expr(&query.root, entry, &tz) {
-						Ok(true) => {}
-						_ => continue,
-					}
-					if tx.send(LogStreamItem::Entry(entry.clone())).await.is_err() {
-						return Ok(());
-					}
-				}
-			}
-			Err(_) => {
-				if last_progress_emit.elapsed() >= Duration::from_millis(500) {
-					let speed = calculate_logs_per_second(processed_logs, search_start);
-					if send_search_progress(
-						tx,
-						processed_logs,
-						speed,
-						Some("waiting for in-memory log buffer"),
-					)
-					.await
-					{
-						return Ok(());
-					}
-					last_progress_emit = Instant::now();
 				}
 			}
-		}
 
-		// 2) Search archived segments on disk
-		log::info!("looking from archive");
-		let window = self.window;
-		let mut prev_end: Option<DateTime<Utc>> = Some(end);
-		let mut processed_segments: HashSet<u32> = HashSet::new();
-		log::info!("prev_end: {:?}", prev_end);
+			// 2) Search archived segments on disk
+			log::info!("looking from archive");
+			let window = self.window;
+			let mut prev_end: Option<DateTime<Utc>> = Some(end);
+			let mut processed_segments: HashSet<u32> = HashSet::new();
+			log::info!("prev_end: {:?}", prev_end);
 
-		'outer: loop {
-			if tx.is_closed() {
-				break;
-			}
-			let current_prev = match prev_end {
-				Some(ts) => ts,
-				None => {
-					log::info!("no previous end; stopping");
+			'outer: loop {
+				if tx.is_closed() {
 					break;
 				}
-			};
-			let end_exists = self
-				.db
-				.segment_exists_at(
-					current_prev,
-					if device_ids.is_empty() {
-						None
-					} else {
-						Some(&device_ids)
-					},
-				)
-				.await?;
-			let mut end = if end_exists {
-				current_prev
-			} else {
-				match self
+				let current_prev = match prev_end {
+					Some(ts) => ts,
+					None => {
+						log::info!("no previous end; stopping");
+						break;
+					}
+				};
+				let end_exists = self
 					.db
-					.prev_segment_end(
-						Some(&current_prev),
+					.segment_exists_at(
+						current_prev,
 						if device_ids.is_empty() {
 							None
 						} else {
 							Some(&device_ids)
 						},
 					)
-					.await?
-				{
-					Some(e) => e,
-					None => {
-						log::info!("no more segments to load");
+					.await?;
+				let mut end = if end_exists {
+					current_prev
+				} else {
+					match self
+						.db
+						.prev_segment_end(
+							Some(&current_prev),
+							if device_ids.is_empty() {
+								None
+							} else {
+								Some(&device_ids)
+							},
+						)
+						.await?
+					{
+						Some(e) => e,
+						None => {
+							log::info!("no more segments to load");
+							break;
+						}
+					}
+				};
+				if let Some(start) = start_bound {
+					if end < start {
 						break;
 					}
 				}
-			};
-			if let Some(start) = start_bound {
-				if end < start {
-					break;
-				}
-			}
-			let mut start = end - window;
-			if let Some(bound) = start_bound {
-				if start < bound {
-					start = bound;
+				let mut start = end - window;
+				if let Some(bound) = start_bound {
+					if start < bound {
+						start = bound;
+					}
 				}
-			}
-			prev_end = Some(start);
+				prev_end = Some(start);
 
-			let timer = std::time::Instant::now();
-			if last_progress_emit.elapsed() >= Duration::from_millis(500) {
-				let speed = calculate_logs_per_second(processed_logs, search_start);
-				if send_search_progress(
-					tx,
-					processed_logs,
-					speed,
-					Some("loading matching segments"),
-				)
-				.await
-				{
-					break;
-				}
-				last_progress_emit = Instant::now();
-			}
-			let segments = match self
-				.db
-				.find_segments(&GetSegmentsQuery {
-					start: Some(start),
-					end: Some(end),
-					device_ids: if device_ids.is_empty() {
-						None
-					} else {
-						Some(device_ids.clone())
-					},
-					..Default::default()
-				})
-				.await
-			{
-				Ok(segments) => segments,
-				Err(err) => {
-					log::error!("failed to load segments: {}", err);
-					return Err(err);
-				}
-			};
-			if segments.is_empty() {
-				log::info!("no segments found in the range {} - {}", start, end);
-				break;
-			}
-			log::info!(
-				"found {} segments in range {} - {} in {:?}",
-				segments.len(),
-				start,
-				end,
-				timer.elapsed()
-			);
-			for segment in &segments {
-				if tx.is_closed() {
-					break 'outer;
-				}
-				if !processed_segments.insert(segment.id) {
-					continue;
-				}
+				let timer = std::time::Instant::now();
 				if last_progress_emit.elapsed() >= Duration::from_millis(500) {
 					let speed = calculate_logs_per_second(processed_logs, search_start);
 					if send_search_progress(
 						tx,
 						processed_logs,
 						speed,
-						Some("loading segment metadata"),
+						Some("loading matching segments"),
 					)
 					.await
 					{
-						break 'outer;
+						break;
 					}
 					last_progress_emit = Instant::now();
 				}
-				let props = match self.db.fetch_segment_props(segment.id).await {
-					Ok(props) => props,
+				let segments = match self
+					.db
+					.find_segments(&GetSegmentsQuery {
+						start: Some(start),
+						end: Some(end),
+						device_ids: if device_ids.is_empty() {
+							None
+						} else {
+							Some(device_ids.clone())
+						},
+						..Default::default()
+					})
+					.await
+				{
+					Ok(segments) => segments,
 					Err(err) => {
-						log::error!("failed to fetch segment props: {}", err);
-						continue;
+						log::error!("failed to load segments: {}", err);
+						return Err(err);
 					}
 				};
-				// Check whether the segment's time window could satisfy the query.
-				let time_match = puppylog::match_date_range(
-					&query.root,
-					segment.first_timestamp,
-					segment.last_timestamp,
-					&tz,
-				);
-				if !time_match {
-					// Only time mismatch changes the `end` scan position.
-					end = segment.first_timestamp;
-					continue;
-				}
-
-				// Only if the date range fits do we bother checking the segment's properties.
-				let prop_match = check_props(&query.root, &props).unwrap_or_default();
-				if !prop_match {
-					// IMPORTANT: do NOT move `end` here; otherwise other devices'
-					// segments will cut off later logs for the target device.
-					continue;
-				}
-				if tx
-					.send(LogStreamItem::SegmentProgress(SegmentProgress {
-						segment_id: segment.id,
-						device_id: segment.device_id.clone(),
-						first_timestamp: segment.first_timestamp,
-						last_timestamp: segment.last_timestamp,
-						logs_count: segment.logs_count,
-					}))
-					.await
-					.is_err()
-				{
-					break 'outer;
+				if segments.is_empty() {
+					log::info!("no segments found in the range {} - {}", start, end);
+					break;
 				}
-				let path = self.logs_path.join(format!("{}.log", segment.id));
 				log::info!(
-					"loading {} segment {} - {}",
-					segment.id,
-					segment.first_timestamp,
-					segment.last_timestamp
+					"found {} segments in range {} - {} in {:?}",
+					segments.len(),
+					start,
+					end,
+					timer.elapsed()
 				);
-				let file: File = match File::open(path) {
-					Ok(file) => file,
-					Err(err) => {
-						log::error!("failed to open log file: {}", err);
-						continue;
-					}
-				};
-				let mut decoder = zstd::Decoder::new(file).unwrap();
-				let segment = LogSegment::parse(&mut decoder);
-				let iter = segment.iter();
-				for entry in iter {
+				// Phase 1: cheap sequential filtering. Dedup, the resume-skip
+				// lookup, and the time/bloom/prop checks all stay sequential
+				// since each can move `end` backwards (or decide a segment is
+				// already fully consumed), which later segments in the window
+				// depend on. What survives is queued up for concurrent decode
+				// instead of being decoded right away.
+				let mut pending: Vec<(SegmentMeta, DateTime<Utc>, u64)> = Vec::new();
+				for segment in &segments {
 					if tx.is_closed() {
 						break 'outer;
 					}
-					processed_logs += 1;
-					if should_emit_progress(processed_logs, &last_progress_emit) {
+					if !processed_segments.insert(segment.id) {
+						continue;
+					}
+					let segment_skip = if resuming {
+						match &self.cursor {
+							Some(c) if c.segment_id == segment.id => {
+								resuming = false;
+								Some(c.logs_seen_in_segment)
+							}
+							_ => None,
+						}
+					} else {
+						Some(0)
+					};
+					let Some(skip_n) = segment_skip else {
+						// Already fully consumed by the call that returned this cursor.
+						continue;
+					};
+					if last_progress_emit.elapsed() >= Duration::from_millis(500) {
 						let speed = calculate_logs_per_second(processed_logs, search_start);
-						if send_search_progress(tx, processed_logs, speed, None).await {
+						if send_search_progress(
+							tx,
+							processed_logs,
+							speed,
+							Some("loading segment metadata"),
+						)
+						.await
+						{
 							break 'outer;
 						}
 						last_progress_emit = Instant::now();
 					}
-					if entry.timestamp > end {
+					// Check whether the segment's time window could satisfy the query.
+					let time_match = puppylog::match_date_range(
+						&simplified_root,
+						segment.first_timestamp,
+						segment.last_timestamp,
+						&tz,
+					);
+					if !time_match {
+						// Only time mismatch changes the `end` scan position.
+						end = segment.first_timestamp;
 						continue;
 					}
-					match check_expr(&query.root, entry, &tz) {
-						Ok(true) => {}
-						_ => continue,
+
+					// A segment with a bloom is skipped outright on a definite miss,
+					// saving the props round-trip below. `None` means the segment
+					// predates blooms, so it's always a "maybe".
+					if let Some(bloom_bytes) = &segment.bloom {
+						if let Some(bloom) = SegmentBloom::from_bytes(bloom_bytes) {
+							let definite_miss = equality_props
+								.iter()
+								.any(|key| !bloom.might_contain(key));
+							if definite_miss {
+								continue;
+							}
+						}
 					}
-					if tx.send(LogStreamItem::Entry(entry.clone())).await.is_err() {
-						log::info!("stopped searching logs at {:?}", entry);
+
+					let props = match self.db.fetch_segment_props(segment.id).await {
+						Ok(props) => props,
+						Err(err) => {
+							log::error!("failed to fetch segment props: {}", err);
+							continue;
+						}
+					};
+					// Only if the date range and bloom fit do we bother checking the segment's properties.
+					let prop_match = check_props(&query.root, &props).unwrap_or_default();
+					if !prop_match {
+						// IMPORTANT: do NOT move `end` here; otherwise other devices'
+						// segments will cut off later logs for the target device.
+						continue;
+					}
+					if tx
+						.send(LogStreamItem::SegmentProgress(SegmentProgress {
+							segment_id: segment.id,
+							device_id: segment.device_id.clone(),
+							first_timestamp: segment.first_timestamp,
+							last_timestamp: segment.last_timestamp,
+							logs_count: segment.logs_count,
+						}))
+						.await
+						.is_err()
+					{
+						break 'outer;
+					}
+					log::info!(
+						"loading {} segment {} - {}",
+						segment.id,
+						segment.first_timestamp,
+						segment.last_timestamp
+					);
+					// `end` at this point is the cutoff later segments in this
+					// window may have already tightened; snapshot it so a
+					// time-mismatch further down the list can't retroactively
+					// change what this segment's decode filters against.
+					pending.push((segment.clone(), end, skip_n));
+				}
+
+				if tx.is_closed() {
+					break 'outer;
+				}
+
+				// Phase 2: decode up to `search_decode_concurrency()` pending
+				// segments at once, each handing its CPU-bound zstd decode and
+				// `check_expr` pass to a blocking-pool thread, so a wide window
+				// full of segments doesn't bottleneck on decoding them one at a
+				// time.
+				let concurrency = crate::config::search_decode_concurrency().max(1);
+				let decoded: Vec<(u32, Vec<(u64, LogEntry)>, u64)> = futures::stream::iter(&pending)
+					.map(|(segment, end_cutoff, skip_n)| {
+						self.decode_pending_segment(segment, *end_cutoff, *skip_n, &query.root, &tz)
+					})
+					.buffer_unordered(concurrency)
+					.collect()
+					.await;
+
+				for (_, _, total_iterated) in &decoded {
+					processed_logs += total_iterated;
+				}
+				if should_emit_progress(processed_logs, &last_progress_emit) {
+					let speed = calculate_logs_per_second(processed_logs, search_start);
+					if send_search_progress(tx, processed_logs, speed, None).await {
 						break 'outer;
 					}
+					last_progress_emit = Instant::now();
+				}
+
+				// Phase 3: merge every segment's already-filtered, newest-first
+				// matches into one globally reverse-chronological stream.
+				// Segments can overlap in time (querying several devices at
+				// once), so simply flushing one decoded segment after another
+				// wouldn't stay ordered — this does a real k-way merge: seed a
+				// max-heap with each segment's newest remaining match, repeatedly
+				// pop the overall newest, and refill from whichever segment it
+				// came from.
+				let mut cursors: Vec<std::slice::Iter<(u64, LogEntry)>> =
+					decoded.iter().map(|(_, matches, _)| matches.iter()).collect();
+				let mut heap: BinaryHeap<MergeItem> = BinaryHeap::new();
+				for (source, cur) in cursors.iter_mut().enumerate() {
+					if let Some((seg_pos, entry)) = cur.next() {
+						heap.push(MergeItem {
+							timestamp: entry.timestamp,
+							source,
+							seg_pos: *seg_pos,
+							entry: entry.clone(),
+						});
+					}
+				}
+				while let Some(item) = heap.pop() {
+					if tx.is_closed() {
+						break 'outer;
+					}
+					if tx.send(LogStreamItem::Entry(item.entry.clone())).await.is_err() {
+						log::info!("stopped searching logs at {:?}", item.entry);
+						break 'outer;
+					}
+					matches_sent += 1;
+					if self.limit.is_some_and(|l| matches_sent as usize >= l) {
+						let _ = tx
+							.send(LogStreamItem::Cursor(SearchCursor {
+								timestamp: item.timestamp,
+								segment_id: decoded[item.source].0,
+								logs_seen_in_segment: item.seg_pos,
+							}))
+							.await;
+						return Ok(());
+					}
+					if let Some((seg_pos, entry)) = cursors[item.source].next() {
+						heap.push(MergeItem {
+							timestamp: entry.timestamp,
+							source: item.source,
+							seg_pos: *seg_pos,
+							entry: entry.clone(),
+						});
+					}
 				}
 			}
 		}
@@ -397,7 +841,276 @@ impl<'a> LogSearcher<'a> {
 				}))
 				.await;
 		}
-		Ok(())
+
+		if matches!(self.mode, StreamMode::Subscribe | StreamMode::SubscribeFuture) {
+			if tx.send(LogStreamItem::Tail).await.is_err() {
+				return Ok(());
+			}
+			self.follow(&query, &tz, tx).await;
+		}
+
+		Ok(())
+	}
+
+	/// Fetches, decrypts, decompresses, and filters one archived segment
+	/// Phase 1 of the archive scan queued up, returning every entry that
+	/// passes `skip_n`/`end_cutoff`/`check_expr` in the segment's own
+	/// newest-first order, plus how many entries were iterated in total
+	/// (matches and non-matches alike), so the merge stage can both
+	/// interleave across segments and keep `processed_logs` accurate. The
+	/// zstd decode and `check_expr` pass are CPU-bound, so they run on a
+	/// blocking-pool thread via `spawn_blocking` instead of the async
+	/// runtime, letting several segments decode in parallel.
+	async fn decode_pending_segment(
+		&self,
+		segment: &SegmentMeta,
+		end_cutoff: DateTime<Utc>,
+		skip_n: u64,
+		root: &Expr,
+		tz: &chrono::FixedOffset,
+	) -> (u32, Vec<(u64, LogEntry)>, u64) {
+		let segment_id = segment.id;
+		let bytes = match self.store.get(segment.id, segment.data_dir.as_deref()).await {
+			Ok(bytes) => bytes,
+			Err(err) => {
+				log::error!("failed to load segment {}: {}", segment.id, err);
+				return (segment_id, Vec::new(), 0);
+			}
+		};
+		if let Some(access_tracker) = self.access_tracker {
+			access_tracker.touch(segment.id);
+		}
+		let bytes = if segment.encrypted {
+			match self
+				.encryption_key
+				.and_then(|key| crate::encryption::decrypt(&key, segment.id, &bytes).ok())
+			{
+				Some(bytes) => bytes,
+				None => {
+					log::error!("failed to decrypt segment {}", segment.id);
+					return (segment_id, Vec::new(), 0);
+				}
+			}
+		} else {
+			bytes
+		};
+		let root = root.clone();
+		let tz = *tz;
+		let is_compressed = segment.compressed;
+		let result = tokio::task::spawn_blocking(move || {
+			let segment = if is_compressed {
+				let mut decoder = zstd::Decoder::new(std::io::Cursor::new(bytes)).unwrap();
+				LogSegment::parse(&mut decoder)
+			} else {
+				LogSegment::parse(&mut std::io::Cursor::new(bytes))
+			}
+			.unwrap_or_else(|err| {
+				log::warn!("segment {} failed to parse: {}", segment_id, err);
+				err.recovered()
+			});
+			let mut seg_pos: u64 = 0;
+			let mut matches = Vec::new();
+			for entry in segment.iter() {
+				seg_pos += 1;
+				if seg_pos <= skip_n {
+					continue;
+				}
+				if entry.timestamp > end_cutoff {
+					continue;
+				}
+				if matches!(check_expr(&root, entry, &tz), Ok(true)) {
+					matches.push((seg_pos, entry.clone()));
+				}
+			}
+			(matches, seg_pos)
+		})
+		.await;
+		match result {
+			Ok((matches, total_iterated)) => (segment_id, matches, total_iterated),
+			Err(err) => {
+				log::error!("segment {} decode task panicked: {}", segment_id, err);
+				(segment_id, Vec::new(), 0)
+			}
+		}
+	}
+
+	/// Tails newly ingested entries off `self.live_tail`, forwarding ones
+	/// that match `query` as `LogStreamItem::Entry` until `tx` is closed or
+	/// the ingestion side drops its broadcast sender. Runs after (or
+	/// instead of) the historical scan in `search`, depending on `self.mode`.
+	async fn follow(
+		&self,
+		query: &QueryAst,
+		tz: &chrono::FixedOffset,
+		tx: &mpsc::Sender<LogStreamItem>,
+	) {
+		let Some(live_tail) = self.live_tail else {
+			return;
+		};
+		let mut rx = live_tail.subscribe();
+		loop {
+			// Race the next live entry against the output side closing, so a
+			// client that disconnects without the ingestion side ever
+			// sending another entry doesn't leave this receiver subscribed
+			// forever.
+			let entry = tokio::select! {
+				_ = tx.closed() => return,
+				res = rx.recv() => match res {
+					Ok(entry) => entry,
+					Err(broadcast::error::RecvError::Lagged(skipped)) => {
+						log::warn!("live tail lagged, dropped {} entries", skipped);
+						continue;
+					}
+					Err(broadcast::error::RecvError::Closed) => return,
+				},
+			};
+			match check_expr(&query.root, &entry, tz) {
+				Ok(true) => {}
+				_ => continue,
+			}
+			if tx.send(LogStreamItem::Entry(entry)).await.is_err() {
+				return;
+			}
+		}
+	}
+
+	/// Runs `search` to completion and writes every matched entry out as one
+	/// or more new, real segments instead of streaming them to a client —
+	/// sorted, serialized, and zstd-compressed the same way
+	/// `DeviceSegmentCompactor::persist_segment` builds a compacted segment.
+	/// The result is an ordinary segment the existing download route and
+	/// `LogSegment::parse` already know how to read, so a saved query result
+	/// is just more archive, not a new file format.
+	///
+	/// `max_bytes` caps each export segment's pre-compression size; once a
+	/// batch would cross it, that batch is flushed as its own segment and a
+	/// fresh one starts, so one huge result set becomes a sequence of
+	/// self-contained files rather than a single unbounded one. `None`
+	/// writes every match into a single segment. Ignores `self.limit`: an
+	/// export always runs to completion, it's not a paginated view.
+	pub async fn search_to_segment(
+		&self,
+		query: QueryAst,
+		max_bytes: Option<usize>,
+	) -> anyhow::Result<Vec<ExportedSegment>> {
+		let (tx, mut rx) = mpsc::channel(100);
+		let search_fut = async move {
+			let result = self.search(query, &tx).await;
+			drop(tx);
+			result
+		};
+		let drain_fut = async {
+			let mut exported = Vec::new();
+			let mut pending: Vec<LogEntry> = Vec::new();
+			let mut pending_size: usize = 0;
+			while let Some(item) = rx.recv().await {
+				let LogStreamItem::Entry(entry) = item else {
+					continue;
+				};
+				pending_size += estimate_entry_size(&entry);
+				pending.push(entry);
+				if max_bytes.is_some_and(|cap| pending_size >= cap) {
+					let batch = std::mem::take(&mut pending);
+					exported.push(self.persist_export_segment(batch).await?);
+					pending_size = 0;
+				}
+			}
+			if !pending.is_empty() {
+				exported.push(self.persist_export_segment(pending).await?);
+			}
+			Ok::<_, anyhow::Error>(exported)
+		};
+		let (search_result, exported) = tokio::join!(search_fut, drain_fut);
+		search_result?;
+		exported
+	}
+
+	/// Writes one export batch as a new segment and registers it exactly
+	/// like `DeviceSegmentCompactor`'s production segments (props, bloom,
+	/// encryption, checksum), so it's indistinguishable from an ordinarily
+	/// ingested segment to `search`, retention, or the download route. A
+	/// query can span multiple devices, so unlike a compacted segment this
+	/// one has no single `device_id` to stamp.
+	async fn persist_export_segment(
+		&self,
+		mut logs: Vec<LogEntry>,
+	) -> anyhow::Result<ExportedSegment> {
+		logs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+		let first_timestamp = logs.first().unwrap().timestamp;
+		let last_timestamp = logs.last().unwrap().timestamp;
+		let logs_count = logs.len() as u64;
+		let seg = LogSegment::from_buffer(logs);
+
+		let mut plain = Vec::new();
+		seg.serialize(&mut plain);
+		let original_size = plain.len();
+		let (compressed, is_compressed) = compress_segment(&plain)?;
+		let compressed_size = compressed.len();
+
+		let segment_id = self
+			.db
+			.new_segment(NewSegmentArgs {
+				device_id: None,
+				first_timestamp,
+				last_timestamp,
+				original_size,
+				compressed_size,
+				logs_count,
+			})
+			.await?;
+
+		let mut unique = HashSet::new();
+		for log in &seg.buffer {
+			unique.extend(log.props.iter().cloned());
+			unique.insert(Prop {
+				key: "level".into(),
+				value: log.level.to_string().into(),
+			});
+		}
+		self.db
+			.upsert_segment_props(segment_id, unique.iter())
+			.await?;
+
+		let mut bloom = SegmentBloom::with_expected_items(unique.len());
+		for prop in &unique {
+			bloom.insert(&format!("{}={}", prop.key, prop.value));
+		}
+		self.db.set_segment_bloom(segment_id, bloom.to_bytes()).await?;
+
+		let compressed = match self.encryption_key {
+			Some(key) => crate::encryption::encrypt(&key, &compressed),
+			None => compressed,
+		};
+		self.db
+			.set_segment_encrypted(segment_id, self.encryption_key.is_some())
+			.await?;
+		self.db
+			.set_segment_checksum(segment_id, crate::checksum::checksum(&compressed))
+			.await?;
+		if !is_compressed {
+			self.db.set_segment_compressed(segment_id, false).await?;
+		}
+		let placed = self.store.put(segment_id, compressed).await?;
+		if let Some(dir) = placed {
+			if let Err(err) = self.db.set_segment_data_dir(segment_id, &dir).await {
+				log::error!("failed to record data dir for segment {}: {}", segment_id, err);
+			}
+		}
+
+		log::info!(
+			"exported search result segment {} ({} logs)",
+			segment_id,
+			logs_count
+		);
+
+		Ok(ExportedSegment {
+			segment_id,
+			first_timestamp,
+			last_timestamp,
+			logs_count,
+			original_size,
+			compressed_size,
+		})
 	}
 }
 
@@ -406,8 +1119,10 @@ mod tests {
 	use super::*;
 	use crate::db::{open_db, NewSegmentArgs};
 	use crate::segment::compress_segment;
+	use crate::segment_store::LocalFsStore;
 	use chrono::{Duration, Utc};
 	use puppylog::{parse_log_query, LogEntry, LogLevel, Prop};
+	use std::collections::HashMap;
 	use std::fs;
 	use std::path::PathBuf;
 	use tempfile::TempDir;
@@ -416,25 +1131,39 @@ mod tests {
 		db: DB,
 		current: Mutex<LogSegment>,
 		logs_path: PathBuf,
+		store: LocalFsStore,
 		_tempdir: TempDir,
 	}
 
 	impl TestSearcherEnv {
 		fn new() -> Self {
+			Self::with_current(LogSegment::new())
+		}
+
+		/// Like `new`, but with a byte-budgeted `current` buffer, for tests
+		/// exercising `LogSegment::with_max_bytes` eviction through a live
+		/// `LogSearcher::search`.
+		fn with_buffer_budget(max_bytes: usize) -> Self {
+			Self::with_current(LogSegment::with_max_bytes(max_bytes))
+		}
+
+		fn with_current(current: LogSegment) -> Self {
 			let tempdir = TempDir::new().unwrap();
 			let logs_path = tempdir.path().join("logs");
 			fs::create_dir_all(&logs_path).unwrap();
 			let db = DB::new(open_db());
+			let store = LocalFsStore::new(logs_path.clone());
 			Self {
 				db,
-				current: Mutex::new(LogSegment::new()),
+				current: Mutex::new(current),
 				logs_path,
+				store,
 				_tempdir: tempdir,
 			}
 		}
 
 		fn searcher(&self) -> LogSearcher<'_> {
-			LogSearcher::new(&self.db, &self.current, &self.logs_path)
+			LogSearcher::new(&self.db, &self.current, &self.store, None, None)
 		}
 
 		async fn persist_segment(&self, entry: &LogEntry, device_id: Option<&str>) -> u32 {
@@ -444,7 +1173,7 @@ mod tests {
 			let mut buff = Vec::new();
 			segment.serialize(&mut buff);
 			let original_size = buff.len();
-			let compressed = compress_segment(&buff).unwrap();
+			let (compressed, is_compressed) = compress_segment(&buff).unwrap();
 			let compressed_size = compressed.len();
 			let segment_id = self
 				.db
@@ -458,10 +1187,13 @@ mod tests {
 				})
 				.await
 				.unwrap();
+			if !is_compressed {
+				self.db.set_segment_compressed(segment_id, false).await.unwrap();
+			}
 			let mut props_vec = entry.props.clone();
 			props_vec.push(Prop {
 				key: "level".into(),
-				value: entry.level.to_string(),
+				value: entry.level.to_string().into(),
 			});
 			self.db
 				.upsert_segment_props(segment_id, props_vec.iter())
@@ -512,6 +1244,47 @@ mod tests {
 		assert_eq!(entries[0].msg, "from-memory");
 	}
 
+	#[tokio::test]
+	async fn search_memory_buffer_evicts_oldest_past_budget() {
+		// Each entry's estimated size is `msg.len() + 32` (no props), so a
+		// budget of 3.5 entries' worth only ever keeps the 3 newest.
+		let entry_size = estimate_entry_size(&LogEntry {
+			msg: "entry-0".into(),
+			..Default::default()
+		});
+		let env = TestSearcherEnv::with_buffer_budget(entry_size * 3 + entry_size / 2);
+		let now = Utc::now();
+		{
+			let mut current = env.current.lock().await;
+			for i in 0..5 {
+				current.add_log_entry(LogEntry {
+					timestamp: now + Duration::seconds(i),
+					level: LogLevel::Info,
+					props: vec![],
+					msg: format!("entry-{i}"),
+					..Default::default()
+				});
+			}
+			assert_eq!(current.evicted_count(), 2);
+			assert!(current.bytes_used() <= entry_size * 3 + entry_size / 2);
+		}
+
+		let mut query = parse_log_query("level = info").unwrap();
+		query.end_date = Some(now + Duration::seconds(10));
+		let (tx, mut rx) = mpsc::channel(16);
+		env.searcher().search(query, &tx).await.unwrap();
+		drop(tx);
+
+		let mut msgs = Vec::new();
+		while let Some(item) = rx.recv().await {
+			if let LogStreamItem::Entry(log) = item {
+				msgs.push(log.msg);
+			}
+		}
+		msgs.sort();
+		assert_eq!(msgs, vec!["entry-2", "entry-3", "entry-4"]);
+	}
+
 	#[tokio::test]
 	async fn search_reads_from_archived_segment() {
 		let env = TestSearcherEnv::new();
@@ -806,136 +1579,327 @@ mod tests {
 			}
 		}
 
-		// Should have at least one progress event
-		assert!(progress_count >= 1);
-	}
-
-	#[tokio::test]
-	async fn search_emits_segment_progress() {
-		let env = TestSearcherEnv::new();
-		let now = Utc::now();
-
-		let entry = LogEntry {
-			timestamp: now - Duration::hours(30),
-			level: LogLevel::Info,
-			props: vec![],
-			msg: "segment-log".into(),
-			..Default::default()
-		};
-		let seg_id = env.persist_segment(&entry, None).await;
-
-		let mut query = parse_log_query("msg = \"segment-log\"").unwrap();
+		// Should have at least one progress event
+		assert!(progress_count >= 1);
+	}
+
+	#[tokio::test]
+	async fn search_emits_segment_progress() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+
+		let entry = LogEntry {
+			timestamp: now - Duration::hours(30),
+			level: LogLevel::Info,
+			props: vec![],
+			msg: "segment-log".into(),
+			..Default::default()
+		};
+		let seg_id = env.persist_segment(&entry, None).await;
+
+		let mut query = parse_log_query("msg = \"segment-log\"").unwrap();
+		query.end_date = Some(now);
+		let (tx, mut rx) = mpsc::channel(16);
+		env.searcher().search(query, &tx).await.unwrap();
+		drop(tx);
+
+		let mut segment_progress = Vec::new();
+		while let Some(item) = rx.recv().await {
+			if let LogStreamItem::SegmentProgress(progress) = item {
+				segment_progress.push(progress);
+			}
+		}
+
+		assert_eq!(segment_progress.len(), 1);
+		assert_eq!(segment_progress[0].segment_id, seg_id);
+		assert_eq!(segment_progress[0].logs_count, 1);
+	}
+
+	#[tokio::test]
+	async fn search_handles_multiple_segments() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+
+		// Create multiple segments at different times
+		for i in 1..=3 {
+			let entry = LogEntry {
+				timestamp: now - Duration::hours(i * 10),
+				level: LogLevel::Info,
+				props: vec![Prop {
+					key: "batch".into(),
+					value: "test".into(),
+				}],
+				msg: format!("log-{}", i),
+				..Default::default()
+			};
+			env.persist_segment(&entry, None).await;
+		}
+
+		let mut query = parse_log_query("batch = test").unwrap();
+		query.end_date = Some(now);
+		let (tx, mut rx) = mpsc::channel(32);
+		env.searcher().search(query, &tx).await.unwrap();
+		drop(tx);
+
+		let mut entries = Vec::new();
+		let mut segments = Vec::new();
+		while let Some(item) = rx.recv().await {
+			match item {
+				LogStreamItem::Entry(log) => entries.push(log),
+				LogStreamItem::SegmentProgress(progress) => segments.push(progress.segment_id),
+				_ => {}
+			}
+		}
+
+		assert_eq!(entries.len(), 3);
+		assert_eq!(segments.len(), 3);
+	}
+
+	#[tokio::test]
+	async fn search_stops_when_channel_closed() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+
+		// Add multiple entries to memory
+		{
+			let mut current = env.current.lock().await;
+			for i in 0..100 {
+				current.add_log_entry(LogEntry {
+					timestamp: now - Duration::seconds(i),
+					level: LogLevel::Info,
+					props: vec![],
+					msg: format!("log-{}", i),
+					..Default::default()
+				});
+			}
+			current.sort();
+		}
+
+		let mut query = parse_log_query("level = info").unwrap();
+		query.end_date = Some(now);
+		let (tx, rx) = mpsc::channel(1);
+
+		// Drop the receiver immediately to close the channel
+		drop(rx);
+
+		// Search should complete without error even though channel is closed
+		let result = env.searcher().search(query, &tx).await;
+		assert!(result.is_ok());
+	}
+
+	#[tokio::test]
+	async fn search_with_custom_window() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+
+		// Create segment outside default 24h window but within custom window
+		let entry = LogEntry {
+			timestamp: now - Duration::hours(48),
+			level: LogLevel::Info,
+			props: vec![],
+			msg: "old-log".into(),
+			..Default::default()
+		};
+		env.persist_segment(&entry, None).await;
+
+		let mut searcher = env.searcher();
+		searcher.window = chrono::Duration::hours(72);
+
+		let mut query = parse_log_query("msg = \"old-log\"").unwrap();
+		query.end_date = Some(now);
+		let (tx, mut rx) = mpsc::channel(16);
+		searcher.search(query, &tx).await.unwrap();
+		drop(tx);
+
+		let mut entries = Vec::new();
+		while let Some(item) = rx.recv().await {
+			if let LogStreamItem::Entry(log) = item {
+				entries.push(log);
+			}
+		}
+
+		assert_eq!(entries.len(), 1);
+		assert_eq!(entries[0].msg, "old-log");
+	}
+
+	#[tokio::test]
+	async fn search_with_msg_like_pattern() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+
+		{
+			let mut current = env.current.lock().await;
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(3),
+				level: LogLevel::Error,
+				props: vec![],
+				msg: "connection error: timeout".into(),
+				..Default::default()
+			});
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(2),
+				level: LogLevel::Info,
+				props: vec![],
+				msg: "connection established".into(),
+				..Default::default()
+			});
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(1),
+				level: LogLevel::Debug,
+				props: vec![],
+				msg: "debug info".into(),
+				..Default::default()
+			});
+			current.sort();
+		}
+
+		let mut query = parse_log_query("msg like \"connection\"").unwrap();
+		query.end_date = Some(now);
+		let (tx, mut rx) = mpsc::channel(16);
+		env.searcher().search(query, &tx).await.unwrap();
+		drop(tx);
+
+		let mut entries = Vec::new();
+		while let Some(item) = rx.recv().await {
+			if let LogStreamItem::Entry(log) = item {
+				entries.push(log);
+			}
+		}
+
+		assert_eq!(entries.len(), 2);
+		assert!(entries.iter().all(|e| e.msg.contains("connection")));
+	}
+
+	#[tokio::test]
+	async fn search_with_level_range_operator() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+
+		{
+			let mut current = env.current.lock().await;
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(4),
+				level: LogLevel::Error,
+				props: vec![],
+				msg: "error-log".into(),
+				..Default::default()
+			});
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(3),
+				level: LogLevel::Warn,
+				props: vec![],
+				msg: "warn-log".into(),
+				..Default::default()
+			});
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(2),
+				level: LogLevel::Info,
+				props: vec![],
+				msg: "info-log".into(),
+				..Default::default()
+			});
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(1),
+				level: LogLevel::Debug,
+				props: vec![],
+				msg: "debug-log".into(),
+				..Default::default()
+			});
+			current.sort();
+		}
+
+		let mut query = parse_log_query("level >= warn").unwrap();
 		query.end_date = Some(now);
 		let (tx, mut rx) = mpsc::channel(16);
 		env.searcher().search(query, &tx).await.unwrap();
 		drop(tx);
 
-		let mut segment_progress = Vec::new();
+		let mut entries = Vec::new();
 		while let Some(item) = rx.recv().await {
-			if let LogStreamItem::SegmentProgress(progress) = item {
-				segment_progress.push(progress);
+			if let LogStreamItem::Entry(log) = item {
+				entries.push(log);
 			}
 		}
 
-		assert_eq!(segment_progress.len(), 1);
-		assert_eq!(segment_progress[0].segment_id, seg_id);
-		assert_eq!(segment_progress[0].logs_count, 1);
+		assert_eq!(entries.len(), 2);
+		assert!(entries.iter().all(|e| e.level >= LogLevel::Warn));
 	}
 
 	#[tokio::test]
-	async fn search_handles_multiple_segments() {
+	async fn search_with_numeric_prop_comparison() {
 		let env = TestSearcherEnv::new();
 		let now = Utc::now();
 
-		// Create multiple segments at different times
-		for i in 1..=3 {
-			let entry = LogEntry {
-				timestamp: now - Duration::hours(i * 10),
+		{
+			let mut current = env.current.lock().await;
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(2),
 				level: LogLevel::Info,
 				props: vec![Prop {
-					key: "batch".into(),
-					value: "test".into(),
+					key: "duration_ms".into(),
+					value: "150".into(),
 				}],
-				msg: format!("log-{}", i),
+				msg: "slow-request".into(),
 				..Default::default()
-			};
-			env.persist_segment(&entry, None).await;
+			});
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(1),
+				level: LogLevel::Info,
+				props: vec![Prop {
+					key: "duration_ms".into(),
+					value: "20".into(),
+				}],
+				msg: "fast-request".into(),
+				..Default::default()
+			});
+			current.sort();
 		}
 
-		let mut query = parse_log_query("batch = test").unwrap();
+		let mut query = parse_log_query("duration_ms > 100").unwrap();
 		query.end_date = Some(now);
-		let (tx, mut rx) = mpsc::channel(32);
+		let (tx, mut rx) = mpsc::channel(16);
 		env.searcher().search(query, &tx).await.unwrap();
 		drop(tx);
 
 		let mut entries = Vec::new();
-		let mut segments = Vec::new();
 		while let Some(item) = rx.recv().await {
-			match item {
-				LogStreamItem::Entry(log) => entries.push(log),
-				LogStreamItem::SegmentProgress(progress) => segments.push(progress.segment_id),
-				_ => {}
+			if let LogStreamItem::Entry(log) = item {
+				entries.push(log);
 			}
 		}
 
-		assert_eq!(entries.len(), 3);
-		assert_eq!(segments.len(), 3);
+		assert_eq!(entries.len(), 1);
+		assert_eq!(entries[0].msg, "slow-request");
 	}
 
 	#[tokio::test]
-	async fn search_stops_when_channel_closed() {
+	async fn search_with_regex_operator() {
 		let env = TestSearcherEnv::new();
 		let now = Utc::now();
 
-		// Add multiple entries to memory
 		{
 			let mut current = env.current.lock().await;
-			for i in 0..100 {
-				current.add_log_entry(LogEntry {
-					timestamp: now - Duration::seconds(i),
-					level: LogLevel::Info,
-					props: vec![],
-					msg: format!("log-{}", i),
-					..Default::default()
-				});
-			}
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(2),
+				level: LogLevel::Error,
+				props: vec![],
+				msg: "conn reset: timeout".into(),
+				..Default::default()
+			});
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::seconds(1),
+				level: LogLevel::Info,
+				props: vec![],
+				msg: "connection established".into(),
+				..Default::default()
+			});
 			current.sort();
 		}
 
-		let mut query = parse_log_query("level = info").unwrap();
-		query.end_date = Some(now);
-		let (tx, rx) = mpsc::channel(1);
-
-		// Drop the receiver immediately to close the channel
-		drop(rx);
-
-		// Search should complete without error even though channel is closed
-		let result = env.searcher().search(query, &tx).await;
-		assert!(result.is_ok());
-	}
-
-	#[tokio::test]
-	async fn search_with_custom_window() {
-		let env = TestSearcherEnv::new();
-		let now = Utc::now();
-
-		// Create segment outside default 24h window but within custom window
-		let entry = LogEntry {
-			timestamp: now - Duration::hours(48),
-			level: LogLevel::Info,
-			props: vec![],
-			msg: "old-log".into(),
-			..Default::default()
-		};
-		env.persist_segment(&entry, None).await;
-
-		let mut searcher = env.searcher();
-		searcher.window = chrono::Duration::hours(72);
-
-		let mut query = parse_log_query("msg = \"old-log\"").unwrap();
+		let mut query = parse_log_query("msg =~ /conn.*timeout/").unwrap();
 		query.end_date = Some(now);
 		let (tx, mut rx) = mpsc::channel(16);
-		searcher.search(query, &tx).await.unwrap();
+		env.searcher().search(query, &tx).await.unwrap();
 		drop(tx);
 
 		let mut entries = Vec::new();
@@ -946,55 +1910,56 @@ mod tests {
 		}
 
 		assert_eq!(entries.len(), 1);
-		assert_eq!(entries[0].msg, "old-log");
+		assert_eq!(entries[0].msg, "conn reset: timeout");
 	}
 
 	#[tokio::test]
-	async fn search_with_msg_like_pattern() {
+	async fn search_batch_tags_entries_with_their_query_index() {
 		let env = TestSearcherEnv::new();
 		let now = Utc::now();
 
 		{
 			let mut current = env.current.lock().await;
-			current.add_log_entry(LogEntry {
-				timestamp: now - Duration::seconds(3),
-				level: LogLevel::Error,
-				props: vec![],
-				msg: "connection error: timeout".into(),
-				..Default::default()
-			});
 			current.add_log_entry(LogEntry {
 				timestamp: now - Duration::seconds(2),
-				level: LogLevel::Info,
+				level: LogLevel::Error,
 				props: vec![],
-				msg: "connection established".into(),
+				msg: "error-log".into(),
 				..Default::default()
 			});
 			current.add_log_entry(LogEntry {
 				timestamp: now - Duration::seconds(1),
 				level: LogLevel::Debug,
 				props: vec![],
-				msg: "debug info".into(),
+				msg: "debug-log".into(),
 				..Default::default()
 			});
 			current.sort();
 		}
 
-		let mut query = parse_log_query("msg like \"connection\"").unwrap();
-		query.end_date = Some(now);
+		let mut error_query = parse_log_query("level = error").unwrap();
+		error_query.end_date = Some(now);
+		let mut debug_query = parse_log_query("level = debug").unwrap();
+		debug_query.end_date = Some(now);
+
 		let (tx, mut rx) = mpsc::channel(16);
-		env.searcher().search(query, &tx).await.unwrap();
+		env.searcher()
+			.search_batch(vec![error_query, debug_query], &tx)
+			.await
+			.unwrap();
 		drop(tx);
 
-		let mut entries = Vec::new();
+		let mut by_index: HashMap<usize, Vec<LogEntry>> = HashMap::new();
 		while let Some(item) = rx.recv().await {
-			if let LogStreamItem::Entry(log) = item {
-				entries.push(log);
+			if let LogStreamItem::BatchEntry { batch_index, entry } = item {
+				by_index.entry(batch_index).or_default().push(entry);
 			}
 		}
 
-		assert_eq!(entries.len(), 2);
-		assert!(entries.iter().all(|e| e.msg.contains("connection")));
+		assert_eq!(by_index[&0].len(), 1);
+		assert_eq!(by_index[&0][0].msg, "error-log");
+		assert_eq!(by_index[&1].len(), 1);
+		assert_eq!(by_index[&1][0].msg, "debug-log");
 	}
 
 	#[tokio::test]
@@ -1146,4 +2111,227 @@ mod tests {
 		assert_eq!(entries.len(), 1);
 		assert_eq!(entries[0].msg, "old-log");
 	}
+
+	#[tokio::test]
+	async fn search_subscribe_streams_history_then_live_entries() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+		{
+			let mut current = env.current.lock().await;
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::minutes(5),
+				level: LogLevel::Info,
+				props: vec![],
+				msg: "historical".into(),
+				..Default::default()
+			});
+		}
+		let (live_tail, _) = broadcast::channel(16);
+
+		let mut query = parse_log_query("msg ~ \"tail\"").unwrap();
+		query.end_date = Some(now);
+		let (tx, mut rx) = mpsc::channel(16);
+		let mut searcher = env.searcher();
+		searcher.mode = StreamMode::Subscribe;
+		searcher.live_tail = Some(&live_tail);
+
+		// `move` so `rx` is owned by this future and drops the moment it
+		// resolves, which is what makes `tx.closed()` resolve for the
+		// searcher's copy and ends `follow`'s loop — no need to also drop
+		// `live_tail`.
+		let drain = async move {
+			while let Some(item) = rx.recv().await {
+				if let LogStreamItem::Entry(log) = item {
+					if log.msg == "live-tail-entry" {
+						return log;
+					}
+				}
+			}
+			panic!("channel closed before the live entry arrived");
+		};
+		let send_live_entry = async {
+			tokio::time::sleep(Duration::from_millis(50)).await;
+			live_tail
+				.send(LogEntry {
+					timestamp: Utc::now(),
+					level: LogLevel::Info,
+					props: vec![],
+					msg: "live-tail-entry".into(),
+					..Default::default()
+				})
+				.unwrap();
+		};
+
+		let (search_result, live, _) = timeout(
+			Duration::from_secs(2),
+			tokio::join!(searcher.search(query, &tx), drain, send_live_entry),
+		)
+		.await
+		.expect("search + live tail did not settle in time");
+		search_result.unwrap();
+		assert_eq!(live.msg, "live-tail-entry");
+	}
+
+	#[tokio::test]
+	async fn search_subscribe_emits_tail_marker_after_history() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+		{
+			let mut current = env.current.lock().await;
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::minutes(5),
+				level: LogLevel::Info,
+				props: vec![],
+				msg: "historical".into(),
+				..Default::default()
+			});
+		}
+		let (live_tail, _) = broadcast::channel(16);
+
+		let mut query = parse_log_query("level = info").unwrap();
+		query.end_date = Some(now);
+		let (tx, mut rx) = mpsc::channel(16);
+		let mut searcher = env.searcher();
+		searcher.mode = StreamMode::Subscribe;
+		searcher.live_tail = Some(&live_tail);
+
+		// `move` so `rx` drops the moment this future resolves, ending
+		// `follow`'s loop via `tx.closed()`.
+		let drain = async move {
+			let first = rx.recv().await;
+			let second = rx.recv().await;
+			(first, second)
+		};
+
+		let (search_result, (first, second)) = timeout(
+			Duration::from_secs(2),
+			tokio::join!(searcher.search(query, &tx), drain),
+		)
+		.await
+		.expect("search did not settle in time");
+		search_result.unwrap();
+
+		match first {
+			Some(LogStreamItem::Entry(log)) => assert_eq!(log.msg, "historical"),
+			other => panic!("expected the historical entry first, got {:?}", other),
+		}
+		assert!(
+			matches!(second, Some(LogStreamItem::Tail)),
+			"expected Tail right after history, got {:?}",
+			second
+		);
+	}
+
+	#[tokio::test]
+	async fn search_subscribe_future_skips_history() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+		{
+			let mut current = env.current.lock().await;
+			current.add_log_entry(LogEntry {
+				timestamp: now - Duration::minutes(5),
+				level: LogLevel::Info,
+				props: vec![],
+				msg: "tail-msg".into(),
+				..Default::default()
+			});
+		}
+		let (live_tail, _) = broadcast::channel(16);
+
+		let mut query = parse_log_query("msg = \"tail-msg\"").unwrap();
+		query.end_date = Some(now);
+		let (tx, mut rx) = mpsc::channel(16);
+		let mut searcher = env.searcher();
+		searcher.mode = StreamMode::SubscribeFuture;
+		searcher.live_tail = Some(&live_tail);
+
+		// `SubscribeFuture` must skip the historical match already sitting
+		// in `current`, so the first entry seen (after the `Tail` marker)
+		// should be the one sent after the search starts. `move` so `rx`
+		// drops the moment this future resolves, which is what lets
+		// `tx.closed()` resolve for the searcher's copy and ends `follow`'s
+		// loop.
+		let drain = async move {
+			loop {
+				match rx.recv().await {
+					Some(LogStreamItem::Tail) => continue,
+					other => return other,
+				}
+			}
+		};
+		let send_live_entry = async {
+			tokio::time::sleep(Duration::from_millis(50)).await;
+			live_tail
+				.send(LogEntry {
+					timestamp: Utc::now(),
+					level: LogLevel::Info,
+					props: vec![],
+					msg: "tail-msg".into(),
+					..Default::default()
+				})
+				.unwrap();
+		};
+
+		let (search_result, entry, _) = timeout(
+			Duration::from_secs(2),
+			tokio::join!(searcher.search(query, &tx), drain, send_live_entry),
+		)
+		.await
+		.expect("search + live tail did not settle in time");
+		search_result.unwrap();
+		match entry {
+			Some(LogStreamItem::Entry(log)) => assert_eq!(log.msg, "tail-msg"),
+			other => panic!("expected a live entry, got {:?}", other),
+		}
+	}
+
+	#[tokio::test]
+	async fn search_limit_emits_cursor_and_resumes_without_duplicates() {
+		let env = TestSearcherEnv::new();
+		let now = Utc::now();
+		let timestamps: Vec<_> = (0..3).map(|i| now - Duration::seconds(i)).collect();
+		{
+			let mut current = env.current.lock().await;
+			for (i, ts) in timestamps.iter().enumerate() {
+				current.add_log_entry(LogEntry {
+					timestamp: *ts,
+					level: LogLevel::Info,
+					props: vec![],
+					msg: format!("page-{}", i),
+					..Default::default()
+				});
+			}
+			current.sort();
+		}
+
+		let mut query = parse_log_query("msg ~ \"page\"").unwrap();
+		query.end_date = Some(now);
+
+		// Page through one match at a time; feeding each page's cursor back
+		// in should reproduce the full reverse-chronological result with no
+		// gaps or duplicates.
+		let mut cursor = None;
+		let mut seen = Vec::new();
+		for _ in 0..timestamps.len() {
+			let mut searcher = env.searcher();
+			searcher.limit = Some(1);
+			searcher.cursor = cursor.take();
+			let (tx, mut rx) = mpsc::channel(16);
+			searcher.search(query.clone(), &tx).await.unwrap();
+			drop(tx);
+
+			let mut page = Vec::new();
+			while let Some(item) = rx.recv().await {
+				match item {
+					LogStreamItem::Entry(log) => page.push(log),
+					LogStreamItem::Cursor(c) => cursor = Some(c),
+					_ => {}
+				}
+			}
+			assert_eq!(page.len(), 1);
+			seen.push(page[0].msg.clone());
+		}
+
+		assert_eq!(seen, vec!["page-0", "page-1", "page-2"]);
+	}
 }
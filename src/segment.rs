@@ -1,7 +1,8 @@
 use chrono::DateTime;
 use chrono::Utc;
+use puppylog::DrainParser;
 use puppylog::LogEntry;
-use puppylog::LogentryDeserializerError;
+use puppylog::LogLevel;
 use serde::Serialize;
 use std::cmp::Ordering;
 use std::io::Read;
@@ -34,8 +35,46 @@ impl<'a> Iterator for LogIterator<'a> {
 }
 
 pub const MAGIC: &str = "PUPPYLOGSEG";
-pub const VERSION: u16 = 1;
+pub const VERSION: u16 = 3;
+/// Size of the base header shared by every version: `MAGIC` (11 bytes) +
+/// `VERSION` (2 bytes, big-endian). A version-3 container has 4 more
+/// header bytes on top of this for `DEFAULT_INDEX_STRIDE` — see
+/// `LogSegment::parse`.
 pub const HEADER_SIZE: usize = 13;
+/// Trailing `entry_count: u64` + `checksum: u64` (both little-endian)
+/// appended after the entry bytes (and, from version 3 on, the block
+/// index) in the container, so `parse` can detect corruption without
+/// decoding every entry first.
+pub const FOOTER_SIZE: usize = 16;
+/// Size in bytes of one sparse block index entry: `timestamp_micros: i64`
+/// + `byte_offset: u64` (both little-endian).
+pub const INDEX_ENTRY_SIZE: usize = 16;
+/// How many entries apart each recorded block index entry is, written into
+/// a version-3 header and read back by `parse` into `index_stride` so a
+/// segment serialized with a different stride still seeks correctly.
+pub const DEFAULT_INDEX_STRIDE: u32 = 4096;
+/// Entry-encoding byte written (after the index stride) by a version-4
+/// container: the plain per-entry wire format `serialize` has always used,
+/// with `LogEntry.msg` stored verbatim.
+pub const ENCODING_RAW: u8 = 0;
+/// Entry-encoding byte written by a version-4 container produced by
+/// `serialize_templated`: every `LogEntry.msg` was run through a
+/// `DrainParser` and replaced with a compact `"{template_id}\x1f{params}"`
+/// marker, with the literal template text held once in a dictionary section
+/// instead of repeated on every entry. `parse` substitutes it back in.
+pub const ENCODING_TEMPLATED: u8 = 1;
+/// Container version written by `serialize_templated`. Adds one entry-
+/// encoding byte to the v3 header and, when that byte is `ENCODING_TEMPLATED`,
+/// a template dictionary section ahead of the entry bytes — everything else
+/// (block index, footer) is laid out exactly like v3, so `parse` shares all
+/// of that logic between the two.
+pub const TEMPLATED_VERSION: u16 = 4;
+/// Separates a templated entry's `template_id` from its params, and each
+/// param from the next, inside the compact marker stored in `LogEntry.msg`.
+/// A unit separator never occurs in a log message the wire format itself
+/// can carry (it isn't produced by `DrainParser::parse`'s tokenizing), so it
+/// can't collide with real param text.
+const TEMPLATE_FIELD_SEP: char = '\u{1f}';
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -48,23 +87,448 @@ pub struct SegmentMeta {
 	pub compressed_size: usize,
 	pub logs_count: u64,
 	pub created_at: DateTime<Utc>,
+	pub level: u32,
+	/// Bloom filter over the segment's `"key=value"` props, or `None` for
+	/// segments written before blooms existed. `None` must be treated as
+	/// "maybe matches" so old data keeps working. Not serialized: it's an
+	/// internal lookup structure, not API-facing metadata.
+	#[serde(skip_serializing)]
+	pub bloom: Option<Vec<u8>>,
+	/// FNV-1a checksum of the compressed bytes written to `{id}.log`, or
+	/// `None` for segments written before checksums existed. `None` is
+	/// treated as "unverifiable, trust it" rather than a failure.
+	#[serde(skip_serializing)]
+	pub checksum: Option<u64>,
+	/// Set by `ScrubWorker` when a checksum mismatch is found; quarantined
+	/// segments are excluded from `find_segments` so a single corrupt file
+	/// can't abort or poison a query.
+	pub quarantined: bool,
+	/// Whether `{id}.log` holds AES-256-GCM ciphertext (see
+	/// [`crate::encryption`]) rather than a plain compressed buffer, so a
+	/// store can hold a mix of both while a `SEGMENT_ENCRYPTION_KEY` is
+	/// rolled out.
+	pub encrypted: bool,
+	/// When a query last read this segment's bytes, updated by
+	/// [`crate::access_tracker::AccessTracker`] and used to order
+	/// `EvictionOrder::LeastRecentlyUsed` retention passes. `None` until the
+	/// segment is queried for the first time.
+	pub last_accessed: Option<DateTime<Utc>>,
+	/// Set via the `/api/v1/segment/{id}/pin` endpoint. A pinned segment is
+	/// skipped by `run_cleanup_pass` and `retention::plan_evictions`, so an
+	/// operator can hold onto a specific segment (e.g. one under
+	/// investigation) regardless of age, disk pressure, or retention policy.
+	pub pinned: bool,
+	/// Which [`crate::data_layout::DataLayout`] directory `{id}.log` was
+	/// written to, recorded by `DB::set_segment_data_dir` right after
+	/// `SegmentStore::put` picks it. `None` for single-directory deployments
+	/// and for segments written before this column existed; either way a
+	/// store falls back to scanning every configured directory. Not
+	/// serialized: a local filesystem path, not API-facing metadata.
+	#[serde(skip_serializing)]
+	pub data_dir: Option<String>,
+	/// Whether `{id}.log` holds zstd-compressed bytes. `compress_segment`
+	/// skips compression (storing the plain buffer instead) when the
+	/// encoded output isn't actually smaller than the input, which can
+	/// happen for small or already-dense segments; `true` for every segment
+	/// written before that skip existed, since compression was previously
+	/// unconditional.
+	pub compressed: bool,
+	/// When `ScrubWorker` last confirmed this segment decodes and matches
+	/// its metadata, so it can be re-verified on a configurable interval
+	/// instead of every full pass. `None` until the segment is scrubbed for
+	/// the first time.
+	#[serde(skip_serializing)]
+	pub last_scrubbed: Option<DateTime<Utc>>,
+}
+
+/// A segment's stored bytes failed their checksum, or the file was missing
+/// or unreadable — distinct from a decompression panic so callers can
+/// report a clean "skipped 1 corrupt segment" instead of crashing mid-scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentCorrupt {
+	pub segment_id: u32,
+}
+
+impl std::fmt::Display for SegmentCorrupt {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "segment {} failed its integrity checksum", self.segment_id)
+	}
+}
+
+impl std::error::Error for SegmentCorrupt {}
+
+/// Why `LogSegment::parse` couldn't return a fully-decoded segment. Unlike
+/// the old behaviour (panicking on a bad magic/version, silently dropping
+/// everything on a read error), every variant is a value the caller can
+/// inspect and recover from — a store reading thousands of segments can't
+/// afford one bad file to take the whole scan down.
+#[derive(Debug)]
+pub enum SegmentError {
+	/// The header's magic bytes didn't match `MAGIC` at all — not a
+	/// puppylog segment, or the file is garbage. No entries could be
+	/// located.
+	BadMagic,
+	/// The header's magic matched but the version byte is one this build
+	/// doesn't know how to decode.
+	UnsupportedVersion(u16),
+	/// The footer's checksum didn't match the entry bytes it covers.
+	/// `recovered` is whatever entries decoded cleanly before the footer
+	/// was checked, so a caller can still serve a best-effort prefix
+	/// instead of losing the segment outright.
+	ChecksumMismatch { recovered: LogSegment },
+	/// The stream ended before a full footer (or, for a legacy v1
+	/// segment, before a clean EOF) was reached. `recovered` holds
+	/// whatever entries were fully decoded up to the cut.
+	Truncated { recovered: LogSegment },
+}
+
+impl std::fmt::Display for SegmentError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SegmentError::BadMagic => write!(f, "segment has an invalid magic header"),
+			SegmentError::UnsupportedVersion(v) => write!(f, "segment has unsupported version {}", v),
+			SegmentError::ChecksumMismatch { recovered } => write!(
+				f,
+				"segment failed its footer checksum, recovered {} entries",
+				recovered.buffer.len()
+			),
+			SegmentError::Truncated { recovered } => write!(
+				f,
+				"segment is truncated, recovered {} entries",
+				recovered.buffer.len()
+			),
+		}
+	}
+}
+
+impl std::error::Error for SegmentError {}
+
+impl SegmentError {
+	/// The best-effort entries available despite the failure: the decoded
+	/// prefix for `ChecksumMismatch`/`Truncated`, or an empty segment for
+	/// `BadMagic`/`UnsupportedVersion`, where the header itself couldn't be
+	/// trusted enough to look for entries at all.
+	pub fn recovered(self) -> LogSegment {
+		match self {
+			SegmentError::ChecksumMismatch { recovered } => recovered,
+			SegmentError::Truncated { recovered } => recovered,
+			SegmentError::BadMagic | SegmentError::UnsupportedVersion(_) => LogSegment::from_buffer(Vec::new()),
+		}
+	}
+}
+
+/// Rough, pre-compression size of one entry once serialized — fixed
+/// overhead for the timestamp/level/random/length-prefix fields plus the
+/// variable-length message and prop strings. Doesn't need to match
+/// `LogEntry::serialize`'s wire format exactly, just track it closely
+/// enough to size a byte budget in practice.
+pub(crate) fn estimate_entry_size(entry: &LogEntry) -> usize {
+	const FIXED_OVERHEAD: usize = 32;
+	FIXED_OVERHEAD
+		+ entry.msg.len()
+		+ entry
+			.props
+			.iter()
+			.map(|p| p.key.len() + p.value.to_string().len())
+			.sum::<usize>()
+}
+
+/// Collects `msg`'s words at the positions where `template` (as returned by
+/// `DrainParser::get_template`) holds a `"*"` wildcard, joined by
+/// `TEMPLATE_FIELD_SEP` — the inverse of `substitute_template_params`.
+/// `msg` was the exact string the template was derived from (via
+/// `DrainParser::parse`), so splitting both on a plain space always lines
+/// up position-for-position.
+fn encode_template_params(msg: &str, template: &[String]) -> String {
+	msg.split(' ')
+		.zip(template.iter())
+		.filter(|(_, t)| t.as_str() == "*")
+		.map(|(word, _)| word)
+		.collect::<Vec<_>>()
+		.join(&TEMPLATE_FIELD_SEP.to_string())
+}
+
+/// Rebuilds the original message from a `"{template_id}\x1f{params}"`
+/// marker (as written by `serialize_templated`) plus the segment's
+/// dictionary, substituting each `"*"` in the template for the next param
+/// in order. Returns `None` for anything that doesn't parse as a marker
+/// (a malformed or truncated dictionary section), leaving the caller to
+/// decide how to degrade.
+fn substitute_template_params(marker: &str, dictionary: &[String]) -> Option<String> {
+	let (id, params) = marker.split_once(TEMPLATE_FIELD_SEP)?;
+	let template = dictionary.get(id.parse::<u32>().ok()?.checked_sub(1)? as usize)?;
+	let mut params = params.split(TEMPLATE_FIELD_SEP);
+	let mut msg = String::with_capacity(template.len());
+	for (i, token) in template.split(' ').enumerate() {
+		if i > 0 {
+			msg.push(' ');
+		}
+		msg.push_str(if token == "*" { params.next().unwrap_or("") } else { token });
+	}
+	Some(msg)
+}
+
+/// Decodes the length-prefixed template strings `serialize_templated` wrote
+/// into the dictionary section: repeated `len: u32` (little-endian) + that
+/// many UTF-8 bytes. Returns `None` if `bytes` doesn't cleanly divide into
+/// that shape, which `parse` treats the same as a truncated segment.
+fn decode_template_dictionary(bytes: &[u8]) -> Option<Vec<String>> {
+	let mut templates = Vec::new();
+	let mut ptr = 0;
+	while ptr < bytes.len() {
+		let len = u32::from_le_bytes(bytes.get(ptr..ptr + 4)?.try_into().unwrap()) as usize;
+		ptr += 4;
+		let raw = bytes.get(ptr..ptr + len)?;
+		templates.push(String::from_utf8_lossy(raw).into_owned());
+		ptr += len;
+	}
+	Some(templates)
+}
+
+/// Everything `LogSegment::parse` and `LogSegment::open_entries` need from a
+/// container once its header, optional dictionary and footer/index framing
+/// have been read, short of deciding how to turn `entry_bytes` into
+/// `LogEntry`s. `entry_count`/`expected_checksum` are `0` for a version-1
+/// container (no footer ever existed), which callers must ignore — matched
+/// by `index` being empty and `entry_bytes` covering the whole stream.
+struct ContainerBody {
+	version: u16,
+	encoding: u8,
+	entry_bytes: Vec<u8>,
+	index: Vec<(DateTime<Utc>, usize)>,
+	dictionary: Vec<String>,
+	entry_count: u64,
+	expected_checksum: u64,
+}
+
+/// Reads a container's header, optional v4 dictionary, and footer/index
+/// framing, returning the raw entry bytes without decoding them into
+/// `LogEntry`s. Shared by `LogSegment::parse` (eager) and
+/// `LogSegment::open_entries` (lazy) so both see identical truncation,
+/// corruption and version-handling behavior.
+fn read_container_body<R: Read>(reader: &mut R) -> Result<ContainerBody, SegmentError> {
+	let mut header = [0u8; HEADER_SIZE];
+	if let Err(err) = reader.read_exact(&mut header) {
+		log::warn!("failed to read segment header: {}", err);
+		return Err(SegmentError::Truncated {
+			recovered: LogSegment::from_buffer(Vec::new()),
+		});
+	}
+	let magic = String::from_utf8_lossy(&header[0..11]);
+	if magic != MAGIC {
+		return Err(SegmentError::BadMagic);
+	}
+	let version = u16::from_be_bytes(header[11..13].try_into().unwrap());
+	if version != 1 && version != 2 && version != VERSION && version != TEMPLATED_VERSION {
+		return Err(SegmentError::UnsupportedVersion(version));
+	}
+
+	let mut encoding = ENCODING_RAW;
+	if version == VERSION || version == TEMPLATED_VERSION {
+		let mut stride = [0u8; 4];
+		if let Err(err) = reader.read_exact(&mut stride) {
+			log::warn!("failed to read segment index stride: {}", err);
+			return Err(SegmentError::Truncated {
+				recovered: LogSegment::from_buffer(Vec::new()),
+			});
+		}
+		// Read back for forward-compatibility (a future writer could use
+		// a different stride); the index offsets are self-describing
+		// regardless of what stride produced them, so nothing further
+		// needs to be done with the value here.
+	}
+	if version == TEMPLATED_VERSION {
+		let mut byte = [0u8; 1];
+		if let Err(err) = reader.read_exact(&mut byte) {
+			log::warn!("failed to read segment encoding byte: {}", err);
+			return Err(SegmentError::Truncated {
+				recovered: LogSegment::from_buffer(Vec::new()),
+			});
+		}
+		encoding = byte[0];
+	}
+
+	let mut buff = Vec::new();
+	if let Err(err) = reader.read_to_end(&mut buff) {
+		log::warn!("truncated segment: {}", err);
+	}
+
+	if version == 1 {
+		return Ok(ContainerBody {
+			version,
+			encoding,
+			entry_bytes: buff,
+			index: Vec::new(),
+			dictionary: Vec::new(),
+			entry_count: 0,
+			expected_checksum: 0,
+		});
+	}
+
+	if buff.len() < FOOTER_SIZE {
+		return Err(SegmentError::Truncated {
+			recovered: LogSegment::decode_entries(&buff),
+		});
+	}
+	let split = buff.len() - FOOTER_SIZE;
+	let (mut rest, footer) = buff.split_at(split);
+	let entry_count = u64::from_le_bytes(footer[0..8].try_into().unwrap());
+	let expected_checksum = u64::from_le_bytes(footer[8..16].try_into().unwrap());
+
+	let mut dictionary = Vec::new();
+	if encoding == ENCODING_TEMPLATED {
+		if rest.len() < 4 {
+			return Err(SegmentError::Truncated {
+				recovered: LogSegment::from_buffer(Vec::new()),
+			});
+		}
+		let (len_bytes, after_len) = rest.split_at(4);
+		let dict_len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+		let Some((dict_bytes, after_dict)) =
+			(dict_len <= after_len.len()).then(|| after_len.split_at(dict_len))
+		else {
+			return Err(SegmentError::Truncated {
+				recovered: LogSegment::from_buffer(Vec::new()),
+			});
+		};
+		let Some(decoded) = decode_template_dictionary(dict_bytes) else {
+			return Err(SegmentError::Truncated {
+				recovered: LogSegment::from_buffer(Vec::new()),
+			});
+		};
+		dictionary = decoded;
+		rest = after_dict;
+	}
+
+	let (entry_bytes, index) = if version == VERSION || version == TEMPLATED_VERSION {
+		match LogSegment::split_index(rest) {
+			Some(parts) => parts,
+			None => {
+				return Err(SegmentError::Truncated {
+					recovered: LogSegment::decode_entries(rest),
+				});
+			}
+		}
+	} else {
+		(rest, Vec::new())
+	};
+
+	Ok(ContainerBody {
+		version,
+		encoding,
+		entry_bytes: entry_bytes.to_vec(),
+		index,
+		dictionary,
+		entry_count,
+		expected_checksum,
+	})
+}
+
+/// Entry-at-a-time cursor over a container's entry bytes, returned by
+/// [`LogSegment::open_entries`]. Decodes lazily via `LogEntry::fast_deserialize`
+/// and (for a v4/templated container) substitutes each entry's
+/// `"{template_id}\x1f{params}"` marker back to its original message on the
+/// way out, the same as `LogSegment::parse` does eagerly for its whole
+/// buffer.
+pub struct SegmentEntryStream {
+	entry_bytes: Vec<u8>,
+	ptr: usize,
+	dictionary: Vec<String>,
+}
+
+impl Iterator for SegmentEntryStream {
+	type Item = LogEntry;
+
+	fn next(&mut self) -> Option<LogEntry> {
+		let mut entry = LogEntry::fast_deserialize(&self.entry_bytes, &mut self.ptr).ok()?;
+		if !self.dictionary.is_empty() {
+			if let Some(msg) = substitute_template_params(&entry.msg, &self.dictionary) {
+				entry.msg = msg;
+			}
+		}
+		Some(entry)
+	}
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct LogSegment {
 	pub buffer: Vec<LogEntry>,
+	/// Byte budget enforced by `add_log_entry`; `usize::MAX` (the default
+	/// for every constructor except `with_max_bytes`) means "no budget",
+	/// which is what archived segments and compaction/export output want —
+	/// they're built once and never grown further, so there's nothing to
+	/// evict. Only a buffer built via `with_max_bytes` (the live in-memory
+	/// "recent tail", à la the Fuchsia logger's fixed-size ring buffer)
+	/// actually drops entries.
+	max_bytes: usize,
+	/// Running total of `estimate_entry_size` over `buffer`, kept in sync by
+	/// `add_log_entry` so enforcing the budget doesn't have to re-sum the
+	/// buffer on every insert.
+	bytes_used: usize,
+	/// Entries `add_log_entry` has dropped so far to stay under `max_bytes`,
+	/// so operators can see memory pressure on a bounded buffer.
+	evicted_count: u64,
+	/// Sparse `(timestamp, byte_offset)` block index, built by `serialize`
+	/// (one entry every `DEFAULT_INDEX_STRIDE` entries) and read back by
+	/// `parse` from a v3 container's footer. Empty for a segment that was
+	/// never serialized/parsed, or one loaded from a v1/v2 container
+	/// written before the index existed — `seek_to` falls back to a
+	/// full-scan-from-zero offset in either case.
+	index: Vec<(DateTime<Utc>, usize)>,
 }
 
 impl LogSegment {
+	/// Builds an unbounded segment directly from already-ordered `logs`,
+	/// skipping `with_logs`'s sort — for compaction/export code paths that
+	/// already produced `logs` in timestamp order.
+	pub fn from_buffer(buffer: Vec<LogEntry>) -> Self {
+		LogSegment {
+			buffer,
+			max_bytes: usize::MAX,
+			bytes_used: 0,
+			evicted_count: 0,
+			index: Vec::new(),
+		}
+	}
 	pub fn with_logs(mut logs: Vec<LogEntry>) -> Self {
 		logs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-		LogSegment { buffer: logs }
+		Self::from_buffer(logs)
 	}
 	pub fn new() -> Self {
 		LogSegment {
 			buffer: Vec::with_capacity(500_000),
+			max_bytes: usize::MAX,
+			bytes_used: 0,
+			evicted_count: 0,
+			index: Vec::new(),
+		}
+	}
+	/// Builds an empty segment with a byte budget: once `add_log_entry`
+	/// would push the running total over `max_bytes`, it evicts the oldest
+	/// entries (the buffer is expected to be kept sorted by timestamp, so
+	/// the front is always the oldest) until back under budget — a
+	/// fixed-size FIFO tail buffer, the way the Fuchsia logger caps its
+	/// in-memory ring to a few MB of recent messages instead of growing
+	/// unboundedly.
+	pub fn with_max_bytes(max_bytes: usize) -> Self {
+		LogSegment {
+			buffer: Vec::new(),
+			max_bytes,
+			bytes_used: 0,
+			evicted_count: 0,
+			index: Vec::new(),
 		}
 	}
+	/// Current estimated size (see `estimate_entry_size`) of everything in
+	/// `buffer`, updated incrementally by `add_log_entry`/eviction.
+	pub fn bytes_used(&self) -> usize {
+		self.bytes_used
+	}
+	/// How many entries `add_log_entry` has evicted so far to stay under
+	/// `max_bytes`. Always `0` for a buffer that was never given a budget.
+	pub fn evicted_count(&self) -> u64 {
+		self.evicted_count
+	}
 	pub fn iter(&self) -> LogIterator {
 		let i = self.buffer.len();
 		LogIterator::new(&self.buffer[..i], i)
@@ -85,56 +549,208 @@ impl LogSegment {
 	}
 
 	pub fn add_log_entry(&mut self, log: LogEntry) {
+		self.bytes_used += estimate_entry_size(&log);
 		self.buffer.push(log);
+		while self.bytes_used > self.max_bytes && !self.buffer.is_empty() {
+			let evicted = self.buffer.remove(0);
+			self.bytes_used -= estimate_entry_size(&evicted);
+			self.evicted_count += 1;
+		}
 	}
 
+	/// Writes the v3 container: header (with `DEFAULT_INDEX_STRIDE`), entry
+	/// bytes, a sparse block index (one `(timestamp, byte_offset)` pair
+	/// every `DEFAULT_INDEX_STRIDE` entries), then a footer of
+	/// `index_count: u64`, `entry_count: u64`, `checksum: u64` (all
+	/// little-endian), the last two matching the v2 footer so `parse`'s
+	/// corruption check doesn't need to special-case the index.
 	pub fn serialize<W: Write>(&self, writer: &mut W) {
 		writer.write_all(MAGIC.as_bytes()).unwrap();
 		writer.write_all(&VERSION.to_be_bytes()).unwrap();
-		for log in &self.buffer {
-			log.serialize(writer);
+		writer.write_all(&DEFAULT_INDEX_STRIDE.to_be_bytes()).unwrap();
+
+		let mut entry_bytes = Vec::new();
+		let mut index = Vec::new();
+		for (i, log) in self.buffer.iter().enumerate() {
+			if i % DEFAULT_INDEX_STRIDE as usize == 0 {
+				index.push((log.timestamp, entry_bytes.len() as u64));
+			}
+			log.serialize(&mut entry_bytes);
+		}
+
+		writer.write_all(&entry_bytes).unwrap();
+		for (timestamp, offset) in &index {
+			writer
+				.write_all(&timestamp.timestamp_micros().to_le_bytes())
+				.unwrap();
+			writer.write_all(&offset.to_le_bytes()).unwrap();
 		}
+		writer.write_all(&(index.len() as u64).to_le_bytes()).unwrap();
+		writer
+			.write_all(&(self.buffer.len() as u64).to_le_bytes())
+			.unwrap();
+		writer
+			.write_all(&crate::checksum::checksum(&entry_bytes).to_le_bytes())
+			.unwrap();
 	}
 
-	pub fn parse<R: Read>(reader: &mut R) -> Self {
-		use std::io::ErrorKind;
+	/// Writes a version-4 (`TEMPLATED_VERSION`) container: same header,
+	/// sparse-index and footer framing as `serialize`'s v3, but with an
+	/// extra entry-encoding byte after the index stride and a one-time
+	/// template dictionary ahead of the entry bytes. Every message is run
+	/// through a fresh `DrainParser` and replaced with a
+	/// `"{template_id}\x1f{params}"` marker before it's serialized — only
+	/// the tokens that vary from the template are written per entry, which
+	/// is what lets zstd shrink these segments harder than the equivalent
+	/// `serialize` output once the repeated template text is gone.
+	pub fn serialize_templated<W: Write>(&self, writer: &mut W) {
+		let mut parser = DrainParser::new();
+		let template_ids: Vec<u32> = self.buffer.iter().map(|log| parser.parse(&log.msg)).collect();
 
-		let mut header = [0u8; HEADER_SIZE];
-		if let Err(err) = reader.read_exact(&mut header) {
-			log::error!("failed to read segment header: {}", err);
-			return LogSegment { buffer: Vec::new() };
+		let mut dict_bytes = Vec::new();
+		for id in 1..=parser.get_templates_count() as u32 {
+			let template = parser.get_template(id).join(" ");
+			dict_bytes.extend_from_slice(&(template.len() as u32).to_le_bytes());
+			dict_bytes.extend_from_slice(template.as_bytes());
 		}
-		let magic = String::from_utf8_lossy(&header[0..11]);
-		if magic != MAGIC {
-			panic!("Invalid magic: {}", magic);
+
+		writer.write_all(MAGIC.as_bytes()).unwrap();
+		writer.write_all(&TEMPLATED_VERSION.to_be_bytes()).unwrap();
+		writer.write_all(&DEFAULT_INDEX_STRIDE.to_be_bytes()).unwrap();
+		writer.write_all(&[ENCODING_TEMPLATED]).unwrap();
+		writer.write_all(&(dict_bytes.len() as u32).to_le_bytes()).unwrap();
+		writer.write_all(&dict_bytes).unwrap();
+
+		let mut entry_bytes = Vec::new();
+		let mut index = Vec::new();
+		for (i, (log, template_id)) in self.buffer.iter().zip(template_ids.iter()).enumerate() {
+			if i % DEFAULT_INDEX_STRIDE as usize == 0 {
+				index.push((log.timestamp, entry_bytes.len() as u64));
+			}
+			let params = encode_template_params(&log.msg, parser.get_template(*template_id));
+			let mut encoded = log.clone();
+			encoded.msg = format!("{template_id}{TEMPLATE_FIELD_SEP}{params}");
+			encoded.serialize(&mut entry_bytes);
 		}
-		let version = u16::from_be_bytes(header[11..13].try_into().unwrap());
-		if version != VERSION {
-			panic!("Invalid version: {}", version);
+
+		writer.write_all(&entry_bytes).unwrap();
+		for (timestamp, offset) in &index {
+			writer
+				.write_all(&timestamp.timestamp_micros().to_le_bytes())
+				.unwrap();
+			writer.write_all(&offset.to_le_bytes()).unwrap();
 		}
+		writer.write_all(&(index.len() as u64).to_le_bytes()).unwrap();
+		writer
+			.write_all(&(self.buffer.len() as u64).to_le_bytes())
+			.unwrap();
+		writer
+			.write_all(&crate::checksum::checksum(&entry_bytes).to_le_bytes())
+			.unwrap();
+	}
+
+	/// Decodes as many entries as `buff` cleanly yields, stopping at the
+	/// first `fast_deserialize` error (whether that's a clean end-of-buffer
+	/// or mid-entry corruption) rather than panicking, so a caller always
+	/// gets a usable prefix back.
+	fn decode_entries(buff: &[u8]) -> Self {
 		let mut log_entries = Vec::new();
-		let mut buff = Vec::new();
 		let mut ptr = 0;
-		match reader.read_to_end(&mut buff) {
-			Ok(_) => {}
-			Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
-				log::warn!("truncated segment: {}", err);
-			}
-			Err(err) => {
-				log::error!("failed to read segment: {}", err);
-				return LogSegment { buffer: Vec::new() };
-			}
-		}
 		loop {
-			match LogEntry::fast_deserialize(&buff, &mut ptr) {
+			match LogEntry::fast_deserialize(buff, &mut ptr) {
 				Ok(log_entry) => log_entries.push(log_entry),
-				Err(LogentryDeserializerError::NotEnoughData) => break,
-				Err(err) => panic!("Error deserializing log entry: {:?}", err),
+				Err(_) => break,
 			}
 		}
-		LogSegment {
-			buffer: log_entries,
+		LogSegment::from_buffer(log_entries)
+	}
+
+	/// Parses a segment container, never panicking: a bad magic or an
+	/// unsupported version is reported directly, and a footer checksum
+	/// mismatch or truncated stream still hands back whatever prefix of
+	/// entries decoded cleanly via [`SegmentError::recovered`]. Version-1
+	/// segments (written before the footer existed) are read as a bare
+	/// stream of entries with no checksum to verify; version-2 segments
+	/// (footer, no block index) are checksummed but come back with an
+	/// empty `index` so `seek_to` falls back to a full scan.
+	pub fn parse<R: Read>(reader: &mut R) -> Result<Self, SegmentError> {
+		let body = read_container_body(reader)?;
+		if body.version == 1 {
+			return Ok(Self::decode_entries(&body.entry_bytes));
 		}
+
+		let mut recovered = Self::decode_entries(&body.entry_bytes);
+		recovered.index = body.index;
+		if body.encoding == ENCODING_TEMPLATED {
+			for entry in recovered.buffer.iter_mut() {
+				if let Some(msg) = substitute_template_params(&entry.msg, &body.dictionary) {
+					entry.msg = msg;
+				}
+			}
+		}
+		if crate::checksum::checksum(&body.entry_bytes) != body.expected_checksum {
+			return Err(SegmentError::ChecksumMismatch { recovered });
+		}
+		if recovered.buffer.len() as u64 != body.entry_count {
+			return Err(SegmentError::Truncated { recovered });
+		}
+		Ok(recovered)
+	}
+
+	/// Splits a v3 segment's entry-bytes-plus-index region (`rest`, i.e.
+	/// everything between the header and the `entry_count`/`checksum`
+	/// footer) into the entry bytes and the decoded sparse index. `rest`
+	/// ends with `index_count: u64` preceded by `index_count` fixed-size
+	/// `INDEX_ENTRY_SIZE` records; anything before that is entry bytes.
+	/// Returns `None` if `rest` is too short to hold a valid index region,
+	/// which `parse` treats as truncation.
+	fn split_index(rest: &[u8]) -> Option<(&[u8], Vec<(DateTime<Utc>, usize)>)> {
+		if rest.len() < 8 {
+			return None;
+		}
+		let index_count_pos = rest.len() - 8;
+		let index_count = u64::from_le_bytes(rest[index_count_pos..].try_into().unwrap()) as usize;
+		let index_bytes_len = index_count.checked_mul(INDEX_ENTRY_SIZE)?;
+		if index_bytes_len > index_count_pos {
+			return None;
+		}
+		let entry_bytes_len = index_count_pos - index_bytes_len;
+		let entry_bytes = &rest[..entry_bytes_len];
+		let index_bytes = &rest[entry_bytes_len..index_count_pos];
+		let mut index = Vec::with_capacity(index_count);
+		for chunk in index_bytes.chunks_exact(INDEX_ENTRY_SIZE) {
+			let ts_micros = i64::from_le_bytes(chunk[0..8].try_into().unwrap());
+			let offset = u64::from_le_bytes(chunk[8..16].try_into().unwrap());
+			let Some(timestamp) = DateTime::from_timestamp_micros(ts_micros) else {
+				continue;
+			};
+			index.push((timestamp, offset as usize));
+		}
+		Some((entry_bytes, index))
+	}
+
+	/// Lazily decodes one [`LogEntry`] at a time from a container, instead of
+	/// `parse`'s eagerly-built `Vec<LogEntry>`. Shares `read_container_body`
+	/// with `parse`, so header/footer/dictionary truncation and
+	/// version-handling errors match exactly; unlike `parse`, a footer
+	/// checksum mismatch isn't surfaced as `Err` here (there's no eagerly
+	/// decoded buffer to attach as `recovered`) — the stream just yields
+	/// whatever `fast_deserialize` can pull from `entry_bytes`, the same
+	/// best-effort behavior `decode_entries` has always had. Used by
+	/// `DeviceSegmentCompactor`'s k-way merge, which needs a cursor per
+	/// input segment live at once rather than every segment's full
+	/// `Vec<LogEntry>` held simultaneously.
+	pub fn open_entries<R: Read>(reader: &mut R) -> Result<SegmentEntryStream, SegmentError> {
+		let body = read_container_body(reader)?;
+		Ok(SegmentEntryStream {
+			entry_bytes: body.entry_bytes,
+			ptr: 0,
+			dictionary: if body.encoding == ENCODING_TEMPLATED {
+				body.dictionary
+			} else {
+				Vec::new()
+			},
+		})
 	}
 
 	pub fn contains_date(&self, date: DateTime<Utc>) -> bool {
@@ -144,13 +760,125 @@ impl LogSegment {
 		let first = self.buffer.first().unwrap();
 		date >= first.timestamp
 	}
+
+	/// First index whose entry's timestamp is `>= date`, i.e. `date_index`'s
+	/// mirror image: `date_index` finds the exclusive end of a `<= date`
+	/// range, this finds the inclusive start of a `>= date` range.
+	fn date_lower_bound(&self, date: DateTime<Utc>) -> usize {
+		self.buffer.partition_point(|log| log.timestamp < date)
+	}
+
+	/// Binary-searches `index` (the sparse block index `parse` read back
+	/// from a v3 container's footer) for the byte offset of the last
+	/// indexed block at or before `date`, so a caller holding the raw
+	/// (decompressed, pre-decode) entry bytes can resume
+	/// `LogEntry::fast_deserialize` from there instead of decoding the
+	/// whole segment from byte 0 — a decode of one block plus the tail
+	/// rather than a full linear scan. Returns `0`, meaning "start from the
+	/// beginning", when `date` precedes the first block or no index is
+	/// present (a v1/v2 segment, or one with fewer than
+	/// `DEFAULT_INDEX_STRIDE` entries).
+	pub fn seek_to(&self, date: DateTime<Utc>) -> usize {
+		match self.index.partition_point(|(ts, _)| *ts <= date) {
+			0 => 0,
+			n => self.index[n - 1].1,
+		}
+	}
+
+	/// Iterates `buffer` newest-first (matching `iter`), applying `filter`'s
+	/// timestamp window up front via `date_index`/`date_lower_bound` so an
+	/// out-of-range prefix or suffix is skipped without scanning every entry,
+	/// then `SegmentFilter::matches` per remaining entry for severity/tags.
+	pub fn iter_filtered<'a>(
+		&'a self,
+		filter: &'a SegmentFilter,
+	) -> impl Iterator<Item = &'a LogEntry> + 'a {
+		let start = filter.first.map_or(0, |d| self.date_lower_bound(d));
+		let end = filter
+			.last
+			.map_or(self.buffer.len(), |d| self.date_index(d));
+		self.buffer[start..end.min(self.buffer.len()).max(start)]
+			.iter()
+			.rev()
+			.filter(move |log| filter.matches(log))
+	}
+}
+
+/// Server-side predicate for `LogSegment::iter_filtered`, ported from the
+/// filtering model Fuchsia's `log_listener` applies while tailing: a minimum
+/// severity plus include/ignore sets of tags, so callers can narrow a
+/// segment down before it's shipped to a client instead of filtering
+/// whole-segment results in memory.
+#[derive(Debug, Clone, Default)]
+pub struct SegmentFilter {
+	/// Entries below this level are dropped. Compared via `LogLevel`'s
+	/// existing `PartialOrd`, so `None` (no minimum) keeps everything.
+	pub min_level: Option<LogLevel>,
+	/// `"key=value"` tags (the same format `SegmentBloom` indexes props
+	/// under) an entry must have at least one of to pass. Empty means no
+	/// include filter is applied.
+	pub include_tags: Vec<String>,
+	/// `"key=value"` tags that disqualify an entry if any are present.
+	/// Checked before `include_tags` so an explicit ignore always wins.
+	pub exclude_tags: Vec<String>,
+	/// Inclusive lower timestamp bound.
+	pub first: Option<DateTime<Utc>>,
+	/// Inclusive upper timestamp bound.
+	pub last: Option<DateTime<Utc>>,
+}
+
+impl SegmentFilter {
+	fn matches(&self, entry: &LogEntry) -> bool {
+		if let Some(min_level) = self.min_level {
+			if entry.level < min_level {
+				return false;
+			}
+		}
+		if !self.exclude_tags.is_empty()
+			&& entry
+				.props
+				.iter()
+				.any(|p| self.exclude_tags.iter().any(|t| *t == format!("{}={}", p.key, p.value)))
+		{
+			return false;
+		}
+		if !self.include_tags.is_empty()
+			&& !entry
+				.props
+				.iter()
+				.any(|p| self.include_tags.iter().any(|t| *t == format!("{}={}", p.key, p.value)))
+		{
+			return false;
+		}
+		true
+	}
 }
 
-pub fn compress_segment(buf: &[u8]) -> anyhow::Result<Vec<u8>> {
-	let mut encoder = Encoder::new(Vec::new(), 14)?;
+/// Reads `SEGMENT_COMPRESSION_LEVEL`, falling back to the default of 14 if
+/// unset or unparseable.
+fn compression_level() -> i32 {
+	std::env::var("SEGMENT_COMPRESSION_LEVEL")
+		.ok()
+		.and_then(|v| v.parse().ok())
+		.unwrap_or(14)
+}
+
+/// Zstd-compresses `buf` at `SEGMENT_COMPRESSION_LEVEL` (default 14), unless
+/// the encoded output isn't actually smaller than the input (possible for
+/// small or already-dense segments), in which case the plain bytes are
+/// returned instead. The returned `bool` is `true` when the first element is
+/// zstd-compressed, `false` when it's the untouched input; callers persist it
+/// via `DB::set_segment_compressed` so a reader knows whether to decode.
+pub fn compress_segment(buf: &[u8]) -> anyhow::Result<(Vec<u8>, bool)> {
+	let mut encoder = Encoder::new(Vec::new(), compression_level())?;
 	encoder.multithread(num_cpus::get() as u32)?;
-	encoder.write_all(&buf)?;
-	Ok(encoder.finish()?)
+	encoder.write_all(buf)?;
+	let compressed = encoder.finish()?;
+	if compressed.len() < buf.len() {
+		Ok((compressed, true))
+	} else {
+		Ok((buf.to_vec(), false))
+	}
 }
 
 #[cfg(test)]
@@ -174,7 +902,7 @@ mod tests {
 			msg: "Hello, world!".to_string(),
 			props: vec![Prop {
 				key: "key".to_string(),
-				value: "value".to_string(),
+				value: "value".to_string().into(),
 			}],
 			..Default::default()
 		};
@@ -188,10 +916,48 @@ mod tests {
 		let mut buff = Vec::new();
 		segment.serialize(&mut buff);
 		let mut reader = Cursor::new(buff);
-		let segment2 = LogSegment::parse(&mut reader);
+		let segment2 = LogSegment::parse(&mut reader).unwrap();
 		assert_eq!(segment, segment2);
 	}
 
+	#[test]
+	pub fn checksum_mismatch_recovers_prefix() {
+		let mut segment = LogSegment::new();
+		let timestamp = DateTime::from_timestamp_micros(1740074054 * 1_000_000).unwrap();
+		segment.add_log_entry(LogEntry {
+			random: 0,
+			timestamp,
+			level: LogLevel::Info,
+			msg: "Hello".to_string(),
+			props: vec![],
+			..Default::default()
+		});
+
+		let mut buff = Vec::new();
+		segment.serialize(&mut buff);
+		// Flip a byte inside the footer's checksum so the entries still
+		// decode cleanly but the checksum no longer matches.
+		let last = buff.len() - 1;
+		buff[last] ^= 0xff;
+
+		let mut reader = Cursor::new(buff);
+		match LogSegment::parse(&mut reader) {
+			Err(SegmentError::ChecksumMismatch { recovered }) => {
+				assert_eq!(recovered.buffer.len(), 1);
+			}
+			other => panic!("expected ChecksumMismatch, got {:?}", other),
+		}
+	}
+
+	#[test]
+	pub fn bad_magic_is_reported_not_panicked() {
+		let mut reader = Cursor::new(vec![0u8; HEADER_SIZE]);
+		assert!(matches!(
+			LogSegment::parse(&mut reader),
+			Err(SegmentError::BadMagic)
+		));
+	}
+
 	#[test]
 	pub fn parse_truncated_does_not_panic() {
 		let mut segment = LogSegment::new();
@@ -217,4 +983,146 @@ mod tests {
 		let mut dec = Decoder::new(cursor).unwrap();
 		let _ = LogSegment::parse(&mut dec);
 	}
+
+	#[test]
+	pub fn version_1_segment_without_footer_still_loads() {
+		let mut segment = LogSegment::new();
+		let timestamp = DateTime::from_timestamp_micros(1740074054 * 1_000_000).unwrap();
+		segment.add_log_entry(LogEntry {
+			random: 0,
+			timestamp,
+			level: LogLevel::Info,
+			msg: "legacy".to_string(),
+			props: vec![],
+			..Default::default()
+		});
+
+		let mut buff = Vec::new();
+		buff.extend_from_slice(MAGIC.as_bytes());
+		buff.extend_from_slice(&1u16.to_be_bytes());
+		for log in segment.iter() {
+			log.serialize(&mut buff);
+		}
+
+		let mut reader = Cursor::new(buff);
+		let parsed = LogSegment::parse(&mut reader).unwrap();
+		assert_eq!(parsed.buffer.len(), 1);
+		assert_eq!(parsed.buffer[0].msg, "legacy");
+	}
+
+	fn entry(msg: &str, seconds: i64) -> LogEntry {
+		LogEntry {
+			timestamp: DateTime::from_timestamp_micros(1740074054 * 1_000_000 + seconds * 1_000_000)
+				.unwrap(),
+			level: LogLevel::Info,
+			msg: msg.to_string(),
+			props: vec![],
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn unbounded_segment_never_evicts() {
+		let mut segment = LogSegment::new();
+		for i in 0..1000 {
+			segment.add_log_entry(entry(&format!("msg-{i}"), i));
+		}
+		assert_eq!(segment.buffer.len(), 1000);
+		assert_eq!(segment.evicted_count(), 0);
+	}
+
+	#[test]
+	fn budgeted_segment_evicts_oldest_first() {
+		let size = estimate_entry_size(&entry("x", 0));
+		let mut segment = LogSegment::with_max_bytes(size * 2);
+		segment.add_log_entry(entry("first", 0));
+		segment.add_log_entry(entry("second", 1));
+		segment.add_log_entry(entry("third", 2));
+
+		let msgs: Vec<_> = segment.buffer.iter().map(|l| l.msg.clone()).collect();
+		assert_eq!(msgs, vec!["second", "third"]);
+		assert_eq!(segment.evicted_count(), 1);
+		assert_eq!(segment.bytes_used(), size * 2);
+	}
+
+	#[test]
+	fn with_max_bytes_tracks_bytes_used() {
+		let mut segment = LogSegment::with_max_bytes(1_000_000);
+		assert_eq!(segment.bytes_used(), 0);
+		segment.add_log_entry(entry("hello", 0));
+		assert_eq!(segment.bytes_used(), estimate_entry_size(&entry("hello", 0)));
+	}
+
+	#[test]
+	fn parsed_segment_seeks_via_sparse_index() {
+		let stride = DEFAULT_INDEX_STRIDE as i64;
+		let total = stride * 3;
+		let mut segment = LogSegment::new();
+		for i in 0..total {
+			segment.add_log_entry(entry(&format!("msg-{i}"), i));
+		}
+
+		let mut buff = Vec::new();
+		segment.serialize(&mut buff);
+		let mut reader = Cursor::new(buff);
+		let parsed = LogSegment::parse(&mut reader).unwrap();
+
+		assert_eq!(parsed.index.len(), 3);
+
+		let second_block_ts = parsed.index[1].0;
+		assert_eq!(parsed.seek_to(second_block_ts), parsed.index[1].1);
+
+		// Seeking just before the second block's timestamp should land on
+		// the first block instead.
+		let just_before = second_block_ts - chrono::Duration::microseconds(1);
+		assert_eq!(parsed.seek_to(just_before), parsed.index[0].1);
+	}
+
+	#[test]
+	fn seek_to_without_index_falls_back_to_zero() {
+		let segment = LogSegment::new();
+		assert_eq!(segment.seek_to(Utc::now()), 0);
+	}
+
+	#[test]
+	fn templated_segment_round_trips_messages() {
+		let mut segment = LogSegment::new();
+		for i in 0..5 {
+			segment.add_log_entry(entry(&format!("user {i} logged in"), i));
+		}
+		segment.add_log_entry(entry("unrelated message", 5));
+
+		let mut buff = Vec::new();
+		segment.serialize_templated(&mut buff);
+		let mut reader = Cursor::new(buff);
+		let parsed = LogSegment::parse(&mut reader).unwrap();
+
+		let msgs: Vec<_> = parsed.buffer.iter().map(|l| l.msg.clone()).collect();
+		assert_eq!(
+			msgs,
+			vec![
+				"user 0 logged in",
+				"user 1 logged in",
+				"user 2 logged in",
+				"user 3 logged in",
+				"user 4 logged in",
+				"unrelated message",
+			]
+		);
+	}
+
+	#[test]
+	fn templated_segment_shrinks_below_raw_serialization() {
+		let mut segment = LogSegment::new();
+		for i in 0..200 {
+			segment.add_log_entry(entry(&format!("user {i} logged in from 10.0.0.{i}"), i));
+		}
+
+		let mut raw = Vec::new();
+		segment.serialize(&mut raw);
+		let mut templated = Vec::new();
+		segment.serialize_templated(&mut templated);
+
+		assert!(templated.len() < raw.len());
+	}
 }
@@ -0,0 +1,102 @@
+//! Free-list allocator for segment file slots, so `DeviceMerger` can recycle
+//! a just-deleted segment's id for its next flush instead of always minting
+//! a brand-new one. Without this, a batch pass's delete-old/create-new
+//! churn briefly doubles the merger's on-disk footprint (every orphan's
+//! bytes plus every replacement segment's bytes coexist, even though the
+//! orphans are logically gone the moment their replacement lands).
+//!
+//! Reuse is delayed on purpose: a freed id only becomes available once
+//! `delay` other ids have been freed after it, so a reader that grabbed the
+//! old segment's path moments before the delete (e.g. a `find_logs` stream
+//! already reading `{id}.log`) has time to finish before the id is handed
+//! back out with different bytes behind it.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+#[derive(Debug)]
+pub struct SegmentSlotAllocator {
+	delay: usize,
+	freed: Mutex<VecDeque<u32>>,
+}
+
+impl SegmentSlotAllocator {
+	pub fn new(delay: usize) -> Self {
+		Self {
+			delay,
+			freed: Mutex::new(VecDeque::new()),
+		}
+	}
+
+	/// Reads `SEGMENT_SLOT_REUSE_DELAY`, falling back to a default of 16
+	/// reclaimed ids of lag if unset or unparseable.
+	pub fn from_env() -> Self {
+		let delay = std::env::var("SEGMENT_SLOT_REUSE_DELAY")
+			.ok()
+			.and_then(|v| v.parse().ok())
+			.unwrap_or(16);
+		Self::new(delay)
+	}
+
+	/// Marks `id` as reclaimable. It won't be handed out by `take` until
+	/// `delay` more ids have been freed after it.
+	pub fn free(&self, id: u32) {
+		self.freed.lock().unwrap().push_back(id);
+	}
+
+	/// Returns the oldest freed id that has aged behind at least `delay`
+	/// more recent frees, or `None` if nothing has aged enough yet (or the
+	/// free list is empty).
+	pub fn take(&self) -> Option<u32> {
+		let mut freed = self.freed.lock().unwrap();
+		if freed.len() > self.delay {
+			freed.pop_front()
+		} else {
+			None
+		}
+	}
+
+	/// Ids currently sitting in the free list, whether aged enough for
+	/// `take` or not — exposed for a Prometheus gauge so an operator can see
+	/// whether reuse is keeping up with the delete rate.
+	pub fn depth(&self) -> usize {
+		self.freed.lock().unwrap().len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn withholds_reuse_until_delay_elapses() {
+		let allocator = SegmentSlotAllocator::new(2);
+		allocator.free(1);
+		assert_eq!(allocator.take(), None, "only one id freed, delay is 2");
+		allocator.free(2);
+		assert_eq!(allocator.take(), None, "two ids freed, still not past the delay");
+		allocator.free(3);
+		assert_eq!(allocator.take(), Some(1), "three ids freed now outpaces the delay of 2");
+	}
+
+	#[test]
+	fn reuses_oldest_freed_id_first() {
+		let allocator = SegmentSlotAllocator::new(0);
+		allocator.free(5);
+		allocator.free(6);
+		assert_eq!(allocator.take(), Some(5));
+		assert_eq!(allocator.take(), Some(6));
+		assert_eq!(allocator.take(), None);
+	}
+
+	#[test]
+	fn depth_counts_ids_still_waiting_or_ready() {
+		let allocator = SegmentSlotAllocator::new(1);
+		assert_eq!(allocator.depth(), 0);
+		allocator.free(1);
+		allocator.free(2);
+		assert_eq!(allocator.depth(), 2);
+		allocator.take();
+		assert_eq!(allocator.depth(), 1);
+	}
+}
@@ -0,0 +1,415 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use crate::data_layout::DataLayout;
+
+/// Where flushed and compacted segment bytes live. `flush_locked`,
+/// `find_logs`, `LogSearcher`, and the background compactors/scrubber all go
+/// through this instead of hard-coding `File::create`/`File::open`, so a
+/// segment can be served from local disk or an object store transparently.
+#[async_trait]
+pub trait SegmentStore: std::fmt::Debug + Send + Sync {
+	/// Returns the data directory the segment was actually written to
+	/// (`Some` for a multi-directory `LocalFsStore`, `None` for a
+	/// single-directory store or a non-directory backend like S3), so the
+	/// caller can persist it via `DB::set_segment_data_dir` for `get`/
+	/// `delete` to go straight there next time.
+	async fn put(&self, segment_id: u32, bytes: Vec<u8>) -> anyhow::Result<Option<String>>;
+	/// `dir_hint` is the segment's `SegmentMeta::data_dir`, if recorded;
+	/// passing `None` (an older segment, or a backend that doesn't track
+	/// directories) falls back to whatever default search the backend uses.
+	async fn get(&self, segment_id: u32, dir_hint: Option<&str>) -> anyhow::Result<Bytes>;
+	async fn delete(&self, segment_id: u32, dir_hint: Option<&str>) -> anyhow::Result<()>;
+
+	/// Moves a corrupt segment's bytes to quarantine storage instead of
+	/// discarding them, so `ScrubWorker` (and an operator investigating the
+	/// alert it fires) can inspect what actually went wrong. Default falls
+	/// back to `delete` for backends without a natural "move" operation.
+	async fn quarantine(&self, segment_id: u32, dir_hint: Option<&str>) -> anyhow::Result<()> {
+		self.delete(segment_id, dir_hint).await
+	}
+
+	/// Data directories backing this store, for the disk-space monitor to
+	/// evaluate individually. Empty for backends (like S3) that aren't
+	/// directory-based.
+	fn data_dirs(&self) -> &[crate::data_layout::DataDir] {
+		&[]
+	}
+
+	/// Crash-recovery pass, run once at startup: drop anything `put` left
+	/// half-written from a crash mid-write. No-op for backends (like S3)
+	/// that install objects atomically on their own.
+	fn recover(&self) {}
+}
+
+/// One `{id}.log` file per segment, spread across the directories in a
+/// [`DataLayout`] (a single unbounded directory when `SEGMENT_DATA_DIRS`
+/// isn't configured).
+#[derive(Debug)]
+pub struct LocalFsStore {
+	layout: DataLayout,
+}
+
+impl LocalFsStore {
+	pub fn new(dir: PathBuf) -> Self {
+		Self {
+			layout: DataLayout::single(dir),
+		}
+	}
+
+	pub fn with_layout(layout: DataLayout) -> Self {
+		Self { layout }
+	}
+
+	fn path(dir: &Path, segment_id: u32) -> PathBuf {
+		dir.join(format!("{}.log", segment_id))
+	}
+
+	fn tmp_path(dir: &Path, segment_id: u32) -> PathBuf {
+		dir.join(format!("{}.log.tmp", segment_id))
+	}
+}
+
+#[async_trait]
+impl SegmentStore for LocalFsStore {
+	/// Writes to a `.tmp` sibling, fsyncs it, then atomically renames it into
+	/// place — so a crash mid-write either leaves the old `{id}.log` (if any)
+	/// untouched or a `.log.tmp` leftover for `recover` to clean up, never a
+	/// torn `{id}.log` that `LogSegment::parse` (or `ScrubWorker`'s checksum
+	/// check) would have to discover is corrupt.
+	async fn put(&self, segment_id: u32, bytes: Vec<u8>) -> anyhow::Result<Option<String>> {
+		use tokio::io::AsyncWriteExt;
+		let dir = self.layout.dir_for_write();
+		let tmp_path = Self::tmp_path(&dir.path, segment_id);
+		let mut file = tokio::fs::File::create(&tmp_path).await?;
+		file.write_all(&bytes).await?;
+		file.sync_all().await?;
+		drop(file);
+		tokio::fs::rename(&tmp_path, Self::path(&dir.path, segment_id)).await?;
+		dir.record_write(bytes.len() as u64);
+		// A single-directory layout (the common case) doesn't need its one
+		// directory recorded per segment; only report it once there's
+		// actually a choice to remember.
+		Ok((self.layout.dirs().len() > 1).then(|| dir.path.display().to_string()))
+	}
+
+	async fn get(&self, segment_id: u32, dir_hint: Option<&str>) -> anyhow::Result<Bytes> {
+		if let Some(hint) = dir_hint {
+			match tokio::fs::read(Self::path(Path::new(hint), segment_id)).await {
+				Ok(bytes) => return Ok(Bytes::from(bytes)),
+				Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+				Err(err) => return Err(err.into()),
+			}
+		}
+		for dir in self.layout.search_order(segment_id) {
+			match tokio::fs::read(Self::path(&dir.path, segment_id)).await {
+				Ok(bytes) => return Ok(Bytes::from(bytes)),
+				Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+				Err(err) => return Err(err.into()),
+			}
+		}
+		Err(std::io::Error::from(std::io::ErrorKind::NotFound).into())
+	}
+
+	async fn delete(&self, segment_id: u32, dir_hint: Option<&str>) -> anyhow::Result<()> {
+		if let Some(hint) = dir_hint {
+			let path = Self::path(Path::new(hint), segment_id);
+			match tokio::fs::metadata(&path).await {
+				Ok(meta) => {
+					let len = meta.len();
+					tokio::fs::remove_file(&path).await?;
+					if let Some(dir) = self.layout.dirs().iter().find(|d| d.path == Path::new(hint)) {
+						dir.record_delete(len);
+					}
+					return Ok(());
+				}
+				Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+				Err(err) => return Err(err.into()),
+			}
+		}
+		for dir in self.layout.search_order(segment_id) {
+			let path = Self::path(&dir.path, segment_id);
+			match tokio::fs::metadata(&path).await {
+				Ok(meta) => {
+					let len = meta.len();
+					tokio::fs::remove_file(&path).await?;
+					dir.record_delete(len);
+					return Ok(());
+				}
+				Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+				Err(err) => return Err(err.into()),
+			}
+		}
+		Ok(())
+	}
+
+	/// Moves `{id}.log` into a `corrupt/` subdirectory of whichever data dir
+	/// holds it, rather than deleting it, so the quarantined bytes are still
+	/// around for forensic inspection after `ScrubWorker` flags them.
+	async fn quarantine(&self, segment_id: u32, dir_hint: Option<&str>) -> anyhow::Result<()> {
+		let dirs: Vec<&Path> = if let Some(hint) = dir_hint {
+			vec![Path::new(hint)]
+		} else {
+			self.layout.search_order(segment_id).into_iter().map(|d| d.path.as_path()).collect()
+		};
+		for dir in dirs {
+			let path = Self::path(dir, segment_id);
+			match tokio::fs::metadata(&path).await {
+				Ok(meta) => {
+					let len = meta.len();
+					let quarantine_dir = dir.join("corrupt");
+					tokio::fs::create_dir_all(&quarantine_dir).await?;
+					tokio::fs::rename(&path, quarantine_dir.join(format!("{}.log", segment_id))).await?;
+					if let Some(d) = self.layout.dirs().iter().find(|d| d.path == dir) {
+						d.record_delete(len);
+					}
+					return Ok(());
+				}
+				Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+				Err(err) => return Err(err.into()),
+			}
+		}
+		Ok(())
+	}
+
+	fn data_dirs(&self) -> &[crate::data_layout::DataDir] {
+		self.layout.dirs()
+	}
+
+	/// Scans every data dir for `.log.tmp` leftovers and deletes them. A
+	/// `.tmp` file is by definition not yet the durable `{id}.log` content —
+	/// `put` only renames it into place once it's fully written and synced —
+	/// so discarding it outright is always safe. If the crash happened after
+	/// the segment's DB row was already committed, that row now points at a
+	/// missing `.log`; `get`'s `NotFound` path and `ScrubWorker` already
+	/// handle a segment whose bytes can't be read, so this pass doesn't need
+	/// to reconcile the DB side too.
+	fn recover(&self) {
+		for dir in self.layout.dirs() {
+			let entries = match std::fs::read_dir(&dir.path) {
+				Ok(entries) => entries,
+				Err(err) => {
+					log::warn!("recover: failed to read {}: {}", dir.path.display(), err);
+					continue;
+				}
+			};
+			for entry in entries.flatten() {
+				let path = entry.path();
+				if path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(".log.tmp")) {
+					match std::fs::remove_file(&path) {
+						Ok(()) => log::info!("recover: removed stray tmp segment {}", path.display()),
+						Err(err) => log::warn!(
+							"recover: failed to remove stray tmp segment {}: {}",
+							path.display(),
+							err
+						),
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Object-store-backed segments, for operators who'd rather keep bulk
+/// archive on cheap storage than on local disk. Selected with
+/// `SEGMENT_STORE_BACKEND=s3`; credentials and region come from the
+/// usual AWS env vars via `AmazonS3Builder::from_env`. Held as `Arc<dyn
+/// ObjectStore>` rather than the concrete `AmazonS3` so `put` can hand it to
+/// `object_store::buffered::BufWriter`, the same multipart-capable writer
+/// the CLI's `S3Target::put_stream` uses.
+#[derive(Debug)]
+pub struct S3Store {
+	client: Arc<dyn object_store::ObjectStore>,
+	prefix: String,
+}
+
+impl S3Store {
+	pub fn new(bucket: &str, prefix: String) -> anyhow::Result<Self> {
+		let client = object_store::aws::AmazonS3Builder::from_env()
+			.with_bucket_name(bucket)
+			.build()?;
+		Ok(Self {
+			client: Arc::new(client),
+			prefix,
+		})
+	}
+
+	fn object_path(&self, segment_id: u32) -> object_store::path::Path {
+		object_store::path::Path::from(format!("{}/{}.log", self.prefix, segment_id))
+	}
+}
+
+#[async_trait]
+impl SegmentStore for S3Store {
+	async fn put(&self, segment_id: u32, bytes: Vec<u8>) -> anyhow::Result<Option<String>> {
+		use object_store::buffered::BufWriter;
+		use tokio::io::AsyncWriteExt;
+
+		// `BufWriter` buffers up to a part-size threshold before it starts
+		// issuing `UploadPart` requests, so a merged segment large enough to
+		// cross that threshold is uploaded as a multipart upload instead of
+		// one `PutObject` call, while small segments still go out as a
+		// single request.
+		let mut writer = BufWriter::new(self.client.clone(), self.object_path(segment_id));
+		writer.write_all(&bytes).await?;
+		writer.shutdown().await?;
+		Ok(None)
+	}
+
+	async fn get(&self, segment_id: u32, _dir_hint: Option<&str>) -> anyhow::Result<Bytes> {
+		use object_store::ObjectStore;
+		let result = self.client.get(&self.object_path(segment_id)).await?;
+		Ok(result.bytes().await?)
+	}
+
+	async fn delete(&self, segment_id: u32, _dir_hint: Option<&str>) -> anyhow::Result<()> {
+		use object_store::{Error as ObjectStoreError, ObjectStore};
+		match self.client.delete(&self.object_path(segment_id)).await {
+			Ok(()) => Ok(()),
+			Err(ObjectStoreError::NotFound { .. }) => Ok(()),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	/// Copies the object to a `corrupt/` prefix, then removes the original —
+	/// `object_store` has no atomic rename, so this is a copy-then-delete
+	/// rather than `LocalFsStore::quarantine`'s single `rename`.
+	async fn quarantine(&self, segment_id: u32, _dir_hint: Option<&str>) -> anyhow::Result<()> {
+		use object_store::{Error as ObjectStoreError, ObjectStore};
+		let from = self.object_path(segment_id);
+		let to = object_store::path::Path::from(format!("{}/corrupt/{}.log", self.prefix, segment_id));
+		match self.client.copy(&from, &to).await {
+			Ok(()) => {}
+			Err(ObjectStoreError::NotFound { .. }) => return Ok(()),
+			Err(err) => return Err(err.into()),
+		}
+		match self.client.delete(&from).await {
+			Ok(()) => Ok(()),
+			Err(ObjectStoreError::NotFound { .. }) => Ok(()),
+			Err(err) => Err(err.into()),
+		}
+	}
+}
+
+/// Parses `SEGMENT_DATA_DIRS`, a comma-separated list of
+/// `path=capacity_bytes[:readonly]` entries (e.g.
+/// `/mnt/a=500000000000,/mnt/b=500000000000:readonly`), into a
+/// [`DataLayout`]. Returns `None` if the env var is unset or malformed, so
+/// the caller can fall back to single-directory mode.
+fn parse_data_dirs(spec: &str) -> Option<DataLayout> {
+	let mut dirs = Vec::new();
+	for entry in spec.split(',') {
+		let entry = entry.trim();
+		if entry.is_empty() {
+			continue;
+		}
+		let (path_and_capacity, state) = match entry.rsplit_once(':') {
+			Some((rest, "readonly")) => (rest, crate::data_layout::DataDirState::ReadOnly),
+			_ => (entry, crate::data_layout::DataDirState::Active),
+		};
+		let (path, capacity) = path_and_capacity.split_once('=')?;
+		let capacity_bytes = capacity.trim().parse::<u64>().ok()?;
+		dirs.push(crate::data_layout::DataDir::new(
+			PathBuf::from(path.trim()),
+			capacity_bytes,
+			state,
+		));
+	}
+	if dirs.is_empty() {
+		None
+	} else {
+		Some(DataLayout::new(dirs))
+	}
+}
+
+/// Builds the segment store selected by `SEGMENT_STORE_BACKEND`
+/// (`"local"`, the default, or `"s3"` plus `SEGMENT_STORE_S3_BUCKET` and an
+/// optional `SEGMENT_STORE_S3_PREFIX`). Falls back to `LocalFsStore` if S3 is
+/// requested but misconfigured, so a bad env var can't take log ingestion down.
+/// For the local backend, `SEGMENT_DATA_DIRS` spreads segments across
+/// multiple capacity-aware directories instead of just `logs_path`.
+///
+/// Runs the store's crash-recovery pass before handing it back, so leftover
+/// `.tmp` files from a write that was interrupted by the previous process
+/// exiting are cleaned up before anything else touches the data dirs.
+pub fn build_segment_store(logs_path: &Path) -> Arc<dyn SegmentStore> {
+	let store = build_segment_store_inner(logs_path);
+	store.recover();
+	store
+}
+
+fn build_segment_store_inner(logs_path: &Path) -> Arc<dyn SegmentStore> {
+	let backend = std::env::var("SEGMENT_STORE_BACKEND").unwrap_or_else(|_| "local".to_string());
+	if backend.eq_ignore_ascii_case("s3") {
+		match std::env::var("SEGMENT_STORE_S3_BUCKET") {
+			Ok(bucket) => {
+				let prefix = std::env::var("SEGMENT_STORE_S3_PREFIX").unwrap_or_default();
+				match S3Store::new(&bucket, prefix) {
+					Ok(store) => return Arc::new(store),
+					Err(err) => log::error!("failed to build S3 segment store: {}", err),
+				}
+			}
+			Err(_) => log::error!("SEGMENT_STORE_BACKEND=s3 but SEGMENT_STORE_S3_BUCKET is unset"),
+		}
+	}
+	if let Ok(spec) = std::env::var("SEGMENT_DATA_DIRS") {
+		match parse_data_dirs(&spec) {
+			Some(layout) => return Arc::new(LocalFsStore::with_layout(layout)),
+			None => log::error!("SEGMENT_DATA_DIRS is set but couldn't be parsed, falling back to logs_path"),
+		}
+	}
+	Arc::new(LocalFsStore::new(logs_path.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn put_leaves_no_tmp_file_behind() {
+		let dir = tempfile::tempdir().unwrap();
+		let store = LocalFsStore::new(dir.path().to_owned());
+		let placed = store.put(1, b"hello".to_vec()).await.unwrap();
+
+		assert_eq!(placed, None, "single-directory layout has nothing worth recording");
+		assert_eq!(store.get(1, None).await.unwrap().as_ref(), b"hello");
+		assert!(!LocalFsStore::tmp_path(dir.path(), 1).exists());
+		assert!(LocalFsStore::path(dir.path(), 1).exists());
+	}
+
+	#[tokio::test]
+	async fn recover_removes_stray_tmp_files_but_keeps_real_segments() {
+		let dir = tempfile::tempdir().unwrap();
+		let store = LocalFsStore::new(dir.path().to_owned());
+		store.put(1, b"real segment".to_vec()).await.unwrap();
+		std::fs::write(LocalFsStore::tmp_path(dir.path(), 2), b"torn write").unwrap();
+
+		store.recover();
+
+		assert!(LocalFsStore::path(dir.path(), 1).exists());
+		assert!(!LocalFsStore::tmp_path(dir.path(), 2).exists());
+	}
+
+	#[tokio::test]
+	async fn put_reports_the_chosen_directory_when_multiple_are_configured() {
+		use crate::data_layout::{DataDir, DataDirState};
+		let a = tempfile::tempdir().unwrap();
+		let b = tempfile::tempdir().unwrap();
+		let layout = DataLayout::new(vec![
+			DataDir::new(a.path().to_owned(), 1_000, DataDirState::Active),
+			DataDir::new(b.path().to_owned(), 1_000, DataDirState::Active),
+		]);
+		let store = LocalFsStore::with_layout(layout);
+		let placed = store.put(1, b"hello".to_vec()).await.unwrap();
+
+		let placed_dir = PathBuf::from(placed.expect("multi-directory put should report its directory"));
+		assert!(placed_dir == a.path() || placed_dir == b.path());
+		assert_eq!(
+			store.get(1, Some(placed_dir.to_str().unwrap())).await.unwrap().as_ref(),
+			b"hello"
+		);
+	}
+}
@@ -0,0 +1,339 @@
+use crate::segment::LogSegment;
+use puppylog::LogEntry;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use zstd::Encoder;
+
+/// Byte budget (see `LogSegment::bytes_used`) a buffered segment is allowed
+/// to reach before `SegmentWriter` rolls it to a new compressed file on
+/// disk, mirroring the capacity-based rollover Fuchsia's `log_listener`
+/// applies to its own log files (`DEFAULT_FILE_CAPACITY`) instead of
+/// growing one file without bound.
+const DEFAULT_MAX_BYTES: usize = 4 * 1024 * 1024;
+/// Entry-count budget alongside `DEFAULT_MAX_BYTES` — whichever limit is hit
+/// first triggers a rollover.
+const DEFAULT_MAX_ENTRIES: usize = 50_000;
+/// How many rotated segment files are kept under the writer's directory
+/// before the oldest (by first timestamp) is deleted.
+const DEFAULT_MAX_SEGMENTS: usize = 20;
+/// How long a low-volume writer can go without hitting either threshold
+/// above before it flushes anyway, so a handful of buffered entries aren't
+/// left unqueryable on disk indefinitely.
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+enum Cmd {
+	Write(LogEntry),
+	Flush,
+}
+
+/// Rotating, capacity-bounded file sink for `LogEntry` values, adapted from
+/// the capacity-based file rotation `log_listener` applies to its on-disk
+/// log files. Entries are buffered in memory into a `LogSegment` and
+/// flushed as a new compressed segment file under `dir` once a byte or
+/// entry-count threshold is hit, or once `flush_interval` elapses with
+/// anything buffered — so a low-volume writer still persists in a
+/// reasonable time instead of waiting indefinitely for the next threshold.
+/// At most `max_segments` files are kept on disk; rolling past that deletes
+/// the oldest by first timestamp.
+///
+/// Unlike `Wal`, this isn't meant to back durable, replayable ingest — it's
+/// a standalone rotation sink for a caller that just wants `LogEntry`s
+/// durably archived to `dir` as ordinary segment files `LogSegment::parse`
+/// can read back.
+#[derive(Debug)]
+pub struct SegmentWriter {
+	tx: mpsc::Sender<Cmd>,
+}
+
+impl SegmentWriter {
+	/// Builds a writer rooted at `crate::config::log_path()` using the
+	/// default thresholds.
+	pub fn new() -> Self {
+		Self::with_dir(crate::config::log_path())
+	}
+
+	/// Builds a writer rooted at `dir` using the default thresholds.
+	pub fn with_dir(dir: PathBuf) -> Self {
+		Self::with_limits(
+			dir,
+			DEFAULT_MAX_BYTES,
+			DEFAULT_MAX_ENTRIES,
+			DEFAULT_MAX_SEGMENTS,
+			DEFAULT_FLUSH_INTERVAL,
+		)
+	}
+
+	pub fn with_limits(
+		dir: PathBuf,
+		max_bytes: usize,
+		max_entries: usize,
+		max_segments: usize,
+		flush_interval: Duration,
+	) -> Self {
+		Self::with_limits_templated(dir, max_bytes, max_entries, max_segments, flush_interval, false)
+	}
+
+	/// Same as `with_limits`, but every rolled segment is written with
+	/// `LogSegment::serialize_templated` (Drain-template compression, see
+	/// `segment::ENCODING_TEMPLATED`) instead of the plain `serialize`
+	/// encoding — the bytes `LogSegment::parse` reads back either way.
+	pub fn with_limits_templated(
+		dir: PathBuf,
+		max_bytes: usize,
+		max_entries: usize,
+		max_segments: usize,
+		flush_interval: Duration,
+		templated: bool,
+	) -> Self {
+		let (tx, rx) = mpsc::channel();
+		thread::spawn(move || run(dir, max_bytes, max_entries, max_segments, flush_interval, templated, rx));
+		Self { tx }
+	}
+
+	/// Buffers `entry`, triggering a rollover if doing so crosses either
+	/// threshold. Never blocks the caller: the buffering and file I/O happen
+	/// on the writer's own background thread.
+	pub fn write(&self, entry: LogEntry) {
+		if let Err(err) = self.tx.send(Cmd::Write(entry)) {
+			log::error!("failed to write to segment writer: {}", err);
+		}
+	}
+
+	/// Forces whatever is currently buffered out to a new segment file,
+	/// regardless of whether either threshold has been hit.
+	pub fn flush(&self) {
+		if let Err(err) = self.tx.send(Cmd::Flush) {
+			log::error!("failed to flush segment writer: {}", err);
+		}
+	}
+}
+
+fn segment_path(dir: &Path, first: i64, last: i64) -> PathBuf {
+	dir.join(format!("segment-{}-{}.log", first, last))
+}
+
+/// Rotated segment files under `dir`, as `(first_timestamp_micros, path)`,
+/// oldest first.
+fn list_segments(dir: &Path) -> Vec<(i64, PathBuf)> {
+	let mut segments = Vec::new();
+	if let Ok(entries) = fs::read_dir(dir) {
+		for entry in entries.flatten() {
+			let name = entry.file_name();
+			let name = name.to_string_lossy();
+			if let Some(rest) = name.strip_prefix("segment-").and_then(|s| s.strip_suffix(".log")) {
+				if let Some((first, _)) = rest.split_once('-') {
+					if let Ok(first) = first.parse::<i64>() {
+						segments.push((first, entry.path()));
+					}
+				}
+			}
+		}
+	}
+	segments.sort_by_key(|(first, _)| *first);
+	segments
+}
+
+/// Deletes the oldest rotated segment files under `dir` until at most
+/// `max_segments` remain.
+fn enforce_retention(dir: &Path, max_segments: usize) {
+	let segments = list_segments(dir);
+	if segments.len() <= max_segments {
+		return;
+	}
+	for (_, path) in &segments[..segments.len() - max_segments] {
+		if let Err(err) = fs::remove_file(path) {
+			log::error!("failed to delete rotated segment {:?}: {}", path, err);
+		}
+	}
+}
+
+/// Always zstd-compresses, unlike `segment::compress_segment` (which skips
+/// compression when it wouldn't shrink the data and records that on the
+/// segment row). This writer's rotated files have no such flag, so every one
+/// it produces must stay unconditionally zstd-decodable.
+fn compress_always(buf: &[u8]) -> anyhow::Result<Vec<u8>> {
+	let mut encoder = Encoder::new(Vec::new(), 14)?;
+	encoder.multithread(num_cpus::get() as u32)?;
+	encoder.write_all(buf)?;
+	Ok(encoder.finish()?)
+}
+
+/// Compresses and writes `buffer` to a new file under `dir`, named from its
+/// first/last timestamps, then resets `buffer` and prunes old segments. A
+/// no-op if `buffer` is empty. Leaves `buffer` untouched on a write failure
+/// so the caller retries on the next flush instead of losing the batch.
+fn flush(dir: &Path, buffer: &mut LogSegment, max_segments: usize, templated: bool) {
+	if buffer.buffer.is_empty() {
+		return;
+	}
+	buffer.sort();
+	let first = buffer.buffer.first().unwrap().timestamp.timestamp_micros();
+	let last = buffer.buffer.last().unwrap().timestamp.timestamp_micros();
+	let mut raw = Vec::new();
+	if templated {
+		buffer.serialize_templated(&mut raw);
+	} else {
+		buffer.serialize(&mut raw);
+	}
+	let compressed = match compress_always(&raw) {
+		Ok(compressed) => compressed,
+		Err(err) => {
+			log::error!("failed to compress segment: {}", err);
+			return;
+		}
+	};
+	let path = segment_path(dir, first, last);
+	if let Err(err) = fs::write(&path, &compressed) {
+		log::error!("failed to write segment {:?}: {}", path, err);
+		return;
+	}
+	*buffer = LogSegment::new();
+	enforce_retention(dir, max_segments);
+}
+
+fn run(
+	dir: PathBuf,
+	max_bytes: usize,
+	max_entries: usize,
+	max_segments: usize,
+	flush_interval: Duration,
+	templated: bool,
+	rx: mpsc::Receiver<Cmd>,
+) {
+	if let Err(err) = fs::create_dir_all(&dir) {
+		log::error!("failed to create segment writer dir {:?}: {}", dir, err);
+		return;
+	}
+	let mut buffer = LogSegment::new();
+	let mut last_flush = Instant::now();
+	loop {
+		match rx.recv_timeout(flush_interval) {
+			Ok(Cmd::Write(entry)) => {
+				buffer.add_log_entry(entry);
+				if buffer.bytes_used() >= max_bytes || buffer.buffer.len() >= max_entries {
+					flush(&dir, &mut buffer, max_segments, templated);
+					last_flush = Instant::now();
+				}
+			}
+			Ok(Cmd::Flush) => {
+				flush(&dir, &mut buffer, max_segments, templated);
+				last_flush = Instant::now();
+			}
+			Err(mpsc::RecvTimeoutError::Timeout) => {
+				if !buffer.buffer.is_empty() && last_flush.elapsed() >= flush_interval {
+					flush(&dir, &mut buffer, max_segments, templated);
+					last_flush = Instant::now();
+				}
+			}
+			Err(mpsc::RecvTimeoutError::Disconnected) => {
+				flush(&dir, &mut buffer, max_segments, templated);
+				break;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::DateTime;
+	use puppylog::LogLevel;
+
+	fn entry(seconds: i64) -> LogEntry {
+		LogEntry {
+			timestamp: DateTime::from_timestamp_micros(1_740_074_054 * 1_000_000 + seconds * 1_000_000)
+				.unwrap(),
+			level: LogLevel::Info,
+			msg: format!("msg-{seconds}"),
+			props: vec![],
+			..Default::default()
+		}
+	}
+
+	fn wait_until(mut cond: impl FnMut() -> bool) {
+		for _ in 0..200 {
+			if cond() {
+				return;
+			}
+			std::thread::sleep(Duration::from_millis(10));
+		}
+		panic!("condition never became true");
+	}
+
+	#[test]
+	fn flushes_once_entry_threshold_hit() {
+		let dir = tempfile::tempdir().unwrap();
+		let writer = SegmentWriter::with_limits(
+			dir.path().to_owned(),
+			usize::MAX,
+			3,
+			DEFAULT_MAX_SEGMENTS,
+			Duration::from_secs(3600),
+		);
+		for i in 0..3 {
+			writer.write(entry(i));
+		}
+		wait_until(|| list_segments(dir.path()).len() == 1);
+	}
+
+	#[test]
+	fn flush_is_forced_without_hitting_thresholds() {
+		let dir = tempfile::tempdir().unwrap();
+		let writer = SegmentWriter::with_limits(
+			dir.path().to_owned(),
+			usize::MAX,
+			usize::MAX,
+			DEFAULT_MAX_SEGMENTS,
+			Duration::from_secs(3600),
+		);
+		writer.write(entry(0));
+		writer.flush();
+		wait_until(|| list_segments(dir.path()).len() == 1);
+	}
+
+	#[test]
+	fn templated_writer_round_trips_messages() {
+		use crate::segment::LogSegment;
+		use std::io::Cursor;
+
+		let dir = tempfile::tempdir().unwrap();
+		let writer = SegmentWriter::with_limits_templated(
+			dir.path().to_owned(),
+			usize::MAX,
+			3,
+			DEFAULT_MAX_SEGMENTS,
+			Duration::from_secs(3600),
+			true,
+		);
+		for i in 0..3 {
+			writer.write(entry(i));
+		}
+		wait_until(|| list_segments(dir.path()).len() == 1);
+
+		let (_, path) = &list_segments(dir.path())[0];
+		let compressed = fs::read(path).unwrap();
+		let decompressed = zstd::decode_all(Cursor::new(compressed)).unwrap();
+		let parsed = LogSegment::parse(&mut Cursor::new(decompressed)).unwrap();
+		let msgs: Vec<_> = parsed.buffer.iter().map(|l| l.msg.clone()).collect();
+		assert_eq!(msgs, vec!["msg-0", "msg-1", "msg-2"]);
+	}
+
+	#[test]
+	fn retention_deletes_oldest_segments_first() {
+		let dir = tempfile::tempdir().unwrap();
+		let writer =
+			SegmentWriter::with_limits(dir.path().to_owned(), usize::MAX, 1, 2, Duration::from_secs(3600));
+		for i in 0..4 {
+			writer.write(entry(i));
+		}
+		wait_until(|| list_segments(dir.path()).len() == 2);
+		let remaining = list_segments(dir.path());
+		assert_eq!(remaining.len(), 2);
+		// The two newest entries (seconds 2 and 3) should be the ones kept.
+		assert!(remaining[0].0 < remaining[1].0);
+	}
+}
@@ -1,24 +1,180 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::Value;
 use std::fs::read_to_string;
 use tokio::sync::Mutex;
 
 use crate::config::settings_path;
 
+/// Which segments `plan_evictions` reaches for first once a policy's
+/// thresholds are crossed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EvictionOrder {
+	/// Evict the oldest segments by `first_timestamp` first, regardless of
+	/// whether they're still being queried.
+	#[default]
+	OldestFirst,
+	/// Evict the least-recently-queried segments first (see
+	/// [`crate::access_tracker`]), so cold-but-old data that's still being
+	/// read survives longer than hot-but-old data nobody looks at. A segment
+	/// that's never been queried (`last_accessed` is `None`) sorts as the
+	/// oldest possible access, so it's evicted before anything that has been.
+	LeastRecentlyUsed,
+}
+
+/// Lifecycle rule for reclaiming disk space, modeled on object-store
+/// retention policies: segments are evicted once they cross `max_age_secs`
+/// or the collection's total compressed size crosses `max_total_bytes`, in
+/// the order `eviction_order` selects. Disabled (and a no-op) unless
+/// `enabled` is set. `per_device_min_age_secs` guarantees a device's
+/// segments survive at least that long regardless of the global quota, so a
+/// noisy neighbor can't starve a low-volume device's retention.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionPolicy {
+	pub enabled: bool,
+	#[serde(default)]
+	pub eviction_order: EvictionOrder,
+	pub max_age_secs: Option<u64>,
+	/// High watermark: eviction kicks in once total segment bytes cross this.
+	pub max_total_bytes: Option<u64>,
+	/// Low watermark: once eviction kicks in, it keeps evicting oldest-first
+	/// until total bytes fall to this mark instead of stopping the instant
+	/// `max_total_bytes` is no longer exceeded, so a steady ingest rate near
+	/// the cap doesn't re-trigger eviction every pass. Defaults to
+	/// `max_total_bytes` (old single-threshold behavior) when unset.
+	#[serde(default)]
+	pub low_watermark_bytes: Option<u64>,
+	#[serde(default)]
+	pub per_device_min_age_secs: HashMap<String, u64>,
+	/// Per-device age cap, enforced independently of (in addition to) the
+	/// global `max_age_secs`: a device listed here is evicted down to its own
+	/// cutoff even if the fleet as a whole is nowhere near its quota.
+	#[serde(default)]
+	pub per_device_max_age_secs: HashMap<String, u64>,
+	/// Per-device byte budget, enforced independently of (in addition to) the
+	/// global `max_total_bytes`, so one device can be given a tighter (or
+	/// looser) storage bound than the rest of the fleet.
+	#[serde(default)]
+	pub per_device_max_total_bytes: HashMap<String, u64>,
+}
+
+/// Per-deployment CORS rule set, applied as a request middleware so it can
+/// be changed at runtime instead of being baked into a fixed `CorsLayer`.
+/// `allowed_origins` supports exact origins and the `"*"` wildcard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorsPolicy {
+	pub allowed_origins: Vec<String>,
+	pub allowed_methods: Vec<String>,
+	pub allowed_headers: Vec<String>,
+	pub max_age_secs: u64,
+	pub allow_credentials: bool,
+}
+
+impl Default for CorsPolicy {
+	fn default() -> Self {
+		Self {
+			allowed_origins: vec!["*".to_string()],
+			allowed_methods: ["GET", "POST", "PUT", "DELETE", "OPTIONS"]
+				.iter()
+				.map(|s| s.to_string())
+				.collect(),
+			allowed_headers: vec!["*".to_string()],
+			max_age_secs: 86_400,
+			allow_credentials: false,
+		}
+	}
+}
+
+/// Gates the device-facing endpoints behind HMAC-SHA256 upload tokens.
+/// `secret` signs and verifies tokens minted by
+/// `crate::device_token::mint`; it's generated once on first load and kept
+/// stable across restarts so previously-issued tokens keep working.
+/// Disabled by default so existing, token-less devices keep working until
+/// an operator opts in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceAuthPolicy {
+	pub enabled: bool,
+	#[serde(default)]
+	pub secret: String,
+}
+
+/// A single saved collection filter, addressed by a user-chosen name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedQuery {
+	pub name: String,
+	pub query: String,
+}
+
+/// On-disk schema version. Bump this and add a branch in `migrate` whenever
+/// `SettingsInner`'s shape changes in a way old files can't just `#[serde(default)]` through.
+const SETTINGS_VERSION: u32 = 2;
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct SettingsInner {
-	pub collection_query: String,
+	#[serde(default)]
+	pub version: u32,
+	#[serde(default)]
+	pub queries: Vec<SavedQuery>,
+	#[serde(default)]
+	pub active_query: Option<String>,
+	#[serde(default)]
+	pub retention_policy: RetentionPolicy,
+	#[serde(default)]
+	pub cors_policy: CorsPolicy,
+	#[serde(default)]
+	pub device_auth: DeviceAuthPolicy,
 }
 
 impl SettingsInner {
+	/// Writes to a temp file in the same directory and `rename`s it over
+	/// `settings_path()`, so a crash mid-write can never leave behind a
+	/// half-written settings file.
 	pub fn save(&self) -> anyhow::Result<()> {
 		let text = serde_json::to_string(self)?;
-		std::fs::write(settings_path(), text)?;
+		let path = settings_path();
+		let tmp_path = path.with_extension("json.tmp");
+		std::fs::write(&tmp_path, text)?;
+		std::fs::rename(&tmp_path, &path)?;
 		Ok(())
 	}
+
+	/// Upgrades a parsed-but-stale settings document to `SETTINGS_VERSION`.
+	/// Version 1 (unversioned) held a single `collection_query: String`;
+	/// it becomes a saved query named `"default"`, activated iff non-empty.
+	fn migrate(mut value: Value, version: u32) -> anyhow::Result<SettingsInner> {
+		let legacy_query = value
+			.get("collection_query")
+			.and_then(|v| v.as_str())
+			.unwrap_or("")
+			.to_string();
+		if let Value::Object(map) = &mut value {
+			map.remove("collection_query");
+		}
+		let mut inner: SettingsInner = serde_json::from_value(value)?;
+		if version < 2 && !legacy_query.is_empty() && inner.queries.is_empty() {
+			inner.queries.push(SavedQuery {
+				name: "default".to_string(),
+				query: legacy_query,
+			});
+			inner.active_query = Some("default".to_string());
+		}
+		inner.version = SETTINGS_VERSION;
+		Ok(inner)
+	}
+}
+
+fn generate_secret() -> String {
+	let mut bytes = [0u8; 32];
+	rand::Rng::fill(&mut rand::rng(), &mut bytes);
+	bytes.iter().map(|b| format!("{b:02x}")).collect()
 }
 
 #[derive(Debug)]
@@ -28,25 +184,122 @@ pub struct Settings {
 
 impl Settings {
 	pub fn load() -> anyhow::Result<Self> {
-		let inner = match read_to_string(settings_path()) {
-			Ok(text) => serde_json::from_str(&text)?,
+		let mut inner = match read_to_string(settings_path()) {
+			Ok(text) => {
+				let value: Value = serde_json::from_str(&text)?;
+				let version = value.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+				if version < SETTINGS_VERSION {
+					let inner = SettingsInner::migrate(value, version)?;
+					inner.save()?;
+					inner
+				} else {
+					serde_json::from_value(value)?
+				}
+			}
 			Err(_) => SettingsInner {
-				collection_query: "qwert".to_string(),
+				version: SETTINGS_VERSION,
 				..Default::default()
 			},
 		};
+		if inner.device_auth.secret.is_empty() {
+			inner.device_auth.secret = generate_secret();
+			inner.save()?;
+		}
 		Ok(Self {
 			inner: Arc::new(Mutex::new(inner)),
 		})
 	}
 
 	pub fn new() -> Self {
+		let mut inner = SettingsInner {
+			version: SETTINGS_VERSION,
+			..Default::default()
+		};
+		inner.device_auth.secret = generate_secret();
 		Self {
-			inner: Arc::new(Mutex::new(SettingsInner::default())),
+			inner: Arc::new(Mutex::new(inner)),
 		}
 	}
 
 	pub async fn inner(&self) -> tokio::sync::MutexGuard<SettingsInner> {
 		self.inner.lock().await
 	}
+
+	pub async fn list_queries(&self) -> Vec<SavedQuery> {
+		self.inner.lock().await.queries.clone()
+	}
+
+	pub async fn get_query(&self, name: &str) -> Option<String> {
+		self.inner
+			.lock()
+			.await
+			.queries
+			.iter()
+			.find(|q| q.name == name)
+			.map(|q| q.query.clone())
+	}
+
+	pub async fn add_query(&self, name: &str, query: &str) -> anyhow::Result<()> {
+		let mut inner = self.inner.lock().await;
+		match inner.queries.iter_mut().find(|q| q.name == name) {
+			Some(existing) => existing.query = query.to_string(),
+			None => inner.queries.push(SavedQuery {
+				name: name.to_string(),
+				query: query.to_string(),
+			}),
+		}
+		inner.save()
+	}
+
+	pub async fn rename_query(&self, name: &str, new_name: &str) -> anyhow::Result<bool> {
+		let mut inner = self.inner.lock().await;
+		if !inner.queries.iter().any(|q| q.name == name) {
+			return Ok(false);
+		}
+		for query in inner.queries.iter_mut() {
+			if query.name == name {
+				query.name = new_name.to_string();
+			}
+		}
+		if inner.active_query.as_deref() == Some(name) {
+			inner.active_query = Some(new_name.to_string());
+		}
+		inner.save()?;
+		Ok(true)
+	}
+
+	pub async fn delete_query(&self, name: &str) -> anyhow::Result<bool> {
+		let mut inner = self.inner.lock().await;
+		let before = inner.queries.len();
+		inner.queries.retain(|q| q.name != name);
+		if inner.queries.len() == before {
+			return Ok(false);
+		}
+		if inner.active_query.as_deref() == Some(name) {
+			inner.active_query = None;
+		}
+		inner.save()?;
+		Ok(true)
+	}
+
+	pub async fn activate_query(&self, name: &str) -> anyhow::Result<bool> {
+		let mut inner = self.inner.lock().await;
+		if !inner.queries.iter().any(|q| q.name == name) {
+			return Ok(false);
+		}
+		inner.active_query = Some(name.to_string());
+		inner.save()?;
+		Ok(true)
+	}
+
+	/// The text of the currently active query, or empty if none is active.
+	pub async fn active_query_text(&self) -> String {
+		let inner = self.inner.lock().await;
+		inner
+			.active_query
+			.as_deref()
+			.and_then(|name| inner.queries.iter().find(|q| q.name == name))
+			.map(|q| q.query.clone())
+			.unwrap_or_default()
+	}
 }
\ No newline at end of file
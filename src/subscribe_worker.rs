@@ -1,97 +1,330 @@
+use crate::metrics::{render_counter, render_gauge};
+use puppylog::check_expr;
+use puppylog::extract_equality_props;
+use puppylog::simplify;
 use puppylog::LogEntry;
 use puppylog::QueryAst;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::error::TrySendError;
 
-pub struct SubscribeReq {
-	pub res_tx: mpsc::Sender<LogEntry>,
-	pub query: QueryAst,
+/// Live-tail pub/sub counters, shared (via the same `Arc`) across every
+/// `Worker` shard a `WorkerManager` spawns, so the atomics already aggregate
+/// across shards without `WorkerManager` needing to sum anything itself.
+#[derive(Debug, Default)]
+pub struct WorkerMetrics {
+	active_subscribers: AtomicI64,
+	entries_published: AtomicU64,
+	entries_matched: AtomicU64,
+	/// Failed deliveries to a subscriber whose query matched: a full
+	/// channel (slow subscriber, entry dropped) or a closed one (dropped
+	/// subscriber, since cleaned up).
+	send_failures: AtomicU64,
 }
 
-#[derive(Debug)]
-pub struct Subscriber {
-	tx: mpsc::Sender<SubscribeReq>,
+impl WorkerMetrics {
+	/// Renders in the same Prometheus text-exposition format `Metrics::render`
+	/// uses, so `get_metrics` can append it straight onto the rest of the scrape.
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+		render_gauge(
+			"puppylog_live_tail_active_subscribers",
+			self.active_subscribers.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_live_tail_entries_published_total",
+			self.entries_published.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_live_tail_entries_matched_total",
+			self.entries_matched.load(Ordering::Relaxed),
+			&mut out,
+		);
+		render_counter(
+			"puppylog_live_tail_send_failures_total",
+			self.send_failures.load(Ordering::Relaxed),
+			&mut out,
+		);
+		out
+	}
 }
 
-impl Subscriber {
-	pub fn new(tx: mpsc::Sender<SubscribeReq>) -> Self {
-		Self { tx }
-	}
-	pub async fn subscribe(&self, query: QueryAst) -> mpsc::Receiver<LogEntry> {
-		let (res_tx, res_rx) = mpsc::channel(100);
-		let _ = self.tx.send(SubscribeReq { res_tx, query }).await;
-		res_rx
-	}
+pub struct SubscribeReq {
+	pub res_tx: mpsc::Sender<LogEntry>,
+	pub query: QueryAst,
 }
 
+/// One live-tail subscription as `Worker` tracks it: the channel to deliver
+/// matches on, the query to test entries against, and the `"key=value"`
+/// equality props (same format `SegmentBloom`/bloom pruning use) pulled out
+/// of its top-level `and`-chain by `extract_equality_props`. `index_keys` is
+/// empty for queries that don't pin any field to a literal (e.g. pure
+/// `msg ~ "..."` searches); those can only ever land in `unconstrained`.
 struct SubscriberInfo {
 	res_tx: mpsc::Sender<LogEntry>,
 	query: QueryAst,
+	index_keys: Vec<String>,
 }
 
+/// Holds one shard's live-tail subscribers and routes published entries to
+/// them without a full linear scan of every subscriber's `QueryAst` per
+/// entry. Subscriptions whose query requires `field = "value"` (the common
+/// "tail one device" case) are indexed by that `"key=value"` string, so an
+/// entry only ever candidates the subscribers whose constraint it actually
+/// satisfies; `check_expr` still runs on every candidate since the index
+/// only captures one necessary condition, not the whole query.
 pub struct Worker {
 	subrx: mpsc::Receiver<SubscribeReq>,
 	pubrx: mpsc::Receiver<LogEntry>,
-	subs: Vec<SubscriberInfo>,
+	subs: HashMap<u64, SubscriberInfo>,
+	index: HashMap<String, HashSet<u64>>,
+	/// Subscribers whose query can't be indexed at all (no top-level
+	/// equality), always re-checked against `check_expr` for every entry.
+	unconstrained: HashSet<u64>,
+	next_id: u64,
+	metrics: Arc<WorkerMetrics>,
 }
 
 impl Worker {
-	pub fn new(subrx: mpsc::Receiver<SubscribeReq>, pubrx: mpsc::Receiver<LogEntry>) -> Self {
+	fn new(
+		subrx: mpsc::Receiver<SubscribeReq>,
+		pubrx: mpsc::Receiver<LogEntry>,
+		metrics: Arc<WorkerMetrics>,
+	) -> Self {
 		Worker {
 			subrx,
 			pubrx,
-			subs: Vec::new(),
+			subs: HashMap::new(),
+			index: HashMap::new(),
+			unconstrained: HashSet::new(),
+			next_id: 0,
+			metrics,
+		}
+	}
+
+	fn add_sub(&mut self, req: SubscribeReq) {
+		self.metrics
+			.active_subscribers
+			.fetch_add(1, Ordering::Relaxed);
+		let index_keys: Vec<String> = extract_equality_props(&simplify(&req.query.root))
+			.iter()
+			.map(|p| format!("{}={}", p.key, p.value))
+			.collect();
+		let id = self.next_id;
+		self.next_id += 1;
+		if index_keys.is_empty() {
+			self.unconstrained.insert(id);
+		} else {
+			for key in &index_keys {
+				self.index.entry(key.clone()).or_default().insert(id);
+			}
+		}
+		self.subs.insert(
+			id,
+			SubscriberInfo {
+				res_tx: req.res_tx,
+				query: req.query,
+				index_keys,
+			},
+		);
+	}
+
+	fn remove_sub(&mut self, id: u64) {
+		let Some(info) = self.subs.remove(&id) else {
+			return;
+		};
+		self.metrics
+			.active_subscribers
+			.fetch_sub(1, Ordering::Relaxed);
+		if info.index_keys.is_empty() {
+			self.unconstrained.remove(&id);
+			return;
+		}
+		for key in &info.index_keys {
+			if let Some(ids) = self.index.get_mut(key) {
+				ids.remove(&id);
+				if ids.is_empty() {
+					self.index.remove(key);
+				}
+			}
 		}
 	}
+
 	async fn handle_entry(&mut self, entry: LogEntry) {
-		let mut i = self.subs.len();
-		while i > 0 {
-			i -= 1;
-			if let Ok(m) = self.subs[i].query.matches(&entry) {
-				if m {
-					if self.subs[i].res_tx.is_closed() {
-						self.subs.remove(i);
-						continue;
-					}
-					match self.subs[i].res_tx.try_send(entry.clone()) {
-						Ok(_) => {}
-						Err(TrySendError::Full(_)) => {}
-						Err(TrySendError::Closed(_)) => {
-							self.subs.remove(i);
-						}
-					}
+		self.metrics
+			.entries_published
+			.fetch_add(1, Ordering::Relaxed);
+		let mut entry_keys: HashSet<String> = entry
+			.props
+			.iter()
+			.map(|p| format!("{}={}", p.key, p.value))
+			.collect();
+		entry_keys.insert(format!("level={}", entry.level));
+
+		let mut candidates: HashSet<u64> = self.unconstrained.clone();
+		for key in &entry_keys {
+			if let Some(ids) = self.index.get(key) {
+				candidates.extend(ids.iter().copied());
+			}
+		}
+
+		let mut closed = Vec::new();
+		for id in candidates {
+			let Some(info) = self.subs.get(&id) else {
+				continue;
+			};
+			// The index only proves one of the subscriber's equality
+			// conditions matched; confirm the rest (and anything it
+			// couldn't express, like `or` branches or ranges) before
+			// delivering.
+			if !info.index_keys.iter().all(|k| entry_keys.contains(k)) {
+				continue;
+			}
+			let tz = info
+				.query
+				.tz_offset
+				.unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
+			if !matches!(check_expr(&info.query.root, &entry, &tz), Ok(true)) {
+				continue;
+			}
+			self.metrics.entries_matched.fetch_add(1, Ordering::Relaxed);
+			if info.res_tx.is_closed() {
+				closed.push(id);
+				self.metrics.send_failures.fetch_add(1, Ordering::Relaxed);
+				continue;
+			}
+			// A full channel just means a slow subscriber; leave it
+			// registered rather than treating it as stale. Only a
+			// disconnected receiver (dropped by the caller) removes it.
+			match info.res_tx.try_send(entry.clone()) {
+				Ok(_) => {}
+				Err(TrySendError::Full(_)) => {
+					self.metrics.send_failures.fetch_add(1, Ordering::Relaxed);
+				}
+				Err(TrySendError::Closed(_)) => {
+					closed.push(id);
+					self.metrics.send_failures.fetch_add(1, Ordering::Relaxed);
 				}
 			}
 		}
+		for id in closed {
+			self.remove_sub(id);
+		}
 	}
+
 	pub async fn run(mut self) {
 		loop {
 			tokio::select! {
 				req = self.subrx.recv() => {
-					if let Some(req) = req {
-						self.subs.push(SubscriberInfo { res_tx: req.res_tx, query: req.query });
-					} else { break; }
+					match req {
+						Some(req) => self.add_sub(req),
+						None => break,
+					}
 				}
 				entry = self.pubrx.recv() => {
-					if let Some(entry) = entry { self.handle_entry(entry).await; } else { break; }
+					match entry {
+						Some(entry) => self.handle_entry(entry).await,
+						None => break,
+					}
 				}
 			}
 		}
 	}
 }
 
+/// Default number of `Worker` shards `WorkerManager` spawns, overridable
+/// with `LIVE_TAIL_WORKER_SHARDS`. Each shard is an independent `Worker`
+/// task with its own subscriber set and index, so subscribe/publish
+/// throughput scales with shard count instead of funneling every live-tail
+/// subscriber through one task.
+const DEFAULT_SHARD_COUNT: usize = 4;
+
+/// Owns a pool of `Worker` shards and routes subscriptions and published
+/// entries to them: new subscriptions land on a shard round-robin, and a
+/// published entry fans out to every shard since any of them might hold a
+/// matching subscriber.
+#[derive(Debug)]
+pub struct WorkerManager {
+	subtxs: Vec<mpsc::Sender<SubscribeReq>>,
+	pubtxs: Vec<mpsc::Sender<LogEntry>>,
+	next_shard: AtomicUsize,
+	metrics: Arc<WorkerMetrics>,
+}
+
+impl WorkerManager {
+	pub fn new() -> Self {
+		let shard_count = std::env::var("LIVE_TAIL_WORKER_SHARDS")
+			.ok()
+			.and_then(|v| v.parse().ok())
+			.unwrap_or(DEFAULT_SHARD_COUNT)
+			.max(1);
+		let metrics = Arc::new(WorkerMetrics::default());
+		let mut subtxs = Vec::with_capacity(shard_count);
+		let mut pubtxs = Vec::with_capacity(shard_count);
+		for _ in 0..shard_count {
+			let (subtx, subrx) = mpsc::channel(100);
+			let (pubtx, pubrx) = mpsc::channel(100);
+			tokio::spawn(Worker::new(subrx, pubrx, metrics.clone()).run());
+			subtxs.push(subtx);
+			pubtxs.push(pubtx);
+		}
+		Self {
+			subtxs,
+			pubtxs,
+			next_shard: AtomicUsize::new(0),
+			metrics,
+		}
+	}
+
+	/// Registers a live-tail subscription on one shard, chosen round-robin
+	/// so no single shard's subscriber set (and index) grows unbounded as
+	/// concurrent tailers scale into the thousands.
+	pub async fn subscribe(&self, query: QueryAst) -> mpsc::Receiver<LogEntry> {
+		let (res_tx, res_rx) = mpsc::channel(100);
+		let shard = self.next_shard.fetch_add(1, Ordering::Relaxed) % self.subtxs.len();
+		let _ = self.subtxs[shard].send(SubscribeReq { res_tx, query }).await;
+		res_rx
+	}
+
+	/// Fans a freshly ingested entry out to every shard; each shard narrows
+	/// to its own candidates via the prop-keyed index before running
+	/// `check_expr`.
+	pub async fn publish(&self, entry: LogEntry) {
+		for pubtx in &self.pubtxs {
+			let _ = pubtx.send(entry.clone()).await;
+		}
+	}
+
+	/// Renders the pub/sub counters shared across every shard, in the same
+	/// Prometheus text format `Metrics::render` uses.
+	pub fn render_metrics(&self) -> String {
+		self.metrics.render()
+	}
+
+	/// Drops every shard's channel handles, which ends each `Worker::run`
+	/// loop (its `recv()` calls return `None`) instead of leaving the
+	/// shard tasks running past shutdown.
+	pub fn shutdown(&mut self) {
+		self.subtxs.clear();
+		self.pubtxs.clear();
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 	use puppylog::{Condition, Expr, Value};
-	use tokio::sync::mpsc;
 	use tokio::time::{sleep, timeout, Duration};
 
 	#[tokio::test]
 	async fn test_matching_subscription() {
 		let (subtx, subrx) = mpsc::channel(10);
 		let (pubtx, pubrx) = mpsc::channel(10);
-		let worker = Worker::new(subrx, pubrx);
+		let worker = Worker::new(subrx, pubrx, Arc::new(WorkerMetrics::default()));
 		let worker_handle = tokio::spawn(worker.run());
 		{
 			let (res_tx, mut res_rx) = mpsc::channel(10);
@@ -128,7 +361,7 @@ mod tests {
 	async fn test_non_matching_subscription() {
 		let (subtx, subrx) = mpsc::channel(10);
 		let (pubtx, pubrx) = mpsc::channel(10);
-		let worker = Worker::new(subrx, pubrx);
+		let worker = Worker::new(subrx, pubrx, Arc::new(WorkerMetrics::default()));
 		let worker_handle = tokio::spawn(worker.run());
 		{
 			let (res_tx, mut res_rx) = mpsc::channel(10);
@@ -155,4 +388,52 @@ mod tests {
 		}
 		worker_handle.await.unwrap();
 	}
+
+	#[tokio::test]
+	async fn test_index_skips_unrelated_device() {
+		let (subtx, subrx) = mpsc::channel(10);
+		let (pubtx, pubrx) = mpsc::channel(10);
+		let worker = Worker::new(subrx, pubrx, Arc::new(WorkerMetrics::default()));
+		let worker_handle = tokio::spawn(worker.run());
+		{
+			let (res_tx, mut res_rx) = mpsc::channel(10);
+			let query = QueryAst {
+				root: Expr::Condition(Condition {
+					left: Box::new(Expr::Value(Value::String("deviceId".to_string()))),
+					operator: puppylog::Operator::Equal,
+					right: Box::new(Expr::Value(Value::String("dev-a".to_string()))),
+				}),
+				..Default::default()
+			};
+			subtx.send(SubscribeReq { res_tx, query }).await.unwrap();
+			sleep(Duration::from_millis(100)).await;
+
+			let other_device = LogEntry {
+				msg: "from dev-b".to_string(),
+				props: vec![puppylog::Prop {
+					key: "deviceId".into(),
+					value: "dev-b".into(),
+				}],
+				..Default::default()
+			};
+			pubtx.send(other_device).await.unwrap();
+			let result = timeout(Duration::from_millis(100), res_rx.recv()).await;
+			assert!(result.is_err());
+
+			let matching_device = LogEntry {
+				msg: "from dev-a".to_string(),
+				props: vec![puppylog::Prop {
+					key: "deviceId".into(),
+					value: "dev-a".into(),
+				}],
+				..Default::default()
+			};
+			pubtx.send(matching_device.clone()).await.unwrap();
+			let received = res_rx.recv().await;
+			assert_eq!(received, Some(matching_device));
+			drop(subtx);
+			drop(pubtx);
+		}
+		worker_handle.await.unwrap();
+	}
 }
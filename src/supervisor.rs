@@ -0,0 +1,176 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Outcome of one `Worker::work` tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerState {
+	/// There's more queued work; call `work` again immediately.
+	Busy,
+	/// Nothing to do right now; sleep for the given duration before retrying.
+	Idle(Duration),
+	/// The worker is finished and should never be polled again.
+	Done,
+}
+
+/// Control messages a `WorkerManager` sends to a running worker's supervising task.
+#[derive(Debug, Clone, Copy)]
+enum Control {
+	Pause,
+	Resume,
+	Stop,
+}
+
+/// Point-in-time view of a registered worker, as returned by `WorkerManager::list`.
+#[derive(Debug, Clone)]
+pub struct WorkerInfo {
+	pub name: String,
+	pub running: bool,
+	pub paused: bool,
+	pub status: String,
+	pub last_error: Option<String>,
+}
+
+/// A long-running background task that can report its own health. Replaces
+/// bare `thread::spawn`/`tokio::spawn` calls with something a `WorkerManager`
+/// can pause, resume, stop, and introspect.
+#[async_trait::async_trait]
+pub trait Worker: Send {
+	/// Stable identifier used in `WorkerInfo` and log lines.
+	fn name(&self) -> &str;
+	/// Perform one unit of work and report whether there's more to do.
+	async fn work(&mut self) -> WorkerState;
+	/// Short human-readable summary of current progress/health.
+	fn status(&self) -> String {
+		String::new()
+	}
+}
+
+struct Shared {
+	running: bool,
+	paused: bool,
+	status: String,
+	last_error: Option<String>,
+}
+
+struct Registered {
+	name: String,
+	control_tx: mpsc::Sender<Control>,
+	shared: Arc<Mutex<Shared>>,
+	#[allow(dead_code)]
+	handle: JoinHandle<()>,
+}
+
+/// Owns every long-running background task in the process behind a uniform
+/// control channel supporting start/pause/resume/stop, so an admin endpoint
+/// can enumerate workers with their current state and last error instead of
+/// guessing whether an ad-hoc spawned task is alive, idle, or stuck.
+#[derive(Default)]
+pub struct WorkerManager {
+	workers: Mutex<Vec<Registered>>,
+}
+
+impl WorkerManager {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Spawn `worker` and start supervising it.
+	pub fn register<W: Worker + 'static>(&self, mut worker: W) {
+		let name = worker.name().to_string();
+		let (control_tx, mut control_rx) = mpsc::channel(8);
+		let shared = Arc::new(Mutex::new(Shared {
+			running: true,
+			paused: false,
+			status: String::new(),
+			last_error: None,
+		}));
+		let shared_task = shared.clone();
+		let task_name = name.clone();
+		let handle = tokio::spawn(async move {
+			let mut paused = false;
+			loop {
+				match control_rx.try_recv() {
+					Ok(Control::Pause) => paused = true,
+					Ok(Control::Resume) => paused = false,
+					Ok(Control::Stop) => break,
+					Err(_) => {}
+				}
+				if let Ok(mut s) = shared_task.lock() {
+					s.paused = paused;
+				}
+				if paused {
+					tokio::time::sleep(Duration::from_millis(200)).await;
+					continue;
+				}
+				let state = worker.work().await;
+				if let Ok(mut s) = shared_task.lock() {
+					s.status = worker.status();
+				}
+				match state {
+					WorkerState::Busy => {}
+					WorkerState::Idle(d) => tokio::time::sleep(d).await,
+					WorkerState::Done => break,
+				}
+			}
+			if let Ok(mut s) = shared_task.lock() {
+				s.running = false;
+			}
+			log::info!("worker '{}' stopped", task_name);
+		});
+		self.workers.lock().unwrap().push(Registered {
+			name,
+			control_tx,
+			shared,
+			handle,
+		});
+	}
+
+	pub async fn pause(&self, name: &str) {
+		if let Some(tx) = self.find(name) {
+			let _ = tx.send(Control::Pause).await;
+		}
+	}
+
+	pub async fn resume(&self, name: &str) {
+		if let Some(tx) = self.find(name) {
+			let _ = tx.send(Control::Resume).await;
+		}
+	}
+
+	pub async fn stop(&self, name: &str) {
+		if let Some(tx) = self.find(name) {
+			let _ = tx.send(Control::Stop).await;
+		}
+	}
+
+	fn find(&self, name: &str) -> Option<mpsc::Sender<Control>> {
+		self.workers
+			.lock()
+			.unwrap()
+			.iter()
+			.find(|w| w.name == name)
+			.map(|w| w.control_tx.clone())
+	}
+
+	/// Snapshot of every registered worker's current state, for an admin
+	/// endpoint to list.
+	pub fn list(&self) -> Vec<WorkerInfo> {
+		self.workers
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|w| {
+				let s = w.shared.lock().unwrap();
+				WorkerInfo {
+					name: w.name.clone(),
+					running: s.running,
+					paused: s.paused,
+					status: s.status.clone(),
+					last_error: s.last_error.clone(),
+				}
+			})
+			.collect()
+	}
+}
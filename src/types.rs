@@ -7,6 +7,7 @@ use serde::Deserialize;
 pub enum SortDir {
 	Asc,
 	Desc,
+	LastAccessedAsc,
 }
 
 #[derive(Deserialize, Debug, Default)]
@@ -16,6 +17,25 @@ pub struct GetSegmentsQuery {
 	pub device_ids: Option<Vec<String>>,
 	pub count: Option<usize>,
 	pub sort: Option<SortDir>,
+	pub level: Option<u32>,
+	/// Keyset cursor: only return rows strictly past this `(first_timestamp,
+	/// id)` pair in iteration order. See `db::encode_segment_cursor`.
+	pub after: Option<String>,
+	/// Keyset cursor: only return rows strictly before this `(first_timestamp,
+	/// id)` pair in iteration order.
+	pub before: Option<String>,
+	/// Walks `(first_timestamp, id)` descending instead of ascending; `after`/
+	/// `before` are interpreted relative to this direction.
+	pub reverse: Option<bool>,
+}
+
+/// One AND-ed term for `DB::find_segments_by_props`: matches a segment that
+/// has a `segment_props` row for `key` equal to any of `values` (an exact
+/// value is just a one-element `values`).
+#[derive(Deserialize, Debug, Clone)]
+pub struct PropFilter {
+	pub key: String,
+	pub values: Vec<String>,
 }
 
 #[derive(Deserialize, Debug, Default)]
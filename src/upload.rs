@@ -1,3 +1,4 @@
+use std::io::Read as _;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -9,7 +10,7 @@ use crate::config::upload_path;
 use crate::context::Context;
 use puppylog::{LogEntry, LogentryDeserializerError};
 
-// Background task that imports *.ready log files into the DB.
+// Background task that imports *.ready (and zstd-compressed *.ready.zst) log files into the DB.
 pub async fn process_log_uploads(ctx: Arc<Context>) {
 	let upload_dir = upload_path();
 	if !upload_dir.exists() {
@@ -51,7 +52,12 @@ pub async fn process_log_uploads(ctx: Arc<Context>) {
 				continue;
 			}
 
-			if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some("ready") {
+			let file_name = match path.file_name().and_then(|s| s.to_str()) {
+				Some(name) => name.to_string(),
+				None => continue,
+			};
+			let compressed = file_name.ends_with(".ready.zst");
+			if !path.is_file() || !(file_name.ends_with(".ready") || compressed) {
 				continue;
 			}
 
@@ -70,7 +76,9 @@ pub async fn process_log_uploads(ctx: Arc<Context>) {
 					buf.clear();
 					log_entries.clear();
 					let mut ptr: usize = 0;
+					let mut file_bytes_read: u64 = 0;
 					let mut chunk = vec![0u8; 8 * 1024 * 1024]; // 8 MiB chunks
+					let mut compressed_buf = Vec::new();
 					loop {
 						match file.read(&mut chunk).await {
 							Ok(0) => {
@@ -78,6 +86,14 @@ pub async fn process_log_uploads(ctx: Arc<Context>) {
 								break;
 							}
 							Ok(n) => {
+								file_bytes_read += n as u64;
+								if compressed {
+									// The stream is zstd-framed, so individual chunks
+									// aren't independently parseable; buffer the raw
+									// bytes and decompress the whole thing once at EOF.
+									compressed_buf.extend_from_slice(&chunk[..n]);
+									continue;
+								}
 								buf.extend_from_slice(&chunk[..n]);
 								// Try to parse as much as we can from current buffer.
 								loop {
@@ -121,6 +137,18 @@ pub async fn process_log_uploads(ctx: Arc<Context>) {
 						}
 					}
 
+					if compressed {
+						match zstd::Decoder::new(std::io::Cursor::new(&compressed_buf[..]))
+							.and_then(|mut decoder| decoder.read_to_end(&mut buf))
+						{
+							Ok(_) => {}
+							Err(e) => {
+								log::error!("failed to decompress {}: {}", path.display(), e);
+								continue;
+							}
+						}
+					}
+
 					// Final parse pass after EOF to drain remaining complete entries.
 					loop {
 						match LogEntry::fast_deserialize(&buf, &mut ptr) {
@@ -136,15 +164,41 @@ pub async fn process_log_uploads(ctx: Arc<Context>) {
 						}
 					}
 
-					ctx.save_logs(&log_entries).await;
+					if let Err(err) = ctx.save_logs(&log_entries).await {
+						// Leave the .ready file in place; it's retried on the
+						// next scan once the ingest token bucket refills.
+						log::warn!(
+							"throttled ingesting {}: {}, retrying next scan",
+							path.display(),
+							err
+						);
+						continue;
+					}
 					let log_count = log_entries.len();
 					let total_bytes = buf.len();
+					ctx.metrics
+						.logs_ingested
+						.fetch_add(log_count as u64, std::sync::atomic::Ordering::Relaxed);
+					ctx.metrics
+						.bytes_uploaded
+						.fetch_add(file_bytes_read, std::sync::atomic::Ordering::Relaxed);
 
-					if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+					let stem = if compressed {
+						file_name.strip_suffix(".ready.zst")
+					} else {
+						file_name.strip_suffix(".ready")
+					};
+					if let Some(stem) = stem {
 						if let Some((device_id, _rest)) = stem.split_once('-') {
+							let compressed_on_disk = compressed.then_some(file_bytes_read as usize);
 							if let Err(e) = ctx
 								.db
-								.update_device_stats(device_id, total_bytes, log_count)
+								.update_device_stats(
+									device_id,
+									total_bytes,
+									log_count,
+									compressed_on_disk,
+								)
 								.await
 							{
 								log::warn!("update_device_stats failed for {}: {}", device_id, e);
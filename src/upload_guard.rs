@@ -1,27 +1,62 @@
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
-pub struct UploadGuard<'a> {
-	pub counter: &'a std::sync::atomic::AtomicUsize,
+/// Holds a slot granted by `AsyncUploadLimiter::acquire`; releases it on drop.
+pub struct AsyncUploadGuard {
+	_permit: OwnedSemaphorePermit,
 }
 
-impl<'a> UploadGuard<'a> {
-	pub fn new(counter: &'a std::sync::atomic::AtomicUsize, max: usize) -> Result<Self, &'static str> {
-		let prev = counter.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |curr| {
-			if curr >= max {
-				None
-			} else {
-				Some(curr + 1)
-			}
-		});
-		match prev {
-			Ok(_) => Ok(Self { counter }),
-			Err(_) => Err("Too many concurrent uploads")
+/// Async admission control for uploads with a bounded, fair (FIFO) wait
+/// queue instead of an immediate hard failure once the concurrency cap is
+/// hit. Built on `tokio::sync::Semaphore`, whose waiters are already served
+/// in arrival order.
+pub struct AsyncUploadLimiter {
+	semaphore: Arc<Semaphore>,
+	max_in_flight: usize,
+	queued: AtomicUsize,
+	max_queue_depth: Option<usize>,
+}
+
+impl AsyncUploadLimiter {
+	pub fn new(max_in_flight: usize, max_queue_depth: Option<usize>) -> Self {
+		Self {
+			semaphore: Arc::new(Semaphore::new(max_in_flight)),
+			max_in_flight,
+			queued: AtomicUsize::new(0),
+			max_queue_depth,
 		}
 	}
-}
 
-impl Drop for UploadGuard<'_> {
-	fn drop(&mut self) {
-		self.counter.fetch_sub(1, Ordering::SeqCst);
+	/// Currently executing uploads holding a slot.
+	pub fn in_flight(&self) -> usize {
+		self.max_in_flight - self.semaphore.available_permits()
+	}
+
+	/// Callers currently waiting in the admission queue.
+	pub fn queued(&self) -> usize {
+		self.queued.load(Ordering::SeqCst)
+	}
+
+	/// Waits up to `wait` for a free slot, enqueueing fairly if the cap is
+	/// already reached. Fails fast with an error if the queue is already at
+	/// `max_queue_depth`, or after `wait` elapses without being admitted.
+	pub async fn acquire(&self, wait: Duration) -> Result<AsyncUploadGuard, &'static str> {
+		if self.semaphore.available_permits() == 0 {
+			if let Some(max_queue_depth) = self.max_queue_depth {
+				if self.queued.load(Ordering::SeqCst) >= max_queue_depth {
+					return Err("Upload admission queue is full");
+				}
+			}
+		}
+		self.queued.fetch_add(1, Ordering::SeqCst);
+		let result = tokio::time::timeout(wait, self.semaphore.clone().acquire_owned()).await;
+		self.queued.fetch_sub(1, Ordering::SeqCst);
+		match result {
+			Ok(Ok(permit)) => Ok(AsyncUploadGuard { _permit: permit }),
+			Ok(Err(_)) => Err("Upload limiter is shut down"),
+			Err(_) => Err("Timed out waiting for a free upload slot"),
+		}
 	}
 }
\ No newline at end of file
@@ -1,18 +1,109 @@
-use std::fs::OpenOptions;
-use std::io::Read;
-use std::path::PathBuf;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
+use std::time::Duration;
 use puppylog::LogEntry;
 use crate::config::log_path;
 
-fn wal_path() -> PathBuf {
-	log_path().join("wal.log")
+/// Controls when `Wal::write` calls are durably fsynced to disk.
+#[derive(Debug, Clone, Copy)]
+pub enum DurabilityPolicy {
+	/// Never fsync explicitly; rely on the OS to flush buffered writes.
+	None,
+	/// Coalesce all writes queued within `max_interval` (or once `max_batch`
+	/// entries have accumulated) into a single buffered write + fsync.
+	Batched { max_interval: Duration, max_batch: usize },
+	/// fsync after every single write. Highest durability, lowest throughput.
+	EachWrite,
+}
+
+impl Default for DurabilityPolicy {
+	fn default() -> Self {
+		DurabilityPolicy::Batched {
+			max_interval: Duration::from_millis(200),
+			max_batch: 256,
+		}
+	}
+}
+
+/// Default size a WAL segment is allowed to grow to before the writer rolls
+/// to a new one. Overridable with `WAL_SEGMENT_BYTES` for tests/tuning.
+const DEFAULT_SEGMENT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+fn wal_dir() -> PathBuf {
+	log_path().join("wal")
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+	dir.join(format!("wal-{:06}.log", id))
+}
+
+/// Segment ids present on disk, in ascending order.
+fn list_segments(dir: &Path) -> Vec<u64> {
+	let mut ids = Vec::new();
+	if let Ok(entries) = fs::read_dir(dir) {
+		for entry in entries.flatten() {
+			let name = entry.file_name();
+			let name = name.to_string_lossy();
+			if let Some(rest) = name.strip_prefix("wal-").and_then(|s| s.strip_suffix(".log")) {
+				if let Ok(id) = rest.parse::<u64>() {
+					ids.push(id);
+				}
+			}
+		}
+	}
+	ids.sort_unstable();
+	ids
+}
+
+fn open_segment(dir: &Path, id: u64) -> std::io::Result<File> {
+	OpenOptions::new()
+		.append(true)
+		.create(true)
+		.open(segment_path(dir, id))
+}
+
+/// Record header: `[u32 length][u32 crc32 of payload]`, followed by `length`
+/// bytes of payload. Framing lets the loader detect a torn write (a record
+/// half-written when the process crashed mid-`serialize`) instead of looping
+/// forever or misparsing the tail as the start of the next record.
+const FRAME_HEADER_LEN: usize = 8;
+
+fn write_framed(file: &mut File, payload: &[u8]) -> std::io::Result<()> {
+	let mut header = [0u8; FRAME_HEADER_LEN];
+	header[0..4].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+	header[4..8].copy_from_slice(&crc32fast::hash(payload).to_le_bytes());
+	file.write_all(&header)?;
+	file.write_all(payload)?;
+	Ok(())
+}
+
+/// Attempts to read one framed record starting at `ptr`. Returns the index
+/// just past the record on success, or `None` if there isn't a full,
+/// CRC-valid record left in `buff` (either a clean EOF or a torn write).
+fn read_framed(buff: &[u8], ptr: usize) -> Option<usize> {
+	if buff.len() - ptr < FRAME_HEADER_LEN {
+		return None;
+	}
+	let len = u32::from_le_bytes(buff[ptr..ptr + 4].try_into().unwrap()) as usize;
+	let crc = u32::from_le_bytes(buff[ptr + 4..ptr + 8].try_into().unwrap());
+	let payload_start = ptr + FRAME_HEADER_LEN;
+	let payload_end = payload_start + len;
+	if buff.len() < payload_end {
+		return None;
+	}
+	if crc32fast::hash(&buff[payload_start..payload_end]) != crc {
+		return None;
+	}
+	Some(payload_end)
 }
 
 enum Cmd {
-	WriteLog(LogEntry),
-	Clear
+	WriteLog(LogEntry, Option<mpsc::Sender<()>>),
+	Clear,
+	Checkpoint(u64),
 }
 
 #[derive(Debug)]
@@ -22,30 +113,134 @@ pub struct Wal {
 
 impl Wal {
 	pub fn new() -> Self {
+		Self::with_policy(DurabilityPolicy::default())
+	}
+
+	pub fn with_policy(policy: DurabilityPolicy) -> Self {
 		let (tx, rx) = mpsc::channel();
+		let segment_max_bytes = std::env::var("WAL_SEGMENT_BYTES")
+			.ok()
+			.and_then(|v| v.parse::<u64>().ok())
+			.unwrap_or(DEFAULT_SEGMENT_MAX_BYTES);
 		thread::spawn(move || {
-			let path = wal_path();
-			log::info!("trying to open wal file: {:?}", path);
-			let mut wal_file = match OpenOptions::new()
-				.append(true)
-				.create(true)
-				.open(path) {
+			let dir = wal_dir();
+			log::info!("trying to open wal dir: {:?}", dir);
+			if let Err(err) = fs::create_dir_all(&dir) {
+				log::error!("Failed to create wal dir: {}", err);
+				return;
+			}
+			// In-memory index of known segments so checkpoint/GC doesn't need
+			// to re-scan the directory on every call. `first_offset` is always
+			// 0 because segments are recycled whole, never truncated mid-file.
+			let mut segments: Vec<(u64, u64)> = list_segments(&dir).into_iter().map(|id| (id, 0)).collect();
+			let mut active_id = segments.last().map(|(id, _)| *id).unwrap_or(1);
+			if segments.is_empty() {
+				segments.push((active_id, 0));
+			}
+			let mut active_file = match open_segment(&dir, active_id) {
 				Ok(file) => file,
 				Err(err) => {
-					log::error!("Failed to open wal file: {}", err);
+					log::error!("Failed to open wal segment {}: {}", active_id, err);
 					return;
 				}
 			};
-			while let Ok(cmd) = rx.recv() {
-				match cmd {
-					Cmd::WriteLog(log) => {
-						log.serialize(&mut wal_file).unwrap();
-					},
-					Cmd::Clear => {
-						log::info!("clearing logs from wal");
-						wal_file.set_len(0).unwrap();
+			let mut active_size = active_file.metadata().map(|m| m.len()).unwrap_or(0);
+			let batch_interval = match policy {
+				DurabilityPolicy::Batched { max_interval, .. } => max_interval,
+				_ => Duration::from_millis(200),
+			};
+			let batch_limit = match policy {
+				DurabilityPolicy::Batched { max_batch, .. } => max_batch,
+				_ => 1,
+			};
+			loop {
+				// Block for the first command, then opportunistically drain
+				// whatever else is already queued into the same batch.
+				let first = match rx.recv_timeout(batch_interval) {
+					Ok(cmd) => cmd,
+					Err(mpsc::RecvTimeoutError::Timeout) => continue,
+					Err(mpsc::RecvTimeoutError::Disconnected) => break,
+				};
+				let mut batch = vec![first];
+				while batch.len() < batch_limit {
+					match rx.try_recv() {
+						Ok(cmd) => batch.push(cmd),
+						Err(_) => break,
 					}
 				}
+				let mut wrote_any = false;
+				let mut acks = Vec::new();
+				for cmd in batch {
+					match cmd {
+						Cmd::WriteLog(log, ack) => {
+							if active_size >= segment_max_bytes {
+								active_id += 1;
+								match open_segment(&dir, active_id) {
+									Ok(file) => {
+										log::info!("rolled wal to new segment {}", active_id);
+										active_file = file;
+										active_size = 0;
+										segments.push((active_id, 0));
+									}
+									Err(err) => {
+										log::error!("Failed to roll wal segment: {}", err);
+										continue;
+									}
+								}
+							}
+							let mut buf = Vec::new();
+							log.serialize(&mut buf).unwrap();
+							if let Err(err) = write_framed(&mut active_file, &buf) {
+								log::error!("Failed to append wal entry: {}", err);
+								continue;
+							}
+							active_size += (FRAME_HEADER_LEN + buf.len()) as u64;
+							wrote_any = true;
+							if matches!(policy, DurabilityPolicy::EachWrite) {
+								if let Err(err) = active_file.sync_data() {
+									log::error!("Failed to fsync wal segment: {}", err);
+								}
+							}
+							if let Some(ack) = ack {
+								acks.push(ack);
+							}
+						},
+						Cmd::Clear => {
+							log::info!("clearing logs from wal");
+							for (id, _) in segments.drain(..).collect::<Vec<_>>() {
+								if id != active_id {
+									let _ = fs::remove_file(segment_path(&dir, id));
+								}
+							}
+							if let Err(err) = active_file.set_len(0) {
+								log::error!("Failed to truncate active wal segment: {}", err);
+							}
+							active_size = 0;
+							segments.push((active_id, 0));
+						},
+						Cmd::Checkpoint(up_to_id) => {
+							segments.retain(|(id, _)| {
+								if *id < up_to_id && *id != active_id {
+									log::info!("checkpoint: removing flushed wal segment {}", id);
+									let _ = fs::remove_file(segment_path(&dir, *id));
+									false
+								} else {
+									true
+								}
+							});
+						}
+					}
+				}
+				// One fsync per batch under the `Batched` policy, regardless
+				// of how many WriteLog commands it contained.
+				if wrote_any && matches!(policy, DurabilityPolicy::Batched { .. }) {
+					if let Err(err) = active_file.sync_data() {
+						log::error!("Failed to fsync wal batch: {}", err);
+					}
+				}
+				for ack in acks {
+					let _ = ack.send(());
+				}
 			}
 		});
 		Self {
@@ -54,41 +249,166 @@ impl Wal {
 	}
 
 	pub fn write(&self, log: LogEntry) {
-		if let Err(err) = self.tx.send(Cmd::WriteLog(log)) {
+		if let Err(err) = self.tx.send(Cmd::WriteLog(log, None)) {
 			log::error!("Failed to write to wal: {}", err);
 		}
 	}
 
+	/// Like `write`, but blocks the calling thread until the batch containing
+	/// this entry has been fsynced (a no-op under `DurabilityPolicy::None`,
+	/// since no batch will ever be synced on its account).
+	pub fn write_durable(&self, log: LogEntry) {
+		let (ack_tx, ack_rx) = mpsc::channel();
+		if let Err(err) = self.tx.send(Cmd::WriteLog(log, Some(ack_tx))) {
+			log::error!("Failed to write to wal: {}", err);
+			return;
+		}
+		let _ = ack_rx.recv();
+	}
+
 	pub fn clear(&self) {
 		if let Err(err) = self.tx.send(Cmd::Clear) {
 			log::error!("Failed to clear wal: {}", err);
 		}
 	}
+
+	/// Delete whole segments older than `up_to_id`, skipping the active one.
+	/// Intended for callers that know entries up to that segment have already
+	/// been durably flushed to the main store.
+	pub fn checkpoint(&self, up_to_id: u64) {
+		if let Err(err) = self.tx.send(Cmd::Checkpoint(up_to_id)) {
+			log::error!("Failed to checkpoint wal: {}", err);
+		}
+	}
+}
+
+/// Total size in bytes of all WAL segments currently on disk.
+pub fn wal_size_bytes() -> u64 {
+	let dir = wal_dir();
+	let mut total = 0;
+	if let Ok(entries) = fs::read_dir(&dir) {
+		for entry in entries.flatten() {
+			if let Ok(meta) = entry.metadata() {
+				total += meta.len();
+			}
+		}
+	}
+	total
+}
+
+/// Re-validates the CRC of every framed record in every WAL segment without
+/// mutating anything, for the background scrub worker. Returns the number of
+/// records whose tail looked like a torn/corrupt write.
+pub fn scrub_wal_segments() -> u64 {
+	let dir = wal_dir();
+	if !dir.exists() {
+		return 0;
+	}
+	let mut corrupt = 0;
+	for id in list_segments(&dir) {
+		let path = segment_path(&dir, id);
+		let mut file = match OpenOptions::new().read(true).open(&path) {
+			Ok(file) => file,
+			Err(_) => continue,
+		};
+		let mut buff = Vec::new();
+		if file.read_to_end(&mut buff).is_err() {
+			continue;
+		}
+		let mut ptr = 0;
+		loop {
+			match read_framed(&buff, ptr) {
+				Some(end) => ptr = end,
+				None => {
+					if ptr < buff.len() {
+						corrupt += 1;
+					}
+					break;
+				}
+			}
+		}
+	}
+	corrupt
+}
+
+/// Reports the on-disk WAL footprint to the `WorkerManager` instead of that
+/// state only being discoverable by reading log lines.
+pub struct WalStatusWorker {
+	status: String,
+}
+
+impl WalStatusWorker {
+	pub fn new() -> Self {
+		Self { status: String::new() }
+	}
+}
+
+#[async_trait::async_trait]
+impl crate::supervisor::Worker for WalStatusWorker {
+	fn name(&self) -> &str {
+		"wal"
+	}
+
+	async fn work(&mut self) -> crate::supervisor::WorkerState {
+		let bytes = wal_size_bytes();
+		self.status = format!("{} bytes buffered on disk", bytes);
+		crate::supervisor::WorkerState::Idle(std::time::Duration::from_secs(10))
+	}
+
+	fn status(&self) -> String {
+		self.status.clone()
+	}
 }
 
 pub fn load_logs_from_wal() -> Vec<LogEntry> {
 	let timer = std::time::Instant::now();
-	let path = wal_path();
-	if !path.exists() {
+	let dir = wal_dir();
+	if !dir.exists() {
 		return Vec::new();
 	}
 	let mut logs = Vec::new();
-	let mut file = OpenOptions::new().read(true).open(path).unwrap();
-	let mut buff = Vec::new();
-	file.read_to_end(&mut buff).unwrap();
-	let mut ptr = 0;
-	loop {
-		match LogEntry::fast_deserialize(&buff, &mut ptr) {
-			Ok(log) => logs.push(log),
-			Err(puppylog::LogentryDeserializerError::NotEnoughData) => {
-				break;
-			},
-			Err(e) => {
-				log::error!("Error deserializing log entry: {:?}", e);
+	for id in list_segments(&dir) {
+		let path = segment_path(&dir, id);
+		let mut file = match OpenOptions::new().read(true).open(&path) {
+			Ok(file) => file,
+			Err(err) => {
+				log::error!("failed to open wal segment {:?}: {}", path, err);
 				continue;
 			}
 		};
+		let mut buff = Vec::new();
+		if let Err(err) = file.read_to_end(&mut buff) {
+			log::error!("failed to read wal segment {:?}: {}", path, err);
+			continue;
+		}
+		let mut ptr = 0;
+		loop {
+			let Some(payload_end) = read_framed(&buff, ptr) else {
+				if ptr < buff.len() {
+					log::warn!(
+						"torn write at byte {} in wal segment {}, truncating and stopping replay",
+						ptr,
+						id
+					);
+					if let Ok(file) = OpenOptions::new().write(true).open(&path) {
+						if let Err(err) = file.set_len(ptr as u64) {
+							log::error!("failed to truncate torn wal segment {:?}: {}", path, err);
+						}
+					}
+				}
+				break;
+			};
+			let payload = &buff[ptr + FRAME_HEADER_LEN..payload_end];
+			let mut payload_ptr = 0;
+			match LogEntry::fast_deserialize(payload, &mut payload_ptr) {
+				Ok(log) => logs.push(log),
+				Err(e) => {
+					log::error!("Error deserializing log entry in segment {}: {:?}", id, e);
+				}
+			};
+			ptr = payload_end;
+		}
 	}
 	log::info!("Loaded {} logs from wal in {:?}", logs.len(), timer.elapsed());
 	logs
-}
\ No newline at end of file
+}
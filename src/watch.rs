@@ -0,0 +1,345 @@
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio::fs::{create_dir_all, metadata, read_dir, remove_file, File};
+use tokio::io::AsyncReadExt;
+use tokio::time::sleep;
+
+use crate::context::Context;
+use puppylog::{LogEntry, LogLevel, LogentryDeserializerError, Prop, PropValue};
+
+/// How a watched directory's files are framed on disk.
+#[derive(Debug, Clone)]
+pub enum SourceFormat {
+	/// puppylog's own binary framing, same as `upload::process_log_uploads`.
+	Binary,
+	/// One JSON object per line.
+	NdJson,
+	/// Free-form text, one log entry per line. `pattern` optionally pulls a
+	/// timestamp/level/message out of named capture groups; without it the
+	/// whole line becomes the message.
+	PlainText { pattern: Option<Regex> },
+}
+
+/// How to derive a file's device id, since arbitrary agents don't
+/// necessarily follow puppylog's `{device_id}-{rest}` upload convention.
+#[derive(Debug, Clone)]
+pub enum DeviceIdRule {
+	/// Text before the first `-` in the file stem, matching
+	/// `upload::process_log_uploads`.
+	FilenamePrefix,
+	/// Every file dropped in this watch belongs to the same device.
+	Fixed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchSpec {
+	pub dir: PathBuf,
+	pub format: SourceFormat,
+	pub device_rule: DeviceIdRule,
+}
+
+/// Parses one `dir|format|device` entry from `UPLOAD_WATCHES`. `format` is
+/// `binary`, `ndjson`, `text`, or `text:<regex>`; `device` is `prefix` or
+/// `fixed:<id>`. Returns `None` (after logging) on a malformed entry so one
+/// bad spec doesn't take the rest of the env var down with it.
+fn parse_spec(raw: &str) -> Option<WatchSpec> {
+	let mut parts = raw.splitn(3, '|');
+	let dir = parts.next()?.trim();
+	let format = parts.next()?.trim();
+	let device = parts.next()?.trim();
+	if dir.is_empty() {
+		log::error!("watch: empty directory in UPLOAD_WATCHES entry {:?}", raw);
+		return None;
+	}
+
+	let format = if format == "binary" {
+		SourceFormat::Binary
+	} else if format == "ndjson" {
+		SourceFormat::NdJson
+	} else if format == "text" {
+		SourceFormat::PlainText { pattern: None }
+	} else if let Some(pattern) = format.strip_prefix("text:") {
+		match Regex::new(pattern) {
+			Ok(re) => SourceFormat::PlainText { pattern: Some(re) },
+			Err(err) => {
+				log::error!("watch: invalid regex {:?} in UPLOAD_WATCHES: {}", pattern, err);
+				return None;
+			}
+		}
+	} else {
+		log::error!("watch: unknown source format {:?} in UPLOAD_WATCHES", format);
+		return None;
+	};
+
+	let device_rule = if device == "prefix" {
+		DeviceIdRule::FilenamePrefix
+	} else if let Some(id) = device.strip_prefix("fixed:") {
+		DeviceIdRule::Fixed(id.to_string())
+	} else {
+		log::error!("watch: unknown device-id rule {:?} in UPLOAD_WATCHES", device);
+		return None;
+	};
+
+	Some(WatchSpec {
+		dir: PathBuf::from(dir),
+		format,
+		device_rule,
+	})
+}
+
+/// Reads `UPLOAD_WATCHES`, a `;`-separated list of `dir|format|device`
+/// entries, into the watches to spawn alongside the default binary upload
+/// directory. Empty or unset means no extra watches.
+pub fn watch_specs_from_env() -> Vec<WatchSpec> {
+	std::env::var("UPLOAD_WATCHES")
+		.ok()
+		.map(|raw| {
+			raw.split(';')
+				.map(str::trim)
+				.filter(|s| !s.is_empty())
+				.filter_map(parse_spec)
+				.collect()
+		})
+		.unwrap_or_default()
+}
+
+fn decode_binary(buf: &[u8]) -> Vec<LogEntry> {
+	let mut entries = Vec::new();
+	let mut ptr: usize = 0;
+	loop {
+		match LogEntry::fast_deserialize(buf, &mut ptr) {
+			Ok(entry) => entries.push(entry),
+			Err(LogentryDeserializerError::NotEnoughData) => break,
+			Err(err) => {
+				log::error!("watch: error deserializing binary log entry: {:?}", err);
+				ptr = ptr.saturating_add(1);
+			}
+		}
+	}
+	entries
+}
+
+#[derive(Deserialize)]
+struct NdJsonLine {
+	#[serde(default)]
+	timestamp: Option<DateTime<Utc>>,
+	#[serde(default)]
+	level: Option<String>,
+	#[serde(default)]
+	msg: Option<String>,
+	#[serde(default)]
+	message: Option<String>,
+	#[serde(flatten)]
+	extra: Map<String, Value>,
+}
+
+fn json_number_to_prop_value(n: &serde_json::Number) -> PropValue {
+	if let Some(i) = n.as_i64() {
+		PropValue::I64(i)
+	} else if let Some(u) = n.as_u64() {
+		PropValue::U64(u)
+	} else {
+		PropValue::F64(n.as_f64().unwrap_or_default())
+	}
+}
+
+/// Flattens an ndjson line's leftover fields into `LogEntry::props`, since
+/// `LogEntry` has no catch-all field of its own. Nested objects/arrays are
+/// dropped rather than stringified; they don't fit `PropValue`'s scalar set.
+fn json_object_to_props(map: &Map<String, Value>) -> Vec<Prop> {
+	map.iter()
+		.filter_map(|(key, value)| {
+			let value = match value {
+				Value::String(s) => PropValue::Str(s.clone()),
+				Value::Bool(b) => PropValue::Bool(*b),
+				Value::Number(n) => json_number_to_prop_value(n),
+				Value::Null | Value::Array(_) | Value::Object(_) => return None,
+			};
+			Some(Prop {
+				key: key.clone(),
+				value,
+			})
+		})
+		.collect()
+}
+
+fn decode_ndjson(buf: &[u8]) -> Vec<LogEntry> {
+	let text = String::from_utf8_lossy(buf);
+	let mut entries = Vec::new();
+	for line in text.lines() {
+		let line = line.trim();
+		if line.is_empty() {
+			continue;
+		}
+		match serde_json::from_str::<NdJsonLine>(line) {
+			Ok(parsed) => {
+				let mut entry = LogEntry::default();
+				if let Some(timestamp) = parsed.timestamp {
+					entry.timestamp = timestamp;
+				}
+				if let Some(level) = parsed.level.as_deref() {
+					entry.level = LogLevel::from_string(level);
+				}
+				entry.msg = parsed.message.or(parsed.msg).unwrap_or_default();
+				entry.props = json_object_to_props(&parsed.extra);
+				entries.push(entry);
+			}
+			Err(err) => log::warn!("watch: skipping malformed ndjson line: {}", err),
+		}
+	}
+	entries
+}
+
+fn decode_plain_text(buf: &[u8], pattern: Option<&Regex>) -> Vec<LogEntry> {
+	let text = String::from_utf8_lossy(buf);
+	text.lines()
+		.filter(|line| !line.trim().is_empty())
+		.map(|line| {
+			let mut entry = LogEntry::default();
+			let captures = pattern.and_then(|re| re.captures(line));
+			match captures {
+				Some(caps) => {
+					if let Some(timestamp) = caps
+						.name("timestamp")
+						.and_then(|m| DateTime::parse_from_rfc3339(m.as_str()).ok())
+					{
+						entry.timestamp = timestamp.with_timezone(&Utc);
+					}
+					if let Some(level) = caps.name("level") {
+						entry.level = LogLevel::from_string(level.as_str());
+					}
+					entry.msg = caps
+						.name("message")
+						.map(|m| m.as_str().to_string())
+						.unwrap_or_else(|| line.to_string());
+				}
+				None => entry.msg = line.to_string(),
+			}
+			entry
+		})
+		.collect()
+}
+
+fn decode(format: &SourceFormat, buf: &[u8]) -> Vec<LogEntry> {
+	match format {
+		SourceFormat::Binary => decode_binary(buf),
+		SourceFormat::NdJson => decode_ndjson(buf),
+		SourceFormat::PlainText { pattern } => decode_plain_text(buf, pattern.as_ref()),
+	}
+}
+
+fn device_id_for(spec: &WatchSpec, path: &std::path::Path) -> Option<String> {
+	match &spec.device_rule {
+		DeviceIdRule::Fixed(id) => Some(id.clone()),
+		DeviceIdRule::FilenamePrefix => path
+			.file_stem()
+			.and_then(|s| s.to_str())
+			.and_then(|stem| stem.split_once('-'))
+			.map(|(device_id, _rest)| device_id.to_string()),
+	}
+}
+
+/// Watches one configured directory for arbitrary-format log files and
+/// imports them the same way `upload::process_log_uploads` does for
+/// puppylog's own binary uploads, decoding per `spec.format` first.
+pub async fn run_watch(ctx: Arc<Context>, spec: WatchSpec) {
+	if !spec.dir.exists() {
+		match create_dir_all(&spec.dir).await {
+			Ok(_) => log::info!("created watch directory {:?}", spec.dir),
+			Err(e) => {
+				log::error!("cannot create {}: {}", spec.dir.display(), e);
+				return;
+			}
+		}
+	}
+
+	loop {
+		let mut dir = match read_dir(&spec.dir).await {
+			Ok(d) => d,
+			Err(e) => {
+				log::error!("cannot read {}: {}", spec.dir.display(), e);
+				sleep(Duration::from_secs(5)).await;
+				continue;
+			}
+		};
+
+		while let Ok(Some(entry)) = dir.next_entry().await {
+			let path = entry.path();
+			if !path.is_file() {
+				continue;
+			}
+
+			// Skip files that are too "hot" (recently modified), likely still being written.
+			if let Ok(meta) = metadata(&path).await {
+				if let Ok(modified) = meta.modified() {
+					if modified.elapsed().unwrap_or(Duration::ZERO) < Duration::from_secs(10) {
+						continue;
+					}
+				}
+			}
+
+			let mut buf = Vec::new();
+			match File::open(&path).await {
+				Ok(mut file) => {
+					if let Err(e) = file.read_to_end(&mut buf).await {
+						log::error!("failed to read {}: {}", path.display(), e);
+						continue;
+					}
+				}
+				Err(e) => {
+					log::error!("cannot open {}: {}", path.display(), e);
+					continue;
+				}
+			}
+
+			let log_entries = decode(&spec.format, &buf);
+			if log_entries.is_empty() {
+				let _ = remove_file(&path).await;
+				continue;
+			}
+
+			if let Err(err) = ctx.save_logs(&log_entries).await {
+				// Leave the file in place; it's retried on the next scan
+				// once the ingest token bucket refills.
+				log::warn!(
+					"throttled ingesting {}: {}, retrying next scan",
+					path.display(),
+					err
+				);
+				continue;
+			}
+
+			let log_count = log_entries.len();
+			let total_bytes = buf.len();
+			ctx.metrics
+				.logs_ingested
+				.fetch_add(log_count as u64, Ordering::Relaxed);
+			ctx.metrics
+				.bytes_uploaded
+				.fetch_add(buf.len() as u64, Ordering::Relaxed);
+
+			if let Some(device_id) = device_id_for(&spec, &path) {
+				if let Err(e) = ctx
+					.db
+					.update_device_stats(&device_id, total_bytes, log_count, None)
+					.await
+				{
+					log::warn!("update_device_stats failed for {}: {}", device_id, e);
+				}
+			}
+
+			if let Err(e) = remove_file(&path).await {
+				log::warn!("failed to delete {}: {}", path.display(), e);
+			}
+		}
+
+		sleep(Duration::from_secs(2)).await;
+	}
+}